@@ -0,0 +1,8 @@
+-- Track files whose decoders fail in a way that points at a damaged file,
+-- so the thumbnail worker stops retrying them and users can review them.
+
+ALTER TABLE images ADD COLUMN corrupt_suspected BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE images ADD COLUMN corrupt_detail TEXT;
+ALTER TABLE images ADD COLUMN corrupt_detected_at DATETIME;
+
+CREATE INDEX IF NOT EXISTS idx_images_corrupt ON images(corrupt_suspected) WHERE corrupt_suspected = 1;
@@ -0,0 +1,6 @@
+-- Real format detected from magic bytes when it disagrees with the extension.
+-- NULL means the extension matches the content (or could not be checked).
+
+ALTER TABLE images ADD COLUMN detected_format TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_images_detected_format ON images(detected_format) WHERE detected_format IS NOT NULL;
@@ -0,0 +1,11 @@
+-- Persisted probe results for video and audio files, used by smart folders.
+
+ALTER TABLE images ADD COLUMN duration REAL;
+ALTER TABLE images ADD COLUMN video_codec TEXT;
+ALTER TABLE images ADD COLUMN audio_codec TEXT;
+ALTER TABLE images ADD COLUMN fps REAL;
+ALTER TABLE images ADD COLUMN bitrate INTEGER;
+ALTER TABLE images ADD COLUMN has_audio BOOLEAN;
+ALTER TABLE images ADD COLUMN media_probed_at DATETIME;
+
+CREATE INDEX IF NOT EXISTS idx_images_duration ON images(duration) WHERE duration IS NOT NULL;
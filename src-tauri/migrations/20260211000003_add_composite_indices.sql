@@ -0,0 +1,10 @@
+-- Composite indices for the grid's most common folder + sort combinations
+
+CREATE INDEX IF NOT EXISTS idx_images_folder_rating ON images(folder_id, rating DESC);
+CREATE INDEX IF NOT EXISTS idx_images_folder_created ON images(folder_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_images_rating ON images(rating);
+
+-- Reverse lookup for "images with tag X" subqueries (the primary key is image_id first)
+CREATE INDEX IF NOT EXISTS idx_image_tags_tag ON image_tags(tag_id, image_id);
+
+ANALYZE;
@@ -0,0 +1,11 @@
+-- Keyboard shortcuts (1-9, a-z) that apply a tag during culling sessions.
+-- `profile` lets several people keep their own mapping on a shared library.
+
+CREATE TABLE IF NOT EXISTS tag_shortcuts (
+    profile TEXT NOT NULL DEFAULT 'default',
+    shortcut_key TEXT NOT NULL,
+    tag_id INTEGER NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (profile, shortcut_key),
+    FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+);
@@ -0,0 +1,30 @@
+-- Persistent review queues for long tagging/rating sessions.
+-- Items are snapshotted when the session starts so the order stays stable
+-- while the user tags (which would otherwise remove items from the filter).
+-- Skipped items come back once the rest of the queue is done; `revisited`
+-- records that they did, so items skipped again don't come back forever.
+
+CREATE TABLE IF NOT EXISTS review_sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT,
+    filter TEXT NOT NULL,
+    position INTEGER,
+    total INTEGER NOT NULL DEFAULT 0,
+    status TEXT NOT NULL DEFAULT 'active',
+    revisited BOOLEAN NOT NULL DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS review_session_items (
+    session_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    image_id INTEGER NOT NULL,
+    state TEXT NOT NULL DEFAULT 'pending',
+    reviewed_at DATETIME,
+    PRIMARY KEY (session_id, position),
+    FOREIGN KEY (session_id) REFERENCES review_sessions(id) ON DELETE CASCADE,
+    FOREIGN KEY (image_id) REFERENCES images(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_review_session_items_image ON review_session_items(image_id);
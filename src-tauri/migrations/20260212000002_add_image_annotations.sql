@@ -0,0 +1,22 @@
+-- Timestamped markdown notes attached to an image, optionally pinned to a
+-- region and/or pointing to a related image. The legacy `images.notes`
+-- column stays as the single free-form description.
+
+CREATE TABLE IF NOT EXISTS image_annotations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    image_id INTEGER NOT NULL,
+    body TEXT NOT NULL DEFAULT '',
+    -- Region in normalized coordinates (0..1 of the image size)
+    region_x REAL,
+    region_y REAL,
+    region_w REAL,
+    region_h REAL,
+    related_image_id INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (image_id) REFERENCES images(id) ON DELETE CASCADE,
+    FOREIGN KEY (related_image_id) REFERENCES images(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_image_annotations_image ON image_annotations(image_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_image_annotations_related ON image_annotations(related_image_id);
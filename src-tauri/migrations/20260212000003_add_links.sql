@@ -0,0 +1,17 @@
+-- External references (Figma files, tickets, client folders...) attached to
+-- either an image or a folder. Folder links apply to everything beneath it.
+
+CREATE TABLE IF NOT EXISTS links (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    image_id INTEGER,
+    folder_id INTEGER,
+    label TEXT NOT NULL DEFAULT '',
+    url TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (image_id) REFERENCES images(id) ON DELETE CASCADE,
+    FOREIGN KEY (folder_id) REFERENCES folders(id) ON DELETE CASCADE,
+    CHECK ((image_id IS NULL) != (folder_id IS NULL))
+);
+
+CREATE INDEX IF NOT EXISTS idx_links_image ON links(image_id);
+CREATE INDEX IF NOT EXISTS idx_links_folder ON links(folder_id);
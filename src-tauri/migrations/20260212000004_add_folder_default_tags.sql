@@ -0,0 +1,10 @@
+-- Tags automatically applied to new files indexed under a folder
+-- (the folder itself or any of its subfolders).
+
+CREATE TABLE IF NOT EXISTS folder_default_tags (
+    folder_id INTEGER NOT NULL,
+    tag_id INTEGER NOT NULL,
+    PRIMARY KEY (folder_id, tag_id),
+    FOREIGN KEY (folder_id) REFERENCES folders(id) ON DELETE CASCADE,
+    FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+);
@@ -0,0 +1,12 @@
+-- Last playback position of audio/video files, so long media resumes where it stopped.
+-- Positions are saved every few seconds while media plays, so they get a table
+-- of their own: on `images`, each save would fire the full-text trigger and
+-- rewrite the search index entry of the file.
+
+CREATE TABLE IF NOT EXISTS playback_positions (
+    image_id INTEGER PRIMARY KEY,
+    position REAL NOT NULL,
+    completed BOOLEAN NOT NULL DEFAULT 0,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (image_id) REFERENCES images(id) ON DELETE CASCADE
+);
@@ -0,0 +1,18 @@
+-- Chapter markers read from video/audio containers by the media info worker,
+-- and whether the file carries an embedded cover picture.
+
+CREATE TABLE IF NOT EXISTS video_chapters (
+    image_id INTEGER NOT NULL,
+    chapter_index INTEGER NOT NULL,
+    start_secs REAL NOT NULL,
+    end_secs REAL NOT NULL,
+    title TEXT,
+    PRIMARY KEY (image_id, chapter_index),
+    FOREIGN KEY (image_id) REFERENCES images(id) ON DELETE CASCADE
+);
+
+ALTER TABLE images ADD COLUMN has_cover_art BOOLEAN NOT NULL DEFAULT 0;
+
+-- Whether the probe looked for chapters and cover art. Media probed before
+-- isn't probed again all at once: a file is when its chapters are asked for.
+ALTER TABLE images ADD COLUMN chapters_probed BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,22 @@
+-- Numbered frame sequences (shot_0001.exr ... shot_0240.exr) detected per
+-- folder. Only the representative (middle) frame is listed in the grid.
+
+CREATE TABLE IF NOT EXISTS image_sequences (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    folder_id INTEGER NOT NULL,
+    -- Filename with one '#' per frame digit, e.g. shot_####.exr
+    pattern TEXT NOT NULL,
+    first_frame INTEGER NOT NULL,
+    last_frame INTEGER NOT NULL,
+    frame_count INTEGER NOT NULL,
+    representative_image_id INTEGER,
+    FOREIGN KEY (folder_id) REFERENCES folders(id) ON DELETE CASCADE,
+    FOREIGN KEY (representative_image_id) REFERENCES images(id) ON DELETE SET NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_image_sequences_pattern ON image_sequences(folder_id, pattern);
+CREATE INDEX IF NOT EXISTS idx_image_sequences_representative ON image_sequences(representative_image_id);
+
+ALTER TABLE images ADD COLUMN sequence_id INTEGER REFERENCES image_sequences(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_images_sequence ON images(sequence_id);
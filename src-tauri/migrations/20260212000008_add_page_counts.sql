@@ -0,0 +1,4 @@
+-- Number of pages (multi-page TIFF) or parts/layers (multi-layer EXR),
+-- NULL for formats that only ever hold one image.
+
+ALTER TABLE images ADD COLUMN page_count INTEGER;
@@ -0,0 +1,11 @@
+-- Outcome of the last scan of each location, for the location health report.
+-- Files that could not be read are skipped by the scan and never reach `images`,
+-- so they can only be counted here.
+
+CREATE TABLE IF NOT EXISTS location_scans (
+    folder_id INTEGER PRIMARY KEY REFERENCES folders(id) ON DELETE CASCADE,
+    started_at DATETIME NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    files_seen INTEGER NOT NULL DEFAULT 0,
+    unreadable_count INTEGER NOT NULL DEFAULT 0
+);
@@ -0,0 +1,34 @@
+-- Materialized auto-collections: a stored filter evaluated on a schedule.
+-- Every evaluation pins the matching ids as a snapshot, so earlier results
+-- ("Best of 2024" as of March) stay available after the library changes.
+
+CREATE TABLE IF NOT EXISTS auto_collections (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    filter TEXT NOT NULL,
+    schedule TEXT NOT NULL DEFAULT 'monthly',
+    last_evaluated_at DATETIME,
+    next_due_at DATETIME,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS auto_collection_snapshots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    collection_id INTEGER NOT NULL,
+    taken_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    image_count INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY (collection_id) REFERENCES auto_collections(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS auto_collection_snapshot_images (
+    snapshot_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    -- No foreign key: the snapshot keeps images removed since it was taken,
+    -- and deleted images are only left out when listed
+    image_id INTEGER NOT NULL,
+    PRIMARY KEY (snapshot_id, position),
+    FOREIGN KEY (snapshot_id) REFERENCES auto_collection_snapshots(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_auto_collection_snapshots_collection ON auto_collection_snapshots(collection_id, taken_at);
+CREATE INDEX IF NOT EXISTS idx_auto_collection_snapshot_images_image ON auto_collection_snapshot_images(image_id);
@@ -0,0 +1,25 @@
+-- Ledger of multi-file operations (bulk renames...). Every step is written
+-- before any file is touched, so an operation interrupted by a crash can be
+-- resumed or rolled back on the next start.
+
+CREATE TABLE IF NOT EXISTS operations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL,
+    -- in_progress, interrupted, completed or rolled_back
+    status TEXT NOT NULL DEFAULT 'in_progress',
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    finished_at DATETIME
+);
+
+CREATE TABLE IF NOT EXISTS operation_steps (
+    operation_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    -- No foreign key: the image row may be gone when the operation is recovered
+    image_id INTEGER NOT NULL,
+    source TEXT NOT NULL,
+    target TEXT NOT NULL,
+    PRIMARY KEY (operation_id, position),
+    FOREIGN KEY (operation_id) REFERENCES operations(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_operations_status ON operations(status);
@@ -0,0 +1,18 @@
+-- Metadata brought in by importers (Apple Photos...), keyed by the path the
+-- file will have in the library. Rows are applied and removed as soon as the
+-- indexer has created the matching images.
+
+CREATE TABLE IF NOT EXISTS pending_import_metadata (
+    path TEXT PRIMARY KEY,
+    -- Importer that produced the row
+    source TEXT NOT NULL,
+    -- Raises the rating, never lowers it
+    rating INTEGER,
+    -- Only fills empty notes
+    notes TEXT,
+    -- Replaces the file's creation time
+    taken_at DATETIME,
+    -- JSON array of [group, name] pairs; new tags are created under the group tag
+    tags TEXT NOT NULL DEFAULT '[]',
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,8 @@
+-- Capture location of images, in decimal degrees (WGS84)
+ALTER TABLE images ADD COLUMN latitude REAL;
+ALTER TABLE images ADD COLUMN longitude REAL;
+
+-- Locations brought in by importers (Google Takeout sidecars...); only fill
+-- images without one
+ALTER TABLE pending_import_metadata ADD COLUMN latitude REAL;
+ALTER TABLE pending_import_metadata ADD COLUMN longitude REAL;
@@ -0,0 +1,17 @@
+-- Stock provider details found in a file's download name or embedded XMP.
+-- Images without a row are not known to come from a stock site.
+
+CREATE TABLE IF NOT EXISTS stock_assets (
+    image_id INTEGER PRIMARY KEY,
+    provider TEXT NOT NULL,
+    asset_id TEXT,
+    -- xmpRights:UsageTerms
+    usage_terms TEXT,
+    -- xmpRights:WebStatement or plus:LicensorURL
+    license_url TEXT,
+    credit TEXT,
+    copyright TEXT,
+    FOREIGN KEY (image_id) REFERENCES images(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_stock_assets_provider ON stock_assets(provider);
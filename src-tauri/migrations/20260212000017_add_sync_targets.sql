@@ -0,0 +1,35 @@
+-- Folders kept in sync with a stored filter (client delivery drives...).
+-- Every file copied by a sync is recorded, so later syncs only ever update
+-- or remove files they placed themselves.
+
+CREATE TABLE IF NOT EXISTS sync_targets (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    destination TEXT NOT NULL,
+    filter TEXT NOT NULL,
+    -- keep, delete or archive: what happens to files leaving the filter
+    delete_policy TEXT NOT NULL DEFAULT 'keep',
+    -- Recreate the folders below the location instead of a flat copy
+    keep_folders BOOLEAN NOT NULL DEFAULT 0,
+    -- Synced in the background whenever membership changes
+    auto_sync BOOLEAN NOT NULL DEFAULT 0,
+    last_synced_at DATETIME,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS sync_files (
+    target_id INTEGER NOT NULL,
+    -- Relative to the destination, with `/` separators
+    relative_path TEXT NOT NULL,
+    -- No foreign key: the file stays tracked after its image is removed
+    image_id INTEGER NOT NULL,
+    -- Source size and modification time when copied
+    source_size INTEGER NOT NULL,
+    source_modified INTEGER NOT NULL,
+    -- Destination modification time after the copy, to spot edits there
+    destination_modified INTEGER NOT NULL,
+    PRIMARY KEY (target_id, relative_path),
+    FOREIGN KEY (target_id) REFERENCES sync_targets(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_sync_files_image ON sync_files(target_id, image_id);
@@ -0,0 +1,19 @@
+-- Locations backed by an S3 bucket or a WebDAV share. Their files are
+-- indexed under a local mirror folder (the root folder below), filled on
+-- demand and trimmed to `cache_max_mb`.
+
+CREATE TABLE IF NOT EXISTS remote_locations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    -- s3 or webdav
+    kind TEXT NOT NULL,
+    -- Connection settings as JSON, without credentials
+    config TEXT NOT NULL,
+    -- OS keychain entry holding the password or secret key, if any
+    secret_ref TEXT,
+    folder_id INTEGER,
+    cache_max_mb INTEGER NOT NULL DEFAULT 2048,
+    last_listed_at DATETIME,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (folder_id) REFERENCES folders(id) ON DELETE SET NULL
+);
@@ -0,0 +1,13 @@
+-- Content keys of library files, used to match them with another instance
+-- that has the same files under different paths (see `crate::peer`).
+
+CREATE TABLE IF NOT EXISTS content_keys (
+    image_id INTEGER PRIMARY KEY,
+    key TEXT NOT NULL,
+    -- Size and modification time the key was computed for
+    size INTEGER NOT NULL,
+    modified_at DATETIME NOT NULL,
+    FOREIGN KEY (image_id) REFERENCES images(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_content_keys_key ON content_keys(key);
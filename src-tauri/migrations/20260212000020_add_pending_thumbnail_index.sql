@@ -0,0 +1,6 @@
+-- Images still waiting for a thumbnail, by folder. The thumbnail queue ranks
+-- them per folder on every batch (see `get_images_needing_thumbnails`).
+
+CREATE INDEX IF NOT EXISTS idx_images_pending_thumbnails
+    ON images(folder_id, id)
+    WHERE thumbnail_path IS NULL;
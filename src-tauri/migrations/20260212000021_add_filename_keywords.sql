@@ -0,0 +1,39 @@
+-- Words of each filename, split on delimiters and camelCase
+-- (`ClientX_Hero_Banner_v03` -> `client x hero banner v03`), so a search for
+-- "hero banner" finds it. Computed in Rust (see `db::keywords`); NULL until
+-- then, and again whenever the file is renamed.
+
+ALTER TABLE images ADD COLUMN filename_keywords TEXT;
+
+-- Word-based companion of `images_fts`, whose trigrams only match substrings
+CREATE VIRTUAL TABLE IF NOT EXISTS images_keywords_fts USING fts5(
+    filename_keywords,
+    content='images',
+    content_rowid='id',
+    tokenize='unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER IF NOT EXISTS images_keywords_ai AFTER INSERT ON images
+WHEN new.filename_keywords IS NOT NULL BEGIN
+  INSERT INTO images_keywords_fts(rowid, filename_keywords) VALUES (new.id, new.filename_keywords);
+END;
+
+CREATE TRIGGER IF NOT EXISTS images_keywords_ad AFTER DELETE ON images
+WHEN old.filename_keywords IS NOT NULL BEGIN
+  INSERT INTO images_keywords_fts(images_keywords_fts, rowid, filename_keywords) VALUES ('delete', old.id, old.filename_keywords);
+END;
+
+CREATE TRIGGER IF NOT EXISTS images_keywords_au AFTER UPDATE OF filename_keywords ON images BEGIN
+  INSERT INTO images_keywords_fts(images_keywords_fts, rowid, filename_keywords)
+    SELECT 'delete', old.id, old.filename_keywords WHERE old.filename_keywords IS NOT NULL;
+  INSERT INTO images_keywords_fts(rowid, filename_keywords)
+    SELECT new.id, new.filename_keywords WHERE new.filename_keywords IS NOT NULL;
+END;
+
+-- A renamed file is queued for new keywords
+CREATE TRIGGER IF NOT EXISTS images_keywords_rename AFTER UPDATE OF filename ON images
+WHEN old.filename IS NOT new.filename AND new.filename_keywords IS NOT NULL BEGIN
+  UPDATE images SET filename_keywords = NULL WHERE id = new.id;
+END;
+
+CREATE INDEX IF NOT EXISTS idx_images_keywords_pending ON images(id) WHERE filename_keywords IS NULL;
@@ -0,0 +1,20 @@
+-- Versioned exports (hero_v01.psd, hero_v02.psd, hero_final.psd) detected
+-- per folder, with the latest version of each chain.
+
+CREATE TABLE IF NOT EXISTS version_chains (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    folder_id INTEGER NOT NULL,
+    -- Filename without its version suffix, e.g. hero.psd
+    base_name TEXT NOT NULL,
+    version_count INTEGER NOT NULL,
+    latest_image_id INTEGER,
+    FOREIGN KEY (folder_id) REFERENCES folders(id) ON DELETE CASCADE,
+    FOREIGN KEY (latest_image_id) REFERENCES images(id) ON DELETE SET NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_version_chains_base ON version_chains(folder_id, base_name);
+CREATE INDEX IF NOT EXISTS idx_version_chains_latest ON version_chains(latest_image_id);
+
+ALTER TABLE images ADD COLUMN version_chain_id INTEGER REFERENCES version_chains(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_images_version_chain ON images(version_chain_id);
@@ -0,0 +1,10 @@
+-- Frame count of videos and animated images, and whether an animation
+-- loops forever. Animated GIF and WebP files also get a duration and frame
+-- rate, so they sort and filter like short videos.
+
+ALTER TABLE images ADD COLUMN frame_count INTEGER;
+ALTER TABLE images ADD COLUMN loops BOOLEAN;
+
+-- Videos already probed: estimate from duration and frame rate
+UPDATE images SET frame_count = CAST(ROUND(duration * fps) AS INTEGER)
+WHERE video_codec IS NOT NULL AND duration > 0 AND fps > 0;
@@ -0,0 +1,11 @@
+-- Parsed EXIF of each file, as shown by the inspector, so it isn't read from
+-- disk every time. `file_mtime` (milliseconds) and `file_size` are those of
+-- the file when it was parsed; a row that no longer matches is stale.
+
+CREATE TABLE IF NOT EXISTS exif_cache (
+    image_id INTEGER PRIMARY KEY,
+    file_mtime INTEGER NOT NULL,
+    file_size INTEGER NOT NULL,
+    data TEXT NOT NULL,
+    FOREIGN KEY (image_id) REFERENCES images(id) ON DELETE CASCADE
+);
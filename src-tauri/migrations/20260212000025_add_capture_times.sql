@@ -0,0 +1,14 @@
+-- Capture time read from the file's EXIF (see `media::capture_time`):
+-- `captured_at` in UTC, `capture_offset` the offset the camera recorded, in
+-- minutes east of UTC (NULL when it recorded none and the library's default
+-- zone was assumed) and `capture_time_raw` the wall-clock time as written,
+-- to convert again when the default zone changes. `capture_checked_at` is
+-- set once the file has been read, with or without a capture time.
+
+ALTER TABLE images ADD COLUMN captured_at DATETIME;
+ALTER TABLE images ADD COLUMN capture_offset INTEGER;
+ALTER TABLE images ADD COLUMN capture_time_raw TEXT;
+ALTER TABLE images ADD COLUMN capture_checked_at DATETIME;
+
+CREATE INDEX IF NOT EXISTS idx_images_captured_at ON images(captured_at);
+CREATE INDEX IF NOT EXISTS idx_images_capture_pending ON images(id) WHERE capture_checked_at IS NULL;
@@ -0,0 +1,20 @@
+-- Timing of each HLS segment transcoded for playback, so a file that
+-- stutters can be explained: `queued_ms` is time spent waiting for a free
+-- FFmpeg slot, `encode_ms` time spent transcoding `media_secs` of media.
+-- Failed segments are recorded too, with the error.
+
+CREATE TABLE IF NOT EXISTS transcode_stats (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    image_id INTEGER NOT NULL,
+    segment_index INTEGER NOT NULL,
+    quality TEXT NOT NULL,
+    encoder TEXT NOT NULL,
+    queued_ms INTEGER NOT NULL,
+    encode_ms INTEGER NOT NULL,
+    media_secs REAL NOT NULL,
+    error TEXT,
+    recorded_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (image_id) REFERENCES images(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_transcode_stats_image ON transcode_stats(image_id, id);
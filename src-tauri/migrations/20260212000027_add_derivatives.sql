@@ -0,0 +1,17 @@
+-- Files derived from an asset: editing proxies, exports, transcodes and
+-- viewer conversions. `kind` is one of `proxy`, `export`, `transcode` or
+-- `preview`; `label` says which variant (a quality, a codec). Rows go with
+-- their source, the files stay on disk.
+
+CREATE TABLE IF NOT EXISTS derivatives (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    image_id INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    path TEXT NOT NULL UNIQUE,
+    label TEXT,
+    size INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (image_id) REFERENCES images(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_derivatives_image ON derivatives(image_id);
@@ -0,0 +1,11 @@
+-- Hash of the whole file (SHA-256), to find identical files across the
+-- library's locations (see `indexer::content_hashes`). Only files whose size
+-- another file shares are read, so `content_hash` stays NULL for the others.
+-- `content_hashed_at` is set once a file has been read, with a NULL hash when
+-- it couldn't be, and cleared by the indexer when the file changes.
+
+ALTER TABLE images ADD COLUMN content_hash TEXT;
+ALTER TABLE images ADD COLUMN content_hashed_at DATETIME;
+
+CREATE INDEX IF NOT EXISTS idx_images_content_hash ON images(content_hash) WHERE content_hash IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_images_hash_pending ON images(size) WHERE content_hashed_at IS NULL;
@@ -0,0 +1,5 @@
+-- Quick thumbnail whose quality upgrade failed (see `thumbnails::upgrade`),
+-- so it isn't tried again until the file gets another thumbnail. Upgraded
+-- thumbnails are told apart by their `-hq` name.
+
+ALTER TABLE images ADD COLUMN thumbnail_upgrade_failed TEXT;
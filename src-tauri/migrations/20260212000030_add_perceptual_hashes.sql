@@ -0,0 +1,9 @@
+-- Perceptual hash of the picture (see `thumbnails::perceptual`): a 64-bit
+-- difference hash computed from the thumbnail, compared by Hamming distance
+-- to find resized or re-encoded copies and burst shots. `perceptual_hash_of`
+-- is the thumbnail it was computed from, so a new thumbnail (the file
+-- changed, or was upgraded) is hashed again; the hash is NULL when the
+-- thumbnail couldn't be read.
+
+ALTER TABLE images ADD COLUMN perceptual_hash INTEGER;
+ALTER TABLE images ADD COLUMN perceptual_hash_of TEXT;
@@ -0,0 +1,26 @@
+-- XMP sidecars (see `media::sidecars`). `sidecar_checked_at` is NULL until
+-- the file's sidecar is looked for, and again once the watcher sees a
+-- sidecar change; `sidecar_modified_at` is the modification time (Unix
+-- milliseconds) of the sidecar as last read or written, so unchanged sidecars
+-- aren't parsed again. `sidecar_pending` marks images whose rating or tags
+-- changed since, to be written out when sidecar writing is on.
+
+ALTER TABLE images ADD COLUMN sidecar_checked_at DATETIME;
+ALTER TABLE images ADD COLUMN sidecar_modified_at INTEGER;
+ALTER TABLE images ADD COLUMN sidecar_pending INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_images_sidecar_unchecked ON images(id) WHERE sidecar_checked_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_images_sidecar_pending ON images(id) WHERE sidecar_pending = 1;
+
+CREATE TRIGGER IF NOT EXISTS images_sidecar_rating AFTER UPDATE OF rating ON images
+WHEN old.rating IS NOT new.rating BEGIN
+  UPDATE images SET sidecar_pending = 1 WHERE id = new.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS image_tags_sidecar_ai AFTER INSERT ON image_tags BEGIN
+  UPDATE images SET sidecar_pending = 1 WHERE id = new.image_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS image_tags_sidecar_ad AFTER DELETE ON image_tags BEGIN
+  UPDATE images SET sidecar_pending = 1 WHERE id = old.image_id;
+END;
@@ -0,0 +1,8 @@
+-- Notes are written back as the caption of the file's XMP (see
+-- `media::sidecars` and `media::embedded_xmp`), so changing them flags the
+-- image like ratings and tags do.
+
+CREATE TRIGGER IF NOT EXISTS images_sidecar_notes AFTER UPDATE OF notes ON images
+WHEN old.notes IS NOT new.notes BEGIN
+  UPDATE images SET sidecar_pending = 1 WHERE id = new.id;
+END;
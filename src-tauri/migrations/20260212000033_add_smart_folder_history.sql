@@ -0,0 +1,20 @@
+-- Periodic statistics of each smart folder (see `library::smart_folder_history`),
+-- to chart how a backlog such as "untagged images" shrinks over time. One row
+-- per smart folder and snapshot: how many images it matched, how many of them
+-- had no tag, and how many had each rating (`rated_0` counts unrated images).
+
+CREATE TABLE IF NOT EXISTS smart_folder_snapshots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    smart_folder_id INTEGER NOT NULL REFERENCES smart_folders(id) ON DELETE CASCADE,
+    taken_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    image_count INTEGER NOT NULL,
+    untagged_count INTEGER NOT NULL,
+    rated_0 INTEGER NOT NULL,
+    rated_1 INTEGER NOT NULL,
+    rated_2 INTEGER NOT NULL,
+    rated_3 INTEGER NOT NULL,
+    rated_4 INTEGER NOT NULL,
+    rated_5 INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_smart_folder_snapshots_folder ON smart_folder_snapshots(smart_folder_id, taken_at);
@@ -0,0 +1,13 @@
+-- GPS positions are now read from EXIF by the capture-time pass (see
+-- `indexer::capture_times`). Camera files already read, and without a
+-- position imported from elsewhere, are queued to be read again for theirs.
+-- Only JPEG and camera RAW files that had EXIF are concerned: files without
+-- EXIF have no position either, and TIFFs are scans and exports.
+
+UPDATE images SET capture_checked_at = NULL
+WHERE latitude IS NULL
+  AND capture_checked_at IS NOT NULL
+  AND capture_time_raw IS NOT NULL
+  AND format IN ('jpg', 'jpeg', 'jpe', 'dng', 'nef', 'cr2', 'arw', 'orf', 'pef', 'srw', 'rw2');
+
+CREATE INDEX IF NOT EXISTS idx_images_location ON images(latitude, longitude);
@@ -0,0 +1,17 @@
+-- Text search ignores accents: "cafe" finds `Café.jpg`, "zurich" finds
+-- notes about Zürich. The trigram index is rebuilt with diacritics removed;
+-- its triggers keep referring to it by name. Sorting by filename moved to
+-- the NATURAL collation, registered on each connection (see
+-- `db::collation`), so nothing is stored for it.
+
+DROP TABLE IF EXISTS images_fts;
+
+CREATE VIRTUAL TABLE images_fts USING fts5(
+    filename,
+    notes,
+    content='images',
+    content_rowid='id',
+    tokenize='trigram remove_diacritics 1'
+);
+
+INSERT INTO images_fts(images_fts) VALUES ('rebuild');
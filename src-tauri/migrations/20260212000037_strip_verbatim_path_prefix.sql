@@ -0,0 +1,46 @@
+-- Windows paths are stored without the `\\?\` prefix `canonicalize` adds
+-- (see `paths`), so a location added by hand and the same location scanned
+-- are one folder. Paths scans stored with the prefix lose it; a row whose
+-- plain path is already taken keeps its prefix and still opens.
+
+UPDATE OR IGNORE folders SET path = substr(path, 5)
+WHERE path LIKE '\\?\_:\%';
+
+UPDATE OR IGNORE folders SET path = '\\' || substr(path, 9)
+WHERE path LIKE '\\?\UNC\%';
+
+UPDATE OR IGNORE images SET path = substr(path, 5)
+WHERE path LIKE '\\?\_:\%';
+
+UPDATE OR IGNORE images SET path = '\\' || substr(path, 9)
+WHERE path LIKE '\\?\UNC\%';
+
+UPDATE OR IGNORE derivatives SET path = substr(path, 5)
+WHERE path LIKE '\\?\_:\%';
+
+UPDATE OR IGNORE derivatives SET path = '\\' || substr(path, 9)
+WHERE path LIKE '\\?\UNC\%';
+
+UPDATE OR IGNORE pending_import_metadata SET path = substr(path, 5)
+WHERE path LIKE '\\?\_:\%';
+
+UPDATE OR IGNORE pending_import_metadata SET path = '\\' || substr(path, 9)
+WHERE path LIKE '\\?\UNC\%';
+
+UPDATE operation_steps SET source = substr(source, 5)
+WHERE source LIKE '\\?\_:\%';
+
+UPDATE operation_steps SET source = '\\' || substr(source, 9)
+WHERE source LIKE '\\?\UNC\%';
+
+UPDATE operation_steps SET target = substr(target, 5)
+WHERE target LIKE '\\?\_:\%';
+
+UPDATE operation_steps SET target = '\\' || substr(target, 9)
+WHERE target LIKE '\\?\UNC\%';
+
+UPDATE sync_targets SET destination = substr(destination, 5)
+WHERE destination LIKE '\\?\_:\%';
+
+UPDATE sync_targets SET destination = '\\' || substr(destination, 9)
+WHERE destination LIKE '\\?\UNC\%';
@@ -0,0 +1,4 @@
+-- Whether the filesystem of a folder tells `a.jpg` from `A.jpg`, probed
+-- when its location is scanned and copied to the folders found in it.
+-- NULL until then: the platform's default filesystem decides.
+ALTER TABLE folders ADD COLUMN case_sensitive BOOLEAN;
@@ -0,0 +1,24 @@
+-- Images taken out of the library, kept with what the user gave them (rating,
+-- notes, tags, collections) so they can be brought back. `reason` is
+-- 'missing' when the file went away from the library (watcher, scan, remote
+-- refresh, removed location) and 'deleted' when it was deleted from the app,
+-- which moves the file to `stored_path` in the library's trash folder.
+-- Entries older than the `trash_retention_days` setting are purged.
+
+CREATE TABLE IF NOT EXISTS trash (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- Id the image had; a restored image gets a new one
+    image_id INTEGER NOT NULL,
+    path TEXT NOT NULL,
+    filename TEXT NOT NULL,
+    size INTEGER NOT NULL DEFAULT 0,
+    format TEXT NOT NULL DEFAULT '',
+    reason TEXT NOT NULL,
+    stored_path TEXT,
+    -- JSON: rating, notes, created_at, tag ids and [collection id, position] pairs
+    snapshot TEXT NOT NULL DEFAULT '{}',
+    trashed_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_trash_path ON trash(path);
+CREATE INDEX IF NOT EXISTS idx_trash_trashed_at ON trash(trashed_at);
@@ -0,0 +1,23 @@
+-- Thumbnail files an image stopped using: its file changed (new content key,
+-- new name), it got another thumbnail, or it left the library. The thumbnail
+-- worker deletes those no other image uses (see `take_stale_thumbnails`).
+-- Shared type icons (`extensions/...`) are never recorded.
+
+CREATE TABLE IF NOT EXISTS stale_thumbnails (
+    filename TEXT PRIMARY KEY
+);
+
+CREATE INDEX IF NOT EXISTS idx_images_thumbnail_path ON images(thumbnail_path);
+
+CREATE TRIGGER IF NOT EXISTS images_thumbnail_replaced AFTER UPDATE OF thumbnail_path ON images
+WHEN OLD.thumbnail_path IS NOT NULL AND OLD.thumbnail_path NOT LIKE '%/%'
+     AND OLD.thumbnail_path IS NOT NEW.thumbnail_path
+BEGIN
+    INSERT OR IGNORE INTO stale_thumbnails (filename) VALUES (OLD.thumbnail_path);
+END;
+
+CREATE TRIGGER IF NOT EXISTS images_thumbnail_removed AFTER DELETE ON images
+WHEN OLD.thumbnail_path IS NOT NULL AND OLD.thumbnail_path NOT LIKE '%/%'
+BEGIN
+    INSERT OR IGNORE INTO stale_thumbnails (filename) VALUES (OLD.thumbnail_path);
+END;
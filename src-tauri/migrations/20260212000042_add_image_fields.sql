@@ -0,0 +1,13 @@
+-- Custom fields: named text values the user keeps on images, such as a
+-- client, a shoot or a licence, filled from an edited catalog CSV (see
+-- `library::catalog_import`) and exported as `field:<name>` columns.
+
+CREATE TABLE IF NOT EXISTS image_fields (
+    image_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (image_id, name),
+    FOREIGN KEY (image_id) REFERENCES images(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_image_fields_name ON image_fields(name);
@@ -0,0 +1,16 @@
+-- The NATURAL collation can't use an index, so sorting a large grid by
+-- filename sorted every match on each page. Images keep the collation's
+-- sort key of their filename instead (see `db::collation::sort_key`),
+-- computed by the app when a file is saved or renamed. Other renames clear
+-- it and a background task fills in missing keys.
+
+ALTER TABLE images ADD COLUMN filename_sort_key BLOB;
+
+CREATE INDEX IF NOT EXISTS idx_images_filename_sort_key ON images(filename_sort_key);
+CREATE INDEX IF NOT EXISTS idx_images_missing_sort_key ON images(id) WHERE filename_sort_key IS NULL;
+
+CREATE TRIGGER IF NOT EXISTS images_filename_renamed AFTER UPDATE OF filename ON images
+WHEN OLD.filename IS NOT NEW.filename
+BEGIN
+    UPDATE images SET filename_sort_key = NULL WHERE id = NEW.id;
+END;
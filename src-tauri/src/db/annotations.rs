@@ -0,0 +1,124 @@
+//! Image annotations: timestamped notes, region markers and image cross-links.
+
+use crate::db::images::IMAGE_COLUMNS;
+use crate::db::models::{AnnotationRegion, ImageAnnotation, ImageMetadata};
+use super::Db;
+use std::collections::HashMap;
+
+impl Db {
+    /// Creates an annotation and returns its id.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if either image doesn't exist or the database fails.
+    pub async fn add_image_annotation(
+        &self,
+        image_id: i64,
+        body: &str,
+        region: Option<AnnotationRegion>,
+        related_image_id: Option<i64>,
+    ) -> Result<i64, sqlx::Error> {
+        let res = sqlx::query(
+            "INSERT INTO image_annotations (image_id, body, region_x, region_y, region_w, region_h, related_image_id)
+             VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(image_id)
+        .bind(body)
+        .bind(region.map(|r| r.x))
+        .bind(region.map(|r| r.y))
+        .bind(region.map(|r| r.w))
+        .bind(region.map(|r| r.h))
+        .bind(related_image_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    /// Replaces the content of an annotation.
+    ///
+    /// Returns `false` if no annotation has this id.
+    pub async fn update_image_annotation(
+        &self,
+        id: i64,
+        body: &str,
+        region: Option<AnnotationRegion>,
+        related_image_id: Option<i64>,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query(
+            "UPDATE image_annotations SET
+                body = ?, region_x = ?, region_y = ?, region_w = ?, region_h = ?, related_image_id = ?,
+                updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?"
+        )
+        .bind(body)
+        .bind(region.map(|r| r.x))
+        .bind(region.map(|r| r.y))
+        .bind(region.map(|r| r.w))
+        .bind(region.map(|r| r.h))
+        .bind(related_image_id)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Deletes an annotation.
+    pub async fn delete_image_annotation(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM image_annotations WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Retrieves the annotations of an image, oldest first.
+    pub async fn get_image_annotations(&self, image_id: i64) -> Result<Vec<ImageAnnotation>, sqlx::Error> {
+        sqlx::query_as::<_, ImageAnnotation>(
+            "SELECT id, image_id, body, region_x, region_y, region_w, region_h, related_image_id, created_at, updated_at
+             FROM image_annotations WHERE image_id = ? ORDER BY created_at, id"
+        )
+        .bind(image_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Retrieves the images linked to or from an image through annotations.
+    pub async fn get_related_images(&self, image_id: i64) -> Result<Vec<ImageMetadata>, sqlx::Error> {
+        sqlx::query_as::<_, ImageMetadata>(&format!(
+            "SELECT {}
+             FROM images i LEFT JOIN playback_positions pp ON pp.image_id = i.id
+             WHERE i.id IN (
+                SELECT related_image_id FROM image_annotations WHERE image_id = ? AND related_image_id IS NOT NULL
+                UNION
+                SELECT image_id FROM image_annotations WHERE related_image_id = ?
+             )
+             ORDER BY i.filename COLLATE NATURAL",
+            IMAGE_COLUMNS
+        ))
+        .bind(image_id)
+        .bind(image_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Retrieves the annotations of several images, keyed by image id, oldest first.
+    pub async fn get_annotations_by_image(
+        &self,
+        image_ids: &[i64],
+    ) -> Result<HashMap<i64, Vec<ImageAnnotation>>, sqlx::Error> {
+        let ids_json = serde_json::to_string(image_ids).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+        let rows = sqlx::query_as::<_, ImageAnnotation>(
+            "SELECT id, image_id, body, region_x, region_y, region_w, region_h, related_image_id, created_at, updated_at
+             FROM image_annotations WHERE image_id IN (SELECT value FROM json_each(?)) ORDER BY created_at, id"
+        )
+        .bind(ids_json)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut annotations: HashMap<i64, Vec<ImageAnnotation>> = HashMap::new();
+        for annotation in rows {
+            annotations.entry(annotation.image_id).or_default().push(annotation);
+        }
+        Ok(annotations)
+    }
+}
@@ -0,0 +1,256 @@
+//! Materialized auto-collections.
+//!
+//! Unlike smart folders, which are evaluated live, an auto-collection stores
+//! a filter that is evaluated on a schedule. Each evaluation pins the matching
+//! ids, in grid order, as a snapshot, and every snapshot is kept as history.
+//! Snapshots keep the ids of images deleted since; those are only left out
+//! when a snapshot is listed.
+
+use crate::db::images::IMAGE_COLUMNS;
+use crate::db::models::{AutoCollection, AutoCollectionSnapshot, ImageMetadata};
+use crate::db::search::ImageFilter;
+use super::Db;
+
+/// Collection only evaluated on request.
+pub const SCHEDULE_MANUAL: &str = "manual";
+
+const COLLECTION_COLUMNS: &str = "SELECT c.id, c.name, c.filter, c.schedule, c.last_evaluated_at, c.next_due_at, c.created_at,
+        (SELECT s.image_count FROM auto_collection_snapshots s WHERE s.collection_id = c.id ORDER BY s.taken_at DESC, s.id DESC LIMIT 1) AS latest_count
+     FROM auto_collections c";
+
+/// SQLite date modifier advancing a schedule by one period.
+///
+/// Returns `None` for `manual`, and `Err` for unknown schedules.
+pub fn schedule_modifier(schedule: &str) -> Result<Option<&'static str>, String> {
+    match schedule {
+        "daily" => Ok(Some("+1 day")),
+        "weekly" => Ok(Some("+7 days")),
+        "monthly" => Ok(Some("+1 month")),
+        SCHEDULE_MANUAL => Ok(None),
+        other => Err(format!("Unknown schedule '{}'", other)),
+    }
+}
+
+impl Db {
+    /// Creates an auto-collection. Scheduled ones are due immediately, so the
+    /// first snapshot is taken on the next scheduler pass.
+    pub async fn create_auto_collection(&self, name: &str, filter_json: &str, schedule: &str) -> Result<i64, sqlx::Error> {
+        let due = if schedule == SCHEDULE_MANUAL { None } else { Some("now") };
+        let result = sqlx::query(
+            "INSERT INTO auto_collections (name, filter, schedule, next_due_at) VALUES (?, ?, ?, datetime(?))"
+        )
+        .bind(name)
+        .bind(filter_json)
+        .bind(schedule)
+        .bind(due)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Updates the name, filter and schedule. Existing snapshots are kept.
+    ///
+    /// The next due date is recomputed from the last evaluation, so switching
+    /// from monthly to weekly can make the collection due right away.
+    pub async fn update_auto_collection(
+        &self,
+        id: i64,
+        name: &str,
+        filter_json: &str,
+        schedule: &str,
+        modifier: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE auto_collections
+             SET name = ?, filter = ?, schedule = ?,
+                 next_due_at = CASE WHEN ? IS NULL THEN NULL
+                                    ELSE COALESCE(datetime(last_evaluated_at, ?), datetime('now')) END
+             WHERE id = ?"
+        )
+        .bind(name)
+        .bind(filter_json)
+        .bind(schedule)
+        .bind(modifier)
+        .bind(modifier)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes a collection and all of its snapshots. Images are not affected.
+    pub async fn delete_auto_collection(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM auto_collections WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_auto_collection(&self, id: i64) -> Result<Option<AutoCollection>, sqlx::Error> {
+        sqlx::query_as::<_, AutoCollection>(&format!("{} WHERE c.id = ?", COLLECTION_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn get_auto_collections(&self) -> Result<Vec<AutoCollection>, sqlx::Error> {
+        sqlx::query_as::<_, AutoCollection>(&format!("{} ORDER BY c.name COLLATE NOCASE", COLLECTION_COLUMNS))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Ids of the scheduled collections whose next snapshot is due.
+    pub async fn get_due_auto_collections(&self) -> Result<Vec<i64>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT id FROM auto_collections
+             WHERE next_due_at IS NOT NULL AND next_due_at <= datetime('now')
+             ORDER BY next_due_at"
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Evaluates the collection's filter and pins the result as a new snapshot.
+    ///
+    /// `modifier` (see [`schedule_modifier`]) sets the next due date from now.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the transaction fails; nothing is written in that case.
+    pub async fn take_auto_collection_snapshot(
+        &self,
+        collection_id: i64,
+        filter: &ImageFilter,
+        modifier: Option<&str>,
+    ) -> Result<i64, sqlx::Error> {
+        let group = filter.parsed_group();
+        let mut query_builder = filter.build_id_query(group.as_ref(), "");
+        let image_ids: Vec<i64> = query_builder.build_query_scalar::<i64>().fetch_all(&self.pool).await?;
+
+        let mut tx = self.pool.begin().await?;
+
+        let snapshot_id = sqlx::query(
+            "INSERT INTO auto_collection_snapshots (collection_id, image_count) VALUES (?, ?)"
+        )
+        .bind(collection_id)
+        .bind(image_ids.len() as i64)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+        // Stay well below SQLite's bound parameter limit
+        for (chunk_index, chunk) in image_ids.chunks(300).enumerate() {
+            let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
+                "INSERT INTO auto_collection_snapshot_images (snapshot_id, position, image_id) "
+            );
+            query_builder.push_values(chunk.iter().enumerate(), |mut row, (i, image_id)| {
+                row.push_bind(snapshot_id)
+                    .push_bind((chunk_index * 300 + i) as i64)
+                    .push_bind(*image_id);
+            });
+            query_builder.build().execute(&mut *tx).await?;
+        }
+
+        sqlx::query(
+            "UPDATE auto_collections
+             SET last_evaluated_at = datetime('now'),
+                 next_due_at = CASE WHEN ? IS NULL THEN NULL ELSE datetime('now', ?) END
+             WHERE id = ?"
+        )
+        .bind(modifier)
+        .bind(modifier)
+        .bind(collection_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(snapshot_id)
+    }
+
+    /// Lists the snapshots of a collection, newest first.
+    pub async fn get_auto_collection_snapshots(&self, collection_id: i64) -> Result<Vec<AutoCollectionSnapshot>, sqlx::Error> {
+        sqlx::query_as::<_, AutoCollectionSnapshot>(
+            "SELECT id, collection_id, taken_at, image_count FROM auto_collection_snapshots
+             WHERE collection_id = ? ORDER BY taken_at DESC, id DESC"
+        )
+        .bind(collection_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Retrieves a page of a snapshot's images in their pinned order.
+    ///
+    /// Images deleted since the snapshot was taken are left out.
+    pub async fn get_auto_collection_snapshot_images(
+        &self,
+        snapshot_id: i64,
+        limit: i32,
+        offset: i32,
+    ) -> Result<Vec<ImageMetadata>, sqlx::Error> {
+        sqlx::query_as::<_, ImageMetadata>(&format!(
+            "SELECT {}
+             FROM auto_collection_snapshot_images si JOIN images i ON i.id = si.image_id LEFT JOIN playback_positions pp ON pp.image_id = i.id
+             WHERE si.snapshot_id = ?
+             ORDER BY si.position LIMIT ? OFFSET ?",
+            IMAGE_COLUMNS
+        ))
+        .bind(snapshot_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Ids of a collection snapshot's images in their pinned order.
+    pub async fn get_auto_collection_snapshot_ids(&self, snapshot_id: i64) -> Result<Vec<i64>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT image_id FROM auto_collection_snapshot_images WHERE snapshot_id = ? ORDER BY position"
+        )
+        .bind(snapshot_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Deletes a single snapshot from a collection's history.
+    pub async fn delete_auto_collection_snapshot(&self, snapshot_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM auto_collection_snapshots WHERE id = ?")
+            .bind(snapshot_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule_modifier() {
+        assert_eq!(schedule_modifier("daily"), Ok(Some("+1 day")));
+        assert_eq!(schedule_modifier("monthly"), Ok(Some("+1 month")));
+        assert_eq!(schedule_modifier("manual"), Ok(None));
+        assert!(schedule_modifier("hourly").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_keep_deleted_images() {
+        let library = crate::testkit::TestLibrary::open("auto-collection-snapshots").await;
+        let db = &library.db;
+        library.seed_images(&["1.jpg", "2.jpg"]).await;
+        let collection_id = db.create_auto_collection("All", "{}", "manual").await.unwrap();
+        let snapshot_id = db
+            .take_auto_collection_snapshot(collection_id, &ImageFilter::default(), None)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM images WHERE id = 1").execute(&db.pool).await.unwrap();
+
+        let mut ids = db.get_auto_collection_snapshot_ids(snapshot_id).await.unwrap();
+        ids.sort();
+        assert_eq!(ids, vec![1, 2], "the snapshot is frozen");
+        let listed = db.get_auto_collection_snapshot_images(snapshot_id, 10, 0).await.unwrap();
+        assert_eq!(listed.iter().map(|image| image.id).collect::<Vec<_>>(), vec![2]);
+    }
+}
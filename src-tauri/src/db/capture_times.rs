@@ -0,0 +1,113 @@
+//! Stored capture times (see `media::capture_time`).
+
+use chrono::{DateTime, Utc};
+
+use crate::media::capture_time::CaptureTime;
+use super::Db;
+
+/// What was read from a file: the capture time as written and its UTC
+/// conversion, or nothing when the file has none, and its GPS position.
+pub struct CaptureRecord {
+    pub image_id: i64,
+    pub raw: Option<String>,
+    pub time: Option<CaptureTime>,
+    pub location: Option<(f64, f64)>,
+}
+
+impl Db {
+    /// Files of the given formats whose capture time hasn't been read yet.
+    pub async fn get_images_needing_capture_time(
+        &self,
+        formats: &[&str],
+        limit: i64,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        if formats.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> =
+            sqlx::QueryBuilder::new("SELECT id, path FROM images WHERE capture_checked_at IS NULL AND format IN (");
+        let mut separated = query_builder.separated(", ");
+        for format in formats {
+            separated.push_bind(*format);
+        }
+        separated.push_unseparated(") LIMIT ");
+        query_builder.push_bind(limit);
+
+        query_builder.build_query_as().fetch_all(&self.pool).await
+    }
+
+    /// Stores what was read from each file and marks it as read. A position
+    /// already known, imported from Takeout or Apple Photos, is kept.
+    pub async fn set_capture_times(&self, records: &[CaptureRecord]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for record in records {
+            if let Some((latitude, longitude)) = record.location {
+                sqlx::query("UPDATE images SET latitude = ?, longitude = ? WHERE id = ? AND latitude IS NULL")
+                    .bind(latitude)
+                    .bind(longitude)
+                    .bind(record.image_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            sqlx::query(
+                "UPDATE images SET
+                    captured_at = ?, capture_offset = ?, capture_time_raw = ?, capture_checked_at = CURRENT_TIMESTAMP
+                 WHERE id = ?"
+            )
+            .bind(record.time.map(|t| t.utc))
+            .bind(record.time.and_then(|t| t.offset_minutes))
+            .bind(&record.raw)
+            .bind(record.image_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Capture times recorded without an offset, which depend on the
+    /// default zone: id, wall-clock time as written and stored UTC time.
+    pub async fn get_zoneless_capture_times(&self) -> Result<Vec<(i64, String, Option<DateTime<Utc>>)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, capture_time_raw, captured_at FROM images
+             WHERE capture_time_raw IS NOT NULL AND capture_offset IS NULL"
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Replaces the UTC capture time of the given images.
+    pub async fn update_captured_at(&self, times: &[(i64, Option<DateTime<Utc>>)]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (id, captured_at) in times {
+            sqlx::query("UPDATE images SET captured_at = ? WHERE id = ?")
+                .bind(captured_at)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Queues files of the given formats that were read without finding a
+    /// capture time to be read again. Returns how many were queued.
+    pub async fn requeue_missing_capture_times(&self, formats: &[&str]) -> Result<u64, sqlx::Error> {
+        if formats.is_empty() {
+            return Ok(0);
+        }
+
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
+            "UPDATE images SET capture_checked_at = NULL
+             WHERE capture_checked_at IS NOT NULL AND capture_time_raw IS NULL AND format IN ("
+        );
+        let mut separated = query_builder.separated(", ");
+        for format in formats {
+            separated.push_bind(*format);
+        }
+        separated.push_unseparated(")");
+
+        Ok(query_builder.build().execute(&self.pool).await?.rows_affected())
+    }
+}
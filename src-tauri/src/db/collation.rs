@@ -0,0 +1,299 @@
+//! Locale-aware ordering of filenames.
+//!
+//! SQLite's `NOCASE` only folds ASCII, so `Äpfel.jpg` sorted after
+//! `Zebra.jpg` and `img10.jpg` before `img2.jpg`. Every connection registers
+//! the `NATURAL` collation instead, which compares names the way people read
+//! them: letters without their accents first ("Ä" with "A"), runs of digits
+//! by value, then accents and case to break ties.
+//!
+//! The `collation_locale` setting tailors the alphabet for languages that
+//! keep some accented letters apart: in Swedish "Å Ä Ö" come after "Z", in
+//! Spanish "Ñ" after "N". Other languages sort like the root locale.
+//!
+//! A collation can't use an index, so the grid sorts images by
+//! `filename_sort_key` instead: the [`sort_key`] of the filename, bytes that
+//! compare like the collation does. Files get their key when the app saves
+//! or renames them (see [`refresh_sort_key`]); any other change of filename
+//! clears it, and `indexer::sort_keys` fills in missing keys in the
+//! background. Changing the locale recomputes them all.
+
+use std::cmp::Ordering;
+use std::sync::RwLock;
+
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::SqliteConnection;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use super::Db;
+
+/// Settings key holding the library's locale, as a language tag (`sv-SE`).
+pub const SETTING_KEY: &str = "collation_locale";
+
+/// Name of the collation in SQL: `ORDER BY filename COLLATE NATURAL`.
+pub const NAME: &str = "NATURAL";
+
+/// Sort keys computed per transaction.
+const SORT_KEY_BATCH: i64 = 500;
+
+/// Alphabet filenames are sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    /// Accented letters sort with their base letter.
+    #[default]
+    Root,
+    /// Swedish and Finnish: `z < å < ä < ö`.
+    Swedish,
+    /// Danish and Norwegian: `z < æ < ø < å`.
+    Danish,
+    /// Spanish: `n < ñ < o`.
+    Spanish,
+}
+
+impl Locale {
+    /// Locale of a language tag; languages without tailoring sort like the
+    /// root locale.
+    pub fn parse(tag: &str) -> Self {
+        let language = tag.trim().split(['-', '_']).next().unwrap_or_default().to_lowercase();
+        match language.as_str() {
+            "sv" | "fi" => Locale::Swedish,
+            "da" | "nb" | "nn" | "no" => Locale::Danish,
+            "es" => Locale::Spanish,
+            _ => Locale::Root,
+        }
+    }
+
+    /// Weight of a letter the locale sorts apart from its base letter.
+    fn tailored(self, c: char) -> Option<u32> {
+        let after_z = |rank: u32| weight('z') + rank;
+        match (self, c) {
+            (Locale::Swedish, 'å') => Some(after_z(1)),
+            (Locale::Swedish, 'ä' | 'æ') => Some(after_z(2)),
+            (Locale::Swedish, 'ö' | 'ø') => Some(after_z(3)),
+            (Locale::Danish, 'æ' | 'ä') => Some(after_z(1)),
+            (Locale::Danish, 'ø' | 'ö') => Some(after_z(2)),
+            (Locale::Danish, 'å') => Some(after_z(3)),
+            (Locale::Spanish, 'ñ') => Some(weight('n') + 1),
+            _ => None,
+        }
+    }
+}
+
+static LOCALE: RwLock<Locale> = RwLock::new(Locale::Root);
+
+/// Makes `locale` the one the collation and new sort keys follow.
+pub fn set(locale: Locale) {
+    *LOCALE.write().unwrap_or_else(|e| e.into_inner()) = locale;
+}
+
+/// Locale the collation follows now.
+pub fn current() -> Locale {
+    *LOCALE.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Applies the `collation_locale` setting (see [`Locale::parse`]).
+pub fn set_from_setting(value: &str) {
+    set(Locale::parse(value));
+}
+
+/// Registers the `NATURAL` collation on the connections opened with
+/// `options`. It follows the current locale, so a change applies to the
+/// next query.
+pub fn register(options: SqliteConnectOptions) -> SqliteConnectOptions {
+    options.collation(NAME, |a: &str, b: &str| compare(a, b, current()))
+}
+
+/// Leaves room between letters for tailored ones.
+fn weight(c: char) -> u32 {
+    (c as u32) << 2
+}
+
+/// What a name is compared by first: numbers by value (digits sort before
+/// letters, as in ASCII), letters by base letter.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Unit {
+    Number { len: usize, digits: String },
+    Letter(u32),
+}
+
+/// Letters without case or accents, ligatures and letters with a stroke
+/// spelled out.
+fn push_letters(c: char, locale: Locale, units: &mut Vec<Unit>) {
+    for lower in c.to_lowercase() {
+        if let Some(weight) = locale.tailored(lower) {
+            units.push(Unit::Letter(weight));
+            continue;
+        }
+        let spelled = match lower {
+            'ß' => "ss",
+            'æ' => "ae",
+            'œ' => "oe",
+            'ø' => "o",
+            'ł' => "l",
+            'đ' | 'ð' => "d",
+            'þ' => "th",
+            'ı' => "i",
+            _ => "",
+        };
+        if !spelled.is_empty() {
+            units.extend(spelled.chars().map(|c| Unit::Letter(weight(c))));
+            continue;
+        }
+        for base in std::iter::once(lower).nfd().filter(|c| !is_combining_mark(*c)) {
+            units.push(Unit::Letter(weight(base)));
+        }
+    }
+}
+
+fn units(text: &str, locale: Locale) -> Vec<Unit> {
+    let mut units = Vec::with_capacity(text.len());
+    // Composed first: macOS hands out decomposed names, `a` + ring for `å`
+    let mut chars = text.nfc().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            let mut digits = String::from(c);
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            let digits = digits.trim_start_matches('0').to_string();
+            units.push(Unit::Number { len: digits.len(), digits });
+        } else {
+            push_letters(c, locale, &mut units);
+        }
+    }
+    units
+}
+
+/// Orders two names under `locale`. Names equal but for accents, case or
+/// leading zeros are ordered by those, so the order stays total.
+pub fn compare(a: &str, b: &str, locale: Locale) -> Ordering {
+    units(a, locale)
+        .cmp(&units(b, locale))
+        .then_with(|| a.to_lowercase().cmp(&b.to_lowercase()))
+        .then_with(|| a.cmp(b))
+}
+
+/// Bytes that order like [`compare`] under `locale` when compared as a
+/// whole, as SQLite compares BLOBs: each unit tagged (numbers before
+/// letters) and fixed-width, a 0 byte to end the units, then the lowercase
+/// name and the name itself for the tie-breaks.
+pub fn sort_key(name: &str, locale: Locale) -> Vec<u8> {
+    let mut key = Vec::with_capacity(name.len() * 6);
+    for unit in units(name, locale) {
+        match unit {
+            Unit::Number { len, digits } => {
+                key.push(1);
+                key.extend_from_slice(&(len as u32).to_be_bytes());
+                key.extend_from_slice(digits.as_bytes());
+            }
+            Unit::Letter(weight) => {
+                key.push(2);
+                key.extend_from_slice(&weight.to_be_bytes());
+            }
+        }
+    }
+    key.push(0);
+    key.extend_from_slice(name.to_lowercase().as_bytes());
+    key.push(0);
+    key.extend_from_slice(name.as_bytes());
+    key
+}
+
+/// Stores the sort key of an image's current filename on `conn`, so files
+/// saved or renamed in a transaction sort in place right away.
+pub(crate) async fn refresh_sort_key(conn: &mut SqliteConnection, image_id: i64, filename: &str) -> Result<(), sqlx::Error> {
+    let key = sort_key(filename, current());
+    sqlx::query("UPDATE images SET filename_sort_key = ? WHERE id = ? AND filename_sort_key IS NOT ?")
+        .bind(&key)
+        .bind(image_id)
+        .bind(&key)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+impl Db {
+    /// Images whose sort key is missing: `(id, filename)`.
+    pub async fn get_images_needing_sort_key(&self, limit: i64) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as("SELECT id, filename FROM images WHERE filename_sort_key IS NULL LIMIT ?")
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Computes and stores the sort keys of the given images in one
+    /// transaction, under the current locale.
+    pub async fn set_filename_sort_keys(&self, images: &[(i64, String)]) -> Result<(), sqlx::Error> {
+        let locale = current();
+        let mut tx = self.pool.begin().await?;
+        for (id, filename) in images {
+            // A rename since the read cleared the key again
+            sqlx::query("UPDATE images SET filename_sort_key = ? WHERE id = ? AND filename = ?")
+                .bind(sort_key(filename, locale))
+                .bind(id)
+                .bind(filename)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Recomputes every `filename_sort_key`, after the locale changed.
+    /// Returns how many were computed.
+    pub async fn refresh_filename_sort_keys(&self) -> Result<u64, sqlx::Error> {
+        sqlx::query("UPDATE images SET filename_sort_key = NULL").execute(&self.pool).await?;
+        let mut refreshed = 0;
+        loop {
+            let images = self.get_images_needing_sort_key(SORT_KEY_BATCH).await?;
+            if images.is_empty() {
+                return Ok(refreshed);
+            }
+            self.set_filename_sort_keys(&images).await?;
+            refreshed += images.len() as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(names: &[&str], locale: Locale) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        names.sort_by(|a, b| compare(a, b, locale));
+        names
+    }
+
+    #[test]
+    fn test_natural_order() {
+        assert_eq!(
+            sorted(&["img10.jpg", "Zebra.jpg", "img2.jpg", "Äpfel.jpg", "apfel.jpg", "Apfel.jpg", "img02.jpg"], Locale::Root),
+            ["Apfel.jpg", "apfel.jpg", "Äpfel.jpg", "img02.jpg", "img2.jpg", "img10.jpg", "Zebra.jpg"]
+        );
+        assert_eq!(sorted(&["Straße", "Strasse", "Strand"], Locale::Root), ["Strand", "Strasse", "Straße"]);
+        assert_eq!(compare("IMG_0009", "IMG_10", Locale::Root), Ordering::Less);
+        assert_eq!(compare("é", "é", Locale::Root), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_tailored_locales() {
+        let names = ["Örebro", "Oslo", "Zürich", "Åre", "Aalborg"];
+        assert_eq!(sorted(&names, Locale::Root), ["Aalborg", "Åre", "Örebro", "Oslo", "Zürich"]);
+        assert_eq!(sorted(&names, Locale::parse("sv-SE")), ["Aalborg", "Oslo", "Zürich", "Åre", "Örebro"]);
+        assert_eq!(sorted(&["ñu", "nube", "oso"], Locale::parse("es")), ["nube", "ñu", "oso"]);
+        assert_eq!(compare("A\u{30a}re", "Zürich", Locale::Swedish), Ordering::Greater);
+        assert_eq!(Locale::parse("nb_NO"), Locale::Danish);
+        assert_eq!(Locale::parse("fr"), Locale::Root);
+    }
+
+    #[test]
+    fn test_sort_key_orders_like_the_collation() {
+        let names = ["img10.jpg", "Zebra.jpg", "img2.jpg", "Äpfel.jpg", "apfel.jpg", "Apfel.jpg", "img02.jpg", "img", "img 2", "Åre", "Oslo", "ñu", "nube", ""];
+        for locale in [Locale::Root, Locale::Swedish, Locale::Spanish] {
+            let mut by_key: Vec<&str> = names.to_vec();
+            by_key.sort_by_key(|name| sort_key(name, locale));
+            assert_eq!(by_key, sorted(&names, locale), "{:?}", locale);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Manual collections: curated, hand-ordered sets of images.
+//!
+//! Unlike smart folders and auto-collections, membership is only ever
+//! changed by the user. The grid pages through a collection in its manual
+//! order with `sort_by: "position"` (see `db::search`).
+
+use crate::db::models::Collection;
+use super::Db;
+
+const COLLECTION_COLUMNS: &str = "SELECT c.id, c.name, c.description, c.created_at, c.updated_at,
+        (SELECT COUNT(*) FROM collection_items ci WHERE ci.collection_id = c.id) AS item_count
+     FROM collections c";
+
+/// Images of a collection in their manual order.
+async fn item_order(tx: &mut sqlx::SqliteConnection, collection_id: i64) -> Result<Vec<i64>, sqlx::Error> {
+    sqlx::query_scalar("SELECT image_id FROM collection_items WHERE collection_id = ? ORDER BY position, image_id")
+        .bind(collection_id)
+        .fetch_all(&mut *tx)
+        .await
+}
+
+/// Numbers the images of a collection from 0 in the order of `order`,
+/// rewriting only the ones that moved, and marks the collection as changed.
+async fn write_order(tx: &mut sqlx::SqliteConnection, collection_id: i64, order: &[i64]) -> Result<(), sqlx::Error> {
+    for (position, image_id) in order.iter().enumerate() {
+        sqlx::query("UPDATE collection_items SET position = ? WHERE collection_id = ? AND image_id = ? AND position != ?")
+            .bind(position as i64)
+            .bind(collection_id)
+            .bind(image_id)
+            .bind(position as i64)
+            .execute(&mut *tx)
+            .await?;
+    }
+    sqlx::query("UPDATE collections SET updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+        .bind(collection_id)
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+/// Appends `image_ids` to a collection, in order, skipping images already
+/// in it or no longer in the library. Returns how many were added.
+async fn append_items(tx: &mut sqlx::SqliteConnection, collection_id: i64, image_ids: &[i64]) -> Result<u64, sqlx::Error> {
+    let mut next: i64 = sqlx::query_scalar("SELECT COALESCE(MAX(position) + 1, 0) FROM collection_items WHERE collection_id = ?")
+        .bind(collection_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let mut added = 0;
+    for image_id in image_ids {
+        let inserted = sqlx::query(
+            "INSERT OR IGNORE INTO collection_items (collection_id, image_id, position)
+             SELECT ?, id, ? FROM images WHERE id = ?"
+        )
+        .bind(collection_id)
+        .bind(next)
+        .bind(image_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        next += inserted as i64;
+        added += inserted;
+    }
+    Ok(added)
+}
+
+/// Takes an image out of every collection it is in, closing the gap it
+/// leaves so positions stay contiguous. Used before the image is deleted.
+pub(crate) async fn leave_collections(conn: &mut sqlx::SqliteConnection, image_id: i64) -> Result<(), sqlx::Error> {
+    let memberships: Vec<(i64, i64)> = sqlx::query_as("SELECT collection_id, position FROM collection_items WHERE image_id = ?")
+        .bind(image_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    for (collection_id, position) in memberships {
+        sqlx::query("DELETE FROM collection_items WHERE collection_id = ? AND image_id = ?")
+            .bind(collection_id)
+            .bind(image_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("UPDATE collection_items SET position = position - 1 WHERE collection_id = ? AND position > ?")
+            .bind(collection_id)
+            .bind(position)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+impl Db {
+    /// Creates a collection holding `image_ids`, in that order.
+    pub async fn create_collection(&self, name: &str, description: Option<&str>, image_ids: &[i64]) -> Result<i64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let collection_id = sqlx::query("INSERT INTO collections (name, description) VALUES (?, ?)")
+            .bind(name)
+            .bind(description)
+            .execute(&mut *tx)
+            .await?
+            .last_insert_rowid();
+        append_items(&mut tx, collection_id, image_ids).await?;
+        tx.commit().await?;
+        Ok(collection_id)
+    }
+
+    /// Retrieves a collection with its image count, if it exists.
+    pub async fn get_collection(&self, id: i64) -> Result<Option<Collection>, sqlx::Error> {
+        sqlx::query_as::<_, Collection>(&format!("{} WHERE c.id = ?", COLLECTION_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Lists all collections by name.
+    pub async fn get_collections(&self) -> Result<Vec<Collection>, sqlx::Error> {
+        sqlx::query_as::<_, Collection>(&format!("{} ORDER BY c.name COLLATE NATURAL, c.id", COLLECTION_COLUMNS))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Renames a collection and changes its description.
+    pub async fn update_collection(&self, id: i64, name: &str, description: Option<&str>) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE collections SET name = ?, description = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(name)
+            .bind(description)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes a collection. Its images are not affected.
+    pub async fn delete_collection(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM collections WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Appends images to the end of a collection. Returns how many were
+    /// added; images already in it keep their place.
+    pub async fn add_to_collection(&self, collection_id: i64, image_ids: &[i64]) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let added = append_items(&mut tx, collection_id, image_ids).await?;
+        if added > 0 {
+            sqlx::query("UPDATE collections SET updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+                .bind(collection_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(added)
+    }
+
+    /// Takes images out of a collection, closing the gaps they leave.
+    /// Returns how many were removed.
+    pub async fn remove_from_collection(&self, collection_id: i64, image_ids: &[i64]) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut removed = 0;
+        for image_id in image_ids {
+            removed += sqlx::query("DELETE FROM collection_items WHERE collection_id = ? AND image_id = ?")
+                .bind(collection_id)
+                .bind(image_id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        }
+        if removed > 0 {
+            let order = item_order(&mut tx, collection_id).await?;
+            write_order(&mut tx, collection_id, &order).await?;
+        }
+        tx.commit().await?;
+        Ok(removed)
+    }
+
+    /// Moves `image_ids`, in that order, right before `before_image_id`, or
+    /// to the end of the collection when it is `None`. Images not in the
+    /// collection are ignored, as is a target that is itself being moved.
+    pub async fn reorder_collection_items(
+        &self,
+        collection_id: i64,
+        image_ids: &[i64],
+        before_image_id: Option<i64>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut order = item_order(&mut tx, collection_id).await?;
+
+        let mut moved = Vec::with_capacity(image_ids.len());
+        for image_id in image_ids {
+            if let Some(index) = order.iter().position(|id| id == image_id) {
+                moved.push(order.remove(index));
+            }
+        }
+        let at = before_image_id
+            .and_then(|before| order.iter().position(|id| *id == before))
+            .unwrap_or(order.len());
+        order.splice(at..at, moved);
+
+        write_order(&mut tx, collection_id, &order).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Collections holding an image, by name.
+    pub async fn get_image_collections(&self, image_id: i64) -> Result<Vec<Collection>, sqlx::Error> {
+        sqlx::query_as::<_, Collection>(&format!(
+            "{} WHERE c.id IN (SELECT collection_id FROM collection_items WHERE image_id = ?) ORDER BY c.name COLLATE NATURAL, c.id",
+            COLLECTION_COLUMNS
+        ))
+        .bind(image_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn order(db: &Db, collection_id: i64) -> Vec<i64> {
+        let mut conn = db.pool.acquire().await.unwrap();
+        item_order(&mut conn, collection_id).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_collection_order() {
+        let library = crate::testkit::TestLibrary::open("collections").await;
+        let db = &library.db;
+        library.seed_images(&["1.jpg", "2.jpg", "3.jpg", "4.jpg", "5.jpg"]).await;
+
+        // Unknown images are skipped, duplicates added once
+        let id = db.create_collection("Portfolio", None, &[3, 1, 99, 3]).await.unwrap();
+        assert_eq!(order(db, id).await, vec![3, 1]);
+        assert_eq!(db.add_to_collection(id, &[1, 5, 2]).await.unwrap(), 2);
+        assert_eq!(order(db, id).await, vec![3, 1, 5, 2]);
+
+        db.reorder_collection_items(id, &[2, 5], Some(3)).await.unwrap();
+        assert_eq!(order(db, id).await, vec![2, 5, 3, 1]);
+        db.reorder_collection_items(id, &[2], None).await.unwrap();
+        assert_eq!(order(db, id).await, vec![5, 3, 1, 2]);
+        // A target that is moved along can't anchor the move: they go last
+        db.reorder_collection_items(id, &[3, 5], Some(5)).await.unwrap();
+        assert_eq!(order(db, id).await, vec![1, 2, 3, 5]);
+
+        assert_eq!(db.remove_from_collection(id, &[2, 4]).await.unwrap(), 1);
+        let positions: Vec<(i64, i64)> =
+            sqlx::query_as("SELECT image_id, position FROM collection_items WHERE collection_id = ? ORDER BY position")
+                .bind(id)
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(positions, vec![(1, 0), (3, 1), (5, 2)]);
+
+        // Deleting an image or the collection removes the memberships
+        sqlx::query("DELETE FROM images WHERE id = 3").execute(&db.pool).await.unwrap();
+        assert_eq!(db.get_collection(id).await.unwrap().unwrap().item_count, 2);
+        assert_eq!(db.get_image_collections(1).await.unwrap().len(), 1);
+        db.delete_collection(id).await.unwrap();
+        assert!(db.get_image_collections(1).await.unwrap().is_empty());
+    }
+}
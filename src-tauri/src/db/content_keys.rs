@@ -0,0 +1,52 @@
+//! Content keys of library files (see `crate::peer`).
+
+use super::Db;
+
+impl Db {
+    /// Images with a thumbnail whose content key is missing or was computed
+    /// for another version of the file: `(id, path, size)`.
+    pub async fn get_images_needing_content_keys(&self, limit: i64) -> Result<Vec<(i64, String, i64)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT i.id, i.path, i.size FROM images i
+             LEFT JOIN content_keys k ON k.image_id = i.id
+             WHERE i.thumbnail_path IS NOT NULL
+               AND (k.image_id IS NULL OR k.size != i.size OR k.modified_at != i.modified_at)
+             LIMIT ?",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Records the key of an image, unless its size changed since `size` was
+    /// read (the key would belong to the old version).
+    pub async fn set_content_key(&self, image_id: i64, key: &str, size: i64) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO content_keys (image_id, key, size, modified_at)
+             SELECT id, ?, size, modified_at FROM images WHERE id = ? AND size = ?",
+        )
+        .bind(key)
+        .bind(image_id)
+        .bind(size)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Path, thumbnail and modification time of the image with the given
+    /// content key, if the key is up to date.
+    pub async fn find_image_by_content_key(
+        &self,
+        key: &str,
+    ) -> Result<Option<(String, Option<String>, chrono::DateTime<chrono::Utc>)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT i.path, i.thumbnail_path, i.modified_at FROM content_keys k
+             JOIN images i ON i.id = k.image_id
+             WHERE k.key = ? AND k.size = i.size AND k.modified_at = i.modified_at
+             LIMIT 1",
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,91 @@
+//! Recorded derivatives of assets (see `library::derivatives`).
+
+use chrono::{DateTime, Utc};
+
+use super::Db;
+
+const DERIVATIVE_COLUMNS: &str = "SELECT id, image_id, kind, path, label, size, created_at FROM derivatives";
+
+/// A file derived from an asset.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct DerivativeRow {
+    pub id: i64,
+    pub image_id: i64,
+    pub kind: String,
+    pub path: String,
+    pub label: Option<String>,
+    pub size: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Db {
+    /// Records `path` as derived from `image_id` and returns its id. A path
+    /// recorded before is updated, since writing it again replaced the file.
+    pub async fn add_derivative(
+        &self,
+        image_id: i64,
+        kind: &str,
+        path: &str,
+        label: Option<&str>,
+        size: Option<i64>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "INSERT INTO derivatives (image_id, kind, path, label, size) VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT(path) DO UPDATE SET
+                image_id = excluded.image_id, kind = excluded.kind, label = excluded.label,
+                size = excluded.size, created_at = CURRENT_TIMESTAMP
+             RETURNING id"
+        )
+        .bind(image_id)
+        .bind(kind)
+        .bind(path)
+        .bind(label)
+        .bind(size)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Derivatives of an image, or of every image, newest first.
+    pub async fn get_derivatives(&self, image_id: Option<i64>) -> Result<Vec<DerivativeRow>, sqlx::Error> {
+        sqlx::query_as::<_, DerivativeRow>(&format!(
+            "{} WHERE ? IS NULL OR image_id = ? ORDER BY created_at DESC, id DESC",
+            DERIVATIVE_COLUMNS
+        ))
+        .bind(image_id)
+        .bind(image_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// The derivative recorded for the file at `path`, if any.
+    pub async fn get_derivative_by_path(&self, path: &str) -> Result<Option<DerivativeRow>, sqlx::Error> {
+        sqlx::query_as::<_, DerivativeRow>(&format!("{} WHERE path = ?", DERIVATIVE_COLUMNS))
+            .bind(path)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn get_derivative(&self, id: i64) -> Result<Option<DerivativeRow>, sqlx::Error> {
+        sqlx::query_as::<_, DerivativeRow>(&format!("{} WHERE id = ?", DERIVATIVE_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Forgets derivatives; their files are left alone.
+    pub async fn delete_derivatives(&self, ids: &[i64]) -> Result<u64, sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> =
+            sqlx::QueryBuilder::new("DELETE FROM derivatives WHERE id IN (");
+        let mut separated = query_builder.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+
+        Ok(query_builder.build().execute(&self.pool).await?.rows_affected())
+    }
+}
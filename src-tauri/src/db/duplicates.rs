@@ -0,0 +1,135 @@
+//! Duplicate files, found through their content hashes (see
+//! `indexer::content_hashes`).
+
+use std::collections::HashMap;
+
+use super::Db;
+
+/// A file of a duplicate group, with what tells the copies apart.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DuplicateRow {
+    pub id: i64,
+    pub path: String,
+    pub filename: String,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub size: i64,
+    pub format: String,
+    pub rating: Option<i32>,
+    pub has_notes: bool,
+    pub tag_count: i64,
+    pub annotation_count: i64,
+    pub has_capture_time: bool,
+}
+
+/// Images with the same content hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashGroup {
+    pub content_hash: String,
+    pub size: i64,
+    /// In library order.
+    pub ids: Vec<i64>,
+}
+
+impl HashGroup {
+    /// Bytes taken by all the copies but one.
+    pub fn wasted_bytes(&self) -> i64 {
+        self.size * (self.ids.len() as i64 - 1)
+    }
+}
+
+impl Db {
+    /// Images that may have a copy, as another image has their size, and
+    /// that haven't been hashed since they last changed: `(id, path)`.
+    pub async fn get_images_needing_content_hash(&self, limit: i64) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, path FROM images
+             WHERE content_hashed_at IS NULL AND size > 0
+               AND size IN (SELECT size FROM images GROUP BY size HAVING COUNT(*) > 1)
+             ORDER BY id
+             LIMIT ?",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Stores the hash of each image, `None` for files that couldn't be read,
+    /// and marks them as hashed.
+    pub async fn set_content_hashes(&self, hashes: &[(i64, Option<String>)]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (id, hash) in hashes {
+            sqlx::query("UPDATE images SET content_hash = ?, content_hashed_at = CURRENT_TIMESTAMP WHERE id = ?")
+                .bind(hash)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    /// Groups of images sharing a content hash, those wasting the most space
+    /// first.
+    pub async fn get_duplicate_groups(&self) -> Result<Vec<HashGroup>, sqlx::Error> {
+        let rows: Vec<(String, i64, i64)> = sqlx::query_as(
+            "SELECT content_hash, id, size FROM images
+             WHERE content_hash IN (
+                 SELECT content_hash FROM images WHERE content_hash IS NOT NULL GROUP BY content_hash HAVING COUNT(*) > 1
+             )
+             ORDER BY content_hash, id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut groups: Vec<HashGroup> = Vec::new();
+        for (content_hash, id, size) in rows {
+            match groups.last_mut() {
+                Some(group) if group.content_hash == content_hash => group.ids.push(id),
+                _ => groups.push(HashGroup { content_hash, size, ids: vec![id] }),
+            }
+        }
+        groups.sort_by(|a, b| b.wasted_bytes().cmp(&a.wasted_bytes()).then_with(|| a.content_hash.cmp(&b.content_hash)));
+        Ok(groups)
+    }
+
+    /// The files of duplicate groups, in the order of `ids`.
+    pub async fn get_duplicate_rows(&self, ids: &[i64]) -> Result<Vec<DuplicateRow>, sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ids_json = serde_json::to_string(ids).unwrap_or_else(|_| "[]".to_string());
+        let rows: Vec<DuplicateRow> = sqlx::query_as(
+            "SELECT i.id, i.path, i.filename, i.width, i.height, i.size, i.format, i.rating,
+                    COALESCE(i.notes, '') != '' AS has_notes,
+                    (SELECT COUNT(*) FROM image_tags t WHERE t.image_id = i.id) AS tag_count,
+                    (SELECT COUNT(*) FROM image_annotations a WHERE a.image_id = i.id) AS annotation_count,
+                    i.captured_at IS NOT NULL AS has_capture_time
+             FROM images i WHERE i.id IN (SELECT value FROM json_each(?))",
+        )
+        .bind(ids_json)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut rows: HashMap<i64, DuplicateRow> = rows.into_iter().map(|row| (row.id, row)).collect();
+        Ok(ids.iter().filter_map(|id| rows.remove(id)).collect())
+    }
+
+    /// Merges each of `others` into `keep_id` (see `merge_image_into`) and
+    /// removes them from the library, in one transaction.
+    pub async fn merge_duplicates(&self, keep_id: i64, others: &[i64]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for other in others.iter().filter(|id| **id != keep_id) {
+            super::folders::merge_image_into(&mut *tx, *other, keep_id).await?;
+        }
+        tx.commit().await
+    }
+
+    /// Gives `keep_id` the metadata of each of `others` (see
+    /// `absorb_image`), leaving them in the library, in one transaction.
+    pub async fn absorb_duplicates(&self, keep_id: i64, others: &[i64]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for other in others.iter().filter(|id| **id != keep_id) {
+            super::folders::absorb_image(&mut *tx, *other, keep_id).await?;
+        }
+        tx.commit().await
+    }
+}
@@ -0,0 +1,154 @@
+//! Cached EXIF parses (see `media::exif_cache`).
+
+use std::collections::HashMap;
+
+use super::Db;
+
+/// An EXIF parse, with the modification time (milliseconds) and size of the
+/// file it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedExif {
+    pub image_id: i64,
+    pub file_mtime: i64,
+    pub file_size: i64,
+    pub data: HashMap<String, String>,
+}
+
+impl Db {
+    /// Cached parses of the given images. Images without one are left out.
+    pub async fn get_cached_exif(&self, image_ids: &[i64]) -> Result<Vec<CachedExif>, sqlx::Error> {
+        if image_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> =
+            sqlx::QueryBuilder::new("SELECT image_id, file_mtime, file_size, data FROM exif_cache WHERE image_id IN (");
+        let mut separated = query_builder.separated(", ");
+        for id in image_ids {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+
+        let rows: Vec<(i64, i64, i64, String)> = query_builder.build_query_as().fetch_all(&self.pool).await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(image_id, file_mtime, file_size, data)| {
+                // An unreadable row is as good as none: the file is parsed again
+                let data = serde_json::from_str(&data).ok()?;
+                Some(CachedExif { image_id, file_mtime, file_size, data })
+            })
+            .collect())
+    }
+
+    /// Stores parses, replacing the previous ones of the same images.
+    pub async fn save_exif(&self, entries: &[CachedExif]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for entry in entries {
+            let data = serde_json::to_string(&entry.data).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+            // The image may have been removed since it was parsed
+            sqlx::query(
+                "INSERT OR REPLACE INTO exif_cache (image_id, file_mtime, file_size, data)
+                 SELECT id, ?, ?, ? FROM images WHERE id = ?"
+            )
+            .bind(entry.file_mtime)
+            .bind(entry.file_size)
+            .bind(data)
+            .bind(entry.image_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Id of the image at `path`, if it is in the library.
+    pub async fn get_image_id_by_path(&self, path: &str) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar("SELECT id FROM images WHERE path = ?")
+            .bind(path)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Paths of the given images. Unknown ids are left out.
+    pub async fn get_image_paths(&self, image_ids: &[i64]) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        if image_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> =
+            sqlx::QueryBuilder::new("SELECT id, path FROM images WHERE id IN (");
+        let mut separated = query_builder.separated(", ");
+        for id in image_ids {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+
+        query_builder.build_query_as().fetch_all(&self.pool).await
+    }
+
+    /// Files of the given formats added in the last `days`, with an id below
+    /// `before_id`, that have no parse or one taken at another file size.
+    /// Newest first.
+    pub async fn get_recent_images_needing_exif(
+        &self,
+        formats: &[&str],
+        days: i64,
+        before_id: i64,
+        limit: i64,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        if formats.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
+            "SELECT i.id, i.path FROM images i LEFT JOIN exif_cache e ON e.image_id = i.id
+             WHERE (e.image_id IS NULL OR e.file_size IS NOT i.size) AND i.id < "
+        );
+        query_builder.push_bind(before_id);
+        query_builder.push(" AND i.added_at >= datetime('now', ");
+        query_builder.push_bind(format!("-{} days", days));
+        query_builder.push(") AND i.format IN (");
+        let mut separated = query_builder.separated(", ");
+        for format in formats {
+            separated.push_bind(*format);
+        }
+        separated.push_unseparated(") ORDER BY i.id DESC LIMIT ");
+        query_builder.push_bind(limit);
+
+        query_builder.build_query_as().fetch_all(&self.pool).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_exif_cache_round_trip() {
+        let library = crate::testkit::TestLibrary::open("exif-cache").await;
+        let db = &library.db;
+        library.seed_images(&["1.jpg", "2.jpg", "3.png"]).await;
+
+        let pending = db.get_recent_images_needing_exif(&["jpg"], 7, i64::MAX, 10).await.unwrap();
+        assert_eq!(pending.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(db.get_recent_images_needing_exif(&["jpg"], 7, 2, 10).await.unwrap().len(), 1);
+
+        let entry = CachedExif {
+            image_id: 2,
+            file_mtime: 1_704_067_200_000,
+            file_size: 100,
+            data: HashMap::from([("Model".to_string(), "X100V".to_string())]),
+        };
+        let removed = CachedExif { image_id: 99, ..entry.clone() };
+        db.save_exif(&[entry.clone(), removed]).await.unwrap();
+        assert_eq!(db.get_cached_exif(&[1, 2, 99]).await.unwrap(), vec![entry]);
+
+        let pending = db.get_recent_images_needing_exif(&["jpg"], 7, i64::MAX, 10).await.unwrap();
+        assert_eq!(pending, vec![(1, "/lib/1.jpg".to_string())]);
+        sqlx::query("UPDATE images SET size = 200 WHERE id = 2").execute(&db.pool).await.unwrap();
+        assert_eq!(db.get_recent_images_needing_exif(&["jpg"], 7, i64::MAX, 10).await.unwrap().len(), 2);
+
+        sqlx::query("DELETE FROM images WHERE id = 2").execute(&db.pool).await.unwrap();
+        assert!(db.get_cached_exif(&[2]).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,34 @@
+//! Custom fields of images: named text values (see `library::catalog_import`).
+
+use super::Db;
+
+/// Prefix of the catalog columns holding a custom field, as in `field:client`.
+pub const FIELD_PREFIX: &str = "field:";
+
+impl Db {
+    /// Names of the custom fields set on at least one image, sorted.
+    pub async fn get_field_names(&self) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT DISTINCT name FROM image_fields ORDER BY name")
+            .fetch_all(&self.pool)
+            .await
+    }
+}
+
+/// Sets the custom field `name` of an image. Returns whether it changed.
+pub async fn set_field(
+    conn: &mut sqlx::SqliteConnection,
+    image_id: i64,
+    name: &str,
+    value: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO image_fields (image_id, name, value) VALUES (?, ?, ?)
+         ON CONFLICT (image_id, name) DO UPDATE SET value = excluded.value WHERE value IS NOT excluded.value",
+    )
+    .bind(image_id)
+    .bind(name)
+    .bind(value)
+    .execute(&mut *conn)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
@@ -4,6 +4,7 @@
 //! to database records and managing hierarchical relationships.
 
 use super::Db;
+use crate::db::models::Tag;
 use sqlx::SqliteConnection;
 
 impl Db {
@@ -17,7 +18,8 @@ impl Db {
 
     /// Finds a folder by its filesystem path.
     ///
-    /// Includes a case-insensitive fallback specifically for macOS.
+    /// On case-insensitive filesystems a path spelled with other cases finds
+    /// the folder too, as it names the same directory.
     pub async fn get_folder_by_path(&self, path: &str) -> Result<Option<i64>, sqlx::Error> {
         let mut conn = self.pool.acquire().await?;
         self.get_folder_id_internal(&mut conn, path).await
@@ -37,11 +39,16 @@ impl Db {
         if let Some(r) = row {
             Ok(Some(r.id))
         } else {
-            // Case-insensitive fallback for move detections on case-insensitive filesystems
-            let row_loose = sqlx::query!("SELECT id as \"id!\" FROM folders WHERE path = ? COLLATE NOCASE", path)
-                .fetch_optional(&mut *conn)
-                .await?;
-            Ok(row_loose.map(|r| r.id))
+            // Only where the filesystem ignores case: on Linux `Trip` and `trip` are two folders
+            sqlx::query_scalar(
+                "SELECT id FROM folders
+                 WHERE path = ? COLLATE NOCASE AND NOT COALESCE(case_sensitive, ?)
+                 ORDER BY id LIMIT 1"
+            )
+            .bind(path)
+            .bind(crate::paths::default_case_sensitive())
+            .fetch_optional(&mut *conn)
+            .await
         }
     }
 
@@ -108,13 +115,16 @@ impl Db {
             return Ok(id);
         }
 
-        let res = sqlx::query!(
-            "INSERT INTO folders (path, name, parent_id, is_root) VALUES (?, ?, ?, ?)",
-            path,
-            name,
-            parent_id,
-            is_root
+        // New folders are on the filesystem of their parent
+        let res = sqlx::query(
+            "INSERT INTO folders (path, name, parent_id, is_root, case_sensitive)
+             VALUES (?, ?, ?, ?, (SELECT case_sensitive FROM folders WHERE id = ?))"
         )
+        .bind(path)
+        .bind(name)
+        .bind(parent_id)
+        .bind(is_root)
+        .bind(parent_id)
         .execute(&mut *conn)
         .await;
 
@@ -132,7 +142,40 @@ impl Db {
         }
     }
 
+    /// Records whether the filesystem of a location tells names apart by
+    /// case, for the location and every folder in it.
+    pub async fn set_location_case_sensitivity(&self, location_id: i64, case_sensitive: bool) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "WITH RECURSIVE family AS (
+                SELECT id FROM folders WHERE id = ?
+                UNION ALL
+                SELECT f.id FROM folders f JOIN family ON f.parent_id = family.id
+             )
+             UPDATE folders SET case_sensitive = ?
+             WHERE id IN family AND case_sensitive IS NOT ?"
+        )
+        .bind(location_id)
+        .bind(case_sensitive)
+        .bind(case_sensitive)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Whether the folder at `path` tells names apart by case, as probed by
+    /// the last scan of its location.
+    pub async fn is_case_sensitive(&self, path: &str) -> Result<bool, sqlx::Error> {
+        let stored: Option<Option<bool>> = sqlx::query_scalar("SELECT case_sensitive FROM folders WHERE path = ?")
+            .bind(path.trim_end_matches('/'))
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(stored.flatten().unwrap_or_else(crate::paths::default_case_sensitive))
+    }
+
     /// Retrieves all thumbnail paths for images within a folder and all its descendants.
+    ///
+    /// Thumbnails are keyed on content, so a duplicate outside the folder may
+    /// share one; those are left out, as they are still in use.
     pub async fn get_location_thumbnails(&self, location_id: i64) -> Result<Vec<String>, sqlx::Error> {
         let rows: Vec<(String,)> = sqlx::query_as(
             "WITH RECURSIVE family AS (
@@ -140,7 +183,12 @@ impl Db {
                 UNION ALL
                 SELECT f.id FROM folders f JOIN family ON f.parent_id = family.id
              )
-             SELECT thumbnail_path FROM images WHERE folder_id IN family AND thumbnail_path IS NOT NULL"
+             SELECT DISTINCT thumbnail_path FROM images
+             WHERE folder_id IN family AND thumbnail_path IS NOT NULL
+               AND thumbnail_path NOT IN (
+                   SELECT thumbnail_path FROM images
+                   WHERE folder_id NOT IN family AND thumbnail_path IS NOT NULL
+               )"
         )
         .bind(location_id)
         .fetch_all(&self.pool)
@@ -149,11 +197,74 @@ impl Db {
         Ok(rows.into_iter().map(|(path,)| path).collect())
     }
 
-    /// Deletes a folder record. Images and child folders are handled by CASCADE.
+    /// Counts `(files, total size, files with a thumbnail, corrupt files)` in a
+    /// folder and all its descendants.
+    pub async fn get_location_file_stats(&self, location_id: i64) -> Result<(i64, i64, i64, i64), sqlx::Error> {
+        sqlx::query_as(
+            "WITH RECURSIVE family AS (
+                SELECT id FROM folders WHERE id = ?
+                UNION ALL
+                SELECT f.id FROM folders f JOIN family ON f.parent_id = family.id
+             )
+             SELECT COUNT(*),
+                    COALESCE(SUM(size), 0),
+                    COUNT(thumbnail_path),
+                    COALESCE(SUM(corrupt_suspected), 0)
+             FROM images WHERE folder_id IN family"
+        )
+        .bind(location_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Stores the outcome of a finished scan of a location.
+    pub async fn record_location_scan(
+        &self,
+        location_id: i64,
+        started_at: chrono::DateTime<chrono::Utc>,
+        duration_ms: i64,
+        files_seen: i64,
+        unreadable_count: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO location_scans (folder_id, started_at, duration_ms, files_seen, unreadable_count)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT(folder_id) DO UPDATE SET
+                started_at = excluded.started_at,
+                duration_ms = excluded.duration_ms,
+                files_seen = excluded.files_seen,
+                unreadable_count = excluded.unreadable_count"
+        )
+        .bind(location_id)
+        .bind(started_at)
+        .bind(duration_ms)
+        .bind(files_seen)
+        .bind(unreadable_count)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns `(started_at, duration_ms, unreadable_count)` of the last scan of a location.
+    pub async fn get_location_scan(
+        &self,
+        location_id: i64,
+    ) -> Result<Option<(chrono::DateTime<chrono::Utc>, i64, i64)>, sqlx::Error> {
+        sqlx::query_as("SELECT started_at, duration_ms, unreadable_count FROM location_scans WHERE folder_id = ?")
+            .bind(location_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Deletes a folder record and its child folders (by CASCADE), moving
+    /// their images to the trash.
     pub async fn delete_folder(&self, folder_id: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        super::trash::trash_folder_images(&mut tx, folder_id, super::trash::REASON_MISSING).await?;
         sqlx::query!("DELETE FROM folders WHERE id = ?", folder_id)
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await?;
+        tx.commit().await?;
         Ok(())
     }
 
@@ -349,4 +460,228 @@ impl Db {
 
         Ok(rows.into_iter().map(|r| (r.id, r.path)).collect())
     }
+
+    /// Replaces the default tags of a folder.
+    pub async fn set_folder_default_tags(&self, folder_id: i64, tag_ids: &[i64]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM folder_default_tags WHERE folder_id = ?")
+            .bind(folder_id)
+            .execute(&mut *tx)
+            .await?;
+        for tag_id in tag_ids {
+            sqlx::query("INSERT OR IGNORE INTO folder_default_tags (folder_id, tag_id) VALUES (?, ?)")
+                .bind(folder_id)
+                .bind(tag_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Whether a folder is a location added by the user.
+    pub async fn is_root_folder(&self, folder_id: i64) -> Result<bool, sqlx::Error> {
+        let is_root: Option<bool> = sqlx::query_scalar("SELECT is_root FROM folders WHERE id = ?")
+            .bind(folder_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(is_root.unwrap_or(false))
+    }
+
+    /// Lists `(id, path)` of the images in a folder and all its subfolders.
+    pub async fn get_folder_images_recursive(&self, folder_id: i64) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(
+            "WITH RECURSIVE sub(id) AS (
+                SELECT ? UNION ALL SELECT f.id FROM folders f JOIN sub ON f.parent_id = sub.id
+             )
+             SELECT id, path FROM images WHERE folder_id IN (SELECT id FROM sub) ORDER BY path"
+        )
+        .bind(folder_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Records a folder merge in one transaction (see `library::merge`).
+    ///
+    /// `moved` images, `(id, path, filename, folder_id)`, point at their new
+    /// place. Each `(source, target)` of `merged` is a duplicate: the target
+    /// image takes the source's tags, annotations, and rating or notes if it
+    /// has none, and the source row is dropped. The source folder goes last,
+    /// with its subfolders, unless an image was left behind.
+    pub async fn apply_folder_merge(
+        &self,
+        source_id: i64,
+        target_id: i64,
+        moved: &[(i64, String, String, i64)],
+        merged: &[(i64, i64)],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        for (id, path, filename, folder_id) in moved {
+            sqlx::query("UPDATE images SET path = ?, filename = ?, folder_id = ? WHERE id = ?")
+                .bind(path)
+                .bind(filename)
+                .bind(folder_id)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        for (source, target) in merged {
+            merge_image_into(&mut *tx, *source, *target).await?;
+        }
+
+        sqlx::query("INSERT OR IGNORE INTO folder_default_tags (folder_id, tag_id) SELECT ?, tag_id FROM folder_default_tags WHERE folder_id = ?")
+            .bind(target_id)
+            .bind(source_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Deleting the folder cascades to images, so never with files still in it
+        sqlx::query(
+            "WITH RECURSIVE sub(id) AS (
+                SELECT ? UNION ALL SELECT f.id FROM folders f JOIN sub ON f.parent_id = sub.id
+             )
+             DELETE FROM folders WHERE id = ? AND NOT EXISTS (SELECT 1 FROM images WHERE folder_id IN (SELECT id FROM sub))"
+        )
+        .bind(source_id)
+        .bind(source_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Gets the default tags of a folder, without those inherited from its ancestors.
+    pub async fn get_folder_default_tags(&self, folder_id: i64) -> Result<Vec<Tag>, sqlx::Error> {
+        sqlx::query_as::<_, Tag>(
+            "SELECT t.id, t.name, t.parent_id, t.color, COALESCE(t.order_index, 0) AS order_index
+             FROM tags t
+             JOIN folder_default_tags d ON d.tag_id = t.id
+             WHERE d.folder_id = ?
+             ORDER BY t.order_index ASC, t.name ASC"
+        )
+        .bind(folder_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
+
+/// Applies the default tags of an image's folder and all its ancestors.
+/// Runs on `conn` so files saved in a batch transaction are tagged with it.
+///
+/// Returns the number of tags added.
+pub(crate) async fn apply_folder_default_tags(conn: &mut SqliteConnection, image_id: i64) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query(
+        "WITH RECURSIVE ancestors(id) AS (
+            SELECT folder_id FROM images WHERE id = ?
+            UNION ALL
+            SELECT f.parent_id FROM folders f JOIN ancestors a ON f.id = a.id WHERE f.parent_id IS NOT NULL
+         )
+         INSERT OR IGNORE INTO image_tags (image_id, tag_id)
+         SELECT DISTINCT ?, tag_id FROM folder_default_tags WHERE folder_id IN ancestors"
+    )
+    .bind(image_id)
+    .bind(image_id)
+    .execute(conn)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+/// Merges image `source` into `target` (see [`absorb_image`]), then deletes
+/// `source`. Used when the two are copies of the same file.
+pub(crate) async fn merge_image_into(conn: &mut SqliteConnection, source: i64, target: i64) -> Result<(), sqlx::Error> {
+    absorb_image(conn, source, target).await?;
+    super::collections::leave_collections(conn, source).await?;
+    sqlx::query("DELETE FROM images WHERE id = ?")
+        .bind(source)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Gives image `target` the tags, fields and collections of `source`, its
+/// rating and notes where it has none, and moves over its annotations, links
+/// and derivatives, leaving `source` in the library. In each collection the
+/// target joins right after the source.
+pub(crate) async fn absorb_image(conn: &mut SqliteConnection, source: i64, target: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT OR IGNORE INTO image_tags (image_id, tag_id) SELECT ?, tag_id FROM image_tags WHERE image_id = ?")
+        .bind(target)
+        .bind(source)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("INSERT OR IGNORE INTO image_fields (image_id, name, value) SELECT ?, name, value FROM image_fields WHERE image_id = ?")
+        .bind(target)
+        .bind(source)
+        .execute(&mut *conn)
+        .await?;
+    let memberships: Vec<(i64, i64)> = sqlx::query_as(
+        "SELECT collection_id, position FROM collection_items
+         WHERE image_id = ?1 AND collection_id NOT IN (SELECT collection_id FROM collection_items WHERE image_id = ?2)"
+    )
+    .bind(source)
+    .bind(target)
+    .fetch_all(&mut *conn)
+    .await?;
+    for (collection_id, position) in memberships {
+        sqlx::query("UPDATE collection_items SET position = position + 1 WHERE collection_id = ? AND position > ?")
+            .bind(collection_id)
+            .bind(position)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("INSERT INTO collection_items (collection_id, image_id, position) VALUES (?, ?, ?)")
+            .bind(collection_id)
+            .bind(target)
+            .bind(position + 1)
+            .execute(&mut *conn)
+            .await?;
+    }
+    for table in ["links", "derivatives"] {
+        sqlx::query(&format!("UPDATE {table} SET image_id = ? WHERE image_id = ?"))
+            .bind(target)
+            .bind(source)
+            .execute(&mut *conn)
+            .await?;
+    }
+    sqlx::query(
+        "UPDATE images SET
+            rating = CASE WHEN COALESCE(rating, 0) = 0 THEN (SELECT rating FROM images WHERE id = ?1) ELSE rating END,
+            notes = CASE WHEN COALESCE(notes, '') = '' THEN (SELECT notes FROM images WHERE id = ?1) ELSE notes END
+         WHERE id = ?2"
+    )
+    .bind(source)
+    .bind(target)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query("UPDATE image_annotations SET image_id = ? WHERE image_id = ?")
+        .bind(target)
+        .bind(source)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[tokio::test]
+    async fn test_case_insensitive_lookup_follows_filesystem() {
+        let library = crate::testkit::TestLibrary::open("folders-case").await;
+        let db = &library.db;
+        let root = db.upsert_folder("/lib", "lib", None, true).await.unwrap();
+        let trip = db.upsert_folder("/lib/Trip", "Trip", Some(root), false).await.unwrap();
+
+        db.set_location_case_sensitivity(root, true).await.unwrap();
+        assert_eq!(db.get_folder_by_path("/lib/trip").await.unwrap(), None);
+        let other = db.upsert_folder("/lib/trip", "trip", Some(root), false).await.unwrap();
+        assert_ne!(other, trip);
+        db.delete_folder(other).await.unwrap();
+
+        db.set_location_case_sensitivity(root, false).await.unwrap();
+        assert_eq!(db.get_folder_by_path("/lib/TRIP").await.unwrap(), Some(trip));
+        // Folders found later are on the filesystem of their parent
+        let day = db.upsert_folder("/lib/Trip/Day1", "Day1", Some(trip), false).await.unwrap();
+        assert_eq!(db.get_folder_by_path("/lib/trip/day1").await.unwrap(), Some(day));
+        assert!(!db.is_case_sensitive("/lib/Trip/Day1").await.unwrap());
+    }
 }
@@ -1,8 +1,26 @@
 //! Image management and metadata queries.
 
 use crate::db::models::ImageMetadata;
+use crate::db::search::ImageFilter;
 use super::Db;
 
+/// Images each folder contributes per round of the thumbnail queue.
+const FOLDER_ROUND: i64 = 24;
+
+/// Failures pointing at a damaged file before it is flagged as corrupt, so
+/// one unlucky read doesn't hide it.
+const CORRUPT_AFTER_FAILURES: i64 = 2;
+
+/// Restricts a filter to images still waiting for a thumbnail.
+const NEEDS_THUMBNAIL_CONDITION: &str =
+    " AND i.thumbnail_path IS NULL AND i.thumbnail_attempts < 3 AND i.corrupt_suspected = 0 ";
+
+/// Columns of an [`ImageMetadata`], for queries reading `images i` with
+/// `LEFT JOIN playback_positions pp ON pp.image_id = i.id`.
+pub(crate) const IMAGE_COLUMNS: &str = "i.id, i.path, i.filename, i.width, i.height, i.size, i.thumbnail_path, i.format, i.rating, i.notes, \
+    i.created_at, i.modified_at, i.added_at, pp.position AS playback_position, COALESCE(pp.completed, 0) AS playback_completed, \
+    i.sequence_id, i.latitude, i.longitude, i.duration, i.frame_count, i.loops, i.captured_at, i.capture_offset";
+
 impl Db {
     /// Updates the star rating for a specific image.
     pub async fn update_image_rating(&self, id: i64, rating: i32) -> Result<(), sqlx::Error> {
@@ -12,6 +30,176 @@ impl Db {
         Ok(())
     }
 
+    /// Sets the star rating of several images in a single transaction.
+    pub async fn update_images_rating(&self, ids: &[i64], rating: i32) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for id in ids {
+            sqlx::query("UPDATE images SET rating = ? WHERE id = ?")
+                .bind(rating)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Retrieves images by id, in the order of `ids`. Unknown ids are skipped.
+    pub async fn get_images_by_ids(&self, ids: &[i64]) -> Result<Vec<ImageMetadata>, sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(format!(
+            "SELECT {} FROM images i LEFT JOIN playback_positions pp ON pp.image_id = i.id WHERE i.id IN (",
+            IMAGE_COLUMNS
+        ));
+        let mut separated = query_builder.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+
+        let mut images = query_builder.build_query_as::<ImageMetadata>().fetch_all(&self.pool).await?;
+        let order: std::collections::HashMap<i64, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        images.sort_by_key(|img| order.get(&img.id).copied().unwrap_or(usize::MAX));
+        Ok(images)
+    }
+
+    /// Summarizes a selection (size, dates, ratings, formats and tags) in a single query.
+    ///
+    /// The ids are bound as one JSON array, so large selections don't run into
+    /// SQLite's bound parameter limit.
+    pub async fn get_selection_summary(&self, ids: &[i64]) -> Result<crate::db::models::SelectionSummary, sqlx::Error> {
+        use crate::db::models::{FormatCount, RatingCount, SelectionSummary, TagCount};
+
+        let ids_json = serde_json::to_string(ids).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+        let row: (i64, i64, Option<chrono::DateTime<chrono::Utc>>, Option<chrono::DateTime<chrono::Utc>>, String, String, String) =
+            sqlx::query_as(
+                "WITH sel AS (
+                    SELECT i.id, i.size, i.rating, i.format, i.created_at
+                    FROM images i WHERE i.id IN (SELECT value FROM json_each(?))
+                 )
+                 SELECT
+                    (SELECT COUNT(*) FROM sel),
+                    (SELECT COALESCE(SUM(size), 0) FROM sel),
+                    (SELECT MIN(created_at) FROM sel),
+                    (SELECT MAX(created_at) FROM sel),
+                    (SELECT json_group_array(json_array(rating, n))
+                     FROM (SELECT COALESCE(rating, 0) AS rating, COUNT(*) AS n FROM sel GROUP BY 1 ORDER BY 1)),
+                    (SELECT json_group_array(json_array(format, n))
+                     FROM (SELECT COALESCE(format, '') AS format, COUNT(*) AS n FROM sel GROUP BY 1 ORDER BY n DESC, 1)),
+                    (SELECT json_group_array(json_array(tag_id, n))
+                     FROM (SELECT it.tag_id, COUNT(*) AS n FROM image_tags it JOIN sel ON sel.id = it.image_id
+                           GROUP BY it.tag_id ORDER BY n DESC))"
+            )
+            .bind(ids_json)
+            .fetch_one(&self.pool)
+            .await?;
+
+        let (count, total_size, created_from, created_to, ratings, formats, tags) = row;
+        let decode = |json: &str| -> Result<Vec<(serde_json::Value, i64)>, sqlx::Error> {
+            serde_json::from_str(json).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+        };
+
+        let rating_counts = decode(&ratings)?
+            .into_iter()
+            .map(|(rating, count)| RatingCount { rating: rating.as_i64().unwrap_or(0), count })
+            .collect();
+        let format_counts = decode(&formats)?
+            .into_iter()
+            .map(|(format, count)| FormatCount { format: format.as_str().unwrap_or_default().to_string(), count })
+            .collect();
+        let tag_counts: Vec<TagCount> = decode(&tags)?
+            .into_iter()
+            .filter_map(|(tag_id, count)| Some(TagCount { tag_id: tag_id.as_i64()?, count }))
+            .collect();
+        let shared_tag_ids = tag_counts.iter().filter(|t| t.count == count).map(|t| t.tag_id).collect();
+
+        Ok(SelectionSummary {
+            count,
+            total_size,
+            created_from,
+            created_to,
+            rating_counts,
+            format_counts,
+            tag_counts,
+            shared_tag_ids,
+        })
+    }
+
+    /// Updates path and filename of several images in one transaction.
+    ///
+    /// Used after in-app renames, where the folder doesn't change.
+    pub async fn apply_renames(&self, renames: &[(i64, String, String)]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (id, new_path, new_filename) in renames {
+            sqlx::query("UPDATE images SET path = ?, filename = ? WHERE id = ?")
+                .bind(new_path)
+                .bind(new_filename)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+            super::collation::refresh_sort_key(&mut *tx, *id, new_filename).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Counts the files without a thumbnail per format, in a folder scope or
+    /// in the whole library when `folder_id` is `None`.
+    pub async fn count_missing_thumbnails_by_format(
+        &self,
+        folder_id: Option<i64>,
+        recursive: bool,
+    ) -> Result<Vec<(Option<String>, i64)>, sqlx::Error> {
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
+            "SELECT format, COUNT(*) FROM images WHERE thumbnail_path IS NULL"
+        );
+        push_folder_scope(&mut query_builder, folder_id, recursive);
+        query_builder.push(" GROUP BY format");
+
+        query_builder
+            .build_query_as::<(Option<String>, i64)>()
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Retrieves `(path, format, size, duration)` for the files of the given
+    /// formats in a folder scope, or in the whole library when `folder_id` is `None`.
+    pub async fn get_scope_files_of_formats(
+        &self,
+        folder_id: Option<i64>,
+        recursive: bool,
+        formats: &[&str],
+    ) -> Result<Vec<(String, Option<String>, Option<i64>, Option<f64>)>, sqlx::Error> {
+        let formats_json = serde_json::to_string(formats).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
+            "SELECT path, format, size, duration FROM images WHERE format IN (SELECT value FROM json_each("
+        );
+        query_builder.push_bind(formats_json);
+        query_builder.push("))");
+        push_folder_scope(&mut query_builder, folder_id, recursive);
+
+        query_builder
+            .build_query_as::<(String, Option<String>, Option<i64>, Option<f64>)>()
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Retrieves up to `per_format` recent thumbnail filenames for each format.
+    pub async fn sample_thumbnails_by_format(&self, per_format: i64) -> Result<Vec<(String, String)>, sqlx::Error> {
+        sqlx::query_as::<_, (String, String)>(
+            "SELECT format, thumbnail_path FROM (
+                SELECT format, thumbnail_path, ROW_NUMBER() OVER (PARTITION BY format ORDER BY id DESC) AS rn
+                FROM images WHERE thumbnail_path IS NOT NULL
+             ) WHERE rn <= ?"
+        )
+        .bind(per_format)
+        .fetch_all(&self.pool)
+        .await
+    }
+
     /// Updates the user notes for a specific image.
     pub async fn update_image_notes(&self, id: i64, notes: String) -> Result<(), sqlx::Error> {
         sqlx::query!("UPDATE images SET notes = ? WHERE id = ?", notes, id)
@@ -21,17 +209,47 @@ impl Db {
     }
 
     /// Retrieves images that do not have a thumbnail generated yet.
+    ///
+    /// The queue is taken breadth-first: every folder gets its first
+    /// `FOLDER_ROUND` images before any folder gets more, the folders holding
+    /// the most recently modified files first. A freshly added location thus
+    /// shows a few thumbnails everywhere instead of finishing one folder at a
+    /// time in id order.
     pub async fn get_images_needing_thumbnails(
         &self,
         limit: i32,
     ) -> Result<Vec<(i64, String)>, sqlx::Error> {
-        let rows = sqlx::query!(
-            "SELECT id, path FROM images WHERE thumbnail_path IS NULL AND thumbnail_attempts < 3 LIMIT ?",
-            limit
+        let rows = sqlx::query_as::<_, (i64, String)>(
+            "SELECT id, path FROM (
+                SELECT id, path, folder_id,
+                       ROW_NUMBER() OVER (PARTITION BY folder_id ORDER BY id) AS folder_rank,
+                       MAX(modified_at) OVER (PARTITION BY folder_id) AS folder_modified
+                FROM images
+                WHERE thumbnail_path IS NULL AND thumbnail_attempts < 3 AND corrupt_suspected = 0
+             )
+             ORDER BY (folder_rank - 1) / ? ASC, folder_modified IS NULL ASC, folder_modified DESC, folder_id DESC, folder_rank ASC
+             LIMIT ?"
         )
+        .bind(FOLDER_ROUND)
+        .bind(limit)
         .fetch_all(&self.pool)
         .await?;
-        Ok(rows.into_iter().map(|r| (r.id, r.path)).collect())
+        Ok(rows)
+    }
+
+    /// Retrieves, in grid order, images matching `filter` that still need a
+    /// thumbnail. Pagination fields of the filter are ignored.
+    pub async fn get_images_needing_thumbnails_in_filter(
+        &self,
+        filter: &ImageFilter,
+        limit: i32,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        let group = filter.parsed_group();
+        let mut query_builder = filter.build_id_query(group.as_ref(), NEEDS_THUMBNAIL_CONDITION);
+        query_builder.push(" LIMIT ");
+        query_builder.push_bind(limit);
+        let ids: Vec<i64> = query_builder.build_query_scalar::<i64>().fetch_all(&self.pool).await?;
+        self.get_images_needing_thumbnails_by_ids(&ids).await
     }
 
     /// Retrieves specific images needing thumbnails by their IDs.
@@ -45,7 +263,7 @@ impl Db {
 
         let placeholders: Vec<String> = ids.iter().map(|_| "?".to_string()).collect();
         let query = format!(
-            "SELECT id, path FROM images WHERE id IN ({}) AND thumbnail_path IS NULL AND thumbnail_attempts < 3",
+            "SELECT id, path FROM images WHERE id IN ({}) AND thumbnail_path IS NULL AND thumbnail_attempts < 3 AND corrupt_suspected = 0",
             placeholders.join(",")
         );
 
@@ -70,6 +288,169 @@ impl Db {
         Ok(())
     }
 
+    /// Records a thumbnail failure that points at a damaged file. The image
+    /// is flagged as probably corrupt, and no longer retried, once it failed
+    /// [`CORRUPT_AFTER_FAILURES`] times; a contradicting header (`mismatch`)
+    /// flags it at once. Returns whether it is flagged.
+    pub async fn record_corrupt_failure(&self, image_id: i64, detail: &str, mismatch: bool) -> Result<bool, sqlx::Error> {
+        let flagged: Option<bool> = sqlx::query_scalar(
+            "UPDATE images SET
+                thumbnail_attempts = COALESCE(thumbnail_attempts, 0) + 1,
+                thumbnail_last_error = ?1,
+                corrupt_suspected = (?2 OR COALESCE(thumbnail_attempts, 0) + 1 >= ?3),
+                corrupt_detail = CASE WHEN ?2 OR COALESCE(thumbnail_attempts, 0) + 1 >= ?3 THEN ?1 ELSE corrupt_detail END,
+                corrupt_detected_at = CASE WHEN ?2 OR COALESCE(thumbnail_attempts, 0) + 1 >= ?3 THEN CURRENT_TIMESTAMP ELSE corrupt_detected_at END
+             WHERE id = ?4
+             RETURNING corrupt_suspected"
+        )
+        .bind(detail)
+        .bind(mismatch)
+        .bind(CORRUPT_AFTER_FAILURES)
+        .bind(image_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(flagged.unwrap_or(false))
+    }
+
+    /// Lists every image currently flagged as corrupt, most recent first.
+    pub async fn get_corrupt_files(&self) -> Result<Vec<crate::db::models::CorruptFile>, sqlx::Error> {
+        sqlx::query_as::<_, crate::db::models::CorruptFile>(
+            "SELECT id, path, filename, format, size, corrupt_detail, corrupt_detected_at,
+                    COALESCE(thumbnail_attempts, 0) AS thumbnail_attempts
+             FROM images
+             WHERE corrupt_suspected = 1
+             ORDER BY corrupt_detected_at DESC"
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Records the magic-byte format of files whose extension lies, in one transaction.
+    ///
+    /// Entries with `None` clear a previously recorded mismatch.
+    pub async fn set_detected_formats(&self, items: &[(String, Option<String>)]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (path, detected) in items {
+            sqlx::query("UPDATE images SET detected_format = ? WHERE path = ?")
+                .bind(detected)
+                .bind(path)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Retrieves the path of an image.
+    pub async fn get_image_path(&self, id: i64) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT path FROM images WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Stores the page/part count of multi-page files, keyed by path.
+    pub async fn set_page_counts(&self, items: &[(String, i32)]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (path, count) in items {
+            sqlx::query("UPDATE images SET page_count = ? WHERE path = ?")
+                .bind(count)
+                .bind(path)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Stores the stock provider details of files, keyed by path.
+    ///
+    /// `None` clears the details of a file that no longer carries any, e.g.
+    /// after its XMP was rewritten.
+    pub async fn set_stock_info(&self, items: &[(String, Option<crate::db::models::StockInfo>)]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (path, info) in items {
+            let Some(info) = info else {
+                sqlx::query("DELETE FROM stock_assets WHERE image_id IN (SELECT id FROM images WHERE path = ?)")
+                    .bind(path)
+                    .execute(&mut *tx)
+                    .await?;
+                continue;
+            };
+            sqlx::query(
+                "INSERT OR REPLACE INTO stock_assets (image_id, provider, asset_id, usage_terms, license_url, credit, copyright)
+                 SELECT id, ?, ?, ?, ?, ?, ? FROM images WHERE path = ?"
+            )
+            .bind(&info.provider)
+            .bind(&info.asset_id)
+            .bind(&info.usage_terms)
+            .bind(&info.license_url)
+            .bind(&info.credit)
+            .bind(&info.copyright)
+            .bind(path)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Stock provider details of an image, if it comes from a stock site.
+    pub async fn get_stock_info(&self, image_id: i64) -> Result<Option<crate::db::models::StockInfo>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT provider, asset_id, usage_terms, license_url, credit, copyright FROM stock_assets WHERE image_id = ?"
+        )
+        .bind(image_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Lists images whose extension does not match their content.
+    pub async fn get_mismatched_extensions(&self) -> Result<Vec<crate::db::models::ExtensionMismatch>, sqlx::Error> {
+        sqlx::query_as::<_, crate::db::models::ExtensionMismatch>(
+            "SELECT id, path, filename, format, detected_format
+             FROM images
+             WHERE detected_format IS NOT NULL
+             ORDER BY path"
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Fetches the extension mismatch record for a single image.
+    pub async fn get_extension_mismatch(&self, id: i64) -> Result<Option<crate::db::models::ExtensionMismatch>, sqlx::Error> {
+        sqlx::query_as::<_, crate::db::models::ExtensionMismatch>(
+            "SELECT id, path, filename, format, detected_format
+             FROM images
+             WHERE id = ? AND detected_format IS NOT NULL"
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Points an image at its renamed path after its extension was corrected.
+    pub async fn apply_extension_fix(
+        &self,
+        id: i64,
+        new_path: &str,
+        new_filename: &str,
+        new_format: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query(
+            "UPDATE images SET path = ?, filename = ?, format = ?, detected_format = NULL WHERE id = ?"
+        )
+        .bind(new_path)
+        .bind(new_filename)
+        .bind(new_format)
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+        super::collation::refresh_sort_key(&mut conn, id, new_filename).await?;
+        Ok(())
+    }
+
     /// Updates the path to the generated thumbnail for an image.
     pub async fn update_thumbnail_path(
         &self,
@@ -82,14 +463,168 @@ impl Db {
         Ok(())
     }
 
-    /// Clears the thumbnail path, effectively flagging it for regeneration.
-    pub async fn clear_thumbnail_path(&self, image_id: i64) -> Result<(), sqlx::Error> {
-        sqlx::query!("UPDATE images SET thumbnail_path = NULL WHERE id = ?", image_id)
+    /// Queues the thumbnails of the given formats again when they fell back
+    /// to a file icon or failed, giving them fresh attempts. Files flagged as
+    /// corrupt stay skipped. Returns the number of images queued.
+    pub async fn requeue_thumbnails_for_formats(&self, formats: &[&str]) -> Result<u64, sqlx::Error> {
+        if formats.is_empty() {
+            return Ok(0);
+        }
+
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
+            "UPDATE images SET thumbnail_path = NULL, thumbnail_attempts = 0, thumbnail_last_error = NULL
+             WHERE corrupt_suspected = 0
+               AND (thumbnail_path LIKE 'extensions/%' OR (thumbnail_path IS NULL AND thumbnail_attempts > 0))
+               AND format IN (",
+        );
+        let mut separated = query_builder.separated(", ");
+        for format in formats {
+            separated.push_bind(*format);
+        }
+        separated.push_unseparated(")");
+
+        let result = query_builder.build().execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Queues the rendered thumbnails of the given formats again, after a
+    /// change that alters how they look. File icons are kept. Returns the
+    /// number of images queued.
+    pub async fn reset_thumbnails_for_formats(&self, formats: &[&str]) -> Result<u64, sqlx::Error> {
+        if formats.is_empty() {
+            return Ok(0);
+        }
+
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
+            "UPDATE images SET thumbnail_path = NULL, thumbnail_attempts = 0, thumbnail_last_error = NULL
+             WHERE thumbnail_path IS NOT NULL AND thumbnail_path NOT LIKE 'extensions/%'
+               AND format IN (",
+        );
+        let mut separated = query_builder.separated(", ");
+        for format in formats {
+            separated.push_bind(*format);
+        }
+        separated.push_unseparated(")");
+
+        let result = query_builder.build().execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Images whose thumbnail may still have a path-keyed name: `(id, path,
+    /// thumbnail_path)`. Content-keyed names contain `-v`, shared icons a `/`.
+    pub async fn get_legacy_thumbnails(&self, limit: i64) -> Result<Vec<(i64, String, String)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, path, thumbnail_path FROM images
+             WHERE thumbnail_path IS NOT NULL AND thumbnail_path NOT LIKE '%-v%' AND thumbnail_path NOT LIKE '%/%'
+             LIMIT ?",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Images of the given formats whose content-keyed thumbnail hasn't been
+    /// upgraded nor failed to: `(id, path, thumbnail_path)`.
+    pub async fn get_thumbnails_to_upgrade(
+        &self,
+        formats: &[&str],
+        limit: i64,
+    ) -> Result<Vec<(i64, String, String)>, sqlx::Error> {
+        if formats.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
+            "SELECT id, path, thumbnail_path FROM images
+             WHERE thumbnail_path LIKE '%-v%' AND thumbnail_path NOT LIKE '%/%' AND thumbnail_path NOT LIKE '%-hq.webp'
+               AND thumbnail_path IS NOT thumbnail_upgrade_failed AND corrupt_suspected = 0
+               AND format IN (",
+        );
+        let mut separated = query_builder.separated(", ");
+        for format in formats {
+            separated.push_bind(*format);
+        }
+        separated.push_unseparated(") ORDER BY id LIMIT ");
+        query_builder.push_bind(limit);
+        query_builder.build_query_as().fetch_all(&self.pool).await
+    }
+
+    /// Swaps the thumbnail `from` of an image for `to`, unless it got another
+    /// one meanwhile. Returns whether it was swapped.
+    pub async fn replace_thumbnail_path(&self, image_id: i64, from: &str, to: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE images SET thumbnail_path = ? WHERE id = ? AND thumbnail_path = ?")
+            .bind(to)
+            .bind(image_id)
+            .bind(from)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Remembers that the upgrade of `thumbnail` failed.
+    pub async fn mark_thumbnail_upgrade_failed(&self, image_id: i64, thumbnail: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE images SET thumbnail_upgrade_failed = ? WHERE id = ?")
+            .bind(thumbnail)
+            .bind(image_id)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
+    /// Whether any image still uses the thumbnail `filename`.
+    pub async fn is_thumbnail_used(&self, filename: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM images WHERE thumbnail_path = ?)")
+            .bind(filename)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Takes up to `limit` thumbnails an image stopped using and returns
+    /// those no image uses anymore, for their file to be deleted. Names a
+    /// copy still uses are forgotten; they come back once it drops them too.
+    pub async fn take_stale_thumbnails(&self, limit: i64) -> Result<Vec<String>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let names: Vec<String> = sqlx::query_scalar("SELECT filename FROM stale_thumbnails LIMIT ?")
+            .bind(limit)
+            .fetch_all(&mut *tx)
+            .await?;
+        let mut unused = Vec::new();
+        for name in names {
+            sqlx::query("DELETE FROM stale_thumbnails WHERE filename = ?")
+                .bind(&name)
+                .execute(&mut *tx)
+                .await?;
+            let used: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM images WHERE thumbnail_path = ?)")
+                .bind(&name)
+                .fetch_one(&mut *tx)
+                .await?;
+            if !used {
+                unused.push(name);
+            }
+        }
+        tx.commit().await?;
+        Ok(unused)
+    }
+
+    /// Clears the thumbnail path, effectively flagging it for regeneration.
+    ///
+    /// An explicit regeneration request also lifts the corrupt flag and the
+    /// retry counter, giving the file a fresh chance.
+    pub async fn clear_thumbnail_path(&self, image_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE images SET
+                thumbnail_path = NULL,
+                thumbnail_attempts = 0,
+                corrupt_suspected = 0,
+                corrupt_detail = NULL,
+                corrupt_detected_at = NULL
+             WHERE id = ?"
+        )
+        .bind(image_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
     /// Saves or updates a single image record.
     ///
     /// Returns `(id, old_folder_id_if_moved, was_newly_inserted)`.
@@ -99,7 +634,7 @@ impl Db {
         img: &crate::db::models::ImageMetadata,
     ) -> Result<(i64, Option<i64>, bool), sqlx::Error> {
         let mut conn = self.pool.acquire().await?;
-        self.save_image_internal(&mut *conn, folder_id, img).await
+        self.save_image_internal(&mut *conn, folder_id, img, &mut crate::paths::FolderListings::default()).await
     }
 
     /// Batch saves multiple image records within a transaction.
@@ -108,8 +643,10 @@ impl Db {
         items: Vec<(i64, crate::db::models::ImageMetadata)>,
     ) -> Result<(), sqlx::Error> {
         let mut tx = self.pool.begin().await?;
+        // Folders of moved-file candidates are read once for the batch
+        let mut listings = crate::paths::FolderListings::default();
         for (folder_id, img) in items {
-            if let Err(e) = self.save_image_internal(&mut *tx, folder_id, &img).await {
+            if let Err(e) = self.save_image_internal(&mut *tx, folder_id, &img, &mut listings).await {
                 eprintln!("Failed to save image in batch: {}", e);
             }
         }
@@ -123,6 +660,7 @@ impl Db {
         conn: &mut sqlx::SqliteConnection,
         folder_id: i64,
         img: &crate::db::models::ImageMetadata,
+        listings: &mut crate::paths::FolderListings,
     ) -> Result<(i64, Option<i64>, bool), sqlx::Error> {
         // 1. Check if path already exists
         let existing: Option<(i64, i64)> = sqlx::query_as("SELECT id, folder_id FROM images WHERE path = ?")
@@ -131,6 +669,21 @@ impl Db {
             .await?;
 
         if let Some((id, old_fid)) = existing {
+            // A changed file deserves another decode attempt, a fresh probe and
+            // a new thumbnail (its content key, hence its thumbnail name, changed)
+            sqlx::query(
+                "UPDATE images SET
+                    corrupt_suspected = 0, corrupt_detail = NULL, corrupt_detected_at = NULL, thumbnail_attempts = 0,
+                    media_probed_at = NULL, thumbnail_path = NULL, capture_checked_at = NULL,
+                    content_hash = NULL, content_hashed_at = NULL
+                 WHERE id = ? AND (size != ? OR modified_at != ?)"
+            )
+            .bind(id)
+            .bind(img.size)
+            .bind(img.modified_at)
+            .execute(&mut *conn)
+            .await?;
+
             sqlx::query!(
                 "UPDATE images SET
                     folder_id = ?, filename = ?, width = ?, height = ?, size = ?, format = ?, modified_at = ?
@@ -140,6 +693,9 @@ impl Db {
             .execute(&mut *conn)
             .await?;
 
+            super::keywords::refresh_keywords(&mut *conn, id, &img.filename).await?;
+            super::collation::refresh_sort_key(&mut *conn, id, &img.filename).await?;
+
             let old_fid_if_changed = if old_fid != folder_id { Some(old_fid) } else { None };
             return Ok((id, old_fid_if_changed, false));
         }
@@ -154,7 +710,8 @@ impl Db {
         .await?;
 
         for (id, old_fid, old_path) in candidates {
-            if !std::path::Path::new(&old_path).exists() {
+            // Spelled exactly: after a case-only rename the old spelling still opens the file
+            if !listings.exists_with_case(&crate::paths::from_db(&old_path)) {
                 sqlx::query!(
                     "UPDATE images SET
                         path = ?, folder_id = ?, filename = ?, format = ?, modified_at = ?
@@ -163,6 +720,8 @@ impl Db {
                 )
                 .execute(&mut *conn)
                 .await?;
+                super::keywords::refresh_keywords(&mut *conn, id, &img.filename).await?;
+                super::collation::refresh_sort_key(&mut *conn, id, &img.filename).await?;
                 return Ok((id, Some(old_fid), false));
             }
         }
@@ -181,10 +740,18 @@ impl Db {
                 modified_at = excluded.modified_at",
             folder_id, img.path, img.filename, img.width, img.height, img.size, img.format, img.created_at, img.modified_at
         )
-        .execute(conn)
+        .execute(&mut *conn)
         .await?;
+        let id = res.last_insert_rowid();
+        super::keywords::refresh_keywords(&mut *conn, id, &img.filename).await?;
+        super::collation::refresh_sort_key(&mut *conn, id, &img.filename).await?;
+
+        // New files under a folder with default tags receive them right away
+        super::folders::apply_folder_default_tags(conn, id).await?;
+        // A file back where a missing one was gets its tags, rating and notes back
+        super::trash::reclaim_missing(conn, id, &img.path, img.size).await?;
 
-        Ok((res.last_insert_rowid(), None, true))
+        Ok((id, None, true))
     }
 
     /// Retrieve context (image ID, folder ID, tags) for an image.
@@ -253,7 +820,7 @@ impl Db {
         Ok(map)
     }
 
-    /// Deletes an image record and returns its metadata context.
+    /// Moves an image to the trash and returns its metadata context.
     pub async fn delete_image_by_path_returning_context(
         &self,
         path: &str
@@ -261,9 +828,7 @@ impl Db {
         let context = self.get_image_context(path).await?;
 
         if let Some((image_id, _, _)) = context {
-            sqlx::query!("DELETE FROM images WHERE id = ?", image_id)
-                .execute(&self.pool)
-                .await?;
+            self.trash_image(image_id, super::trash::REASON_MISSING, None).await?;
         }
 
         Ok(context)
@@ -286,12 +851,14 @@ impl Db {
 
         if let Some((id, old_folder_id, w, h, s, f, c_at, _m_at, thumb, rating, notes)) = row {
             let now = chrono::Utc::now().to_rfc3339();
+            let mut conn = self.pool.acquire().await?;
             sqlx::query!(
                 "UPDATE images SET path = ?, filename = ?, folder_id = ?, modified_at = ? WHERE id = ?",
                 new_path, new_filename, new_folder_id, now, id
             )
-            .execute(&self.pool)
+            .execute(&mut *conn)
             .await?;
+            super::collation::refresh_sort_key(&mut conn, id, new_filename).await?;
 
             let created_dt = chrono::DateTime::parse_from_rfc3339(&c_at).map(|dt| dt.with_timezone(&chrono::Utc)).unwrap_or_else(|_| chrono::Utc::now());
             let modified_dt = chrono::Utc::now();
@@ -310,9 +877,36 @@ impl Db {
                 notes,
                 format: f,
                 added_at: None,
+                playback_position: None,
+                playback_completed: false,
+                sequence_id: None,
+                latitude: None,
+                longitude: None,
+                duration: None,
+                frame_count: None,
+                loops: None,
+                captured_at: None,
+                capture_offset: None,
             }, old_folder_id)))
         } else {
             Ok(None)
         }
     }
 }
+
+/// Restricts an images query, already holding a `WHERE` clause, to a folder
+/// and, with `recursive`, its subfolders. `None` keeps the whole library.
+fn push_folder_scope(query_builder: &mut sqlx::QueryBuilder<sqlx::Sqlite>, folder_id: Option<i64>, recursive: bool) {
+    match (folder_id, recursive) {
+        (Some(fid), true) => {
+            query_builder.push(" AND folder_id IN (WITH RECURSIVE sub(id) AS (SELECT ");
+            query_builder.push_bind(fid);
+            query_builder.push(" UNION ALL SELECT f.id FROM folders f JOIN sub ON f.parent_id = sub.id) SELECT id FROM sub)");
+        }
+        (Some(fid), false) => {
+            query_builder.push(" AND folder_id = ");
+            query_builder.push_bind(fid);
+        }
+        (None, _) => {}
+    }
+}
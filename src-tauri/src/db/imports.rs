@@ -0,0 +1,197 @@
+//! Metadata queued by importers until the indexer creates the images.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::db::models::ImportedMetadata;
+use super::Db;
+
+/// What applying queued metadata changed for one importer.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppliedImport {
+    /// Indexed files whose queued metadata was applied.
+    pub files: usize,
+    /// Files whose rating was raised.
+    pub ratings: usize,
+    /// Files whose empty notes were filled.
+    pub notes: usize,
+    /// Files whose creation time was replaced by the capture time.
+    pub taken_times: usize,
+    /// Files that got a location, having none.
+    pub locations: usize,
+    /// Files that got at least one new tag.
+    pub tagged: usize,
+}
+
+/// Id of the tag called `name`, created (under `parent_id`) if missing.
+///
+/// Tag names are unique library-wide, so an existing tag is reused wherever
+/// it sits in the hierarchy.
+pub(crate) async fn ensure_tag(
+    conn: &mut sqlx::SqliteConnection,
+    name: &str,
+    parent_id: Option<i64>,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query("INSERT OR IGNORE INTO tags (name, parent_id) VALUES (?, ?)")
+        .bind(name)
+        .bind(parent_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query_scalar("SELECT id FROM tags WHERE name = ?")
+        .bind(name)
+        .fetch_one(&mut *conn)
+        .await
+}
+
+impl Db {
+    /// Queues imported metadata. A later import of the same path replaces it.
+    pub async fn queue_import_metadata(&self, source: &str, entries: &[ImportedMetadata]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for entry in entries {
+            let tags = serde_json::to_string(&entry.tags).unwrap_or_else(|_| "[]".to_string());
+            sqlx::query(
+                "INSERT OR REPLACE INTO pending_import_metadata (path, source, rating, notes, taken_at, tags, latitude, longitude)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&entry.path)
+            .bind(source)
+            .bind(entry.rating)
+            .bind(&entry.notes)
+            .bind(entry.taken_at)
+            .bind(tags)
+            .bind(entry.latitude)
+            .bind(entry.longitude)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Applies the queued metadata of every file that is indexed by now, and
+    /// drops it from the queue. Returns what changed, per importer.
+    ///
+    /// Fields the library already had (a higher rating, notes, a location)
+    /// are kept and not counted as applied.
+    pub async fn apply_pending_import_metadata(&self) -> Result<HashMap<String, AppliedImport>, sqlx::Error> {
+        let rows: Vec<(String, String, i64, Option<i32>, Option<String>, Option<DateTime<Utc>>, String, Option<f64>, Option<f64>)> = sqlx::query_as(
+            "SELECT p.path, p.source, i.id, p.rating, p.notes, p.taken_at, p.tags, p.latitude, p.longitude
+             FROM pending_import_metadata p JOIN images i ON i.path = p.path"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let mut applied: HashMap<String, AppliedImport> = HashMap::new();
+        if rows.is_empty() {
+            return Ok(applied);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let mut tag_ids: HashMap<(String, String), i64> = HashMap::new();
+
+        for (path, source, image_id, rating, notes, taken_at, tags, latitude, longitude) in &rows {
+            let counts = applied.entry(source.clone()).or_default();
+            counts.files += 1;
+
+            if let Some(rating) = rating {
+                let res = sqlx::query("UPDATE images SET rating = ? WHERE id = ? AND COALESCE(rating, 0) < ?")
+                    .bind(rating)
+                    .bind(image_id)
+                    .bind(rating)
+                    .execute(&mut *tx)
+                    .await?;
+                counts.ratings += (res.rows_affected() > 0) as usize;
+            }
+
+            if let Some(notes) = notes {
+                let res = sqlx::query("UPDATE images SET notes = ? WHERE id = ? AND (notes IS NULL OR notes = '')")
+                    .bind(notes)
+                    .bind(image_id)
+                    .execute(&mut *tx)
+                    .await?;
+                counts.notes += (res.rows_affected() > 0) as usize;
+            }
+
+            if let Some(taken_at) = taken_at {
+                let res = sqlx::query("UPDATE images SET created_at = ? WHERE id = ? AND created_at IS NOT ?")
+                    .bind(taken_at)
+                    .bind(image_id)
+                    .bind(taken_at)
+                    .execute(&mut *tx)
+                    .await?;
+                counts.taken_times += (res.rows_affected() > 0) as usize;
+            }
+
+            if let (Some(latitude), Some(longitude)) = (latitude, longitude) {
+                let res = sqlx::query("UPDATE images SET latitude = ?, longitude = ? WHERE id = ? AND latitude IS NULL")
+                    .bind(latitude)
+                    .bind(longitude)
+                    .bind(image_id)
+                    .execute(&mut *tx)
+                    .await?;
+                counts.locations += (res.rows_affected() > 0) as usize;
+            }
+
+            let tags: Vec<(String, String)> = serde_json::from_str(tags).unwrap_or_default();
+            let mut tagged = false;
+            for key in tags {
+                let tag_id = match tag_ids.get(&key) {
+                    Some(id) => *id,
+                    None => {
+                        let group_id = ensure_tag(&mut tx, &key.0, None).await?;
+                        let id = ensure_tag(&mut tx, &key.1, Some(group_id)).await?;
+                        tag_ids.insert(key, id);
+                        id
+                    }
+                };
+                let res = sqlx::query("INSERT OR IGNORE INTO image_tags (image_id, tag_id) VALUES (?, ?)")
+                    .bind(image_id)
+                    .bind(tag_id)
+                    .execute(&mut *tx)
+                    .await?;
+                tagged |= res.rows_affected() > 0;
+            }
+            counts.tagged += tagged as usize;
+
+            sqlx::query("DELETE FROM pending_import_metadata WHERE path = ?")
+                .bind(path)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(applied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_apply_counts_changed_fields() {
+        let library = crate::testkit::TestLibrary::open("imports-applied").await;
+        let db = &library.db;
+        library.seed_images(&["1.jpg", "2.jpg"]).await;
+        sqlx::query("UPDATE images SET notes = 'Mine' WHERE id = 2").execute(&db.pool).await.unwrap();
+
+        let entry = |path: &str| ImportedMetadata {
+            path: path.to_string(),
+            notes: Some("Beach".to_string()),
+            tags: vec![("People".to_string(), "Ana".to_string())],
+            ..Default::default()
+        };
+        db.queue_import_metadata("google_takeout", &[entry("/lib/1.jpg"), entry("/lib/2.jpg"), entry("/lib/3.jpg")])
+            .await
+            .unwrap();
+
+        let applied = db.apply_pending_import_metadata().await.unwrap();
+        // The note of image 2 is kept, and image 3 is not indexed yet
+        assert_eq!(
+            applied.get("google_takeout"),
+            Some(&AppliedImport { files: 2, notes: 1, tagged: 2, ..Default::default() })
+        );
+    }
+}
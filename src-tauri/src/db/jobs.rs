@@ -0,0 +1,204 @@
+//! Persistence of the background job queue (see `crate::jobs`).
+
+use crate::db::models::Job;
+use super::Db;
+
+pub const JOB_QUEUED: &str = "queued";
+pub const JOB_RUNNING: &str = "running";
+pub const JOB_COMPLETED: &str = "completed";
+pub const JOB_FAILED: &str = "failed";
+pub const JOB_CANCELLED: &str = "cancelled";
+
+const JOB_COLUMNS: &str = "id, kind, label, payload, priority, status, progress_done, progress_total, message, error, result, attempts, created_at, started_at, finished_at";
+
+impl Db {
+    /// Adds a job to the queue.
+    pub async fn enqueue_job(&self, kind: &str, label: &str, payload: &str, priority: i64) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query("INSERT INTO jobs (kind, label, payload, priority) VALUES (?, ?, ?, ?)")
+            .bind(kind)
+            .bind(label)
+            .bind(payload)
+            .bind(priority)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Records a job that is already running outside the queue, such as a scan.
+    pub async fn insert_running_job(&self, kind: &str, label: &str) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT INTO jobs (kind, label, status, attempts, started_at) VALUES (?, ?, 'running', 1, CURRENT_TIMESTAMP)"
+        )
+        .bind(kind)
+        .bind(label)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Marks the highest-priority queued job as running and returns it.
+    ///
+    /// A single statement, so concurrent runners never claim the same job.
+    pub async fn claim_next_job(&self) -> Result<Option<Job>, sqlx::Error> {
+        sqlx::query_as::<_, Job>(&format!(
+            "UPDATE jobs SET status = 'running', started_at = CURRENT_TIMESTAMP, finished_at = NULL, attempts = attempts + 1
+             WHERE id = (SELECT id FROM jobs WHERE status = 'queued' ORDER BY priority DESC, id LIMIT 1)
+             RETURNING {}",
+            JOB_COLUMNS
+        ))
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn update_job_progress(
+        &self,
+        id: i64,
+        done: i64,
+        total: Option<i64>,
+        message: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE jobs SET progress_done = ?, progress_total = ?, message = COALESCE(?, message) WHERE id = ?")
+            .bind(done)
+            .bind(total)
+            .bind(message)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Records the outcome of a running job.
+    pub async fn finish_job(
+        &self,
+        id: i64,
+        status: &str,
+        error: Option<&str>,
+        result: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE jobs SET status = ?, error = ?, result = ?, finished_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(status)
+            .bind(error)
+            .bind(result)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_job(&self, id: i64) -> Result<Option<Job>, sqlx::Error> {
+        sqlx::query_as::<_, Job>(&format!("SELECT {} FROM jobs WHERE id = ?", JOB_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Lists jobs, newest first, optionally only those with `status`.
+    pub async fn list_jobs(&self, status: Option<&str>, limit: i64) -> Result<Vec<Job>, sqlx::Error> {
+        sqlx::query_as::<_, Job>(&format!(
+            "SELECT {} FROM jobs WHERE (? IS NULL OR status = ?) ORDER BY id DESC LIMIT ?",
+            JOB_COLUMNS
+        ))
+        .bind(status)
+        .bind(status)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Whether a job of `kind` with `label` is queued or running.
+    pub async fn has_pending_job(&self, kind: &str, label: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM jobs WHERE kind = ? AND label = ? AND status IN (?, ?))")
+            .bind(kind)
+            .bind(label)
+            .bind(JOB_QUEUED)
+            .bind(JOB_RUNNING)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Cancels a job that hasn't started yet. Returns `false` if it was not queued.
+    pub async fn cancel_queued_job(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE jobs SET status = 'cancelled', finished_at = CURRENT_TIMESTAMP WHERE id = ? AND status = 'queued'"
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Puts a failed or cancelled job back in the queue. Returns `false` if
+    /// the job is not in one of those states.
+    pub async fn requeue_job(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE jobs SET status = 'queued', error = NULL, result = NULL, message = NULL, progress_done = 0,
+                             progress_total = NULL, started_at = NULL, finished_at = NULL
+             WHERE id = ? AND status IN ('failed', 'cancelled')"
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Deletes finished jobs (completed, failed or cancelled) that ended more
+    /// than `days` days ago. Returns the number deleted.
+    pub async fn purge_finished_jobs(&self, days: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "DELETE FROM jobs
+             WHERE status IN (?, ?, ?) AND finished_at < datetime('now', '-' || ? || ' days')"
+        )
+        .bind(JOB_COMPLETED)
+        .bind(JOB_FAILED)
+        .bind(JOB_CANCELLED)
+        .bind(days)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Recovers jobs left running by a previous session: those with a handler
+    /// (`runnable_kinds`) are queued again, the others are marked as failed.
+    pub async fn recover_interrupted_jobs(&self, runnable_kinds: &[&str]) -> Result<u64, sqlx::Error> {
+        let kinds = serde_json::to_string(runnable_kinds).unwrap_or_else(|_| "[]".to_string());
+        let result = sqlx::query(
+            "UPDATE jobs
+             SET status = CASE WHEN kind IN (SELECT value FROM json_each(?)) THEN 'queued' ELSE 'failed' END,
+                 error = CASE WHEN kind IN (SELECT value FROM json_each(?)) THEN NULL ELSE 'Interrupted by shutdown' END,
+                 finished_at = CASE WHEN kind IN (SELECT value FROM json_each(?)) THEN NULL ELSE CURRENT_TIMESTAMP END
+             WHERE status = 'running'"
+        )
+        .bind(&kinds)
+        .bind(&kinds)
+        .bind(&kinds)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_purge_finished_jobs() {
+        let library = crate::testkit::TestLibrary::open("purge-jobs").await;
+        let db = &library.db;
+        let old = db.enqueue_job("transcode", "old", "{}", 0).await.unwrap();
+        let recent = db.enqueue_job("transcode", "recent", "{}", 0).await.unwrap();
+        let queued = db.enqueue_job("transcode", "queued", "{}", 0).await.unwrap();
+        db.finish_job(old, JOB_COMPLETED, None, None).await.unwrap();
+        db.finish_job(recent, JOB_FAILED, Some("boom"), None).await.unwrap();
+        sqlx::query("UPDATE jobs SET finished_at = datetime('now', '-40 days') WHERE id = ?")
+            .bind(old)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        assert_eq!(db.purge_finished_jobs(30).await.unwrap(), 1);
+        assert!(db.get_job(old).await.unwrap().is_none());
+        assert!(db.get_job(recent).await.unwrap().is_some());
+        assert!(db.get_job(queued).await.unwrap().is_some());
+    }
+}
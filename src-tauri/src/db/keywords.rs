@@ -0,0 +1,113 @@
+//! Searchable words of filenames.
+//!
+//! `images_fts` indexes filenames as trigrams, which finds substrings but not
+//! words glued together by the naming conventions of a studio:
+//! `ClientX_Hero_Banner_v03_final.psd` doesn't contain "hero banner". Each
+//! filename is also split into lowercase words, stored in
+//! `images.filename_keywords` and indexed word by word in
+//! `images_keywords_fts`, which the quick search consults too.
+
+use sqlx::SqliteConnection;
+
+use super::Db;
+
+/// Splits a filename, without its extension, into lowercase words.
+///
+/// Words break on any character that is not a letter or a digit, and on
+/// camelCase boundaries: `heroBanner` gives `hero banner` and `HTMLExport`
+/// gives `html export`. Letters and digits stay together (`v03`).
+pub fn filename_keywords(filename: &str) -> String {
+    let stem = match filename.rsplit_once('.') {
+        Some((stem, _extension)) if !stem.is_empty() => stem,
+        _ => filename,
+    };
+    keyword_tokens(stem).join(" ")
+}
+
+/// Splits `text` into lowercase words, see [`filename_keywords`].
+pub fn keyword_tokens(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for part in text.split(|c: char| !c.is_alphanumeric()).filter(|p| !p.is_empty()) {
+        let chars: Vec<char> = part.chars().collect();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let (prev, current) = (chars[i - 1], chars[i]);
+            let next_is_lower = chars.get(i + 1).is_some_and(|c| c.is_lowercase());
+            // `xH` starts a word, and so does the `P` of `HTMLParser`
+            let boundary = current.is_uppercase()
+                && (prev.is_lowercase() || (prev.is_uppercase() && next_is_lower));
+            if boundary {
+                tokens.push(chars[start..i].iter().collect::<String>().to_lowercase());
+                start = i;
+            }
+        }
+        tokens.push(chars[start..].iter().collect::<String>().to_lowercase());
+    }
+    tokens
+}
+
+/// Builds an FTS5 query matching rows holding every word of `search`, each
+/// as a prefix. Returns `None` when the search has no words.
+pub fn keyword_match_query(search: &str) -> Option<String> {
+    let tokens = keyword_tokens(search);
+    if tokens.is_empty() {
+        return None;
+    }
+    Some(tokens.iter().map(|t| format!("\"{}\"*", t.replace('"', "\"\""))).collect::<Vec<_>>().join(" "))
+}
+
+/// Stores the keywords of an image's current filename on `conn`, so files
+/// saved in a batch transaction are searchable by word right away.
+pub(crate) async fn refresh_keywords(conn: &mut SqliteConnection, image_id: i64, filename: &str) -> Result<(), sqlx::Error> {
+    let keywords = filename_keywords(filename);
+    sqlx::query("UPDATE images SET filename_keywords = ? WHERE id = ? AND filename_keywords IS NOT ?")
+        .bind(&keywords)
+        .bind(image_id)
+        .bind(&keywords)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+impl Db {
+    /// Images whose keywords are missing, new or renamed: `(id, filename)`.
+    pub async fn get_images_needing_keywords(&self, limit: i64) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as("SELECT id, filename FROM images WHERE filename_keywords IS NULL LIMIT ?")
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Computes and stores the keywords of the given images in one transaction.
+    pub async fn set_filename_keywords(&self, images: &[(i64, String)]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (id, filename) in images {
+            refresh_keywords(&mut *tx, *id, filename).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filename_keywords() {
+        assert_eq!(filename_keywords("ClientX_Hero_Banner_v03_final.psd"), "client x hero banner v03 final");
+        assert_eq!(filename_keywords("heroBanner-2024 (copy).jpg"), "hero banner 2024 copy");
+        assert_eq!(filename_keywords("HTMLExport.png"), "html export");
+        assert_eq!(filename_keywords("IMG_0001.CR3"), "img 0001");
+        assert_eq!(filename_keywords("Café Olé.jpg"), "café olé");
+        assert_eq!(filename_keywords("README"), "readme");
+        assert_eq!(filename_keywords("___.jpg"), "");
+    }
+
+    #[test]
+    fn test_keyword_match_query() {
+        assert_eq!(keyword_match_query("hero banner").as_deref(), Some("\"hero\"* \"banner\"*"));
+        assert_eq!(keyword_match_query("heroBanner").as_deref(), Some("\"hero\"* \"banner\"*"));
+        assert_eq!(keyword_match_query(" -- "), None);
+    }
+}
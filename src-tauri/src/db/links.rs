@@ -0,0 +1,147 @@
+//! External links attached to images and folders.
+
+use crate::db::models::AssetLink;
+use super::Db;
+use std::collections::HashMap;
+
+/// A link with the image it applies to, directly or through a folder.
+#[derive(sqlx::FromRow)]
+struct OwnedLink {
+    owner: i64,
+    #[sqlx(flatten)]
+    link: AssetLink,
+}
+
+const LINK_COLUMNS: &str = "SELECT id, image_id, folder_id, label, url, created_at FROM links";
+
+impl Db {
+    /// Attaches a link to an image or a folder and returns its id.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the target doesn't exist, if both or neither targets
+    /// are given, or if the database fails.
+    pub async fn add_link(
+        &self,
+        image_id: Option<i64>,
+        folder_id: Option<i64>,
+        label: &str,
+        url: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let res = sqlx::query("INSERT INTO links (image_id, folder_id, label, url) VALUES (?, ?, ?, ?)")
+            .bind(image_id)
+            .bind(folder_id)
+            .bind(label)
+            .bind(url)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    /// Updates the label and URL of a link.
+    ///
+    /// Returns `false` if no link has this id.
+    pub async fn update_link(&self, id: i64, label: &str, url: &str) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query("UPDATE links SET label = ?, url = ? WHERE id = ?")
+            .bind(label)
+            .bind(url)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Deletes a link.
+    pub async fn delete_link(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM links WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Retrieves the links attached directly to a folder.
+    pub async fn get_folder_links(&self, folder_id: i64) -> Result<Vec<AssetLink>, sqlx::Error> {
+        sqlx::query_as::<_, AssetLink>(&format!("{} WHERE folder_id = ? ORDER BY created_at, id", LINK_COLUMNS))
+            .bind(folder_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Retrieves the links of an image, followed by those inherited from its
+    /// folder and every ancestor folder.
+    pub async fn get_image_links(&self, image_id: i64) -> Result<Vec<AssetLink>, sqlx::Error> {
+        sqlx::query_as::<_, AssetLink>(
+            "WITH RECURSIVE ancestors(id) AS (
+                SELECT folder_id FROM images WHERE id = ?
+                UNION ALL
+                SELECT f.parent_id FROM folders f JOIN ancestors a ON f.id = a.id WHERE f.parent_id IS NOT NULL
+             )
+             SELECT id, image_id, folder_id, label, url, created_at FROM links
+             WHERE image_id = ? OR folder_id IN ancestors
+             ORDER BY (image_id IS NULL), created_at, id"
+        )
+        .bind(image_id)
+        .bind(image_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Retrieves the links of several images, keyed by image id, each as
+    /// `get_image_links` returns them: the image's own links first, then
+    /// those inherited from its folders.
+    pub async fn get_links_by_image(&self, image_ids: &[i64]) -> Result<HashMap<i64, Vec<AssetLink>>, sqlx::Error> {
+        let ids_json = serde_json::to_string(image_ids).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+        let rows: Vec<OwnedLink> = sqlx::query_as(
+            "WITH RECURSIVE sel(id) AS (SELECT value FROM json_each(?)),
+             ancestors(image_id, folder_id) AS (
+                SELECT id, folder_id FROM images WHERE id IN (SELECT id FROM sel)
+                UNION ALL
+                SELECT a.image_id, f.parent_id FROM folders f JOIN ancestors a ON f.id = a.folder_id WHERE f.parent_id IS NOT NULL
+             )
+             SELECT l.image_id AS owner, l.id, l.image_id, l.folder_id, l.label, l.url, l.created_at, 0 AS inherited
+             FROM links l WHERE l.image_id IN (SELECT id FROM sel)
+             UNION ALL
+             SELECT a.image_id, l.id, l.image_id, l.folder_id, l.label, l.url, l.created_at, 1
+             FROM ancestors a JOIN links l ON l.folder_id = a.folder_id
+             ORDER BY owner, inherited, created_at, id"
+        )
+        .bind(ids_json)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut links: HashMap<i64, Vec<AssetLink>> = HashMap::new();
+        for row in rows {
+            links.entry(row.owner).or_default().push(row.link);
+        }
+        Ok(links)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[tokio::test]
+    async fn test_links_by_image_match_single_lookups() {
+        let library = crate::testkit::TestLibrary::open("links-batch").await;
+        let db = &library.db;
+        library.seed_images(&["1.png", "2.png", "3.png"]).await;
+        sqlx::query("INSERT INTO folders (id, path, name, parent_id) VALUES (2, '/lib/a', 'a', 1)")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE images SET folder_id = 2 WHERE id IN (1, 3)").execute(&db.pool).await.unwrap();
+        db.add_link(None, Some(1), "Root", "https://root").await.unwrap();
+        db.add_link(None, Some(2), "Folder", "https://folder").await.unwrap();
+        db.add_link(Some(1), None, "Own", "https://own").await.unwrap();
+
+        let batch = db.get_links_by_image(&[1, 2, 3]).await.unwrap();
+        for id in [1, 2, 3] {
+            let single: Vec<i64> = db.get_image_links(id).await.unwrap().iter().map(|l| l.id).collect();
+            let batched: Vec<i64> = batch.get(&id).map(|links| links.iter().map(|l| l.id).collect()).unwrap_or_default();
+            assert_eq!(batched, single, "links of image {}", id);
+        }
+        assert_eq!(batch[&1].len(), 3);
+        assert_eq!(batch[&1][0].label, "Own");
+        assert_eq!(batch[&2].len(), 1);
+    }
+}
@@ -0,0 +1,257 @@
+//! Library-wide find-and-replace used by the admin tools.
+//!
+//! Both operations compute the changes in Rust with reads only, so a dry run
+//! never writes, and apply them in a single transaction.
+
+use std::collections::HashSet;
+
+use regex::Regex;
+use sqlx::Row;
+
+use crate::db::models::{NotesReplacement, PathPrefixReplacement, TextChange};
+use super::Db;
+
+/// Number of before/after pairs returned for preview.
+const SAMPLE_SIZE: usize = 20;
+
+/// Strips trailing separators so `D:\` and `D:` or `/mnt/a/` and `/mnt/a` match the same paths.
+pub fn normalize_prefix(prefix: &str) -> &str {
+    prefix.trim_end_matches(['/', '\\'])
+}
+
+/// Replaces `old` at the start of `path`, only on a path component boundary
+/// (`/photos` matches `/photos/a.jpg` but not `/photos2/a.jpg`).
+///
+/// Both prefixes are expected to be normalized.
+pub fn replace_prefix(path: &str, old: &str, new: &str) -> Option<String> {
+    let rest = path.strip_prefix(old)?;
+    if rest.is_empty() || rest.starts_with(['/', '\\']) {
+        Some(format!("{}{}", new, rest))
+    } else {
+        None
+    }
+}
+
+/// Columns holding paths outside `folders` and `images`, rewritten along
+/// with them. A rewrite that would collide there keeps the old path, as
+/// with the verbatim prefix migration.
+const OTHER_PATH_COLUMNS: &[(&str, &str)] = &[
+    ("derivatives", "path"),
+    ("pending_import_metadata", "path"),
+    ("trash", "path"),
+    ("trash", "stored_path"),
+    ("sync_targets", "destination"),
+];
+
+/// Rows of `table` whose `column` starts with `old`, keyed by rowid, with
+/// their new path.
+async fn path_changes(
+    conn: &mut sqlx::SqliteConnection,
+    table: &str,
+    column: &str,
+    old: &str,
+    new: &str,
+) -> Result<Vec<TextChange>, sqlx::Error> {
+    // SUBSTR counts characters, not bytes
+    let rows = sqlx::query(&format!(
+        "SELECT rowid AS id, {column} AS path FROM {table} WHERE SUBSTR({column}, 1, ?) = ? ORDER BY {column}"
+    ))
+    .bind(old.chars().count() as i64)
+    .bind(old)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let path: String = row.get("path");
+            let after = replace_prefix(&path, old, new)?;
+            Some(TextChange { id: row.get("id"), before: path, after })
+        })
+        .collect())
+}
+
+/// First new path of `changes` already held by a row of `table` that isn't
+/// rewritten itself.
+async fn find_conflict(
+    conn: &mut sqlx::SqliteConnection,
+    table: &str,
+    changes: &[TextChange],
+) -> Result<Option<String>, sqlx::Error> {
+    let after: Vec<&str> = changes.iter().map(|change| change.after.as_str()).collect();
+    let after_json = serde_json::to_string(&after).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+    let taken: Vec<String> = sqlx::query_scalar(&format!(
+        "SELECT path FROM {table} WHERE path IN (SELECT value FROM json_each(?))"
+    ))
+    .bind(after_json)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let moving: HashSet<&str> = changes.iter().map(|change| change.before.as_str()).collect();
+    Ok(taken.into_iter().find(|path| !moving.contains(path.as_str())))
+}
+
+impl Db {
+    /// Rewrites the start of every stored path, e.g. after a drive letter or
+    /// mount point changed: folders, images and the other tables holding
+    /// paths.
+    ///
+    /// The changes are planned with reads only; a dry run, or a plan where a
+    /// new folder or image path is already taken (see
+    /// `PathPrefixReplacement::conflict`), writes nothing. Otherwise they are
+    /// written in one transaction.
+    ///
+    /// Prefixes must already be normalized with [`normalize_prefix`].
+    pub async fn replace_path_prefix(&self, old: &str, new: &str, dry_run: bool) -> Result<PathPrefixReplacement, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let folders = path_changes(&mut tx, "folders", "path", old, new).await?;
+        let images = path_changes(&mut tx, "images", "path", old, new).await?;
+        let mut others = Vec::with_capacity(OTHER_PATH_COLUMNS.len());
+        for (table, column) in OTHER_PATH_COLUMNS {
+            others.push((*table, *column, path_changes(&mut tx, table, column, old, new).await?));
+        }
+
+        let conflict = match find_conflict(&mut tx, "folders", &folders).await? {
+            Some(path) => Some(path),
+            None => find_conflict(&mut tx, "images", &images).await?,
+        };
+        let applied = !dry_run && conflict.is_none();
+
+        if applied {
+            for change in &folders {
+                // A folder at exactly the old prefix is renamed after its new last component
+                let name = (change.before == old)
+                    .then(|| new.rsplit(['/', '\\']).next().unwrap_or(new).to_string());
+                sqlx::query("UPDATE folders SET path = ?, name = COALESCE(?, name) WHERE id = ?")
+                    .bind(&change.after)
+                    .bind(name)
+                    .bind(change.id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            for change in &images {
+                sqlx::query("UPDATE images SET path = ? WHERE id = ?")
+                    .bind(&change.after)
+                    .bind(change.id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            for (table, column, changes) in &others {
+                let update = format!("UPDATE OR IGNORE {table} SET {column} = ? WHERE rowid = ?");
+                for change in changes {
+                    sqlx::query(&update)
+                        .bind(&change.after)
+                        .bind(change.id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+            tx.commit().await?;
+        }
+
+        Ok(PathPrefixReplacement {
+            folders: folders.len() as i64,
+            images: images.len() as i64,
+            other: others.iter().map(|(_, _, changes)| changes.len() as i64).sum(),
+            samples: images.into_iter().take(SAMPLE_SIZE).collect(),
+            conflict,
+            applied,
+        })
+    }
+
+    /// Replaces every match of `pattern` in image notes. `replacement` may
+    /// reference capture groups as `$1` or `${name}`.
+    pub async fn find_replace_notes(&self, pattern: &Regex, replacement: &str, dry_run: bool) -> Result<NotesReplacement, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let rows = sqlx::query("SELECT id, notes FROM images WHERE notes IS NOT NULL AND notes != '' ORDER BY id")
+            .fetch_all(&mut *tx)
+            .await?;
+
+        let changes: Vec<TextChange> = rows
+            .into_iter()
+            .filter_map(|row| {
+                let notes: String = row.get("notes");
+                let after = pattern.replace_all(&notes, replacement);
+                (after != notes).then(|| TextChange { id: row.get("id"), after: after.into_owned(), before: notes })
+            })
+            .collect();
+
+        if !dry_run {
+            for change in &changes {
+                sqlx::query("UPDATE images SET notes = ? WHERE id = ?")
+                    .bind(&change.after)
+                    .bind(change.id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await?;
+        }
+
+        Ok(NotesReplacement {
+            affected: changes.len() as i64,
+            samples: changes.into_iter().take(SAMPLE_SIZE).collect(),
+            applied: !dry_run,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_prefix() {
+        assert_eq!(replace_prefix("D:\\Photos\\a.jpg", "D:", "E:").as_deref(), Some("E:\\Photos\\a.jpg"));
+        assert_eq!(replace_prefix("/mnt/a", "/mnt/a", "/Volumes/A").as_deref(), Some("/Volumes/A"));
+        assert_eq!(replace_prefix("/mnt/a/b.png", "/mnt/a", "/Volumes/A").as_deref(), Some("/Volumes/A/b.png"));
+        // Not on a component boundary
+        assert_eq!(replace_prefix("/mnt/ab/c.png", "/mnt/a", "/x"), None);
+        assert_eq!(replace_prefix("/other/a.png", "/mnt/a", "/x"), None);
+    }
+
+    #[test]
+    fn test_normalize_prefix() {
+        assert_eq!(normalize_prefix("D:\\"), "D:");
+        assert_eq!(normalize_prefix("/mnt/a//"), "/mnt/a");
+        assert_eq!(normalize_prefix("/"), "");
+    }
+
+    #[tokio::test]
+    async fn test_replace_path_prefix() {
+        let library = crate::testkit::TestLibrary::open("path-prefix").await;
+        let db = &library.db;
+        for statement in [
+            "INSERT INTO folders (id, path, name) VALUES (1, '/mnt/a', 'a')",
+            "INSERT INTO images (id, folder_id, path, filename, size, format, created_at, modified_at)
+             VALUES (1, 1, '/mnt/a/1.jpg', '1.jpg', 100, 'jpg', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')",
+            "INSERT INTO derivatives (image_id, kind, path) VALUES (1, 'export', '/mnt/a/exports/1.jpg')",
+            "INSERT INTO trash (image_id, path, filename, reason) VALUES (9, '/mnt/a/9.jpg', '9.jpg', 'missing')",
+        ] {
+            sqlx::query(statement).execute(&db.pool).await.unwrap();
+        }
+        let image_path = || async move {
+            sqlx::query_scalar::<_, String>("SELECT path FROM images WHERE id = 1").fetch_one(&db.pool).await.unwrap()
+        };
+
+        let preview = db.replace_path_prefix("/mnt/a", "/mnt/b", true).await.unwrap();
+        assert_eq!((preview.folders, preview.images, preview.other, preview.applied), (1, 1, 2, false));
+        assert_eq!(image_path().await, "/mnt/a/1.jpg", "a dry run writes nothing");
+
+        let applied = db.replace_path_prefix("/mnt/a", "/mnt/b", false).await.unwrap();
+        assert!(applied.applied);
+        assert_eq!(image_path().await, "/mnt/b/1.jpg");
+        let trashed: String = sqlx::query_scalar("SELECT path FROM trash").fetch_one(&db.pool).await.unwrap();
+        assert_eq!(trashed, "/mnt/b/9.jpg");
+        let derivative: String = sqlx::query_scalar("SELECT path FROM derivatives").fetch_one(&db.pool).await.unwrap();
+        assert_eq!(derivative, "/mnt/b/exports/1.jpg");
+
+        // Moving onto a location already in the library is refused
+        sqlx::query("INSERT INTO folders (id, path, name) VALUES (2, '/mnt/c', 'c')").execute(&db.pool).await.unwrap();
+        let refused = db.replace_path_prefix("/mnt/b", "/mnt/c", false).await.unwrap();
+        assert_eq!(refused.conflict.as_deref(), Some("/mnt/c"));
+        assert!(!refused.applied);
+        assert_eq!(image_path().await, "/mnt/b/1.jpg");
+    }
+}
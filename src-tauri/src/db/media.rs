@@ -0,0 +1,215 @@
+//! Persisted audio/video stream information.
+//!
+//! Probe results are written once per file by the media info worker so that
+//! searches on duration, codec, frame rate or bitrate don't need FFprobe.
+
+use crate::db::models::{PlaybackState, VideoChapter};
+use crate::media::animation::AnimationInfo;
+use crate::streaming::probe::VideoInfo;
+use super::Db;
+
+impl Db {
+    /// Retrieves audio/video files that have not been probed yet.
+    pub async fn get_media_needing_probe(
+        &self,
+        formats: &[&str],
+        limit: i32,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        if formats.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
+            "SELECT id, path FROM images WHERE media_probed_at IS NULL AND format IN ("
+        );
+        let mut separated = query_builder.separated(", ");
+        for format in formats {
+            separated.push_bind(*format);
+        }
+        separated.push_unseparated(") LIMIT ");
+        query_builder.push_bind(limit);
+
+        query_builder.build_query_as::<(i64, String)>().fetch_all(&self.pool).await
+    }
+
+    /// Stores probe results for a file, replacing any previously stored chapters.
+    ///
+    /// A thumbnail rendered before the file was known to have cover art is
+    /// queued again, to be rendered from the cover.
+    pub async fn update_media_info(&self, image_id: i64, info: &VideoInfo) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        if info.has_cover_art {
+            sqlx::query(
+                "UPDATE images SET thumbnail_path = NULL, thumbnail_attempts = 0
+                 WHERE id = ? AND has_cover_art = 0 AND thumbnail_path IS NOT NULL"
+            )
+            .bind(image_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(
+            "UPDATE images SET
+                duration = ?, video_codec = ?, audio_codec = ?, fps = ?, frame_count = ?, bitrate = ?, has_audio = ?,
+                has_cover_art = ?, chapters_probed = 1, media_probed_at = CURRENT_TIMESTAMP
+             WHERE id = ?"
+        )
+        .bind(info.duration_secs)
+        .bind(&info.video_codec)
+        .bind(&info.audio_codec)
+        .bind(info.fps)
+        .bind(info.frame_count)
+        .bind(info.bitrate)
+        .bind(info.has_audio)
+        .bind(info.has_cover_art)
+        .bind(image_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM video_chapters WHERE image_id = ?")
+            .bind(image_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for (index, chapter) in info.chapters.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO video_chapters (image_id, chapter_index, start_secs, end_secs, title)
+                 VALUES (?, ?, ?, ?, ?)"
+            )
+            .bind(image_id)
+            .bind(index as i64)
+            .bind(chapter.start_secs)
+            .bind(chapter.end_secs)
+            .bind(&chapter.title)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Stores the frames of a GIF or WebP file. Only animations get a
+    /// duration and frame rate, so stills don't pass for zero-length clips.
+    pub async fn update_animation_info(&self, image_id: i64, info: &AnimationInfo) -> Result<(), sqlx::Error> {
+        let animated = info.is_animated();
+        sqlx::query(
+            "UPDATE images SET duration = ?, fps = ?, frame_count = ?, loops = ?, media_probed_at = CURRENT_TIMESTAMP
+             WHERE id = ?"
+        )
+        .bind(animated.then_some(info.duration_secs))
+        .bind(info.fps())
+        .bind(info.frame_count as i64)
+        .bind(animated.then_some(info.loops))
+        .bind(image_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Whether the chapters of a media file were looked for. Files probed
+    /// before chapters were read haven't been.
+    pub async fn are_chapters_probed(&self, image_id: i64) -> Result<bool, sqlx::Error> {
+        let probed: Option<bool> = sqlx::query_scalar("SELECT chapters_probed FROM images WHERE id = ?")
+            .bind(image_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(probed.unwrap_or(true))
+    }
+
+    /// Ids among `ids` of files known to embed cover art.
+    pub async fn get_cover_art_ids(&self, ids: &[i64]) -> Result<Vec<i64>, sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> =
+            sqlx::QueryBuilder::new("SELECT id FROM images WHERE has_cover_art = 1 AND id IN (");
+        let mut separated = query_builder.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+        query_builder.build_query_scalar().fetch_all(&self.pool).await
+    }
+
+    /// Retrieves the chapter markers of a media file, in order.
+    pub async fn get_video_chapters(&self, image_id: i64) -> Result<Vec<VideoChapter>, sqlx::Error> {
+        sqlx::query_as::<_, VideoChapter>(
+            "SELECT chapter_index, start_secs, end_secs, title
+             FROM video_chapters WHERE image_id = ? ORDER BY chapter_index"
+        )
+        .bind(image_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Marks a file as probed even though FFprobe failed, so it isn't retried forever.
+    pub async fn mark_media_probe_failed(&self, image_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE images SET media_probed_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(image_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Probed `(video_codec, audio_codec)` of a media file, `None` until the
+    /// media info worker has probed it.
+    pub async fn get_media_codecs(&self, path: &str) -> Result<Option<(Option<String>, Option<String>)>, sqlx::Error> {
+        sqlx::query_as("SELECT video_codec, audio_codec FROM images WHERE path = ? AND media_probed_at IS NOT NULL")
+            .bind(path)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Probed duration of a media file, if known.
+    pub async fn get_media_duration(&self, image_id: i64) -> Result<Option<f64>, sqlx::Error> {
+        let duration: Option<Option<f64>> = sqlx::query_scalar("SELECT duration FROM images WHERE id = ?")
+            .bind(image_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(duration.flatten())
+    }
+
+    /// Saves the playback position of a media file.
+    pub async fn set_playback_position(
+        &self,
+        image_id: i64,
+        position: f64,
+        completed: bool,
+    ) -> Result<(), sqlx::Error> {
+        // Selected from `images` so an unknown id saves nothing, like an update would
+        sqlx::query(
+            "INSERT INTO playback_positions (image_id, position, completed, updated_at)
+             SELECT id, ?, ?, CURRENT_TIMESTAMP FROM images WHERE id = ?
+             ON CONFLICT(image_id) DO UPDATE SET
+                position = excluded.position, completed = excluded.completed, updated_at = excluded.updated_at"
+        )
+        .bind(position)
+        .bind(completed)
+        .bind(image_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Retrieves the saved playback position, `None` if the file was never played.
+    pub async fn get_playback_position(&self, image_id: i64) -> Result<Option<PlaybackState>, sqlx::Error> {
+        sqlx::query_as::<_, PlaybackState>(
+            "SELECT image_id, position, completed, updated_at FROM playback_positions WHERE image_id = ?"
+        )
+        .bind(image_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Forgets the playback position of a media file.
+    pub async fn clear_playback_position(&self, image_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM playback_positions WHERE image_id = ?")
+        .bind(image_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -10,6 +10,36 @@ pub mod tags;
 pub mod smart_folders;
 pub mod settings;
 pub mod search;
+pub mod media;
+pub mod review;
+pub mod annotations;
+pub mod links;
+pub mod sequences;
+pub mod versions;
+pub mod auto_collections;
+pub mod maintenance;
+pub mod playlists;
+pub mod jobs;
+pub mod operations;
+pub mod imports;
+pub mod sync;
+pub mod remote;
+pub mod content_keys;
+pub mod stacks;
+pub mod keywords;
+pub mod vacuum;
+pub mod exif;
+pub mod capture_times;
+pub mod transcode_stats;
+pub mod derivatives;
+pub mod duplicates;
+pub mod similar;
+pub mod sidecars;
+pub mod smart_folder_history;
+pub mod collation;
+pub mod collections;
+pub mod trash;
+pub mod fields;
 
 use sqlx::sqlite::SqlitePool;
 use std::path::PathBuf;
@@ -37,13 +67,21 @@ impl Db {
     ///
     /// Returns a `sqlx::Error` if the connection fails or if migrations fail to run.
     pub async fn new(path: PathBuf) -> AppResult<Self> {
-        use sqlx::sqlite::SqliteConnectOptions;
+        use sqlx::sqlite::{SqliteAutoVacuum, SqliteConnectOptions};
         use sqlx::Executor;
         use std::str::FromStr;
 
         let url = format!("sqlite:{}", path.to_string_lossy());
-        let options = SqliteConnectOptions::from_str(&url)?
-            .create_if_missing(true);
+        // Incremental auto-vacuum lets `db::vacuum` free space without a full
+        // VACUUM. `with_regexp` registers a Rust regex backed REGEXP function on every
+        // connection, which the `matches_regex` search operator relies on, and
+        // `collation::register` the `NATURAL` collation names sort by.
+        let options = collation::register(
+            SqliteConnectOptions::from_str(&url)?
+                .create_if_missing(true)
+                .auto_vacuum(SqliteAutoVacuum::Incremental)
+                .with_regexp(),
+        );
 
         let pool = SqlitePool::connect_with(options).await?;
 
@@ -63,18 +101,4 @@ impl Db {
     pub fn inner(&self) -> &SqlitePool {
         &self.pool
     }
-
-    /// Performs routine database maintenance missions.
-    ///
-    /// Runs `VACUUM` to reclaim space and `ANALYZE` to update query planner statistics.
-    ///
-    /// # Errors
-    ///
-    /// Returns `Err` if the maintenance queries fail.
-    pub async fn run_maintenance(&self) -> AppResult<()> {
-        println!("DEBUG: DB - Running Maintenance (VACUUM + ANALYZE)");
-        sqlx::query("VACUUM").execute(&self.pool).await?;
-        sqlx::query("ANALYZE").execute(&self.pool).await?;
-        Ok(())
-    }
 }
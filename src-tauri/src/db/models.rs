@@ -38,6 +38,35 @@ pub struct ImageMetadata {
     /// Time when the image was first indexed by Mundam.
     #[sqlx(default)]
     pub added_at: Option<DateTime<Utc>>,
+    /// Last playback position in seconds, for audio and video.
+    #[sqlx(default)]
+    pub playback_position: Option<f64>,
+    /// Whether the media was played to the end.
+    #[sqlx(default)]
+    pub playback_completed: bool,
+    /// Frame sequence this file belongs to, if it is part of one.
+    #[sqlx(default)]
+    pub sequence_id: Option<i64>,
+    /// Capture location in decimal degrees, if known.
+    #[sqlx(default)]
+    pub latitude: Option<f64>,
+    #[sqlx(default)]
+    pub longitude: Option<f64>,
+    /// Length in seconds of audio, video and animated images.
+    #[sqlx(default)]
+    pub duration: Option<f64>,
+    /// Frames of a video or an animated image.
+    #[sqlx(default)]
+    pub frame_count: Option<i64>,
+    /// Whether an animated image plays forever.
+    #[sqlx(default)]
+    pub loops: Option<bool>,
+    /// Capture time from the file's EXIF, in UTC (see `media::capture_time`).
+    #[sqlx(default)]
+    pub captured_at: Option<DateTime<Utc>>,
+    /// Offset the camera recorded with the capture time, in minutes east of UTC.
+    #[sqlx(default)]
+    pub capture_offset: Option<i32>,
 }
 
 /// A categorization tag that can be applied to images.
@@ -63,6 +92,38 @@ pub struct TagCount {
     pub count: i64,
 }
 
+/// Aggregate details of a multi-selection, for the inspector.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SelectionSummary {
+    /// Selected images that still exist.
+    pub count: i64,
+    /// Combined size in bytes.
+    pub total_size: i64,
+    /// Oldest and newest creation dates in the selection.
+    pub created_from: Option<DateTime<Utc>>,
+    pub created_to: Option<DateTime<Utc>>,
+    /// Number of images per star rating (0 = unrated).
+    pub rating_counts: Vec<RatingCount>,
+    /// Number of images per format, most common first.
+    pub format_counts: Vec<FormatCount>,
+    /// Number of selected images carrying each tag.
+    pub tag_counts: Vec<TagCount>,
+    /// Tags present on every selected image.
+    pub shared_tag_ids: Vec<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RatingCount {
+    pub rating: i64,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FormatCount {
+    pub format: String,
+    pub count: i64,
+}
+
 /// Count of images within a specific folder.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FolderCount {
@@ -97,3 +158,569 @@ pub struct SmartFolder {
     /// ISO-8601 creation timestamp.
     pub created_at: DateTime<Utc>,
 }
+
+/// An image whose decoding failed in a way that suggests a damaged file.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct CorruptFile {
+    /// Unique identifier for the image.
+    pub id: i64,
+    /// Absolute filesystem path to the file.
+    pub path: String,
+    /// Filename with extension.
+    pub filename: String,
+    /// Detected format.
+    pub format: Option<String>,
+    /// File size in bytes.
+    pub size: Option<i64>,
+    /// Last decoder error that triggered the flag.
+    pub corrupt_detail: Option<String>,
+    /// When the file was flagged.
+    pub corrupt_detected_at: Option<DateTime<Utc>>,
+    /// Number of thumbnail attempts made so far.
+    pub thumbnail_attempts: i64,
+}
+
+/// An image whose extension disagrees with the format found in its header.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ExtensionMismatch {
+    /// Unique identifier for the image.
+    pub id: i64,
+    /// Absolute filesystem path to the file.
+    pub path: String,
+    /// Filename with extension.
+    pub filename: String,
+    /// Format implied by the extension.
+    pub format: String,
+    /// Canonical extension of the format found in the file header.
+    pub detected_format: String,
+}
+
+/// Ingestion health of a location (root folder) and everything below it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LocationHealth {
+    pub location_id: i64,
+    /// Indexed files.
+    pub file_count: i64,
+    /// Combined size of the indexed files in bytes.
+    pub total_size: i64,
+    /// Files skipped by the last scan because they could not be read.
+    pub unreadable_count: i64,
+    /// Indexed files flagged as probably corrupt by the thumbnail worker.
+    pub corrupt_count: i64,
+    /// When the last scan started, `None` if the location was never scanned.
+    pub last_scan_at: Option<DateTime<Utc>>,
+    /// Duration of the last scan in milliseconds.
+    pub last_scan_duration_ms: Option<i64>,
+    /// Whether a file watcher is running for the location.
+    pub watcher_active: bool,
+    /// Files that have a thumbnail.
+    pub thumbnail_count: i64,
+    /// Share of files with a thumbnail, 0-100.
+    pub thumbnail_coverage: f64,
+}
+
+/// Saved playback progress of an audio or video file.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PlaybackState {
+    /// Media file the position belongs to.
+    pub image_id: i64,
+    /// Position in seconds to resume from.
+    pub position: f64,
+    /// Whether the media was played to the end.
+    pub completed: bool,
+    /// When the position was last saved.
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// A chapter marker of a video or audio file.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct VideoChapter {
+    /// Zero-based position of the chapter in the file.
+    pub chapter_index: i64,
+    /// Chapter start in seconds.
+    pub start_secs: f64,
+    /// Chapter end in seconds.
+    pub end_secs: f64,
+    /// Chapter title from the container, if any.
+    pub title: Option<String>,
+}
+
+/// A numbered frame sequence (`shot_####.exr`) shown as a single grid item.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ImageSequence {
+    pub id: i64,
+    pub folder_id: i64,
+    /// Filename pattern with `#` for each frame digit.
+    pub pattern: String,
+    pub first_frame: i64,
+    pub last_frame: i64,
+    /// Number of frames on disk (gaps in the range are not counted).
+    pub frame_count: i64,
+    /// Middle frame, used as the sequence thumbnail.
+    pub representative_image_id: Option<i64>,
+}
+
+/// Versions of one file (`hero_v01.psd` … `hero_final.psd`) in a folder.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct VersionChain {
+    pub id: i64,
+    pub folder_id: i64,
+    /// Lowercase filename without its version suffix.
+    pub base_name: String,
+    pub version_count: i64,
+    /// Highest version, the one kept by the "latest versions" filter.
+    pub latest_image_id: Option<i64>,
+}
+
+/// One file of a version chain.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChainVersion {
+    pub image_id: i64,
+    pub filename: String,
+    pub path: String,
+    pub thumbnail_path: Option<String>,
+    pub is_latest: bool,
+}
+
+/// One frame of an image sequence, for scrubbing.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SequenceFrame {
+    pub image_id: i64,
+    pub frame: i64,
+    pub path: String,
+    pub thumbnail_path: Option<String>,
+}
+
+/// A keyboard key mapped to a tag for fast culling.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TagShortcut {
+    /// Shortcut profile the mapping belongs to.
+    pub profile: String,
+    /// Normalized key: a digit 1-9 or a lowercase letter.
+    pub shortcut_key: String,
+    /// Tag applied when the key is pressed.
+    pub tag_id: i64,
+}
+
+/// A persistent review queue over a snapshot of filtered images.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ReviewSession {
+    /// Unique identifier for the session.
+    pub id: i64,
+    /// Optional display name.
+    pub name: Option<String>,
+    /// JSON filter the session was started from.
+    pub filter: String,
+    /// Position of the current item, `None` once every item was handled.
+    pub position: Option<i64>,
+    /// Number of items in the queue when it was created.
+    pub total: i64,
+    /// Number of items already skipped, advanced or applied.
+    pub reviewed: i64,
+    /// `active` or `completed`.
+    pub status: String,
+    /// Whether the skipped items were already served again.
+    pub revisited: bool,
+    /// ISO-8601 creation timestamp.
+    pub created_at: DateTime<Utc>,
+    /// Last time progress was recorded.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A review session together with the image at its cursor.
+#[derive(Debug, Serialize)]
+pub struct ReviewCursor {
+    /// Session state after the last step.
+    pub session: ReviewSession,
+    /// Image to review next, `None` when the queue is exhausted.
+    pub current: Option<ImageMetadata>,
+}
+
+/// A rectangle on an image, in normalized coordinates (0..1).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct AnnotationRegion {
+    /// Left edge, as a fraction of the image width.
+    pub x: f64,
+    /// Top edge, as a fraction of the image height.
+    pub y: f64,
+    /// Width, as a fraction of the image width.
+    pub w: f64,
+    /// Height, as a fraction of the image height.
+    pub h: f64,
+}
+
+/// A timestamped note attached to an image.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ImageAnnotation {
+    /// Unique identifier for the annotation.
+    pub id: i64,
+    /// Image the annotation belongs to.
+    pub image_id: i64,
+    /// Markdown text of the note.
+    pub body: String,
+    /// Left edge of the annotated region, if any.
+    pub region_x: Option<f64>,
+    /// Top edge of the annotated region, if any.
+    pub region_y: Option<f64>,
+    /// Width of the annotated region, if any.
+    pub region_w: Option<f64>,
+    /// Height of the annotated region, if any.
+    pub region_h: Option<f64>,
+    /// Image this note links to (`related_to`).
+    pub related_image_id: Option<i64>,
+    /// ISO-8601 creation timestamp.
+    pub created_at: DateTime<Utc>,
+    /// Last edit timestamp.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An external reference attached to an image or a folder.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AssetLink {
+    /// Unique identifier for the link.
+    pub id: i64,
+    /// Image the link is attached to, if any.
+    pub image_id: Option<i64>,
+    /// Folder the link is attached to, if any.
+    pub folder_id: Option<i64>,
+    /// Display label (e.g. "Figma", "JIRA-123").
+    pub label: String,
+    /// Target URL or absolute path.
+    pub url: String,
+    /// ISO-8601 creation timestamp.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Everything Mundam knows about an image, as written to a metadata bundle.
+#[derive(Debug, Serialize)]
+pub struct MetadataBundleEntry {
+    /// Core file metadata.
+    pub image: ImageMetadata,
+    /// Names of the tags applied to the image.
+    pub tags: Vec<String>,
+    /// Notes and region annotations.
+    pub annotations: Vec<ImageAnnotation>,
+    /// Links on the image and on its parent folders.
+    pub links: Vec<AssetLink>,
+}
+
+/// A self-contained JSON export of image metadata.
+#[derive(Debug, Serialize)]
+pub struct MetadataBundle {
+    /// Bundle format version.
+    pub version: u32,
+    /// ISO-8601 export timestamp.
+    pub exported_at: DateTime<Utc>,
+    /// One entry per exported image.
+    pub images: Vec<MetadataBundleEntry>,
+}
+
+/// A stored filter whose results are pinned as snapshots on a schedule.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AutoCollection {
+    /// Unique identifier for the collection.
+    pub id: i64,
+    /// Display name.
+    pub name: String,
+    /// JSON filter evaluated for each snapshot.
+    pub filter: String,
+    /// `daily`, `weekly`, `monthly` or `manual`.
+    pub schedule: String,
+    /// When the last snapshot was taken.
+    pub last_evaluated_at: Option<DateTime<Utc>>,
+    /// When the scheduler takes the next snapshot, `None` for manual collections.
+    pub next_due_at: Option<DateTime<Utc>>,
+    /// Number of images in the latest snapshot.
+    pub latest_count: Option<i64>,
+    /// ISO-8601 creation timestamp.
+    pub created_at: DateTime<Utc>,
+}
+
+/// A frozen result of an auto-collection.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AutoCollectionSnapshot {
+    /// Unique identifier for the snapshot.
+    pub id: i64,
+    /// Collection the snapshot belongs to.
+    pub collection_id: i64,
+    /// When the filter was evaluated.
+    pub taken_at: DateTime<Utc>,
+    /// Number of images matched at that time.
+    pub image_count: i64,
+}
+
+/// Statistics of a smart folder at some point in time.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SmartFolderSnapshot {
+    /// Unique identifier for the snapshot.
+    pub id: i64,
+    /// Smart folder the snapshot belongs to.
+    pub smart_folder_id: i64,
+    /// When the query was evaluated.
+    pub taken_at: DateTime<Utc>,
+    /// Number of images matched at that time.
+    pub image_count: i64,
+    /// Matched images without any tag.
+    pub untagged_count: i64,
+    /// Matched images per rating, from unrated (index 0) to five stars.
+    pub ratings: [i64; 6],
+}
+
+/// Before and after values of a record touched by a find-and-replace.
+#[derive(Debug, Serialize)]
+pub struct TextChange {
+    /// Image or folder id.
+    pub id: i64,
+    pub before: String,
+    pub after: String,
+}
+
+/// Outcome of `replace_path_prefix`.
+#[derive(Debug, Serialize)]
+pub struct PathPrefixReplacement {
+    /// Number of folders whose path starts with the old prefix.
+    pub folders: i64,
+    /// Number of images whose path starts with the old prefix.
+    pub images: i64,
+    /// Rows of the other tables holding paths (derivatives, pending
+    /// imported metadata, trash, sync destinations) rewritten with them.
+    pub other: i64,
+    /// First affected image paths, for preview.
+    pub samples: Vec<TextChange>,
+    /// A rewritten folder or image path that is already in the library.
+    /// Nothing is written when set.
+    pub conflict: Option<String>,
+    /// `false` for dry runs and conflicts.
+    pub applied: bool,
+}
+
+/// Outcome of `find_replace_notes`.
+#[derive(Debug, Serialize)]
+pub struct NotesReplacement {
+    /// Number of images whose notes match the pattern.
+    pub affected: i64,
+    /// First affected notes, for preview.
+    pub samples: Vec<TextChange>,
+    /// `false` for dry runs.
+    pub applied: bool,
+}
+
+/// An ordered list of images for slideshows and media player exports.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Playlist {
+    /// Unique identifier for the playlist.
+    pub id: i64,
+    /// Display name.
+    pub name: String,
+    /// Seconds each item stays on screen unless it sets its own duration.
+    pub default_duration: f64,
+    /// Number of items.
+    pub item_count: i64,
+    /// ISO-8601 creation timestamp.
+    pub created_at: DateTime<Utc>,
+    /// Last time the items or settings changed.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An item of a playlist, with the image it shows.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PlaylistItem {
+    /// Zero-based position in the playlist.
+    pub position: i64,
+    /// Own duration in seconds, `None` to use the playlist default.
+    #[sqlx(rename = "item_duration")]
+    pub duration: Option<f64>,
+    #[sqlx(flatten)]
+    pub image: ImageMetadata,
+}
+
+/// An item as sent by the frontend when reordering or retiming a playlist.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistItemInput {
+    pub image_id: i64,
+    pub duration: Option<f64>,
+}
+
+/// A manually curated, hand-ordered set of images (an album).
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Collection {
+    /// Unique identifier for the collection.
+    pub id: i64,
+    /// Display name.
+    pub name: String,
+    /// Optional free-form description.
+    pub description: Option<String>,
+    /// Number of images.
+    pub item_count: i64,
+    /// ISO-8601 creation timestamp.
+    pub created_at: DateTime<Utc>,
+    /// Last time the images, their order or the name changed.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An image taken out of the library, kept in the trash until it is
+/// restored or purged (see `db::trash`).
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TrashItem {
+    /// Unique identifier of the trash entry.
+    pub id: i64,
+    /// Id the image had in the library.
+    pub image_id: i64,
+    /// Path the file had in the library.
+    pub path: String,
+    /// Name of the file.
+    pub filename: String,
+    /// File size in bytes when it was trashed.
+    pub size: i64,
+    /// File extension, as for images.
+    pub format: String,
+    /// `missing` when the file went away, `deleted` when deleted from the app.
+    pub reason: String,
+    /// Whether the trash folder holds the file.
+    pub has_file: bool,
+    /// When the image left the library.
+    pub trashed_at: DateTime<Utc>,
+}
+
+/// A background job of the shared queue.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Job {
+    /// Unique identifier for the job.
+    pub id: i64,
+    /// Handler that runs the job (`transcode`, `export_metadata`...).
+    pub kind: String,
+    /// Human readable description, usually the file being processed.
+    pub label: String,
+    /// JSON arguments of the handler.
+    pub payload: String,
+    /// Higher runs first.
+    pub priority: i64,
+    /// `queued`, `running`, `completed`, `failed` or `cancelled`.
+    pub status: String,
+    /// Units of work done so far.
+    pub progress_done: i64,
+    /// Total units of work, if known.
+    pub progress_total: Option<i64>,
+    /// Latest progress message.
+    pub message: Option<String>,
+    /// Error of the last attempt.
+    pub error: Option<String>,
+    /// JSON result of a completed job.
+    pub result: Option<String>,
+    /// Number of times the job was started.
+    pub attempts: i64,
+    /// ISO-8601 creation timestamp.
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// A multi-file operation recorded in the ledger.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Operation {
+    /// Unique identifier for the operation.
+    pub id: i64,
+    /// What the operation does (`bulk_rename`...).
+    pub kind: String,
+    /// `in_progress`, `interrupted`, `completed` or `rolled_back`.
+    pub status: String,
+    /// Number of files the operation moves.
+    pub step_count: i64,
+    /// ISO-8601 creation timestamp.
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// One file move of a ledger operation.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OperationStep {
+    pub operation_id: i64,
+    pub position: i64,
+    pub image_id: i64,
+    /// Path before the operation.
+    pub source: String,
+    /// Path after the operation.
+    pub target: String,
+}
+
+/// Metadata an importer maps onto a file, applied once the file is indexed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImportedMetadata {
+    /// Path of the file in the library.
+    pub path: String,
+    pub rating: Option<i32>,
+    pub notes: Option<String>,
+    /// Capture time, replacing the file's creation time.
+    pub taken_at: Option<DateTime<Utc>>,
+    /// `(group, name)` pairs; missing tags are created under the group tag.
+    pub tags: Vec<(String, String)>,
+    /// Capture location, only applied to images without one.
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+/// Stock provider details of an image, from its download name or embedded XMP.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct StockInfo {
+    /// Provider name, e.g. "Adobe Stock".
+    pub provider: String,
+    /// The provider's id for the asset, from the download name or XMP.
+    pub asset_id: Option<String>,
+    /// License text (`xmpRights:UsageTerms`).
+    pub usage_terms: Option<String>,
+    /// License page (`xmpRights:WebStatement` or the licensor URL).
+    pub license_url: Option<String>,
+    /// Photographer credit line (`photoshop:Credit`).
+    pub credit: Option<String>,
+    /// Copyright notice (`dc:rights`).
+    pub copyright: Option<String>,
+}
+
+/// A folder kept in sync with a stored filter.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SyncTarget {
+    pub id: i64,
+    pub name: String,
+    /// Folder the matching files are copied to, as stored in the database
+    /// (see `paths::to_db`).
+    pub destination: String,
+    /// JSON filter selecting the synced images.
+    pub filter: String,
+    /// `keep`, `delete` or `archive`: what happens to files leaving the filter.
+    pub delete_policy: String,
+    /// Whether the folders below the location are recreated.
+    pub keep_folders: bool,
+    /// Whether the target is synced in the background.
+    pub auto_sync: bool,
+    pub last_synced_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A file copied to a sync target.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct SyncedFile {
+    pub relative_path: String,
+    pub image_id: i64,
+    pub source_size: i64,
+    /// Unix seconds.
+    pub source_modified: i64,
+    /// Unix seconds.
+    pub destination_modified: i64,
+}
+
+/// A location backed by object storage. Credentials are not exposed.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct RemoteLocation {
+    pub id: i64,
+    pub name: String,
+    /// `s3` or `webdav`.
+    pub kind: String,
+    /// Root folder of the location's mirror.
+    pub folder_id: Option<i64>,
+    /// Size limit of the local cache in megabytes.
+    pub cache_max_mb: i64,
+    /// When the file listing was last refreshed.
+    pub last_listed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,139 @@
+//! Ledger of multi-file operations (see `crate::library::operations`).
+
+use crate::db::models::{Operation, OperationStep};
+use super::Db;
+
+pub const OPERATION_IN_PROGRESS: &str = "in_progress";
+pub const OPERATION_INTERRUPTED: &str = "interrupted";
+pub const OPERATION_COMPLETED: &str = "completed";
+pub const OPERATION_ROLLED_BACK: &str = "rolled_back";
+
+const OPERATION_COLUMNS: &str = "SELECT o.id, o.kind, o.status, o.created_at, o.finished_at,
+        (SELECT COUNT(*) FROM operation_steps s WHERE s.operation_id = o.id) AS step_count
+     FROM operations o";
+
+impl Db {
+    /// Records an operation and all of its `(image_id, source, target)` moves
+    /// before any of them is applied.
+    pub async fn begin_operation(&self, kind: &str, steps: &[(i64, String, String)]) -> Result<i64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let operation_id = sqlx::query("INSERT INTO operations (kind) VALUES (?)")
+            .bind(kind)
+            .execute(&mut *tx)
+            .await?
+            .last_insert_rowid();
+
+        // Stay well below SQLite's bound parameter limit
+        for (chunk_index, chunk) in steps.chunks(150).enumerate() {
+            let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
+                "INSERT INTO operation_steps (operation_id, position, image_id, source, target) "
+            );
+            query_builder.push_values(chunk.iter().enumerate(), |mut row, (i, (image_id, source, target))| {
+                row.push_bind(operation_id)
+                    .push_bind((chunk_index * 150 + i) as i64)
+                    .push_bind(*image_id)
+                    .push_bind(source)
+                    .push_bind(target);
+            });
+            query_builder.build().execute(&mut *tx).await?;
+        }
+
+        tx.commit().await?;
+        Ok(operation_id)
+    }
+
+    pub async fn finish_operation(&self, id: i64, status: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE operations SET status = ?, finished_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(status)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Flags operations left in progress by a previous session as interrupted.
+    /// Called once at startup, before anything can start a new operation.
+    pub async fn mark_interrupted_operations(&self) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("UPDATE operations SET status = ? WHERE status = ?")
+            .bind(OPERATION_INTERRUPTED)
+            .bind(OPERATION_IN_PROGRESS)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get_operation(&self, id: i64) -> Result<Option<Operation>, sqlx::Error> {
+        sqlx::query_as::<_, Operation>(&format!("{} WHERE o.id = ?", OPERATION_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Lists operations with `status`, oldest first.
+    pub async fn get_operations_by_status(&self, status: &str) -> Result<Vec<Operation>, sqlx::Error> {
+        sqlx::query_as::<_, Operation>(&format!("{} WHERE o.status = ? ORDER BY o.id", OPERATION_COLUMNS))
+            .bind(status)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn get_operation_steps(&self, operation_id: i64) -> Result<Vec<OperationStep>, sqlx::Error> {
+        sqlx::query_as::<_, OperationStep>(
+            "SELECT operation_id, position, image_id, source, target FROM operation_steps
+             WHERE operation_id = ? ORDER BY position"
+        )
+        .bind(operation_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Points images at their recovered `(image_id, path)` locations and
+    /// closes the operation with `status`, in one transaction.
+    ///
+    /// A scan that ran after the crash may have indexed a moved file as a new
+    /// image; that row is dropped so the original one, with its tags and
+    /// ratings, takes the path back. Images deleted meanwhile are skipped.
+    pub async fn settle_operation(&self, id: i64, paths: &[(i64, String)], status: &str) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        for (image_id, path) in paths {
+            let filename = std::path::Path::new(path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            sqlx::query("DELETE FROM images WHERE path = ? AND id != ? AND EXISTS (SELECT 1 FROM images WHERE id = ?)")
+                .bind(path)
+                .bind(image_id)
+                .bind(image_id)
+                .execute(&mut *tx)
+                .await?;
+            // Moves across folders (folder merges) also change the folder
+            let parent = std::path::Path::new(path)
+                .parent()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default();
+            sqlx::query(
+                "UPDATE images SET path = ?, filename = ?,
+                    folder_id = COALESCE((SELECT id FROM folders WHERE path = ?), folder_id)
+                 WHERE id = ?"
+            )
+            .bind(path)
+            .bind(&filename)
+            .bind(&parent)
+            .bind(image_id)
+            .execute(&mut *tx)
+            .await?;
+            super::collation::refresh_sort_key(&mut *tx, *image_id, &filename).await?;
+        }
+
+        sqlx::query("UPDATE operations SET status = ?, finished_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(status)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
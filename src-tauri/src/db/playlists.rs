@@ -0,0 +1,149 @@
+//! Ordered playlists used for slideshows and M3U exports.
+
+use crate::db::images::IMAGE_COLUMNS;
+use crate::db::models::{Playlist, PlaylistItem, PlaylistItemInput};
+use super::Db;
+
+const PLAYLIST_COLUMNS: &str = "SELECT p.id, p.name, p.default_duration, p.created_at, p.updated_at,
+        (SELECT COUNT(*) FROM playlist_items pi WHERE pi.playlist_id = p.id) AS item_count
+     FROM playlists p";
+
+/// Replaces the items of a playlist inside an open transaction.
+async fn write_items(
+    tx: &mut sqlx::SqliteConnection,
+    playlist_id: i64,
+    items: &[PlaylistItemInput],
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM playlist_items WHERE playlist_id = ?")
+        .bind(playlist_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Stay well below SQLite's bound parameter limit
+    for (chunk_index, chunk) in items.chunks(200).enumerate() {
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
+            "INSERT INTO playlist_items (playlist_id, position, image_id, duration) "
+        );
+        query_builder.push_values(chunk.iter().enumerate(), |mut row, (i, item)| {
+            row.push_bind(playlist_id)
+                .push_bind((chunk_index * 200 + i) as i64)
+                .push_bind(item.image_id)
+                .push_bind(item.duration);
+        });
+        query_builder.build().execute(&mut *tx).await?;
+    }
+    Ok(())
+}
+
+impl Db {
+    /// Creates a playlist over `image_ids`, preserving their order.
+    pub async fn create_playlist(&self, name: &str, default_duration: f64, image_ids: &[i64]) -> Result<i64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let playlist_id = sqlx::query("INSERT INTO playlists (name, default_duration) VALUES (?, ?)")
+            .bind(name)
+            .bind(default_duration)
+            .execute(&mut *tx)
+            .await?
+            .last_insert_rowid();
+
+        let items: Vec<PlaylistItemInput> = image_ids
+            .iter()
+            .map(|&image_id| PlaylistItemInput { image_id, duration: None })
+            .collect();
+        write_items(&mut tx, playlist_id, &items).await?;
+
+        tx.commit().await?;
+        Ok(playlist_id)
+    }
+
+    pub async fn get_playlist(&self, id: i64) -> Result<Option<Playlist>, sqlx::Error> {
+        sqlx::query_as::<_, Playlist>(&format!("{} WHERE p.id = ?", PLAYLIST_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Lists all playlists, most recently changed first.
+    pub async fn get_playlists(&self) -> Result<Vec<Playlist>, sqlx::Error> {
+        sqlx::query_as::<_, Playlist>(&format!("{} ORDER BY p.updated_at DESC, p.id DESC", PLAYLIST_COLUMNS))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Retrieves the items of a playlist in order.
+    ///
+    /// Items whose image was deleted are left out.
+    pub async fn get_playlist_items(&self, playlist_id: i64) -> Result<Vec<PlaylistItem>, sqlx::Error> {
+        sqlx::query_as::<_, PlaylistItem>(&format!(
+            "SELECT pi.position, pi.duration AS item_duration, {}
+             FROM playlist_items pi JOIN images i ON i.id = pi.image_id LEFT JOIN playback_positions pp ON pp.image_id = i.id
+             WHERE pi.playlist_id = ?
+             ORDER BY pi.position",
+            IMAGE_COLUMNS
+        ))
+        .bind(playlist_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Renames a playlist and changes its default duration.
+    pub async fn update_playlist(&self, id: i64, name: &str, default_duration: f64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE playlists SET name = ?, default_duration = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(name)
+            .bind(default_duration)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Replaces the items of a playlist, e.g. after reordering or retiming.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the transaction fails; the previous items are kept in that case.
+    pub async fn set_playlist_items(&self, playlist_id: i64, items: &[PlaylistItemInput]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        write_items(&mut tx, playlist_id, items).await?;
+        sqlx::query("UPDATE playlists SET updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(playlist_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Deletes a playlist and its items. Images are not affected.
+    pub async fn delete_playlist(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM playlists WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_items_keep_their_own_duration_apart_from_the_clip_length() {
+        let library = crate::testkit::TestLibrary::open("playlist-items").await;
+        let db = &library.db;
+        library.seed_images(&["clip.mp4"]).await;
+        sqlx::query("UPDATE images SET duration = 42.5, latitude = 48.85, longitude = 2.35 WHERE id = 1")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let playlist_id = db.create_playlist("Clips", 5.0, &[1]).await.unwrap();
+        db.set_playlist_items(playlist_id, &[PlaylistItemInput { image_id: 1, duration: Some(3.0) }]).await.unwrap();
+
+        let items = db.get_playlist_items(playlist_id).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].duration, Some(3.0));
+        assert_eq!(items[0].image.duration, Some(42.5));
+        assert_eq!((items[0].image.latitude, items[0].image.longitude), (Some(48.85), Some(2.35)));
+    }
+}
@@ -0,0 +1,120 @@
+//! Remote locations (see `crate::storage`).
+
+use crate::db::models::RemoteLocation;
+use super::Db;
+
+const REMOTE_COLUMNS: &str = "SELECT id, name, kind, folder_id, cache_max_mb, last_listed_at, created_at FROM remote_locations";
+
+impl Db {
+    pub async fn create_remote_location(
+        &self,
+        name: &str,
+        kind: &str,
+        config_json: &str,
+        secret_ref: Option<&str>,
+        cache_max_mb: i64,
+    ) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query("INSERT INTO remote_locations (name, kind, config, secret_ref, cache_max_mb) VALUES (?, ?, ?, ?, ?)")
+            .bind(name)
+            .bind(kind)
+            .bind(config_json)
+            .bind(secret_ref)
+            .bind(cache_max_mb)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn set_remote_location_folder(&self, id: i64, folder_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE remote_locations SET folder_id = ? WHERE id = ?")
+            .bind(folder_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_remote_location_cache_limit(&self, id: i64, cache_max_mb: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE remote_locations SET cache_max_mb = ? WHERE id = ?")
+            .bind(cache_max_mb)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn mark_remote_location_listed(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE remote_locations SET last_listed_at = datetime('now') WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes a remote location and its root folder, with every image below.
+    pub async fn delete_remote_location(&self, id: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM folders WHERE id = (SELECT folder_id FROM remote_locations WHERE id = ?)")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM remote_locations WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_remote_location(&self, id: i64) -> Result<Option<RemoteLocation>, sqlx::Error> {
+        sqlx::query_as::<_, RemoteLocation>(&format!("{} WHERE id = ?", REMOTE_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn get_remote_locations(&self) -> Result<Vec<RemoteLocation>, sqlx::Error> {
+        sqlx::query_as::<_, RemoteLocation>(&format!("{} ORDER BY name COLLATE NOCASE", REMOTE_COLUMNS))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Connection settings (JSON), cache limit and keychain entry of the
+    /// credentials of a remote location.
+    pub async fn get_remote_location_config(&self, id: i64) -> Result<Option<(String, i64, Option<String>)>, sqlx::Error> {
+        sqlx::query_as("SELECT config, cache_max_mb, secret_ref FROM remote_locations WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Drops the thumbnails of images whose remote file changed, so they are
+    /// rendered again from the new version.
+    pub async fn clear_thumbnails_by_paths(&self, paths: &[String]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for path in paths {
+            sqlx::query("UPDATE images SET thumbnail_path = NULL, thumbnail_attempts = 0 WHERE path = ?")
+                .bind(path)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Moves the images of files gone from a remote location to the trash.
+    pub async fn delete_images_by_paths(&self, paths: &[String]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for path in paths {
+            let id: Option<i64> = sqlx::query_scalar("SELECT id FROM images WHERE path = ?")
+                .bind(path)
+                .fetch_optional(&mut *tx)
+                .await?;
+            if let Some(id) = id {
+                super::trash::trash_image(&mut tx, id, super::trash::REASON_MISSING, None).await?;
+            }
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
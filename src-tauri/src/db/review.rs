@@ -0,0 +1,250 @@
+//! Persistent review queues ("tagging sessions").
+//!
+//! A session snapshots the ids matching a filter in grid order and keeps a
+//! cursor over them, so a long culling session can be resumed after the app
+//! is closed. Skipped items are served again, once, after the last pending
+//! one.
+
+use crate::db::images::IMAGE_COLUMNS;
+use crate::db::models::{ImageMetadata, ReviewCursor, ReviewSession};
+use crate::db::search::ImageFilter;
+use super::Db;
+
+/// Item has not been looked at yet.
+pub const REVIEW_PENDING: &str = "pending";
+/// Item was looked at and left unchanged.
+pub const REVIEW_ADVANCED: &str = "advanced";
+/// Item was skipped to come back to later.
+pub const REVIEW_SKIPPED: &str = "skipped";
+/// Tags or rating were applied to the item.
+pub const REVIEW_APPLIED: &str = "applied";
+
+/// Restricts a session to items still missing tags or a rating.
+const NEEDS_REVIEW_CONDITION: &str =
+    " AND (NOT EXISTS (SELECT 1 FROM image_tags it WHERE it.image_id = i.id) OR i.rating IS NULL OR i.rating = 0) ";
+
+const SESSION_COLUMNS: &str = "SELECT s.id, s.name, s.filter, s.position, s.total, s.status, s.revisited, s.created_at, s.updated_at,
+        (SELECT COUNT(*) FROM review_session_items ri WHERE ri.session_id = s.id AND ri.state != 'pending') AS reviewed
+     FROM review_sessions s";
+
+impl Db {
+    /// Retrieves, in grid order, the untagged or unrated images matching a filter.
+    ///
+    /// Pagination fields of the filter are ignored.
+    pub async fn get_images_needing_review(&self, filter: &ImageFilter) -> Result<Vec<i64>, sqlx::Error> {
+        let group = filter.parsed_group();
+        let mut query_builder = filter.build_id_query(group.as_ref(), NEEDS_REVIEW_CONDITION);
+        query_builder.build_query_scalar::<i64>().fetch_all(&self.pool).await
+    }
+
+    /// Creates a review session over `image_ids`, preserving their order.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the transaction fails; nothing is written in that case.
+    pub async fn create_review_session(
+        &self,
+        name: Option<&str>,
+        filter_json: &str,
+        image_ids: &[i64],
+    ) -> Result<i64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let (position, status) = if image_ids.is_empty() {
+            (None, "completed")
+        } else {
+            (Some(0_i64), "active")
+        };
+
+        let session_id = sqlx::query(
+            "INSERT INTO review_sessions (name, filter, position, total, status) VALUES (?, ?, ?, ?, ?)"
+        )
+        .bind(name)
+        .bind(filter_json)
+        .bind(position)
+        .bind(image_ids.len() as i64)
+        .bind(status)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+        // Stay well below SQLite's bound parameter limit
+        for (chunk_index, chunk) in image_ids.chunks(300).enumerate() {
+            let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
+                "INSERT INTO review_session_items (session_id, position, image_id) "
+            );
+            query_builder.push_values(chunk.iter().enumerate(), |mut row, (i, image_id)| {
+                row.push_bind(session_id)
+                    .push_bind((chunk_index * 300 + i) as i64)
+                    .push_bind(*image_id);
+            });
+            query_builder.build().execute(&mut *tx).await?;
+        }
+
+        tx.commit().await?;
+        Ok(session_id)
+    }
+
+    /// Retrieves a single review session.
+    pub async fn get_review_session(&self, session_id: i64) -> Result<Option<ReviewSession>, sqlx::Error> {
+        sqlx::query_as::<_, ReviewSession>(&format!("{} WHERE s.id = ?", SESSION_COLUMNS))
+            .bind(session_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Lists all review sessions, most recently used first.
+    pub async fn get_review_sessions(&self) -> Result<Vec<ReviewSession>, sqlx::Error> {
+        sqlx::query_as::<_, ReviewSession>(&format!("{} ORDER BY s.updated_at DESC, s.id DESC", SESSION_COLUMNS))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Returns the session and the image at its cursor.
+    pub async fn get_review_cursor(&self, session_id: i64) -> Result<Option<ReviewCursor>, sqlx::Error> {
+        let Some(session) = self.get_review_session(session_id).await? else {
+            return Ok(None);
+        };
+
+        let current = match session.position {
+            Some(position) => {
+                sqlx::query_as::<_, ImageMetadata>(&format!(
+                    "SELECT {}
+                     FROM review_session_items ri JOIN images i ON i.id = ri.image_id LEFT JOIN playback_positions pp ON pp.image_id = i.id
+                     WHERE ri.session_id = ? AND ri.position >= ? AND ri.state = 'pending'
+                     ORDER BY ri.position LIMIT 1",
+                    IMAGE_COLUMNS
+                ))
+                .bind(session_id)
+                .bind(position)
+                .fetch_optional(&self.pool)
+                .await?
+            }
+            None => None,
+        };
+
+        Ok(Some(ReviewCursor { session, current }))
+    }
+
+    /// Records the outcome of the current item and moves the cursor to the
+    /// next pending one. When none is left, the skipped items are made
+    /// pending again the first time, and the session completes after that.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the database fails.
+    pub async fn record_review_step(&self, session_id: i64, state: &str) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // The stored cursor may point at an item whose image was deleted since,
+        // so resolve it to the first pending item at or after it.
+        let position: Option<i64> = sqlx::query_scalar(
+            "SELECT MIN(ri.position) FROM review_sessions s
+             JOIN review_session_items ri ON ri.session_id = s.id
+             WHERE s.id = ? AND ri.position >= s.position AND ri.state = 'pending'"
+        )
+        .bind(session_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let Some(position) = position else {
+            // Unknown or already completed session: nothing to record
+            return Ok(());
+        };
+
+        sqlx::query(
+            "UPDATE review_session_items SET state = ?, reviewed_at = CURRENT_TIMESTAMP
+             WHERE session_id = ? AND position = ?"
+        )
+        .bind(state)
+        .bind(session_id)
+        .bind(position)
+        .execute(&mut *tx)
+        .await?;
+
+        let mut next: Option<i64> = sqlx::query_scalar(
+            "SELECT MIN(position) FROM review_session_items
+             WHERE session_id = ? AND position > ? AND state = 'pending'"
+        )
+        .bind(session_id)
+        .bind(position)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // End of the queue: go back to what was skipped, once
+        if next.is_none() {
+            let revisited = sqlx::query(
+                "UPDATE review_session_items SET state = 'pending', reviewed_at = NULL
+                 WHERE session_id = ? AND state = 'skipped'
+                   AND NOT (SELECT revisited FROM review_sessions WHERE id = ?)"
+            )
+            .bind(session_id)
+            .bind(session_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+            if revisited > 0 {
+                next = sqlx::query_scalar(
+                    "SELECT MIN(position) FROM review_session_items WHERE session_id = ? AND state = 'pending'"
+                )
+                .bind(session_id)
+                .fetch_one(&mut *tx)
+                .await?;
+            }
+            sqlx::query("UPDATE review_sessions SET revisited = 1 WHERE id = ?")
+                .bind(session_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query(
+            "UPDATE review_sessions SET position = ?, status = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?"
+        )
+        .bind(next)
+        .bind(if next.is_some() { "active" } else { "completed" })
+        .bind(session_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Deletes a review session and its items. Images are not affected.
+    pub async fn delete_review_session(&self, session_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM review_sessions WHERE id = ?")
+            .bind(session_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_skipped_items_come_back_once() {
+        let library = crate::testkit::TestLibrary::open("review-revisit").await;
+        let db = &library.db;
+        library.seed_images(&["1.jpg", "2.jpg", "3.jpg"]).await;
+        let session_id = db.create_review_session(None, "{}", &[1, 2, 3]).await.unwrap();
+        let current = |cursor: Option<ReviewCursor>| cursor.unwrap().current.map(|image| image.id);
+
+        db.record_review_step(session_id, REVIEW_SKIPPED).await.unwrap();
+        db.record_review_step(session_id, REVIEW_ADVANCED).await.unwrap();
+        db.record_review_step(session_id, REVIEW_SKIPPED).await.unwrap();
+        // Both skipped items are served again, in order
+        assert_eq!(current(db.get_review_cursor(session_id).await.unwrap()), Some(1));
+        db.record_review_step(session_id, REVIEW_APPLIED).await.unwrap();
+        assert_eq!(current(db.get_review_cursor(session_id).await.unwrap()), Some(3));
+
+        // Skipped a second time: the session is done
+        db.record_review_step(session_id, REVIEW_SKIPPED).await.unwrap();
+        let cursor = db.get_review_cursor(session_id).await.unwrap().unwrap();
+        assert_eq!(cursor.current.map(|image| image.id), None);
+        assert_eq!(cursor.session.status, "completed");
+        assert!(cursor.session.revisited);
+    }
+}
@@ -4,6 +4,8 @@
 //! into optimized SQLite queries using `sqlx::QueryBuilder`.
 
 use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use crate::db::images::IMAGE_COLUMNS;
 use crate::db::models::ImageMetadata;
 use super::Db;
 
@@ -38,6 +40,108 @@ pub struct SearchGroup {
     pub items: Vec<SearchItem>,
 }
 
+/// Queries slower than this are logged with their SQL.
+const SLOW_QUERY_THRESHOLD: Duration = Duration::from_millis(250);
+
+/// A complete grid filter, as sent by the frontend to `explain_filter`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ImageFilter {
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+    pub tag_ids: Vec<i64>,
+    pub match_all: bool,
+    pub untagged: Option<bool>,
+    pub folder_id: Option<i64>,
+    pub recursive: bool,
+    /// Manual collection the images must belong to (see `db::collections`).
+    pub collection_id: Option<i64>,
+    pub sort_by: Option<String>,
+    pub sort_order: Option<String>,
+    /// Advanced query, either as a JSON string or as an inline object.
+    pub advanced_query: Option<serde_json::Value>,
+    pub search_query: Option<String>,
+}
+
+impl ImageFilter {
+    /// Parses the advanced query into a search group, whatever its encoding.
+    pub(crate) fn parsed_group(&self) -> Option<SearchGroup> {
+        match self.advanced_query.as_ref()? {
+            serde_json::Value::String(json) => serde_json::from_str(json).ok(),
+            value => serde_json::from_value(value.clone()).ok(),
+        }
+    }
+
+    /// Builds an unpaginated query for the ids matching this filter, in grid order.
+    ///
+    /// `extra_condition` is appended as-is to the WHERE clause, so it must be
+    /// a constant SQL fragment.
+    pub(crate) fn build_id_query<'a>(&'a self, group: Option<&'a SearchGroup>, extra_condition: &str) -> sqlx::QueryBuilder<'a, sqlx::Sqlite> {
+        let mut query_builder = new_folder_scoped_query("", self.folder_id, self.recursive);
+        query_builder.push(" SELECT i.id FROM images i WHERE 1=1 ");
+        push_filter_conditions(
+            &mut query_builder,
+            &self.tag_ids,
+            self.match_all,
+            self.untagged,
+            self.folder_id,
+            self.recursive,
+            self.collection_id,
+            group,
+            self.search_query.as_deref(),
+        );
+        query_builder.push(extra_condition);
+        push_order_by(&mut query_builder, self.sort_by.as_deref(), self.sort_order.as_deref(), self.collection_id);
+        query_builder
+    }
+
+    /// Builds a query for the statistics of the images matching this filter:
+    /// their count, how many have no tag, and how many have each rating from
+    /// 0 (unrated) to 5, in that order.
+    pub(crate) fn build_stats_query<'a>(&'a self, group: Option<&'a SearchGroup>) -> sqlx::QueryBuilder<'a, sqlx::Sqlite> {
+        let mut query_builder = new_folder_scoped_query("", self.folder_id, self.recursive);
+        query_builder.push(
+            " SELECT COUNT(*),
+                COALESCE(SUM(NOT EXISTS (SELECT 1 FROM image_tags it WHERE it.image_id = i.id)), 0),
+                COALESCE(SUM(COALESCE(i.rating, 0) <= 0), 0),
+                COALESCE(SUM(i.rating = 1), 0), COALESCE(SUM(i.rating = 2), 0), COALESCE(SUM(i.rating = 3), 0),
+                COALESCE(SUM(i.rating = 4), 0), COALESCE(SUM(i.rating >= 5), 0)
+              FROM images i WHERE 1=1 ",
+        );
+        push_filter_conditions(
+            &mut query_builder,
+            &self.tag_ids,
+            self.match_all,
+            self.untagged,
+            self.folder_id,
+            self.recursive,
+            self.collection_id,
+            group,
+            self.search_query.as_deref(),
+        );
+        query_builder
+    }
+
+    /// Builds the grid query for this filter.
+    fn build_query<'a>(&'a self, prefix: &str, group: Option<&'a SearchGroup>) -> sqlx::QueryBuilder<'a, sqlx::Sqlite> {
+        build_images_query(
+            prefix,
+            self.limit.unwrap_or(100),
+            self.offset.unwrap_or(0),
+            &self.tag_ids,
+            self.match_all,
+            self.untagged,
+            self.folder_id,
+            self.recursive,
+            self.collection_id,
+            self.sort_by.as_deref(),
+            self.sort_order.as_deref(),
+            group,
+            self.search_query.as_deref(),
+        )
+    }
+}
+
 impl Db {
     /// Retrieves a paginated and filtered list of images based on various criteria.
     #[allow(clippy::too_many_arguments)] // Deep filtering naturally requires many parameters
@@ -50,185 +154,363 @@ impl Db {
         untagged: Option<bool>,
         folder_id: Option<i64>,
         recursive: bool,
+        collection_id: Option<i64>,
         sort_by: Option<String>,
         sort_order: Option<String>,
         advanced_query: Option<String>,
         search_query: Option<String>,
     ) -> Result<Vec<ImageMetadata>, sqlx::Error> {
-        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
-            "WITH RECURSIVE target_folders AS (
-               SELECT id FROM folders WHERE id = "
+        let parsed_group = advanced_query.as_ref().and_then(|q| serde_json::from_str::<SearchGroup>(q).ok());
+
+        let mut query_builder = build_images_query(
+            "",
+            limit,
+            offset,
+            &tag_ids,
+            match_all,
+            untagged,
+            folder_id,
+            recursive,
+            collection_id,
+            sort_by.as_deref(),
+            sort_order.as_deref(),
+            parsed_group.as_ref(),
+            search_query.as_deref(),
         );
 
-        if let Some(fid) = folder_id {
-            query_builder.push_bind(fid);
-            if recursive {
-                query_builder.push(" UNION ALL SELECT f.id FROM folders f JOIN target_folders tf ON f.parent_id = tf.id");
-            }
-        } else {
-             query_builder.push(" -1 ");
-        }
+        let started = Instant::now();
+        let images = query_builder.build_query_as::<ImageMetadata>().fetch_all(&self.pool).await?;
+        log_if_slow("get_images_filtered", query_builder.sql(), started.elapsed());
+        Ok(images)
+    }
 
-        query_builder.push(") SELECT DISTINCT i.id, i.path, i.filename, i.width, i.height, i.size, i.thumbnail_path, i.format, i.rating, i.notes, i.created_at, i.modified_at, i.added_at FROM images i ");
+    /// Gets the total count of images matching the search criteria.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_image_count_filtered(
+        &self,
+        tag_ids: Vec<i64>,
+        match_all: bool,
+        untagged: Option<bool>,
+        folder_id: Option<i64>,
+        recursive: bool,
+        collection_id: Option<i64>,
+        advanced_query: Option<String>,
+        search_query: Option<String>,
+    ) -> Result<i64, sqlx::Error> {
+        let mut query_builder = new_folder_scoped_query("", folder_id, recursive);
 
-        if !tag_ids.is_empty() {
-            query_builder.push(" JOIN image_tags it ON i.id = it.image_id ");
-        }
+        query_builder.push(" SELECT COUNT(*) FROM images i ");
 
         query_builder.push(" WHERE 1=1 ");
 
         let parsed_group = advanced_query.as_ref().and_then(|q| serde_json::from_str::<SearchGroup>(q).ok());
-        if let Some(ref group) = parsed_group {
-            query_builder.push(" AND ");
-            build_where_clause(group, &mut query_builder);
-        }
+        push_filter_conditions(
+            &mut query_builder,
+            &tag_ids,
+            match_all,
+            untagged,
+            folder_id,
+            recursive,
+            collection_id,
+            parsed_group.as_ref(),
+            search_query.as_deref(),
+        );
 
-        if let Some(search) = search_query {
-            if !search.is_empty() {
-                query_builder.push(" AND (i.filename LIKE ");
-                query_builder.push_bind(format!("%{}%", search));
-                query_builder.push(" OR i.notes LIKE ");
-                query_builder.push_bind(format!("%{}%", search));
-                query_builder.push(") ");
-            }
-        }
+        // Tag filters are plain subqueries, so the count never needs DISTINCT or GROUP BY
+        let started = Instant::now();
+        let (count,) = query_builder.build_query_as::<(i64,)>().fetch_one(&self.pool).await?;
+        log_if_slow("get_image_count_filtered", query_builder.sql(), started.elapsed());
+        Ok(count)
+    }
 
-        if let Some(fid) = folder_id {
-            if recursive {
-                query_builder.push(" AND i.folder_id IN target_folders ");
-            } else {
-                query_builder.push(" AND i.folder_id = ");
-                query_builder.push_bind(fid);
-            }
-        }
+    /// Grid rows of `filter`, honoring its pagination, and how many images
+    /// match it in total.
+    pub async fn get_filter_window(&self, filter: &ImageFilter) -> Result<(Vec<ImageMetadata>, i64), sqlx::Error> {
+        let parsed_group = filter.parsed_group();
+        let mut query_builder = filter.build_query("", parsed_group.as_ref());
+        let started = Instant::now();
+        let images = query_builder.build_query_as::<ImageMetadata>().fetch_all(&self.pool).await?;
+        log_if_slow("get_filter_window", query_builder.sql(), started.elapsed());
 
-        if untagged == Some(true) {
-            query_builder.push(" AND i.id NOT IN (SELECT DISTINCT image_id FROM image_tags) ");
-        }
+        let advanced_query = filter.advanced_query.as_ref().map(|query| match query {
+            serde_json::Value::String(json) => json.clone(),
+            value => value.to_string(),
+        });
+        let total = self
+            .get_image_count_filtered(
+                filter.tag_ids.clone(),
+                filter.match_all,
+                filter.untagged,
+                filter.folder_id,
+                filter.recursive,
+                filter.collection_id,
+                advanced_query,
+                filter.search_query.clone(),
+            )
+            .await?;
+        Ok((images, total))
+    }
 
-        if !tag_ids.is_empty() {
-            query_builder.push(" AND it.tag_id IN (");
-            let mut separated = query_builder.separated(", ");
-            for id in &tag_ids {
-                separated.push_bind(id);
-            }
-            separated.push_unseparated(") ");
+    /// Returns the SQL generated for a filter together with SQLite's query plan.
+    ///
+    /// The query is also executed once to measure its real duration.
+    pub async fn explain_filter(&self, filter: &ImageFilter) -> Result<FilterExplanation, sqlx::Error> {
+        let parsed_group = filter.parsed_group();
 
-            if match_all {
-                query_builder.push(" GROUP BY i.id HAVING COUNT(DISTINCT it.tag_id) = ");
-                query_builder.push_bind(tag_ids.len() as i32);
-            }
-        }
+        let mut plan_builder = filter.build_query("EXPLAIN QUERY PLAN ", parsed_group.as_ref());
+        let plan_rows = plan_builder
+            .build_query_as::<(i64, i64, i64, String)>()
+            .fetch_all(&self.pool)
+            .await?;
 
-        // Sorting Logic
-        let allowed_cols = ["filename", "created_at", "modified_at", "added_at", "size", "format", "rating"];
-        let final_sort_by = sort_by.as_deref().filter(|c| allowed_cols.contains(c)).unwrap_or("id");
-        let final_order = sort_order.as_deref().filter(|o| *o == "asc" || *o == "desc").unwrap_or("desc");
+        // Indent each step under its parent, like the sqlite3 shell does
+        let mut depths: std::collections::HashMap<i64, usize> = std::collections::HashMap::new();
+        let plan = plan_rows
+            .into_iter()
+            .map(|(id, parent, _, detail)| {
+                let depth = depths.get(&parent).map(|d| d + 1).unwrap_or(0);
+                depths.insert(id, depth);
+                format!("{}{}", "  ".repeat(depth), detail)
+            })
+            .collect();
 
-        query_builder.push(" ORDER BY (");
-        query_builder.push(final_sort_by);
-        query_builder.push(" IS NULL) ASC, ");
-        query_builder.push(final_sort_by);
+        let mut query_builder = filter.build_query("", parsed_group.as_ref());
+        let started = Instant::now();
+        let rows = query_builder.build_query_as::<ImageMetadata>().fetch_all(&self.pool).await?;
+        let elapsed = started.elapsed();
 
-        if ["filename", "format"].contains(&final_sort_by) {
-            query_builder.push(" COLLATE NOCASE ");
-        }
-        query_builder.push(" ");
-        query_builder.push(final_order);
+        Ok(FilterExplanation {
+            sql: query_builder.sql().to_string(),
+            plan,
+            duration_ms: elapsed.as_secs_f64() * 1000.0,
+            row_count: rows.len(),
+        })
+    }
+}
+
+/// Generated SQL, query plan and timing for a grid filter.
+#[derive(Debug, Serialize, Clone)]
+pub struct FilterExplanation {
+    /// The SQL sent to SQLite, with `?` placeholders for bound values.
+    pub sql: String,
+    /// `EXPLAIN QUERY PLAN` output, one indented line per step.
+    pub plan: Vec<String>,
+    /// Wall time of a real execution of the query.
+    pub duration_ms: f64,
+    /// Number of rows returned by that execution.
+    pub row_count: usize,
+}
+
+/// Logs queries that exceed `SLOW_QUERY_THRESHOLD`.
+fn log_if_slow(label: &str, sql: &str, elapsed: Duration) {
+    if elapsed >= SLOW_QUERY_THRESHOLD {
+        let compact = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+        eprintln!("WARN: Slow query {} took {:?}: {}", label, elapsed, compact);
+    }
+}
+
+/// Builds the paginated grid query, optionally prefixed (e.g. with `EXPLAIN QUERY PLAN`).
+#[allow(clippy::too_many_arguments)]
+fn build_images_query<'a>(
+    prefix: &str,
+    limit: i32,
+    offset: i32,
+    tag_ids: &'a [i64],
+    match_all: bool,
+    untagged: Option<bool>,
+    folder_id: Option<i64>,
+    recursive: bool,
+    collection_id: Option<i64>,
+    sort_by: Option<&str>,
+    sort_order: Option<&str>,
+    group: Option<&'a SearchGroup>,
+    search_query: Option<&str>,
+) -> sqlx::QueryBuilder<'a, sqlx::Sqlite> {
+    let mut query_builder = new_folder_scoped_query(prefix, folder_id, recursive);
+
+    query_builder.push(format!(
+        " SELECT {} FROM images i LEFT JOIN playback_positions pp ON pp.image_id = i.id ",
+        IMAGE_COLUMNS
+    ));
+
+    query_builder.push(" WHERE 1=1 ");
 
-        if final_sort_by != "filename" {
-            query_builder.push(", filename COLLATE NOCASE ASC");
+    push_filter_conditions(
+        &mut query_builder,
+        tag_ids,
+        match_all,
+        untagged,
+        folder_id,
+        recursive,
+        collection_id,
+        group,
+        search_query,
+    );
+
+    push_order_by(&mut query_builder, sort_by, sort_order, collection_id);
+
+    query_builder.push(" LIMIT ");
+    query_builder.push_bind(limit);
+    query_builder.push(" OFFSET ");
+    query_builder.push_bind(offset);
+
+    query_builder
+}
+
+/// Starts a query with the `target_folders` CTE used by folder filters.
+///
+/// When no folder is selected the CTE is empty and never referenced.
+fn new_folder_scoped_query<'a>(prefix: &str, folder_id: Option<i64>, recursive: bool) -> sqlx::QueryBuilder<'a, sqlx::Sqlite> {
+    let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(format!(
+        "{}WITH RECURSIVE target_folders AS (
+           SELECT id FROM folders WHERE id = ",
+        prefix
+    ));
+
+    if let Some(fid) = folder_id {
+        query_builder.push_bind(fid);
+        if recursive {
+            query_builder.push(" UNION ALL SELECT f.id FROM folders f JOIN target_folders tf ON f.parent_id = tf.id");
         }
+    } else {
+         query_builder.push(" -1 ");
+    }
 
-        query_builder.push(" LIMIT ");
-        query_builder.push_bind(limit);
-        query_builder.push(" OFFSET ");
-        query_builder.push_bind(offset);
+    query_builder.push(") ");
+    query_builder
+}
 
-        let images = query_builder.build_query_as::<ImageMetadata>().fetch_all(&self.pool).await?;
-        Ok(images)
+/// Appends the grid ORDER BY, falling back to id for unknown columns.
+/// `position` is the manual order of the collection being shown.
+fn push_order_by(
+    query_builder: &mut sqlx::QueryBuilder<'_, sqlx::Sqlite>,
+    sort_by: Option<&str>,
+    sort_order: Option<&str>,
+    collection_id: Option<i64>,
+) {
+    let allowed_cols = ["filename", "created_at", "modified_at", "added_at", "size", "format", "rating", "duration", "captured_at"];
+    let final_order = sort_order.filter(|o| *o == "asc" || *o == "desc").unwrap_or("desc");
+
+    if let (Some("position"), Some(collection_id)) = (sort_by, collection_id) {
+        query_builder.push(" ORDER BY (SELECT ci.position FROM collection_items ci WHERE ci.collection_id = ");
+        query_builder.push_bind(collection_id);
+        query_builder.push(" AND ci.image_id = i.id) ");
+        query_builder.push(final_order);
+        query_builder.push(", i.id ");
+        query_builder.push(final_order);
+        return;
     }
 
-    /// Gets the total count of images matching the search criteria.
-    #[allow(clippy::too_many_arguments)]
-    pub async fn get_image_count_filtered(
-        &self,
-        tag_ids: Vec<i64>,
-        match_all: bool,
-        untagged: Option<bool>,
-        folder_id: Option<i64>,
-        recursive: bool,
-        advanced_query: Option<String>,
-        search_query: Option<String>,
-    ) -> Result<i64, sqlx::Error> {
-        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
-            "WITH RECURSIVE target_folders AS (
-               SELECT id FROM folders WHERE id = "
-        );
+    let final_sort_by = sort_by.filter(|c| allowed_cols.contains(c)).unwrap_or("id");
 
-        if let Some(fid) = folder_id {
-            query_builder.push_bind(fid);
-            if recursive {
-                query_builder.push(" UNION ALL SELECT f.id FROM folders f JOIN target_folders tf ON f.parent_id = tf.id");
-            }
-        } else {
-             query_builder.push(" -1 ");
-        }
+    query_builder.push(" ORDER BY (");
+    query_builder.push(final_sort_by);
+    query_builder.push(" IS NULL) ASC, ");
 
-        query_builder.push(") SELECT DISTINCT i.id FROM images i ");
+    // Filenames follow the library's locale, through their indexed sort key
+    // (see `db::collation`)
+    if final_sort_by == "filename" {
+        query_builder.push("filename_sort_key ");
+    } else if final_sort_by == "format" {
+        query_builder.push("format COLLATE NOCASE ");
+    } else {
+        query_builder.push(final_sort_by);
+        query_builder.push(" ");
+    }
+    query_builder.push(final_order);
 
-        if !tag_ids.is_empty() {
-            query_builder.push(" JOIN image_tags it ON i.id = it.image_id ");
-        }
+    if final_sort_by != "filename" {
+        query_builder.push(", filename_sort_key ASC");
+    }
+}
 
-        query_builder.push(" WHERE 1=1 ");
+/// Appends the WHERE conditions shared by the grid and count queries.
+#[allow(clippy::too_many_arguments)]
+fn push_filter_conditions<'a>(
+    query_builder: &mut sqlx::QueryBuilder<'a, sqlx::Sqlite>,
+    tag_ids: &'a [i64],
+    match_all: bool,
+    untagged: Option<bool>,
+    folder_id: Option<i64>,
+    recursive: bool,
+    collection_id: Option<i64>,
+    group: Option<&'a SearchGroup>,
+    search_query: Option<&str>,
+) {
+    if let Some(collection_id) = collection_id {
+        query_builder.push(" AND i.id IN (SELECT image_id FROM collection_items WHERE collection_id = ");
+        query_builder.push_bind(collection_id);
+        query_builder.push(") ");
+    }
 
-        let parsed_group = advanced_query.as_ref().and_then(|q| serde_json::from_str::<SearchGroup>(q).ok());
-        if let Some(ref group) = parsed_group {
-            query_builder.push(" AND ");
-            build_where_clause(group, &mut query_builder);
-        }
+    if let Some(group) = group {
+        query_builder.push(" AND ");
+        build_where_clause(group, query_builder);
+    }
 
-        if let Some(search) = search_query {
-            if !search.is_empty() {
-                query_builder.push(" AND (i.filename LIKE ");
-                query_builder.push_bind(format!("%{}%", search));
-                query_builder.push(" OR i.notes LIKE ");
-                query_builder.push_bind(format!("%{}%", search));
-                query_builder.push(") ");
+    if let Some(search) = search_query {
+        if !search.is_empty() {
+            query_builder.push(" AND (i.filename LIKE ");
+            query_builder.push_bind(format!("%{}%", search));
+            query_builder.push(" OR i.notes LIKE ");
+            query_builder.push_bind(format!("%{}%", search));
+            query_builder.push(" OR EXISTS (SELECT 1 FROM image_annotations a WHERE a.image_id = i.id AND a.body LIKE ");
+            query_builder.push_bind(format!("%{}%", search));
+            query_builder.push(")");
+            // The trigram index ignores accents, so "cafe" finds `Café.jpg`;
+            // it needs three characters to match anything
+            if search.chars().count() >= 3 {
+                query_builder.push(" OR i.id IN (SELECT rowid FROM images_fts WHERE images_fts MATCH ");
+                query_builder.push_bind(fts_phrase(search));
+                query_builder.push(")");
             }
-        }
-
-        if let Some(fid) = folder_id {
-            if recursive {
-                query_builder.push(" AND i.folder_id IN target_folders ");
-            } else {
-                query_builder.push(" AND i.folder_id = ");
-                query_builder.push_bind(fid);
+            // Words of the filename, so "hero banner" finds `Hero_Banner_v03.psd`
+            if let Some(keywords) = super::keywords::keyword_match_query(search) {
+                query_builder.push(" OR i.id IN (SELECT rowid FROM images_keywords_fts WHERE images_keywords_fts MATCH ");
+                query_builder.push_bind(keywords);
+                query_builder.push(")");
             }
+            query_builder.push(") ");
         }
+    }
 
-        if untagged == Some(true) {
-            query_builder.push(" AND i.id NOT IN (SELECT DISTINCT image_id FROM image_tags) ");
+    if let Some(fid) = folder_id {
+        if recursive {
+            query_builder.push(" AND i.folder_id IN target_folders ");
+        } else {
+            query_builder.push(" AND i.folder_id = ");
+            query_builder.push_bind(fid);
         }
+    }
 
-        if !tag_ids.is_empty() {
-            query_builder.push(" AND it.tag_id IN (");
+    if untagged == Some(true) {
+        query_builder.push(" AND NOT EXISTS (SELECT 1 FROM image_tags it WHERE it.image_id = i.id) ");
+    }
+
+    // Frame sequences are listed once, through their representative frame,
+    // while browsing; a search or filter lists every frame it matches
+    let searching = search_query.is_some_and(|search| !search.is_empty());
+    if !searching && group.is_none() && tag_ids.is_empty() && untagged != Some(true) {
+        query_builder.push(" AND (i.sequence_id IS NULL OR i.id IN (SELECT s.representative_image_id FROM image_sequences s WHERE s.id = i.sequence_id)) ");
+    }
+
+    if !tag_ids.is_empty() {
+        if match_all {
+            // One EXISTS probe per tag hits the (image_id, tag_id) primary key
+            // directly, instead of joining and grouping every tagged row.
+            for id in tag_ids {
+                query_builder.push(" AND EXISTS (SELECT 1 FROM image_tags it WHERE it.image_id = i.id AND it.tag_id = ");
+                query_builder.push_bind(id);
+                query_builder.push(") ");
+            }
+        } else {
+            query_builder.push(" AND i.id IN (SELECT it.image_id FROM image_tags it WHERE it.tag_id IN (");
             let mut separated = query_builder.separated(", ");
-            for id in &tag_ids {
+            for id in tag_ids {
                 separated.push_bind(id);
             }
-            separated.push_unseparated(") ");
-
-            if match_all {
-                query_builder.push(" GROUP BY i.id HAVING COUNT(DISTINCT it.tag_id) = ");
-                query_builder.push_bind(tag_ids.len() as i32);
-            }
+            separated.push_unseparated(")) ");
         }
-
-        // Fetch only IDs to count rows (most efficient way to count DISTINCT with HAVING in SQLx builder)
-        let rows = query_builder.build_query_as::<(i64,)>().fetch_all(&self.pool).await?;
-        Ok(rows.len() as i64)
     }
 }
 
@@ -258,18 +540,77 @@ pub fn build_where_clause<'a>(group: &'a SearchGroup, query_builder: &mut sqlx::
     query_builder.push(") ");
 }
 
+/// Keys `build_criterion_clause` understands; any other matches everything.
+pub const CRITERION_KEYS: &[&str] = &[
+    "filename", "notes", "format", "size", "width", "height", "rating", "duration", "fps", "bitrate", "frame_count",
+    "codec", "has_audio", "loops", "added_at", "created_at", "modified_at", "captured_at", "annotations", "related_to",
+    "tags", "folder", "version", "stock", "working_set", "location",
+    "collection",
+];
+
+/// Length of a degree of latitude, for `location` searches.
+const KM_PER_DEGREE: f64 = 111.32;
+
+/// Operators that apply to any key.
+pub const AUDIT_OPERATORS: &[&str] = &["has_no_thumbnail", "has_thumbnail"];
+
 fn build_criterion_clause<'a>(c: &'a SearchCriterion, query_builder: &mut sqlx::QueryBuilder<'a, sqlx::Sqlite>) {
+    // Thumbnail state is an audit operator usable on any key
+    match c.operator.as_str() {
+        "has_no_thumbnail" => {
+            query_builder.push(" i.thumbnail_path IS NULL ");
+            return;
+        },
+        "has_thumbnail" => {
+            query_builder.push(" i.thumbnail_path IS NOT NULL ");
+            return;
+        },
+        _ => {},
+    }
+
     match c.key.as_str() {
         "filename" | "notes" | "format" => {
             let is_fts_target = c.key == "filename" || c.key == "notes";
 
             match c.operator.as_str() {
+                "is_empty" => {
+                    query_builder.push(" (i.");
+                    query_builder.push(&c.key);
+                    query_builder.push(" IS NULL OR i.");
+                    query_builder.push(&c.key);
+                    query_builder.push(" = '') ");
+                },
+                "is_not_empty" => {
+                    query_builder.push(" (i.");
+                    query_builder.push(&c.key);
+                    query_builder.push(" IS NOT NULL AND i.");
+                    query_builder.push(&c.key);
+                    query_builder.push(" != '') ");
+                },
+                "matches_regex" => {
+                    let pattern = c.value.as_str().unwrap_or("");
+                    // An invalid pattern would abort the whole query inside SQLite
+                    if regex::Regex::new(pattern).is_ok() {
+                        query_builder.push(" i.");
+                        query_builder.push(&c.key);
+                        query_builder.push(" REGEXP ");
+                        query_builder.push_bind(pattern);
+                    } else {
+                        query_builder.push(" 1=0 ");
+                    }
+                },
+                "glob" => {
+                    query_builder.push(" i.");
+                    query_builder.push(&c.key);
+                    query_builder.push(" GLOB ");
+                    query_builder.push_bind(c.value.as_str().unwrap_or(""));
+                },
                 "contains" => {
                     if is_fts_target {
                         query_builder.push(" i.id IN (SELECT rowid FROM images_fts WHERE ");
                         query_builder.push(&c.key);
                         query_builder.push(" MATCH ");
-                        query_builder.push_bind(format!("\"{}\"", c.value.as_str().unwrap_or("")));
+                        query_builder.push_bind(fts_phrase(c.value.as_str().unwrap_or("")));
                         query_builder.push(") ");
                     } else {
                         query_builder.push(" i.");
@@ -283,7 +624,7 @@ fn build_criterion_clause<'a>(c: &'a SearchCriterion, query_builder: &mut sqlx::
                         query_builder.push(" i.id NOT IN (SELECT rowid FROM images_fts WHERE ");
                         query_builder.push(&c.key);
                         query_builder.push(" MATCH ");
-                        query_builder.push_bind(format!("\"{}\"", c.value.as_str().unwrap_or("")));
+                        query_builder.push_bind(fts_phrase(c.value.as_str().unwrap_or("")));
                         query_builder.push(") ");
                      } else {
                         query_builder.push(" i.");
@@ -338,6 +679,24 @@ fn build_criterion_clause<'a>(c: &'a SearchCriterion, query_builder: &mut sqlx::
                 _ => { query_builder.push(" 1=1 "); },
             }
         },
+        "size" | "width" | "height" | "rating" if matches!(c.operator.as_str(), "is_empty" | "is_not_empty") => {
+            // An unrated image stores 0 rather than NULL, so both mean "empty" for rating
+            let empty_clause = if c.key == "rating" {
+                " (i.rating IS NULL OR i.rating = 0) "
+            } else {
+                match c.key.as_str() {
+                    "size" => " i.size IS NULL ",
+                    "width" => " i.width IS NULL ",
+                    _ => " i.height IS NULL ",
+                }
+            };
+            if c.operator == "is_empty" {
+                query_builder.push(empty_clause);
+            } else {
+                query_builder.push(" NOT");
+                query_builder.push(empty_clause);
+            }
+        },
         "size" | "width" | "height" | "rating" => {
             query_builder.push(" i.");
             query_builder.push(&c.key);
@@ -360,7 +719,71 @@ fn build_criterion_clause<'a>(c: &'a SearchCriterion, query_builder: &mut sqlx::
                 _ => { query_builder.push(" = 1 "); },
             }
         },
-        "added_at" | "created_at" | "modified_at" => {
+        "duration" | "fps" | "bitrate" | "frame_count" => {
+            // Media columns are NULL until the media info worker has probed the file
+            query_builder.push(" i.");
+            query_builder.push(&c.key);
+            match c.operator.as_str() {
+                "gt" => { query_builder.push(" > "); query_builder.push_bind(c.value.as_f64().unwrap_or(0.0)); },
+                "lt" => { query_builder.push(" < "); query_builder.push_bind(c.value.as_f64().unwrap_or(0.0)); },
+                "eq" => { query_builder.push(" = "); query_builder.push_bind(c.value.as_f64().unwrap_or(0.0)); },
+                "gte" => { query_builder.push(" >= "); query_builder.push_bind(c.value.as_f64().unwrap_or(0.0)); },
+                "lte" => { query_builder.push(" <= "); query_builder.push_bind(c.value.as_f64().unwrap_or(0.0)); },
+                "between" => {
+                    if let Some(arr) = c.value.as_array() {
+                        if arr.len() == 2 {
+                            query_builder.push(" BETWEEN ");
+                            query_builder.push_bind(arr[0].as_f64().unwrap_or(0.0));
+                            query_builder.push(" AND ");
+                            query_builder.push_bind(arr[1].as_f64().unwrap_or(0.0));
+                        } else { query_builder.push(" = 1 "); }
+                    } else { query_builder.push(" = 1 "); }
+                },
+                _ => { query_builder.push(" = 1 "); },
+            }
+        },
+        "codec" => {
+            let codec = normalize_codec_name(c.value.as_str().unwrap_or(""));
+            match c.operator.as_str() {
+                "equals" | "eq" | "is" => {
+                    query_builder.push(" (i.video_codec = ");
+                    query_builder.push_bind(codec.clone());
+                    query_builder.push(" OR i.audio_codec = ");
+                    query_builder.push_bind(codec);
+                    query_builder.push(") ");
+                },
+                "not_equals" | "is_not" => {
+                    query_builder.push(" (COALESCE(i.video_codec, '') != ");
+                    query_builder.push_bind(codec.clone());
+                    query_builder.push(" AND COALESCE(i.audio_codec, '') != ");
+                    query_builder.push_bind(codec);
+                    query_builder.push(") ");
+                },
+                "contains" => {
+                    query_builder.push(" (i.video_codec LIKE ");
+                    query_builder.push_bind(format!("%{}%", codec));
+                    query_builder.push(" OR i.audio_codec LIKE ");
+                    query_builder.push_bind(format!("%{}%", codec));
+                    query_builder.push(") ");
+                },
+                _ => { query_builder.push(" 1=1 "); },
+            }
+        },
+        "has_audio" | "loops" => {
+            let wanted = c.value.as_bool()
+                .or_else(|| c.value.as_str().map(|v| v == "true"))
+                .unwrap_or(true);
+            match c.operator.as_str() {
+                "is" | "eq" | "equals" => {
+                    query_builder.push(" i.");
+                    query_builder.push(&c.key);
+                    query_builder.push(" = ");
+                    query_builder.push_bind(wanted);
+                },
+                _ => { query_builder.push(" 1=1 "); },
+            }
+        },
+        "added_at" | "created_at" | "modified_at" | "captured_at" => {
             query_builder.push(" i.");
             query_builder.push(&c.key);
             let val = c.value.as_str().unwrap_or("");
@@ -384,6 +807,75 @@ fn build_criterion_clause<'a>(c: &'a SearchCriterion, query_builder: &mut sqlx::
                 _ => { query_builder.push(" = 1 "); },
             }
         },
+        "annotations" => {
+            let pattern = format!("%{}%", c.value.as_str().unwrap_or(""));
+            match c.operator.as_str() {
+                "contains" => {
+                    query_builder.push(" EXISTS (SELECT 1 FROM image_annotations a WHERE a.image_id = i.id AND a.body LIKE ");
+                    query_builder.push_bind(pattern);
+                    query_builder.push(") ");
+                },
+                "not_contains" => {
+                    query_builder.push(" NOT EXISTS (SELECT 1 FROM image_annotations a WHERE a.image_id = i.id AND a.body LIKE ");
+                    query_builder.push_bind(pattern);
+                    query_builder.push(") ");
+                },
+                "is_empty" => { query_builder.push(" NOT EXISTS (SELECT 1 FROM image_annotations a WHERE a.image_id = i.id) "); },
+                "is_not_empty" => { query_builder.push(" EXISTS (SELECT 1 FROM image_annotations a WHERE a.image_id = i.id) "); },
+                _ => { query_builder.push(" 1=1 "); },
+            }
+        },
+        "related_to" => {
+            // Cross-links are symmetric: match images linking to or linked from the given one
+            let image_id = c.value.as_i64().or_else(|| c.value.as_str().and_then(|s| s.parse::<i64>().ok()));
+            match (c.operator.as_str(), image_id) {
+                ("is" | "eq" | "equals", Some(id)) => {
+                    query_builder.push(" (i.id IN (SELECT related_image_id FROM image_annotations WHERE image_id = ");
+                    query_builder.push_bind(id);
+                    query_builder.push(") OR i.id IN (SELECT image_id FROM image_annotations WHERE related_image_id = ");
+                    query_builder.push_bind(id);
+                    query_builder.push(")) ");
+                },
+                _ => { query_builder.push(" 1=1 "); },
+            }
+        },
+        "working_set" => {
+            // A set popped since the query was written matches nothing
+            let set_id = c.value.as_u64().or_else(|| c.value.as_str().and_then(|s| s.parse::<u64>().ok()));
+            let ids_json = set_id.and_then(|id| crate::library::working_sets::lock().ids_json(id));
+            match (c.operator.as_str(), ids_json) {
+                ("in", Some(ids_json)) => {
+                    query_builder.push(" i.id IN (SELECT value FROM json_each(");
+                    query_builder.push_bind(ids_json.to_string());
+                    query_builder.push(")) ");
+                },
+                ("not_in", Some(ids_json)) => {
+                    query_builder.push(" i.id NOT IN (SELECT value FROM json_each(");
+                    query_builder.push_bind(ids_json.to_string());
+                    query_builder.push(")) ");
+                },
+                ("in", None) => { query_builder.push(" 1=0 "); },
+                _ => { query_builder.push(" 1=1 "); },
+            }
+        },
+        "collection" => {
+            let collection_id = c.value.as_i64().or_else(|| c.value.as_str().and_then(|s| s.parse::<i64>().ok()));
+            match (c.operator.as_str(), collection_id) {
+                ("in", Some(id)) => {
+                    query_builder.push(" i.id IN (SELECT image_id FROM collection_items WHERE collection_id = ");
+                    query_builder.push_bind(id);
+                    query_builder.push(") ");
+                },
+                ("not_in", Some(id)) => {
+                    query_builder.push(" i.id NOT IN (SELECT image_id FROM collection_items WHERE collection_id = ");
+                    query_builder.push_bind(id);
+                    query_builder.push(") ");
+                },
+                ("is_empty", _) => { query_builder.push(" i.id NOT IN (SELECT image_id FROM collection_items) "); },
+                ("is_not_empty", _) => { query_builder.push(" i.id IN (SELECT image_id FROM collection_items) "); },
+                _ => { query_builder.push(" 1=1 "); },
+            }
+        },
         "tags" => {
             let tag_id = c.value.as_str().and_then(|s| s.parse::<i64>().ok()).or_else(|| c.value.as_i64());
             match c.operator.as_str() {
@@ -410,6 +902,15 @@ fn build_criterion_clause<'a>(c: &'a SearchCriterion, query_builder: &mut sqlx::
                     query_builder.push(" i.folder_id = ");
                     query_builder.push_bind(c.value.as_i64().unwrap_or(0));
                 },
+                "not_is" => {
+                    query_builder.push(" i.folder_id != ");
+                    query_builder.push_bind(c.value.as_i64().unwrap_or(0));
+                },
+                "not_in" | "not_in_folder" => {
+                     query_builder.push(" i.folder_id NOT IN (WITH RECURSIVE subfolders AS (SELECT id, 0 as depth FROM folders WHERE id = ");
+                     query_builder.push_bind(c.value.as_i64().unwrap_or(0));
+                     query_builder.push(" UNION ALL SELECT f.id, s.depth + 1 FROM folders f JOIN subfolders s ON f.parent_id = s.id WHERE s.depth < 50) SELECT id FROM subfolders) ");
+                },
                 "in" => {
                      query_builder.push(" i.folder_id IN (WITH RECURSIVE subfolders AS (SELECT id, 0 as depth FROM folders WHERE id = ");
                      query_builder.push_bind(c.value.as_i64().unwrap_or(0));
@@ -418,6 +919,487 @@ fn build_criterion_clause<'a>(c: &'a SearchCriterion, query_builder: &mut sqlx::
                 _ => { query_builder.push(" 1=1 "); },
             }
         },
+        // Version chains (`hero_v01.psd` … `hero_final.psd`); a chain whose
+        // latest file is gone shows all its versions until it is re-detected
+        "version" => {
+            match c.operator.as_str() {
+                "latest" => {
+                    query_builder.push(" (i.version_chain_id IS NULL OR NOT EXISTS (SELECT 1 FROM version_chains v WHERE v.id = i.version_chain_id AND v.latest_image_id IS NOT NULL AND v.latest_image_id != i.id)) ");
+                },
+                "superseded" => {
+                    query_builder.push(" EXISTS (SELECT 1 FROM version_chains v WHERE v.id = i.version_chain_id AND v.latest_image_id IS NOT NULL AND v.latest_image_id != i.id) ");
+                },
+                "is_not_empty" => { query_builder.push(" i.version_chain_id IS NOT NULL "); },
+                "is_empty" => { query_builder.push(" i.version_chain_id IS NULL "); },
+                _ => { query_builder.push(" 1=1 "); },
+            }
+        },
+        "stock" => {
+            match c.operator.as_str() {
+                "is_not_empty" => { query_builder.push(" i.id IN (SELECT image_id FROM stock_assets) "); },
+                "is_empty" => { query_builder.push(" i.id NOT IN (SELECT image_id FROM stock_assets) "); },
+                "is" | "eq" | "equals" => {
+                    query_builder.push(" i.id IN (SELECT image_id FROM stock_assets WHERE provider = ");
+                    query_builder.push_bind(c.value.as_str().unwrap_or(""));
+                    query_builder.push(") ");
+                },
+                // Compliance audit: stock assets with neither usage terms nor a license page
+                "missing_license" => {
+                    query_builder.push(" i.id IN (SELECT image_id FROM stock_assets WHERE COALESCE(usage_terms, '') = '' AND COALESCE(license_url, '') = '') ");
+                },
+                _ => { query_builder.push(" 1=1 "); },
+            }
+        },
+        // GPS position, for map views: `within_bounds` takes the visible
+        // `{ south, west, north, east }` (west above east crosses the 180th
+        // meridian), `near` a `{ latitude, longitude, radiusKm }` circle
+        "location" => {
+            let field = |name: &str| {
+                c.value.get(name).and_then(|v| v.as_f64().or_else(|| v.as_str().and_then(|s| s.trim().parse().ok())))
+            };
+            match c.operator.as_str() {
+                "is_not_empty" => { query_builder.push(" i.latitude IS NOT NULL "); },
+                "is_empty" => { query_builder.push(" i.latitude IS NULL "); },
+                "within_bounds" => match (field("south"), field("west"), field("north"), field("east")) {
+                    (Some(south), Some(west), Some(north), Some(east)) if south <= north => {
+                        query_builder.push(" (i.latitude BETWEEN ");
+                        query_builder.push_bind(south);
+                        query_builder.push(" AND ");
+                        query_builder.push_bind(north);
+                        if west <= east {
+                            query_builder.push(" AND i.longitude BETWEEN ");
+                            query_builder.push_bind(west);
+                            query_builder.push(" AND ");
+                            query_builder.push_bind(east);
+                        } else {
+                            query_builder.push(" AND (i.longitude >= ");
+                            query_builder.push_bind(west);
+                            query_builder.push(" OR i.longitude <= ");
+                            query_builder.push_bind(east);
+                            query_builder.push(")");
+                        }
+                        query_builder.push(") ");
+                    },
+                    _ => { query_builder.push(" 1=0 "); },
+                },
+                "near" => match (field("latitude"), field("longitude"), field("radiusKm")) {
+                    (Some(latitude), Some(longitude), Some(radius)) if radius >= 0.0 && latitude.abs() <= 90.0 => {
+                        // Equirectangular distance: close enough at the scale
+                        // of a map view, and needs no SQL math functions
+                        let span = radius / KM_PER_DEGREE;
+                        let scale = latitude.to_radians().cos().max(0.01);
+                        query_builder.push(" (i.latitude BETWEEN ");
+                        query_builder.push_bind(latitude - span);
+                        query_builder.push(" AND ");
+                        query_builder.push_bind(latitude + span);
+                        query_builder.push(" AND (i.latitude - ");
+                        query_builder.push_bind(latitude);
+                        query_builder.push(") * (i.latitude - ");
+                        query_builder.push_bind(latitude);
+                        query_builder.push(") + MIN(ABS(i.longitude - ");
+                        query_builder.push_bind(longitude);
+                        query_builder.push("), 360 - ABS(i.longitude - ");
+                        query_builder.push_bind(longitude);
+                        query_builder.push(")) * MIN(ABS(i.longitude - ");
+                        query_builder.push_bind(longitude);
+                        query_builder.push("), 360 - ABS(i.longitude - ");
+                        query_builder.push_bind(longitude);
+                        query_builder.push(")) * ");
+                        query_builder.push_bind(scale * scale);
+                        query_builder.push(" <= ");
+                        query_builder.push_bind(span * span);
+                        query_builder.push(") ");
+                    },
+                    _ => { query_builder.push(" 1=0 "); },
+                },
+                _ => { query_builder.push(" 1=1 "); },
+            }
+        },
         _ => { query_builder.push(" 1=1 "); },
     }
 }
+
+/// Quotes `text` as an FTS5 phrase, so its punctuation isn't read as query
+/// syntax.
+fn fts_phrase(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+/// Maps user-facing codec names ("H.264", "H265") to FFprobe's codec names.
+fn normalize_codec_name(name: &str) -> String {
+    let compact: String = name
+        .to_lowercase()
+        .chars()
+        .filter(|ch| !matches!(ch, '.' | '-' | ' '))
+        .collect();
+
+    match compact.as_str() {
+        "avc" | "avc1" | "x264" => "h264".to_string(),
+        "h265" | "x265" | "hvc1" => "hevc".to_string(),
+        "prores422" | "proreshq" => "prores".to_string(),
+        _ => compact,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::TestLibrary;
+
+    fn criterion(key: &str, operator: &str, value: serde_json::Value) -> SearchCriterion {
+        SearchCriterion {
+            id: "c1".to_string(),
+            key: key.to_string(),
+            operator: operator.to_string(),
+            value,
+        }
+    }
+
+    fn render(c: &SearchCriterion) -> String {
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new("");
+        build_criterion_clause(c, &mut query_builder);
+        query_builder.sql().split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn test_notes_empty_operators() {
+        let c = criterion("notes", "is_empty", serde_json::Value::Null);
+        assert_eq!(render(&c), "(i.notes IS NULL OR i.notes = '')");
+
+        let c = criterion("notes", "is_not_empty", serde_json::Value::Null);
+        assert_eq!(render(&c), "(i.notes IS NOT NULL AND i.notes != '')");
+    }
+
+    #[test]
+    fn test_rating_empty_treats_zero_as_unrated() {
+        let c = criterion("rating", "is_empty", serde_json::Value::Null);
+        assert_eq!(render(&c), "(i.rating IS NULL OR i.rating = 0)");
+
+        let c = criterion("rating", "is_not_empty", serde_json::Value::Null);
+        assert_eq!(render(&c), "NOT (i.rating IS NULL OR i.rating = 0)");
+    }
+
+    #[test]
+    fn test_not_in_folder_is_recursive() {
+        let c = criterion("folder", "not_in_folder", serde_json::json!(7));
+        let sql = render(&c);
+        assert!(sql.starts_with("i.folder_id NOT IN (WITH RECURSIVE subfolders"));
+        assert!(sql.contains("WHERE id = ?"));
+    }
+
+    #[test]
+    fn test_working_set() {
+        let set = crate::library::working_sets::lock().push(vec![3, 5], None).unwrap();
+        let c = criterion("working_set", "in", serde_json::json!(set.id.to_string()));
+        assert_eq!(render(&c), "i.id IN (SELECT value FROM json_each(?))");
+
+        let c = criterion("working_set", "in", serde_json::json!(u64::MAX));
+        assert_eq!(render(&c), "1=0");
+        let c = criterion("working_set", "not_in", serde_json::json!(u64::MAX));
+        assert_eq!(render(&c), "1=1");
+    }
+
+    #[test]
+    fn test_has_no_thumbnail_ignores_key() {
+        let c = criterion("thumbnail", "has_no_thumbnail", serde_json::Value::Null);
+        assert_eq!(render(&c), "i.thumbnail_path IS NULL");
+
+        let c = criterion("filename", "has_no_thumbnail", serde_json::Value::Null);
+        assert_eq!(render(&c), "i.thumbnail_path IS NULL");
+    }
+
+    #[test]
+    fn test_stock_missing_license() {
+        let c = criterion("stock", "missing_license", serde_json::Value::Null);
+        assert_eq!(
+            render(&c),
+            "i.id IN (SELECT image_id FROM stock_assets WHERE COALESCE(usage_terms, '') = '' AND COALESCE(license_url, '') = '')"
+        );
+
+        let c = criterion("stock", "is", serde_json::json!("Adobe Stock"));
+        assert_eq!(render(&c), "i.id IN (SELECT image_id FROM stock_assets WHERE provider = ? )");
+    }
+
+    #[test]
+    fn test_media_keys() {
+        let c = criterion("duration", "gt", serde_json::json!(600));
+        assert_eq!(render(&c), "i.duration > ?");
+
+        let c = criterion("frame_count", "lte", serde_json::json!(24));
+        assert_eq!(render(&c), "i.frame_count <= ?");
+
+        let c = criterion("loops", "is", serde_json::json!(true));
+        assert_eq!(render(&c), "i.loops = ?");
+
+        let c = criterion("codec", "eq", serde_json::json!("H.264"));
+        assert_eq!(render(&c), "(i.video_codec = ? OR i.audio_codec = ?)");
+        assert_eq!(normalize_codec_name("H.264"), "h264");
+        assert_eq!(normalize_codec_name("H265"), "hevc");
+    }
+
+    #[test]
+    fn test_pattern_operators() {
+        let c = criterion("filename", "matches_regex", serde_json::json!(r"IMG_\d{4}\.cr3"));
+        assert_eq!(render(&c), "i.filename REGEXP ?");
+
+        let c = criterion("filename", "matches_regex", serde_json::json!("IMG_(unclosed"));
+        assert_eq!(render(&c), "1=0");
+
+        let c = criterion("filename", "glob", serde_json::json!("IMG_[0-9]*.CR3"));
+        assert_eq!(render(&c), "i.filename GLOB ?");
+    }
+
+    /// Opens a test library named after `name`, seeded with `count` images
+    /// spread over 10 folders, tagged deterministically with up to 5 tags.
+    async fn seeded_db(name: &str, count: i64) -> TestLibrary {
+        let library = TestLibrary::open(name).await;
+        let mut tx = library.db.pool.begin().await.unwrap();
+        for folder in 1..=10 {
+            sqlx::query("INSERT INTO folders (id, path, name) VALUES (?, ?, ?)")
+                .bind(folder)
+                .bind(format!("/lib/{}", folder))
+                .bind(folder.to_string())
+                .execute(&mut *tx).await.unwrap();
+        }
+        for tag in 1..=5 {
+            sqlx::query("INSERT INTO tags (id, name) VALUES (?, ?)")
+                .bind(tag)
+                .bind(format!("tag{}", tag))
+                .execute(&mut *tx).await.unwrap();
+        }
+        for id in 1..=count {
+            sqlx::query("INSERT INTO images (id, folder_id, path, filename, size, format, rating, created_at, modified_at)
+                         VALUES (?, ?, ?, ?, ?, 'jpg', ?, '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')")
+                .bind(id)
+                .bind(id % 10 + 1)
+                .bind(format!("/lib/{}/img{}.jpg", id % 10 + 1, id))
+                .bind(format!("img{}.jpg", id))
+                .bind(id * 10)
+                .bind(id % 6)
+                .execute(&mut *tx).await.unwrap();
+            for tag in 1..=5 {
+                if id % (tag + 1) == 0 {
+                    sqlx::query("INSERT INTO image_tags (image_id, tag_id) VALUES (?, ?)")
+                        .bind(id)
+                        .bind(tag)
+                        .execute(&mut *tx).await.unwrap();
+                }
+            }
+        }
+        tx.commit().await.unwrap();
+        library
+    }
+
+    #[tokio::test]
+    async fn test_match_all_matches_legacy_group_by() {
+        let library = seeded_db("search-match-all", 5000).await;
+        let db = &library.db;
+        let tag_ids = vec![1, 2];
+
+        // Legacy JOIN + GROUP BY/HAVING formulation, kept here as the baseline
+        let legacy: Vec<(i64,)> = sqlx::query_as(
+            "SELECT DISTINCT i.id FROM images i JOIN image_tags it ON i.id = it.image_id
+             WHERE it.tag_id IN (?, ?) GROUP BY i.id HAVING COUNT(DISTINCT it.tag_id) = 2 ORDER BY i.id"
+        )
+        .bind(1).bind(2)
+        .fetch_all(&db.pool).await.unwrap();
+
+        let count = db.get_image_count_filtered(tag_ids.clone(), true, None, None, false, None, None, None).await.unwrap();
+        let images = db.get_images_filtered(10_000, 0, tag_ids, true, None, None, false, None, None, Some("asc".into()), None, None).await.unwrap();
+
+        // Tagged with both: multiples of 2 and of 3
+        let mut ids: Vec<i64> = images.iter().map(|i| i.id).collect();
+        ids.sort_unstable();
+        assert_eq!(ids.len(), 833);
+        assert_eq!(ids, legacy.into_iter().map(|(id,)| id).collect::<Vec<_>>());
+        assert_eq!(count, ids.len() as i64);
+    }
+
+    #[tokio::test]
+    async fn test_match_any_and_untagged_counts() {
+        let library = seeded_db("search-match-any", 600).await;
+        let db = &library.db;
+
+        let any = db.get_image_count_filtered(vec![4, 5], false, None, None, false, None, None, None).await.unwrap();
+        let expected_any = (1..=600).filter(|id| id % 5 == 0 || id % 6 == 0).count() as i64;
+        assert_eq!(any, expected_any);
+
+        let untagged = db.get_image_count_filtered(vec![], false, Some(true), None, false, None, None, None).await.unwrap();
+        let expected_untagged = (1..=600).filter(|id| (2..=6).all(|d| id % d != 0)).count() as i64;
+        assert_eq!(untagged, expected_untagged);
+    }
+
+    #[tokio::test]
+    async fn test_search_matches_filename_words() {
+        let library = seeded_db("search-keywords", 20).await;
+        let db = &library.db;
+        sqlx::query("INSERT INTO images (id, folder_id, path, filename, size, format) VALUES (100, 1, '/lib/1/ClientX_Hero_Banner_v03_final.psd', 'ClientX_Hero_Banner_v03_final.psd', 1, 'psd')")
+            .execute(&db.pool).await.unwrap();
+        let pending = db.get_images_needing_keywords(1000).await.unwrap();
+        assert_eq!(pending.len(), 21, "rows inserted outside `save_image` wait for their keywords");
+        db.set_filename_keywords(&pending).await.unwrap();
+
+        let search = |query: &str| {
+            let query = query.to_string();
+            async move {
+                db.get_images_filtered(100, 0, vec![], false, None, None, false, None, None, None, None, Some(query))
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|i| i.id)
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(search("hero banner").await, vec![100]);
+        assert_eq!(search("banner client").await, vec![100]);
+        assert_eq!(search("ban").await, vec![100]);
+        assert!(search("hero poster").await.is_empty());
+
+        // A rename queues the file for new keywords
+        sqlx::query("UPDATE images SET filename = 'Poster.psd' WHERE id = 100").execute(&db.pool).await.unwrap();
+        let pending = db.get_images_needing_keywords(10).await.unwrap();
+        assert_eq!(pending, vec![(100, "Poster.psd".to_string())]);
+        db.set_filename_keywords(&pending).await.unwrap();
+        assert!(search("hero banner").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_accents_and_natural_sort() {
+        let library = seeded_db("search-natural-sort", 0).await;
+        let db = &library.db;
+        for (id, filename) in [(1, "img10.jpg"), (2, "Äpfel.jpg"), (3, "img2.jpg"), (4, "Café_Zürich.jpg"), (5, "Zebra.jpg")] {
+            sqlx::query("INSERT INTO images (id, folder_id, path, filename, size, format) VALUES (?, 1, ?, ?, 1, 'jpg')")
+                .bind(id)
+                .bind(format!("/lib/1/{}", filename))
+                .bind(filename)
+                .execute(&db.pool).await.unwrap();
+        }
+        let pending = db.get_images_needing_sort_key(100).await.unwrap();
+        assert_eq!(pending.len(), 5, "rows inserted outside `save_image` wait for their sort keys");
+        db.set_filename_sort_keys(&pending).await.unwrap();
+
+        let listed = |sort_by: &str, search: Option<&str>| {
+            let (sort_by, search) = (sort_by.to_string(), search.map(String::from));
+            async move {
+                db.get_images_filtered(100, 0, vec![], false, None, None, false, None, Some(sort_by), Some("asc".into()), None, search)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|i| i.id)
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(listed("filename", None).await, vec![2, 4, 3, 1, 5]);
+        assert_eq!(listed("size", None).await, vec![2, 4, 3, 1, 5], "ties fall back to the filename order");
+        assert_eq!(listed("filename", Some("cafe")).await, vec![4]);
+        assert_eq!(listed("filename", Some("urich")).await, vec![4]);
+        // A renamed file is sorted by its new name once its key is filled in
+        sqlx::query("UPDATE images SET filename = 'Aardvark.jpg' WHERE id = 5").execute(&db.pool).await.unwrap();
+        let pending = db.get_images_needing_sort_key(100).await.unwrap();
+        assert_eq!(pending, vec![(5, "Aardvark.jpg".to_string())]);
+        db.set_filename_sort_keys(&pending).await.unwrap();
+        assert_eq!(listed("filename", None).await, vec![5, 2, 4, 3, 1]);
+
+        let c = criterion("filename", "contains", serde_json::json!("APFEL"));
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new("SELECT i.id FROM images i WHERE ");
+        build_criterion_clause(&c, &mut query_builder);
+        assert_eq!(query_builder.build_query_scalar::<i64>().fetch_all(&db.pool).await.unwrap(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_latest_versions_filter() {
+        let library = seeded_db("search-versions", 10).await;
+        let db = &library.db;
+        for (id, filename) in [(101, "hero_v01.psd"), (102, "hero_v02.psd"), (103, "hero_v02_final.psd")] {
+            sqlx::query("INSERT INTO images (id, folder_id, path, filename, size, format) VALUES (?, 1, ?, ?, 1, 'psd')")
+                .bind(id)
+                .bind(format!("/lib/1/{}", filename))
+                .bind(filename)
+                .execute(&db.pool).await.unwrap();
+        }
+        crate::indexer::versions::refresh_folder_version_chains(db, &[1]).await;
+
+        let filter = |operator: &str| {
+            serde_json::json!({
+                "id": "root",
+                "logicalOperator": "and",
+                "items": [{ "id": "c", "key": "version", "operator": operator, "value": null }]
+            })
+            .to_string()
+        };
+        let ids = |images: Vec<ImageMetadata>| images.into_iter().map(|i| i.id).filter(|id| *id > 100).collect::<Vec<_>>();
+
+        let latest = db.get_images_filtered(100, 0, vec![], false, None, Some(1), false, None, None, None, Some(filter("latest")), None).await.unwrap();
+        assert_eq!(ids(latest), vec![103]);
+        let superseded = db.get_images_filtered(100, 0, vec![], false, None, Some(1), false, None, None, None, Some(filter("superseded")), None).await.unwrap();
+        assert_eq!(ids(superseded), vec![102, 101]);
+
+        let chains = db.get_folder_version_chains(1).await.unwrap();
+        assert_eq!(chains.len(), 1);
+        let versions = db.get_chain_versions(chains[0].id).await.unwrap();
+        assert_eq!(versions.iter().map(|v| v.image_id).collect::<Vec<_>>(), vec![101, 102, 103]);
+        assert!(versions[2].is_latest);
+    }
+
+    #[tokio::test]
+    async fn test_collection_filter() {
+        let library = seeded_db("search-collections", 10).await;
+        let db = &library.db;
+        let id = db.create_collection("Picks", None, &[7, 2, 9]).await.unwrap();
+        let ids = |images: Vec<ImageMetadata>| images.into_iter().map(|i| i.id).collect::<Vec<_>>();
+
+        let manual = db.get_images_filtered(100, 0, vec![], false, None, None, false, Some(id), Some("position".into()), Some("asc".into()), None, None).await.unwrap();
+        assert_eq!(ids(manual), vec![7, 2, 9]);
+        let reversed = db.get_images_filtered(100, 0, vec![], false, None, None, false, Some(id), Some("position".into()), Some("desc".into()), None, None).await.unwrap();
+        assert_eq!(ids(reversed), vec![9, 2, 7]);
+        assert_eq!(db.get_image_count_filtered(vec![], false, None, None, false, Some(id), None, None).await.unwrap(), 3);
+
+        let c = criterion("collection", "not_in", serde_json::json!(id));
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new("SELECT COUNT(*) FROM images i WHERE ");
+        build_criterion_clause(&c, &mut query_builder);
+        assert_eq!(query_builder.build_query_scalar::<i64>().fetch_one(&db.pool).await.unwrap(), 7);
+    }
+
+    #[test]
+    fn test_unknown_key_matches_everything() {
+        let c = criterion("nonexistent", "eq", serde_json::json!(1));
+        assert_eq!(render(&c), "1=1");
+    }
+
+    #[tokio::test]
+    async fn test_location() {
+        let library = crate::testkit::TestLibrary::open("search-location").await;
+        let db = &library.db;
+        library.seed_images(&["1.jpg", "2.jpg", "3.jpg", "4.jpg", "5.jpg"]).await;
+        // Paris, Versailles, Lyon, Fiji (east of the 180th meridian); image 5 has no position
+        let places = [(1, 48.8584, 2.2945), (2, 48.8049, 2.1204), (3, 45.7640, 4.8357), (4, -17.7134, -179.9)];
+        for (id, latitude, longitude) in places {
+            sqlx::query("UPDATE images SET latitude = ?, longitude = ? WHERE id = ?")
+                .bind(latitude)
+                .bind(longitude)
+                .bind(id)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+
+        let matching = |operator: &str, value: serde_json::Value| {
+            let c = criterion("location", operator, value);
+            async move {
+                let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new("SELECT i.id FROM images i WHERE ");
+                build_criterion_clause(&c, &mut query_builder);
+                query_builder.push(" ORDER BY i.id");
+                query_builder.build_query_scalar::<i64>().fetch_all(&db.pool).await.unwrap()
+            }
+        };
+        assert_eq!(matching("near", serde_json::json!({ "latitude": 48.8566, "longitude": 2.3522, "radiusKm": 5 })).await, vec![1]);
+        assert_eq!(matching("near", serde_json::json!({ "latitude": 48.8566, "longitude": 2.3522, "radiusKm": 20 })).await, vec![1, 2]);
+        assert_eq!(matching("near", serde_json::json!({ "latitude": -17.7, "longitude": 179.9, "radiusKm": 30 })).await, vec![4]);
+        let france = serde_json::json!({ "south": 42.3, "west": -4.8, "north": 51.1, "east": 8.2 });
+        assert_eq!(matching("within_bounds", france).await, vec![1, 2, 3]);
+        let pacific = serde_json::json!({ "south": -20, "west": 170, "north": -10, "east": -170 });
+        assert_eq!(matching("within_bounds", pacific).await, vec![4]);
+        assert_eq!(matching("within_bounds", serde_json::json!({ "south": 10 })).await, Vec::<i64>::new());
+        assert_eq!(matching("is_empty", serde_json::Value::Null).await, vec![5]);
+    }
+}
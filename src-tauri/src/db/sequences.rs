@@ -0,0 +1,157 @@
+//! Frame sequences grouped from numbered files.
+
+use crate::db::models::{ImageSequence, SequenceFrame};
+use crate::indexer::sequences::DetectedSequence;
+use super::Db;
+
+const SEQUENCE_COLUMNS: &str =
+    "SELECT id, folder_id, pattern, first_frame, last_frame, frame_count, representative_image_id FROM image_sequences";
+
+impl Db {
+    /// Lists `(id, filename)` of the files directly inside a folder.
+    pub async fn get_folder_filenames(&self, folder_id: i64) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as::<_, (i64, String)>("SELECT id, filename FROM images WHERE folder_id = ?")
+            .bind(folder_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Replaces the stored sequences of a folder with freshly detected ones.
+    ///
+    /// Sequences are matched by pattern, so one that is still there keeps its
+    /// id across refreshes; only the patterns that disappeared are deleted.
+    pub async fn replace_folder_sequences(
+        &self,
+        folder_id: i64,
+        sequences: &[DetectedSequence],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE images SET sequence_id = NULL WHERE folder_id = ? AND sequence_id IS NOT NULL")
+            .bind(folder_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let mut kept_ids = Vec::with_capacity(sequences.len());
+        for sequence in sequences {
+            let sequence_id: i64 = sqlx::query_scalar(
+                "INSERT INTO image_sequences (folder_id, pattern, first_frame, last_frame, frame_count, representative_image_id)
+                 VALUES (?, ?, ?, ?, ?, ?)
+                 ON CONFLICT(folder_id, pattern) DO UPDATE SET
+                    first_frame = excluded.first_frame,
+                    last_frame = excluded.last_frame,
+                    frame_count = excluded.frame_count,
+                    representative_image_id = excluded.representative_image_id
+                 RETURNING id"
+            )
+            .bind(folder_id)
+            .bind(&sequence.pattern)
+            .bind(sequence.first_frame())
+            .bind(sequence.last_frame())
+            .bind(sequence.frames.len() as i64)
+            .bind(sequence.representative())
+            .fetch_one(&mut *tx)
+            .await?;
+            kept_ids.push(sequence_id);
+
+            for chunk in sequence.frames.chunks(500) {
+                let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> =
+                    sqlx::QueryBuilder::new("UPDATE images SET sequence_id = ");
+                query_builder.push_bind(sequence_id);
+                query_builder.push(" WHERE id IN (");
+                let mut separated = query_builder.separated(", ");
+                for (_, image_id) in chunk {
+                    separated.push_bind(*image_id);
+                }
+                separated.push_unseparated(")");
+                query_builder.build().execute(&mut *tx).await?;
+            }
+        }
+
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> =
+            sqlx::QueryBuilder::new("DELETE FROM image_sequences WHERE folder_id = ");
+        query_builder.push_bind(folder_id);
+        if !kept_ids.is_empty() {
+            query_builder.push(" AND id NOT IN (");
+            let mut separated = query_builder.separated(", ");
+            for id in &kept_ids {
+                separated.push_bind(*id);
+            }
+            separated.push_unseparated(")");
+        }
+        query_builder.build().execute(&mut *tx).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Lists the sequences detected in a folder.
+    pub async fn get_folder_sequences(&self, folder_id: i64) -> Result<Vec<ImageSequence>, sqlx::Error> {
+        sqlx::query_as::<_, ImageSequence>(&format!("{} WHERE folder_id = ? ORDER BY pattern", SEQUENCE_COLUMNS))
+            .bind(folder_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Retrieves a sequence by id.
+    pub async fn get_image_sequence(&self, id: i64) -> Result<Option<ImageSequence>, sqlx::Error> {
+        sqlx::query_as::<_, ImageSequence>(&format!("{} WHERE id = ?", SEQUENCE_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Lists the frames of a sequence in order, with their thumbnails.
+    ///
+    /// The frame number is parsed back from the filename, so no per-frame
+    /// column is needed.
+    pub async fn get_sequence_frames(&self, sequence_id: i64) -> Result<Vec<SequenceFrame>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (i64, String, String, Option<String>)>(
+            "SELECT id, filename, path, thumbnail_path FROM images WHERE sequence_id = ?"
+        )
+        .bind(sequence_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut frames: Vec<SequenceFrame> = rows
+            .into_iter()
+            .filter_map(|(image_id, filename, path, thumbnail_path)| {
+                let frame = crate::indexer::sequences::parse_frame_name(&filename)?.frame;
+                Some(SequenceFrame { image_id, frame, path, thumbnail_path })
+            })
+            .collect();
+        frames.sort_by_key(|f| f.frame);
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::indexer::sequences::group_sequences;
+
+    #[tokio::test]
+    async fn test_sequence_ids_survive_refresh() {
+        let library = crate::testkit::TestLibrary::open("sequences-refresh").await;
+        let db = &library.db;
+        let frames: Vec<String> = (1..=8).map(|frame| format!("shot_{:04}.exr", frame)).collect();
+        library.seed_images(&frames).await;
+
+        let files = db.get_folder_filenames(1).await.unwrap();
+        db.replace_folder_sequences(1, &group_sequences(&files)).await.unwrap();
+        let first = db.get_folder_sequences(1).await.unwrap();
+        assert_eq!(first.len(), 1);
+
+        // A frame landing in the folder updates the sequence in place
+        library.seed_images(&["shot_0009.exr"]).await;
+        let files = db.get_folder_filenames(1).await.unwrap();
+        db.replace_folder_sequences(1, &group_sequences(&files)).await.unwrap();
+        let second = db.get_folder_sequences(1).await.unwrap();
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].id, first[0].id);
+        assert_eq!(second[0].frame_count, 9);
+
+        // Sequences that are gone are deleted
+        db.replace_folder_sequences(1, &[]).await.unwrap();
+        assert!(db.get_folder_sequences(1).await.unwrap().is_empty());
+    }
+}
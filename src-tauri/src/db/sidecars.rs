@@ -0,0 +1,305 @@
+//! Stored state of XMP sidecars (see `media::sidecars`).
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use super::imports::ensure_tag;
+use super::Db;
+use crate::media::sidecars::SidecarMetadata;
+
+/// A file whose rating, notes or tags changed since they were last written.
+pub struct PendingWrite {
+    pub image_id: i64,
+    pub path: String,
+    pub format: String,
+    pub rating: i32,
+    pub notes: Option<String>,
+    /// Modification time of the sidecar as last read or written.
+    pub sidecar_modified_at: Option<i64>,
+}
+
+/// What was found next to a file: the sidecar's modification time, and its
+/// metadata when it was parsed (not when unchanged since last read).
+pub struct SidecarRead {
+    pub image_id: i64,
+    pub modified: Option<i64>,
+    pub metadata: Option<SidecarMetadata>,
+}
+
+impl Db {
+    /// Files whose sidecar hasn't been looked for since they were indexed or
+    /// it changed: id, path and modification time of the sidecar last read.
+    pub async fn get_images_needing_sidecar_check(&self, limit: i64) -> Result<Vec<(i64, String, Option<i64>)>, sqlx::Error> {
+        sqlx::query_as("SELECT id, path, sidecar_modified_at FROM images WHERE sidecar_checked_at IS NULL LIMIT ?")
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Applies what was read from sidecars and marks the files as checked.
+    /// The rating is only taken when Mundam has none waiting to be written,
+    /// the caption only when the file has no notes, and tags are added,
+    /// never removed. None of it counts as a change to write back.
+    pub async fn store_sidecar_reads(&self, reads: &[SidecarRead]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for read in reads {
+            let Some(pending) = sqlx::query_scalar::<_, i64>("SELECT sidecar_pending FROM images WHERE id = ?")
+                .bind(read.image_id)
+                .fetch_optional(&mut *tx)
+                .await?
+            else {
+                continue;
+            };
+
+            if let Some(metadata) = &read.metadata {
+                if let (Some(rating), 0) = (metadata.rating, pending) {
+                    sqlx::query("UPDATE images SET rating = ? WHERE id = ?")
+                        .bind(rating)
+                        .bind(read.image_id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                if let (Some(caption), 0) = (&metadata.description, pending) {
+                    sqlx::query("UPDATE images SET notes = ? WHERE id = ? AND COALESCE(notes, '') = ''")
+                        .bind(caption)
+                        .bind(read.image_id)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                for path in metadata.tag_paths() {
+                    let mut parent_id = None;
+                    for name in &path {
+                        parent_id = Some(ensure_tag(&mut tx, name, parent_id).await?);
+                    }
+                    if let Some(tag_id) = parent_id {
+                        sqlx::query("INSERT OR IGNORE INTO image_tags (image_id, tag_id) VALUES (?, ?)")
+                            .bind(read.image_id)
+                            .bind(tag_id)
+                            .execute(&mut *tx)
+                            .await?;
+                    }
+                }
+            }
+
+            // The triggers flag the changes above; they came from the sidecar
+            sqlx::query(
+                "UPDATE images SET sidecar_checked_at = CURRENT_TIMESTAMP, sidecar_modified_at = ?, sidecar_pending = ?
+                 WHERE id = ?"
+            )
+            .bind(read.modified)
+            .bind(pending)
+            .bind(read.image_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Queues the files the given sidecars may belong to to be checked
+    /// again: `IMG_1.xmp` belongs to `IMG_1.CR3` and `IMG_1.jpg`,
+    /// `IMG_1.CR3.xmp` to `IMG_1.CR3`.
+    pub async fn reset_sidecar_checks(&self, sidecars: &[PathBuf]) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut reset = 0;
+        for sidecar in sidecars {
+            let (Some(folder), Some(stem)) = (sidecar.parent(), sidecar.file_stem()) else {
+                continue;
+            };
+            let stem = stem.to_string_lossy().to_ascii_lowercase();
+            let prefix = format!("{}.", stem);
+            reset += sqlx::query(
+                "UPDATE images SET sidecar_checked_at = NULL
+                 WHERE folder_id IN (SELECT id FROM folders WHERE path = ?)
+                   AND (lower(filename) = ? OR substr(lower(filename), 1, length(?)) = ?)"
+            )
+            .bind(crate::paths::to_db(folder))
+            .bind(&stem)
+            .bind(&prefix)
+            .bind(&prefix)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        tx.commit().await?;
+        Ok(reset)
+    }
+
+    /// Queues the given files' sidecars to be read again before their
+    /// pending changes are written, for sidecars another application
+    /// changed since they were last read.
+    pub async fn recheck_sidecars(&self, image_ids: &[i64]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for id in image_ids {
+            sqlx::query("UPDATE images SET sidecar_checked_at = NULL WHERE id = ?").bind(id).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Files whose rating, notes or tags changed since their sidecar was
+    /// last written or read.
+    pub async fn get_pending_sidecar_writes(&self, limit: i64) -> Result<Vec<PendingWrite>, sqlx::Error> {
+        let rows: Vec<(i64, String, String, i32, Option<String>, Option<i64>)> = sqlx::query_as(
+            "SELECT id, path, format, COALESCE(rating, 0), notes, sidecar_modified_at FROM images
+             WHERE sidecar_pending = 1 AND sidecar_checked_at IS NOT NULL LIMIT ?"
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(image_id, path, format, rating, notes, sidecar_modified_at)| PendingWrite {
+                image_id,
+                path,
+                format,
+                rating,
+                notes,
+                sidecar_modified_at,
+            })
+            .collect())
+    }
+
+    /// Tags of the given images as paths from the root tag down.
+    pub async fn get_tag_paths(&self, image_ids: &[i64]) -> Result<HashMap<i64, Vec<Vec<String>>>, sqlx::Error> {
+        let mut paths: HashMap<i64, Vec<Vec<String>>> = HashMap::new();
+        if image_ids.is_empty() {
+            return Ok(paths);
+        }
+
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
+            "WITH RECURSIVE tag_path(id, parent_id, path) AS (
+                SELECT id, parent_id, name FROM tags
+                UNION ALL
+                SELECT tag_path.id, tags.parent_id, tags.name || char(31) || tag_path.path
+                FROM tag_path JOIN tags ON tags.id = tag_path.parent_id
+             )
+             SELECT it.image_id, tag_path.path FROM image_tags it
+             JOIN tag_path ON tag_path.id = it.tag_id AND tag_path.parent_id IS NULL
+             WHERE it.image_id IN (",
+        );
+        let mut separated = query_builder.separated(", ");
+        for id in image_ids {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(") ORDER BY it.image_id, tag_path.path");
+
+        let rows: Vec<(i64, String)> = query_builder.build_query_as().fetch_all(&self.pool).await?;
+        for (image_id, path) in rows {
+            paths.entry(image_id).or_default().push(path.split('\u{1f}').map(String::from).collect());
+        }
+        Ok(paths)
+    }
+
+    /// Records the new size and modification time of a file the metadata
+    /// was written into, so it isn't indexed again as changed. Its content
+    /// hash is read again; its thumbnail still holds.
+    pub async fn set_rewritten_file(
+        &self,
+        image_id: i64,
+        size: i64,
+        modified_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE images SET size = ?, modified_at = ?, content_hash = NULL, content_hashed_at = NULL WHERE id = ?"
+        )
+        .bind(size)
+        .bind(modified_at)
+        .bind(image_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Marks the sidecars of the given images as written, with their new
+    /// modification time (`None` when writing failed, to retry after the
+    /// next change rather than right away).
+    pub async fn mark_sidecars_written(&self, written: &[(i64, Option<i64>)]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (id, modified) in written {
+            sqlx::query(
+                "UPDATE images SET sidecar_pending = 0, sidecar_modified_at = COALESCE(?, sidecar_modified_at),
+                    sidecar_checked_at = CURRENT_TIMESTAMP
+                 WHERE id = ?"
+            )
+            .bind(modified)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn pending(db: &Db) -> Vec<(i64, i32)> {
+        db.get_pending_sidecar_writes(10).await.unwrap().into_iter().map(|write| (write.image_id, write.rating)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_sidecar_reads_and_writes() {
+        let library = crate::testkit::TestLibrary::open("sidecars").await;
+        let db = &library.db;
+        library.seed_images(&["IMG_1.CR3", "IMG_1.jpg", "IMG_10.jpg"]).await;
+        assert_eq!(db.get_images_needing_sidecar_check(10).await.unwrap().len(), 3);
+
+        let metadata = SidecarMetadata {
+            rating: Some(4),
+            label: Some("Red".to_string()),
+            description: Some("Pont Neuf".to_string()),
+            keywords: vec![vec!["Places".to_string(), "Paris".to_string()]],
+        };
+        let reads = [
+            SidecarRead { image_id: 1, modified: Some(1000), metadata: Some(metadata) },
+            SidecarRead { image_id: 2, modified: None, metadata: None },
+            SidecarRead { image_id: 3, modified: None, metadata: None },
+        ];
+        db.store_sidecar_reads(&reads).await.unwrap();
+        assert!(db.get_images_needing_sidecar_check(10).await.unwrap().is_empty());
+        // What came from the sidecar isn't written back
+        assert!(pending(db).await.is_empty());
+        let paths = db.get_tag_paths(&[1, 2]).await.unwrap();
+        assert_eq!(paths[&1], vec![vec!["Labels", "Red"], vec!["Places", "Paris"]]);
+        assert!(!paths.contains_key(&2));
+        let notes: Option<String> =
+            sqlx::query_scalar("SELECT notes FROM images WHERE id = 1").fetch_one(&db.pool).await.unwrap();
+        assert_eq!(notes.as_deref(), Some("Pont Neuf"));
+
+        // A rating changed in Mundam wins over the sidecar until written out
+        sqlx::query("UPDATE images SET rating = 2 WHERE id = 1").execute(&db.pool).await.unwrap();
+        assert_eq!(pending(db).await, vec![(1, 2)]);
+        let reread = SidecarMetadata { rating: Some(5), ..Default::default() };
+        db.store_sidecar_reads(&[SidecarRead { image_id: 1, modified: Some(2000), metadata: Some(reread) }]).await.unwrap();
+        assert_eq!(pending(db).await, vec![(1, 2)]);
+        db.mark_sidecars_written(&[(1, Some(3000))]).await.unwrap();
+        assert!(pending(db).await.is_empty());
+        let modified: Option<i64> = sqlx::query_scalar("SELECT sidecar_modified_at FROM images WHERE id = 1")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(modified, Some(3000));
+
+        // A sidecar changed elsewhere is read again, the change still waiting
+        sqlx::query("UPDATE images SET rating = 1 WHERE id = 1").execute(&db.pool).await.unwrap();
+        assert_eq!(db.get_pending_sidecar_writes(10).await.unwrap()[0].sidecar_modified_at, Some(3000));
+        db.recheck_sidecars(&[1]).await.unwrap();
+        assert!(pending(db).await.is_empty());
+        let reread = SidecarMetadata { keywords: vec![vec!["night".to_string()]], ..Default::default() };
+        db.store_sidecar_reads(&[SidecarRead { image_id: 1, modified: Some(4000), metadata: Some(reread) }]).await.unwrap();
+        assert_eq!(pending(db).await, vec![(1, 1)]);
+        assert!(db.get_tag_paths(&[1]).await.unwrap()[&1].contains(&vec!["night".to_string()]));
+        db.mark_sidecars_written(&[(1, Some(5000))]).await.unwrap();
+
+        // `IMG_1.xmp` may belong to both files named IMG_1, not to IMG_10
+        assert_eq!(db.reset_sidecar_checks(&[PathBuf::from("/lib/img_1.XMP")]).await.unwrap(), 2);
+        let ids: Vec<i64> = db.get_images_needing_sidecar_check(10).await.unwrap().into_iter().map(|(id, ..)| id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        // Files not checked yet aren't written before their sidecar is read
+        sqlx::query("UPDATE images SET rating = 3 WHERE id = 2").execute(&db.pool).await.unwrap();
+        assert!(pending(db).await.is_empty());
+    }
+}
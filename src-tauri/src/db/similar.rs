@@ -0,0 +1,52 @@
+//! Perceptual hashes of pictures (see `thumbnails::perceptual`).
+
+use super::Db;
+
+impl Db {
+    /// Images whose thumbnail hasn't been hashed yet: `(id, thumbnail_path)`.
+    /// Shared icons (`extensions/...`) are left out.
+    pub async fn get_thumbnails_needing_perceptual_hash(&self, limit: i64) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, thumbnail_path FROM images
+             WHERE thumbnail_path IS NOT NULL AND thumbnail_path NOT LIKE '%/%'
+               AND thumbnail_path IS NOT perceptual_hash_of
+             ORDER BY id
+             LIMIT ?",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Stores the hash computed from each thumbnail, `None` when it couldn't
+    /// be read.
+    pub async fn set_perceptual_hashes(&self, hashes: &[(i64, String, Option<u64>)]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (id, thumbnail, hash) in hashes {
+            sqlx::query("UPDATE images SET perceptual_hash = ?, perceptual_hash_of = ? WHERE id = ?")
+                .bind(hash.map(|hash| hash as i64))
+                .bind(thumbnail)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    pub async fn get_perceptual_hash(&self, image_id: i64) -> Result<Option<u64>, sqlx::Error> {
+        let hash: Option<Option<i64>> = sqlx::query_scalar("SELECT perceptual_hash FROM images WHERE id = ?")
+            .bind(image_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(hash.flatten().map(|hash| hash as u64))
+    }
+
+    /// Every stored hash: `(id, hash)`.
+    pub async fn get_perceptual_hashes(&self) -> Result<Vec<(i64, u64)>, sqlx::Error> {
+        let rows: Vec<(i64, i64)> =
+            sqlx::query_as("SELECT id, perceptual_hash FROM images WHERE perceptual_hash IS NOT NULL")
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(rows.into_iter().map(|(id, hash)| (id, hash as u64)).collect())
+    }
+}
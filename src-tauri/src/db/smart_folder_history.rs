@@ -0,0 +1,84 @@
+//! Statistics history of smart folders (see `library::smart_folder_history`).
+
+use chrono::{DateTime, Utc};
+
+use crate::db::models::SmartFolderSnapshot;
+use crate::db::search::{ImageFilter, SearchGroup};
+use super::Db;
+
+/// Columns of a snapshot row, in `SmartFolderSnapshot` order.
+type SnapshotRow = (i64, i64, DateTime<Utc>, i64, i64, i64, i64, i64, i64, i64, i64);
+
+impl Db {
+    /// Evaluates the query of a smart folder and records its statistics.
+    /// Returns the id of the new snapshot.
+    pub async fn take_smart_folder_snapshot(&self, smart_folder_id: i64, query: &SearchGroup) -> Result<i64, sqlx::Error> {
+        let filter = ImageFilter::default();
+        let mut query_builder = filter.build_stats_query(Some(query));
+        let (count, untagged, r0, r1, r2, r3, r4, r5): (i64, i64, i64, i64, i64, i64, i64, i64) =
+            query_builder.build_query_as().fetch_one(&self.pool).await?;
+
+        let id = sqlx::query(
+            "INSERT INTO smart_folder_snapshots
+                (smart_folder_id, image_count, untagged_count, rated_0, rated_1, rated_2, rated_3, rated_4, rated_5)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(smart_folder_id)
+        .bind(count)
+        .bind(untagged)
+        .bind(r0)
+        .bind(r1)
+        .bind(r2)
+        .bind(r3)
+        .bind(r4)
+        .bind(r5)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    /// Smart folders without a snapshot in the last `hours`: id and query.
+    pub async fn get_smart_folders_due_for_snapshot(&self, hours: i64) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT f.id, f.query_json FROM smart_folders f
+             WHERE NOT EXISTS (
+                 SELECT 1 FROM smart_folder_snapshots s
+                 WHERE s.smart_folder_id = f.id AND s.taken_at > datetime('now', ?)
+             )"
+        )
+        .bind(format!("-{} hours", hours))
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Snapshots of a smart folder taken since `since`, oldest first.
+    pub async fn get_smart_folder_snapshots(
+        &self,
+        smart_folder_id: i64,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<SmartFolderSnapshot>, sqlx::Error> {
+        let rows: Vec<SnapshotRow> = sqlx::query_as(
+            "SELECT id, smart_folder_id, taken_at, image_count, untagged_count,
+                    rated_0, rated_1, rated_2, rated_3, rated_4, rated_5
+             FROM smart_folder_snapshots
+             WHERE smart_folder_id = ? AND taken_at >= ?
+             ORDER BY taken_at, id"
+        )
+        .bind(smart_folder_id)
+        .bind(since.format("%Y-%m-%d %H:%M:%S").to_string())
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(id, smart_folder_id, taken_at, image_count, untagged_count, r0, r1, r2, r3, r4, r5)| SmartFolderSnapshot {
+                id,
+                smart_folder_id,
+                taken_at,
+                image_count,
+                untagged_count,
+                ratings: [r0, r1, r2, r3, r4, r5],
+            })
+            .collect())
+    }
+}
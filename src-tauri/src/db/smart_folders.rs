@@ -51,4 +51,30 @@ impl Db {
             .await?;
         Ok(())
     }
+
+    /// Name and query of a smart folder.
+    pub async fn get_smart_folder(&self, id: i64) -> Result<Option<(String, String)>, sqlx::Error> {
+        sqlx::query_as("SELECT name, query_json FROM smart_folders WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Names of every smart folder.
+    pub async fn get_smart_folder_names(&self) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT name FROM smart_folders").fetch_all(&self.pool).await
+    }
+
+    /// Id of the tag at the end of `names`, each one a child of the one
+    /// before, creating the missing ones. Tag names are unique, so an
+    /// existing tag keeps its place wherever it is in the tree.
+    pub async fn ensure_tag_path(&self, names: &[String]) -> Result<Option<i64>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut parent_id = None;
+        for name in names {
+            parent_id = Some(super::imports::ensure_tag(&mut tx, name, parent_id).await?);
+        }
+        tx.commit().await?;
+        Ok(parent_id)
+    }
 }
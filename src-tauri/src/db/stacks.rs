@@ -0,0 +1,128 @@
+//! Stack membership: files of one shot saved side by side in several formats.
+//!
+//! Stacks aren't stored. An image's stack is identified by its [`StackKey`],
+//! its folder plus its filename without the last extension, compared
+//! case-insensitively: `/shoot/IMG_0001.CR3` and `/shoot/img_0001.jpg` are one
+//! stack, while the same names in another folder are another. An image alone
+//! under its key is a stack of one, and names without an extension belong to
+//! no stack.
+
+use std::collections::HashSet;
+
+use super::Db;
+
+/// Stack keys looked up per query; each takes two bound parameters.
+const KEYS_PER_QUERY: usize = 200;
+
+/// Identity of a stack: the folder of its members and their shared stem.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StackKey {
+    pub folder_id: i64,
+    pub stem: String,
+}
+
+impl StackKey {
+    /// Key of the stack `filename` in `folder_id` belongs to, if any.
+    pub fn of(folder_id: i64, filename: &str) -> Option<Self> {
+        Some(Self { folder_id, stem: stack_stem(filename)? })
+    }
+}
+
+/// Stem shared by the members of a stack: `IMG_0001.CR3` and `img_0001.jpg`
+/// both give `img_0001`. Names without an extension belong to no stack.
+pub fn stack_stem(filename: &str) -> Option<String> {
+    let (stem, _extension) = filename.rsplit_once('.')?;
+    (!stem.is_empty()).then(|| stem.to_lowercase())
+}
+
+/// Escapes `%`, `_` and `\` for a `LIKE ... ESCAPE '\'` pattern.
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+impl Db {
+    /// Retrieves the ids of every member of the stacks `ids` belong to,
+    /// `ids` included: images with the same [`StackKey`].
+    pub async fn get_stack_members(&self, ids: &[i64]) -> Result<Vec<i64>, sqlx::Error> {
+        let mut keys: HashSet<StackKey> = HashSet::new();
+        for chunk in ids.chunks(KEYS_PER_QUERY * 2) {
+            let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> =
+                sqlx::QueryBuilder::new("SELECT folder_id, filename FROM images WHERE id IN (");
+            let mut separated = query_builder.separated(", ");
+            for id in chunk {
+                separated.push_bind(*id);
+            }
+            separated.push_unseparated(")");
+            let rows: Vec<(i64, String)> = query_builder.build_query_as().fetch_all(&self.pool).await?;
+            keys.extend(rows.into_iter().filter_map(|(folder_id, filename)| StackKey::of(folder_id, &filename)));
+        }
+
+        let keys: Vec<StackKey> = keys.into_iter().collect();
+        let mut members = Vec::new();
+        for chunk in keys.chunks(KEYS_PER_QUERY) {
+            let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> =
+                sqlx::QueryBuilder::new("SELECT id, folder_id, filename FROM images WHERE ");
+            for (index, key) in chunk.iter().enumerate() {
+                if index > 0 {
+                    query_builder.push(" OR ");
+                }
+                query_builder.push("(folder_id = ");
+                query_builder.push_bind(key.folder_id);
+                query_builder.push(" AND filename LIKE ");
+                query_builder.push_bind(format!("{}.%", escape_like(&key.stem)));
+                query_builder.push(" ESCAPE '\\')");
+            }
+            let rows: Vec<(i64, i64, String)> = query_builder.build_query_as().fetch_all(&self.pool).await?;
+
+            // `IMG_0001.%` also matches `IMG_0001.tar.gz`, whose stem differs
+            let chunk_keys: HashSet<&StackKey> = chunk.iter().collect();
+            members.extend(rows.into_iter().filter_map(|(id, folder_id, filename)| {
+                chunk_keys.contains(&StackKey::of(folder_id, &filename)?).then_some(id)
+            }));
+        }
+
+        members.sort_unstable();
+        members.dedup();
+        Ok(members)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stack_stem() {
+        assert_eq!(stack_stem("IMG_0001.CR3").as_deref(), Some("img_0001"));
+        assert_eq!(stack_stem("img_0001.jpg").as_deref(), Some("img_0001"));
+        assert_eq!(stack_stem("archive.tar.gz").as_deref(), Some("archive.tar"));
+        assert_eq!(stack_stem("README"), None);
+        assert_eq!(stack_stem(".hidden"), None);
+    }
+
+    #[tokio::test]
+    async fn test_get_stack_members() {
+        let library = crate::testkit::TestLibrary::open("stacks").await;
+        let db = &library.db;
+        library
+            .seed_images(&["IMG_0001.CR3", "img_0001.jpg", "IMG_0001.tar.gz", "IMG_0002.jpg", "IMG%0001.jpg", "README", "IMG_0001.jpg"])
+            .await;
+        sqlx::query("INSERT INTO folders (id, path, name) VALUES (2, '/other', 'other')").execute(&db.pool).await.unwrap();
+        sqlx::query("UPDATE images SET folder_id = 2, path = '/other/IMG_0001.jpg' WHERE id = 7")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        // Same folder and stem, whatever the case; not `.tar.gz`, another folder or a LIKE wildcard
+        assert_eq!(db.get_stack_members(&[1]).await.unwrap(), vec![1, 2]);
+        assert_eq!(db.get_stack_members(&[2, 4]).await.unwrap(), vec![1, 2, 4]);
+        assert_eq!(db.get_stack_members(&[7]).await.unwrap(), vec![7]);
+        assert_eq!(db.get_stack_members(&[6]).await.unwrap(), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("IMG_50%"), "IMG\\_50\\%");
+        assert_eq!(escape_like("a\\b"), "a\\\\b");
+    }
+}
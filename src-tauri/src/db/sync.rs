@@ -0,0 +1,121 @@
+//! Sync targets and the files copied to them (see `crate::library::sync`).
+
+use crate::db::models::{SyncTarget, SyncedFile};
+use super::Db;
+
+const TARGET_COLUMNS: &str = "SELECT id, name, destination, filter, delete_policy, keep_folders, auto_sync, last_synced_at, created_at
+     FROM sync_targets";
+
+impl Db {
+    pub async fn create_sync_target(
+        &self,
+        name: &str,
+        destination: &str,
+        filter_json: &str,
+        delete_policy: &str,
+        keep_folders: bool,
+        auto_sync: bool,
+    ) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT INTO sync_targets (name, destination, filter, delete_policy, keep_folders, auto_sync)
+             VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(name)
+        .bind(destination)
+        .bind(filter_json)
+        .bind(delete_policy)
+        .bind(keep_folders)
+        .bind(auto_sync)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Updates a target. Files already copied stay tracked, so moving the
+    /// destination should go with a new target instead.
+    pub async fn update_sync_target(
+        &self,
+        id: i64,
+        name: &str,
+        filter_json: &str,
+        delete_policy: &str,
+        auto_sync: bool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE sync_targets SET name = ?, filter = ?, delete_policy = ?, auto_sync = ? WHERE id = ?")
+            .bind(name)
+            .bind(filter_json)
+            .bind(delete_policy)
+            .bind(auto_sync)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes a target and forgets its files, which are left in place.
+    pub async fn delete_sync_target(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM sync_targets WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_sync_target(&self, id: i64) -> Result<Option<SyncTarget>, sqlx::Error> {
+        sqlx::query_as::<_, SyncTarget>(&format!("{} WHERE id = ?", TARGET_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn get_sync_targets(&self) -> Result<Vec<SyncTarget>, sqlx::Error> {
+        sqlx::query_as::<_, SyncTarget>(&format!("{} ORDER BY name COLLATE NOCASE", TARGET_COLUMNS))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn get_synced_files(&self, target_id: i64) -> Result<Vec<SyncedFile>, sqlx::Error> {
+        sqlx::query_as::<_, SyncedFile>(
+            "SELECT relative_path, image_id, source_size, source_modified, destination_modified
+             FROM sync_files WHERE target_id = ?"
+        )
+        .bind(target_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Records a file copied to a target, replacing an earlier copy.
+    pub async fn record_synced_file(&self, target_id: i64, file: &SyncedFile) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO sync_files
+                (target_id, relative_path, image_id, source_size, source_modified, destination_modified)
+             VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(target_id)
+        .bind(&file.relative_path)
+        .bind(file.image_id)
+        .bind(file.source_size)
+        .bind(file.source_modified)
+        .bind(file.destination_modified)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn forget_synced_file(&self, target_id: i64, relative_path: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM sync_files WHERE target_id = ? AND relative_path = ?")
+            .bind(target_id)
+            .bind(relative_path)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn mark_sync_target_synced(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE sync_targets SET last_synced_at = datetime('now') WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -1,7 +1,8 @@
 //! Tag management and image-tag relationship queries.
 
-use crate::db::models::{Tag, TagCount, LibraryStats, FolderCount};
+use crate::db::models::{Tag, TagCount, LibraryStats, FolderCount, TagShortcut};
 use super::Db;
+use std::collections::HashMap;
 
 impl Db {
     /// Creates a new tag in the database.
@@ -118,6 +119,20 @@ impl Db {
         Ok(())
     }
 
+    /// Removes a tag from several images in a single transaction.
+    pub async fn remove_tag_from_images(&self, image_ids: &[i64], tag_id: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for image_id in image_ids {
+            sqlx::query("DELETE FROM image_tags WHERE image_id = ? AND tag_id = ?")
+                .bind(image_id)
+                .bind(tag_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
     /// Gets all tags associated with a specific image.
     pub async fn get_tags_for_image(&self, image_id: i64) -> Result<Vec<Tag>, sqlx::Error> {
         let tags = sqlx::query_as!(
@@ -201,4 +216,110 @@ impl Db {
             folder_counts_recursive,
         })
     }
+
+    /// Assigns a keyboard shortcut to a tag, or clears it when `tag_id` is `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the tag doesn't exist or the database fails.
+    pub async fn assign_tag_shortcut(
+        &self,
+        profile: &str,
+        key: &str,
+        tag_id: Option<i64>,
+    ) -> Result<(), sqlx::Error> {
+        match tag_id {
+            Some(tag_id) => {
+                sqlx::query(
+                    "INSERT INTO tag_shortcuts (profile, shortcut_key, tag_id) VALUES (?, ?, ?)
+                     ON CONFLICT(profile, shortcut_key) DO UPDATE SET tag_id = excluded.tag_id, updated_at = CURRENT_TIMESTAMP"
+                )
+                .bind(profile)
+                .bind(key)
+                .bind(tag_id)
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM tag_shortcuts WHERE profile = ? AND shortcut_key = ?")
+                    .bind(profile)
+                    .bind(key)
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Retrieves every shortcut of a profile, ordered by key.
+    pub async fn get_tag_shortcuts(&self, profile: &str) -> Result<Vec<TagShortcut>, sqlx::Error> {
+        sqlx::query_as::<_, TagShortcut>(
+            "SELECT profile, shortcut_key, tag_id FROM tag_shortcuts WHERE profile = ? ORDER BY shortcut_key"
+        )
+        .bind(profile)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Resolves a pressed key to the tag it is mapped to.
+    pub async fn resolve_tag_shortcut(&self, profile: &str, key: &str) -> Result<Option<Tag>, sqlx::Error> {
+        sqlx::query_as::<_, Tag>(
+            "SELECT t.id, t.name, t.parent_id, t.color, t.order_index
+             FROM tag_shortcuts ts JOIN tags t ON t.id = ts.tag_id
+             WHERE ts.profile = ? AND ts.shortcut_key = ?"
+        )
+        .bind(profile)
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Retrieves `(image_id, parent tag name, tag name)` for the tags of several images.
+    pub async fn get_tag_names_for_images(
+        &self,
+        image_ids: &[i64],
+    ) -> Result<Vec<(i64, Option<String>, String)>, sqlx::Error> {
+        if image_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
+            "SELECT it.image_id, p.name, t.name
+             FROM image_tags it
+             JOIN tags t ON t.id = it.tag_id
+             LEFT JOIN tags p ON p.id = t.parent_id
+             WHERE it.image_id IN ("
+        );
+        let mut separated = query_builder.separated(", ");
+        for id in image_ids {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(") ORDER BY t.order_index, t.name");
+
+        query_builder
+            .build_query_as::<(i64, Option<String>, String)>()
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Retrieves the tag names of several images, keyed by image id, in the
+    /// order of `get_tags_for_image`.
+    pub async fn get_tag_names_by_image(&self, image_ids: &[i64]) -> Result<HashMap<i64, Vec<String>>, sqlx::Error> {
+        let ids_json = serde_json::to_string(image_ids).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+        let rows: Vec<(i64, String)> = sqlx::query_as(
+            "SELECT it.image_id, t.name
+             FROM image_tags it JOIN tags t ON t.id = it.tag_id
+             WHERE it.image_id IN (SELECT value FROM json_each(?))
+             ORDER BY t.order_index ASC, t.name ASC"
+        )
+        .bind(ids_json)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut names: HashMap<i64, Vec<String>> = HashMap::new();
+        for (image_id, name) in rows {
+            names.entry(image_id).or_default().push(name);
+        }
+        Ok(names)
+    }
 }
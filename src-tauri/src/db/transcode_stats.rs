@@ -0,0 +1,66 @@
+//! Recorded segment transcodes (see `streaming::stats`).
+
+use chrono::{DateTime, Utc};
+
+use crate::streaming::stats::SegmentTiming;
+use super::Db;
+
+/// Segments kept per file; older ones are dropped as new ones are recorded.
+const KEPT_PER_IMAGE: i64 = 500;
+
+/// A recorded segment transcode.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct SegmentStatRow {
+    pub segment_index: i64,
+    pub quality: String,
+    pub encoder: String,
+    pub queued_ms: i64,
+    pub encode_ms: i64,
+    pub media_secs: f64,
+    pub error: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl Db {
+    /// Records a segment transcode of `image_id`.
+    pub async fn record_segment_timing(&self, image_id: i64, timing: &SegmentTiming) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO transcode_stats (image_id, segment_index, quality, encoder, queued_ms, encode_ms, media_secs, error)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(image_id)
+        .bind(timing.segment_index as i64)
+        .bind(&timing.quality)
+        .bind(&timing.encoder)
+        .bind(timing.queued.as_millis() as i64)
+        .bind(timing.encoding.as_millis() as i64)
+        .bind(timing.media_secs)
+        .bind(&timing.error)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM transcode_stats WHERE image_id = ? AND id <= (
+                SELECT id FROM transcode_stats WHERE image_id = ? ORDER BY id DESC LIMIT 1 OFFSET ?
+             )"
+        )
+        .bind(image_id)
+        .bind(image_id)
+        .bind(KEPT_PER_IMAGE)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Recorded segment transcodes of `image_id`, newest first.
+    pub async fn get_segment_timings(&self, image_id: i64) -> Result<Vec<SegmentStatRow>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT segment_index, quality, encoder, queued_ms, encode_ms, media_secs, error, recorded_at
+             FROM transcode_stats WHERE image_id = ? ORDER BY id DESC"
+        )
+        .bind(image_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,490 @@
+//! Trash: images taken out of the library, with what the user gave them.
+//!
+//! Deleting an image row loses its rating, notes, tags, collections,
+//! annotations, custom fields, links and recorded derivatives, so removals go
+//! through [`trash_image`], which keeps a snapshot of them first. When a
+//! missing file shows up again at the same path with the same size (a drive
+//! plugged back in, a folder moved back) the indexer hands the snapshot to
+//! the new image on its own; otherwise `restore_from_trash` brings an entry
+//! back. Entries older than the retention window are purged.
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+
+use crate::db::models::TrashItem;
+use super::Db;
+
+/// Settings key holding the number of days entries stay in the trash.
+pub const RETENTION_SETTING_KEY: &str = "trash_retention_days";
+/// Days entries stay in the trash when the setting is missing or invalid.
+pub const DEFAULT_RETENTION_DAYS: i64 = 30;
+
+/// The file went away from the library: deleted or moved outside of it,
+/// its drive unplugged or its location removed.
+pub const REASON_MISSING: &str = "missing";
+/// Deleted from the app; the trash folder holds the file.
+pub const REASON_DELETED: &str = "deleted";
+
+const TRASH_COLUMNS: &str = "SELECT id, image_id, path, filename, size, format, reason,
+        stored_path IS NOT NULL AS has_file, trashed_at
+     FROM trash";
+
+/// What an image had that the file doesn't.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Snapshot {
+    rating: i32,
+    notes: Option<String>,
+    created_at: Option<String>,
+    tag_ids: Vec<i64>,
+    /// Collection id and position.
+    collections: Vec<(i64, i64)>,
+    annotations: Vec<AnnotationSnapshot>,
+    /// Custom field name and value.
+    fields: Vec<(String, String)>,
+    /// Link label and URL.
+    links: Vec<(String, String)>,
+    derivatives: Vec<DerivativeSnapshot>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+struct AnnotationSnapshot {
+    body: String,
+    region_x: Option<f64>,
+    region_y: Option<f64>,
+    region_w: Option<f64>,
+    region_h: Option<f64>,
+    related_image_id: Option<i64>,
+    created_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+struct DerivativeSnapshot {
+    kind: String,
+    path: String,
+    label: Option<String>,
+    size: Option<i64>,
+}
+
+/// A trash entry with what it takes to restore it.
+#[derive(Debug, sqlx::FromRow)]
+pub struct TrashEntry {
+    /// Unique identifier of the entry.
+    pub id: i64,
+    /// Path the file had in the library.
+    pub path: String,
+    /// [`REASON_MISSING`] or [`REASON_DELETED`].
+    pub reason: String,
+    /// Where the trash folder keeps the file, for deleted images.
+    pub stored_path: Option<String>,
+}
+
+/// Moves image `image_id` to the trash, deleting it from the library, with
+/// the file kept at `stored_path` if any. Returns the id of the entry,
+/// `None` if there is no such image.
+pub(crate) async fn trash_image(
+    conn: &mut SqliteConnection,
+    image_id: i64,
+    reason: &str,
+    stored_path: Option<&str>,
+) -> Result<Option<i64>, sqlx::Error> {
+    let row: Option<(String, String, Option<i64>, Option<String>, Option<i32>, Option<String>, String)> =
+        sqlx::query_as("SELECT path, filename, size, format, rating, notes, created_at FROM images WHERE id = ?")
+            .bind(image_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+    let Some((path, filename, size, format, rating, notes, created_at)) = row else {
+        return Ok(None);
+    };
+
+    let snapshot = Snapshot {
+        rating: rating.unwrap_or(0),
+        notes,
+        created_at: Some(created_at),
+        tag_ids: sqlx::query_scalar("SELECT tag_id FROM image_tags WHERE image_id = ? ORDER BY tag_id")
+            .bind(image_id)
+            .fetch_all(&mut *conn)
+            .await?,
+        collections: sqlx::query_as("SELECT collection_id, position FROM collection_items WHERE image_id = ? ORDER BY collection_id")
+            .bind(image_id)
+            .fetch_all(&mut *conn)
+            .await?,
+        annotations: sqlx::query_as(
+            "SELECT body, region_x, region_y, region_w, region_h, related_image_id, created_at
+             FROM image_annotations WHERE image_id = ? ORDER BY id"
+        )
+        .bind(image_id)
+        .fetch_all(&mut *conn)
+        .await?,
+        fields: sqlx::query_as("SELECT name, value FROM image_fields WHERE image_id = ? ORDER BY name")
+            .bind(image_id)
+            .fetch_all(&mut *conn)
+            .await?,
+        links: sqlx::query_as("SELECT label, url FROM links WHERE image_id = ? ORDER BY id")
+            .bind(image_id)
+            .fetch_all(&mut *conn)
+            .await?,
+        derivatives: sqlx::query_as("SELECT kind, path, label, size FROM derivatives WHERE image_id = ? ORDER BY id")
+            .bind(image_id)
+            .fetch_all(&mut *conn)
+            .await?,
+    };
+    let snapshot = serde_json::to_string(&snapshot).unwrap_or_else(|_| "{}".to_string());
+
+    // A file that goes missing again only needs its latest snapshot
+    sqlx::query("DELETE FROM trash WHERE path = ? AND reason = ? AND stored_path IS NULL")
+        .bind(&path)
+        .bind(REASON_MISSING)
+        .execute(&mut *conn)
+        .await?;
+    let trash_id = sqlx::query(
+        "INSERT INTO trash (image_id, path, filename, size, format, reason, stored_path, snapshot)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(image_id)
+    .bind(&path)
+    .bind(&filename)
+    .bind(size.unwrap_or(0))
+    .bind(format.unwrap_or_default())
+    .bind(reason)
+    .bind(stored_path)
+    .bind(snapshot)
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+
+    super::collections::leave_collections(conn, image_id).await?;
+    sqlx::query("DELETE FROM images WHERE id = ?")
+        .bind(image_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(Some(trash_id))
+}
+
+/// Moves the images of a folder and its descendants to the trash.
+pub(crate) async fn trash_folder_images(conn: &mut SqliteConnection, folder_id: i64, reason: &str) -> Result<u64, sqlx::Error> {
+    let ids: Vec<i64> = sqlx::query_scalar(
+        "WITH RECURSIVE family AS (
+            SELECT id FROM folders WHERE id = ?
+            UNION ALL
+            SELECT f.id FROM folders f JOIN family ON f.parent_id = family.id
+         )
+         SELECT id FROM images WHERE folder_id IN family"
+    )
+    .bind(folder_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    for id in &ids {
+        trash_image(conn, *id, reason, None).await?;
+    }
+    Ok(ids.len() as u64)
+}
+
+/// Gives image `image_id` what an entry kept: the higher of the two
+/// ratings, the notes where it has none, the creation time, the tags and
+/// collections still in the library, at its old place in each collection,
+/// the annotations, links and derivatives, and the custom fields it has no
+/// value for.
+async fn apply_snapshot(conn: &mut SqliteConnection, image_id: i64, snapshot: &str) -> Result<(), sqlx::Error> {
+    let snapshot: Snapshot = serde_json::from_str(snapshot).unwrap_or_default();
+    sqlx::query(
+        "UPDATE images SET
+            rating = MAX(COALESCE(rating, 0), ?),
+            notes = CASE WHEN notes IS NULL OR notes = '' THEN ? ELSE notes END,
+            created_at = COALESCE(?, created_at)
+         WHERE id = ?"
+    )
+    .bind(snapshot.rating)
+    .bind(&snapshot.notes)
+    .bind(&snapshot.created_at)
+    .bind(image_id)
+    .execute(&mut *conn)
+    .await?;
+
+    for tag_id in snapshot.tag_ids {
+        sqlx::query("INSERT OR IGNORE INTO image_tags (image_id, tag_id) SELECT ?, id FROM tags WHERE id = ?")
+            .bind(image_id)
+            .bind(tag_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    for (collection_id, position) in snapshot.collections {
+        sqlx::query("UPDATE collection_items SET position = position + 1 WHERE collection_id = ? AND position >= ?")
+            .bind(collection_id)
+            .bind(position)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("INSERT OR IGNORE INTO collection_items (collection_id, image_id, position) SELECT id, ?, ? FROM collections WHERE id = ?")
+            .bind(image_id)
+            .bind(position)
+            .bind(collection_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    for annotation in snapshot.annotations {
+        sqlx::query(
+            "INSERT INTO image_annotations (image_id, body, region_x, region_y, region_w, region_h, related_image_id, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, (SELECT id FROM images WHERE id = ?), COALESCE(?, CURRENT_TIMESTAMP))"
+        )
+        .bind(image_id)
+        .bind(&annotation.body)
+        .bind(annotation.region_x)
+        .bind(annotation.region_y)
+        .bind(annotation.region_w)
+        .bind(annotation.region_h)
+        .bind(annotation.related_image_id)
+        .bind(&annotation.created_at)
+        .execute(&mut *conn)
+        .await?;
+    }
+    for (name, value) in snapshot.fields {
+        sqlx::query("INSERT OR IGNORE INTO image_fields (image_id, name, value) VALUES (?, ?, ?)")
+            .bind(image_id)
+            .bind(name)
+            .bind(value)
+            .execute(&mut *conn)
+            .await?;
+    }
+    for (label, url) in snapshot.links {
+        sqlx::query("INSERT INTO links (image_id, label, url) VALUES (?, ?, ?)")
+            .bind(image_id)
+            .bind(label)
+            .bind(url)
+            .execute(&mut *conn)
+            .await?;
+    }
+    for derivative in snapshot.derivatives {
+        sqlx::query("INSERT OR IGNORE INTO derivatives (image_id, kind, path, label, size) VALUES (?, ?, ?, ?, ?)")
+            .bind(image_id)
+            .bind(&derivative.kind)
+            .bind(&derivative.path)
+            .bind(&derivative.label)
+            .bind(derivative.size)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Hands a newly indexed image the snapshot of the missing file last seen
+/// at its path, and drops that entry. Returns whether there was one.
+///
+/// The file must have the size it had: another file of the same name
+/// created meanwhile is a new image, and the entry stays in the trash.
+pub(crate) async fn reclaim_missing(
+    conn: &mut SqliteConnection,
+    image_id: i64,
+    path: &str,
+    size: i64,
+) -> Result<bool, sqlx::Error> {
+    let entry: Option<(i64, String)> = sqlx::query_as(
+        "SELECT id, snapshot FROM trash WHERE path = ? AND reason = ? AND size = ?
+         ORDER BY trashed_at DESC, id DESC LIMIT 1"
+    )
+    .bind(path)
+    .bind(REASON_MISSING)
+    .bind(size)
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some((trash_id, snapshot)) = entry else {
+        return Ok(false);
+    };
+    apply_snapshot(conn, image_id, &snapshot).await?;
+    sqlx::query("DELETE FROM trash WHERE id = ?")
+        .bind(trash_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(true)
+}
+
+impl Db {
+    /// Moves an image to the trash. Returns the id of the entry, `None` if
+    /// there is no such image.
+    pub async fn trash_image(&self, image_id: i64, reason: &str, stored_path: Option<&str>) -> Result<Option<i64>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let trash_id = trash_image(&mut tx, image_id, reason, stored_path).await?;
+        tx.commit().await?;
+        Ok(trash_id)
+    }
+
+    /// Entries in the trash, most recent first.
+    pub async fn get_trash(&self) -> Result<Vec<TrashItem>, sqlx::Error> {
+        sqlx::query_as::<_, TrashItem>(&format!("{} ORDER BY trashed_at DESC, id DESC", TRASH_COLUMNS))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// The entry `id` with what it takes to restore it.
+    pub async fn get_trash_entry(&self, id: i64) -> Result<Option<TrashEntry>, sqlx::Error> {
+        sqlx::query_as::<_, TrashEntry>("SELECT id, path, reason, stored_path FROM trash WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Hands image `image_id`, indexed again from the entry's file, what the
+    /// entry kept, and drops the entry.
+    pub async fn restore_trash_entry(&self, id: i64, image_id: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let snapshot: Option<String> = sqlx::query_scalar("SELECT snapshot FROM trash WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if let Some(snapshot) = snapshot {
+            apply_snapshot(&mut tx, image_id, &snapshot).await?;
+            sqlx::query("DELETE FROM trash WHERE id = ?")
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Drops the entries `ids`, or all of them for `None`. Returns the files
+    /// the trash folder held for them, for the caller to delete.
+    pub async fn remove_trash_entries(&self, ids: Option<&[i64]>) -> Result<Vec<String>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut stored = Vec::new();
+        match ids {
+            Some(ids) => {
+                for id in ids {
+                    let path: Option<Option<String>> = sqlx::query_scalar("DELETE FROM trash WHERE id = ? RETURNING stored_path")
+                        .bind(id)
+                        .fetch_optional(&mut *tx)
+                        .await?;
+                    stored.extend(path.flatten());
+                }
+            }
+            None => {
+                let paths: Vec<Option<String>> = sqlx::query_scalar("DELETE FROM trash RETURNING stored_path")
+                    .fetch_all(&mut *tx)
+                    .await?;
+                stored.extend(paths.into_iter().flatten());
+            }
+        }
+        tx.commit().await?;
+        Ok(stored)
+    }
+
+    /// Drops the entries trashed more than `days` ago. Returns the files the
+    /// trash folder held for them, for the caller to delete.
+    pub async fn purge_expired_trash(&self, days: i64) -> Result<Vec<String>, sqlx::Error> {
+        let paths: Vec<Option<String>> =
+            sqlx::query_scalar("DELETE FROM trash WHERE trashed_at < datetime('now', ?) RETURNING stored_path")
+                .bind(format!("-{} days", days.max(0)))
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(paths.into_iter().flatten().collect())
+    }
+
+    /// Days entries stay in the trash, from the `trash_retention_days`
+    /// setting.
+    pub async fn trash_retention_days(&self) -> i64 {
+        match self.get_setting(RETENTION_SETTING_KEY).await {
+            Ok(Some(val)) => val.as_i64().filter(|days| *days >= 0).unwrap_or(DEFAULT_RETENTION_DAYS),
+            _ => DEFAULT_RETENTION_DAYS,
+        }
+    }
+
+    /// Root folder of the location holding `path`, if it is still in the
+    /// library. Either separator ends a component, as in
+    /// `maintenance::replace_prefix`, and roots may end with one (`D:\`).
+    pub async fn get_location_for_path(&self, path: &str) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT id FROM (SELECT id, rtrim(path, '/\\') AS root FROM folders WHERE is_root = 1)
+             WHERE substr(?1, 1, length(root) + 1) IN (root || '/', root || '\\')
+             ORDER BY length(root) DESC LIMIT 1"
+        )
+        .bind(path)
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_trash_keeps_user_data() {
+        let library = crate::testkit::TestLibrary::open("trash").await;
+        let db = &library.db;
+        library.seed_images(&["1.jpg", "2.jpg", "3.jpg"]).await;
+        sqlx::query("UPDATE images SET created_at = '2020-05-01T00:00:00Z', rating = 4, notes = 'keeper'")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO tags (id, name) VALUES (1, 'family')").execute(&db.pool).await.unwrap();
+        sqlx::query("INSERT INTO image_tags (image_id, tag_id) VALUES (2, 1)").execute(&db.pool).await.unwrap();
+        let album = db.create_collection("Album", None, &[1, 2, 3]).await.unwrap();
+        for statement in [
+            "INSERT INTO image_annotations (image_id, body, region_x, region_y, region_w, region_h) VALUES (2, 'face', 0.1, 0.2, 0.3, 0.4)",
+            "INSERT INTO image_fields (image_id, name, value) VALUES (2, 'client', 'ACME')",
+            "INSERT INTO links (image_id, label, url) VALUES (2, 'Brief', 'https://example.com/brief')",
+            "INSERT INTO derivatives (image_id, kind, path) VALUES (2, 'export', '/out/2.jpg')",
+        ] {
+            sqlx::query(statement).execute(&db.pool).await.unwrap();
+        }
+
+        let trash_id = db.trash_image(2, REASON_MISSING, None).await.unwrap().unwrap();
+        assert_eq!(db.trash_image(2, REASON_MISSING, None).await.unwrap(), None);
+        let trash = db.get_trash().await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!((trash[0].image_id, trash[0].reason.as_str(), trash[0].has_file), (2, REASON_MISSING, false));
+
+        // The file shows up again at its path and gets everything back
+        sqlx::query(
+            "INSERT INTO images (id, folder_id, path, filename, size, format, created_at, modified_at)
+             VALUES (7, 1, '/lib/2.jpg', '2.jpg', 100, 'jpg', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')"
+        )
+        .execute(&db.pool)
+        .await
+        .unwrap();
+        let mut conn = db.pool.acquire().await.unwrap();
+        // Another file with that name isn't the one that went missing
+        assert!(!reclaim_missing(&mut conn, 7, "/lib/2.jpg", 999).await.unwrap());
+        assert!(reclaim_missing(&mut conn, 7, "/lib/2.jpg", 100).await.unwrap());
+        drop(conn);
+        assert!(db.get_trash_entry(trash_id).await.unwrap().is_none());
+        let (rating, notes, created_at): (i32, String, String) =
+            sqlx::query_as("SELECT rating, notes, created_at FROM images WHERE id = 7").fetch_one(&db.pool).await.unwrap();
+        assert_eq!((rating, notes.as_str(), created_at.as_str()), (4, "keeper", "2020-05-01T00:00:00Z"));
+        let tags: Vec<i64> = sqlx::query_scalar("SELECT tag_id FROM image_tags WHERE image_id = 7").fetch_all(&db.pool).await.unwrap();
+        assert_eq!(tags, vec![1]);
+        let order: Vec<i64> =
+            sqlx::query_scalar("SELECT image_id FROM collection_items WHERE collection_id = ? ORDER BY position")
+                .bind(album)
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(order, vec![1, 7, 3]);
+        let kept: (String, f64, String, String, String) = sqlx::query_as(
+            "SELECT a.body, a.region_w, v.value, l.url, d.path FROM image_annotations a
+             JOIN image_fields v ON v.image_id = a.image_id
+             JOIN links l ON l.image_id = a.image_id
+             JOIN derivatives d ON d.image_id = a.image_id
+             WHERE a.image_id = 7"
+        )
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(kept, ("face".to_string(), 0.3, "ACME".to_string(), "https://example.com/brief".to_string(), "/out/2.jpg".to_string()));
+
+        sqlx::query("INSERT INTO folders (id, path, name, is_root) VALUES (9, 'D:\\', 'D:', 1)").execute(&db.pool).await.unwrap();
+        assert_eq!(db.get_location_for_path("D:\\photos\\a.jpg").await.unwrap(), Some(9));
+        assert_eq!(db.get_location_for_path("/lib/1.jpg").await.unwrap(), Some(1));
+        assert_eq!(db.get_location_for_path("/lib2/1.jpg").await.unwrap(), None);
+
+        // Removed locations go to the trash with their images
+        db.delete_folder(1).await.unwrap();
+        assert_eq!(db.get_trash().await.unwrap().len(), 3);
+        assert_eq!(db.get_location_for_path("/lib/1.jpg").await.unwrap(), None);
+
+        sqlx::query("UPDATE trash SET trashed_at = datetime('now', '-40 days') WHERE image_id = 1").execute(&db.pool).await.unwrap();
+        assert!(db.purge_expired_trash(30).await.unwrap().is_empty());
+        assert_eq!(db.get_trash().await.unwrap().len(), 2);
+        db.remove_trash_entries(None).await.unwrap();
+        assert!(db.get_trash().await.unwrap().is_empty());
+    }
+}
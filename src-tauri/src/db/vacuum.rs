@@ -0,0 +1,307 @@
+//! Space and statistics upkeep of the database file.
+//!
+//! Libraries are opened with `auto_vacuum = INCREMENTAL`, so pages freed by
+//! deletes can be handed back to the OS a few at a time with
+//! `incremental_vacuum`, without the exclusive lock of a full `VACUUM`. A
+//! database created before that setting only switches over after one full
+//! `VACUUM`, which the next manual maintenance run does.
+//!
+//! [`Db::get_status`] reports what power users need to judge whether a run
+//! is due: file and WAL sizes, free pages, page cache efficiency and when
+//! each kind of upkeep last ran.
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::Db;
+
+/// Value of `PRAGMA auto_vacuum` in incremental mode.
+const AUTO_VACUUM_INCREMENTAL: i64 = 2;
+
+/// Setting holding the [`MaintenanceLog`].
+const LOG_SETTING_KEY: &str = "db_maintenance_log";
+
+/// Pages of the database file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DbSpace {
+    pub page_size: i64,
+    pub page_count: i64,
+    /// Unused pages, which a vacuum gives back.
+    pub freelist_count: i64,
+}
+
+impl DbSpace {
+    pub fn size_bytes(&self) -> u64 {
+        (self.page_size * self.page_count).max(0) as u64
+    }
+
+    pub fn free_bytes(&self) -> u64 {
+        (self.page_size * self.freelist_count).max(0) as u64
+    }
+}
+
+/// Outcome of a maintenance run.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceReport {
+    pub size_before: u64,
+    pub size_after: u64,
+    pub reclaimed_bytes: u64,
+    /// Whether a full `VACUUM` ran, to switch the file to incremental mode.
+    pub full_vacuum: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaintenanceKind {
+    Vacuum,
+    FullVacuum,
+    Analyze,
+    Checkpoint,
+}
+
+/// When each kind of upkeep last ran, as RFC 3339 timestamps.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MaintenanceLog {
+    pub last_vacuum_at: Option<String>,
+    pub last_full_vacuum_at: Option<String>,
+    pub last_analyze_at: Option<String>,
+    pub last_checkpoint_at: Option<String>,
+}
+
+/// Reply of `get_db_status`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DbStatus {
+    pub path: String,
+    pub file_bytes: u64,
+    /// Size of the write-ahead log not yet checkpointed into the file.
+    pub wal_bytes: u64,
+    pub page_size: i64,
+    pub page_count: i64,
+    pub freelist_count: i64,
+    /// Share of the file taken by free pages, from 0 to 1.
+    pub fragmentation: f64,
+    pub incremental_vacuum: bool,
+    pub cache_hits: i64,
+    pub cache_misses: i64,
+    /// Share of page reads served from the page cache, once there were any.
+    pub cache_hit_rate: Option<f64>,
+    #[serde(flatten)]
+    pub maintenance: MaintenanceLog,
+}
+
+impl Db {
+    pub async fn get_space(&self) -> Result<DbSpace, sqlx::Error> {
+        let page_size: i64 = sqlx::query_scalar("PRAGMA page_size").fetch_one(&self.pool).await?;
+        let page_count: i64 = sqlx::query_scalar("PRAGMA page_count").fetch_one(&self.pool).await?;
+        let freelist_count: i64 = sqlx::query_scalar("PRAGMA freelist_count").fetch_one(&self.pool).await?;
+        Ok(DbSpace { page_size, page_count, freelist_count })
+    }
+
+    /// Whether freed pages can be reclaimed with [`Db::incremental_vacuum`].
+    pub async fn is_incremental_vacuum(&self) -> Result<bool, sqlx::Error> {
+        let mode: i64 = sqlx::query_scalar("PRAGMA auto_vacuum").fetch_one(&self.pool).await?;
+        Ok(mode == AUTO_VACUUM_INCREMENTAL)
+    }
+
+    /// Gives up to `pages` free pages back to the OS.
+    pub async fn incremental_vacuum(&self, pages: i64) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!("PRAGMA incremental_vacuum({})", pages.max(1)))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Copies the WAL back into the database without waiting for readers.
+    pub async fn checkpoint(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("PRAGMA wal_checkpoint(PASSIVE)").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Checkpoints the whole WAL and truncates it to zero bytes. Waits for
+    /// writers, and for readers still using the log.
+    pub async fn truncate_wal(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Refreshes the statistics the query planner chooses indexes by.
+    pub async fn analyze(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("ANALYZE").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Whether `ANALYZE` ever ran on this database.
+    pub async fn has_statistics(&self) -> Result<bool, sqlx::Error> {
+        let exists: Option<i64> = sqlx::query_scalar("SELECT 1 FROM sqlite_master WHERE name = 'sqlite_stat1'")
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(exists.is_some())
+    }
+
+    /// Reclaims free space and refreshes planner statistics.
+    ///
+    /// Runs a full `VACUUM` only while the file is not in incremental mode
+    /// yet; afterwards the free pages are released incrementally, so the
+    /// library stays usable meanwhile.
+    pub async fn run_maintenance(&self) -> Result<MaintenanceReport, sqlx::Error> {
+        let before = self.get_space().await?;
+        let full_vacuum = !self.is_incremental_vacuum().await?;
+        if full_vacuum {
+            println!("INFO: Converting the database to incremental vacuum with a full VACUUM");
+            // Connections ask for incremental mode when they open, VACUUM applies it
+            sqlx::query("PRAGMA auto_vacuum = INCREMENTAL").execute(&self.pool).await?;
+            sqlx::query("VACUUM").execute(&self.pool).await?;
+        } else {
+            self.incremental_vacuum(before.freelist_count).await?;
+        }
+        self.analyze().await?;
+        self.checkpoint().await?;
+        let vacuum = if full_vacuum { MaintenanceKind::FullVacuum } else { MaintenanceKind::Vacuum };
+        for kind in [vacuum, MaintenanceKind::Analyze, MaintenanceKind::Checkpoint] {
+            self.record_maintenance(kind).await?;
+        }
+
+        let after = self.get_space().await?;
+        let report = MaintenanceReport {
+            size_before: before.size_bytes(),
+            size_after: after.size_bytes(),
+            reclaimed_bytes: before.size_bytes().saturating_sub(after.size_bytes()),
+            full_vacuum,
+        };
+        println!("INFO: Database maintenance reclaimed {} bytes", report.reclaimed_bytes);
+        Ok(report)
+    }
+
+    pub async fn get_maintenance_log(&self) -> Result<MaintenanceLog, sqlx::Error> {
+        Ok(self
+            .get_setting(LOG_SETTING_KEY)
+            .await?
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default())
+    }
+
+    /// Stamps `kind` with the current time in the maintenance log.
+    pub async fn record_maintenance(&self, kind: MaintenanceKind) -> Result<(), sqlx::Error> {
+        let mut log = self.get_maintenance_log().await?;
+        let now = Some(Utc::now().to_rfc3339());
+        match kind {
+            MaintenanceKind::Vacuum => log.last_vacuum_at = now,
+            MaintenanceKind::FullVacuum => {
+                log.last_full_vacuum_at = now.clone();
+                log.last_vacuum_at = now;
+            }
+            MaintenanceKind::Analyze => log.last_analyze_at = now,
+            MaintenanceKind::Checkpoint => log.last_checkpoint_at = now,
+        }
+        let value = serde_json::to_value(&log).unwrap_or_default();
+        self.set_setting(LOG_SETTING_KEY, &value).await
+    }
+
+    /// Sizes, free space, cache counters and maintenance history of the
+    /// library database.
+    pub async fn get_status(&self) -> Result<DbStatus, sqlx::Error> {
+        let files: Vec<(i64, String, String)> = sqlx::query_as("PRAGMA database_list").fetch_all(&self.pool).await?;
+        let path = files
+            .into_iter()
+            .find(|(_, name, _)| name == "main")
+            .map(|(_, _, file)| file)
+            .unwrap_or_default();
+        let file_size = |path: &str| std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+
+        let space = self.get_space().await?;
+        let (cache_hits, cache_misses) = self.cache_counters().await?;
+        let lookups = cache_hits + cache_misses;
+
+        Ok(DbStatus {
+            file_bytes: if path.is_empty() { space.size_bytes() } else { file_size(&path) },
+            wal_bytes: if path.is_empty() { 0 } else { file_size(&format!("{}-wal", path)) },
+            path,
+            page_size: space.page_size,
+            page_count: space.page_count,
+            freelist_count: space.freelist_count,
+            fragmentation: if space.page_count > 0 {
+                space.freelist_count as f64 / space.page_count as f64
+            } else {
+                0.0
+            },
+            incremental_vacuum: self.is_incremental_vacuum().await?,
+            cache_hits,
+            cache_misses,
+            cache_hit_rate: (lookups > 0).then(|| cache_hits as f64 / lookups as f64),
+            maintenance: self.get_maintenance_log().await?,
+        })
+    }
+
+    /// Page cache hits and misses summed over the idle pooled connections.
+    /// Each connection counts from when it opened, which for a pool that
+    /// keeps its connections is close to the app start.
+    async fn cache_counters(&self) -> Result<(i64, i64), sqlx::Error> {
+        use libsqlite3_sys::{sqlite3_db_status, SQLITE_DBSTATUS_CACHE_HIT, SQLITE_DBSTATUS_CACHE_MISS};
+
+        // Connections are held until all are read, so each is counted once
+        let mut held = Vec::new();
+        let (mut hits, mut misses) = (0i64, 0i64);
+        for _ in 0..self.pool.num_idle().max(1) {
+            let mut conn = self.pool.acquire().await?;
+            {
+                let mut handle = conn.lock_handle().await?;
+                let raw = handle.as_raw_handle().as_ptr();
+                for (op, total) in [(SQLITE_DBSTATUS_CACHE_HIT, &mut hits), (SQLITE_DBSTATUS_CACHE_MISS, &mut misses)] {
+                    let (mut current, mut highwater) = (0, 0);
+                    // SAFETY: the handle is locked for the duration of the call
+                    let rc = unsafe { sqlite3_db_status(raw, op, &mut current, &mut highwater, 0) };
+                    if rc == libsqlite3_sys::SQLITE_OK {
+                        *total += current as i64;
+                    }
+                }
+            }
+            held.push(conn);
+        }
+        Ok((hits, misses))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[tokio::test]
+    async fn test_incremental_vacuum_reclaims_free_pages() {
+        let library = crate::testkit::TestLibrary::open("vacuum").await;
+        let db = &library.db;
+        assert!(db.is_incremental_vacuum().await.unwrap());
+
+        sqlx::query("CREATE TABLE filler (data BLOB)").execute(&db.pool).await.unwrap();
+        for _ in 0..200 {
+            sqlx::query("INSERT INTO filler VALUES (zeroblob(8192))").execute(&db.pool).await.unwrap();
+        }
+        sqlx::query("DELETE FROM filler").execute(&db.pool).await.unwrap();
+        assert!(db.get_space().await.unwrap().freelist_count > 0);
+
+        let report = db.run_maintenance().await.unwrap();
+        assert!(!report.full_vacuum);
+        assert!(report.reclaimed_bytes > 0);
+        assert_eq!(db.get_space().await.unwrap().freelist_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_status_reports_files_and_maintenance() {
+        let library = crate::testkit::TestLibrary::open("db-status").await;
+        let db = &library.db;
+        sqlx::query("SELECT COUNT(*) FROM images").fetch_one(&db.pool).await.unwrap();
+
+        let status = db.get_status().await.unwrap();
+        assert!(status.path.ends_with("mundam.db"));
+        assert!(status.file_bytes > 0);
+        assert!(status.incremental_vacuum);
+        assert!(status.cache_hits + status.cache_misses > 0);
+        assert!(status.maintenance.last_analyze_at.is_none());
+
+        db.run_maintenance().await.unwrap();
+        db.truncate_wal().await.unwrap();
+        let status = db.get_status().await.unwrap();
+        assert!(status.maintenance.last_analyze_at.is_some());
+        assert!(status.maintenance.last_vacuum_at.is_some());
+        assert!(status.maintenance.last_full_vacuum_at.is_none());
+    }
+}
@@ -0,0 +1,93 @@
+//! Version chains grouped from versioned filenames.
+
+use crate::db::models::{ChainVersion, VersionChain};
+use crate::indexer::versions::DetectedChain;
+use super::Db;
+
+const CHAIN_COLUMNS: &str = "SELECT id, folder_id, base_name, version_count, latest_image_id FROM version_chains";
+
+impl Db {
+    /// Replaces the stored version chains of a folder with freshly detected ones.
+    pub async fn replace_folder_version_chains(
+        &self,
+        folder_id: i64,
+        chains: &[DetectedChain],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE images SET version_chain_id = NULL WHERE folder_id = ? AND version_chain_id IS NOT NULL")
+            .bind(folder_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM version_chains WHERE folder_id = ?")
+            .bind(folder_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for chain in chains {
+            let res = sqlx::query(
+                "INSERT INTO version_chains (folder_id, base_name, version_count, latest_image_id) VALUES (?, ?, ?, ?)"
+            )
+            .bind(folder_id)
+            .bind(&chain.base_name)
+            .bind(chain.versions.len() as i64)
+            .bind(chain.latest())
+            .execute(&mut *tx)
+            .await?;
+            let chain_id = res.last_insert_rowid();
+
+            for chunk in chain.versions.chunks(500) {
+                let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> =
+                    sqlx::QueryBuilder::new("UPDATE images SET version_chain_id = ");
+                query_builder.push_bind(chain_id);
+                query_builder.push(" WHERE id IN (");
+                let mut separated = query_builder.separated(", ");
+                for (_, image_id) in chunk {
+                    separated.push_bind(*image_id);
+                }
+                separated.push_unseparated(")");
+                query_builder.build().execute(&mut *tx).await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Lists the version chains detected in a folder.
+    pub async fn get_folder_version_chains(&self, folder_id: i64) -> Result<Vec<VersionChain>, sqlx::Error> {
+        sqlx::query_as::<_, VersionChain>(&format!("{} WHERE folder_id = ? ORDER BY base_name", CHAIN_COLUMNS))
+            .bind(folder_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Lists the versions of a chain from the oldest to the latest.
+    ///
+    /// The order is parsed back from the filenames, so no per-file column is
+    /// needed.
+    pub async fn get_chain_versions(&self, chain_id: i64) -> Result<Vec<ChainVersion>, sqlx::Error> {
+        let latest: Option<i64> = sqlx::query_scalar("SELECT latest_image_id FROM version_chains WHERE id = ?")
+            .bind(chain_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .flatten();
+        let rows = sqlx::query_as::<_, (i64, String, String, Option<String>)>(
+            "SELECT id, filename, path, thumbnail_path FROM images WHERE version_chain_id = ?"
+        )
+        .bind(chain_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut versions: Vec<(crate::indexer::versions::VersionRank, ChainVersion)> = rows
+            .into_iter()
+            .filter_map(|(image_id, filename, path, thumbnail_path)| {
+                let rank = crate::indexer::versions::parse_version_name(&filename)?.rank;
+                let is_latest = latest == Some(image_id);
+                Some((rank, ChainVersion { image_id, filename, path, thumbnail_path, is_latest }))
+            })
+            .collect();
+        versions.sort_by_key(|(rank, version)| (*rank, version.image_id));
+        Ok(versions.into_iter().map(|(_, version)| version).collect())
+    }
+}
@@ -4,7 +4,14 @@
 //! errors that can occur within the application. It uses `thiserror` for
 //! idiomatic error definition and implements `serde::Serialize` to allow
 //! returning structured error information to the frontend.
+//!
+//! Commands reject with `{ code, message, context }`: `code` is a stable
+//! snake_case name the frontend can branch on (`not_found`, `ffmpeg_missing`,
+//! `busy`...), `message` is the full human-readable text and `context` the
+//! detail carried by the variant, if any. The custom protocols answer with
+//! the same object as a JSON body.
 
+use serde::ser::SerializeStruct;
 use serde::{Serialize, Serializer};
 use thiserror::Error;
 
@@ -27,10 +34,14 @@ pub enum AppError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
-    /// Error related to image processing or transcoding.
+    /// Error related to media transcoding.
     #[error("Transcoding error: {0}")]
     Transcoding(String),
 
+    /// Error when an image can't be decoded or processed.
+    #[error("Image error: {0}")]
+    Image(String),
+
     /// Error when a resource (file, folder, tag) is not found.
     #[error("Not found: {0}")]
     NotFound(String),
@@ -39,21 +50,126 @@ pub enum AppError {
     #[error("Internal state error: {0}")]
     Internal(String),
 
+    /// Error when a command that edits the library runs in read-only mode.
+    #[error("The library is read-only: {0} is not allowed")]
+    ReadOnly(String),
+
+    /// Error returned by a remote storage backend (S3, WebDAV).
+    #[error("Remote storage error: {0}")]
+    Remote(String),
+
+    /// Error when an operation needs FFmpeg and none was found.
+    #[error("FFmpeg is not available (neither bundled nor in system PATH)")]
+    FfmpegMissing,
+
+    /// Error when a file type or operation is not supported.
+    #[error("Unsupported: {0}")]
+    Unsupported(String),
+
+    /// Error when a resource is in use by another operation; retrying later may succeed.
+    #[error("Busy: {0}")]
+    Busy(String),
+
     /// Generic error with a custom message.
     #[error("Error: {0}")]
     Generic(String),
 }
 
+impl AppError {
+    /// Stable machine-readable code of the error, sent to the frontend.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Db(_) => "db",
+            AppError::Migration(_) => "migration",
+            AppError::Tauri(_) => "tauri",
+            AppError::Io(_) => "io",
+            AppError::Transcoding(_) => "transcoding",
+            AppError::Image(_) => "image",
+            AppError::NotFound(_) => "not_found",
+            AppError::Internal(_) => "internal",
+            AppError::ReadOnly(_) => "read_only",
+            AppError::Remote(_) => "remote",
+            AppError::FfmpegMissing => "ffmpeg_missing",
+            AppError::Unsupported(_) => "unsupported",
+            AppError::Busy(_) => "busy",
+            AppError::Generic(_) => "generic",
+        }
+    }
+
+    /// The detail carried by the error, without the category prefix of its message.
+    pub fn context(&self) -> Option<String> {
+        match self {
+            AppError::Db(e) => Some(e.to_string()),
+            AppError::Migration(e) => Some(e.to_string()),
+            AppError::Tauri(e) => Some(e.to_string()),
+            AppError::Io(e) => Some(e.to_string()),
+            AppError::FfmpegMissing => None,
+            AppError::Transcoding(detail)
+            | AppError::Image(detail)
+            | AppError::NotFound(detail)
+            | AppError::Internal(detail)
+            | AppError::ReadOnly(detail)
+            | AppError::Remote(detail)
+            | AppError::Unsupported(detail)
+            | AppError::Busy(detail)
+            | AppError::Generic(detail) => Some(detail.clone()),
+        }
+    }
+
+    /// HTTP status the custom protocols answer this error with.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            AppError::NotFound(_) => 404,
+            AppError::Io(e) if e.kind() == std::io::ErrorKind::NotFound => 404,
+            AppError::Io(e) if e.kind() == std::io::ErrorKind::PermissionDenied => 403,
+            AppError::ReadOnly(_) => 403,
+            AppError::Unsupported(_) => 415,
+            AppError::Remote(_) => 502,
+            AppError::FfmpegMissing | AppError::Busy(_) => 503,
+            _ => 500,
+        }
+    }
+}
+
 impl Serialize for AppError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        // For now, we serialize the error as its string representation.
-        // In the future, we could serialize a structured object with an error code.
-        serializer.serialize_str(&self.to_string())
+        let mut state = serializer.serialize_struct("AppError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("context", &self.context())?;
+        state.end()
     }
 }
 
 /// A specialized `Result` type for Mundam backend operations.
 pub type AppResult<T> = Result<T, AppError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serializes_code_message_and_context() {
+        let value = serde_json::to_value(AppError::NotFound("Image 7".to_string())).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({ "code": "not_found", "message": "Not found: Image 7", "context": "Image 7" })
+        );
+
+        let value = serde_json::to_value(AppError::FfmpegMissing).unwrap();
+        assert_eq!(value["code"], "ffmpeg_missing");
+        assert!(value["context"].is_null());
+    }
+
+    #[test]
+    fn test_http_status_follows_the_error_kind() {
+        assert_eq!(AppError::NotFound(String::new()).http_status(), 404);
+        assert_eq!(AppError::Io(std::io::ErrorKind::NotFound.into()).http_status(), 404);
+        assert_eq!(AppError::Busy(String::new()).http_status(), 503);
+        assert_eq!(AppError::Unsupported(String::new()).http_status(), 415);
+        assert_eq!(AppError::Generic(String::new()).http_status(), 500);
+    }
+}
@@ -830,6 +830,24 @@ pub const SUPPORTED_FORMATS: &[FileFormat] = &[
         preview_strategy: PreviewStrategy::None,
         playback: PlaybackStrategy::AudioHls, // USER: Transcode replaced by LinearHLS
     },
+    FileFormat {
+        name: "Blackmagic RAW",
+        extensions: &["braw"],
+        mime_types: &["video/x-braw"],
+        type_category: MediaType::Video,
+        strategy: ThumbnailStrategy::Ffmpeg, // Frames come from the recorded proxy (media::camera_raw)
+        preview_strategy: PreviewStrategy::None,
+        playback: PlaybackStrategy::Hls,
+    },
+    FileFormat {
+        name: "REDCODE RAW",
+        extensions: &["r3d"],
+        mime_types: &["video/x-red-r3d"],
+        type_category: MediaType::Video,
+        strategy: ThumbnailStrategy::Ffmpeg, // Frames come from the recorded proxy (media::camera_raw)
+        preview_strategy: PreviewStrategy::None,
+        playback: PlaybackStrategy::Hls,
+    },
     FileFormat {
         name: "Material Exchange Format",
         extensions: &["mxf"],
@@ -1041,7 +1059,7 @@ pub const SUPPORTED_FORMATS: &[FileFormat] = &[
     },
     FileFormat {
         name: "MPEG-4 Audio",
-        extensions: &["m4a", "aac", "m4r"],
+        extensions: &["m4a", "aac", "m4r", "m4b"],
         mime_types: &["audio/mp4", "audio/aac", "audio/x-m4a"],
         type_category: MediaType::Audio,
         strategy: ThumbnailStrategy::Icon,
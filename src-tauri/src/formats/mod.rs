@@ -9,6 +9,21 @@ pub mod definitions;
 pub use types::*;
 pub use definitions::SUPPORTED_FORMATS;
 
+/// MIME types shared by so many formats that magic bytes alone can't prove an
+/// extension wrong.
+const AMBIGUOUS_CONTAINER_MIMES: &[&str] = &[
+    "image/tiff",
+    "application/zip",
+    "application/octet-stream",
+    "application/x-ole-storage",
+    "video/mp4",
+    "audio/mp4",
+    "audio/x-m4a",
+    "video/quicktime",
+    "video/x-matroska",
+    "audio/x-matroska",
+];
+
 #[derive(Debug, Clone, Serialize)]
 pub struct FileFormat {
     pub name: &'static str,
@@ -53,7 +68,8 @@ impl FileFormat {
                 // CRITICAL FIX: If infer says it's a generic format like TIFF or ZIP,
                 // we check the extension FIRST because many professional formats (RAW, Adobe, Affinity)
                 // use these containers but need specific processing.
-                if mime == "image/tiff" || mime == "application/zip" || mime == "application/octet-stream" {
+                // Blackmagic RAW uses a QuickTime container the same way.
+                if mime == "image/tiff" || mime == "application/zip" || mime == "application/octet-stream" || mime == "video/quicktime" {
                     if let Some(fmt) = Self::detect_extension(path_fallback) {
                          // If the extension match is also a member of this container family or the strategy is specific, use it.
                          return Some(fmt);
@@ -71,7 +87,36 @@ impl FileFormat {
         Self::detect_extension(path_fallback)
     }
 
-    fn detect_extension(path: &Path) -> Option<&'static FileFormat> {
+    /// Detects files whose extension disagrees with their magic bytes.
+    ///
+    /// Returns the format the content actually belongs to, or `None` when the
+    /// extension is plausible. Container families that legitimately host many
+    /// extensions (TIFF-based RAWs, ZIP-based design files, ISO-BMFF, Matroska)
+    /// are never reported, since their magic bytes can't tell them apart.
+    pub fn detect_mismatch(path: &Path) -> Option<&'static FileFormat> {
+        let mut file = File::open(path).ok()?;
+        let mut buffer = [0u8; 1024];
+        let read = file.read(&mut buffer).ok()?;
+        let mime = infer::get(&buffer[..read])?.mime_type();
+
+        if AMBIGUOUS_CONTAINER_MIMES.contains(&mime) {
+            return None;
+        }
+
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        let claimed: Vec<&'static FileFormat> = SUPPORTED_FORMATS
+            .iter()
+            .filter(|f| f.extensions.contains(&ext.as_str()))
+            .collect();
+        if claimed.is_empty() || claimed.iter().any(|f| f.mime_types.contains(&mime)) {
+            return None;
+        }
+
+        SUPPORTED_FORMATS.iter().find(|f| f.mime_types.contains(&mime))
+    }
+
+    /// Looks a format up by extension only, without reading the file.
+    pub fn detect_extension(path: &Path) -> Option<&'static FileFormat> {
         if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
             let ext_lower = ext.to_lowercase();
             return SUPPORTED_FORMATS.iter().find(|f| f.extensions.contains(&ext_lower.as_str()));
@@ -85,3 +130,31 @@ impl FileFormat {
         Self::detect_extension(path).is_some()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_HEADER: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 0x0D, b'I', b'H', b'D', b'R'];
+
+    fn write_temp(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join("mundam_format_tests");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_detect_mismatch_reports_lying_extension() {
+        let path = write_temp("really_png.jpg", PNG_HEADER);
+        let detected = FileFormat::detect_mismatch(&path).map(|f| f.name);
+        assert_eq!(detected, Some("PNG Image"));
+    }
+
+    #[test]
+    fn test_detect_mismatch_accepts_matching_extension() {
+        let path = write_temp("honest.png", PNG_HEADER);
+        assert!(FileFormat::detect_mismatch(&path).is_none());
+    }
+}
@@ -0,0 +1,30 @@
+//! Entry points for the fuzz targets in `fuzz/`, built with the `fuzzing`
+//! feature only.
+//!
+//! Each one feeds arbitrary bytes to a parser that reads untrusted files.
+//! Errors are expected and ignored; the targets are looking for panics,
+//! hangs and runaway allocations.
+
+use std::io::Cursor;
+
+use crate::thumbnails::extractors::{binary_jpeg, mdp, sai, sai2};
+
+pub fn mdp(data: &[u8]) {
+    let _ = mdp::extract_mdp_preview_from_reader(&mut Cursor::new(data));
+}
+
+pub fn sai(data: &[u8]) {
+    let _ = sai::extract_sai_preview_from_reader(Cursor::new(data));
+}
+
+pub fn sai2(data: &[u8]) {
+    let _ = sai2::extract_sai2_preview_from_reader(&mut Cursor::new(data));
+}
+
+pub fn eps(data: &[u8]) {
+    let _ = binary_jpeg::extract_eps_binary_pointer_from_reader(&mut Cursor::new(data));
+}
+
+pub fn binary_jpeg(data: &[u8]) {
+    let _ = binary_jpeg::find_any_embedded(data);
+}
@@ -0,0 +1,167 @@
+//! Background read of capture times (see `media::capture_time`) and GPS
+//! positions.
+//!
+//! Files are read after indexing rather than during it, like filename
+//! keywords: parsing EXIF means reading the file, which would slow scans of
+//! large RAW libraries. Files that change on disk are queued again by the
+//! indexer.
+
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::time::{sleep, Duration};
+
+use crate::db::capture_times::CaptureRecord;
+use crate::db::Db;
+use crate::media::capture_time::{self, DefaultZone};
+use crate::media::exif_cache::EXIF_FORMATS;
+use crate::media::metadata_reader;
+use crate::paths;
+
+/// Files read per transaction.
+const BATCH_SIZE: i64 = 100;
+
+/// Pause once every file has been read.
+const IDLE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Outcome of [`repair`].
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureTimeRepair {
+    /// Times recorded without an offset, converted again.
+    pub renormalized: u64,
+    /// Files without a capture time, queued to be read again.
+    pub requeued: u64,
+}
+
+/// The library's default zone, from the `default_timezone` setting.
+pub async fn default_zone(db: &Db) -> DefaultZone {
+    let setting = db.get_setting(capture_time::SETTING_KEY).await.ok().flatten();
+    DefaultZone::from_setting(setting.as_ref().and_then(|value| value.as_str()))
+}
+
+/// Converts again the capture times recorded without an offset, for a new
+/// default zone. Returns how many changed.
+pub async fn renormalize(db: &Db, zone: DefaultZone) -> Result<u64, sqlx::Error> {
+    let changed: Vec<_> = db
+        .get_zoneless_capture_times()
+        .await?
+        .into_iter()
+        .filter_map(|(id, raw, stored)| {
+            let utc = capture_time::normalize(&raw, None, zone).map(|time| time.utc);
+            (utc != stored).then_some((id, utc))
+        })
+        .collect();
+    db.update_captured_at(&changed).await?;
+    Ok(changed.len() as u64)
+}
+
+/// Fixes capture times imported before they were normalized, or under
+/// another default zone: converts the stored ones again and queues files
+/// where none was found to be read again.
+pub async fn repair(db: &Db) -> Result<CaptureTimeRepair, sqlx::Error> {
+    let renormalized = renormalize(db, default_zone(db).await).await?;
+    let requeued = db.requeue_missing_capture_times(EXIF_FORMATS).await?;
+    Ok(CaptureTimeRepair { renormalized, requeued })
+}
+
+fn read(image_id: i64, path: &str, zone: DefaultZone) -> CaptureRecord {
+    let exif = metadata_reader::read_capture_exif(&paths::from_db(path)).unwrap_or_default();
+    match exif.time {
+        Some((raw, offset)) => {
+            let offset = offset.as_deref().and_then(capture_time::parse_offset);
+            let time = capture_time::normalize(&raw, offset, zone);
+            CaptureRecord { image_id, raw: time.is_some().then_some(raw), time, location: exif.location }
+        }
+        None => CaptureRecord { image_id, raw: None, time: None, location: exif.location },
+    }
+}
+
+/// Starts the background task reading the capture time of new photos, in
+/// batches, then checking again every `IDLE_INTERVAL`. Paused while the
+/// library is read-only.
+pub fn start(db: Arc<Db>) {
+    tauri::async_runtime::spawn(async move {
+        let mut found: u64 = 0;
+        loop {
+            if crate::library::read_only::is_enabled() {
+                sleep(IDLE_INTERVAL).await;
+                continue;
+            }
+            match db.get_images_needing_capture_time(EXIF_FORMATS, BATCH_SIZE).await {
+                Ok(images) if !images.is_empty() => {
+                    let zone = default_zone(&db).await;
+                    let records = tauri::async_runtime::spawn_blocking(move || {
+                        images.iter().map(|(id, path)| read(*id, path, zone)).collect::<Vec<_>>()
+                    })
+                    .await
+                    .unwrap_or_default();
+                    found += records.iter().filter(|record| record.time.is_some()).count() as u64;
+                    // An empty batch means the reads panicked; wait rather than spin
+                    if records.is_empty() {
+                        sleep(IDLE_INTERVAL).await;
+                        continue;
+                    }
+                    if let Err(e) = db.set_capture_times(&records).await {
+                        eprintln!("WARN: Could not store capture times: {}", e);
+                        sleep(IDLE_INTERVAL).await;
+                        continue;
+                    }
+                    sleep(Duration::from_millis(50)).await;
+                }
+                Ok(_) => {
+                    if found > 0 {
+                        println!("INFO: Read the capture time of {} files", found);
+                        found = 0;
+                    }
+                    sleep(IDLE_INTERVAL).await;
+                }
+                Err(e) => {
+                    eprintln!("WARN: Could not list files needing a capture time: {}", e);
+                    sleep(IDLE_INTERVAL).await;
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+
+    #[tokio::test]
+    async fn test_repair_follows_the_default_zone() {
+        let library = crate::testkit::TestLibrary::open("capture-times").await;
+        let db = &library.db;
+        library.seed_images(&["1.jpg", "2.jpg", "3.jpg"]).await;
+        assert_eq!(db.get_images_needing_capture_time(EXIF_FORMATS, 10).await.unwrap().len(), 3);
+
+        let utc_zone = DefaultZone::utc();
+        let record = |image_id, raw: &str, offset| CaptureRecord {
+            image_id,
+            raw: Some(raw.to_string()),
+            time: capture_time::normalize(raw, capture_time::parse_offset(offset), utc_zone),
+            location: None,
+        };
+        db.set_capture_times(&[
+            record(1, "2024:05:01 14:00:00", "+02:00"),
+            record(2, "2024:05:01 14:00:00", ""),
+            CaptureRecord { image_id: 3, raw: None, time: None, location: None },
+        ])
+        .await
+        .unwrap();
+        assert!(db.get_images_needing_capture_time(EXIF_FORMATS, 10).await.unwrap().is_empty());
+
+        db.set_setting(capture_time::SETTING_KEY, &serde_json::json!("+09:00")).await.unwrap();
+        let outcome = repair(db).await.unwrap();
+        assert_eq!((outcome.renormalized, outcome.requeued), (1, 1));
+
+        let times: Vec<(i64, Option<DateTime<Utc>>, Option<i32>)> =
+            sqlx::query_as("SELECT id, captured_at, capture_offset FROM images ORDER BY id").fetch_all(&db.pool).await.unwrap();
+        let at = |value: &str| Some(DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc));
+        assert_eq!(times[0], (1, at("2024-05-01T12:00:00Z"), Some(120)), "a recorded offset is kept");
+        assert_eq!(times[1], (2, at("2024-05-01T05:00:00Z"), None));
+        assert_eq!(db.get_images_needing_capture_time(EXIF_FORMATS, 10).await.unwrap(), vec![(3, "/lib/3.jpg".to_string())]);
+    }
+}
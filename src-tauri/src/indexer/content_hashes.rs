@@ -0,0 +1,115 @@
+//! Background hashing of file contents, to find identical files (see
+//! `library::duplicates`).
+//!
+//! Files are hashed after indexing rather than during it, like capture
+//! times, as it means reading them whole. A file whose size no other file
+//! has can't have a copy, so only files sharing a size are read; a file
+//! joining one of their sizes later gets the others hashed along with it.
+//! Files that change on disk are queued again by the indexer.
+//!
+//! SHA-256 is the hash the library already uses for content keys (see
+//! `crate::peer`); reading the files costs more than hashing them.
+
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+use tokio::time::{sleep, Duration};
+
+use crate::db::Db;
+use crate::paths;
+
+/// Files hashed per transaction.
+const BATCH_SIZE: i64 = 50;
+
+/// Pause once every file has been hashed.
+const IDLE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Hex SHA-256 of the whole file at `path`.
+pub fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Starts the background task hashing files that share their size with
+/// another, in batches, then checking again every `IDLE_INTERVAL`. Paused
+/// while the library is read-only.
+pub fn start(db: Arc<Db>) {
+    tauri::async_runtime::spawn(async move {
+        let (mut hashed, mut failed) = (0u64, 0u64);
+        loop {
+            if crate::library::read_only::is_enabled() {
+                sleep(IDLE_INTERVAL).await;
+                continue;
+            }
+            match db.get_images_needing_content_hash(BATCH_SIZE).await {
+                Ok(images) if !images.is_empty() => {
+                    // Unreadable files are stored without a hash, so they aren't retried until they change
+                    let hashes = tauri::async_runtime::spawn_blocking(move || {
+                        images.into_iter().map(|(id, path)| (id, hash_file(&paths::from_db(&path)).ok())).collect::<Vec<_>>()
+                    })
+                    .await
+                    .unwrap_or_default();
+                    // An empty batch means the reads panicked; wait rather than spin
+                    if hashes.is_empty() {
+                        sleep(IDLE_INTERVAL).await;
+                        continue;
+                    }
+                    hashed += hashes.iter().filter(|(_, hash)| hash.is_some()).count() as u64;
+                    failed += hashes.iter().filter(|(_, hash)| hash.is_none()).count() as u64;
+                    if let Err(e) = db.set_content_hashes(&hashes).await {
+                        eprintln!("WARN: Could not store content hashes: {}", e);
+                        sleep(IDLE_INTERVAL).await;
+                        continue;
+                    }
+                    sleep(Duration::from_millis(50)).await;
+                }
+                Ok(_) => {
+                    if hashed > 0 || failed > 0 {
+                        println!("INFO: Hashed {} files to find duplicates ({} unreadable)", hashed, failed);
+                        (hashed, failed) = (0, 0);
+                    }
+                    sleep(IDLE_INTERVAL).await;
+                }
+                Err(e) => {
+                    eprintln!("WARN: Could not list files needing a content hash: {}", e);
+                    sleep(IDLE_INTERVAL).await;
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_file() {
+        let dir = std::env::temp_dir().join(format!("mundam-content-hash-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Larger than the read buffer, and equal to its copy but for the last byte
+        let mut data = vec![7u8; 1024 * 1024 + 10];
+        std::fs::write(dir.join("a.bin"), &data).unwrap();
+        std::fs::write(dir.join("b.bin"), &data).unwrap();
+        *data.last_mut().unwrap() = 8;
+        std::fs::write(dir.join("c.bin"), &data).unwrap();
+
+        let a = hash_file(&dir.join("a.bin")).unwrap();
+        assert_eq!(a.len(), 64);
+        assert_eq!(hash_file(&dir.join("b.bin")).unwrap(), a);
+        assert_ne!(hash_file(&dir.join("c.bin")).unwrap(), a);
+        assert!(hash_file(&dir.join("missing.bin")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
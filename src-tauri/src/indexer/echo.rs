@@ -0,0 +1,50 @@
+//! Suppression of watcher events caused by Mundam's own file operations.
+//!
+//! Commands that rename or move files update the database themselves. They
+//! register the affected paths here first so the watcher doesn't process the
+//! resulting filesystem events a second time.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// How long a registered path stays suppressed.
+const ECHO_WINDOW: Duration = Duration::from_secs(5);
+
+fn expected() -> &'static Mutex<HashMap<String, Instant>> {
+    static EXPECTED: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+    EXPECTED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Registers paths that are about to change because of an in-app operation.
+pub fn expect_changes<I, S>(paths: I)
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let deadline = Instant::now() + ECHO_WINDOW;
+    let mut map = expected().lock().unwrap_or_else(|e| e.into_inner());
+    for path in paths {
+        map.insert(path.into(), deadline);
+    }
+}
+
+/// Returns `true` if events for `path` should be ignored by the watcher.
+pub fn is_expected(path: &str) -> bool {
+    let now = Instant::now();
+    let mut map = expected().lock().unwrap_or_else(|e| e.into_inner());
+    map.retain(|_, deadline| *deadline > now);
+    map.contains_key(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expected_paths() {
+        expect_changes(["/tmp/mundam-echo/a.jpg".to_string()]);
+        assert!(is_expected("/tmp/mundam-echo/a.jpg"));
+        assert!(!is_expected("/tmp/mundam-echo/b.jpg"));
+    }
+}
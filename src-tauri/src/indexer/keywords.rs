@@ -0,0 +1,48 @@
+//! Background fill of filename keywords (see `db::keywords`).
+//!
+//! Files saved by the indexer get their keywords right away. This catches
+//! the rest: the library as it was before keywords existed, and files renamed
+//! since, whose keywords the database clears.
+
+use std::sync::Arc;
+
+use tokio::time::{sleep, Duration};
+
+use crate::db::Db;
+
+/// Files handled per transaction.
+const BATCH_SIZE: i64 = 500;
+
+/// Pause once every file has its keywords.
+const IDLE_INTERVAL: Duration = Duration::from_secs(30);
+
+pub fn start(db: Arc<Db>) {
+    tauri::async_runtime::spawn(async move {
+        let mut filled: u64 = 0;
+        loop {
+            match db.get_images_needing_keywords(BATCH_SIZE).await {
+                Ok(images) if !images.is_empty() => {
+                    if let Err(e) = db.set_filename_keywords(&images).await {
+                        eprintln!("WARN: Could not store filename keywords: {}", e);
+                        sleep(IDLE_INTERVAL).await;
+                        continue;
+                    }
+                    filled += images.len() as u64;
+                    // Let the grid and the indexer at the database between batches
+                    sleep(Duration::from_millis(50)).await;
+                }
+                Ok(_) => {
+                    if filled > 0 {
+                        println!("INFO: Stored filename keywords for {} files", filled);
+                        filled = 0;
+                    }
+                    sleep(IDLE_INTERVAL).await;
+                }
+                Err(e) => {
+                    eprintln!("WARN: Could not list files needing keywords: {}", e);
+                    sleep(IDLE_INTERVAL).await;
+                }
+            }
+        }
+    });
+}
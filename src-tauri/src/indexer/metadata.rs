@@ -2,6 +2,7 @@ use chrono::{DateTime, Utc};
 use imagesize::size;
 use std::path::Path;
 use crate::db::models::ImageMetadata;
+use crate::paths;
 
 pub fn get_image_metadata(path: &Path) -> Option<ImageMetadata> {
     let metadata = std::fs::metadata(path).ok()?;
@@ -13,12 +14,12 @@ pub fn get_image_metadata(path: &Path) -> Option<ImageMetadata> {
         Err(_) => (None, None),
     };
 
-    let filename = path.file_name()?.to_string_lossy().to_string();
+    let filename = paths::name_to_db(path.file_name()?);
     let format = path.extension()?.to_string_lossy().to_string().to_lowercase();
 
     Some(ImageMetadata {
         id: 0,
-        path: path.to_string_lossy().to_string(),
+        path: paths::to_db(path),
         filename,
         width,
         height,
@@ -30,5 +31,23 @@ pub fn get_image_metadata(path: &Path) -> Option<ImageMetadata> {
         modified_at,
         created_at,
         added_at: None,
+        playback_position: None,
+        playback_completed: false,
+        sequence_id: None,
+        latitude: None,
+        longitude: None,
+        duration: None,
+        frame_count: None,
+        loops: None,
+        captured_at: None,
+        capture_offset: None,
     })
 }
+
+/// Returns the canonical extension of the real format when the file's
+/// extension does not match its magic bytes.
+pub fn get_detected_format(path: &Path) -> Option<String> {
+    crate::formats::FileFormat::detect_mismatch(path)
+        .and_then(|f| f.extensions.first())
+        .map(|ext| ext.to_string())
+}
@@ -3,8 +3,17 @@ pub mod types;
 pub use types::*;
 pub mod watcher;
 pub mod scan;
+pub mod echo;
+pub mod sequences;
+pub mod versions;
+pub mod takeout;
+pub mod keywords;
+pub mod sort_keys;
+pub mod capture_times;
+pub mod content_hashes;
 
 use crate::db::Db;
+use crate::paths;
 use std::sync::Arc;
 use tauri::AppHandle;
 
@@ -33,6 +42,17 @@ impl Indexer {
         }
     }
 
+    /// Whether a watcher is registered for the root, under its given or canonical path.
+    pub async fn is_watching(&self, root_path: &str) -> bool {
+        let canonical = std::path::Path::new(root_path)
+            .canonicalize()
+            .map(|p| normalize_path(&paths::to_db(&p)))
+            .ok();
+        let registry = self.registry.lock().await;
+        registry.watchers.contains_key(&normalize_path(root_path))
+            || canonical.is_some_and(|p| registry.watchers.contains_key(&p))
+    }
+
     pub async fn start_scan(&self, root_path: std::path::PathBuf) {
         scan::run_scan(
             self.app_handle.clone(),
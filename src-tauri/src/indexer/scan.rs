@@ -1,44 +1,70 @@
 use super::types::{ProgressPayload, IndexedImage, WatcherRegistry};
 use super::watcher::start_watcher;
 use crate::db::Db;
-use crate::db::models::ImageMetadata;
-use crate::indexer::metadata::get_image_metadata;
+use crate::db::models::{ImageMetadata, StockInfo};
+use crate::indexer::metadata::{get_image_metadata, get_detected_format};
+use crate::indexer::takeout::{self, TakeoutReport};
+use crate::paths;
 use chrono::{DateTime, Utc};
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
 use tokio::sync::mpsc;
 use walkdir::WalkDir;
 
-pub async fn run_scan(
-    app: AppHandle,
+/// Files handed to one blocking metadata task at a time.
+const SCAN_CHUNK_SIZE: usize = 64;
+
+pub async fn run_scan<R: Runtime>(
+    app: AppHandle<R>,
     db: Arc<Db>,
     registry: Arc<tokio::sync::Mutex<WatcherRegistry>>,
     root_path: PathBuf
 ) {
     // Normalize root path (absolute and resolve symlinks)
     let root_path = root_path.canonicalize().unwrap_or(root_path);
-    let root_str = normalize_path(&root_path.to_string_lossy());
+    let root_str = normalize_path(&paths::to_db(&root_path));
 
     println!("DEBUG: Indexer::start_scan for {}", root_str);
     let root_for_watcher = root_path.clone();
+    let scan_started_at = Utc::now();
+    let scan_timer = std::time::Instant::now();
+    // Entries the walk or the metadata readers could not open
+    let unreadable = Arc::new(AtomicUsize::new(0));
+    // Shown in the job list next to queued jobs
+    let job = match app.try_state::<Arc<crate::jobs::JobQueue>>() {
+        Some(queue) => queue.track("scan", &root_str).await,
+        None => None,
+    };
 
     // 1. Initial Quick Scan - Collect files and folders
     let comparison_cache = db.get_all_files_comparison_data(&root_str).await.unwrap_or_default();
     let mut files_to_process: Vec<(PathBuf, String)> = Vec::new();
     let mut clean_count: usize = 0;
     let mut unique_dirs: HashSet<String> = HashSet::new();
-
-    for entry in WalkDir::new(&root_path).into_iter().filter_map(|e| e.ok()) {
+    // JSON files per folder, possible Google Takeout sidecars
+    let mut sidecars_by_dir: HashMap<String, HashSet<String>> = HashMap::new();
+    // XMP sidecars, possibly edited while Mundam was closed
+    let mut xmp_sidecars: Vec<PathBuf> = Vec::new();
+
+    for entry in WalkDir::new(&root_path) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => {
+                unreadable.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+        };
         let path = entry.path();
-        let path_str = normalize_path(&path.to_string_lossy());
+        let path_str = normalize_path(&paths::to_db(&path));
 
         if entry.file_type().is_dir() {
             unique_dirs.insert(path_str);
         } else if entry.file_type().is_file() && is_image_file(path) {
             let parent = path.parent()
-                .map(|p| normalize_path(&p.to_string_lossy()))
+                .map(|p| normalize_path(&paths::to_db(p)))
                 .unwrap_or_default();
             unique_dirs.insert(parent.clone());
 
@@ -60,9 +86,31 @@ pub async fn run_scan(
             } else {
                 clean_count += 1;
             }
+        } else if entry.file_type().is_file() && takeout::is_sidecar_file(path) {
+            if let Some(parent) = path.parent() {
+                sidecars_by_dir
+                    .entry(normalize_path(&paths::to_db(&parent)))
+                    .or_default()
+                    .insert(paths::name_to_db(entry.file_name()));
+            }
+        } else if entry.file_type().is_file() && crate::media::sidecars::is_sidecar(path) {
+            xmp_sidecars.push(path.to_path_buf());
+        }
+    }
+
+    // Unchanged sidecars are only looked at, not parsed again
+    if !xmp_sidecars.is_empty() {
+        if let Err(e) = db.reset_sidecar_checks(&xmp_sidecars).await {
+            eprintln!("WARN: Could not queue the sidecars found: {}", e);
         }
     }
 
+    let takeout_report = if !sidecars_by_dir.is_empty() && !files_to_process.is_empty() && takeout::is_enabled(&db).await {
+        queue_takeout_metadata(&db, &files_to_process, sidecars_by_dir).await
+    } else {
+        None
+    };
+
     let total_files = files_to_process.len() + clean_count;
     println!("DEBUG: Indexer found {} images ({} changed, {} unchanged) and {} folders",
         total_files, files_to_process.len(), clean_count, unique_dirs.len());
@@ -70,6 +118,13 @@ pub async fn run_scan(
     // Ensure root is in the set
     unique_dirs.insert(root_str.clone());
 
+    // Probed before the folders found are matched to the stored ones, which
+    // ignores case only where the filesystem does
+    let case_sensitive = paths::is_case_sensitive(&root_path);
+    if let Ok(Some(root_id)) = db.get_folder_by_path(&root_str).await {
+        record_case_sensitivity(&db, root_id, case_sensitive).await;
+    }
+
     println!("DEBUG: Ensuring folder hierarchy for {} folders...", unique_dirs.len());
     // 2. Ensure Hierarchy Exists
     let folder_map = match ensure_folder_hierarchy(&db, unique_dirs, &root_str).await {
@@ -82,6 +137,9 @@ pub async fn run_scan(
             HashMap::new()
         }
     };
+    if let Some(&root_id) = folder_map.get(&root_str) {
+        record_case_sensitivity(&db, root_id, case_sensitive).await;
+    }
 
     // 3. Prune Orphaned Folders
     if !folder_map.is_empty() {
@@ -109,13 +167,21 @@ pub async fn run_scan(
         let app_worker = app.clone();
         let db_worker = db.clone();
         let folder_map_worker = folder_map.clone();
+        let unreadable_worker = unreadable.clone();
+        let root_str_worker = root_str.clone();
 
         tokio::spawn(async move {
             let mut processed: usize = clean_count;
             let mut batch: Vec<(i64, ImageMetadata)> = Vec::new();
+            let mut detected_batch: Vec<(String, Option<String>)> = Vec::new();
+            let mut page_batch: Vec<(String, i32)> = Vec::new();
+            let mut stock_batch: Vec<(String, Option<StockInfo>)> = Vec::new();
 
             // Initial progress for clean files
             if clean_count > 0 {
+                if let Some(job) = &job {
+                    job.progress(processed as i64, Some(total_files as i64), Some("Verifying unchanged files...")).await;
+                }
                 let _ = app_worker.emit(
                     "indexer:progress",
                     ProgressPayload {
@@ -131,9 +197,18 @@ pub async fn run_scan(
 
                 if let Some(&folder_id) = folder_map_worker.get(&indexed.parent_dir) {
                     batch.push((folder_id, indexed.metadata.clone()));
+                    detected_batch.push((indexed.metadata.path.clone(), indexed.detected_format.clone()));
+                    if let Some(count) = indexed.page_count {
+                        page_batch.push((indexed.metadata.path.clone(), count));
+                    }
+                    // Also clears the details of a file that no longer carries any
+                    stock_batch.push((indexed.metadata.path.clone(), indexed.stock.clone()));
                 }
 
                 if processed % chunk_size == 0 || processed == total_files {
+                    if let Some(job) = &job {
+                        job.progress(processed as i64, Some(total_files as i64), Some(&indexed.metadata.filename)).await;
+                    }
                     let _ = app_worker.emit(
                         "indexer:progress",
                         ProgressPayload {
@@ -146,6 +221,18 @@ pub async fn run_scan(
                     if let Err(e) = db_worker.save_images_batch(batch.drain(..).collect()).await {
                         eprintln!("Failed to save images batch: {}", e);
                     }
+                    if let Err(e) = db_worker.set_detected_formats(&detected_batch).await {
+                        eprintln!("Failed to save detected formats: {}", e);
+                    }
+                    detected_batch.clear();
+                    if let Err(e) = db_worker.set_page_counts(&page_batch).await {
+                        eprintln!("Failed to save page counts: {}", e);
+                    }
+                    page_batch.clear();
+                    if let Err(e) = db_worker.set_stock_info(&stock_batch).await {
+                        eprintln!("Failed to save stock info: {}", e);
+                    }
+                    stock_batch.clear();
                 }
             }
 
@@ -154,32 +241,172 @@ pub async fn run_scan(
                 if let Err(e) = db_worker.save_images_batch(batch).await {
                     eprintln!("Failed to save final images batch: {}", e);
                 }
+                if let Err(e) = db_worker.set_detected_formats(&detected_batch).await {
+                    eprintln!("Failed to save final detected formats: {}", e);
+                }
+                if let Err(e) = db_worker.set_page_counts(&page_batch).await {
+                    eprintln!("Failed to save final page counts: {}", e);
+                }
+                if let Err(e) = db_worker.set_stock_info(&stock_batch).await {
+                    eprintln!("Failed to save final stock info: {}", e);
+                }
+            }
+
+            let folder_ids: Vec<i64> = folder_map_worker.values().copied().collect();
+            super::sequences::refresh_folder_sequences(&db_worker, &folder_ids).await;
+            super::versions::refresh_folder_version_chains(&db_worker, &folder_ids).await;
+
+            // Albums, keywords and ratings queued by importers for files indexed just now
+            let applied = match db_worker.apply_pending_import_metadata().await {
+                Ok(applied) => applied,
+                Err(e) => {
+                    eprintln!("Failed to apply imported metadata: {}", e);
+                    HashMap::new()
+                }
+            };
+            let files: usize = applied.values().map(|a| a.files).sum();
+            if files > 0 {
+                println!("INFO: Applied imported metadata to {} images", files);
+            }
+            let takeout_report = takeout_report.map(|report| report.with_applied(applied.get(takeout::SOURCE)));
+            if let Some(report) = &takeout_report {
+                let _ = app_worker.emit("indexer:takeout-report", report);
+            }
+
+            // The producer has dropped its sender, so every skipped file is counted by now
+            if let Some(&root_id) = folder_map_worker.get(&root_str_worker) {
+                record_scan(&db_worker, root_id, scan_started_at, scan_timer, total_files, unreadable_worker.load(Ordering::Relaxed)).await;
             }
 
+            if let Some(job) = job {
+                job.finish(Ok(Some(serde_json::json!({ "files": total_files, "takeout": takeout_report })))).await;
+            }
             let _ = app_worker.emit("indexer:complete", total_files);
         });
 
-        // 5. Producer - Distribute work
-        for (path, parent_dir) in files_to_process {
-            let tx_clone = tx.clone();
-            tokio::spawn(async move {
-                if let Some(meta) = get_image_metadata(&path) {
-                    let _ = tx_clone.send(IndexedImage {
-                        metadata: meta,
-                        parent_dir,
-                    }).await;
+        // 5. Producer - Extract metadata on a bounded pool of blocking tasks.
+        // Chunks are submitted in walk order and their results forwarded in the
+        // same order; once `workers` chunks are in flight we wait for the oldest,
+        // and `tx.send` blocks while the saver is behind, so memory stays flat.
+        let workers = crate::settings::config::scan_worker_count(&db).await;
+        println!("INFO: Extracting metadata with {} workers", workers);
+
+        let unreadable_producer = unreadable.clone();
+        tokio::spawn(async move {
+            let mut in_flight = VecDeque::with_capacity(workers);
+            let mut files = files_to_process.into_iter();
+
+            loop {
+                let chunk: Vec<(PathBuf, String)> = files.by_ref().take(SCAN_CHUNK_SIZE).collect();
+                let exhausted = chunk.is_empty();
+
+                if !exhausted {
+                    in_flight.push_back(tokio::task::spawn_blocking(move || extract_chunk(chunk)));
+                    if in_flight.len() < workers {
+                        continue;
+                    }
                 }
-            });
-        }
+
+                let Some(handle) = in_flight.pop_front() else { break };
+                match handle.await {
+                    Ok((results, skipped)) => {
+                        unreadable_producer.fetch_add(skipped, Ordering::Relaxed);
+                        for indexed in results {
+                            if tx.send(indexed).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Err(e) => eprintln!("Metadata extraction task failed: {}", e),
+                }
+            }
+        });
     } else {
+        if let Some(&root_id) = folder_map.get(&root_str) {
+            record_scan(&db, root_id, scan_started_at, scan_timer, 0, unreadable.load(Ordering::Relaxed)).await;
+        }
+        if let Some(job) = job {
+            job.finish(Ok(Some(serde_json::json!({ "files": 0 })))).await;
+        }
         let _ = app.emit("indexer:complete", 0);
     }
 
     // 6. Start File Watcher
-    start_watcher(app, db, registry, root_for_watcher, root_str);
+    // With the case sensitivity just probed, which a watcher reading it earlier would miss
+    start_watcher(app, db, registry, root_for_watcher, root_str, case_sensitive);
+}
+
+/// Reads the Google Takeout sidecars of the files about to be indexed and
+/// queues their metadata. Returns a report if any sidecar was found.
+async fn queue_takeout_metadata(
+    db: &Db,
+    files: &[(PathBuf, String)],
+    sidecars_by_dir: HashMap<String, HashSet<String>>,
+) -> Option<TakeoutReport> {
+    let files = files.to_vec();
+    let (entries, report) = match tokio::task::spawn_blocking(move || takeout::read_sidecars(&files, &sidecars_by_dir)).await {
+        Ok(read) => read,
+        Err(e) => {
+            eprintln!("Takeout sidecar task failed: {}", e);
+            return None;
+        }
+    };
+    if entries.is_empty() {
+        return None;
+    }
+
+    if let Err(e) = db.queue_import_metadata(takeout::SOURCE, &entries).await {
+        eprintln!("Failed to queue Takeout metadata: {}", e);
+        return None;
+    }
+    println!("INFO: Read {} Takeout sidecars", report.sidecars);
+    Some(report)
+}
+
+/// Reads metadata for a chunk of files, skipping ones that can't be read.
+///
+/// Returns the indexed files and the number of skipped ones.
+fn extract_chunk(chunk: Vec<(PathBuf, String)>) -> (Vec<IndexedImage>, usize) {
+    let total = chunk.len();
+    let indexed: Vec<IndexedImage> = chunk
+        .into_iter()
+        .filter_map(|(path, parent_dir)| {
+            let meta = get_image_metadata(&path)?;
+            Some(IndexedImage {
+                metadata: meta,
+                parent_dir,
+                detected_format: get_detected_format(&path),
+                page_count: crate::thumbnails::pages::count_pages(&path),
+                stock: crate::media::stock::read_stock_info(&path),
+            })
+        })
+        .collect();
+    let skipped = total - indexed.len();
+    (indexed, skipped)
+}
+
+/// Stores the scan outcome shown by the location health report.
+async fn record_scan(
+    db: &Db,
+    root_id: i64,
+    started_at: DateTime<Utc>,
+    timer: std::time::Instant,
+    files_seen: usize,
+    unreadable: usize,
+) {
+    let duration_ms = timer.elapsed().as_millis() as i64;
+    if let Err(e) = db.record_location_scan(root_id, started_at, duration_ms, files_seen as i64, unreadable as i64).await {
+        eprintln!("WARN: Could not record scan of location {}: {}", root_id, e);
+    }
+}
+
+async fn record_case_sensitivity(db: &Db, root_id: i64, case_sensitive: bool) {
+    if let Err(e) = db.set_location_case_sensitivity(root_id, case_sensitive).await {
+        eprintln!("WARN: Could not record the case sensitivity of location {}: {}", root_id, e);
+    }
 }
 
-async fn ensure_folder_hierarchy(
+pub(crate) async fn ensure_folder_hierarchy(
     db: &Db,
     folders: std::collections::HashSet<String>,
     root_path: &str,
@@ -0,0 +1,160 @@
+//! Image sequence detection.
+//!
+//! Renders and scans are written as one file per frame (`shot_0001.exr` …
+//! `shot_0240.exr`). Files in the same folder that only differ by a trailing
+//! frame number are grouped into a sequence so the grid shows a single item.
+
+use std::collections::HashMap;
+
+use crate::db::Db;
+
+/// Formats that are commonly written as frame sequences.
+///
+/// Camera formats (jpg, raw) are left out: consecutive photo numbers
+/// (`IMG_0001.jpg`) are separate shots, not frames.
+const SEQUENCE_EXTENSIONS: &[&str] = &["exr", "dpx", "cin", "png", "tif", "tiff", "tga", "sgi", "rgb", "hdr"];
+
+/// Minimum number of frames before files are grouped.
+const MIN_SEQUENCE_FRAMES: usize = 5;
+
+/// Minimum number of frame digits, so `v2.png` and `v3.png` stay apart.
+const MIN_FRAME_DIGITS: usize = 3;
+
+/// A filename split around its frame number.
+#[derive(Debug, PartialEq)]
+pub struct FrameName<'a> {
+    /// Everything before the frame number, separator included.
+    pub prefix: &'a str,
+    pub frame: i64,
+    /// Width of the frame number, padding zeros included.
+    pub digits: usize,
+    /// Lowercased extension, without the dot.
+    pub extension: String,
+}
+
+/// Splits `shot_0042.exr` into `("shot_", 42, 4 digits, "exr")`.
+pub fn parse_frame_name(filename: &str) -> Option<FrameName<'_>> {
+    let (stem, extension) = filename.rsplit_once('.')?;
+    let extension = extension.to_lowercase();
+    if !SEQUENCE_EXTENSIONS.contains(&extension.as_str()) {
+        return None;
+    }
+
+    let digits = stem.bytes().rev().take_while(u8::is_ascii_digit).count();
+    if digits < MIN_FRAME_DIGITS {
+        return None;
+    }
+
+    let (prefix, number) = stem.split_at(stem.len() - digits);
+    Some(FrameName {
+        prefix,
+        frame: number.parse().ok()?,
+        digits,
+        extension,
+    })
+}
+
+/// A group of files recognised as one sequence.
+#[derive(Debug, PartialEq)]
+pub struct DetectedSequence {
+    /// Filename with one `#` per frame digit.
+    pub pattern: String,
+    /// `(frame, image_id)` sorted by frame.
+    pub frames: Vec<(i64, i64)>,
+}
+
+impl DetectedSequence {
+    /// Lowest frame number, or 0 for an empty sequence.
+    pub fn first_frame(&self) -> i64 {
+        self.frames.first().map(|f| f.0).unwrap_or(0)
+    }
+
+    /// Highest frame number, or 0 for an empty sequence.
+    pub fn last_frame(&self) -> i64 {
+        self.frames.last().map(|f| f.0).unwrap_or(0)
+    }
+
+    /// Middle frame, which is more representative of a shot than the first one.
+    pub fn representative(&self) -> Option<i64> {
+        self.frames.get(self.frames.len() / 2).map(|f| f.1)
+    }
+}
+
+/// Groups `(image_id, filename)` pairs of one folder into sequences.
+pub fn group_sequences(files: &[(i64, String)]) -> Vec<DetectedSequence> {
+    let mut groups: HashMap<String, Vec<(i64, i64)>> = HashMap::new();
+    for (id, filename) in files {
+        if let Some(name) = parse_frame_name(filename) {
+            let pattern = format!("{}{}.{}", name.prefix, "#".repeat(name.digits), name.extension);
+            groups.entry(pattern).or_default().push((name.frame, *id));
+        }
+    }
+
+    let mut sequences: Vec<DetectedSequence> = groups
+        .into_iter()
+        .filter(|(_, frames)| frames.len() >= MIN_SEQUENCE_FRAMES)
+        .map(|(pattern, mut frames)| {
+            frames.sort_unstable();
+            DetectedSequence { pattern, frames }
+        })
+        .collect();
+    sequences.sort_by(|a, b| a.pattern.cmp(&b.pattern));
+    sequences
+}
+
+/// Re-detects the sequences of the given folders and stores them.
+pub async fn refresh_folder_sequences(db: &Db, folder_ids: &[i64]) {
+    let mut total = 0;
+    for &folder_id in folder_ids {
+        let files = match db.get_folder_filenames(folder_id).await {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("WARN: Could not list folder {} for sequence detection: {}", folder_id, e);
+                continue;
+            }
+        };
+
+        let sequences = group_sequences(&files);
+        total += sequences.len();
+        if let Err(e) = db.replace_folder_sequences(folder_id, &sequences).await {
+            eprintln!("WARN: Could not store sequences for folder {}: {}", folder_id, e);
+        }
+    }
+
+    if total > 0 {
+        println!("INFO: Detected {} image sequences in {} folders", total, folder_ids.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_frame_name() {
+        let name = parse_frame_name("shot_0042.EXR").unwrap();
+        assert_eq!(name, FrameName { prefix: "shot_", frame: 42, digits: 4, extension: "exr".to_string() });
+        assert_eq!(parse_frame_name("plate.1001.dpx").unwrap().prefix, "plate.");
+        assert_eq!(parse_frame_name("0001.png").unwrap().prefix, "");
+        assert!(parse_frame_name("shot_v2.exr").is_none());
+        assert!(parse_frame_name("IMG_0001.jpg").is_none());
+        assert!(parse_frame_name("readme").is_none());
+    }
+
+    #[test]
+    fn test_group_sequences() {
+        let mut files: Vec<(i64, String)> = (1..=9).map(|f| (100 + f, format!("shot_{:04}.exr", f))).collect();
+        files.push((1, "shot_0001.png".to_string()));
+        files.push((2, "cover.exr".to_string()));
+        files.extend((1..=3).map(|f| (200 + f, format!("short_{:03}.dpx", f))));
+
+        let sequences = group_sequences(&files);
+        assert_eq!(sequences.len(), 1);
+
+        let sequence = &sequences[0];
+        assert_eq!(sequence.pattern, "shot_####.exr");
+        assert_eq!((sequence.first_frame(), sequence.last_frame()), (1, 9));
+        assert_eq!(sequence.frames.len(), 9);
+        assert_eq!(sequence.representative(), Some(105));
+    }
+}
@@ -0,0 +1,56 @@
+//! Background fill of filename sort keys (see `db::collation`).
+//!
+//! Files saved or renamed by the app get their key right away. This catches
+//! the rest: the library as it was before sort keys existed, and files whose
+//! name changed some other way, whose keys the database clears. Until then
+//! those files sort before the others.
+
+use std::sync::Arc;
+
+use tokio::time::{sleep, Duration};
+
+use crate::db::Db;
+
+/// Files handled per transaction.
+const BATCH_SIZE: i64 = 500;
+
+/// Pause once every file has its sort key.
+const IDLE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Starts the background task filling in missing sort keys, in batches,
+/// then checking again every `IDLE_INTERVAL`. Paused while the library is
+/// read-only.
+pub fn start(db: Arc<Db>) {
+    tauri::async_runtime::spawn(async move {
+        let mut filled: u64 = 0;
+        loop {
+            if crate::library::read_only::is_enabled() {
+                sleep(IDLE_INTERVAL).await;
+                continue;
+            }
+            match db.get_images_needing_sort_key(BATCH_SIZE).await {
+                Ok(images) if !images.is_empty() => {
+                    if let Err(e) = db.set_filename_sort_keys(&images).await {
+                        eprintln!("WARN: Could not store filename sort keys: {}", e);
+                        sleep(IDLE_INTERVAL).await;
+                        continue;
+                    }
+                    filled += images.len() as u64;
+                    // Let the grid and the indexer at the database between batches
+                    sleep(Duration::from_millis(50)).await;
+                }
+                Ok(_) => {
+                    if filled > 0 {
+                        println!("INFO: Stored filename sort keys for {} files", filled);
+                        filled = 0;
+                    }
+                    sleep(IDLE_INTERVAL).await;
+                }
+                Err(e) => {
+                    eprintln!("WARN: Could not list files needing sort keys: {}", e);
+                    sleep(IDLE_INTERVAL).await;
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,378 @@
+//! Google Takeout (Google Photos export) sidecars.
+//!
+//! Takeout writes the metadata of each photo to a JSON file next to it:
+//! capture time, description, people, location and favorite flag. While a
+//! folder is scanned, and when the watcher picks up new files, the sidecars
+//! of new and changed files are read and queued as imported metadata (see
+//! `db::imports`), so they are merged once the images exist. Files indexed
+//! before keep their metadata, so edits made in Mundam aren't overwritten by
+//! a rescan.
+//!
+//! Sidecar names are inconsistent across Takeout versions: `IMG_1.JPG.json`,
+//! `IMG_1.JPG.supplemental-metadata.json`, both truncated to 51 characters,
+//! `IMG_1.JPG(1).json` for `IMG_1(1).JPG`, and no sidecar of its own for
+//! `IMG_1-edited.JPG`. [`find_sidecar`] resolves all of them.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db::imports::AppliedImport;
+use crate::db::models::ImportedMetadata;
+use crate::db::Db;
+use crate::paths;
+
+/// Importer name recorded with queued metadata.
+pub const SOURCE: &str = "google_takeout";
+
+/// Settings key of the toggle; sidecars are read unless it is `false`.
+pub const SETTING_KEY: &str = "import_takeout_sidecars";
+
+/// Parent tag of the tags created for people.
+const PEOPLE_TAG: &str = "People";
+
+/// Rating given to favorites.
+const FAVORITE_RATING: i32 = 5;
+
+/// Length under which Takeout doesn't truncate sidecar names (51 with `.json`).
+const TRUNCATED_NAME_LENGTH: usize = 46;
+
+const SUPPLEMENTAL_SUFFIX: &str = ".supplemental-metadata";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Sidecar {
+    description: Option<String>,
+    photo_taken_time: Option<SidecarTime>,
+    geo_data: Option<SidecarGeo>,
+    geo_data_exif: Option<SidecarGeo>,
+    #[serde(default)]
+    people: Vec<SidecarPerson>,
+    #[serde(default)]
+    favorited: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SidecarTime {
+    /// Unix time, as a string.
+    timestamp: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SidecarGeo {
+    latitude: f64,
+    longitude: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SidecarPerson {
+    name: String,
+}
+
+/// Sidecars read by a scan and the fields they brought into the library,
+/// returned with the scan job. Fields a file already had are not counted.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TakeoutReport {
+    /// Files matched with a readable sidecar.
+    pub sidecars: usize,
+    /// Files whose creation time was set to the capture time.
+    pub taken_times: usize,
+    /// Files whose empty notes got the description.
+    pub descriptions: usize,
+    /// Files tagged with at least one new person.
+    pub people: usize,
+    /// Files that got a location, having none.
+    pub locations: usize,
+    /// Favorites whose rating was raised.
+    pub favorites: usize,
+}
+
+impl TakeoutReport {
+    /// Takes the field counts from what applying the queued sidecars changed.
+    pub fn with_applied(self, applied: Option<&AppliedImport>) -> Self {
+        let applied = applied.cloned().unwrap_or_default();
+        Self {
+            sidecars: self.sidecars,
+            taken_times: applied.taken_times,
+            descriptions: applied.notes,
+            people: applied.tagged,
+            locations: applied.locations,
+            favorites: applied.ratings,
+        }
+    }
+}
+
+/// Whether scans read Takeout sidecars (the `import_takeout_sidecars` setting).
+pub async fn is_enabled(db: &Db) -> bool {
+    match db.get_setting(SETTING_KEY).await {
+        Ok(Some(val)) => val.as_bool().unwrap_or(true),
+        _ => true,
+    }
+}
+
+pub fn is_sidecar_file(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("json"))
+}
+
+/// Splits the `(n)` Takeout appends to duplicate names:
+/// `IMG_1(1).JPG` gives `("IMG_1.JPG", "(1)")`.
+fn split_counter(file_name: &str) -> (String, String) {
+    let path = Path::new(file_name);
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+
+    if let Some(open) = stem.strip_suffix(')').and_then(|s| s.rfind('(')) {
+        let digits = &stem[open + 1..stem.len() - 1];
+        if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+            return (format!("{}{}", &stem[..open], extension), stem[open..].to_string());
+        }
+    }
+    (file_name.to_string(), String::new())
+}
+
+/// Whether the sidecar name without `{counter}.json`, `stem`, belongs to `name`.
+fn matches_name(stem: &str, name: &str) -> bool {
+    if stem == name {
+        return true;
+    }
+    // Old exports drop the media extension
+    if Path::new(name).file_stem().is_some_and(|s| s.to_string_lossy() == stem) {
+        return true;
+    }
+    // `.supplemental-metadata`, possibly cut short, or a name cut short
+    let full = format!("{}{}", name, SUPPLEMENTAL_SUFFIX);
+    full.starts_with(stem) && (stem.len() > name.len() || stem.chars().count() >= TRUNCATED_NAME_LENGTH)
+}
+
+/// Finds the sidecar of `file_name` among the JSON files of its folder.
+pub fn find_sidecar<'a>(file_name: &str, sidecars: &'a HashSet<String>) -> Option<&'a String> {
+    let (name, counter) = split_counter(file_name);
+    let mut names = vec![name.clone()];
+    // Edited copies share the sidecar of the original
+    let path = Path::new(&name);
+    if let Some(stem) = path.file_stem().map(|s| s.to_string_lossy().to_string()) {
+        if let Some(original) = stem.strip_suffix("-edited") {
+            let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+            names.push(format!("{}{}", original, extension));
+        }
+    }
+
+    let suffix = format!("{}.json", counter);
+    for name in &names {
+        if let Some(exact) = sidecars.get(&format!("{}{}", name, suffix)) {
+            return Some(exact);
+        }
+        let mut candidates: Vec<&String> = sidecars
+            .iter()
+            .filter(|s| s.strip_suffix(&suffix).is_some_and(|stem| matches_name(stem, name)))
+            .collect();
+        // Longest first: the least truncated name is the most specific
+        candidates.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        if let Some(found) = candidates.first() {
+            return Some(found);
+        }
+    }
+    None
+}
+
+/// Parses a sidecar into the metadata of the file at `path`. Returns `None`
+/// for JSON that isn't a Takeout sidecar.
+pub fn parse_sidecar(path: &str, content: &str) -> Option<ImportedMetadata> {
+    let value: serde_json::Value = serde_json::from_str(content).ok()?;
+    // Album `metadata.json` files and other JSON have no capture time
+    value.get("photoTakenTime")?;
+    let sidecar: Sidecar = serde_json::from_value(value).ok()?;
+
+    let taken_at = sidecar
+        .photo_taken_time
+        .and_then(|t| t.timestamp.parse::<i64>().ok())
+        .filter(|seconds| *seconds > 0)
+        .and_then(|seconds| DateTime::<Utc>::from_timestamp(seconds, 0));
+    // Missing locations are written as 0, 0
+    let location = [sidecar.geo_data, sidecar.geo_data_exif]
+        .into_iter()
+        .flatten()
+        .find(|g| g.latitude != 0.0 || g.longitude != 0.0);
+
+    let mut seen = HashSet::new();
+    let tags = sidecar
+        .people
+        .into_iter()
+        .map(|p| p.name.trim().to_string())
+        .filter(|name| !name.is_empty() && seen.insert(name.clone()))
+        .map(|name| (PEOPLE_TAG.to_string(), name))
+        .collect();
+
+    Some(ImportedMetadata {
+        path: path.to_string(),
+        rating: sidecar.favorited.then_some(FAVORITE_RATING),
+        notes: sidecar.description.filter(|d| !d.trim().is_empty()),
+        taken_at,
+        tags,
+        latitude: location.as_ref().map(|g| g.latitude),
+        longitude: location.as_ref().map(|g| g.longitude),
+    })
+}
+
+/// Reads the sidecars of `files`, given the JSON file names of each folder.
+/// Blocking.
+pub fn read_sidecars(
+    files: &[(PathBuf, String)],
+    sidecars_by_dir: &HashMap<String, HashSet<String>>,
+) -> (Vec<ImportedMetadata>, TakeoutReport) {
+    let mut entries = Vec::new();
+    let mut report = TakeoutReport::default();
+
+    for (path, parent) in files {
+        let Some(sidecars) = sidecars_by_dir.get(parent) else { continue };
+        let Some(file_name) = path.file_name().map(paths::name_to_db) else { continue };
+        let Some(sidecar) = find_sidecar(&file_name, sidecars) else { continue };
+
+        let content = match std::fs::read_to_string(paths::from_db(parent).join(paths::from_db(sidecar))) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("WARN: Could not read Takeout sidecar {}: {}", sidecar, e);
+                continue;
+            }
+        };
+        if let Some(entry) = parse_sidecar(&paths::to_db(path), &content) {
+            report.sidecars += 1;
+            entries.push(entry);
+        }
+    }
+    (entries, report)
+}
+
+/// Reads the sidecars of files added outside a scan, listing the JSON files
+/// of their folders. Blocking.
+pub fn read_sidecars_of(files: &[PathBuf]) -> (Vec<ImportedMetadata>, TakeoutReport) {
+    let mut sidecars_by_dir: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut with_parents = Vec::with_capacity(files.len());
+    for path in files {
+        let Some(parent) = path.parent() else { continue };
+        let parent_db = paths::to_db(parent);
+        if !sidecars_by_dir.contains_key(&parent_db) {
+            let names = std::fs::read_dir(parent)
+                .map(|entries| {
+                    entries
+                        .filter_map(|e| e.ok())
+                        .filter(|e| is_sidecar_file(&e.path()))
+                        .map(|e| paths::name_to_db(&e.file_name()))
+                        .collect()
+                })
+                .unwrap_or_default();
+            sidecars_by_dir.insert(parent_db.clone(), names);
+        }
+        with_parents.push((path.clone(), parent_db));
+    }
+    read_sidecars(&with_parents, &sidecars_by_dir)
+}
+
+/// Merges the sidecars of files the watcher just indexed. Returns a report
+/// if any sidecar was found.
+pub async fn import_added(db: &Db, files: Vec<PathBuf>) -> Option<TakeoutReport> {
+    if files.is_empty() || !is_enabled(db).await {
+        return None;
+    }
+    let (entries, report) = match tokio::task::spawn_blocking(move || read_sidecars_of(&files)).await {
+        Ok(read) => read,
+        Err(e) => {
+            eprintln!("Takeout sidecar task failed: {}", e);
+            return None;
+        }
+    };
+    if entries.is_empty() {
+        return None;
+    }
+
+    if let Err(e) = db.queue_import_metadata(SOURCE, &entries).await {
+        eprintln!("Failed to queue Takeout metadata: {}", e);
+        return None;
+    }
+    match db.apply_pending_import_metadata().await {
+        Ok(applied) => Some(report.with_applied(applied.get(SOURCE))),
+        Err(e) => {
+            eprintln!("Failed to apply imported metadata: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(list: &[&str]) -> HashSet<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_find_sidecar() {
+        let mut sidecars = names(&[
+            "IMG_1.JPG.json",
+            "IMG_2.JPG.supplemental-metadata.json",
+            "IMG_3.JPG.supplemental-me.json",
+            "IMG_1.JPG(1).json",
+            "PXL_20230101_123456789.PORTRAIT.ORIGINAL.jpg.s.json",
+            "VID_4.json",
+            "IMG_1.json",
+            "metadata.json",
+        ]);
+        let long_name = format!("{}.jpg", "A".repeat(50));
+        sidecars.insert(format!("{}.json", "A".repeat(TRUNCATED_NAME_LENGTH)));
+        let find = |name: &str| find_sidecar(name, &sidecars).map(String::as_str);
+
+        assert_eq!(find("IMG_1.JPG"), Some("IMG_1.JPG.json"));
+        assert_eq!(find("IMG_1-edited.JPG"), Some("IMG_1.JPG.json"));
+        assert_eq!(find("IMG_1(1).JPG"), Some("IMG_1.JPG(1).json"));
+        assert_eq!(find("IMG_2.JPG"), Some("IMG_2.JPG.supplemental-metadata.json"));
+        assert_eq!(find("IMG_3.JPG"), Some("IMG_3.JPG.supplemental-me.json"));
+        assert_eq!(
+            find("PXL_20230101_123456789.PORTRAIT.ORIGINAL.jpg"),
+            Some("PXL_20230101_123456789.PORTRAIT.ORIGINAL.jpg.s.json")
+        );
+        assert_eq!(find("VID_4.MP4"), Some("VID_4.json"));
+        assert_eq!(find(long_name.as_str()), Some(format!("{}.json", "A".repeat(TRUNCATED_NAME_LENGTH)).as_str()));
+        assert_eq!(find("IMG_5.JPG"), None);
+        // A short prefix is not a truncated name
+        assert_eq!(find("IMG_10.JPG"), None);
+    }
+
+    #[test]
+    fn test_parse_sidecar() {
+        let content = r#"{
+            "title": "IMG_1.JPG",
+            "description": "Beach",
+            "photoTakenTime": { "timestamp": "1700000000", "formatted": "Nov 14, 2023" },
+            "geoData": { "latitude": 0.0, "longitude": 0.0, "altitude": 0.0 },
+            "geoDataExif": { "latitude": 38.72, "longitude": -9.14, "altitude": 12.0 },
+            "people": [{ "name": "Ana" }, { "name": "Ana" }, { "name": " " }],
+            "favorited": true
+        }"#;
+        let entry = parse_sidecar("/t/IMG_1.JPG", content).unwrap();
+        assert_eq!(entry.taken_at.unwrap().timestamp(), 1_700_000_000);
+        assert_eq!(entry.notes.as_deref(), Some("Beach"));
+        assert_eq!(entry.tags, vec![("People".to_string(), "Ana".to_string())]);
+        assert_eq!((entry.latitude, entry.longitude), (Some(38.72), Some(-9.14)));
+        assert_eq!(entry.rating, Some(FAVORITE_RATING));
+
+        let bare = parse_sidecar("/t/a.jpg", r#"{ "description": "", "photoTakenTime": { "timestamp": "0" } }"#).unwrap();
+        assert_eq!((bare.taken_at, bare.notes, bare.latitude, bare.rating), (None, None, None, None));
+        // Album metadata has no capture time
+        assert!(parse_sidecar("/t/a.jpg", r#"{ "title": "Trip", "date": {} }"#).is_none());
+    }
+
+    #[test]
+    fn test_report_counts_applied_fields() {
+        let report = TakeoutReport { sidecars: 2, ..Default::default() };
+        let applied = AppliedImport { files: 2, notes: 1, locations: 1, ..Default::default() };
+        assert_eq!(
+            report.clone().with_applied(Some(&applied)),
+            TakeoutReport { sidecars: 2, descriptions: 1, locations: 1, ..Default::default() }
+        );
+        assert_eq!(report.with_applied(None), TakeoutReport { sidecars: 2, ..Default::default() });
+    }
+}
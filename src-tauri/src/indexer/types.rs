@@ -1,5 +1,5 @@
 use serde::Serialize;
-use crate::db::models::ImageMetadata;
+use crate::db::models::{ImageMetadata, StockInfo};
 use std::collections::HashMap;
 
 #[derive(Clone, Serialize)]
@@ -36,6 +36,12 @@ pub struct RemovedItemContext {
 pub struct IndexedImage {
     pub metadata: ImageMetadata,
     pub parent_dir: String,
+    /// Real format when the extension lies about the content
+    pub detected_format: Option<String>,
+    /// Pages or EXR layers, for multi-page formats
+    pub page_count: Option<i32>,
+    /// Stock provider details, for files from stock sites
+    pub stock: Option<StockInfo>,
 }
 
 #[derive(Default)]
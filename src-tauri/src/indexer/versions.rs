@@ -0,0 +1,207 @@
+//! Version chain detection.
+//!
+//! Design folders hold every export of a file: `hero_v01.psd`,
+//! `hero_v02.psd`, `hero_v03_final.psd`. Files in the same folder that only
+//! differ by such a version suffix are grouped into a chain, and the highest
+//! version is recorded as its latest, so the grid can hide the older ones.
+
+use std::collections::HashMap;
+
+use crate::db::Db;
+
+/// Position of a file in its chain. Fields compare in order: any `final`
+/// beats any numbered version, `final2` beats `final`, then `v` numbers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VersionRank {
+    pub is_final: bool,
+    pub final_number: u32,
+    pub version: u32,
+}
+
+impl VersionRank {
+    /// Whether the filename carried a version suffix at all.
+    pub fn is_versioned(&self) -> bool {
+        *self != VersionRank::default()
+    }
+}
+
+/// A filename split into its chain key and version.
+#[derive(Debug, PartialEq)]
+pub struct VersionName {
+    /// Lowercase filename without the version suffix: `hero.psd`.
+    pub base_name: String,
+    pub rank: VersionRank,
+}
+
+/// Reads one suffix segment (`v03`, `ver3`, `final`, `final2`) into `rank`.
+/// Returns `false` for anything else.
+fn read_segment(segment: &str, rank: &mut VersionRank) -> bool {
+    let segment = segment.to_lowercase();
+    let number = |digits: &str| -> Option<u32> {
+        (!digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())).then(|| digits.parse().ok()).flatten()
+    };
+
+    if let Some(rest) = segment.strip_prefix("final") {
+        if rank.is_final {
+            return false;
+        }
+        let final_number = if rest.is_empty() { Some(0) } else { number(rest) };
+        return match final_number {
+            Some(n) => {
+                *rank = VersionRank { is_final: true, final_number: n, ..*rank };
+                true
+            }
+            None => false,
+        };
+    }
+
+    let digits = ["version", "ver", "v"].iter().find_map(|prefix| segment.strip_prefix(prefix));
+    match digits.and_then(number) {
+        Some(version) if rank.version == 0 => {
+            rank.version = version;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Splits `hero_v03_final.PSD` into `("hero.psd", v3 final)`.
+///
+/// Suffix segments are read from the end of the name, each after a `_`,
+/// `-`, `.` or space. A name without any still parses, as the unversioned
+/// original of its chain. Returns `None` when nothing is left once the
+/// suffixes are removed.
+pub fn parse_version_name(filename: &str) -> Option<VersionName> {
+    let (mut stem, extension) = match filename.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, extension.to_lowercase()),
+        _ => (filename, String::new()),
+    };
+
+    let mut rank = VersionRank::default();
+    while let Some(index) = stem.rfind(['_', '-', '.', ' ']) {
+        if !read_segment(&stem[index + 1..], &mut rank) {
+            break;
+        }
+        stem = &stem[..index];
+    }
+
+    let stem = stem.trim_end_matches(['_', '-', '.', ' ']);
+    if stem.is_empty() {
+        return None;
+    }
+    let base_name = if extension.is_empty() {
+        stem.to_lowercase()
+    } else {
+        format!("{}.{}", stem.to_lowercase(), extension)
+    };
+    Some(VersionName { base_name, rank })
+}
+
+/// A group of files recognised as versions of one file.
+#[derive(Debug, PartialEq)]
+pub struct DetectedChain {
+    pub base_name: String,
+    /// `(rank, image_id)` from the oldest version to the latest.
+    pub versions: Vec<(VersionRank, i64)>,
+}
+
+impl DetectedChain {
+    pub fn latest(&self) -> Option<i64> {
+        self.versions.last().map(|v| v.1)
+    }
+}
+
+/// Groups `(image_id, filename)` pairs of one folder into version chains.
+///
+/// A chain needs two files and at least one version suffix.
+pub fn group_version_chains(files: &[(i64, String)]) -> Vec<DetectedChain> {
+    let mut groups: HashMap<String, Vec<(VersionRank, i64)>> = HashMap::new();
+    for (id, filename) in files {
+        if let Some(name) = parse_version_name(filename) {
+            groups.entry(name.base_name).or_default().push((name.rank, *id));
+        }
+    }
+
+    let mut chains: Vec<DetectedChain> = groups
+        .into_iter()
+        .filter(|(_, versions)| versions.len() >= 2 && versions.iter().any(|(rank, _)| rank.is_versioned()))
+        .map(|(base_name, mut versions)| {
+            // Same rank twice (`hero_v2`, `hero-v2`): the later import wins
+            versions.sort_unstable();
+            DetectedChain { base_name, versions }
+        })
+        .collect();
+    chains.sort_by(|a, b| a.base_name.cmp(&b.base_name));
+    chains
+}
+
+/// Re-detects the version chains of the given folders and stores them.
+pub async fn refresh_folder_version_chains(db: &Db, folder_ids: &[i64]) {
+    let mut total = 0;
+    for &folder_id in folder_ids {
+        let files = match db.get_folder_filenames(folder_id).await {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("WARN: Could not list folder {} for version detection: {}", folder_id, e);
+                continue;
+            }
+        };
+
+        let chains = group_version_chains(&files);
+        total += chains.len();
+        if let Err(e) = db.replace_folder_version_chains(folder_id, &chains).await {
+            eprintln!("WARN: Could not store version chains for folder {}: {}", folder_id, e);
+        }
+    }
+
+    if total > 0 {
+        println!("INFO: Detected {} version chains in {} folders", total, folder_ids.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rank(version: u32, is_final: bool) -> VersionRank {
+        VersionRank { is_final, final_number: 0, version }
+    }
+
+    #[test]
+    fn test_parse_version_name() {
+        let name = parse_version_name("Hero_Banner_v03_final.PSD").unwrap();
+        assert_eq!(name, VersionName { base_name: "hero_banner.psd".to_string(), rank: rank(3, true) });
+        assert_eq!(parse_version_name("hero-V12.png").unwrap().rank, rank(12, false));
+        assert_eq!(parse_version_name("hero ver2.png").unwrap().base_name, "hero.png");
+        assert_eq!(parse_version_name("hero_final2.png").unwrap().rank.final_number, 2);
+        assert_eq!(parse_version_name("hero.png").unwrap().rank, VersionRank::default());
+        // Not a version suffix
+        assert_eq!(parse_version_name("hero_vintage.png").unwrap().base_name, "hero_vintage.png");
+        assert_eq!(parse_version_name("finalize_v2.png").unwrap().base_name, "finalize.png");
+        assert!(parse_version_name("v01.psd").is_none());
+        assert!(parse_version_name("final.psd").is_none());
+    }
+
+    #[test]
+    fn test_group_version_chains() {
+        let files = vec![
+            (1, "hero.psd".to_string()),
+            (2, "hero_v01.psd".to_string()),
+            (3, "hero_v10.psd".to_string()),
+            (4, "hero_v02.psd".to_string()),
+            (5, "hero_final.psd".to_string()),
+            (6, "hero_v02.png".to_string()),
+            (7, "logo.psd".to_string()),
+            (8, "logo.png".to_string()),
+            (9, "icon_v1.svg".to_string()),
+        ];
+
+        let chains = group_version_chains(&files);
+        assert_eq!(chains.len(), 1, "single files and unversioned names form no chain");
+
+        let chain = &chains[0];
+        assert_eq!(chain.base_name, "hero.psd");
+        assert_eq!(chain.versions.iter().map(|v| v.1).collect::<Vec<_>>(), vec![1, 2, 4, 3, 5]);
+        assert_eq!(chain.latest(), Some(5));
+    }
+}
@@ -1,21 +1,24 @@
 use crate::db::Db;
 use crate::db::models::ImageMetadata;
-use crate::indexer::metadata::get_image_metadata;
+use crate::indexer::metadata::{get_image_metadata, get_detected_format};
+use crate::paths;
+use super::echo;
 use super::types::{BatchChangePayload, AddedItemContext, RemovedItemContext, WatcherRegistry};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
-use tauri::{AppHandle, Emitter, Manager};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
 use tokio::sync::mpsc;
 use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 
-pub fn start_watcher(
-    app: AppHandle,
+pub fn start_watcher<R: Runtime>(
+    app: AppHandle<R>,
     db: Arc<Db>,
     registry: Arc<tokio::sync::Mutex<WatcherRegistry>>,
     path: PathBuf,
-    root_str: String
+    root_str: String,
+    case_sensitive: bool
 ) {
     let watch_path = path.canonicalize().unwrap_or(path);
     let app_data_dir = app.path().app_local_data_dir().unwrap_or_else(|_| PathBuf::from(""));
@@ -53,6 +56,10 @@ pub fn start_watcher(
         let mut buffer_removed: std::collections::HashSet<String> = std::collections::HashSet::new();
         let mut buffer_renamed: HashMap<String, String> = HashMap::new();
         let mut pending_renames: HashMap<usize, String> = HashMap::new();
+        let mut buffer_sidecars: HashSet<PathBuf> = HashSet::new();
+        // Where case is ignored, the old spelling of a file renamed by case only still opens
+        // it: paths seen to exist are checked against their folder's listing once per batch
+        let mut buffer_case_checks: HashSet<PathBuf> = HashSet::new();
         let mut refresh_needed = false;
 
         let mut timer = tokio::time::interval(debouncer_window);
@@ -64,14 +71,27 @@ pub fn start_watcher(
                     break;
                 }
                 Some(event) = rx.recv() => {
-                    if event.paths.iter().any(|p| p.starts_with(&app_data_dir)) { continue; }
+                    // An unresolved (empty) app data dir would be a prefix of every path
+                    if !app_data_dir.as_os_str().is_empty() && event.paths.iter().any(|p| p.starts_with(&app_data_dir)) { continue; }
+                    // Renames done by Mundam itself are already reflected in the DB
+                    if !event.paths.is_empty() && event.paths.iter().all(|p| echo::is_expected(&normalize_path(&paths::to_db(p)))) {
+                        continue;
+                    }
+                    // New shots of a capture session are indexed by the session itself
+                    if !event.paths.is_empty() && event.paths.iter().all(|p| crate::library::capture_session::handles(p)) {
+                        continue;
+                    }
+                    // Sidecars edited by other applications are read again
+                    for path in event.paths.iter().filter(|p| crate::media::sidecars::is_sidecar(p)) {
+                        buffer_sidecars.insert(path.clone());
+                    }
                     // println!("DEBUG: Watcher RAW - {:?}", event);
 
                     match event.kind {
                         EventKind::Modify(notify::event::ModifyKind::Name(notify::event::RenameMode::Both)) => {
                             if event.paths.len() == 2 {
-                                let from = normalize_path(&event.paths[0].to_string_lossy());
-                                let to = normalize_path(&event.paths[1].to_string_lossy());
+                                let from = normalize_path(&paths::to_db(&event.paths[0]));
+                                let to = normalize_path(&paths::to_db(&event.paths[1]));
 
                                 if buffer_added_folders.remove(&from) {
                                     buffer_added_folders.insert(to);
@@ -84,7 +104,7 @@ pub fn start_watcher(
                         },
                         EventKind::Modify(notify::event::ModifyKind::Name(notify::event::RenameMode::From)) => {
                             if !event.paths.is_empty() {
-                                let path_str = normalize_path(&event.paths[0].to_string_lossy());
+                                let path_str = normalize_path(&paths::to_db(&event.paths[0]));
                                 if let Some(tracker) = event.attrs.tracker() {
                                     pending_renames.insert(tracker, path_str);
                                 } else {
@@ -94,7 +114,7 @@ pub fn start_watcher(
                         },
                         EventKind::Modify(notify::event::ModifyKind::Name(notify::event::RenameMode::To)) => {
                             if !event.paths.is_empty() {
-                                let path_str = normalize_path(&event.paths[0].to_string_lossy());
+                                let path_str = normalize_path(&paths::to_db(&event.paths[0]));
                                 let matched_from = if let Some(tracker) = event.attrs.tracker() {
                                     pending_renames.remove(&tracker)
                                 } else {
@@ -125,9 +145,12 @@ pub fn start_watcher(
                         },
                         _ => {
                             for path in event.paths {
-                                let path_str = normalize_path(&path.to_string_lossy());
+                                let path_str = normalize_path(&paths::to_db(&path));
                                 if path.exists() {
                                     if path_str != root_str_clone {
+                                        if !case_sensitive {
+                                            buffer_case_checks.insert(path.clone());
+                                        }
                                         if path.is_dir() {
                                             buffer_removed.remove(&path_str);
                                             buffer_added_folders.insert(path_str);
@@ -151,6 +174,24 @@ pub fn start_watcher(
                     for (_, path) in pending_renames.drain() {
                         buffer_removed.insert(path);
                     }
+                    if !buffer_case_checks.is_empty() {
+                        let mut listings = paths::FolderListings::default();
+                        for path in buffer_case_checks.drain() {
+                            let Some(actual) = listings.on_disk_path(&path).filter(|actual| *actual != path) else {
+                                continue;
+                            };
+                            let path_str = normalize_path(&paths::to_db(&path));
+                            buffer_added.remove(&path_str);
+                            buffer_added_folders.remove(&path_str);
+                            buffer_renamed.insert(path_str, normalize_path(&paths::to_db(&actual)));
+                        }
+                    }
+                    if !buffer_sidecars.is_empty() {
+                        let sidecars: Vec<PathBuf> = buffer_sidecars.drain().collect();
+                        if let Err(e) = db.reset_sidecar_checks(&sidecars).await {
+                            eprintln!("WARN: Could not queue changed sidecars: {}", e);
+                        }
+                    }
 
                     // Heuristics for non-tracked renames
                     let removed_list: Vec<String> = buffer_removed.iter().cloned().collect();
@@ -197,12 +238,15 @@ pub fn start_watcher(
                     let mut res_removed: Vec<RemovedItemContext> = Vec::new();
                     let mut res_updated: Vec<AddedItemContext> = Vec::new();
 
+                    // Folders whose frame files changed, so their sequences are re-detected
+                    let mut sequence_folders: HashSet<i64> = HashSet::new();
+
                     // A. Process Renames
                     for (from, to) in buffer_renamed.drain() {
                         let to_path = PathBuf::from(&to);
                         let new_name = to_path.file_name().and_then(|n| n.to_str()).unwrap_or("").to_string();
 
-                        if to_path.is_dir() {
+                        if paths::from_db(&to).is_dir() {
                             println!("DEBUG: Watcher - Processing FOLDER RENAME: {} -> {}", from, to);
                             match db.rename_folder(&from, &to, &new_name).await {
                                 Ok(true) => { println!("DEBUG: Watcher - Success folder rename: {} -> {}", from, to); },
@@ -223,6 +267,11 @@ pub fn start_watcher(
                             if folder_id > 0 {
                                 match db.rename_image(&from, &to, &new_name, folder_id).await {
                                     Ok(Some((meta, old_fid))) => {
+                                        let old_name = Path::new(&from).file_name().and_then(|n| n.to_str()).unwrap_or("");
+                                        if is_frame(old_name) || is_frame(&new_name) {
+                                            sequence_folders.insert(old_fid);
+                                            sequence_folders.insert(folder_id);
+                                        }
                                         res_updated.push(AddedItemContext {
                                             metadata: meta,
                                             folder_id,
@@ -230,7 +279,7 @@ pub fn start_watcher(
                                         });
                                     },
                                     _ => {
-                                        if let Some(meta) = get_image_metadata(&to_path) {
+                                        if let Some(meta) = get_image_metadata(&paths::from_db(&to)) {
                                             buffer_added.insert(to, meta);
                                         }
                                     }
@@ -258,21 +307,27 @@ pub fn start_watcher(
                             match db.get_image_context(&path_clone).await {
                                 Ok(Some((_img_id, _fid, _tags))) => {
                                     // Still in DB at this path? If so, it wasn't adopted.
-                                    if let Ok(Some((deleted_id, _, _))) = db.delete_image_by_path_returning_context(&path_clone).await {
+                                    if let Ok(Some((deleted_id, deleted_fid, _))) = db.delete_image_by_path_returning_context(&path_clone).await {
                                         println!("DEBUG: Watcher - Finalized removal for: {}", path_clone);
                                         let thumb = app_data_dir.join("thumbnails").join(format!("{}.webp", deleted_id));
                                         let _ = std::fs::remove_file(thumb);
+                                        let removed_name = Path::new(&path_clone).file_name().and_then(|n| n.to_str()).unwrap_or("");
+                                        if is_frame(removed_name) {
+                                            crate::indexer::sequences::refresh_folder_sequences(&db, &[deleted_fid]).await;
+                                        }
+                                        crate::library::filter_subscriptions::notify_changes(&app);
                                     }
                                 },
                                 Ok(None) => {
                                     // Check if it's a folder
                                     if let Ok(Some(fid)) = db.get_folder_by_path(&path_clone).await {
-                                        if !std::path::Path::new(&path_clone).exists() {
+                                        if !paths::from_db(&path_clone).exists() {
                                                 println!("DEBUG: Watcher - Deleting folder (delay expired): {}", path_clone);
                                                 let _ = db.delete_folder(fid).await;
                                                 let _ = app.emit("library:batch-change", BatchChangePayload {
                                                     added: vec![], removed: vec![], updated: vec![], needs_refresh: true
                                                 });
+                                                crate::library::filter_subscriptions::notify_changes(&app);
                                         }
                                     }
                                 },
@@ -290,10 +345,12 @@ pub fn start_watcher(
                     }
 
                     // D. Process Added Images
+                    // Folders that received a new version of a file, so their chains move on
+                    let mut versioned_folders: HashSet<i64> = HashSet::new();
                     for (path, meta) in buffer_added.drain() {
                         let parent = normalize_path(&Path::new(&path).parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default());
                         if let Ok(fid) = db.ensure_folder_hierarchy(&parent).await {
-                            match db.save_image(fid, &meta).await {
+                            match save_file(&db, fid, &meta).await {
                                 Ok((id, old_fid, is_new)) => {
                                     let mut meta_with_id = meta.clone();
                                     meta_with_id.id = id;
@@ -304,7 +361,17 @@ pub fn start_watcher(
                                         old_folder_id: old_fid
                                     };
 
+                                    if (is_new || old_fid.is_some()) && is_frame(&meta.filename) {
+                                        sequence_folders.insert(fid);
+                                        sequence_folders.extend(old_fid);
+                                    }
+
                                     if is_new {
+                                        let versioned = crate::indexer::versions::parse_version_name(&meta.filename)
+                                            .is_some_and(|name| name.rank.is_versioned());
+                                        if versioned {
+                                            versioned_folders.insert(fid);
+                                        }
                                         res_added.push(ctx);
                                     } else {
                                         res_updated.push(ctx);
@@ -315,6 +382,26 @@ pub fn start_watcher(
                         }
                     }
 
+                    if !sequence_folders.is_empty() {
+                        let folder_ids: Vec<i64> = sequence_folders.into_iter().collect();
+                        crate::indexer::sequences::refresh_folder_sequences(&db, &folder_ids).await;
+                    }
+
+                    if !versioned_folders.is_empty() {
+                        let folder_ids: Vec<i64> = versioned_folders.into_iter().collect();
+                        crate::indexer::versions::refresh_folder_version_chains(&db, &folder_ids).await;
+                    }
+
+                    if !res_added.is_empty() {
+                        let added: Vec<String> = res_added.iter().map(|ctx| ctx.metadata.path.clone()).collect();
+                        crate::library::proxies::queue_added(&app, &db, &added).await;
+                        // Takeout folders copied in while the app runs
+                        let files = added.iter().map(|path| paths::from_db(path)).collect();
+                        if let Some(report) = crate::indexer::takeout::import_added(&db, files).await {
+                            let _ = app.emit("indexer:takeout-report", &report);
+                        }
+                    }
+
                     if !res_added.is_empty() || !res_removed.is_empty() || !res_updated.is_empty() || refresh_needed {
                         let _ = app.emit("library:batch-change", BatchChangePayload {
                             added: res_added,
@@ -323,6 +410,7 @@ pub fn start_watcher(
                             needs_refresh: refresh_needed,
                         });
                         refresh_needed = false;
+                        crate::library::filter_subscriptions::notify_changes(&app);
                     }
                 }
             }
@@ -330,6 +418,40 @@ pub fn start_watcher(
     });
 }
 
+/// Saves a new or changed file with what the scan would record about it:
+/// its real format, page count and stock details. Returns the image id, its
+/// previous folder when it moved, and whether it is new.
+pub async fn save_file(db: &Db, folder_id: i64, meta: &ImageMetadata) -> Result<(i64, Option<i64>, bool), sqlx::Error> {
+    let saved = db.save_image(folder_id, meta).await?;
+    let path = paths::from_db(&meta.path);
+    // Each reads the file
+    let read = tauri::async_runtime::spawn_blocking(move || {
+        (get_detected_format(&path), crate::thumbnails::pages::count_pages(&path), crate::media::stock::read_stock_info(&path))
+    })
+    .await;
+    let Ok((detected, pages, stock)) = read else {
+        eprintln!("Error reading the details of {}", meta.path);
+        return Ok(saved);
+    };
+    if let Err(e) = db.set_detected_formats(&[(meta.path.clone(), detected)]).await {
+        eprintln!("Error saving detected format: {}", e);
+    }
+    if let Some(count) = pages {
+        if let Err(e) = db.set_page_counts(&[(meta.path.clone(), count)]).await {
+            eprintln!("Error saving page count: {}", e);
+        }
+    }
+    if let Err(e) = db.set_stock_info(&[(meta.path.clone(), stock)]).await {
+        eprintln!("Error saving stock info: {}", e);
+    }
+    Ok(saved)
+}
+
+/// Whether a file could belong to a frame sequence.
+fn is_frame(filename: &str) -> bool {
+    crate::indexer::sequences::parse_frame_name(filename).is_some()
+}
+
 fn normalize_path(path: &str) -> String {
     let p = path.trim_end_matches('/');
     if p.is_empty() { return "/".to_string(); }
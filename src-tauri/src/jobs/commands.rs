@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+use tauri::State;
+
+use super::{handlers, JobQueue};
+use crate::db::models::Job;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+
+/// Jobs returned by `list_jobs` when no limit is given.
+const DEFAULT_LIST_LIMIT: i64 = 200;
+
+/// Queues a background job and returns it.
+///
+/// `kind` is `transcode` (`{ path, quality? }`), `export_metadata`
+/// (`{ imageIds, outputPath }`), `render_slideshow`
+/// (`{ playlistId, preset, outputPath, transition? }`),
+/// `import_apple_photos` (`{ libraryPath, mode: "reference" | "copy",
+/// destination? }`), `sync_folder` (`{ targetId }`), `generate_proxy`
+/// (`{ path }`) or `ingest_card` (`{ source, destination, nameTemplate?,
+/// folderTemplate?, session? }`). Higher priorities run first. Progress is reported through
+/// the `job:progress` event.
+#[tauri::command]
+pub async fn enqueue_job(
+    db: State<'_, Arc<Db>>,
+    queue: State<'_, Arc<JobQueue>>,
+    kind: String,
+    payload: Value,
+    priority: Option<i64>,
+    label: Option<String>,
+) -> AppResult<Job> {
+    let label = label.unwrap_or_else(|| handlers::default_label(&kind, &payload));
+    let id = queue.enqueue(&kind, &label, &payload, priority.unwrap_or(0)).await?;
+    db.get_job(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Job {} not found", id)))
+}
+
+/// Lists jobs, newest first, optionally only those with `status`.
+#[tauri::command]
+pub async fn list_jobs(
+    db: State<'_, Arc<Db>>,
+    status: Option<String>,
+    limit: Option<i64>,
+) -> AppResult<Vec<Job>> {
+    Ok(db.list_jobs(status.as_deref(), limit.unwrap_or(DEFAULT_LIST_LIMIT)).await?)
+}
+
+#[tauri::command]
+pub async fn cancel_job(queue: State<'_, Arc<JobQueue>>, id: i64) -> AppResult<()> {
+    queue.cancel(id).await
+}
+
+/// Queues a failed or cancelled job again and returns it.
+#[tauri::command]
+pub async fn retry_job(db: State<'_, Arc<Db>>, queue: State<'_, Arc<JobQueue>>, id: i64) -> AppResult<Job> {
+    queue.retry(id).await?;
+    db.get_job(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Job {} not found", id)))
+}
@@ -0,0 +1,194 @@
+//! Handlers of the queued job kinds.
+//!
+//! Each handler reads its arguments from the job's JSON payload. FFmpeg
+//! processes are started under the job's id (`process_manager::for_job`),
+//! which is how `cancel_job` finds the processes to kill, and handlers check
+//! for cancellation before each expensive step.
+
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager};
+
+use super::JobContext;
+use crate::db::Db;
+use crate::error::AppError;
+use crate::transcoding::cache::TranscodeCache;
+use crate::transcoding::ffmpeg_pipe::FfmpegTranscoder;
+use crate::transcoding::quality::TranscodeQuality;
+
+/// Job kinds with a handler. Other kinds are only tracked.
+pub const RUNNABLE_KINDS: &[&str] = &[
+    "transcode",
+    "export_metadata",
+    "render_slideshow",
+    "import_apple_photos",
+    "sync_folder",
+    "generate_proxy",
+    "ingest_card",
+];
+
+/// Job kinds that edit the library, refused while it is read-only.
+pub const MUTATING_KINDS: &[&str] = &["import_apple_photos", "sync_folder", "generate_proxy", "ingest_card"];
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TranscodePayload {
+    path: String,
+    quality: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportMetadataPayload {
+    image_ids: Vec<i64>,
+    output_path: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RenderSlideshowPayload {
+    playlist_id: i64,
+    preset: String,
+    output_path: String,
+    transition: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateProxyPayload {
+    path: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SyncFolderPayload {
+    target_id: i64,
+}
+
+/// Stops a handler whose job was cancelled before its next step.
+fn check_cancelled(ctx: &JobContext) -> Result<(), String> {
+    if ctx.is_cancelled() {
+        return Err("Cancelled".to_string());
+    }
+    Ok(())
+}
+
+fn parse<T: serde::de::DeserializeOwned>(kind: &str, payload: &Value) -> Result<T, String> {
+    serde_json::from_value(payload.clone()).map_err(|e| format!("Invalid {} payload: {}", kind, e))
+}
+
+/// Label of a job when the caller doesn't give one: the file it reads or writes.
+pub fn default_label(kind: &str, payload: &Value) -> String {
+    let key = match kind {
+        "transcode" | "generate_proxy" => "path",
+        "import_apple_photos" => "libraryPath",
+        "ingest_card" => "source",
+        _ => "outputPath",
+    };
+    payload
+        .get(key)
+        .and_then(Value::as_str)
+        .map(String::from)
+        .unwrap_or_else(|| kind.to_string())
+}
+
+/// Runs a queued job. Returns the JSON result stored with the job.
+pub async fn execute(
+    app: &AppHandle,
+    db: &Db,
+    ctx: &JobContext,
+    kind: &str,
+    payload: Value,
+) -> Result<Option<Value>, String> {
+    match kind {
+        "transcode" => {
+            let args: TranscodePayload = parse(kind, &payload)?;
+            let quality = args.quality.and_then(|q| TranscodeQuality::from_str(&q)).unwrap_or_default();
+            let app_data = app.path().app_local_data_dir().map_err(|e| e.to_string())?;
+            let cache = TranscodeCache::new(&app_data);
+            let source = crate::paths::from_db(&args.path);
+            crate::peer::client::fetch_transcode(db, &cache, &source, quality).await;
+            let transcoder = FfmpegTranscoder::new_with_app(cache, app);
+            if !transcoder.is_available() {
+                return Err(AppError::FfmpegMissing.to_string());
+            }
+
+            check_cancelled(ctx)?;
+            ctx.progress(0, Some(1), Some("Transcoding")).await;
+            let job_id = Some(ctx.id);
+            let output = tauri::async_runtime::spawn_blocking(move || {
+                crate::streaming::process_manager::for_job(job_id, || transcoder.transcode_sync(&source, quality))
+            })
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+            ctx.progress(1, Some(1), None).await;
+            Ok(Some(json!({ "path": output.to_string_lossy() })))
+        }
+        "export_metadata" => {
+            let args: ExportMetadataPayload = parse(kind, &payload)?;
+            check_cancelled(ctx)?;
+            let count = crate::library::commands::export::write_metadata_bundle(db, &args.image_ids, &args.output_path)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(Some(json!({ "path": args.output_path, "count": count })))
+        }
+        "render_slideshow" => {
+            let args: RenderSlideshowPayload = parse(kind, &payload)?;
+            check_cancelled(ctx)?;
+            ctx.progress(0, None, Some("Rendering")).await;
+            crate::library::slideshow::render_playlist(
+                app,
+                db,
+                args.playlist_id,
+                &args.preset,
+                Path::new(&args.output_path),
+                args.transition,
+                Some(ctx.id),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(Some(json!({ "path": args.output_path })))
+        }
+        "import_apple_photos" => {
+            let options: crate::library::apple_photos::ImportOptions = parse(kind, &payload)?;
+            let report = crate::library::apple_photos::import(app, db, &options, ctx).await?;
+            serde_json::to_value(report).map(Some).map_err(|e| e.to_string())
+        }
+        "ingest_card" => {
+            let options: crate::library::ingest::IngestOptions = parse(kind, &payload)?;
+            let report = crate::library::ingest::ingest(app, db, &options, ctx).await?;
+            serde_json::to_value(report).map(Some).map_err(|e| e.to_string())
+        }
+        "sync_folder" => {
+            let args: SyncFolderPayload = parse(kind, &payload)?;
+            let result = crate::library::sync::run(db, args.target_id, Some(ctx)).await.map_err(|e| e.to_string())?;
+            serde_json::to_value(result).map(Some).map_err(|e| e.to_string())
+        }
+        "generate_proxy" => {
+            let args: GenerateProxyPayload = parse(kind, &payload)?;
+            check_cancelled(ctx)?;
+            ctx.progress(0, Some(1), Some("Generating proxy")).await;
+            let output = crate::library::proxies::generate(app, db, &crate::paths::from_db(&args.path), Some(ctx.id))
+                .await
+                .map_err(|e| e.to_string())?;
+            ctx.progress(1, Some(1), None).await;
+            Ok(Some(json!({ "path": output.to_string_lossy() })))
+        }
+        other => Err(format!("No handler for job kind '{}'", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_label() {
+        assert_eq!(default_label("transcode", &json!({ "path": "/v/a.mkv" })), "/v/a.mkv");
+        assert_eq!(default_label("render_slideshow", &json!({ "outputPath": "/o/s.mp4", "playlistId": 1 })), "/o/s.mp4");
+        assert_eq!(default_label("export_metadata", &json!({})), "export_metadata");
+    }
+}
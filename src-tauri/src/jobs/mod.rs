@@ -0,0 +1,308 @@
+//! Background job queue shared by the whole backend.
+//!
+//! Jobs are persisted in the `jobs` table, run by a small pool of runners in
+//! priority order, and report through a single `job:progress` event. Two
+//! kinds of jobs exist:
+//!
+//! * queued jobs (transcodes, exports, slideshow renders, imports) run by a handler
+//!   from [`handlers`]; they can be cancelled and retried, and are queued
+//!   again if the app quits while they run;
+//! * tracked jobs ([`JobQueue::track`]) for work started elsewhere, such as
+//!   scans and the thumbnail backlog, which only report progress.
+
+pub mod commands;
+pub mod handlers;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Notify;
+
+use crate::db::jobs::{JOB_CANCELLED, JOB_COMPLETED, JOB_FAILED, JOB_QUEUED, JOB_RUNNING};
+use crate::db::models::Job;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::streaming::process_manager;
+
+/// Number of queued jobs run at the same time.
+const RUNNERS: usize = 2;
+
+/// Fallback poll interval, in case a wake-up is missed.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Days finished jobs stay listed before `library::upkeep` deletes them.
+pub const FINISHED_JOB_RETENTION_DAYS: i64 = 30;
+
+/// Minimum time between two persisted progress updates of a job.
+const PROGRESS_PERSIST_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Payload of the `job:progress` event, emitted on every state change and
+/// progress update of any job.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobProgressPayload {
+    pub id: i64,
+    pub kind: String,
+    pub label: String,
+    /// `queued`, `running`, `completed`, `failed` or `cancelled`.
+    pub status: String,
+    pub done: i64,
+    /// `None` while the amount of work is unknown.
+    pub total: Option<i64>,
+    pub message: Option<String>,
+    pub error: Option<String>,
+    /// Whether `cancel_job` can stop the job.
+    pub cancellable: bool,
+}
+
+pub struct JobQueue {
+    db: Arc<Db>,
+    app: AppHandle,
+    wake: Notify,
+    /// Cancellation flags of the jobs run by this queue.
+    running: Mutex<HashMap<i64, Arc<AtomicBool>>>,
+}
+
+/// Handle given to the code doing the work of a job.
+pub struct JobContext {
+    pub id: i64,
+    pub kind: String,
+    pub label: String,
+    queue: Arc<JobQueue>,
+    cancelled: Arc<AtomicBool>,
+    cancellable: bool,
+    last_persist: Mutex<Option<Instant>>,
+}
+
+impl JobQueue {
+    pub fn new(db: Arc<Db>, app: AppHandle) -> Arc<Self> {
+        Arc::new(Self { db, app, wake: Notify::new(), running: Mutex::new(HashMap::new()) })
+    }
+
+    /// Recovers jobs interrupted by the last shutdown and starts the runners.
+    pub async fn start(self: &Arc<Self>) {
+        match self.db.recover_interrupted_jobs(handlers::RUNNABLE_KINDS).await {
+            Ok(0) => {}
+            Ok(count) => println!("INFO: Recovered {} jobs interrupted by the last shutdown", count),
+            Err(e) => eprintln!("WARN: Failed to recover interrupted jobs: {}", e),
+        }
+
+        for _ in 0..RUNNERS {
+            let queue = self.clone();
+            tauri::async_runtime::spawn(async move { queue.run_loop().await });
+        }
+    }
+
+    async fn run_loop(self: Arc<Self>) {
+        loop {
+            match self.db.claim_next_job().await {
+                Ok(Some(job)) => self.run(job).await,
+                Ok(None) => {
+                    let _ = tokio::time::timeout(POLL_INTERVAL, self.wake.notified()).await;
+                }
+                Err(e) => {
+                    eprintln!("Job queue DB error: {}", e);
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    async fn run(self: &Arc<Self>, job: Job) {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        if let Ok(mut running) = self.running.lock() {
+            running.insert(job.id, cancelled.clone());
+        }
+
+        let ctx = JobContext {
+            id: job.id,
+            kind: job.kind.clone(),
+            label: job.label.clone(),
+            queue: self.clone(),
+            cancelled,
+            cancellable: true,
+            last_persist: Mutex::new(None),
+        };
+        ctx.emit(JOB_RUNNING, 0, None, None, None);
+
+        let payload: Value = serde_json::from_str(&job.payload).unwrap_or(Value::Null);
+        let result = handlers::execute(&self.app, &self.db, &ctx, &job.kind, payload).await;
+
+        if let Ok(mut running) = self.running.lock() {
+            running.remove(&job.id);
+        }
+        ctx.finish(result).await;
+    }
+
+    /// Adds a job to the queue and wakes a runner.
+    ///
+    /// # Errors
+    /// Returns `AppError::Unsupported` for kinds without a handler and
+    /// `AppError::ReadOnly` for kinds that edit a read-only library.
+    pub async fn enqueue(&self, kind: &str, label: &str, payload: &Value, priority: i64) -> AppResult<i64> {
+        if !handlers::RUNNABLE_KINDS.contains(&kind) {
+            return Err(AppError::Unsupported(format!("Unknown job kind '{}'", kind)));
+        }
+        if handlers::MUTATING_KINDS.contains(&kind) && crate::library::read_only::is_enabled() {
+            return Err(AppError::ReadOnly(format!("{} jobs", kind)));
+        }
+        let id = self.db.enqueue_job(kind, label, &payload.to_string(), priority).await?;
+        self.emit_job(id).await;
+        self.wake.notify_one();
+        Ok(id)
+    }
+
+    /// Records work started outside the queue so it shows up with the other
+    /// jobs. Returns `None` if the job could not be recorded.
+    pub async fn track(self: &Arc<Self>, kind: &str, label: &str) -> Option<JobContext> {
+        let id = match self.db.insert_running_job(kind, label).await {
+            Ok(id) => id,
+            Err(e) => {
+                eprintln!("WARN: Failed to record {} job: {}", kind, e);
+                return None;
+            }
+        };
+        let ctx = JobContext {
+            id,
+            kind: kind.to_string(),
+            label: label.to_string(),
+            queue: self.clone(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            cancellable: false,
+            last_persist: Mutex::new(None),
+        };
+        ctx.emit(JOB_RUNNING, 0, None, None, None);
+        Some(ctx)
+    }
+
+    /// Cancels a queued job, or asks a running one to stop.
+    ///
+    /// Running jobs stop at their next cancellation check; FFmpeg processes
+    /// started for them are killed right away.
+    ///
+    /// # Errors
+    /// Returns `AppError::NotFound` for unknown jobs and `AppError::Generic`
+    /// for finished jobs and tracked jobs, which can't be cancelled.
+    pub async fn cancel(&self, id: i64) -> AppResult<()> {
+        let job = self.db.get_job(id).await?.ok_or_else(|| AppError::NotFound(format!("Job {} not found", id)))?;
+
+        if job.status == JOB_QUEUED && self.db.cancel_queued_job(id).await? {
+            self.emit_job(id).await;
+            return Ok(());
+        }
+
+        let flag = self.running.lock().ok().and_then(|running| running.get(&id).cloned());
+        match flag {
+            Some(flag) => {
+                flag.store(true, Ordering::Relaxed);
+                let killed = process_manager::lock_global().cancel_job(id);
+                println!("INFO: Cancelling job {} ({} processes killed)", id, killed);
+                Ok(())
+            }
+            None => Err(AppError::Generic(format!("Job {} is {} and can't be cancelled", id, job.status))),
+        }
+    }
+
+    /// Queues a failed or cancelled job again.
+    pub async fn retry(&self, id: i64) -> AppResult<()> {
+        let job = self.db.get_job(id).await?.ok_or_else(|| AppError::NotFound(format!("Job {} not found", id)))?;
+        if !handlers::RUNNABLE_KINDS.contains(&job.kind.as_str()) {
+            return Err(AppError::Unsupported(format!("{} jobs can't be retried", job.kind)));
+        }
+        if handlers::MUTATING_KINDS.contains(&job.kind.as_str()) && crate::library::read_only::is_enabled() {
+            return Err(AppError::ReadOnly(format!("{} jobs", job.kind)));
+        }
+        if !self.db.requeue_job(id).await? {
+            if job.status == JOB_QUEUED || job.status == JOB_RUNNING {
+                return Err(AppError::Busy(format!("Job {} is already {}", id, job.status)));
+            }
+            return Err(AppError::Generic(format!("Job {} is {}, only failed or cancelled jobs can be retried", id, job.status)));
+        }
+        self.emit_job(id).await;
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// Emits the stored state of a job.
+    async fn emit_job(&self, id: i64) {
+        if let Ok(Some(job)) = self.db.get_job(id).await {
+            let cancellable = handlers::RUNNABLE_KINDS.contains(&job.kind.as_str());
+            let _ = self.app.emit("job:progress", JobProgressPayload {
+                id: job.id,
+                kind: job.kind,
+                label: job.label,
+                status: job.status,
+                done: job.progress_done,
+                total: job.progress_total,
+                message: job.message,
+                error: job.error,
+                cancellable,
+            });
+        }
+    }
+}
+
+impl JobContext {
+    /// Whether `cancel_job` was called for this job.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn emit(&self, status: &str, done: i64, total: Option<i64>, message: Option<&str>, error: Option<&str>) {
+        let _ = self.queue.app.emit("job:progress", JobProgressPayload {
+            id: self.id,
+            kind: self.kind.clone(),
+            label: self.label.clone(),
+            status: status.to_string(),
+            done,
+            total,
+            message: message.map(String::from),
+            error: error.map(String::from),
+            cancellable: self.cancellable,
+        });
+    }
+
+    /// Reports progress. Every call is emitted; writes to the database are
+    /// throttled.
+    pub async fn progress(&self, done: i64, total: Option<i64>, message: Option<&str>) {
+        self.emit(JOB_RUNNING, done, total, message, None);
+
+        let persist = match self.last_persist.lock() {
+            Ok(mut last) => {
+                let due = match *last {
+                    Some(at) => at.elapsed() >= PROGRESS_PERSIST_INTERVAL,
+                    None => true,
+                };
+                if due {
+                    *last = Some(Instant::now());
+                }
+                due
+            }
+            Err(_) => false,
+        };
+        if persist {
+            if let Err(e) = self.queue.db.update_job_progress(self.id, done, total, message).await {
+                eprintln!("WARN: Failed to store progress of job {}: {}", self.id, e);
+            }
+        }
+    }
+
+    /// Records the outcome of the job. A job cancelled while running is
+    /// recorded as cancelled whatever its handler returned.
+    pub async fn finish(self, result: Result<Option<Value>, String>) {
+        let (status, error, result) = match result {
+            _ if self.is_cancelled() => (JOB_CANCELLED, None, None),
+            Ok(value) => (JOB_COMPLETED, None, value.map(|v| v.to_string())),
+            Err(e) => (JOB_FAILED, Some(e), None),
+        };
+
+        if let Err(e) = self.queue.db.finish_job(self.id, status, error.as_deref(), result.as_deref()).await {
+            eprintln!("WARN: Failed to store outcome of job {}: {}", self.id, e);
+        }
+        self.queue.emit_job(self.id).await;
+    }
+}
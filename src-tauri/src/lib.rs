@@ -6,19 +6,32 @@ mod protocols;
 // Moved to thumbnails: thumbnail_worker, thumbnail_priority
 mod thumbnails;
 pub mod formats;
+mod paths;
 // Moved to settings: config
 mod transcoding;
 mod streaming;
 pub mod library;
 mod media;
 mod settings;
+mod jobs;
+mod storage;
+mod peer;
+mod startup;
+#[cfg(test)]
+mod testkit;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
 
 
-use crate::db::Db;
-use crate::indexer::Indexer;
 use tauri::Manager;
 
 
+/// Runs a sandboxed thumbnail render when the executable was started as a
+/// decoder helper. Returns the exit code, or `None` for a normal launch.
+pub fn run_thumbnail_helper() -> Option<i32> {
+    crate::thumbnails::isolated::run_helper_from_args()
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let builder = tauri::Builder::default();
@@ -31,51 +44,13 @@ pub fn run() {
                 .expect("Failed to get app data dir");
             std::fs::create_dir_all(&app_data).ok();
 
-            let db_path = app_data.join("mundam.db");
-            let thumbnails_dir = app_data.join("thumbnails");
-            std::fs::create_dir_all(&thumbnails_dir).ok();
-
-            // Initialize DB and Worker
-            let handle = app.handle().clone();
-            tauri::async_runtime::spawn(async move {
-                match Db::new(db_path).await {
-                    Ok(db) => {
-                        let db_arc = std::sync::Arc::new(db);
-                        let watcher_registry = std::sync::Arc::new(tokio::sync::Mutex::new(crate::indexer::WatcherRegistry::default()));
-
-                        // Load Config
-                        let app_config = crate::settings::config::load_config(&db_arc).await;
-                        let config_state = crate::settings::config::ConfigState(std::sync::Mutex::new(app_config.clone()));
+            let db_path = app_data.join(crate::startup::LIBRARY_FILE);
+            std::fs::create_dir_all(app_data.join("thumbnails")).ok();
+            crate::storage::init(&app_data);
 
-                        let priority_state = std::sync::Arc::new(crate::thumbnails::priority::ThumbnailPriorityState::default());
-
-                        handle.manage(db_arc.clone());
-                        handle.manage(watcher_registry.clone());
-                        handle.manage(config_state);
-                        handle.manage(priority_state.clone());
-
-                        let worker = crate::thumbnails::worker::ThumbnailWorker::new(
-                            db_arc.clone(),
-                            thumbnails_dir,
-                            handle.clone(),
-                            app_config,
-                            priority_state,
-                        );
-                        worker.start().await;
-
-                        // Start Watchers for Existing Roots
-                        if let Ok(roots) = db_arc.get_all_root_folders().await {
-                             println!("INFO: Starting watchers for {} roots", roots.len());
-                             for (_id, path) in roots {
-                                 let indexer = Indexer::new(handle.clone(), &db_arc, watcher_registry.clone());
-                                 let root_path = std::path::PathBuf::from(path);
-                                 indexer.start_scan(root_path).await;
-                             }
-                        }
-                    }
-                    Err(e) => eprintln!("Failed to initialize database: {}", e),
-                }
-            });
+            // Open the library and start the workers in the background, reporting each stage
+            app.manage(crate::startup::StartupState::new(&db_path));
+            tauri::async_runtime::spawn(crate::startup::initialize(app.handle().clone(), db_path, app_data));
 
             // Start HLS Streaming Server
             crate::streaming::server::spawn_server(app.handle().clone());
@@ -88,7 +63,7 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_mcp_bridge::init())
-        .invoke_handler(tauri::generate_handler![
+        .invoke_handler(library::read_only::guard(tauri::generate_handler![
             library::commands::indexing::start_indexing,
             library::commands::tags::create_tag,
             library::commands::tags::update_tag,
@@ -101,40 +76,192 @@ pub fn run() {
             library::commands::tags::add_tags_to_images_batch,
             library::commands::tags::get_images_filtered,
             library::commands::tags::get_image_count_filtered,
+            library::commands::tags::subscribe_filter,
+            library::commands::tags::set_filter_window,
+            library::commands::tags::unsubscribe_filter,
             library::commands::tags::update_image_rating,
             library::commands::tags::update_image_notes,
+            library::commands::tags::assign_tag_shortcut,
+            library::commands::tags::get_tag_shortcuts,
+            library::commands::tags::resolve_tag_shortcut,
+            library::commands::tags::get_selection_summary,
+            library::commands::review::start_review_session,
+            library::commands::review::get_review_sessions,
+            library::commands::review::get_review_cursor,
+            library::commands::review::review_advance,
+            library::commands::review::review_skip,
+            library::commands::review::review_apply,
+            library::commands::review::delete_review_session,
+            library::commands::annotations::add_image_annotation,
+            library::commands::annotations::update_image_annotation,
+            library::commands::annotations::delete_image_annotation,
+            library::commands::annotations::get_image_annotations,
+            library::commands::annotations::get_related_images,
+            library::commands::links::add_link,
+            library::commands::links::update_link,
+            library::commands::links::delete_link,
+            library::commands::links::get_image_links,
+            library::commands::links::get_folder_links,
+            library::commands::export::export_metadata_bundle,
+            library::commands::export::export_metadata,
+            library::commands::export::import_metadata_csv,
+            library::commands::sync::get_sync_targets,
+            library::commands::sync::create_sync_target,
+            library::commands::sync::update_sync_target,
+            library::commands::sync::delete_sync_target,
+            library::commands::sync::preview_sync_target,
+            library::commands::sync::run_sync_target,
+            library::commands::derivatives::get_derivatives,
+            library::commands::derivatives::link_derivative,
+            library::commands::derivatives::delete_derivative,
+            library::commands::derivatives::clean_up_derivatives,
+            library::commands::proxies::get_proxy_settings,
+            library::commands::proxies::set_proxy_settings,
+            library::commands::proxies::queue_proxies,
+            library::commands::working_sets::push_working_set,
+            library::commands::working_sets::pop_working_set,
+            library::commands::working_sets::get_working_sets,
+            library::commands::working_sets::clear_working_sets,
+            library::commands::duplicates::find_duplicates,
+            library::commands::duplicates::get_duplicate_group,
+            library::commands::duplicates::resolve_duplicates,
+            storage::commands::get_remote_locations,
+            storage::commands::add_remote_location,
+            storage::commands::refresh_remote_location,
+            storage::commands::remove_remote_location,
+            storage::commands::set_remote_cache_limit,
+            storage::commands::get_remote_cache_usage,
+            storage::commands::clear_remote_cache,
+            storage::commands::fetch_remote_file,
+            peer::commands::get_peer_settings,
+            peer::commands::set_peer_settings,
+            peer::commands::test_peer_connection,
+            library::commands::rename::rename_images_bulk,
+            library::commands::folders::create_project_from_template,
+            library::commands::estimate::estimate_operation_size,
+            library::commands::playback::set_playback_position,
+            library::commands::playback::get_playback_position,
+            library::commands::playback::clear_playback_position,
+            library::commands::playback::get_video_chapters,
+            library::commands::sequences::detect_image_sequences,
+            library::commands::sequences::get_folder_sequences,
+            library::commands::sequences::get_image_sequence,
+            library::commands::sequences::get_sequence_frames,
+            library::commands::versions::detect_version_chains,
+            library::commands::versions::get_folder_version_chains,
+            library::commands::versions::get_chain_versions,
+            library::commands::pages::get_image_pages,
+            library::commands::pages::render_page,
+            library::commands::psd::get_psd_layers,
+            library::commands::psd::render_psd_layer,
             library::commands::metadata::get_image_exif,
+            library::commands::metadata::get_exif_batch,
+            library::commands::metadata::get_stock_info,
             thumbnails::commands::request_thumbnail_regenerate,
             thumbnails::commands::set_thumbnail_priority,
+            thumbnails::commands::prefetch_folder,
+            thumbnails::commands::cancel_prefetch,
+            thumbnails::commands::set_active_context,
+            thumbnails::commands::get_thumbnail_worker_status,
+            thumbnails::commands::get_model_dependencies,
+            thumbnails::commands::get_image_histogram,
+            thumbnails::commands::get_similar_images,
             library::commands::folders::add_location,
             library::commands::folders::remove_location,
             library::commands::folders::get_locations,
+            library::commands::folders::get_location_health,
             library::commands::folders::get_all_subfolders,
             library::commands::folders::get_subfolder_counts,
             library::commands::folders::get_location_root_counts,
+            library::commands::folders::get_folder_default_tags,
+            library::commands::folders::set_folder_default_tags,
+            library::commands::folders::merge_folders,
             library::commands::smart_folders::get_smart_folders,
             library::commands::smart_folders::save_smart_folder,
             library::commands::smart_folders::update_smart_folder,
             library::commands::smart_folders::delete_smart_folder,
+            library::commands::smart_folders::export_smart_folder,
+            library::commands::smart_folders::import_smart_folder,
+            library::commands::smart_folders::get_smart_folder_trends,
+            library::commands::auto_collections::get_auto_collections,
+            library::commands::auto_collections::create_auto_collection,
+            library::commands::auto_collections::update_auto_collection,
+            library::commands::auto_collections::delete_auto_collection,
+            library::commands::auto_collections::evaluate_auto_collection,
+            library::commands::auto_collections::get_auto_collection_snapshots,
+            library::commands::auto_collections::get_auto_collection_snapshot_images,
+            library::commands::auto_collections::delete_auto_collection_snapshot,
+            library::commands::maintenance::replace_path_prefix,
+            library::commands::maintenance::find_replace_notes,
+            library::commands::maintenance::repair_capture_times,
+            library::commands::compare::compare_images,
+            library::commands::playlists::create_playlist,
+            library::commands::playlists::get_playlists,
+            library::commands::playlists::get_playlist_items,
+            library::commands::playlists::update_playlist,
+            library::commands::playlists::set_playlist_items,
+            library::commands::playlists::delete_playlist,
+            library::commands::playlists::export_playlist_m3u,
+            library::commands::playlists::render_slideshow,
+            library::commands::collections::create_collection,
+            library::commands::collections::get_collections,
+            library::commands::collections::update_collection,
+            library::commands::collections::delete_collection,
+            library::commands::collections::add_to_collection,
+            library::commands::collections::remove_from_collection,
+            library::commands::collections::reorder_collection_items,
+            library::commands::collections::get_image_collections,
+            library::commands::trash::get_trash,
+            library::commands::trash::trash_images,
+            library::commands::trash::restore_from_trash,
+            library::commands::trash::empty_trash,
+            library::commands::operations::get_interrupted_operations,
+            library::commands::operations::get_operation_steps,
+            library::commands::operations::resume_operation,
+            library::commands::operations::rollback_operation,
+            library::commands::lock::get_library_lock_status,
+            startup::commands::get_app_status,
+            startup::commands::retry_app_init,
+            library::commands::imports::find_apple_photos_library,
+            library::commands::imports::get_import_sources,
+            library::commands::capture::start_capture_session,
+            library::commands::capture::stop_capture_session,
+            library::commands::capture::get_capture_session,
             settings::commands::get_setting,
             settings::commands::set_setting,
             settings::commands::run_db_maintenance,
+            settings::commands::get_db_status,
+            settings::commands::checkpoint_db,
 
             library::commands::formats::get_library_supported_formats,
+            library::commands::diagnostics::get_corrupt_files,
+            library::commands::diagnostics::get_mismatched_extensions,
+            library::commands::diagnostics::fix_extension,
+            library::commands::diagnostics::explain_filter,
             media::commands::get_audio_waveform_data,
+            media::commands::get_active_ffmpeg_jobs,
 
             // Transcoding commands
             transcoding::commands::needs_transcoding,
             transcoding::commands::is_native_format,
             transcoding::commands::get_stream_url,
+            transcoding::commands::register_webview_codecs,
+            transcoding::commands::get_stream_token,
             transcoding::commands::get_quality_options,
             transcoding::commands::transcode_file,
             transcoding::commands::is_cached,
             transcoding::commands::get_cache_stats,
             transcoding::commands::cleanup_cache,
             transcoding::commands::clear_cache,
-            transcoding::commands::ffmpeg_available
-        ])
+            transcoding::commands::ffmpeg_available,
+            transcoding::commands::get_transcode_stats,
+
+            // Job queue commands
+            jobs::commands::enqueue_job,
+            jobs::commands::list_jobs,
+            jobs::commands::cancel_job,
+            jobs::commands::retry_job
+        ]))
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,534 @@
+//! Import from an Apple Photos library (`*.photoslibrary`).
+//!
+//! The library database (`database/Photos.sqlite`) is copied to a temporary
+//! folder and read directly, the way osxphotos does, so Photos can stay
+//! open. Originals are either referenced in place (the library's `originals`
+//! folder becomes a location) or copied out under their original names.
+//! Albums and keywords become tags, favorites get five stars and
+//! descriptions fill empty notes; this metadata is queued until the indexer
+//! has created the images (see `db::imports`). Copies are recorded in the
+//! operations ledger first, so an interrupted import can be finished or
+//! undone (see `library::operations`).
+//!
+//! The Core Data schema changes between macOS releases, most visibly in the
+//! numbered many-to-many tables (`Z_28ASSETS`, `Z_1KEYWORDS`...), which are
+//! found by their column names instead of being hardcoded.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{Connection, SqliteConnection};
+use tauri::AppHandle;
+
+use crate::db::models::ImportedMetadata;
+use crate::db::operations::OPERATION_COMPLETED;
+use crate::db::Db;
+use crate::jobs::JobContext;
+
+/// Importer name recorded with queued metadata.
+pub const SOURCE: &str = "apple_photos";
+
+/// Ledger kind of the copies made in copy mode.
+pub const COPY_OPERATION: &str = "photos_copy";
+
+/// Parent tag of the tags created for albums.
+const ALBUMS_TAG: &str = "Photos Albums";
+/// Parent tag of the tags created for keywords.
+const KEYWORDS_TAG: &str = "Photos Keywords";
+
+/// Rating given to favorites.
+const FAVORITE_RATING: i32 = 5;
+
+/// Seconds between the Unix epoch and the Core Data epoch (2001-01-01).
+const CORE_DATA_EPOCH_OFFSET: i64 = 978_307_200;
+
+/// `ZGENERICALBUM.ZKIND` of albums created by the user.
+const USER_ALBUM_KIND: i64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Index the originals inside the library package.
+    Reference,
+    /// Copy the originals to a destination folder.
+    Copy,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportOptions {
+    /// The `*.photoslibrary` package.
+    pub library_path: String,
+    pub mode: ImportMode,
+    /// Folder receiving the copies, required in copy mode.
+    pub destination: Option<String>,
+}
+
+/// Summary returned as the job result.
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    /// Assets in the library, trashed ones excluded.
+    pub assets: usize,
+    /// Assets referenced or copied.
+    pub imported: usize,
+    /// Assets whose original isn't on this Mac (iCloud "Optimize Storage").
+    pub missing: usize,
+    /// Assets of `imported` already copied by an earlier import, in copy mode.
+    pub already_copied: usize,
+    pub albums: usize,
+    pub keywords: usize,
+    pub favorites: usize,
+    /// Folder added as a location.
+    pub location: String,
+}
+
+/// One asset read from the Photos database.
+#[derive(Debug, Clone)]
+struct PhotosAsset {
+    /// Path relative to the `originals` folder.
+    relative_path: PathBuf,
+    original_filename: Option<String>,
+    favorite: bool,
+    taken_at: Option<DateTime<Utc>>,
+    description: Option<String>,
+    albums: Vec<String>,
+    keywords: Vec<String>,
+}
+
+/// Default library location of the current user.
+pub fn default_library_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    let path = PathBuf::from(home).join("Pictures").join("Photos Library.photoslibrary");
+    path.exists().then_some(path)
+}
+
+/// Converts a Core Data timestamp (seconds since 2001-01-01 UTC).
+pub fn core_data_time(seconds: f64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(seconds.floor() as i64 + CORE_DATA_EPOCH_OFFSET, 0)
+}
+
+/// Finds the columns of a Core Data join table: the one ending in
+/// `left_suffix` and the one ending in `right_suffix`. `Z_FOK_*` ordering
+/// columns are ignored.
+pub fn join_columns(columns: &[String], left_suffix: &str, right_suffix: &str) -> Option<(String, String)> {
+    let find = |suffix: &str| {
+        columns
+            .iter()
+            .find(|c| c.starts_with("Z_") && !c.starts_with("Z_FOK_") && c.ends_with(suffix))
+            .cloned()
+    };
+    Some((find(left_suffix)?, find(right_suffix)?))
+}
+
+/// Name for a copied file that doesn't clash with names already taken,
+/// compared case-insensitively: `IMG_1.HEIC`, then `IMG_1 (2).HEIC`...
+pub fn unique_name(name: &str, taken: &mut HashSet<String>) -> String {
+    let path = Path::new(name);
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+
+    let mut candidate = name.to_string();
+    let mut counter = 2;
+    while !taken.insert(candidate.to_lowercase()) {
+        candidate = format!("{} ({}){}", stem, counter, extension);
+        counter += 1;
+    }
+    candidate
+}
+
+/// Where an asset ends up.
+struct PlannedAsset {
+    /// Index in the assets read from the database.
+    asset: usize,
+    /// Path of the file in the library.
+    path: PathBuf,
+    /// Original to copy to `path`, unless the file is referenced or already there.
+    copy_from: Option<PathBuf>,
+}
+
+/// Decides where each asset goes. Returns the plan and the number of assets
+/// whose original is missing.
+///
+/// In copy mode, a file already in `location` under the asset's name and with
+/// its size is taken as an earlier import's copy and reused, so importing the
+/// library again doesn't add `IMG_1 (2).HEIC` next to `IMG_1.HEIC`.
+fn plan(assets: &[PhotosAsset], originals: &Path, location: &Path, mode: ImportMode) -> (Vec<PlannedAsset>, usize) {
+    let mut taken_names: HashSet<String> = std::fs::read_dir(location)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.file_name().to_string_lossy().to_lowercase()).collect())
+        .unwrap_or_default();
+    let mut planned = Vec::with_capacity(assets.len());
+    let mut missing = 0;
+
+    for (index, asset) in assets.iter().enumerate() {
+        let source = originals.join(&asset.relative_path);
+        let Ok(source_meta) = std::fs::metadata(&source) else {
+            missing += 1;
+            continue;
+        };
+
+        let (path, copy_from) = match mode {
+            ImportMode::Reference => (source, None),
+            ImportMode::Copy => {
+                let name = asset
+                    .original_filename
+                    .clone()
+                    .unwrap_or_else(|| asset.relative_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default());
+                let existing = location.join(&name);
+                let copied_before = taken_names.contains(&name.to_lowercase())
+                    && std::fs::metadata(&existing).is_ok_and(|m| m.len() == source_meta.len());
+                if copied_before {
+                    (existing, None)
+                } else {
+                    (location.join(unique_name(&name, &mut taken_names)), Some(source))
+                }
+            }
+        };
+        planned.push(PlannedAsset { asset: index, path, copy_from });
+    }
+    (planned, missing)
+}
+
+/// The folder holding originals: `originals` since Photos 5, `Masters` before.
+fn originals_dir(library: &Path) -> Option<PathBuf> {
+    ["originals", "Masters"].iter().map(|name| library.join(name)).find(|p| p.is_dir())
+}
+
+/// Copies the Photos database (and its WAL) so it can be read while Photos runs.
+fn copy_database(library: &Path, temp_dir: &Path) -> Result<PathBuf, String> {
+    let source = library.join("database").join("Photos.sqlite");
+    if !source.exists() {
+        return Err(format!("{} is not a Photos library (no database/Photos.sqlite)", library.display()));
+    }
+    std::fs::create_dir_all(temp_dir).map_err(|e| e.to_string())?;
+
+    let target = temp_dir.join("Photos.sqlite");
+    std::fs::copy(&source, &target).map_err(|e| format!("Failed to copy the Photos database: {}", e))?;
+    for suffix in ["-wal", "-shm"] {
+        let sidecar = library.join("database").join(format!("Photos.sqlite{}", suffix));
+        if sidecar.exists() {
+            let _ = std::fs::copy(&sidecar, temp_dir.join(format!("Photos.sqlite{}", suffix)));
+        }
+    }
+    Ok(target)
+}
+
+async fn table_exists(conn: &mut SqliteConnection, name: &str) -> Result<bool, sqlx::Error> {
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?")
+        .bind(name)
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(count > 0)
+}
+
+/// Finds a numbered join table (`Z_<n><suffix>`) and its two columns.
+async fn find_join_table(
+    conn: &mut SqliteConnection,
+    table_suffix: &str,
+    left_suffix: &str,
+    right_suffix: &str,
+) -> Result<Option<(String, String, String)>, sqlx::Error> {
+    let tables: Vec<String> = sqlx::query_scalar(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name LIKE 'Z\\_%' ESCAPE '\\' AND name LIKE ?"
+    )
+    .bind(format!("%{}", table_suffix))
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for table in tables {
+        let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info(?)")
+            .bind(&table)
+            .fetch_all(&mut *conn)
+            .await?;
+        if let Some((left, right)) = join_columns(&columns, left_suffix, right_suffix) {
+            return Ok(Some((table, left, right)));
+        }
+    }
+    Ok(None)
+}
+
+/// Reads assets with their albums and keywords.
+async fn read_assets(database: &Path) -> Result<Vec<PhotosAsset>, sqlx::Error> {
+    // A private copy, so SQLite may replay the copied WAL into it
+    let options = SqliteConnectOptions::new().filename(database);
+    let mut conn = SqliteConnection::connect_with(&options).await?;
+
+    // Photos 5 (macOS 10.15) called the asset table ZGENERICASSET
+    let asset_table = if table_exists(&mut conn, "ZASSET").await? { "ZASSET" } else { "ZGENERICASSET" };
+
+    let rows: Vec<(i64, Option<String>, Option<String>, Option<i64>, Option<f64>, Option<String>, Option<String>)> =
+        sqlx::query_as(&format!(
+            "SELECT a.Z_PK, a.ZDIRECTORY, a.ZFILENAME, a.ZFAVORITE, a.ZDATECREATED, aa.ZORIGINALFILENAME, d.ZLONGDESCRIPTION
+             FROM {} a
+             LEFT JOIN ZADDITIONALASSETATTRIBUTES aa ON aa.ZASSET = a.Z_PK
+             LEFT JOIN ZASSETDESCRIPTION d ON d.ZASSETATTRIBUTES = aa.Z_PK
+             WHERE COALESCE(a.ZTRASHEDSTATE, 0) = 0",
+            asset_table
+        ))
+        .fetch_all(&mut conn)
+        .await?;
+
+    let mut albums: HashMap<i64, Vec<String>> = HashMap::new();
+    if let Some((table, album_column, asset_column)) = find_join_table(&mut conn, "ASSETS", "ALBUMS", "ASSETS").await? {
+        let pairs: Vec<(i64, String)> = sqlx::query_as(&format!(
+            "SELECT j.{asset_column}, g.ZTITLE FROM {table} j
+             JOIN ZGENERICALBUM g ON g.Z_PK = j.{album_column}
+             WHERE g.ZKIND = ? AND COALESCE(g.ZTRASHEDSTATE, 0) = 0 AND g.ZTITLE IS NOT NULL"
+        ))
+        .bind(USER_ALBUM_KIND)
+        .fetch_all(&mut conn)
+        .await?;
+        for (asset_id, title) in pairs {
+            albums.entry(asset_id).or_default().push(title);
+        }
+    }
+
+    let mut keywords: HashMap<i64, Vec<String>> = HashMap::new();
+    if let Some((table, attributes_column, keyword_column)) =
+        find_join_table(&mut conn, "KEYWORDS", "ASSETATTRIBUTES", "KEYWORDS").await?
+    {
+        let pairs: Vec<(i64, String)> = sqlx::query_as(&format!(
+            "SELECT aa.ZASSET, k.ZTITLE FROM {table} j
+             JOIN ZADDITIONALASSETATTRIBUTES aa ON aa.Z_PK = j.{attributes_column}
+             JOIN ZKEYWORD k ON k.Z_PK = j.{keyword_column}
+             WHERE k.ZTITLE IS NOT NULL"
+        ))
+        .fetch_all(&mut conn)
+        .await?;
+        for (asset_id, title) in pairs {
+            keywords.entry(asset_id).or_default().push(title);
+        }
+    }
+
+    conn.close().await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|(id, directory, filename, favorite, created, original_filename, description)| {
+            let filename = filename?;
+            let relative_path = match directory {
+                Some(directory) => PathBuf::from(directory).join(filename),
+                None => PathBuf::from(filename),
+            };
+            Some(PhotosAsset {
+                relative_path,
+                original_filename,
+                favorite: favorite.unwrap_or(0) != 0,
+                taken_at: created.and_then(core_data_time),
+                description: description.filter(|d| !d.trim().is_empty()),
+                albums: albums.remove(&id).unwrap_or_default(),
+                keywords: keywords.remove(&id).unwrap_or_default(),
+            })
+        })
+        .collect())
+}
+
+fn metadata_for(asset: &PhotosAsset, path: &Path) -> ImportedMetadata {
+    let tags = asset
+        .albums
+        .iter()
+        .map(|a| (ALBUMS_TAG.to_string(), a.clone()))
+        .chain(asset.keywords.iter().map(|k| (KEYWORDS_TAG.to_string(), k.clone())))
+        .collect();
+    ImportedMetadata {
+        path: crate::paths::to_db(path),
+        rating: asset.favorite.then_some(FAVORITE_RATING),
+        notes: asset.description.clone(),
+        taken_at: asset.taken_at,
+        tags,
+        ..Default::default()
+    }
+}
+
+/// Runs an import as a job: reads the library, references or copies the
+/// originals, queues their metadata and adds the folder as a location.
+pub async fn import(app: &AppHandle, db: &Db, options: &ImportOptions, ctx: &JobContext) -> Result<ImportReport, String> {
+    let library = PathBuf::from(&options.library_path);
+    let originals = originals_dir(&library)
+        .ok_or_else(|| format!("{} has no originals folder", library.display()))?;
+
+    ctx.progress(0, None, Some("Reading the Photos database")).await;
+    let temp_dir = std::env::temp_dir().join(format!("mundam-photos-{}", ctx.id));
+    let database = {
+        let library = library.clone();
+        let temp_dir = temp_dir.clone();
+        tauri::async_runtime::spawn_blocking(move || copy_database(&library, &temp_dir))
+            .await
+            .map_err(|e| e.to_string())??
+    };
+    let assets = read_assets(&database).await.map_err(|e| format!("Failed to read the Photos database: {}", e));
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    let assets = assets?;
+
+    let originals = originals.canonicalize().map_err(|e| e.to_string())?;
+    let location = match options.mode {
+        ImportMode::Reference => originals.clone(),
+        ImportMode::Copy => {
+            let destination = options
+                .destination
+                .as_deref()
+                .ok_or_else(|| "Copy mode needs a destination folder".to_string())?;
+            std::fs::create_dir_all(destination).map_err(|e| e.to_string())?;
+            PathBuf::from(destination).canonicalize().map_err(|e| e.to_string())?
+        }
+    };
+
+    let mut report = ImportReport { assets: assets.len(), location: location.to_string_lossy().to_string(), ..Default::default() };
+    let (planned, missing) = {
+        let assets = assets.clone();
+        let (originals, location, mode) = (originals.clone(), location.clone(), options.mode);
+        tauri::async_runtime::spawn_blocking(move || plan(&assets, &originals, &location, mode))
+            .await
+            .map_err(|e| e.to_string())?
+    };
+    report.missing = missing;
+
+    let copies: Vec<(i64, String, String)> = planned
+        .iter()
+        .filter_map(|p| Some((0, crate::paths::to_db(p.copy_from.as_ref()?), crate::paths::to_db(&p.path))))
+        .collect();
+    let operation_id = if copies.is_empty() {
+        None
+    } else {
+        Some(db.begin_operation(COPY_OPERATION, &copies).await.map_err(|e| e.to_string())?)
+    };
+
+    let mut album_names = HashSet::new();
+    let mut keyword_names = HashSet::new();
+    let mut entries = Vec::with_capacity(planned.len());
+    let mut copied: Vec<PathBuf> = Vec::with_capacity(copies.len());
+    let total = planned.len() as i64;
+
+    for (index, planned) in planned.into_iter().enumerate() {
+        if ctx.is_cancelled() {
+            if let Some(operation_id) = operation_id {
+                crate::library::operations::discard_copies(db, operation_id, &copied).await;
+            }
+            return Err("Import cancelled".to_string());
+        }
+        let asset = &assets[planned.asset];
+        let path = planned.path;
+
+        if let Some(source) = planned.copy_from {
+            let taken_at = asset.taken_at;
+            let copy_target = path.clone();
+            let result = tauri::async_runtime::spawn_blocking(move || -> std::io::Result<()> {
+                crate::library::ingest::copy_file(&source, &copy_target)?;
+                // Keep the capture time on the copy, for tools other than Mundam
+                if let Some(taken_at) = taken_at {
+                    std::fs::File::options()
+                        .write(true)
+                        .open(&copy_target)?
+                        .set_modified(taken_at.into())?;
+                }
+                Ok(())
+            })
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|r| r.map_err(|e| format!("Failed to copy {}: {}", path.display(), e)));
+            if let Err(e) = result {
+                if let Some(operation_id) = operation_id {
+                    crate::library::operations::discard_copies(db, operation_id, &copied).await;
+                }
+                return Err(e);
+            }
+            copied.push(path.clone());
+        } else if options.mode == ImportMode::Copy {
+            report.already_copied += 1;
+        }
+
+        report.imported += 1;
+        report.favorites += asset.favorite as usize;
+        album_names.extend(asset.albums.iter().cloned());
+        keyword_names.extend(asset.keywords.iter().cloned());
+        entries.push(metadata_for(asset, &path));
+
+        if index % 50 == 0 {
+            ctx.progress(index as i64, Some(total), Some(&asset.relative_path.to_string_lossy())).await;
+        }
+    }
+    report.albums = album_names.len();
+    report.keywords = keyword_names.len();
+    if let Some(operation_id) = operation_id {
+        if let Err(e) = db.finish_operation(operation_id, OPERATION_COMPLETED).await {
+            eprintln!("WARN: Failed to close operation {}: {}", operation_id, e);
+        }
+    }
+
+    db.queue_import_metadata(SOURCE, &entries).await.map_err(|e| e.to_string())?;
+    // Files indexed before the import get their metadata right away, the
+    // others when the scan below reaches them
+    db.apply_pending_import_metadata().await.map_err(|e| e.to_string())?;
+    crate::library::commands::folders::register_location(app, db, report.location.clone())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    ctx.progress(total, Some(total), None).await;
+    println!(
+        "INFO: Imported {} of {} Photos assets ({} not downloaded) into {}",
+        report.imported, report.assets, report.missing, report.location
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_core_data_time() {
+        assert_eq!(core_data_time(0.0).unwrap().to_rfc3339(), "2001-01-01T00:00:00+00:00");
+        assert_eq!(core_data_time(86_400.5).unwrap().to_rfc3339(), "2001-01-02T00:00:00+00:00");
+    }
+
+    #[test]
+    fn test_join_columns() {
+        let columns: Vec<String> = ["Z_28ALBUMS", "Z_3ASSETS", "Z_FOK_3ASSETS"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(join_columns(&columns, "ALBUMS", "ASSETS"), Some(("Z_28ALBUMS".to_string(), "Z_3ASSETS".to_string())));
+        assert_eq!(join_columns(&columns, "ASSETATTRIBUTES", "KEYWORDS"), None);
+    }
+
+    #[test]
+    fn test_unique_name() {
+        let mut taken = HashSet::from(["img_1.heic".to_string()]);
+        assert_eq!(unique_name("IMG_1.HEIC", &mut taken), "IMG_1 (2).HEIC");
+        assert_eq!(unique_name("IMG_1.HEIC", &mut taken), "IMG_1 (3).HEIC");
+        assert_eq!(unique_name("IMG_2.JPG", &mut taken), "IMG_2.JPG");
+    }
+
+    #[test]
+    fn test_plan_reuses_earlier_copies() {
+        let dir = std::env::temp_dir().join(format!("mundam-photos-plan-{}", std::process::id()));
+        let (originals, location) = (dir.join("originals"), dir.join("copies"));
+        std::fs::create_dir_all(originals.join("A")).unwrap();
+        std::fs::create_dir_all(&location).unwrap();
+        std::fs::write(originals.join("A/1.heic"), b"first").unwrap();
+        std::fs::write(originals.join("A/2.heic"), b"second").unwrap();
+        // Copied by an earlier import, and an unrelated file with a taken name
+        std::fs::write(location.join("IMG_1.HEIC"), b"first").unwrap();
+        std::fs::write(location.join("IMG_2.HEIC"), b"other file").unwrap();
+
+        let asset = |path: &str, name: &str| PhotosAsset {
+            relative_path: PathBuf::from(path),
+            original_filename: Some(name.to_string()),
+            favorite: false,
+            taken_at: None,
+            description: None,
+            albums: Vec::new(),
+            keywords: Vec::new(),
+        };
+        let assets = [asset("A/1.heic", "IMG_1.HEIC"), asset("A/2.heic", "IMG_2.HEIC"), asset("A/3.heic", "IMG_3.HEIC")];
+
+        let (planned, missing) = plan(&assets, &originals, &location, ImportMode::Copy);
+        assert_eq!(missing, 1);
+        assert_eq!(planned.len(), 2);
+        assert_eq!(planned[0].path, location.join("IMG_1.HEIC"));
+        assert!(planned[0].copy_from.is_none());
+        assert_eq!(planned[1].path, location.join("IMG_2 (2).HEIC"));
+        assert_eq!(planned[1].copy_from, Some(originals.join("A/2.heic")));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
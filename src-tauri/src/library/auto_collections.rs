@@ -0,0 +1,53 @@
+//! Scheduler that takes the due snapshots of auto-collections.
+
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+
+use crate::db::auto_collections::schedule_modifier;
+use crate::db::search::ImageFilter;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+
+/// How often due collections are checked. Schedules are daily at the finest,
+/// so this only bounds how late a snapshot can be.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Evaluates a collection now and returns the new snapshot id.
+///
+/// Scheduled collections are next due one period from now.
+pub async fn evaluate(db: &Db, collection_id: i64) -> AppResult<i64> {
+    let collection = db
+        .get_auto_collection(collection_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Auto-collection {} not found", collection_id)))?;
+
+    let filter: ImageFilter = serde_json::from_str(&collection.filter)
+        .map_err(|e| AppError::Generic(format!("Invalid filter JSON: {}", e)))?;
+    let modifier = schedule_modifier(&collection.schedule).map_err(AppError::Generic)?;
+
+    Ok(db.take_auto_collection_snapshot(collection_id, &filter, modifier).await?)
+}
+
+/// Starts the auto-collection scheduler on the async runtime.
+pub fn start(db: Arc<Db>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            // Snapshots are library edits, so they wait while the library is read-only
+            if !crate::library::read_only::is_enabled() {
+                match db.get_due_auto_collections().await {
+                    Ok(due) => {
+                        for collection_id in due {
+                            match evaluate(&db, collection_id).await {
+                                Ok(_) => println!("INFO: Took scheduled snapshot of auto-collection {}", collection_id),
+                                Err(e) => eprintln!("WARN: Auto-collection {} snapshot failed: {}", collection_id, e),
+                            }
+                        }
+                    }
+                    Err(e) => eprintln!("Auto-collection scheduler DB error: {}", e),
+                }
+            }
+
+            sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
@@ -0,0 +1,323 @@
+//! Capture sessions: a folder that tethering software, or a camera's Wi-Fi
+//! hot folder, writes new shots into while the photographer works.
+//!
+//! The library watchers gather events for 600 ms and leave thumbnails to the
+//! worker's queue, which suits copies and edits but not someone checking
+//! each frame as it comes in. During a session the folder gets a watcher of
+//! its own, which takes a file as soon as its size stops changing: it is
+//! indexed and tagged with the session right away, its thumbnail goes to the
+//! head of the worker's queue, and the viewer-sized preview is rendered
+//! aside, `capture:shot` being emitted once it is so the viewer can advance
+//! to it. Meanwhile the library watchers leave the folder's files to the
+//! session; files still being written when it stops are taken as they are.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+use chrono::{DateTime, Local, Utc};
+use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Duration;
+
+use crate::db::models::ImportedMetadata;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::indexer::{AddedItemContext, BatchChangePayload};
+use crate::library::ingest::SESSIONS_TAG;
+use crate::thumbnails::priority::ThumbnailPriorityState;
+
+/// Importer name recorded with queued metadata.
+const SOURCE: &str = "capture";
+
+/// How often files being written are checked.
+const SETTLE_POLL: Duration = Duration::from_millis(50);
+
+/// How long a file must keep its size to count as written.
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Longest side of the preview rendered for each shot; the viewer gets it
+/// from the cache by asking for `?maxdim=` this size.
+pub const PREVIEW_MAXDIM: u32 = 2048;
+
+/// A running or stopped capture session.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureSession {
+    pub folder: String,
+    /// Tag applied to each shot, under `Sessions`.
+    pub tag: String,
+    pub started_at: DateTime<Utc>,
+    /// New files taken so far.
+    pub shots: usize,
+}
+
+/// Payload of the `capture:shot` event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureShot {
+    pub image_id: i64,
+    pub folder_id: i64,
+    pub path: String,
+    /// Whether the preview is in the cache (or the file is small enough to
+    /// be shown as it is).
+    pub preview_ready: bool,
+    /// Shots of the session so far, this one included.
+    pub shots: usize,
+}
+
+struct Running {
+    session: CaptureSession,
+    /// Ends the session's task, which first takes the files still being
+    /// written.
+    stop: oneshot::Sender<()>,
+}
+
+static RUNNING: Mutex<Option<Running>> = Mutex::new(None);
+
+fn running() -> MutexGuard<'static, Option<Running>> {
+    RUNNING.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The running session, if any.
+pub fn current() -> Option<CaptureSession> {
+    running().as_ref().map(|running| running.session.clone())
+}
+
+/// Whether `path` is a file the running session takes care of, so the
+/// library watchers can leave it alone.
+pub fn handles(path: &Path) -> bool {
+    let is_session_shot = running()
+        .as_ref()
+        .is_some_and(|running| is_shot(Path::new(&running.session.folder), path));
+    // Removals and sidecars still go through the library watchers
+    is_session_shot && path.is_file()
+}
+
+/// Tag of a session started without one: the folder name and the day.
+fn default_tag(folder: &Path, day: DateTime<Local>) -> String {
+    let name = folder.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    format!("{} {}", name, day.format("%Y-%m-%d")).trim().to_string()
+}
+
+/// Whether `path` may be a shot: a supported, visible file right in `folder`.
+fn is_shot(folder: &Path, path: &Path) -> bool {
+    path.parent() == Some(folder)
+        && !path.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.'))
+        && crate::formats::FileFormat::is_supported_extension(path)
+}
+
+/// Starts a session on `folder`, replacing the running one. The folder is
+/// added to the library when it isn't part of it.
+pub async fn start(app: &AppHandle, db: Arc<Db>, folder: &str, tag: Option<String>) -> AppResult<CaptureSession> {
+    let path = PathBuf::from(folder)
+        .canonicalize()
+        .map_err(|_| AppError::NotFound(format!("Folder not found: {}", folder)))?;
+    if !path.is_dir() {
+        return Err(AppError::Generic(format!("Not a folder: {}", folder)));
+    }
+    let folder = path.to_string_lossy().to_string();
+
+    let inside_location = db
+        .get_all_root_folders()
+        .await?
+        .iter()
+        .any(|(_, root)| path.starts_with(root));
+    if inside_location {
+        db.ensure_folder_hierarchy(&folder).await?;
+    } else {
+        crate::library::commands::folders::register_location(app, &db, folder.clone()).await?;
+    }
+
+    let tag = tag
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| default_tag(&path, Local::now()));
+    let session = CaptureSession { folder, tag: tag.clone(), started_at: Utc::now(), shots: 0 };
+
+    let (stop_tx, stop_rx) = oneshot::channel();
+    // Swapped under one lock, so concurrent starts each end the session they replace
+    let replaced = running().replace(Running { session: session.clone(), stop: stop_tx });
+    if let Some(replaced) = replaced {
+        end(replaced);
+    }
+    tauri::async_runtime::spawn(follow(app.clone(), db, path, tag, stop_rx));
+    println!("INFO: Capture session started on {} ({})", session.folder, session.tag);
+    Ok(session)
+}
+
+/// Stops the running session. Returns it, with its final count of shots.
+pub fn stop() -> Option<CaptureSession> {
+    running().take().map(end)
+}
+
+fn end(stopped: Running) -> CaptureSession {
+    let _ = stopped.stop.send(());
+    println!("INFO: Capture session on {} stopped after {} shots", stopped.session.folder, stopped.session.shots);
+    stopped.session
+}
+
+/// Watches `folder` and takes each file once it is written, until `stop`.
+async fn follow(app: AppHandle, db: Arc<Db>, folder: PathBuf, tag: String, mut stop: oneshot::Receiver<()>) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<PathBuf>();
+    let watcher = RecommendedWatcher::new(
+        move |res: notify::Result<Event>| {
+            if let Ok(event) = res {
+                for path in event.paths {
+                    let _ = tx.send(path);
+                }
+            }
+        },
+        Config::default(),
+    );
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            eprintln!("ERROR: Could not watch the capture folder {}: {}", folder.display(), e);
+            return;
+        }
+    };
+    if let Err(e) = watcher.watch(&folder, RecursiveMode::NonRecursive) {
+        eprintln!("ERROR: Could not watch the capture folder {}: {}", folder.display(), e);
+        return;
+    }
+
+    // Files being written: their size and when it last changed
+    let mut writing: HashMap<PathBuf, (u64, Instant)> = HashMap::new();
+    let mut timer = tokio::time::interval(SETTLE_POLL);
+    loop {
+        tokio::select! {
+            _ = &mut stop => {
+                // The library watchers saw none of their events, so they are taken now
+                let mut unfinished: Vec<PathBuf> = std::mem::take(&mut writing).into_keys().filter(|path| path.is_file()).collect();
+                unfinished.sort();
+                for path in unfinished {
+                    if let Err(e) = capture(&app, &db, &path, &tag).await {
+                        eprintln!("WARN: Could not take the shot {}: {}", path.display(), e);
+                    }
+                }
+                return;
+            }
+            Some(path) = rx.recv() => {
+                if is_shot(&folder, &path) {
+                    let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                    writing.insert(path, (size, Instant::now()));
+                }
+            }
+            _ = timer.tick() => {
+                if writing.is_empty() {
+                    continue;
+                }
+                let mut written = Vec::new();
+                writing.retain(|path, (size, changed)| {
+                    let Ok(metadata) = std::fs::metadata(path) else {
+                        // Gone, or renamed to the name the event for it will carry
+                        return false;
+                    };
+                    if metadata.len() != *size {
+                        *size = metadata.len();
+                        *changed = Instant::now();
+                        true
+                    } else if *size > 0 && changed.elapsed() >= SETTLE_TIME {
+                        written.push(path.clone());
+                        false
+                    } else {
+                        true
+                    }
+                });
+                written.sort();
+                for path in written {
+                    if let Err(e) = capture(&app, &db, &path, &tag).await {
+                        eprintln!("WARN: Could not take the shot {}: {}", path.display(), e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Indexes a written file and announces it when it is new.
+async fn capture(app: &AppHandle, db: &Db, path: &Path, tag: &str) -> Result<(), String> {
+    let meta = crate::indexer::metadata::get_image_metadata(path).ok_or("Could not read the file")?;
+    let parent = path.parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+    let folder_id = db.ensure_folder_hierarchy(&parent).await.map_err(|e| e.to_string())?;
+    let (id, old_folder_id, is_new) =
+        crate::indexer::watcher::save_file(db, folder_id, &meta).await.map_err(|e| e.to_string())?;
+
+    let tagged = ImportedMetadata {
+        path: meta.path.clone(),
+        tags: vec![(SESSIONS_TAG.to_string(), tag.to_string())],
+        ..Default::default()
+    };
+    db.queue_import_metadata(SOURCE, &[tagged]).await.map_err(|e| e.to_string())?;
+    db.apply_pending_import_metadata().await.map_err(|e| e.to_string())?;
+    if let Some(priority) = app.try_state::<Arc<ThumbnailPriorityState>>() {
+        priority.add_priority(&[id]);
+    }
+
+    let mut metadata = meta.clone();
+    metadata.id = id;
+    let item = AddedItemContext { metadata, folder_id, old_folder_id };
+    let (added, updated) = if is_new { (vec![item], vec![]) } else { (vec![], vec![item]) };
+    let _ = app.emit("library:batch-change", BatchChangePayload { added, removed: vec![], updated, needs_refresh: false });
+    crate::library::filter_subscriptions::notify_changes(app);
+    // A shot edited or rewritten by the tethering software isn't a new frame
+    if !is_new {
+        return Ok(());
+    }
+    crate::library::proxies::queue_added(app, db, &[meta.path.clone()]).await;
+
+    let shots = match running().as_mut() {
+        Some(running) if Path::new(&running.session.folder) == path.parent().unwrap_or(path) => {
+            running.session.shots += 1;
+            running.session.shots
+        }
+        _ => return Ok(()),
+    };
+    // Rendered aside, so the next shots don't wait for it
+    let app = app.clone();
+    let source = path.to_path_buf();
+    tauri::async_runtime::spawn(async move {
+        let preview_app = app.clone();
+        let rendered = tauri::async_runtime::spawn_blocking(move || {
+            crate::thumbnails::variants::get_or_create_variant(&preview_app, &source, PREVIEW_MAXDIM).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string());
+        let preview_ready = match rendered {
+            Ok(Ok(_)) => true,
+            Ok(Err(e)) | Err(e) => {
+                eprintln!("WARN: Could not render the preview of {}: {}", meta.path, e);
+                false
+            }
+        };
+        let _ = app.emit("capture:shot", CaptureShot { image_id: id, folder_id, path: meta.path, preview_ready, shots });
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_default_tag() {
+        let day = Local.with_ymd_and_hms(2024, 5, 1, 23, 59, 0).unwrap();
+        assert_eq!(default_tag(Path::new("/shoots/Studio"), day), "Studio 2024-05-01");
+        assert_eq!(default_tag(Path::new("/"), day), "2024-05-01");
+    }
+
+    #[test]
+    fn test_is_shot() {
+        let folder = Path::new("/shoots/studio");
+        assert!(is_shot(folder, &folder.join("IMG_0001.CR3")));
+        assert!(is_shot(folder, &folder.join("frame.jpg")));
+        assert!(!is_shot(folder, &folder.join(".IMG_0001.CR3.tmp")));
+        assert!(!is_shot(folder, &folder.join("capture.lock")));
+        assert!(!is_shot(folder, &folder.join("selects").join("frame.jpg")));
+    }
+}
@@ -0,0 +1,431 @@
+//! Tabular export of the catalog (CSV, JSON or Parquet).
+//!
+//! Only the ids of the filtered images are held in memory. Rows are read
+//! from the database a page at a time, in grid order, and written straight
+//! to the output, so catalogs of any size can be exported.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use serde::Deserialize;
+use sqlx::Row;
+
+use crate::db::fields::FIELD_PREFIX;
+use crate::db::search::ImageFilter;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+
+/// Images read from the database at a time.
+const PAGE_SIZE: usize = 500;
+
+/// Rows per Parquet row group.
+const ROW_GROUP_SIZE: usize = 10_000;
+
+/// File format of a catalog export, sent as `"csv"`, `"json"` or `"parquet"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Parquet,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnKind {
+    Int,
+    Float,
+    Text,
+}
+
+/// Exportable columns: name, SQL expression over `images i` / `folders f`, kind.
+const COLUMNS: &[(&str, &str, ColumnKind)] = &[
+    ("id", "i.id", ColumnKind::Int),
+    ("path", "i.path", ColumnKind::Text),
+    ("filename", "i.filename", ColumnKind::Text),
+    ("folder", "f.path", ColumnKind::Text),
+    ("format", "i.format", ColumnKind::Text),
+    ("width", "i.width", ColumnKind::Int),
+    ("height", "i.height", ColumnKind::Int),
+    ("size", "i.size", ColumnKind::Int),
+    ("rating", "i.rating", ColumnKind::Int),
+    ("notes", "i.notes", ColumnKind::Text),
+    ("tags", "(SELECT group_concat(t.name, '; ') FROM image_tags it JOIN tags t ON t.id = it.tag_id WHERE it.image_id = i.id)", ColumnKind::Text),
+    ("created_at", "i.created_at", ColumnKind::Text),
+    ("modified_at", "i.modified_at", ColumnKind::Text),
+    ("added_at", "i.added_at", ColumnKind::Text),
+    ("duration", "i.duration", ColumnKind::Float),
+    ("video_codec", "i.video_codec", ColumnKind::Text),
+    ("audio_codec", "i.audio_codec", ColumnKind::Text),
+    ("latitude", "i.latitude", ColumnKind::Float),
+    ("longitude", "i.longitude", ColumnKind::Float),
+    ("stock_provider", "(SELECT provider FROM stock_assets s WHERE s.image_id = i.id)", ColumnKind::Text),
+];
+
+/// A column picked for export: one of [`COLUMNS`] or a custom field.
+#[derive(Debug, Clone)]
+struct Column {
+    name: String,
+    /// SQL expression over `images i` / `folders f`.
+    sql: String,
+    kind: ColumnKind,
+}
+
+impl Column {
+    fn field(name: &str) -> Self {
+        Self {
+            name: format!("{}{}", FIELD_PREFIX, name),
+            sql: format!(
+                "(SELECT value FROM image_fields v WHERE v.image_id = i.id AND v.name = '{}')",
+                name.replace('\'', "''")
+            ),
+            kind: ColumnKind::Text,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    Null,
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl Cell {
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Cell::Null => serde_json::Value::Null,
+            Cell::Int(v) => (*v).into(),
+            Cell::Float(v) => serde_json::Number::from_f64(*v).map(serde_json::Value::Number).unwrap_or_default(),
+            Cell::Text(v) => v.clone().into(),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        match self {
+            Cell::Null => String::new(),
+            Cell::Int(v) => v.to_string(),
+            Cell::Float(v) => v.to_string(),
+            Cell::Text(v) => v.clone(),
+        }
+    }
+}
+
+/// Resolves requested column names, in the requested order; `field:<name>`
+/// picks a custom field. `None` or an empty list exports every column and
+/// the custom fields in `field_names`.
+fn resolve_columns(names: Option<&[String]>, field_names: &[String]) -> AppResult<Vec<Column>> {
+    let builtin = |&(name, sql, kind): &(&str, &str, ColumnKind)| Column { name: name.to_string(), sql: sql.to_string(), kind };
+    match names {
+        None | Some([]) => Ok(COLUMNS.iter().map(builtin).chain(field_names.iter().map(|name| Column::field(name))).collect()),
+        Some(names) => names
+            .iter()
+            .map(|name| match name.strip_prefix(FIELD_PREFIX) {
+                Some(field) if !field.trim().is_empty() => Ok(Column::field(field.trim())),
+                _ => COLUMNS
+                    .iter()
+                    .find(|(column, _, _)| column == name)
+                    .map(builtin)
+                    .ok_or_else(|| AppError::Generic(format!("Unknown export column '{}'", name))),
+            })
+            .collect(),
+    }
+}
+
+/// Quotes a CSV field when it holds a separator, quote or line break.
+pub fn csv_field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+trait RowSink {
+    fn write_row(&mut self, row: &[Cell]) -> Result<(), String>;
+    fn finish(self: Box<Self>) -> Result<(), String>;
+}
+
+struct CsvSink {
+    out: BufWriter<File>,
+}
+
+impl CsvSink {
+    fn new(file: File, columns: &[Column]) -> Result<Self, String> {
+        let mut sink = Self { out: BufWriter::new(file) };
+        let header: Vec<Cell> = columns.iter().map(|c| Cell::Text(c.name.clone())).collect();
+        sink.write_row(&header)?;
+        Ok(sink)
+    }
+}
+
+impl RowSink for CsvSink {
+    fn write_row(&mut self, row: &[Cell]) -> Result<(), String> {
+        let line: Vec<String> = row.iter().map(|cell| csv_field(&cell.to_text()).into_owned()).collect();
+        writeln!(self.out, "{}", line.join(",")).map_err(|e| e.to_string())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), String> {
+        self.out.flush().map_err(|e| e.to_string())
+    }
+}
+
+struct JsonSink {
+    out: BufWriter<File>,
+    names: Vec<String>,
+    first: bool,
+}
+
+impl RowSink for JsonSink {
+    fn write_row(&mut self, row: &[Cell]) -> Result<(), String> {
+        let object: serde_json::Map<String, serde_json::Value> =
+            self.names.iter().zip(row).map(|(name, cell)| (name.clone(), cell.to_json())).collect();
+        let separator = if self.first { "[\n" } else { ",\n" };
+        self.first = false;
+        self.out.write_all(separator.as_bytes()).map_err(|e| e.to_string())?;
+        serde_json::to_writer(&mut self.out, &object).map_err(|e| e.to_string())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), String> {
+        let end = if self.first { "[]\n" } else { "\n]\n" };
+        self.out.write_all(end.as_bytes()).map_err(|e| e.to_string())?;
+        self.out.flush().map_err(|e| e.to_string())
+    }
+}
+
+struct ParquetSink {
+    writer: SerializedFileWriter<BufWriter<File>>,
+    kinds: Vec<ColumnKind>,
+    /// Buffered row group, column by column.
+    buffer: Vec<Vec<Cell>>,
+}
+
+impl ParquetSink {
+    fn new(file: File, columns: &[Column]) -> Result<Self, String> {
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|c| {
+                // The schema parser only takes plain identifiers
+                let name: String = c.name.chars().map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' }).collect();
+                match c.kind {
+                    ColumnKind::Int => format!("OPTIONAL INT64 {};", name),
+                    ColumnKind::Float => format!("OPTIONAL DOUBLE {};", name),
+                    ColumnKind::Text => format!("OPTIONAL BYTE_ARRAY {} (UTF8);", name),
+                }
+            })
+            .collect();
+        let schema = parse_message_type(&format!("message image {{ {} }}", fields.join(" "))).map_err(|e| e.to_string())?;
+        let properties = Arc::new(WriterProperties::builder().build());
+        let writer = SerializedFileWriter::new(BufWriter::new(file), Arc::new(schema), properties).map_err(|e| e.to_string())?;
+        Ok(Self {
+            writer,
+            kinds: columns.iter().map(|c| c.kind).collect(),
+            buffer: vec![Vec::new(); columns.len()],
+        })
+    }
+
+    fn flush_row_group(&mut self) -> Result<(), String> {
+        if self.buffer.first().map_or(0, Vec::len) == 0 {
+            return Ok(());
+        }
+        let mut row_group = self.writer.next_row_group().map_err(|e| e.to_string())?;
+        for (cells, kind) in self.buffer.iter_mut().zip(&self.kinds) {
+            let Some(mut column) = row_group.next_column().map_err(|e| e.to_string())? else { break };
+            let levels: Vec<i16> = cells.iter().map(|cell| i16::from(*cell != Cell::Null)).collect();
+            let written = match kind {
+                ColumnKind::Int => {
+                    let values: Vec<i64> = cells.iter().filter_map(|c| if let Cell::Int(v) = c { Some(*v) } else { None }).collect();
+                    column.typed::<Int64Type>().write_batch(&values, Some(&levels), None)
+                }
+                ColumnKind::Float => {
+                    let values: Vec<f64> = cells.iter().filter_map(|c| if let Cell::Float(v) = c { Some(*v) } else { None }).collect();
+                    column.typed::<DoubleType>().write_batch(&values, Some(&levels), None)
+                }
+                ColumnKind::Text => {
+                    let values: Vec<ByteArray> = cells
+                        .iter()
+                        .filter_map(|c| if let Cell::Text(v) = c { Some(ByteArray::from(v.as_str())) } else { None })
+                        .collect();
+                    column.typed::<ByteArrayType>().write_batch(&values, Some(&levels), None)
+                }
+            };
+            written.map_err(|e| e.to_string())?;
+            column.close().map_err(|e| e.to_string())?;
+            cells.clear();
+        }
+        row_group.close().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+impl RowSink for ParquetSink {
+    fn write_row(&mut self, row: &[Cell]) -> Result<(), String> {
+        for (column, cell) in self.buffer.iter_mut().zip(row) {
+            column.push(cell.clone());
+        }
+        if self.buffer.first().is_some_and(|column| column.len() >= ROW_GROUP_SIZE) {
+            self.flush_row_group()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), String> {
+        self.flush_row_group()?;
+        self.writer.close().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+fn open_sink(format: ExportFormat, output_path: &Path, columns: &[Column]) -> Result<Box<dyn RowSink + Send>, String> {
+    let file = File::create(output_path).map_err(|e| format!("Failed to create {}: {}", output_path.display(), e))?;
+    Ok(match format {
+        ExportFormat::Csv => Box::new(CsvSink::new(file, columns)?),
+        ExportFormat::Json => Box::new(JsonSink {
+            out: BufWriter::new(file),
+            names: columns.iter().map(|c| c.name.clone()).collect(),
+            first: true,
+        }),
+        ExportFormat::Parquet => Box::new(ParquetSink::new(file, columns)?),
+    })
+}
+
+/// Reads one page of rows, keyed by image id.
+async fn read_page(db: &Db, ids: &[i64], columns: &[Column]) -> Result<HashMap<i64, Vec<Cell>>, sqlx::Error> {
+    let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new("SELECT i.id");
+    for column in columns {
+        query_builder.push(", ");
+        query_builder.push(&column.sql);
+    }
+    query_builder.push(" FROM images i LEFT JOIN folders f ON f.id = i.folder_id WHERE i.id IN (");
+    let mut separated = query_builder.separated(", ");
+    for id in ids {
+        separated.push_bind(*id);
+    }
+    separated.push_unseparated(")");
+
+    let rows = query_builder.build().fetch_all(&db.pool).await?;
+    let mut page = HashMap::with_capacity(rows.len());
+    for row in rows {
+        let id: i64 = row.try_get(0)?;
+        let mut cells = Vec::with_capacity(columns.len());
+        for (index, column) in columns.iter().enumerate() {
+            let cell = match column.kind {
+                ColumnKind::Int => row.try_get::<Option<i64>, _>(index + 1)?.map(Cell::Int),
+                ColumnKind::Float => row.try_get::<Option<f64>, _>(index + 1)?.map(Cell::Float),
+                ColumnKind::Text => row.try_get::<Option<String>, _>(index + 1)?.map(Cell::Text),
+            };
+            cells.push(cell.unwrap_or(Cell::Null));
+        }
+        page.insert(id, cells);
+    }
+    Ok(page)
+}
+
+/// Hidden file an export is written to before it is renamed to `output_path`,
+/// so a failed or interrupted export never leaves a truncated file behind.
+fn partial_path(output_path: &Path) -> PathBuf {
+    let name = output_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    output_path.with_file_name(format!(".{}.part", name))
+}
+
+/// Exports the images matching `filter`, in grid order, to `output_path`.
+/// Returns the number of rows written.
+pub async fn export(
+    db: &Db,
+    filter: &ImageFilter,
+    format: ExportFormat,
+    columns: Option<&[String]>,
+    output_path: &Path,
+) -> AppResult<usize> {
+    let field_names = match columns {
+        None | Some([]) => db.get_field_names().await?,
+        Some(_) => Vec::new(),
+    };
+    let columns = resolve_columns(columns, &field_names)?;
+    let group = filter.parsed_group();
+    let mut query_builder = filter.build_id_query(group.as_ref(), "");
+    let ids = query_builder.build_query_scalar::<i64>().fetch_all(&db.pool).await?;
+
+    let partial = partial_path(output_path);
+    let result = write_rows(db, &ids, format, &columns, &partial).await;
+    let output_path = output_path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let result = result.and_then(|written| {
+            std::fs::rename(&partial, &output_path)
+                .map_err(|e| AppError::Generic(format!("Failed to write {}: {}", output_path.display(), e)))?;
+            Ok(written)
+        });
+        if result.is_err() {
+            let _ = std::fs::remove_file(&partial);
+        }
+        result
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+/// Writes the rows of `ids` to `path`, returning how many were written.
+async fn write_rows(db: &Db, ids: &[i64], format: ExportFormat, columns: &[Column], path: &Path) -> AppResult<usize> {
+    let mut sink = {
+        let path = path.to_path_buf();
+        let columns = columns.to_vec();
+        tokio::task::spawn_blocking(move || open_sink(format, &path, &columns))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?
+            .map_err(AppError::Generic)?
+    };
+
+    let mut written = 0;
+    for chunk in ids.chunks(PAGE_SIZE) {
+        let mut page = read_page(db, chunk, columns).await?;
+        let rows: Vec<Vec<Cell>> = chunk.iter().filter_map(|id| page.remove(id)).collect();
+        written += rows.len();
+        sink = tokio::task::spawn_blocking(move || -> Result<_, String> {
+            for row in &rows {
+                sink.write_row(row)?;
+            }
+            Ok(sink)
+        })
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::Generic)?;
+    }
+
+    tokio::task::spawn_blocking(move || sink.finish())
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::Generic)?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_resolve_columns() {
+        assert_eq!(resolve_columns(None, &[]).unwrap().len(), COLUMNS.len());
+        let all = resolve_columns(None, &["client".to_string()]).unwrap();
+        assert_eq!(all.last().unwrap().name, "field:client");
+        let picked = resolve_columns(Some(&["rating".to_string(), "path".to_string(), "field:it's".to_string()]), &[]).unwrap();
+        assert_eq!(picked.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), vec!["rating", "path", "field:it's"]);
+        assert!(picked[2].sql.contains("'it''s'"));
+        assert!(resolve_columns(Some(&["nope".to_string()]), &[]).is_err());
+        assert!(resolve_columns(Some(&["field:".to_string()]), &[]).is_err());
+    }
+}
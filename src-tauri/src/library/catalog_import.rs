@@ -0,0 +1,400 @@
+//! Metadata patches from CSV, the round trip of `catalog_export`.
+//!
+//! Each row is matched to an image by its key column (path, id or content
+//! hash) and the mapped columns are applied. Empty cells leave the field
+//! alone; a `tags` cell replaces the image's tags with the listed ones, and a
+//! column mapped to `field:<name>` sets that custom field (see `db::fields`). A dry
+//! run applies everything inside a transaction that is rolled back, so it
+//! reports exactly what a real import would do.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::fields::{set_field, FIELD_PREFIX};
+use crate::db::imports::ensure_tag;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+
+/// Fields a CSV column can be mapped to, besides `field:<name>` custom
+/// fields.
+pub const TARGETS: &[&str] = &["rating", "notes", "tags", "latitude", "longitude"];
+
+/// How rows are matched to images.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchBy {
+    #[default]
+    Path,
+    Id,
+    /// The full content hash of the file (`images.content_hash`), as listed
+    /// by the duplicate finder.
+    Hash,
+}
+
+/// Changes parsed from one CSV row.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetadataPatch {
+    /// 1-based row number, the header being row 1.
+    pub line: usize,
+    pub key: String,
+    pub rating: Option<i32>,
+    pub notes: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// Custom fields to set, by name.
+    pub fields: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowIssue {
+    pub line: usize,
+    /// Key of the row, empty if the key cell is.
+    pub key: String,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvImportReport {
+    /// Data rows in the file.
+    pub rows: usize,
+    /// Rows matching at least one image.
+    pub matched: usize,
+    /// Images whose metadata changed.
+    pub updated: usize,
+    /// Rows without a matching image.
+    pub unmatched: Vec<RowIssue>,
+    /// Rows skipped because a cell couldn't be parsed.
+    pub invalid: Vec<RowIssue>,
+    /// Whether the changes were rolled back.
+    pub dry_run: bool,
+}
+
+/// Parses CSV text (RFC 4180: quoted fields, doubled quotes, CRLF). The
+/// delimiter is `,` unless the header has more `;`, as written by Excel in
+/// locales using a decimal comma.
+pub fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let header = content.lines().next().unwrap_or("");
+    let delimiter = if header.matches(';').count() > header.matches(',').count() { ';' } else { ',' };
+
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' => in_quotes = true,
+            c if c == delimiter => row.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    // Blank lines
+    rows.retain(|r| !(r.len() == 1 && r[0].trim().is_empty()));
+    rows
+}
+
+/// Splits a tags cell: `;` separated, or `,` when there is no `;`.
+fn split_tags(cell: &str) -> Vec<String> {
+    let separator = if cell.contains(';') { ';' } else { ',' };
+    // Repeats anywhere in the cell, not only next to each other
+    let mut seen = HashSet::new();
+    cell.split(separator)
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty() && seen.insert(t.clone()))
+        .collect()
+}
+
+fn parse_number<T: std::str::FromStr>(column: &str, cell: &str) -> Result<T, String> {
+    // Decimal commas from spreadsheets in other locales
+    cell.replace(',', ".").parse().map_err(|_| format!("Invalid {} '{}'", column, cell))
+}
+
+/// Turns parsed CSV rows into patches. `mapping` maps CSV column names to
+/// [`TARGETS`]. Returns the patches and the rows that couldn't be parsed.
+pub fn build_patches(
+    rows: &[Vec<String>],
+    key_column: &str,
+    mapping: &HashMap<String, String>,
+) -> AppResult<(Vec<MetadataPatch>, Vec<RowIssue>)> {
+    let header = rows.first().ok_or_else(|| AppError::Generic("The CSV file is empty".to_string()))?;
+    let position = |name: &str| header.iter().position(|h| h.trim() == name);
+
+    let key_index = position(key_column)
+        .ok_or_else(|| AppError::Generic(format!("Key column '{}' not found in the CSV header", key_column)))?;
+    let mut columns = Vec::new();
+    for (column, target) in mapping {
+        let is_field = target.strip_prefix(FIELD_PREFIX).is_some_and(|name| !name.trim().is_empty());
+        if !is_field && !TARGETS.contains(&target.as_str()) {
+            return Err(AppError::Generic(format!(
+                "Unknown target '{}' for column '{}' (expected one of {} or {}<name>)",
+                target,
+                column,
+                TARGETS.join(", "),
+                FIELD_PREFIX
+            )));
+        }
+        let index = position(column).ok_or_else(|| AppError::Generic(format!("Column '{}' not found in the CSV header", column)))?;
+        columns.push((index, column.as_str(), target.as_str()));
+    }
+
+    let mut patches = Vec::new();
+    let mut invalid = Vec::new();
+    for (offset, row) in rows.iter().enumerate().skip(1) {
+        let line = offset + 1;
+        let key = row.get(key_index).map(|k| k.trim().to_string()).unwrap_or_default();
+        if key.is_empty() {
+            invalid.push(RowIssue { line, key, message: "Empty key".to_string() });
+            continue;
+        }
+
+        let mut patch = MetadataPatch { line, key: key.clone(), ..Default::default() };
+        let parsed: Result<(), String> = columns.iter().try_for_each(|&(index, column, target)| {
+            let cell = row.get(index).map(|c| c.trim()).unwrap_or("");
+            if cell.is_empty() {
+                return Ok(());
+            }
+            match target {
+                "rating" => {
+                    let rating: i32 = parse_number(column, cell)?;
+                    if !(0..=5).contains(&rating) {
+                        return Err(format!("Rating {} is not between 0 and 5", rating));
+                    }
+                    patch.rating = Some(rating);
+                }
+                "notes" => patch.notes = Some(cell.to_string()),
+                "tags" => patch.tags = Some(split_tags(cell)),
+                "latitude" => patch.latitude = Some(parse_number(column, cell)?),
+                "longitude" => patch.longitude = Some(parse_number(column, cell)?),
+                field => {
+                    let name = field.strip_prefix(FIELD_PREFIX).unwrap_or(field).trim();
+                    patch.fields.push((name.to_string(), cell.to_string()));
+                }
+            }
+            Ok(())
+        });
+        match parsed {
+            Ok(()) => patches.push(patch),
+            Err(message) => invalid.push(RowIssue { line, key, message }),
+        }
+    }
+    Ok((patches, invalid))
+}
+
+/// Ids of the images a row's key points to. A content hash can match
+/// several copies, which are all patched.
+async fn find_images(conn: &mut sqlx::SqliteConnection, match_by: MatchBy, key: &str) -> Result<Vec<i64>, sqlx::Error> {
+    match match_by {
+        MatchBy::Id => {
+            let Ok(id) = key.parse::<i64>() else { return Ok(Vec::new()) };
+            sqlx::query_scalar("SELECT id FROM images WHERE id = ?").bind(id).fetch_all(&mut *conn).await
+        }
+        MatchBy::Path => sqlx::query_scalar("SELECT id FROM images WHERE path = ?").bind(key).fetch_all(&mut *conn).await,
+        MatchBy::Hash => {
+            sqlx::query_scalar("SELECT id FROM images WHERE content_hash = ?")
+                .bind(key.to_lowercase())
+                .fetch_all(&mut *conn)
+                .await
+        }
+    }
+}
+
+/// Applies one patch, returning whether anything changed.
+async fn apply_patch(conn: &mut sqlx::SqliteConnection, image_id: i64, patch: &MetadataPatch) -> Result<bool, sqlx::Error> {
+    let mut changed = 0;
+    if let Some(rating) = patch.rating {
+        changed += sqlx::query("UPDATE images SET rating = ? WHERE id = ? AND rating IS NOT ?")
+            .bind(rating)
+            .bind(image_id)
+            .bind(rating)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+    }
+    if let Some(notes) = &patch.notes {
+        changed += sqlx::query("UPDATE images SET notes = ? WHERE id = ? AND notes IS NOT ?")
+            .bind(notes)
+            .bind(image_id)
+            .bind(notes)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+    }
+    for (column, value) in [("latitude", patch.latitude), ("longitude", patch.longitude)] {
+        if let Some(value) = value {
+            changed += sqlx::query(&format!("UPDATE images SET {0} = ? WHERE id = ? AND {0} IS NOT ?", column))
+                .bind(value)
+                .bind(image_id)
+                .bind(value)
+                .execute(&mut *conn)
+                .await?
+                .rows_affected();
+        }
+    }
+
+    for (name, value) in &patch.fields {
+        if set_field(&mut *conn, image_id, name, value).await? {
+            changed += 1;
+        }
+    }
+
+    if let Some(tags) = &patch.tags {
+        let mut wanted = HashSet::new();
+        for name in tags {
+            wanted.insert(ensure_tag(&mut *conn, name, None).await?);
+        }
+        let current: HashSet<i64> = sqlx::query_scalar::<_, i64>("SELECT tag_id FROM image_tags WHERE image_id = ?")
+            .bind(image_id)
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .collect();
+        for tag_id in current.difference(&wanted) {
+            sqlx::query("DELETE FROM image_tags WHERE image_id = ? AND tag_id = ?")
+                .bind(image_id)
+                .bind(*tag_id)
+                .execute(&mut *conn)
+                .await?;
+            changed += 1;
+        }
+        for tag_id in wanted.difference(&current) {
+            sqlx::query("INSERT INTO image_tags (image_id, tag_id) VALUES (?, ?)")
+                .bind(image_id)
+                .bind(*tag_id)
+                .execute(&mut *conn)
+                .await?;
+            changed += 1;
+        }
+    }
+    Ok(changed > 0)
+}
+
+/// Imports the CSV file at `path`. With `dry_run` every change is rolled
+/// back and the report tells what would have happened.
+pub async fn import(
+    db: &Db,
+    path: &Path,
+    key_column: &str,
+    match_by: MatchBy,
+    mapping: &HashMap<String, String>,
+    dry_run: bool,
+) -> AppResult<CsvImportReport> {
+    let content = tokio::fs::read(path).await?;
+    let rows = parse_csv(&String::from_utf8_lossy(&content));
+    let (patches, invalid) = build_patches(&rows, key_column, mapping)?;
+
+    let mut report = CsvImportReport {
+        rows: rows.len().saturating_sub(1),
+        invalid,
+        dry_run,
+        ..Default::default()
+    };
+
+    let mut tx = db.pool.begin().await?;
+    for patch in &patches {
+        let image_ids = find_images(&mut tx, match_by, &patch.key).await?;
+        if image_ids.is_empty() {
+            report.unmatched.push(RowIssue {
+                line: patch.line,
+                key: patch.key.clone(),
+                message: "No matching image".to_string(),
+            });
+            continue;
+        }
+        report.matched += 1;
+        for image_id in image_ids {
+            if apply_patch(&mut tx, image_id, patch).await? {
+                report.updated += 1;
+            }
+        }
+    }
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+
+    println!(
+        "INFO: CSV import from {:?}: {} rows, {} matched, {} updated, {} unmatched{}",
+        path,
+        report.rows,
+        report.matched,
+        report.updated,
+        report.unmatched.len(),
+        if dry_run { " (dry run)" } else { "" }
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let rows = parse_csv("\u{feff}path,notes\r\n/a.jpg,\"Hello, \"\"world\"\"\"\r\n\r\n/b.jpg,\"two\nlines\"\n");
+        assert_eq!(rows, vec![
+            vec!["path".to_string(), "notes".to_string()],
+            vec!["/a.jpg".to_string(), "Hello, \"world\"".to_string()],
+            vec!["/b.jpg".to_string(), "two\nlines".to_string()],
+        ]);
+
+        let rows = parse_csv("path;rating\n/a.jpg;4");
+        assert_eq!(rows[1], vec!["/a.jpg".to_string(), "4".to_string()]);
+    }
+
+    #[test]
+    fn test_build_patches() {
+        let rows = parse_csv(
+            "path,stars,keywords,lat,client\n/a.jpg,4,sky; sea; sky,\"38,7\",ACME\n/b.jpg,,,,\n/c.jpg,9,,,\n,3,,,\n",
+        );
+        let mapping = HashMap::from([
+            ("stars".to_string(), "rating".to_string()),
+            ("keywords".to_string(), "tags".to_string()),
+            ("lat".to_string(), "latitude".to_string()),
+            ("client".to_string(), "field:client".to_string()),
+        ]);
+        let (patches, invalid) = build_patches(&rows, "path", &mapping).unwrap();
+
+        assert_eq!(patches.len(), 2);
+        assert_eq!(patches[0].rating, Some(4));
+        assert_eq!(patches[0].tags, Some(vec!["sky".to_string(), "sea".to_string()]));
+        assert_eq!(patches[0].latitude, Some(38.7));
+        assert_eq!(patches[0].fields, vec![("client".to_string(), "ACME".to_string())]);
+        // Empty cells change nothing
+        assert_eq!(patches[1], MetadataPatch { line: 3, key: "/b.jpg".to_string(), ..Default::default() });
+        assert_eq!(invalid.iter().map(|i| i.line).collect::<Vec<_>>(), vec![4, 5]);
+
+        let bad = HashMap::from([("stars".to_string(), "colour".to_string())]);
+        assert!(build_patches(&rows, "path", &bad).is_err());
+        let unnamed = HashMap::from([("client".to_string(), "field: ".to_string())]);
+        assert!(build_patches(&rows, "path", &unnamed).is_err());
+        assert!(build_patches(&rows, "missing", &mapping).is_err());
+    }
+}
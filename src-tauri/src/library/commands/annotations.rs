@@ -0,0 +1,89 @@
+use crate::db::Db;
+use crate::db::models::{AnnotationRegion, ImageAnnotation, ImageMetadata};
+use crate::error::{AppError, AppResult};
+use std::sync::Arc;
+use tauri::State;
+
+/// Rejects regions outside the image or with no area.
+fn validate_region(region: Option<AnnotationRegion>) -> AppResult<Option<AnnotationRegion>> {
+    if let Some(r) = region {
+        let in_bounds = [r.x, r.y, r.w, r.h].iter().all(|v| v.is_finite() && (0.0..=1.0).contains(v));
+        if !in_bounds || r.w <= 0.0 || r.h <= 0.0 || r.x + r.w > 1.0 + f64::EPSILON || r.y + r.h > 1.0 + f64::EPSILON {
+            return Err(AppError::Generic(format!(
+                "Invalid annotation region {:?}: coordinates must be normalized to 0..1",
+                r
+            )));
+        }
+    }
+    Ok(region)
+}
+
+/// Adds a note to an image, optionally pinned to a region or linking another image.
+#[tauri::command]
+pub async fn add_image_annotation(
+    db: State<'_, Arc<Db>>,
+    image_id: i64,
+    body: String,
+    region: Option<AnnotationRegion>,
+    related_image_id: Option<i64>,
+) -> AppResult<i64> {
+    let region = validate_region(region)?;
+    if related_image_id == Some(image_id) {
+        return Err(AppError::Generic("An image cannot be related to itself".to_string()));
+    }
+    Ok(db.add_image_annotation(image_id, &body, region, related_image_id).await?)
+}
+
+/// Replaces the text, region and linked image of an annotation.
+#[tauri::command]
+pub async fn update_image_annotation(
+    db: State<'_, Arc<Db>>,
+    id: i64,
+    body: String,
+    region: Option<AnnotationRegion>,
+    related_image_id: Option<i64>,
+) -> AppResult<()> {
+    let region = validate_region(region)?;
+    if !db.update_image_annotation(id, &body, region, related_image_id).await? {
+        return Err(AppError::NotFound(format!("Annotation {} not found", id)));
+    }
+    Ok(())
+}
+
+/// Deletes an annotation; unknown ids are ignored.
+#[tauri::command]
+pub async fn delete_image_annotation(db: State<'_, Arc<Db>>, id: i64) -> AppResult<()> {
+    Ok(db.delete_image_annotation(id).await?)
+}
+
+/// Lists the annotations of an image, oldest first.
+#[tauri::command]
+pub async fn get_image_annotations(db: State<'_, Arc<Db>>, image_id: i64) -> AppResult<Vec<ImageAnnotation>> {
+    Ok(db.get_image_annotations(image_id).await?)
+}
+
+/// Lists images cross-linked with an image, in either direction.
+#[tauri::command]
+pub async fn get_related_images(db: State<'_, Arc<Db>>, image_id: i64) -> AppResult<Vec<ImageMetadata>> {
+    Ok(db.get_related_images(image_id).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(x: f64, y: f64, w: f64, h: f64) -> Option<AnnotationRegion> {
+        Some(AnnotationRegion { x, y, w, h })
+    }
+
+    #[test]
+    fn test_validate_region() {
+        assert!(validate_region(None).is_ok());
+        assert!(validate_region(region(0.1, 0.2, 0.5, 0.5)).is_ok());
+        assert!(validate_region(region(0.0, 0.0, 1.0, 1.0)).is_ok());
+        assert!(validate_region(region(0.6, 0.0, 0.5, 0.5)).is_err());
+        assert!(validate_region(region(0.1, 0.1, 0.0, 0.5)).is_err());
+        assert!(validate_region(region(-0.1, 0.1, 0.2, 0.2)).is_err());
+        assert!(validate_region(region(f64::NAN, 0.1, 0.2, 0.2)).is_err());
+    }
+}
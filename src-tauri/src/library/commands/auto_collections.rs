@@ -0,0 +1,85 @@
+use crate::db::auto_collections::schedule_modifier;
+use crate::db::models::{AutoCollection, AutoCollectionSnapshot, ImageMetadata};
+use crate::db::search::ImageFilter;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use std::sync::Arc;
+use tauri::State;
+
+/// Checks that the filter parses and the schedule is known, returning the
+/// schedule's date modifier.
+fn validate(filter_json: &str, schedule: &str) -> AppResult<Option<&'static str>> {
+    serde_json::from_str::<ImageFilter>(filter_json)
+        .map_err(|e| AppError::Generic(format!("Invalid filter JSON: {}", e)))?;
+    schedule_modifier(schedule).map_err(AppError::Generic)
+}
+
+#[tauri::command]
+pub async fn get_auto_collections(db: State<'_, Arc<Db>>) -> AppResult<Vec<AutoCollection>> {
+    Ok(db.get_auto_collections().await?)
+}
+
+/// Creates an auto-collection from a filter (the camelCase fields of
+/// `explain_filter`). `schedule` is `daily`, `weekly`, `monthly` or `manual`.
+#[tauri::command]
+pub async fn create_auto_collection(
+    db: State<'_, Arc<Db>>,
+    name: String,
+    filter_json: String,
+    schedule: String,
+) -> AppResult<i64> {
+    validate(&filter_json, &schedule)?;
+    Ok(db.create_auto_collection(&name, &filter_json, &schedule).await?)
+}
+
+#[tauri::command]
+pub async fn update_auto_collection(
+    db: State<'_, Arc<Db>>,
+    id: i64,
+    name: String,
+    filter_json: String,
+    schedule: String,
+) -> AppResult<()> {
+    let modifier = validate(&filter_json, &schedule)?;
+    Ok(db.update_auto_collection(id, &name, &filter_json, &schedule, modifier).await?)
+}
+
+#[tauri::command]
+pub async fn delete_auto_collection(db: State<'_, Arc<Db>>, id: i64) -> AppResult<()> {
+    Ok(db.delete_auto_collection(id).await?)
+}
+
+/// Takes a snapshot right away, outside the schedule.
+#[tauri::command]
+pub async fn evaluate_auto_collection(db: State<'_, Arc<Db>>, id: i64) -> AppResult<AutoCollectionSnapshot> {
+    let snapshot_id = crate::library::auto_collections::evaluate(&db, id).await?;
+    db.get_auto_collection_snapshots(id)
+        .await?
+        .into_iter()
+        .find(|s| s.id == snapshot_id)
+        .ok_or_else(|| AppError::Internal("Snapshot vanished after being taken".to_string()))
+}
+
+/// Lists the snapshot history of a collection, newest first.
+#[tauri::command]
+pub async fn get_auto_collection_snapshots(
+    db: State<'_, Arc<Db>>,
+    id: i64,
+) -> AppResult<Vec<AutoCollectionSnapshot>> {
+    Ok(db.get_auto_collection_snapshots(id).await?)
+}
+
+#[tauri::command]
+pub async fn get_auto_collection_snapshot_images(
+    db: State<'_, Arc<Db>>,
+    snapshot_id: i64,
+    limit: i32,
+    offset: i32,
+) -> AppResult<Vec<ImageMetadata>> {
+    Ok(db.get_auto_collection_snapshot_images(snapshot_id, limit, offset).await?)
+}
+
+#[tauri::command]
+pub async fn delete_auto_collection_snapshot(db: State<'_, Arc<Db>>, snapshot_id: i64) -> AppResult<()> {
+    Ok(db.delete_auto_collection_snapshot(snapshot_id).await?)
+}
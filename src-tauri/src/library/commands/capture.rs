@@ -0,0 +1,30 @@
+use crate::db::Db;
+use crate::error::AppResult;
+use crate::library::capture_session::{self, CaptureSession};
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+/// Starts a capture session on `folder`, replacing the running one. Each new
+/// shot is tagged `tag` under `Sessions` (the folder name and day when
+/// missing) and announced with `capture:shot`.
+#[tauri::command]
+pub async fn start_capture_session(
+    folder: String,
+    tag: Option<String>,
+    app: AppHandle,
+    db: State<'_, Arc<Db>>,
+) -> AppResult<CaptureSession> {
+    capture_session::start(&app, db.inner().clone(), &folder, tag).await
+}
+
+/// Stops the running capture session and returns it, if there was one.
+#[tauri::command]
+pub fn stop_capture_session() -> Option<CaptureSession> {
+    capture_session::stop()
+}
+
+/// The running capture session, with its shots so far, if any.
+#[tauri::command]
+pub fn get_capture_session() -> Option<CaptureSession> {
+    capture_session::current()
+}
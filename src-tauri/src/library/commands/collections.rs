@@ -0,0 +1,91 @@
+use crate::db::models::Collection;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use std::sync::Arc;
+use tauri::State;
+
+/// Retrieves a collection, or `NotFound` if there is none with that id.
+async fn get_collection_or_not_found(db: &Db, id: i64) -> AppResult<Collection> {
+    db.get_collection(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Collection {} not found", id)))
+}
+
+/// Creates a collection, optionally holding `image_ids` in that order.
+#[tauri::command]
+pub async fn create_collection(
+    db: State<'_, Arc<Db>>,
+    name: String,
+    description: Option<String>,
+    image_ids: Option<Vec<i64>>,
+) -> AppResult<Collection> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::Generic("Collection name cannot be empty".to_string()));
+    }
+    let id = db
+        .create_collection(name, description.as_deref(), &image_ids.unwrap_or_default())
+        .await?;
+    get_collection_or_not_found(&db, id).await
+}
+
+/// Lists all collections by name.
+#[tauri::command]
+pub async fn get_collections(db: State<'_, Arc<Db>>) -> AppResult<Vec<Collection>> {
+    Ok(db.get_collections().await?)
+}
+
+/// Renames a collection and replaces its description.
+#[tauri::command]
+pub async fn update_collection(
+    db: State<'_, Arc<Db>>,
+    id: i64,
+    name: String,
+    description: Option<String>,
+) -> AppResult<Collection> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::Generic("Collection name cannot be empty".to_string()));
+    }
+    get_collection_or_not_found(&db, id).await?;
+    db.update_collection(id, name, description.as_deref()).await?;
+    get_collection_or_not_found(&db, id).await
+}
+
+/// Deletes a collection; its images stay in the library.
+#[tauri::command]
+pub async fn delete_collection(db: State<'_, Arc<Db>>, id: i64) -> AppResult<()> {
+    Ok(db.delete_collection(id).await?)
+}
+
+/// Appends images to the end of a collection. Returns how many were added.
+#[tauri::command]
+pub async fn add_to_collection(db: State<'_, Arc<Db>>, id: i64, image_ids: Vec<i64>) -> AppResult<u64> {
+    get_collection_or_not_found(&db, id).await?;
+    Ok(db.add_to_collection(id, &image_ids).await?)
+}
+
+/// Takes images out of a collection. Returns how many were removed.
+#[tauri::command]
+pub async fn remove_from_collection(db: State<'_, Arc<Db>>, id: i64, image_ids: Vec<i64>) -> AppResult<u64> {
+    Ok(db.remove_from_collection(id, &image_ids).await?)
+}
+
+/// Moves images right before `before_image_id`, or to the end when it is
+/// omitted, the way a drag and drop in the grid does.
+#[tauri::command]
+pub async fn reorder_collection_items(
+    db: State<'_, Arc<Db>>,
+    id: i64,
+    image_ids: Vec<i64>,
+    before_image_id: Option<i64>,
+) -> AppResult<()> {
+    get_collection_or_not_found(&db, id).await?;
+    Ok(db.reorder_collection_items(id, &image_ids, before_image_id).await?)
+}
+
+/// Lists the collections an image belongs to, by name.
+#[tauri::command]
+pub async fn get_image_collections(db: State<'_, Arc<Db>>, image_id: i64) -> AppResult<Vec<Collection>> {
+    Ok(db.get_image_collections(image_id).await?)
+}
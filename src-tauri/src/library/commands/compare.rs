@@ -0,0 +1,152 @@
+use crate::db::models::ImageMetadata;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::media::metadata_reader;
+use crate::paths;
+use crate::thumbnails::compare::{self, PixelDiff};
+use crate::thumbnails::variants::{MAX_MAXDIM, MIN_MAXDIM};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+/// Default heatmap size, enough for a side-by-side view.
+const DEFAULT_DIFF_MAXDIM: u32 = 2048;
+
+/// One row of the aligned metadata table.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct FieldDiff {
+    /// `file` for library fields, `exif` for EXIF tags.
+    pub group: &'static str,
+    pub field: String,
+    pub a: Option<String>,
+    pub b: Option<String>,
+    pub differs: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImageComparison {
+    pub a: ImageMetadata,
+    pub b: ImageMetadata,
+    /// Library fields first, then the union of both EXIF tag sets by name.
+    pub fields: Vec<FieldDiff>,
+    /// Pixel-difference heatmap, when requested.
+    pub pixel_diff: Option<PixelDiff>,
+}
+
+fn library_fields(image: &ImageMetadata) -> Vec<(&'static str, Option<String>)> {
+    vec![
+        ("filename", Some(image.filename.clone())),
+        ("format", Some(image.format.clone())),
+        ("width", image.width.map(|v| v.to_string())),
+        ("height", image.height.map(|v| v.to_string())),
+        ("size", Some(image.size.to_string())),
+        ("rating", Some(image.rating.to_string())),
+        ("created_at", Some(image.created_at.to_rfc3339())),
+        ("modified_at", Some(image.modified_at.to_rfc3339())),
+        ("notes", image.notes.clone().filter(|n| !n.is_empty())),
+    ]
+}
+
+/// Aligns the library fields and EXIF tags of two images row by row.
+fn align_fields(
+    a: &ImageMetadata,
+    b: &ImageMetadata,
+    exif_a: HashMap<String, String>,
+    exif_b: HashMap<String, String>,
+) -> Vec<FieldDiff> {
+    let mut fields: Vec<FieldDiff> = library_fields(a)
+        .into_iter()
+        .zip(library_fields(b))
+        .map(|((field, a), (_, b))| FieldDiff { group: "file", field: field.to_string(), differs: a != b, a, b })
+        .collect();
+
+    let mut exif: BTreeMap<String, (Option<String>, Option<String>)> = BTreeMap::new();
+    for (key, value) in exif_a {
+        exif.entry(key).or_default().0 = Some(value);
+    }
+    for (key, value) in exif_b {
+        exif.entry(key).or_default().1 = Some(value);
+    }
+    fields.extend(exif.into_iter().map(|(field, (a, b))| FieldDiff { group: "exif", field, differs: a != b, a, b }));
+    fields
+}
+
+/// Compares two images for an A/B view.
+///
+/// Returns both records and an aligned table of library fields and EXIF tags.
+/// With `pixel_diff`, `b` is also resampled onto `a` and a difference heatmap
+/// no larger than `maxdim` is rendered into the preview cache.
+#[tauri::command]
+pub async fn compare_images(
+    app: AppHandle,
+    db: State<'_, Arc<Db>>,
+    id_a: i64,
+    id_b: i64,
+    pixel_diff: Option<bool>,
+    maxdim: Option<u32>,
+) -> AppResult<ImageComparison> {
+    let images = db.get_images_by_ids(&[id_a, id_b]).await?;
+    let find = |id: i64| {
+        images
+            .iter()
+            .find(|image| image.id == id)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Image {} not found", id)))
+    };
+    let (a, b) = (find(id_a)?, find(id_b)?);
+
+    let (path_a, path_b) = (paths::from_db(&a.path), paths::from_db(&b.path));
+    let want_diff = pixel_diff.unwrap_or(false);
+    let maxdim = maxdim.unwrap_or(DEFAULT_DIFF_MAXDIM).clamp(MIN_MAXDIM, MAX_MAXDIM);
+
+    let (exif_a, exif_b, pixel_diff) = tauri::async_runtime::spawn_blocking(move || {
+        let exif_a = metadata_reader::read_exif(&path_a);
+        let exif_b = metadata_reader::read_exif(&path_b);
+        let pixel_diff = if want_diff {
+            Some(compare::get_or_render_diff(&app, &path_a, &path_b, maxdim).map_err(|e| e.to_string())?)
+        } else {
+            None
+        };
+        Ok::<_, String>((exif_a, exif_b, pixel_diff))
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+    .map_err(AppError::Image)?;
+
+    let fields = align_fields(&a, &b, exif_a, exif_b);
+    Ok(ImageComparison { a, b, fields, pixel_diff })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(id: i64, width: i32, rating: i32) -> ImageMetadata {
+        serde_json::from_value(serde_json::json!({
+            "id": id, "path": format!("/tmp/{}.png", id), "filename": format!("{}.png", id),
+            "width": width, "height": 100, "size": 1000, "format": "png", "thumbnail_path": null,
+            "rating": rating, "notes": null, "playback_completed": false,
+            "modified_at": "2024-01-01T00:00:00Z", "created_at": "2024-01-01T00:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_align_fields() {
+        let exif_a = HashMap::from([("Model".to_string(), "X100".to_string()), ("ISO".to_string(), "200".to_string())]);
+        let exif_b = HashMap::from([("ISO".to_string(), "400".to_string())]);
+        let fields = align_fields(&image(1, 200, 3), &image(2, 200, 5), exif_a, exif_b);
+
+        let get = |name: &str| fields.iter().find(|f| f.field == name).unwrap();
+        assert!(!get("width").differs);
+        assert!(get("rating").differs);
+        assert!(!get("notes").differs);
+        assert_eq!(get("Model").b, None);
+        assert!(get("Model").differs);
+        assert_eq!((get("ISO").a.as_deref(), get("ISO").b.as_deref()), (Some("200"), Some("400")));
+        // EXIF rows come after the library fields, sorted by tag name
+        let exif: Vec<&str> = fields.iter().filter(|f| f.group == "exif").map(|f| f.field.as_str()).collect();
+        assert_eq!(exif, vec!["ISO", "Model"]);
+    }
+}
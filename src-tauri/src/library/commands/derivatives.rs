@@ -0,0 +1,54 @@
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::library::derivatives::{self, Derivative, DerivativeCleanup, DerivativeKind};
+use std::sync::Arc;
+use tauri::State;
+
+/// Lists the files derived from an image: proxies, exports, transcodes and
+/// viewer conversions, newest first.
+#[tauri::command]
+pub async fn get_derivatives(db: State<'_, Arc<Db>>, image_id: i64) -> AppResult<Vec<Derivative>> {
+    Ok(derivatives::list(&db, Some(image_id)).await?)
+}
+
+/// Links a file made elsewhere (an export from another app) to its source.
+#[tauri::command]
+pub async fn link_derivative(
+    db: State<'_, Arc<Db>>,
+    image_id: i64,
+    path: String,
+    kind: DerivativeKind,
+    label: Option<String>,
+) -> AppResult<i64> {
+    if !kind.is_linkable() {
+        return Err(AppError::Generic(format!("{} files are recorded by Mundam, not linked", kind.as_str())));
+    }
+    let file = &crate::paths::from_db(&path);
+    if !file.is_absolute() || !file.is_file() {
+        return Err(AppError::NotFound(path));
+    }
+    let label = label.as_deref().map(str::trim).filter(|label| !label.is_empty());
+    Ok(derivatives::record(&db, image_id, kind, file, label).await?)
+}
+
+/// Forgets a derivative, deleting its file too when `delete_file` is set.
+#[tauri::command]
+pub async fn delete_derivative(db: State<'_, Arc<Db>>, id: i64, delete_file: bool) -> AppResult<()> {
+    let derivative = db
+        .get_derivative(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Derivative {}", id)))?;
+    let file = crate::paths::from_db(&derivative.path);
+    if delete_file && file.exists() {
+        std::fs::remove_file(&file)?;
+    }
+    db.delete_derivatives(&[id]).await?;
+    Ok(())
+}
+
+/// Deletes the transcodes and viewer conversions of an image, or of every
+/// image, and forgets derivatives whose file is gone.
+#[tauri::command]
+pub async fn clean_up_derivatives(db: State<'_, Arc<Db>>, image_id: Option<i64>) -> AppResult<DerivativeCleanup> {
+    Ok(derivatives::clean_up(&db, image_id).await?)
+}
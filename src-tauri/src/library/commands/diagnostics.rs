@@ -0,0 +1,74 @@
+use crate::db::Db;
+use crate::db::models::{CorruptFile, ExtensionMismatch};
+use crate::db::search::{FilterExplanation, ImageFilter};
+use crate::error::{AppError, AppResult};
+use crate::indexer::echo;
+use crate::paths;
+use std::sync::Arc;
+use tauri::State;
+
+/// Lists files flagged as corrupt by the thumbnail worker, with the decoder error.
+#[tauri::command]
+pub async fn get_corrupt_files(db: State<'_, Arc<Db>>) -> AppResult<Vec<CorruptFile>> {
+    Ok(db.get_corrupt_files().await?)
+}
+
+/// Lists files whose extension does not match the format found in their header.
+#[tauri::command]
+pub async fn get_mismatched_extensions(db: State<'_, Arc<Db>>) -> AppResult<Vec<ExtensionMismatch>> {
+    Ok(db.get_mismatched_extensions().await?)
+}
+
+/// Renames a file so its extension matches its real format.
+///
+/// Returns the new absolute path.
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the image has no recorded mismatch and
+/// `AppError::Generic` if a file already exists at the corrected path.
+#[tauri::command]
+pub async fn fix_extension(db: State<'_, Arc<Db>>, id: i64) -> AppResult<String> {
+    let mismatch = db
+        .get_extension_mismatch(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No extension mismatch recorded for image {}", id)))?;
+
+    let old_path = paths::from_db(&mismatch.path);
+    let new_path = old_path.with_extension(&mismatch.detected_format);
+    if tokio::fs::try_exists(&new_path).await.unwrap_or(true) {
+        return Err(AppError::Generic(format!(
+            "Cannot fix extension, target already exists: {}",
+            new_path.display()
+        )));
+    }
+
+    let new_path_str = paths::to_db(&new_path);
+    echo::expect_changes([mismatch.path.clone(), new_path_str.clone()]);
+    tokio::fs::rename(&old_path, &new_path).await?;
+
+    let new_filename = new_path
+        .file_name()
+        .map(paths::name_to_db)
+        .unwrap_or_default();
+
+    if let Err(e) = db
+        .apply_extension_fix(id, &new_path_str, &new_filename, &mismatch.detected_format)
+        .await
+    {
+        // Keep disk and DB consistent if the update failed
+        tokio::fs::rename(&new_path, &old_path).await.ok();
+        return Err(e.into());
+    }
+
+    Ok(new_path_str)
+}
+
+/// Developer tool: returns the SQL, query plan and timing for a grid filter.
+///
+/// `filter_json` uses the same fields as `get_images_filtered` in camelCase.
+#[tauri::command]
+pub async fn explain_filter(db: State<'_, Arc<Db>>, filter_json: String) -> AppResult<FilterExplanation> {
+    let filter: ImageFilter = serde_json::from_str(&filter_json)
+        .map_err(|e| AppError::Generic(format!("Invalid filter JSON: {}", e)))?;
+    Ok(db.explain_filter(&filter).await?)
+}
@@ -0,0 +1,38 @@
+use crate::db::Db;
+use crate::error::AppResult;
+use crate::library::duplicates::{self, DuplicateAction, DuplicateGroup, DuplicateResolution, DuplicateSet};
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+/// Every group of identical files in the library, across all its locations,
+/// biggest waste first. Files are hashed in the background, so groups appear
+/// as hashing progresses.
+#[tauri::command]
+pub async fn find_duplicates(db: State<'_, Arc<Db>>) -> AppResult<Vec<DuplicateSet>> {
+    duplicates::find_duplicates(&db).await
+}
+
+/// Group `index` (from 0) of the library's duplicates, biggest waste first,
+/// with the quality signals of each copy and a suggested keeper. `None`
+/// past the last group.
+#[tauri::command]
+pub async fn get_duplicate_group(db: State<'_, Arc<Db>>, index: usize) -> AppResult<Option<DuplicateGroup>> {
+    duplicates::get_group(&db, index).await
+}
+
+/// Keeps `keep_id` and moves the other copies of its group to the trash, or
+/// to `destination` with `action: "move"`. The keeper takes their tags,
+/// annotations, rating and notes.
+#[tauri::command]
+pub async fn resolve_duplicates(
+    app: AppHandle,
+    db: State<'_, Arc<Db>>,
+    keep_id: i64,
+    action: DuplicateAction,
+    destination: Option<String>,
+) -> AppResult<DuplicateResolution> {
+    let result = duplicates::resolve(&app, &db, keep_id, action, destination.as_deref().map(Path::new)).await?;
+    crate::library::filter_subscriptions::notify_changes(&app);
+    Ok(result)
+}
@@ -0,0 +1,198 @@
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::library::estimate::{
+    average_by_format, estimate_transcode_bytes, has_enough_space, DB_BYTES_PER_FILE, DEFAULT_THUMBNAIL_BYTES,
+};
+use crate::formats::{MediaType, PlaybackStrategy, SUPPORTED_FORMATS};
+use crate::transcoding::cache::TranscodeCache;
+use crate::transcoding::quality::TranscodeQuality;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
+
+/// Thumbnails sampled per format to compute the average thumbnail size.
+const THUMBNAIL_SAMPLES_PER_FORMAT: i64 = 50;
+
+/// What an estimate is computed for.
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OperationScope {
+    /// Folder to estimate for; the whole library when absent.
+    pub folder_id: Option<i64>,
+    pub recursive: bool,
+    /// Directory to import, for the `import` operation.
+    pub path: Option<String>,
+    /// Transcoding quality, for the `transcode` operation.
+    pub quality: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FormatEstimate {
+    pub format: String,
+    pub file_count: usize,
+    pub estimated_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OperationEstimate {
+    pub op: String,
+    pub file_count: usize,
+    pub estimated_bytes: u64,
+    /// Directory the output is written to.
+    pub target_dir: String,
+    /// Free space on the target volume, if it could be read.
+    pub available_bytes: Option<u64>,
+    /// `false` when the target volume is likely to run out of space.
+    pub sufficient: bool,
+    pub by_format: Vec<FormatEstimate>,
+}
+
+/// Predicts the disk space needed by a heavy operation before starting it.
+///
+/// `op` is one of `thumbnails` (generate missing thumbnails), `transcode`
+/// (transcode every non-native audio/video file) or `import` (index a new
+/// directory given in `scope.path`).
+#[tauri::command]
+pub async fn estimate_operation_size(
+    app: AppHandle,
+    db: State<'_, Arc<Db>>,
+    op: String,
+    scope: Option<OperationScope>,
+) -> AppResult<OperationEstimate> {
+    let scope = scope.unwrap_or_default();
+    let app_data = app.path().app_local_data_dir()?;
+    let thumbnails_dir = app_data.join("thumbnails");
+
+    let (target_dir, per_format) = match op.as_str() {
+        "thumbnails" => {
+            let averages = thumbnail_averages(&db, &thumbnails_dir).await?;
+            let missing = db.count_missing_thumbnails_by_format(scope.folder_id, scope.recursive).await?;
+            let mut per_format: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+            for (format, count) in missing {
+                let format = format.unwrap_or_default();
+                let bytes = averages.get(&format.to_lowercase()).copied().unwrap_or(DEFAULT_THUMBNAIL_BYTES);
+                let entry = per_format.entry(format).or_default();
+                entry.0 += count.max(0) as usize;
+                entry.1 += bytes * count.max(0) as u64;
+            }
+            (thumbnails_dir, per_format)
+        }
+        "transcode" => {
+            let quality = scope.quality.as_deref().and_then(TranscodeQuality::from_str).unwrap_or_default();
+            let formats = transcode_formats();
+            let extensions: Vec<&str> = formats.keys().copied().collect();
+            let files = db.get_scope_files_of_formats(scope.folder_id, scope.recursive, &extensions).await?;
+            let cache = TranscodeCache::new(&app_data);
+            let target_dir = cache.dir().to_path_buf();
+            let per_format = tauri::async_runtime::spawn_blocking(move || {
+                let mut per_format: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+                for (path, format, size, duration) in files {
+                    let format = format.unwrap_or_default();
+                    let is_video = formats.get(format.as_str()).copied().unwrap_or(false);
+                    if cache.exists(&crate::paths::from_db(&path), quality) {
+                        continue;
+                    }
+                    let bytes = estimate_transcode_bytes(duration, size.unwrap_or(0).max(0) as u64, is_video, quality);
+                    let entry = per_format.entry(format).or_default();
+                    entry.0 += 1;
+                    entry.1 += bytes;
+                }
+                per_format
+            })
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+            (target_dir, per_format)
+        }
+        "import" => {
+            let root = scope
+                .path
+                .clone()
+                .ok_or_else(|| AppError::Generic("The import estimate needs a path".to_string()))?;
+            let averages = thumbnail_averages(&db, &thumbnails_dir).await?;
+            let per_format = tauri::async_runtime::spawn_blocking(move || {
+                let mut per_format: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+                for entry in walkdir::WalkDir::new(&root).into_iter().filter_map(|e| e.ok()) {
+                    let path = entry.path();
+                    if !entry.file_type().is_file() || !crate::formats::FileFormat::is_supported_extension(path) {
+                        continue;
+                    }
+                    let format = path
+                        .extension()
+                        .map(|e| e.to_string_lossy().to_lowercase())
+                        .unwrap_or_default();
+                    let bytes = averages.get(&format).copied().unwrap_or(DEFAULT_THUMBNAIL_BYTES) + DB_BYTES_PER_FILE;
+                    let slot = per_format.entry(format).or_default();
+                    slot.0 += 1;
+                    slot.1 += bytes;
+                }
+                per_format
+            })
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+            (app_data.clone(), per_format)
+        }
+        _ => return Err(AppError::Generic(format!("Unknown operation: {}", op))),
+    };
+
+    let estimated_bytes = per_format.values().map(|(_, bytes)| bytes).sum();
+    let file_count = per_format.values().map(|(count, _)| count).sum();
+    let available_bytes = available_space(&target_dir);
+
+    Ok(OperationEstimate {
+        op,
+        file_count,
+        estimated_bytes,
+        target_dir: target_dir.to_string_lossy().to_string(),
+        available_bytes,
+        sufficient: available_bytes.map_or(true, |free| has_enough_space(estimated_bytes, free)),
+        by_format: per_format
+            .into_iter()
+            .map(|(format, (file_count, estimated_bytes))| FormatEstimate { format, file_count, estimated_bytes })
+            .collect(),
+    })
+}
+
+/// Extensions of the audio and video formats played through a transcode,
+/// each with whether it is a video.
+fn transcode_formats() -> HashMap<&'static str, bool> {
+    SUPPORTED_FORMATS
+        .iter()
+        .filter(|format| !matches!(format.playback, PlaybackStrategy::Native))
+        .filter(|format| matches!(format.type_category, MediaType::Audio | MediaType::Video))
+        .flat_map(|format| {
+            let is_video = matches!(format.type_category, MediaType::Video);
+            format.extensions.iter().map(move |extension| (*extension, is_video))
+        })
+        .collect()
+}
+
+/// Average size of existing thumbnails, per source format.
+async fn thumbnail_averages(db: &Db, thumbnails_dir: &Path) -> AppResult<HashMap<String, u64>> {
+    let samples = db.sample_thumbnails_by_format(THUMBNAIL_SAMPLES_PER_FORMAT).await?;
+    let thumbnails_dir = thumbnails_dir.to_path_buf();
+    let sized = tauri::async_runtime::spawn_blocking(move || {
+        samples
+            .into_iter()
+            .filter_map(|(format, filename)| {
+                std::fs::metadata(thumbnails_dir.join(filename)).ok().map(|m| (format, m.len()))
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(average_by_format(&sized))
+}
+
+/// Free space on the volume holding `dir`, walking up to the nearest existing ancestor.
+fn available_space(dir: &Path) -> Option<u64> {
+    let mut current: Option<PathBuf> = Some(dir.to_path_buf());
+    while let Some(path) = current {
+        if path.exists() {
+            return fs2::available_space(&path).ok();
+        }
+        current = path.parent().map(Path::to_path_buf);
+    }
+    None
+}
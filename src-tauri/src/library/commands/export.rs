@@ -0,0 +1,109 @@
+use crate::db::Db;
+use crate::db::models::{MetadataBundle, MetadataBundleEntry};
+use crate::db::search::ImageFilter;
+use crate::error::{AppError, AppResult};
+use crate::library::catalog_export::{self, ExportFormat};
+use crate::library::catalog_import::{self, CsvImportReport, MatchBy};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tauri::State;
+
+/// Current version of the metadata bundle format.
+const METADATA_BUNDLE_VERSION: u32 = 1;
+
+/// Writes a JSON bundle with the metadata, tags, annotations and links of
+/// the given images to `output_path`.
+///
+/// Returns the number of exported images.
+#[tauri::command]
+pub async fn export_metadata_bundle(
+    db: State<'_, Arc<Db>>,
+    image_ids: Vec<i64>,
+    output_path: String,
+) -> AppResult<usize> {
+    write_metadata_bundle(&db, &image_ids, &output_path).await
+}
+
+/// Exports the metadata of the images matching a grid filter (`filter_json`,
+/// camelCase as in `explain_filter`) to a CSV, JSON or Parquet file, in grid
+/// order. `columns` picks and orders the columns (`id`, `path`, `filename`,
+/// `folder`, `format`, `width`, `height`, `size`, `rating`, `notes`, `tags`,
+/// `created_at`, `modified_at`, `added_at`, `duration`, `video_codec`,
+/// `audio_codec`, `latitude`, `longitude`, `stock_provider`, or
+/// `field:<name>` for a custom field); all, with every custom field, by
+/// default.
+///
+/// Returns the number of exported images.
+#[tauri::command]
+pub async fn export_metadata(
+    db: State<'_, Arc<Db>>,
+    filter_json: String,
+    format: ExportFormat,
+    columns: Option<Vec<String>>,
+    output_path: String,
+) -> AppResult<usize> {
+    let filter: ImageFilter = serde_json::from_str(&filter_json)
+        .map_err(|e| AppError::Generic(format!("Invalid filter JSON: {}", e)))?;
+    catalog_export::export(&db, &filter, format, columns.as_deref(), Path::new(&output_path)).await
+}
+
+/// Applies metadata from a CSV file, such as an edited `export_metadata`
+/// CSV. Rows are matched to images by `key_column`, read as `match_by`
+/// (`path` by default, `id` or `hash`, the full content hash), and `mapping`
+/// maps CSV columns to `rating`, `notes`, `tags` (`;` separated, replacing
+/// the image's tags), `latitude`, `longitude` or `field:<name>` (a custom
+/// field). Empty cells change nothing.
+///
+/// With `dry_run` nothing is written; the report is the same either way.
+#[tauri::command]
+pub async fn import_metadata_csv(
+    db: State<'_, Arc<Db>>,
+    path: String,
+    key_column: String,
+    match_by: Option<MatchBy>,
+    mapping: HashMap<String, String>,
+    dry_run: Option<bool>,
+) -> AppResult<CsvImportReport> {
+    catalog_import::import(
+        &db,
+        Path::new(&path),
+        &key_column,
+        match_by.unwrap_or_default(),
+        &mapping,
+        dry_run.unwrap_or(false),
+    )
+    .await
+}
+
+/// Body of `export_metadata_bundle`, shared with the `export_metadata` job.
+pub async fn write_metadata_bundle(db: &Db, image_ids: &[i64], output_path: &str) -> AppResult<usize> {
+    let images = db.get_images_by_ids(image_ids).await?;
+
+    let mut tags = db.get_tag_names_by_image(image_ids).await?;
+    let mut annotations = db.get_annotations_by_image(image_ids).await?;
+    let mut links = db.get_links_by_image(image_ids).await?;
+
+    let entries: Vec<MetadataBundleEntry> = images
+        .into_iter()
+        .map(|image| MetadataBundleEntry {
+            tags: tags.remove(&image.id).unwrap_or_default(),
+            annotations: annotations.remove(&image.id).unwrap_or_default(),
+            links: links.remove(&image.id).unwrap_or_default(),
+            image,
+        })
+        .collect();
+
+    let count = entries.len();
+    let bundle = MetadataBundle {
+        version: METADATA_BUNDLE_VERSION,
+        exported_at: chrono::Utc::now(),
+        images: entries,
+    };
+
+    let json = serde_json::to_vec_pretty(&bundle)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize metadata bundle: {}", e)))?;
+    tokio::fs::write(output_path, json).await?;
+
+    Ok(count)
+}
@@ -1,10 +1,10 @@
 use crate::db::Db;
 use crate::error::{AppError, AppResult};
 use crate::indexer::Indexer;
-use serde::Serialize;
-use std::path::PathBuf;
+use crate::paths;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tauri::{AppHandle, Manager, State};
+use tauri::{AppHandle, Emitter, Manager, State};
 
 #[derive(Serialize)]
 pub struct FolderNode {
@@ -23,8 +23,13 @@ pub async fn add_location(
     db: State<'_, Arc<Db>>,
 ) -> AppResult<FolderNode> {
     println!("COMMAND: add_location (add_root) called with path: {}", path);
+    register_location(&app, &db, path).await
+}
 
-    let root = PathBuf::from(&path);
+/// Body of `add_location`, shared with importers that add the folder they
+/// imported into.
+pub async fn register_location(app: &AppHandle, db: &Db, path: String) -> AppResult<FolderNode> {
+    let root = paths::from_db(&path);
 
     // Validate path exists and is a directory
     if !root.exists() {
@@ -38,7 +43,7 @@ pub async fn add_location(
     let mut parent_id = None;
     let mut current = root.parent();
     while let Some(p) = current {
-        let p_str = p.to_string_lossy().to_string();
+        let p_str = paths::to_db(p);
         if let Some(id) = db.get_folder_by_path(&p_str).await? {
             parent_id = Some(id);
             break;
@@ -68,7 +73,7 @@ pub async fn add_location(
     let registry = app.try_state::<Arc<tokio::sync::Mutex<crate::indexer::WatcherRegistry>>>()
         .ok_or_else(|| AppError::Internal("Registry not initialized".to_string()))?;
 
-    let indexer = Indexer::new(app.clone(), db.inner(), registry.inner().clone());
+    let indexer = Indexer::new(app.clone(), db, registry.inner().clone());
     tokio::spawn(async move {
         indexer.start_scan(root).await;
     });
@@ -152,6 +157,45 @@ pub async fn get_locations(
         .collect())
 }
 
+/// Reports how completely a location is ingested: files, size, unreadable
+/// files, last scan, watcher and thumbnail coverage.
+#[tauri::command]
+pub async fn get_location_health(
+    location_id: i64,
+    app: AppHandle,
+    db: State<'_, Arc<Db>>,
+) -> AppResult<crate::db::models::LocationHealth> {
+    let location_path = db.get_folder_path(location_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Folder not found: {}", location_id)))?;
+
+    let (file_count, total_size, thumbnail_count, corrupt_count) = db.get_location_file_stats(location_id).await?;
+    let last_scan = db.get_location_scan(location_id).await?;
+
+    let registry = app.try_state::<Arc<tokio::sync::Mutex<crate::indexer::WatcherRegistry>>>()
+        .ok_or_else(|| AppError::Internal("Registry not initialized".to_string()))?;
+    let indexer = Indexer::new(app.clone(), db.inner(), registry.inner().clone());
+    let watcher_active = indexer.is_watching(&location_path).await;
+
+    let thumbnail_coverage = if file_count > 0 {
+        thumbnail_count as f64 * 100.0 / file_count as f64
+    } else {
+        100.0
+    };
+
+    Ok(crate::db::models::LocationHealth {
+        location_id,
+        file_count,
+        total_size,
+        unreadable_count: last_scan.map(|(_, _, unreadable)| unreadable).unwrap_or(0),
+        corrupt_count,
+        last_scan_at: last_scan.map(|(started_at, _, _)| started_at),
+        last_scan_duration_ms: last_scan.map(|(_, duration, _)| duration),
+        watcher_active,
+        thumbnail_count,
+        thumbnail_coverage,
+    })
+}
+
 // Deprecated or Aliased commands for compatibility if needed (but we are refactoring frontend)
 
 #[tauri::command]
@@ -174,3 +218,147 @@ pub async fn get_location_root_counts(
 ) -> AppResult<Vec<(i64, i64)>> {
     Ok(vec![])
 }
+
+/// Tags a folder gives to every new file indexed under it, subfolders included.
+/// Inherited defaults of parent folders are not listed.
+#[tauri::command]
+pub async fn get_folder_default_tags(
+    folder_id: i64,
+    db: State<'_, Arc<Db>>,
+) -> AppResult<Vec<crate::db::models::Tag>> {
+    Ok(db.get_folder_default_tags(folder_id).await?)
+}
+
+/// Replaces the default tags of a folder. Files already in the library keep
+/// their tags; only files indexed from now on receive the new defaults.
+#[tauri::command]
+pub async fn set_folder_default_tags(
+    folder_id: i64,
+    tag_ids: Vec<i64>,
+    db: State<'_, Arc<Db>>,
+) -> AppResult<()> {
+    if db.get_folder_path(folder_id).await?.is_none() {
+        return Err(AppError::NotFound(format!("Folder {} not found", folder_id)));
+    }
+    Ok(db.set_folder_default_tags(folder_id, &tag_ids).await?)
+}
+
+/// Merges folder `source_id` into `target_id`: files and subfolders move
+/// over, identical files are merged into one image and the emptied source is
+/// removed (see `library::merge`). With `dry_run`, only returns the plan.
+#[tauri::command]
+pub async fn merge_folders(
+    app: AppHandle,
+    db: State<'_, Arc<Db>>,
+    source_id: i64,
+    target_id: i64,
+    dry_run: Option<bool>,
+) -> AppResult<crate::library::merge::FolderMergeResult> {
+    crate::library::merge::merge(&app, &db, source_id, target_id, dry_run.unwrap_or(false)).await
+}
+
+/// A folder structure to create inside a location.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectTemplate {
+    /// Name of the project folder created in the location.
+    pub name: String,
+    /// Subfolders, relative to the project folder (e.g. `02_WIP/Renders`).
+    pub folders: Vec<TemplateFolder>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateFolder {
+    pub path: String,
+    /// Tags applied to every file later added under this subfolder.
+    #[serde(default)]
+    pub tag_ids: Vec<i64>,
+}
+
+/// Normalizes a template path to `a/b/c`, rejecting anything that could
+/// escape the project folder.
+fn normalize_template_path(path: &str) -> AppResult<String> {
+    let segments: Vec<&str> = path
+        .split(['/', '\\'])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    let invalid = segments.is_empty()
+        || path.starts_with('/')
+        || segments.iter().any(|s| *s == "." || *s == ".." || s.contains(':'));
+    if invalid {
+        return Err(AppError::Generic(format!("Invalid template folder: '{}'", path)));
+    }
+    Ok(segments.join("/"))
+}
+
+/// Creates a project folder from a template inside a location.
+///
+/// The folders are registered in the library right away, so they show up
+/// before the watcher notices them. Returns the id of the project folder.
+#[tauri::command]
+pub async fn create_project_from_template(
+    app: AppHandle,
+    db: State<'_, Arc<Db>>,
+    location_id: i64,
+    template: ProjectTemplate,
+) -> AppResult<i64> {
+    let location_path = db.get_folder_path(location_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Folder not found: {}", location_id)))?;
+
+    let project_name = normalize_template_path(&template.name)?;
+    if project_name.contains('/') {
+        return Err(AppError::Generic(format!("Invalid project name: '{}'", template.name)));
+    }
+
+    let project_path = paths::from_db(&location_path).join(&project_name);
+    if tokio::fs::try_exists(&project_path).await.unwrap_or(true) {
+        return Err(AppError::Generic(format!("Folder already exists: {}", project_path.display())));
+    }
+
+    // Validate everything before touching the disk
+    let mut folders = Vec::with_capacity(template.folders.len());
+    for folder in &template.folders {
+        let relative = normalize_template_path(&folder.path)?;
+        let path = relative.split('/').fold(project_path.clone(), |path, part| path.join(part));
+        folders.push((path, &folder.tag_ids));
+    }
+
+    tokio::fs::create_dir_all(&project_path).await?;
+    for (path, _) in &folders {
+        tokio::fs::create_dir_all(path).await?;
+    }
+
+    let project_id = db.ensure_folder_hierarchy(&paths::to_db(&project_path)).await?;
+    for (path, tag_ids) in &folders {
+        let folder_id = db.ensure_folder_hierarchy(&paths::to_db(path)).await?;
+        if !tag_ids.is_empty() {
+            db.set_folder_default_tags(folder_id, tag_ids).await?;
+        }
+    }
+
+    println!("INFO: Created project {} with {} folders", project_path.display(), folders.len());
+    let _ = app.emit("library:batch-change", crate::indexer::BatchChangePayload {
+        added: vec![], removed: vec![], updated: vec![], needs_refresh: true
+    });
+
+    Ok(project_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_template_path() {
+        assert_eq!(normalize_template_path("01_Brief").unwrap(), "01_Brief");
+        assert_eq!(normalize_template_path("02_WIP//Renders/").unwrap(), "02_WIP/Renders");
+        assert_eq!(normalize_template_path("03_Final\\Print").unwrap(), "03_Final/Print");
+        assert!(normalize_template_path("../outside").is_err());
+        assert!(normalize_template_path("/absolute").is_err());
+        assert!(normalize_template_path("C:/x").is_err());
+        assert!(normalize_template_path("  ").is_err());
+    }
+}
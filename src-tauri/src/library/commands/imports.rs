@@ -0,0 +1,16 @@
+use crate::library::apple_photos;
+use crate::library::ingest::{self, ImportSource};
+
+/// The current user's Photos library, if any, to prefill the importer.
+/// Imports themselves run as `import_apple_photos` jobs.
+#[tauri::command]
+pub fn find_apple_photos_library() -> Option<String> {
+    apple_photos::default_library_path().map(|p| p.to_string_lossy().to_string())
+}
+
+/// Memory cards mounted now. Cards that appear later are announced with
+/// `import:source-detected`; they are copied by `ingest_card` jobs.
+#[tauri::command]
+pub fn get_import_sources() -> Vec<ImportSource> {
+    ingest::sources()
+}
@@ -0,0 +1,85 @@
+use crate::db::Db;
+use crate::db::models::AssetLink;
+use crate::error::{AppError, AppResult};
+use std::sync::Arc;
+use tauri::State;
+
+/// Accepts URLs with a scheme (`https:`, `figma:`, `file:`...) and absolute paths.
+fn validate_link_target(url: &str) -> AppResult<String> {
+    let url = url.trim();
+    let has_scheme = url
+        .split_once(':')
+        .map(|(scheme, _)| {
+            // Exclude Windows drive letters such as `C:`
+            scheme.len() > 1
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        })
+        .unwrap_or(false);
+
+    if has_scheme || std::path::Path::new(url).is_absolute() {
+        Ok(url.to_string())
+    } else {
+        Err(AppError::Generic(format!("Invalid link target: '{}'", url)))
+    }
+}
+
+/// Attaches a link to exactly one of an image or a folder.
+#[tauri::command]
+pub async fn add_link(
+    db: State<'_, Arc<Db>>,
+    image_id: Option<i64>,
+    folder_id: Option<i64>,
+    label: String,
+    url: String,
+) -> AppResult<i64> {
+    if image_id.is_some() == folder_id.is_some() {
+        return Err(AppError::Generic("A link must target either an image or a folder".to_string()));
+    }
+    let url = validate_link_target(&url)?;
+    Ok(db.add_link(image_id, folder_id, label.trim(), &url).await?)
+}
+
+#[tauri::command]
+pub async fn update_link(db: State<'_, Arc<Db>>, id: i64, label: String, url: String) -> AppResult<()> {
+    let url = validate_link_target(&url)?;
+    if !db.update_link(id, label.trim(), &url).await? {
+        return Err(AppError::NotFound(format!("Link {} not found", id)));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_link(db: State<'_, Arc<Db>>, id: i64) -> AppResult<()> {
+    Ok(db.delete_link(id).await?)
+}
+
+/// Lists an image's links, including those inherited from its folders.
+#[tauri::command]
+pub async fn get_image_links(db: State<'_, Arc<Db>>, image_id: i64) -> AppResult<Vec<AssetLink>> {
+    Ok(db.get_image_links(image_id).await?)
+}
+
+#[tauri::command]
+pub async fn get_folder_links(db: State<'_, Arc<Db>>, folder_id: i64) -> AppResult<Vec<AssetLink>> {
+    Ok(db.get_folder_links(folder_id).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_link_target() {
+        assert!(validate_link_target("https://www.figma.com/file/abc").is_ok());
+        assert!(validate_link_target("mailto:client@example.com").is_ok());
+        assert!(validate_link_target("file:///Volumes/Clients/Acme").is_ok());
+        assert_eq!(validate_link_target("  https://x.y  ").unwrap(), "https://x.y");
+        assert!(validate_link_target("relative/folder").is_err());
+        assert!(validate_link_target("").is_err());
+        #[cfg(unix)]
+        assert!(validate_link_target("/Volumes/Clients/Acme").is_ok());
+        #[cfg(windows)]
+        assert!(validate_link_target(r"C:\Clients\Acme").is_ok());
+    }
+}
@@ -0,0 +1,9 @@
+use crate::library::lock::LockStatus;
+use tauri::State;
+
+/// Whether this instance writes the library. When another instance holds
+/// the lock, the frontend offers to keep browsing read-only or to quit.
+#[tauri::command]
+pub fn get_library_lock_status(status: State<'_, LockStatus>) -> LockStatus {
+    status.inner().clone()
+}
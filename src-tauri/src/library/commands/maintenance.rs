@@ -0,0 +1,94 @@
+use crate::db::maintenance::{normalize_prefix, replace_prefix};
+use crate::db::models::{NotesReplacement, PathPrefixReplacement};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::indexer::capture_times::CaptureTimeRepair;
+use crate::indexer::Indexer;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
+
+/// Rewrites the start of every stored path, for drive letter or mount point
+/// changes that span several locations (`D:` to `E:`, `/Volumes/Old` to `/mnt/new`).
+///
+/// Only whole path components match. With `dry_run` the counts and a sample
+/// are returned without writing anything. Watchers of the affected locations
+/// are moved to their new paths.
+///
+/// # Errors
+/// Returns `AppError::Generic` for an empty prefix and when a rewritten path
+/// already exists in the library; nothing is changed in that case.
+#[tauri::command]
+pub async fn replace_path_prefix(
+    app: AppHandle,
+    db: State<'_, Arc<Db>>,
+    old_prefix: String,
+    new_prefix: String,
+    dry_run: Option<bool>,
+) -> AppResult<PathPrefixReplacement> {
+    let old = normalize_prefix(&old_prefix);
+    let new = normalize_prefix(&new_prefix);
+    if old.is_empty() || new.is_empty() {
+        return Err(AppError::Generic("Path prefixes can't be empty or a filesystem root".to_string()));
+    }
+    let dry_run = dry_run.unwrap_or(false);
+
+    let moved_roots: Vec<(String, String)> = db
+        .get_all_root_folders()
+        .await?
+        .into_iter()
+        .filter_map(|(_, path)| replace_prefix(&path, old, new).map(|new_path| (path, new_path)))
+        .collect();
+
+    let result = db.replace_path_prefix(old, new, dry_run).await.map_err(|e| match e.as_database_error() {
+        // SQLITE_CONSTRAINT_UNIQUE
+        Some(db_err) if db_err.code().as_deref() == Some("2067") => {
+            AppError::Generic(format!("A path under '{}' already exists in the library", new))
+        }
+        _ => AppError::Db(e),
+    })?;
+    if let Some(path) = &result.conflict {
+        return Err(AppError::Generic(format!("'{}' already exists in the library", path)));
+    }
+
+    if result.applied && !moved_roots.is_empty() {
+        let registry = app.try_state::<Arc<tokio::sync::Mutex<crate::indexer::WatcherRegistry>>>()
+            .ok_or_else(|| AppError::Internal("Registry not initialized".to_string()))?;
+        let indexer = Indexer::new(app.clone(), db.inner(), registry.inner().clone());
+
+        for (old_path, new_path) in moved_roots {
+            println!("INFO: Location moved from '{}' to '{}'", old_path, new_path);
+            indexer.stop_watcher(&old_path).await;
+            indexer.start_scan(std::path::PathBuf::from(new_path)).await;
+        }
+    }
+
+    Ok(result)
+}
+
+/// Replaces a regular expression in the notes of every image.
+///
+/// `replacement` may use `$1` or `${name}` for capture groups. With `dry_run`
+/// the affected count and a sample are returned without writing anything.
+#[tauri::command]
+pub async fn find_replace_notes(
+    db: State<'_, Arc<Db>>,
+    pattern: String,
+    replacement: String,
+    dry_run: Option<bool>,
+) -> AppResult<NotesReplacement> {
+    if pattern.is_empty() {
+        return Err(AppError::Generic("Pattern can't be empty".to_string()));
+    }
+    let regex = regex::Regex::new(&pattern)
+        .map_err(|e| AppError::Generic(format!("Invalid pattern: {}", e)))?;
+
+    Ok(db.find_replace_notes(&regex, &replacement, dry_run.unwrap_or(false)).await?)
+}
+
+/// Converts stored capture times again with the current default time zone
+/// and queues files where none was found to be read again, for dates
+/// imported before they were normalized to UTC.
+#[tauri::command]
+pub async fn repair_capture_times(db: State<'_, Arc<Db>>) -> AppResult<CaptureTimeRepair> {
+    Ok(crate::indexer::capture_times::repair(&db).await?)
+}
@@ -1,16 +1,26 @@
+use crate::db::models::StockInfo;
+use crate::db::Db;
 use crate::error::{AppError, AppResult};
-use crate::media::metadata_reader;
+use crate::media::{exif_cache, metadata_reader};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::State;
 
+/// EXIF of a file, from the cache when the file hasn't changed since it was
+/// parsed. Files outside the library are parsed every time.
 #[tauri::command]
-pub async fn get_image_exif(path: String) -> AppResult<HashMap<String, String>> {
+pub async fn get_image_exif(db: State<'_, Arc<Db>>, path: String) -> AppResult<HashMap<String, String>> {
     // Check if file exists
-    let path_buf = PathBuf::from(&path);
+    let path_buf = crate::paths::from_db(&path);
     if !path_buf.exists() {
         return Err(AppError::NotFound(format!("File not found: {}", path)));
     }
 
+    if let Some(id) = db.get_image_id_by_path(&path).await? {
+        let mut exif = exif_cache::load(&db, vec![(id, path)]).await;
+        return Ok(exif.remove(&id).unwrap_or_default());
+    }
+
     // Run on blocking thread since file I/O can be slow
     let res = tauri::async_runtime::spawn_blocking(move || metadata_reader::read_exif(&path_buf))
         .await
@@ -18,3 +28,21 @@ pub async fn get_image_exif(path: String) -> AppResult<HashMap<String, String>>
 
     Ok(res)
 }
+
+/// EXIF of several images at once, keyed by id, for inspecting a selection.
+/// Images whose file is missing are left out.
+#[tauri::command]
+pub async fn get_exif_batch(
+    db: State<'_, Arc<Db>>,
+    ids: Vec<i64>,
+) -> AppResult<HashMap<i64, HashMap<String, String>>> {
+    let images = db.get_image_paths(&ids).await?;
+    Ok(exif_cache::load(&db, images).await)
+}
+
+/// Stock provider and license details of an image, or `None` if it isn't
+/// known to come from a stock site.
+#[tauri::command]
+pub async fn get_stock_info(db: State<'_, Arc<Db>>, image_id: i64) -> AppResult<Option<StockInfo>> {
+    Ok(db.get_stock_info(image_id).await?)
+}
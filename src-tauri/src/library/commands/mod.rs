@@ -4,3 +4,42 @@ pub mod metadata;
 pub mod smart_folders;
 pub mod formats;
 pub mod indexing;
+pub mod diagnostics;
+pub mod review;
+pub mod annotations;
+pub mod links;
+pub mod export;
+pub mod rename;
+pub mod estimate;
+pub mod playback;
+pub mod sequences;
+pub mod versions;
+pub mod pages;
+pub mod psd;
+pub mod auto_collections;
+pub mod maintenance;
+pub mod compare;
+pub mod playlists;
+pub mod collections;
+pub mod operations;
+pub mod lock;
+pub mod imports;
+pub mod sync;
+pub mod derivatives;
+pub mod proxies;
+pub mod working_sets;
+pub mod duplicates;
+pub mod capture;
+pub mod trash;
+
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use std::path::PathBuf;
+
+/// Path on disk of the image `id`, for commands working on a single file.
+pub(crate) async fn image_path(db: &Db, id: i64) -> AppResult<PathBuf> {
+    db.get_image_path(id)
+        .await?
+        .map(|path| crate::paths::from_db(&path))
+        .ok_or_else(|| AppError::NotFound(format!("Image {} not found", id)))
+}
@@ -0,0 +1,40 @@
+use crate::db::models::{Operation, OperationStep};
+use crate::db::operations::OPERATION_INTERRUPTED;
+use crate::db::Db;
+use crate::error::AppResult;
+use crate::indexer::BatchChangePayload;
+use crate::library::operations::{self, OperationRecovery};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, State};
+
+/// Operations a crash left half-applied. The frontend checks this on start
+/// and offers to resume or roll each one back.
+#[tauri::command]
+pub async fn get_interrupted_operations(db: State<'_, Arc<Db>>) -> AppResult<Vec<Operation>> {
+    Ok(db.get_operations_by_status(OPERATION_INTERRUPTED).await?)
+}
+
+#[tauri::command]
+pub async fn get_operation_steps(db: State<'_, Arc<Db>>, id: i64) -> AppResult<Vec<OperationStep>> {
+    Ok(db.get_operation_steps(id).await?)
+}
+
+/// Applies the remaining steps of an interrupted operation.
+#[tauri::command]
+pub async fn resume_operation(app: AppHandle, db: State<'_, Arc<Db>>, id: i64) -> AppResult<OperationRecovery> {
+    recover(&app, &db, id, true).await
+}
+
+/// Undoes the steps an interrupted operation already applied.
+#[tauri::command]
+pub async fn rollback_operation(app: AppHandle, db: State<'_, Arc<Db>>, id: i64) -> AppResult<OperationRecovery> {
+    recover(&app, &db, id, false).await
+}
+
+async fn recover(app: &AppHandle, db: &Db, id: i64, resume: bool) -> AppResult<OperationRecovery> {
+    let recovery = operations::recover(db, id, resume).await?;
+    let _ = app.emit("library:batch-change", BatchChangePayload {
+        added: vec![], removed: vec![], updated: vec![], needs_refresh: true
+    });
+    Ok(recovery)
+}
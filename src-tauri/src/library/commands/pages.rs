@@ -0,0 +1,56 @@
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::thumbnails::pages::{self, PageInfo};
+use crate::thumbnails::variants::MAX_MAXDIM;
+use super::image_path;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+/// Lists the pages of a multi-page TIFF or the parts/layers of an EXR.
+#[tauri::command]
+pub async fn get_image_pages(
+    db: State<'_, Arc<Db>>,
+    id: i64,
+) -> AppResult<Vec<PageInfo>> {
+    let path = image_path(&db, id).await?;
+    if !pages::supports_pages(&path) {
+        return Err(AppError::Unsupported(format!("{} has no pages", path.display())));
+    }
+    let path_for_task = path.clone();
+    let pages = tauri::async_runtime::spawn_blocking(move || {
+        pages::list_pages(&path_for_task).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+    .map_err(AppError::Generic)?;
+
+    // Keep the stored count in sync with what was actually read
+    if !crate::library::read_only::is_enabled() {
+        db.set_page_counts(&[(crate::paths::to_db(&path), pages.len() as i32)]).await?;
+    }
+    Ok(pages)
+}
+
+/// Renders one page into the preview cache and returns the cached file path.
+///
+/// The same page is also served by the `image://` protocol with `?page=`.
+#[tauri::command]
+pub async fn render_page(
+    app: AppHandle,
+    db: State<'_, Arc<Db>>,
+    id: i64,
+    index: u32,
+    maxdim: Option<u32>,
+) -> AppResult<String> {
+    let path = image_path(&db, id).await?;
+    let maxdim = maxdim.unwrap_or(MAX_MAXDIM).min(MAX_MAXDIM);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        pages::get_or_render_page(&app, &path, index, maxdim)
+            .map(|p| p.to_string_lossy().to_string())
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+    .map_err(AppError::Transcoding)
+}
@@ -0,0 +1,107 @@
+use crate::db::Db;
+use crate::db::models::{PlaybackState, VideoChapter};
+use crate::error::{AppError, AppResult};
+use crate::streaming::probe;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+/// Share of the duration after which the media counts as finished, so end
+/// credits don't keep a video "in progress".
+const COMPLETION_RATIO: f64 = 0.95;
+
+/// Whether `position` is close enough to the end of `duration` to be done.
+fn is_playback_complete(position: f64, duration: Option<f64>) -> bool {
+    match duration.filter(|d| d.is_finite() && *d > 0.0) {
+        Some(duration) => position >= duration * COMPLETION_RATIO,
+        None => false,
+    }
+}
+
+/// Saves where playback of a media file stopped.
+///
+/// `duration` is what the player reports; the probed duration is used when
+/// it is missing. Completed media resumes from the beginning next time.
+/// Nothing is saved while the library is read-only; the position is only
+/// echoed back.
+#[tauri::command]
+pub async fn set_playback_position(
+    db: State<'_, Arc<Db>>,
+    image_id: i64,
+    position: f64,
+    duration: Option<f64>,
+) -> AppResult<PlaybackState> {
+    if !position.is_finite() || position < 0.0 {
+        return Err(AppError::Generic(format!("Invalid playback position: {}", position)));
+    }
+
+    let duration = match duration {
+        Some(d) => Some(d),
+        None => db.get_media_duration(image_id).await?,
+    };
+    let completed = is_playback_complete(position, duration);
+    if crate::library::read_only::is_enabled() {
+        return Ok(PlaybackState { image_id, position, completed, updated_at: None });
+    }
+
+    db.set_playback_position(image_id, position, completed).await?;
+    db.get_playback_position(image_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Media {} not found", image_id)))
+}
+
+/// Returns the saved playback position of a media file, if any.
+#[tauri::command]
+pub async fn get_playback_position(
+    db: State<'_, Arc<Db>>,
+    image_id: i64,
+) -> AppResult<Option<PlaybackState>> {
+    Ok(db.get_playback_position(image_id).await?)
+}
+
+/// Forgets where playback of a media file stopped, so it starts over. Does
+/// nothing while the library is read-only.
+#[tauri::command]
+pub async fn clear_playback_position(
+    db: State<'_, Arc<Db>>,
+    image_id: i64,
+) -> AppResult<()> {
+    if crate::library::read_only::is_enabled() {
+        return Ok(());
+    }
+    Ok(db.clear_playback_position(image_id).await?)
+}
+
+/// Returns the chapter markers found when the file was probed. Files
+/// probed before chapters were read are probed again first, unless the
+/// library is read-only.
+#[tauri::command]
+pub async fn get_video_chapters(
+    app: AppHandle,
+    db: State<'_, Arc<Db>>,
+    id: i64,
+) -> AppResult<Vec<VideoChapter>> {
+    if !crate::library::read_only::is_enabled() && !db.are_chapters_probed(id).await? {
+        if let Some(path) = db.get_image_path(id).await? {
+            match probe::get_video_info(&app, &crate::paths::from_db(&path)).await {
+                Ok(info) => db.update_media_info(id, &info).await?,
+                Err(e) => eprintln!("WARN: Could not probe chapters of {}: {}", path, e),
+            }
+        }
+    }
+    Ok(db.get_video_chapters(id).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_playback_complete() {
+        assert!(!is_playback_complete(10.0, Some(100.0)));
+        assert!(is_playback_complete(96.0, Some(100.0)));
+        assert!(is_playback_complete(3500.0, Some(3600.0)));
+        assert!(!is_playback_complete(3000.0, Some(3600.0)));
+        assert!(!is_playback_complete(50.0, None));
+        assert!(!is_playback_complete(50.0, Some(0.0)));
+    }
+}
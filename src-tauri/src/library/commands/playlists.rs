@@ -0,0 +1,134 @@
+use crate::db::models::{Playlist, PlaylistItem, PlaylistItemInput};
+use crate::db::search::ImageFilter;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::formats::MediaType;
+use crate::library::slideshow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+/// Seconds per item when the playlist doesn't say otherwise.
+const DEFAULT_ITEM_DURATION: f64 = 5.0;
+
+async fn get_playlist_or_not_found(db: &Db, id: i64) -> AppResult<Playlist> {
+    db.get_playlist(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Playlist {} not found", id)))
+}
+
+/// Creates a playlist from exactly one source: a grid filter (the camelCase
+/// fields of `explain_filter`), an explicit list of ids, or an auto-collection
+/// snapshot. Items keep the order of their source.
+#[tauri::command]
+pub async fn create_playlist(
+    db: State<'_, Arc<Db>>,
+    name: String,
+    filter_json: Option<String>,
+    image_ids: Option<Vec<i64>>,
+    snapshot_id: Option<i64>,
+    default_duration: Option<f64>,
+) -> AppResult<Playlist> {
+    let ids = match (filter_json, image_ids, snapshot_id) {
+        (Some(filter_json), None, None) => {
+            let filter: ImageFilter = serde_json::from_str(&filter_json)
+                .map_err(|e| AppError::Generic(format!("Invalid filter JSON: {}", e)))?;
+            let group = filter.parsed_group();
+            let mut query_builder = filter.build_id_query(group.as_ref(), "");
+            query_builder.build_query_scalar::<i64>().fetch_all(&db.pool).await?
+        }
+        (None, Some(ids), None) => ids,
+        (None, None, Some(snapshot_id)) => db.get_auto_collection_snapshot_ids(snapshot_id).await?,
+        _ => {
+            return Err(AppError::Generic(
+                "A playlist needs exactly one of filterJson, imageIds or snapshotId".to_string(),
+            ))
+        }
+    };
+
+    let default_duration = default_duration.unwrap_or(DEFAULT_ITEM_DURATION).max(0.1);
+    let id = db.create_playlist(&name, default_duration, &ids).await?;
+    get_playlist_or_not_found(&db, id).await
+}
+
+#[tauri::command]
+pub async fn get_playlists(db: State<'_, Arc<Db>>) -> AppResult<Vec<Playlist>> {
+    Ok(db.get_playlists().await?)
+}
+
+#[tauri::command]
+pub async fn get_playlist_items(db: State<'_, Arc<Db>>, id: i64) -> AppResult<Vec<PlaylistItem>> {
+    Ok(db.get_playlist_items(id).await?)
+}
+
+#[tauri::command]
+pub async fn update_playlist(
+    db: State<'_, Arc<Db>>,
+    id: i64,
+    name: String,
+    default_duration: f64,
+) -> AppResult<()> {
+    Ok(db.update_playlist(id, &name, default_duration.max(0.1)).await?)
+}
+
+/// Replaces the items of a playlist, in order. Used for reordering, removing
+/// items and setting per-item durations (`null` uses the playlist default).
+#[tauri::command]
+pub async fn set_playlist_items(
+    db: State<'_, Arc<Db>>,
+    id: i64,
+    items: Vec<PlaylistItemInput>,
+) -> AppResult<()> {
+    Ok(db.set_playlist_items(id, &items).await?)
+}
+
+#[tauri::command]
+pub async fn delete_playlist(db: State<'_, Arc<Db>>, id: i64) -> AppResult<()> {
+    Ok(db.delete_playlist(id).await?)
+}
+
+/// Writes the playlist as an extended M3U file for media players.
+///
+/// Returns the number of entries written.
+#[tauri::command]
+pub async fn export_playlist_m3u(
+    db: State<'_, Arc<Db>>,
+    id: i64,
+    output_path: String,
+) -> AppResult<usize> {
+    let playlist = get_playlist_or_not_found(&db, id).await?;
+    let items = db.get_playlist_items(id).await?;
+
+    let entries: Vec<(PathBuf, f64, bool)> = tauri::async_runtime::spawn_blocking(move || {
+        items
+            .into_iter()
+            .map(|item| {
+                let path = crate::paths::from_db(&item.image.path);
+                let is_still = !matches!(slideshow::media_type(&path), Some(MediaType::Video | MediaType::Audio));
+                (path, item.duration.unwrap_or(playlist.default_duration), is_still)
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    tokio::fs::write(&output_path, slideshow::build_m3u(&entries)).await?;
+    Ok(entries.len())
+}
+
+/// Renders the playlist to an MP4 with crossfades.
+///
+/// `preset` is `720p`, `1080p`, `4k` or `square`. Audio items are skipped and
+/// formats FFmpeg can't read go through the preview cache.
+#[tauri::command]
+pub async fn render_slideshow(
+    app: AppHandle,
+    db: State<'_, Arc<Db>>,
+    playlist_id: i64,
+    preset: String,
+    output_path: String,
+    transition: Option<f64>,
+) -> AppResult<String> {
+    slideshow::render_playlist(&app, &db, playlist_id, &preset, Path::new(&output_path), transition, None).await?;
+    Ok(output_path)
+}
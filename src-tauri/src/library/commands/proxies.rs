@@ -0,0 +1,52 @@
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::jobs::JobQueue;
+use crate::library::proxies::{self, ProxySettings};
+use std::path::Path;
+use std::sync::Arc;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_proxy_settings(db: State<'_, Arc<Db>>) -> AppResult<ProxySettings> {
+    Ok(proxies::load_settings(&db).await)
+}
+
+/// Saves the proxy settings. Turning generation on needs a proxy folder.
+#[tauri::command]
+pub async fn set_proxy_settings(db: State<'_, Arc<Db>>, mut settings: ProxySettings) -> AppResult<ProxySettings> {
+    settings.folder = settings.folder.map(|folder| folder.trim().to_string()).filter(|folder| !folder.is_empty());
+    settings.watch_folders.retain(|folder| !folder.trim().is_empty());
+    if let Some(folder) = settings.folder.as_deref().filter(|folder| !Path::new(folder).is_absolute()) {
+        return Err(AppError::Generic(format!("The proxy folder must be an absolute path: {}", folder)));
+    }
+    if settings.enabled && settings.folder.is_none() {
+        return Err(AppError::Generic("Choose a proxy folder before turning proxy generation on".to_string()));
+    }
+    let value = serde_json::to_value(&settings).map_err(|e| AppError::Internal(e.to_string()))?;
+    db.set_setting(proxies::SETTING_KEY, &value).await?;
+    Ok(settings)
+}
+
+/// Queues proxies of the given videos, whether or not they are in a watched
+/// folder. Images that aren't videos, and videos already queued, are
+/// skipped. Returns the ids of the jobs queued, which `cancel_job` and
+/// `retry_job` control like any other.
+#[tauri::command]
+pub async fn queue_proxies(
+    db: State<'_, Arc<Db>>,
+    queue: State<'_, Arc<JobQueue>>,
+    image_ids: Vec<i64>,
+) -> AppResult<Vec<i64>> {
+    let settings = proxies::load_settings(&db).await;
+    if settings.proxy_folder().is_none() {
+        return Err(AppError::Generic("No proxy folder is set".to_string()));
+    }
+    let sources: Vec<String> = db
+        .get_image_paths(&image_ids)
+        .await?
+        .into_iter()
+        .map(|(_, path)| path)
+        .filter(|path| settings.accepts(Path::new(path)))
+        .collect();
+    proxies::queue(&db, &queue, &sources).await
+}
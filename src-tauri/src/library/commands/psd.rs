@@ -0,0 +1,45 @@
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::thumbnails::psd_layers::{self, PsdNode};
+use crate::thumbnails::variants::MAX_MAXDIM;
+use super::image_path;
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+/// Lists the layers and groups of a PSD as a flat list with parent ids.
+#[tauri::command]
+pub async fn get_psd_layers(
+    db: State<'_, Arc<Db>>,
+    id: i64,
+) -> AppResult<Vec<PsdNode>> {
+    let path = image_path(&db, id).await?;
+    tauri::async_runtime::spawn_blocking(move || {
+        psd_layers::list_layers(&path).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+    .map_err(AppError::Generic)
+}
+
+/// Renders one layer, cropped to its bounds, into the preview cache and
+/// returns the cached file path.
+#[tauri::command]
+pub async fn render_psd_layer(
+    app: AppHandle,
+    db: State<'_, Arc<Db>>,
+    id: i64,
+    layer_id: u32,
+    maxdim: Option<u32>,
+) -> AppResult<String> {
+    let path = image_path(&db, id).await?;
+    let maxdim = maxdim.unwrap_or(MAX_MAXDIM).min(MAX_MAXDIM);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        psd_layers::get_or_render_layer(&app, &path, layer_id, maxdim)
+            .map(|p| p.to_string_lossy().to_string())
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+    .map_err(AppError::Transcoding)
+}
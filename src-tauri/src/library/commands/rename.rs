@@ -0,0 +1,174 @@
+use crate::db::operations::{OPERATION_COMPLETED, OPERATION_ROLLED_BACK};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::indexer::echo;
+use crate::indexer::BatchChangePayload;
+use crate::library::rename::{render_template, RenameContext};
+use crate::paths;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, State};
+
+/// Planned rename of one file.
+#[derive(Debug, Serialize)]
+pub struct RenamePreviewItem {
+    pub id: i64,
+    pub old_path: String,
+    pub new_path: String,
+    pub new_filename: String,
+    /// Why this file can't be renamed, if anything prevents it.
+    pub conflict: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkRenameResult {
+    pub items: Vec<RenamePreviewItem>,
+    /// `false` for dry runs and when any item has a conflict.
+    pub applied: bool,
+}
+
+/// Renames files according to a template (see `library::rename` for tokens).
+///
+/// With `dry_run`, or when any conflict is found, nothing is touched and the
+/// plan is returned for preview. Otherwise every file is renamed and the
+/// database updated in one transaction; a failure rolls back the renames
+/// already done on disk. The plan is written to the operations ledger first,
+/// so a crash half-way can be resumed or rolled back on the next start.
+#[tauri::command]
+pub async fn rename_images_bulk(
+    app: AppHandle,
+    db: State<'_, Arc<Db>>,
+    ids: Vec<i64>,
+    template: String,
+    dry_run: Option<bool>,
+    start_seq: Option<u32>,
+) -> AppResult<BulkRenameResult> {
+    let images = db.get_images_by_ids(&ids).await?;
+
+    let mut tags_by_image: HashMap<i64, Vec<(Option<String>, String)>> = HashMap::new();
+    for (image_id, parent, name) in db.get_tag_names_for_images(&ids).await? {
+        tags_by_image.entry(image_id).or_default().push((parent, name));
+    }
+
+    let start_seq = start_seq.unwrap_or(1);
+    let mut items: Vec<RenamePreviewItem> = images
+        .iter()
+        .enumerate()
+        .map(|(index, image)| {
+            let old_path = Path::new(&image.path);
+            let ctx = RenameContext {
+                original_stem: old_path.file_stem().and_then(|s| s.to_str()).unwrap_or(&image.filename),
+                extension: old_path.extension().and_then(|e| e.to_str()),
+                seq: start_seq + index as u32,
+                created_at: image.created_at,
+                tags: tags_by_image.get(&image.id).map(|t| t.as_slice()).unwrap_or(&[]),
+            };
+
+            match render_template(&template, &ctx) {
+                Ok(new_filename) => {
+                    let new_path = old_path.with_file_name(&new_filename).to_string_lossy().to_string();
+                    RenamePreviewItem { id: image.id, old_path: image.path.clone(), new_path, new_filename, conflict: None }
+                }
+                Err(e) => RenamePreviewItem {
+                    id: image.id,
+                    old_path: image.path.clone(),
+                    new_path: image.path.clone(),
+                    new_filename: image.filename.clone(),
+                    conflict: Some(e),
+                },
+            }
+        })
+        .collect();
+
+    detect_conflicts(&mut items);
+
+    let has_conflicts = items.iter().any(|i| i.conflict.is_some());
+    if dry_run.unwrap_or(false) || has_conflicts {
+        return Ok(BulkRenameResult { items, applied: false });
+    }
+
+    let pending: Vec<&RenamePreviewItem> = items.iter().filter(|i| i.new_path != i.old_path).collect();
+    let steps: Vec<(i64, String, String)> = pending
+        .iter()
+        .map(|i| (i.id, i.old_path.clone(), i.new_path.clone()))
+        .collect();
+    let operation_id = db.begin_operation("bulk_rename", &steps).await?;
+    echo::expect_changes(pending.iter().flat_map(|i| [i.old_path.clone(), i.new_path.clone()]));
+
+    let mut done: Vec<&RenamePreviewItem> = Vec::with_capacity(pending.len());
+    for item in &pending {
+        if let Err(e) = tokio::fs::rename(paths::from_db(&item.old_path), paths::from_db(&item.new_path)).await {
+            roll_back(&db, operation_id, &done).await;
+            return Err(AppError::Io(e));
+        }
+        done.push(item);
+    }
+
+    let updates: Vec<(i64, String, String)> = pending
+        .iter()
+        .map(|i| (i.id, i.new_path.clone(), i.new_filename.clone()))
+        .collect();
+    if let Err(e) = db.apply_renames(&updates).await {
+        roll_back(&db, operation_id, &done).await;
+        return Err(e.into());
+    }
+    if let Err(e) = db.finish_operation(operation_id, OPERATION_COMPLETED).await {
+        eprintln!("WARN: Failed to close operation {}: {}", operation_id, e);
+    }
+
+    println!("INFO: Bulk renamed {} files", updates.len());
+    let _ = app.emit("library:batch-change", BatchChangePayload {
+        added: vec![], removed: vec![], updated: vec![], needs_refresh: true
+    });
+
+    Ok(BulkRenameResult { items, applied: true })
+}
+
+/// Flags duplicate targets inside the batch and targets taken by other files.
+fn detect_conflicts(items: &mut [RenamePreviewItem]) {
+    // Compare case-insensitively, as the default macOS and Windows filesystems are
+    let key = |p: &str| p.to_lowercase();
+
+    let mut target_counts: HashMap<String, usize> = HashMap::new();
+    for item in items.iter().filter(|i| i.conflict.is_none()) {
+        *target_counts.entry(key(&item.new_path)).or_default() += 1;
+    }
+    let sources: HashSet<String> = items.iter().map(|i| key(&i.old_path)).collect();
+
+    for item in items.iter_mut().filter(|i| i.conflict.is_none()) {
+        let target = key(&item.new_path);
+        if target == key(&item.old_path) {
+            // A case-only rename, blocked only by another file spelled exactly so
+            if item.new_path != item.old_path && paths::exists_with_case(&paths::from_db(&item.new_path)) {
+                item.conflict = Some("A file with this name already exists".to_string());
+            }
+            continue;
+        }
+        if target_counts.get(&target).copied().unwrap_or(0) > 1 {
+            item.conflict = Some("Another file in the batch gets the same name".to_string());
+        } else if sources.contains(&target) {
+            item.conflict = Some("Target name is used by another file in the batch".to_string());
+        } else if paths::from_db(&item.new_path).exists() {
+            item.conflict = Some("A file with this name already exists".to_string());
+        }
+    }
+}
+
+/// Reverts the renames already done on disk. The operation is only closed
+/// if every file is back, otherwise it stays in the ledger for recovery.
+async fn roll_back(db: &Db, operation_id: i64, done: &[&RenamePreviewItem]) {
+    let mut reverted = true;
+    for item in done.iter().rev() {
+        if let Err(e) = tokio::fs::rename(paths::from_db(&item.new_path), paths::from_db(&item.old_path)).await {
+            eprintln!("ERROR: Failed to roll back rename {} -> {}: {}", item.new_path, item.old_path, e);
+            reverted = false;
+        }
+    }
+    if reverted {
+        if let Err(e) = db.finish_operation(operation_id, OPERATION_ROLLED_BACK).await {
+            eprintln!("WARN: Failed to close operation {}: {}", operation_id, e);
+        }
+    }
+}
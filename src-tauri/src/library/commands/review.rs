@@ -0,0 +1,90 @@
+use crate::db::Db;
+use crate::db::models::{ReviewCursor, ReviewSession};
+use crate::db::review::{REVIEW_ADVANCED, REVIEW_APPLIED, REVIEW_SKIPPED};
+use crate::db::search::ImageFilter;
+use crate::error::{AppError, AppResult};
+use std::sync::Arc;
+use tauri::State;
+
+/// Starts a review queue over the untagged or unrated images matching a filter.
+///
+/// `filter_json` uses the same camelCase fields as `explain_filter`. The item
+/// order is fixed at creation, so tagging items doesn't reshuffle the queue.
+#[tauri::command]
+pub async fn start_review_session(
+    db: State<'_, Arc<Db>>,
+    filter_json: String,
+    name: Option<String>,
+) -> AppResult<ReviewCursor> {
+    let filter: ImageFilter = serde_json::from_str(&filter_json)
+        .map_err(|e| AppError::Generic(format!("Invalid filter JSON: {}", e)))?;
+
+    let image_ids = db.get_images_needing_review(&filter).await?;
+    let session_id = db
+        .create_review_session(name.as_deref(), &filter_json, &image_ids)
+        .await?;
+
+    get_cursor(&db, session_id).await
+}
+
+/// Lists saved review sessions so an interrupted one can be resumed.
+#[tauri::command]
+pub async fn get_review_sessions(db: State<'_, Arc<Db>>) -> AppResult<Vec<ReviewSession>> {
+    Ok(db.get_review_sessions().await?)
+}
+
+/// Returns a session and the item to review next.
+#[tauri::command]
+pub async fn get_review_cursor(db: State<'_, Arc<Db>>, session_id: i64) -> AppResult<ReviewCursor> {
+    get_cursor(&db, session_id).await
+}
+
+/// Moves past the current item without changing it.
+#[tauri::command]
+pub async fn review_advance(db: State<'_, Arc<Db>>, session_id: i64) -> AppResult<ReviewCursor> {
+    db.record_review_step(session_id, REVIEW_ADVANCED).await?;
+    get_cursor(&db, session_id).await
+}
+
+/// Marks the current item as skipped and moves to the next one. Skipped
+/// items come back once the rest of the queue is done.
+#[tauri::command]
+pub async fn review_skip(db: State<'_, Arc<Db>>, session_id: i64) -> AppResult<ReviewCursor> {
+    db.record_review_step(session_id, REVIEW_SKIPPED).await?;
+    get_cursor(&db, session_id).await
+}
+
+/// Applies tags and/or a rating to the current item, then moves to the next one.
+#[tauri::command]
+pub async fn review_apply(
+    db: State<'_, Arc<Db>>,
+    session_id: i64,
+    tag_ids: Vec<i64>,
+    rating: Option<i32>,
+) -> AppResult<ReviewCursor> {
+    let cursor = get_cursor(&db, session_id).await?;
+    let Some(image) = cursor.current else {
+        return Err(AppError::Generic(format!("Review session {} has no current item", session_id)));
+    };
+
+    let image_ids = crate::library::stacks::with_siblings(&db, vec![image.id]).await?;
+    db.add_tags_to_images_batch(image_ids.clone(), tag_ids).await?;
+    if let Some(rating) = rating {
+        db.update_images_rating(&image_ids, rating).await?;
+    }
+
+    db.record_review_step(session_id, REVIEW_APPLIED).await?;
+    get_cursor(&db, session_id).await
+}
+
+/// Deletes a review session; its images are left as they are.
+#[tauri::command]
+pub async fn delete_review_session(db: State<'_, Arc<Db>>, session_id: i64) -> AppResult<()> {
+    Ok(db.delete_review_session(session_id).await?)
+}
+
+async fn get_cursor(db: &Db, session_id: i64) -> AppResult<ReviewCursor> {
+    db.get_review_cursor(session_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Review session {} not found", session_id)))
+}
@@ -0,0 +1,44 @@
+use crate::db::Db;
+use crate::db::models::{ImageSequence, SequenceFrame};
+use crate::error::{AppError, AppResult};
+use std::sync::Arc;
+use tauri::State;
+
+/// Re-runs sequence detection for a folder and returns what was found.
+#[tauri::command]
+pub async fn detect_image_sequences(
+    db: State<'_, Arc<Db>>,
+    folder_id: i64,
+) -> AppResult<Vec<ImageSequence>> {
+    crate::indexer::sequences::refresh_folder_sequences(&db, &[folder_id]).await;
+    Ok(db.get_folder_sequences(folder_id).await?)
+}
+
+/// Lists the sequences last detected in a folder, without re-detecting them.
+#[tauri::command]
+pub async fn get_folder_sequences(
+    db: State<'_, Arc<Db>>,
+    folder_id: i64,
+) -> AppResult<Vec<ImageSequence>> {
+    Ok(db.get_folder_sequences(folder_id).await?)
+}
+
+/// Retrieves a sequence by id, as referenced by an image's `sequence_id`.
+#[tauri::command]
+pub async fn get_image_sequence(
+    db: State<'_, Arc<Db>>,
+    id: i64,
+) -> AppResult<ImageSequence> {
+    db.get_image_sequence(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Image sequence {} not found", id)))
+}
+
+/// Lists the frames of a sequence with their thumbnails, for scrub previews.
+#[tauri::command]
+pub async fn get_sequence_frames(
+    db: State<'_, Arc<Db>>,
+    sequence_id: i64,
+) -> AppResult<Vec<SequenceFrame>> {
+    Ok(db.get_sequence_frames(sequence_id).await?)
+}
@@ -1,6 +1,8 @@
 use crate::db::Db;
-use crate::db::models::SmartFolder;
+use crate::db::models::{SmartFolder, SmartFolderSnapshot};
 use crate::error::AppResult;
+use crate::library::smart_folder_history;
+use crate::library::smart_folder_sharing::{self, SmartFolderImport};
 use std::sync::Arc;
 use tauri::State;
 
@@ -32,3 +34,27 @@ pub async fn update_smart_folder(
 pub async fn delete_smart_folder(db: State<'_, Arc<Db>>, id: i64) -> AppResult<()> {
     Ok(db.delete_smart_folder(id).await?)
 }
+
+/// Smart folder `id` as JSON that another library can import.
+#[tauri::command]
+pub async fn export_smart_folder(db: State<'_, Arc<Db>>, id: i64) -> AppResult<String> {
+    smart_folder_sharing::export(&db, id).await
+}
+
+/// Saves a smart folder shared as JSON, its tags, folders and collections
+/// mapped onto this library (see `smart_folder_sharing::import`).
+#[tauri::command]
+pub async fn import_smart_folder(db: State<'_, Arc<Db>>, json: String) -> AppResult<SmartFolderImport> {
+    smart_folder_sharing::import(&db, &json).await
+}
+
+/// Recorded statistics of smart folder `id` over the last `days` (90 by
+/// default), oldest first, to chart how its backlog evolves.
+#[tauri::command]
+pub async fn get_smart_folder_trends(
+    db: State<'_, Arc<Db>>,
+    id: i64,
+    days: Option<u32>,
+) -> AppResult<Vec<SmartFolderSnapshot>> {
+    smart_folder_history::trends(&db, id, days).await
+}
@@ -0,0 +1,83 @@
+use crate::db::models::{Job, SyncTarget};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::jobs::JobQueue;
+use crate::library::sync::{self, SyncDiff};
+use std::sync::Arc;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_sync_targets(db: State<'_, Arc<Db>>) -> AppResult<Vec<SyncTarget>> {
+    Ok(db.get_sync_targets().await?)
+}
+
+/// Creates a sync target mirroring the images matching a filter (the
+/// camelCase fields of `explain_filter`) into `destination`.
+/// `delete_policy` is `keep`, `delete` or `archive` (moved to `_removed` in
+/// the destination); `keep_folders` recreates the folders below the
+/// location instead of copying flat.
+#[tauri::command]
+pub async fn create_sync_target(
+    db: State<'_, Arc<Db>>,
+    name: String,
+    destination: String,
+    filter_json: String,
+    delete_policy: String,
+    keep_folders: Option<bool>,
+    auto_sync: Option<bool>,
+) -> AppResult<i64> {
+    sync::validate(&filter_json, &delete_policy)?;
+    let destination_path = std::path::Path::new(&destination);
+    if !destination_path.is_absolute() {
+        return Err(AppError::Generic(format!("Destination must be an absolute path: {}", destination)));
+    }
+    Ok(db
+        .create_sync_target(
+            &name,
+            &crate::paths::to_db(destination_path),
+            &filter_json,
+            &delete_policy,
+            keep_folders.unwrap_or(false),
+            auto_sync.unwrap_or(false),
+        )
+        .await?)
+}
+
+#[tauri::command]
+pub async fn update_sync_target(
+    db: State<'_, Arc<Db>>,
+    id: i64,
+    name: String,
+    filter_json: String,
+    delete_policy: String,
+    auto_sync: bool,
+) -> AppResult<()> {
+    sync::validate(&filter_json, &delete_policy)?;
+    Ok(db.update_sync_target(id, &name, &filter_json, &delete_policy, auto_sync).await?)
+}
+
+/// Deletes a sync target. Files already copied are left in place.
+#[tauri::command]
+pub async fn delete_sync_target(db: State<'_, Arc<Db>>, id: i64) -> AppResult<()> {
+    Ok(db.delete_sync_target(id).await?)
+}
+
+/// Dry run: what a sync of the target would copy, update and remove.
+#[tauri::command]
+pub async fn preview_sync_target(db: State<'_, Arc<Db>>, id: i64) -> AppResult<SyncDiff> {
+    sync::preview(&db, id).await
+}
+
+/// Queues a `sync_folder` job for the target and returns it.
+#[tauri::command]
+pub async fn run_sync_target(db: State<'_, Arc<Db>>, queue: State<'_, Arc<JobQueue>>, id: i64) -> AppResult<Job> {
+    let target = db
+        .get_sync_target(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Sync target {} not found", id)))?;
+    let payload = serde_json::json!({ "targetId": id });
+    let job_id = queue.enqueue("sync_folder", &sync::job_label(&target), &payload, 0).await?;
+    db.get_job(job_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Job {} not found", job_id)))
+}
@@ -1,6 +1,9 @@
 use crate::db::Db;
-use crate::db::models::{Tag, ImageMetadata, LibraryStats};
-use crate::error::AppResult;
+use crate::db::models::{Tag, ImageMetadata, LibraryStats, SelectionSummary, TagShortcut};
+use crate::error::{AppError, AppResult};
+use crate::db::search::ImageFilter;
+use crate::library::filter_subscriptions::{FilterDelta, FilterSubscriptions, FilterWindow};
+use crate::library::stacks;
 use std::sync::Arc;
 use tauri::State;
 
@@ -49,7 +52,8 @@ pub async fn add_tag_to_image(
     image_id: i64,
     tag_id: i64,
 ) -> AppResult<()> {
-    Ok(db.add_tag_to_image(image_id, tag_id).await?)
+    let image_ids = stacks::with_siblings(&db, vec![image_id]).await?;
+    Ok(db.add_tags_to_images_batch(image_ids, vec![tag_id]).await?)
 }
 
 #[tauri::command]
@@ -58,7 +62,8 @@ pub async fn remove_tag_from_image(
     image_id: i64,
     tag_id: i64,
 ) -> AppResult<()> {
-    Ok(db.remove_tag_from_image(image_id, tag_id).await?)
+    let image_ids = stacks::with_siblings(&db, vec![image_id]).await?;
+    Ok(db.remove_tag_from_images(&image_ids, tag_id).await?)
 }
 
 #[tauri::command]
@@ -72,6 +77,7 @@ pub async fn add_tags_to_images_batch(
     image_ids: Vec<i64>,
     tag_ids: Vec<i64>,
 ) -> AppResult<()> {
+    let image_ids = stacks::with_siblings(&db, image_ids).await?;
     Ok(db.add_tags_to_images_batch(image_ids, tag_ids).await?)
 }
 
@@ -85,12 +91,13 @@ pub async fn get_images_filtered(
     untagged: Option<bool>,
     folder_id: Option<i64>,
     recursive: bool,
+    collection_id: Option<i64>,
     sort_by: Option<String>,
     sort_order: Option<String>,
     advanced_query: Option<String>,
     search_query: Option<String>,
 ) -> AppResult<Vec<ImageMetadata>> {
-    Ok(db.get_images_filtered(limit, offset, tag_ids, match_all, untagged, folder_id, recursive, sort_by, sort_order, advanced_query, search_query).await?)
+    Ok(db.get_images_filtered(limit, offset, tag_ids, match_all, untagged, folder_id, recursive, collection_id, sort_by, sort_order, advanced_query, search_query).await?)
 }
 
 #[tauri::command]
@@ -101,10 +108,43 @@ pub async fn get_image_count_filtered(
     untagged: Option<bool>,
     folder_id: Option<i64>,
     recursive: bool,
+    collection_id: Option<i64>,
     advanced_query: Option<String>,
     search_query: Option<String>,
 ) -> AppResult<i64> {
-    Ok(db.get_image_count_filtered(tag_ids, match_all, untagged, folder_id, recursive, advanced_query, search_query).await?)
+    Ok(db.get_image_count_filtered(tag_ids, match_all, untagged, folder_id, recursive, collection_id, advanced_query, search_query).await?)
+}
+
+/// Subscribes to the rows `window` of `filter` shows. Later changes to them
+/// arrive as `grid:delta` events.
+#[tauri::command]
+pub async fn subscribe_filter(
+    db: State<'_, Arc<Db>>,
+    subscriptions: State<'_, Arc<FilterSubscriptions>>,
+    filter: ImageFilter,
+    window: FilterWindow,
+) -> AppResult<FilterDelta> {
+    Ok(subscriptions.subscribe(&db, filter, window).await?)
+}
+
+/// Moves or resizes the window of a subscription, returning what changed.
+#[tauri::command]
+pub async fn set_filter_window(
+    db: State<'_, Arc<Db>>,
+    subscriptions: State<'_, Arc<FilterSubscriptions>>,
+    subscription_id: u64,
+    window: FilterWindow,
+) -> AppResult<FilterDelta> {
+    subscriptions
+        .set_window(&db, subscription_id, window)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Filter subscription {}", subscription_id)))
+}
+
+/// Ends a subscription when its grid goes away or changes filter.
+#[tauri::command]
+pub fn unsubscribe_filter(subscriptions: State<'_, Arc<FilterSubscriptions>>, subscription_id: u64) {
+    subscriptions.unsubscribe(subscription_id);
 }
 
 #[tauri::command]
@@ -113,7 +153,11 @@ pub async fn update_image_rating(
     id: i64,
     rating: i32,
 ) -> AppResult<()> {
-    Ok(db.update_image_rating(id, rating).await?)
+    let ids = stacks::with_siblings(&db, vec![id]).await?;
+    db.update_images_rating(&ids, rating).await?;
+    // Written to the files or their sidecars when write-back is on
+    crate::media::sidecars::wake();
+    Ok(())
 }
 
 #[tauri::command]
@@ -122,5 +166,66 @@ pub async fn update_image_notes(
     id: i64,
     notes: String,
 ) -> AppResult<()> {
-    Ok(db.update_image_notes(id, notes).await?)
+    db.update_image_notes(id, notes).await?;
+    crate::media::sidecars::wake();
+    Ok(())
+}
+
+/// Profile used when the frontend does not specify one.
+const DEFAULT_SHORTCUT_PROFILE: &str = "default";
+
+/// Validates a shortcut key, accepting digits 1-9 and letters (case-insensitive).
+fn normalize_shortcut_key(key: &str) -> AppResult<String> {
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if matches!(c, '1'..='9') || c.is_ascii_alphabetic() => {
+            Ok(c.to_ascii_lowercase().to_string())
+        }
+        _ => Err(AppError::Generic(format!(
+            "Invalid shortcut key '{}': use 1-9 or a single letter",
+            key
+        ))),
+    }
+}
+
+/// Maps a keyboard key to a tag. Passing no `tag_id` removes the mapping.
+#[tauri::command]
+pub async fn assign_tag_shortcut(
+    db: State<'_, Arc<Db>>,
+    key: String,
+    tag_id: Option<i64>,
+    profile: Option<String>,
+) -> AppResult<()> {
+    let key = normalize_shortcut_key(&key)?;
+    let profile = profile.unwrap_or_else(|| DEFAULT_SHORTCUT_PROFILE.to_string());
+    Ok(db.assign_tag_shortcut(&profile, &key, tag_id).await?)
+}
+
+/// Lists the key-to-tag mappings of a shortcut profile.
+#[tauri::command]
+pub async fn get_tag_shortcuts(
+    db: State<'_, Arc<Db>>,
+    profile: Option<String>,
+) -> AppResult<Vec<TagShortcut>> {
+    let profile = profile.unwrap_or_else(|| DEFAULT_SHORTCUT_PROFILE.to_string());
+    Ok(db.get_tag_shortcuts(&profile).await?)
+}
+
+/// Returns the tag mapped to a key, if any.
+#[tauri::command]
+pub async fn resolve_tag_shortcut(
+    db: State<'_, Arc<Db>>,
+    key: String,
+    profile: Option<String>,
+) -> AppResult<Option<Tag>> {
+    let key = normalize_shortcut_key(&key)?;
+    let profile = profile.unwrap_or_else(|| DEFAULT_SHORTCUT_PROFILE.to_string());
+    Ok(db.resolve_tag_shortcut(&profile, &key).await?)
+}
+
+/// Aggregate info for a multi-selection: shared tags, ratings, size, formats
+/// and date range, in place of one call per selected item.
+#[tauri::command]
+pub async fn get_selection_summary(db: State<'_, Arc<Db>>, ids: Vec<i64>) -> AppResult<SelectionSummary> {
+    Ok(db.get_selection_summary(&ids).await?)
 }
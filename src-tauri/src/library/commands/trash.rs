@@ -0,0 +1,40 @@
+use crate::db::models::TrashItem;
+use crate::db::Db;
+use crate::error::AppResult;
+use crate::library::trash::{self, TrashOutcome};
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+/// Images taken out of the library and still restorable, most recent
+/// first: deleted from the app, or whose file went missing.
+#[tauri::command]
+pub async fn get_trash(db: State<'_, Arc<Db>>) -> AppResult<Vec<TrashItem>> {
+    Ok(db.get_trash().await?)
+}
+
+/// Deletes images from the library. Their files are moved to the trash
+/// folder and their tags, rating, notes and collections kept, until they
+/// are restored or the trash is emptied. Returns the new trash entries.
+#[tauri::command]
+pub async fn trash_images(app: AppHandle, db: State<'_, Arc<Db>>, image_ids: Vec<i64>) -> AppResult<TrashOutcome> {
+    let result = trash::delete_images(&app, &db, &image_ids).await?;
+    crate::library::filter_subscriptions::notify_changes(&app);
+    Ok(result)
+}
+
+/// Brings trash entries back into the library as they were. Returns the
+/// ids of the restored images.
+#[tauri::command]
+pub async fn restore_from_trash(app: AppHandle, db: State<'_, Arc<Db>>, ids: Vec<i64>) -> AppResult<TrashOutcome> {
+    let result = trash::restore(&app, &db, &ids).await?;
+    crate::library::filter_subscriptions::notify_changes(&app);
+    Ok(result)
+}
+
+/// Drops trash entries for good, all of them when `ids` is missing, and
+/// deletes the files the trash folder kept. Returns how many files were
+/// deleted.
+#[tauri::command]
+pub async fn empty_trash(db: State<'_, Arc<Db>>, ids: Option<Vec<i64>>) -> AppResult<usize> {
+    trash::empty(&db, ids.as_deref()).await
+}
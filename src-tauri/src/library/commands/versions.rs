@@ -0,0 +1,32 @@
+use crate::db::Db;
+use crate::db::models::{ChainVersion, VersionChain};
+use crate::error::AppResult;
+use std::sync::Arc;
+use tauri::State;
+
+/// Re-runs version chain detection for a folder and returns what was found.
+#[tauri::command]
+pub async fn detect_version_chains(
+    db: State<'_, Arc<Db>>,
+    folder_id: i64,
+) -> AppResult<Vec<VersionChain>> {
+    crate::indexer::versions::refresh_folder_version_chains(&db, &[folder_id]).await;
+    Ok(db.get_folder_version_chains(folder_id).await?)
+}
+
+#[tauri::command]
+pub async fn get_folder_version_chains(
+    db: State<'_, Arc<Db>>,
+    folder_id: i64,
+) -> AppResult<Vec<VersionChain>> {
+    Ok(db.get_folder_version_chains(folder_id).await?)
+}
+
+/// Lists the versions of a chain from the oldest to the latest.
+#[tauri::command]
+pub async fn get_chain_versions(
+    db: State<'_, Arc<Db>>,
+    chain_id: i64,
+) -> AppResult<Vec<ChainVersion>> {
+    Ok(db.get_chain_versions(chain_id).await?)
+}
@@ -0,0 +1,39 @@
+use crate::error::{AppError, AppResult};
+use crate::library::filter_subscriptions;
+use crate::library::working_sets::{self, WorkingSet, MAX_DEPTH};
+use tauri::AppHandle;
+
+/// Pushes a working set of images, narrowed to those in the current top set.
+/// Searches scope to it with a `working_set` criterion holding its id.
+#[tauri::command]
+pub fn push_working_set(image_ids: Vec<i64>, label: Option<String>) -> AppResult<WorkingSet> {
+    if image_ids.is_empty() {
+        return Err(AppError::Generic("A working set needs at least one image".to_string()));
+    }
+    let label = label.map(|label| label.trim().to_string()).filter(|label| !label.is_empty());
+    working_sets::lock()
+        .push(image_ids, label)
+        .ok_or_else(|| AppError::Generic(format!("Working sets are limited to {} levels", MAX_DEPTH)))
+}
+
+/// Removes the top working set and returns the one below, if any. Grids
+/// scoped to the removed set are refreshed, and show nothing.
+#[tauri::command]
+pub fn pop_working_set(app: AppHandle) -> AppResult<Option<WorkingSet>> {
+    let top = working_sets::lock().pop();
+    filter_subscriptions::notify_changes(&app);
+    Ok(top)
+}
+
+/// Working sets of this session, bottom first.
+#[tauri::command]
+pub fn get_working_sets() -> Vec<WorkingSet> {
+    working_sets::lock().list()
+}
+
+#[tauri::command]
+pub fn clear_working_sets(app: AppHandle) -> AppResult<()> {
+    working_sets::lock().clear();
+    filter_subscriptions::notify_changes(&app);
+    Ok(())
+}
@@ -0,0 +1,217 @@
+//! Files derived from library assets.
+//!
+//! Editing proxies, exported copies, transcodes and viewer conversions are
+//! recorded against the asset they came from, so an asset's versions can be
+//! listed, and the ones Mundam can make again cleaned up to reclaim space.
+//! Files Mundam writes are recorded as they're made; files made elsewhere
+//! (an export from another app) can be linked by hand.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::db::derivatives::DerivativeRow;
+use crate::db::Db;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DerivativeKind {
+    /// An editing proxy.
+    Proxy,
+    /// A copy exported for use elsewhere.
+    Export,
+    /// A playback transcode or remux.
+    Transcode,
+    /// A conversion for a viewer, like a model as GLB.
+    Preview,
+}
+
+impl DerivativeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DerivativeKind::Proxy => "proxy",
+            DerivativeKind::Export => "export",
+            DerivativeKind::Transcode => "transcode",
+            DerivativeKind::Preview => "preview",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "proxy" => Some(DerivativeKind::Proxy),
+            "export" => Some(DerivativeKind::Export),
+            "transcode" => Some(DerivativeKind::Transcode),
+            "preview" => Some(DerivativeKind::Preview),
+            _ => None,
+        }
+    }
+
+    /// Whether Mundam makes the file again when needed, so cleanup may
+    /// delete it. Proxies and exports are the user's. Only Mundam records
+    /// these kinds (see [`is_linkable`](Self::is_linkable)), so cleanup never
+    /// deletes a file it didn't write.
+    pub fn is_disposable(self) -> bool {
+        matches!(self, DerivativeKind::Transcode | DerivativeKind::Preview)
+    }
+
+    /// Whether a file made elsewhere may be linked by hand as this kind.
+    pub fn is_linkable(self) -> bool {
+        !self.is_disposable()
+    }
+}
+
+/// A derivative as listed.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Derivative {
+    pub id: i64,
+    pub image_id: i64,
+    pub kind: DerivativeKind,
+    pub path: String,
+    pub label: Option<String>,
+    pub size: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    /// Whether the file is still on disk.
+    pub exists: bool,
+}
+
+impl Derivative {
+    fn from_row(row: DerivativeRow) -> Option<Self> {
+        Some(Derivative {
+            kind: DerivativeKind::parse(&row.kind)?,
+            exists: crate::paths::from_db(&row.path).is_file(),
+            id: row.id,
+            image_id: row.image_id,
+            path: row.path,
+            label: row.label,
+            size: row.size,
+            created_at: row.created_at,
+        })
+    }
+}
+
+/// Outcome of [`clean_up`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DerivativeCleanup {
+    /// Files deleted.
+    pub deleted: u64,
+    /// Records of files already gone, forgotten.
+    pub forgotten: u64,
+    pub freed_bytes: u64,
+}
+
+/// Records `path` as derived from `image_id`.
+pub async fn record(
+    db: &Db,
+    image_id: i64,
+    kind: DerivativeKind,
+    path: &Path,
+    label: Option<&str>,
+) -> Result<i64, sqlx::Error> {
+    let size = std::fs::metadata(path).ok().map(|metadata| metadata.len() as i64);
+    db.add_derivative(image_id, kind.as_str(), &crate::paths::to_db(path), label, size).await
+}
+
+/// Records in the background that `path` was derived from the asset at
+/// `source`, for code that knows the source by path. Files outside the
+/// library aren't recorded, nor files already recorded as they are, so it
+/// may be called each time a cached file is served.
+pub fn record_for_source<R: Runtime>(
+    app: &AppHandle<R>,
+    source: &Path,
+    kind: DerivativeKind,
+    path: &Path,
+    label: Option<&str>,
+) {
+    let Some(db) = app.try_state::<Arc<Db>>().map(|db| db.inner().clone()) else {
+        return;
+    };
+    let source = crate::paths::to_db(source);
+    let path = path.to_path_buf();
+    let label = label.map(String::from);
+    tauri::async_runtime::spawn(async move {
+        let size = tokio::fs::metadata(&path).await.ok().map(|metadata| metadata.len() as i64);
+        let result = match db.get_derivative_by_path(&crate::paths::to_db(&path)).await {
+            Ok(Some(known)) if known.size == size && known.kind == kind.as_str() => Ok(()),
+            Ok(_) => match db.get_image_id_by_path(&source).await {
+                Ok(Some(image_id)) => record(&db, image_id, kind, &path, label.as_deref()).await.map(|_| ()),
+                Ok(None) => Ok(()),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            eprintln!("WARN: Could not record derivative {:?} of {}: {}", path, source, e);
+        }
+    });
+}
+
+/// Derivatives of an image, or of every image, newest first.
+pub async fn list(db: &Db, image_id: Option<i64>) -> Result<Vec<Derivative>, sqlx::Error> {
+    Ok(db.get_derivatives(image_id).await?.into_iter().filter_map(Derivative::from_row).collect())
+}
+
+/// Deletes the disposable derivatives of an image, or of every image, and
+/// forgets the records of files already gone. Proxies and exports are kept.
+pub async fn clean_up(db: &Db, image_id: Option<i64>) -> Result<DerivativeCleanup, sqlx::Error> {
+    let mut cleanup = DerivativeCleanup::default();
+    let mut removed = Vec::new();
+    for derivative in list(db, image_id).await? {
+        if !derivative.exists {
+            cleanup.forgotten += 1;
+            removed.push(derivative.id);
+        } else if derivative.kind.is_disposable() {
+            let file = crate::paths::from_db(&derivative.path);
+            let size = tokio::fs::metadata(&file).await.map(|metadata| metadata.len()).unwrap_or(0);
+            match tokio::fs::remove_file(&file).await {
+                Ok(()) => {
+                    cleanup.deleted += 1;
+                    cleanup.freed_bytes += size;
+                    removed.push(derivative.id);
+                }
+                Err(e) => eprintln!("WARN: Could not delete derivative {}: {}", derivative.path, e),
+            }
+        }
+    }
+    db.delete_derivatives(&removed).await?;
+    Ok(cleanup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cleanup_keeps_user_files() {
+        let library = crate::testkit::TestLibrary::open("derivatives").await;
+        let db = &library.db;
+        library.seed_images(&["clip.mov"]).await;
+
+        let dir = std::env::temp_dir().join(format!("mundam-derivatives-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (proxy, transcode) = (dir.join("clip_proxy.mov"), dir.join("clip.mp4"));
+        std::fs::write(&proxy, b"proxy").unwrap();
+        std::fs::write(&transcode, b"transcode").unwrap();
+
+        record(db, 1, DerivativeKind::Proxy, &proxy, Some("prores_proxy")).await.unwrap();
+        let first = record(db, 1, DerivativeKind::Transcode, &transcode, Some("standard")).await.unwrap();
+        // Writing a file again updates its record
+        assert_eq!(record(db, 1, DerivativeKind::Transcode, &transcode, Some("high")).await.unwrap(), first);
+        record(db, 1, DerivativeKind::Export, &dir.join("gone.jpg"), None).await.unwrap();
+
+        let listed = list(db, Some(1)).await.unwrap();
+        assert_eq!(listed.len(), 3);
+        assert_eq!(listed.iter().find(|d| d.id == first).unwrap().label.as_deref(), Some("high"));
+
+        let cleanup = clean_up(db, Some(1)).await.unwrap();
+        assert_eq!(cleanup, DerivativeCleanup { deleted: 1, forgotten: 1, freed_bytes: 9 });
+        assert!(proxy.exists() && !transcode.exists());
+        let kept: Vec<DerivativeKind> = list(db, None).await.unwrap().iter().map(|d| d.kind).collect();
+        assert_eq!(kept, vec![DerivativeKind::Proxy]);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
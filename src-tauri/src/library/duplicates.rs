@@ -0,0 +1,370 @@
+//! Duplicate resolution.
+//!
+//! Copies of a file are found through the content hashes the indexer stores
+//! (see `indexer::content_hashes`): only files whose size another file
+//! shares are hashed, so finding them stays cheap on large libraries. All
+//! groups can be listed at once, or reviewed one at a time, biggest waste
+//! first, with what tells the copies apart and a suggested keeper. Resolving
+//! a group keeps one file, which takes the tags, fields, annotations,
+//! collections, links, derivatives, rating and notes of the others, and moves the others to the trash (see
+//! `library::trash`) or out of the way. Each copy is compared byte for byte
+//! with the keeper before it is touched.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Runtime};
+
+use crate::db::duplicates::DuplicateRow;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::indexer::echo;
+use crate::indexer::BatchChangePayload;
+use crate::library::merge;
+use crate::paths;
+
+/// Formats that keep more of the picture than their usual alternatives.
+const LOSSLESS_FORMATS: &[&str] = &["dng", "cr2", "cr3", "nef", "arw", "raf", "orf", "rw2", "tif", "tiff", "png", "psd", "exr"];
+
+/// A file of a duplicate group.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateFile {
+    pub id: i64,
+    pub path: String,
+    pub filename: String,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub size: i64,
+    pub format: String,
+    pub rating: i32,
+    pub tag_count: i64,
+    pub annotation_count: i64,
+    pub has_notes: bool,
+    pub has_capture_time: bool,
+    /// How much was added to the file in the library: one point per tag and
+    /// annotation, plus one each for a rating, notes and a capture time.
+    pub metadata_score: i64,
+}
+
+impl DuplicateFile {
+    fn from_row(row: DuplicateRow) -> Self {
+        let rating = row.rating.unwrap_or(0);
+        let metadata_score = row.tag_count
+            + row.annotation_count
+            + (rating > 0) as i64
+            + row.has_notes as i64
+            + row.has_capture_time as i64;
+        DuplicateFile {
+            id: row.id,
+            path: row.path,
+            filename: row.filename,
+            width: row.width,
+            height: row.height,
+            size: row.size,
+            format: row.format,
+            rating,
+            tag_count: row.tag_count,
+            annotation_count: row.annotation_count,
+            has_notes: row.has_notes,
+            has_capture_time: row.has_capture_time,
+            metadata_score,
+        }
+    }
+
+    fn pixels(&self) -> i64 {
+        self.width.unwrap_or(0) as i64 * self.height.unwrap_or(0) as i64
+    }
+
+    fn is_lossless(&self) -> bool {
+        LOSSLESS_FORMATS.contains(&self.format.to_lowercase().as_str())
+    }
+}
+
+/// A group of copies, as reviewed.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    /// Position of the group, from 0.
+    pub index: usize,
+    /// Number of groups in the library.
+    pub group_count: usize,
+    pub files: Vec<DuplicateFile>,
+    /// The file worth keeping, by resolution, format, metadata, then size.
+    pub suggested_keep_id: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateAction {
+    /// Moves the other files to the trash, from which they can be restored.
+    Delete,
+    /// Moves the other files to a folder, out of the library's way.
+    Move,
+}
+
+/// Outcome of [`resolve`].
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateResolution {
+    pub kept_id: i64,
+    /// Images merged into the keeper.
+    pub removed: Vec<i64>,
+    /// Files left alone, with the reason.
+    pub skipped: Vec<String>,
+    pub freed_bytes: u64,
+}
+
+/// The file to keep: the largest picture, then a lossless format, then the
+/// most metadata, then the largest file, then the oldest in the library.
+pub fn suggest_keeper(files: &[DuplicateFile]) -> Option<i64> {
+    files
+        .iter()
+        .max_by_key(|file| (file.pixels(), file.is_lossless(), file.metadata_score, file.size, std::cmp::Reverse(file.id)))
+        .map(|file| file.id)
+}
+
+/// A group of identical files, as listed by [`find_duplicates`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateSet {
+    pub content_hash: String,
+    pub size: i64,
+    /// Bytes taken by all the copies but one.
+    pub wasted_bytes: i64,
+    pub files: Vec<DuplicateFile>,
+}
+
+/// Every group of identical files in the library, wherever they are, those
+/// wasting the most space first.
+pub async fn find_duplicates(db: &Db) -> AppResult<Vec<DuplicateSet>> {
+    let groups = db.get_duplicate_groups().await?;
+    let ids: Vec<i64> = groups.iter().flat_map(|group| group.ids.iter().copied()).collect();
+    let mut files: HashMap<i64, DuplicateFile> =
+        db.get_duplicate_rows(&ids).await?.into_iter().map(|row| (row.id, DuplicateFile::from_row(row))).collect();
+    Ok(groups
+        .into_iter()
+        .map(|group| DuplicateSet {
+            wasted_bytes: group.wasted_bytes(),
+            files: group.ids.iter().filter_map(|id| files.remove(id)).collect(),
+            content_hash: group.content_hash,
+            size: group.size,
+        })
+        .collect())
+}
+
+/// Group `index` of the library's duplicates, `None` past the last one.
+pub async fn get_group(db: &Db, index: usize) -> AppResult<Option<DuplicateGroup>> {
+    let groups = db.get_duplicate_groups().await?;
+    let Some(group) = groups.get(index) else {
+        return Ok(None);
+    };
+    let files: Vec<DuplicateFile> = db.get_duplicate_rows(&group.ids).await?.into_iter().map(DuplicateFile::from_row).collect();
+    let Some(suggested_keep_id) = suggest_keeper(&files) else {
+        return Ok(None);
+    };
+    Ok(Some(DuplicateGroup { index, group_count: groups.len(), files, suggested_keep_id }))
+}
+
+/// Moves a file, copying it when `to` is on another volume.
+pub(crate) fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(from, to)?;
+    std::fs::remove_file(from)
+}
+
+/// Keeps `keep_id` and removes the other copies of its group: trashed, or
+/// moved to `destination` for [`DuplicateAction::Move`].
+pub async fn resolve<R: Runtime>(
+    app: &AppHandle<R>,
+    db: &Db,
+    keep_id: i64,
+    action: DuplicateAction,
+    destination: Option<&Path>,
+) -> AppResult<DuplicateResolution> {
+    let destination = match action {
+        DuplicateAction::Move => {
+            let destination = destination
+                .filter(|d| d.is_absolute())
+                .ok_or_else(|| AppError::Generic("Moving duplicates needs an absolute destination folder".to_string()))?;
+            Some(destination.to_path_buf())
+        }
+        DuplicateAction::Delete => None,
+    };
+
+    let group = db
+        .get_duplicate_groups()
+        .await?
+        .into_iter()
+        .find(|group| group.ids.contains(&keep_id))
+        .ok_or_else(|| AppError::NotFound(format!("Image {} has no duplicates", keep_id)))?;
+    let files = db.get_duplicate_rows(&group.ids).await?;
+    let keeper = files
+        .iter()
+        .find(|file| file.id == keep_id)
+        .map(|file| paths::from_db(&file.path))
+        .ok_or_else(|| AppError::NotFound(format!("Image {}", keep_id)))?;
+    let others: Vec<(i64, String, i64)> =
+        files.into_iter().filter(|file| file.id != keep_id).map(|file| (file.id, file.path, file.size)).collect();
+
+    let sizes: HashMap<i64, i64> = others.iter().map(|(id, _, size)| (*id, *size)).collect();
+
+    echo::expect_changes(others.iter().map(|(_, path, _)| path.clone()));
+    let mut outcome = tauri::async_runtime::spawn_blocking(move || -> std::io::Result<DuplicateResolution> {
+        if let Some(destination) = &destination {
+            std::fs::create_dir_all(destination)?;
+        }
+        let mut resolution = DuplicateResolution { kept_id: keep_id, ..Default::default() };
+        let mut planned = HashSet::new();
+        for (id, path, size) in others {
+            let source = &paths::from_db(&path);
+            if !merge::same_content(source, &keeper) {
+                resolution.skipped.push(format!("{}: not the same content as the kept file", path));
+                continue;
+            }
+            let result = match &destination {
+                Some(destination) => {
+                    let target = merge::free_name(&destination.join(source.file_name().unwrap_or_default()), &planned);
+                    planned.insert(target.to_string_lossy().to_lowercase());
+                    move_file(source, &target)
+                }
+                // Trashed once the keeper has what it holds
+                None => Ok(()),
+            };
+            match result {
+                Ok(()) => {
+                    resolution.removed.push(id);
+                    resolution.freed_bytes += size.max(0) as u64;
+                }
+                Err(e) => resolution.skipped.push(format!("{}: {}", path, e)),
+            }
+        }
+        Ok(resolution)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+
+    match action {
+        DuplicateAction::Move => db.merge_duplicates(keep_id, &outcome.removed).await?,
+        DuplicateAction::Delete => {
+            db.absorb_duplicates(keep_id, &outcome.removed).await?;
+            let trashed = super::trash::delete_images(app, db, &outcome.removed).await?;
+            outcome.skipped.extend(trashed.skipped);
+            // Copies the trash couldn't take are still in the library
+            let left: HashSet<i64> = db.get_images_by_ids(&outcome.removed).await?.into_iter().map(|image| image.id).collect();
+            outcome.removed.retain(|id| !left.contains(id));
+            outcome.freed_bytes = outcome.removed.iter().map(|id| sizes.get(id).copied().unwrap_or(0).max(0) as u64).sum();
+        }
+    }
+    println!(
+        "INFO: Kept image {} and removed {} duplicates ({} skipped)",
+        keep_id,
+        outcome.removed.len(),
+        outcome.skipped.len()
+    );
+    let _ = app.emit("library:batch-change", BatchChangePayload {
+        added: vec![], removed: vec![], updated: vec![], needs_refresh: true
+    });
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::duplicates::HashGroup;
+
+    fn file(id: i64, pixels: (i32, i32), format: &str, metadata_score: i64) -> DuplicateFile {
+        DuplicateFile {
+            id,
+            path: format!("/lib/{}.{}", id, format),
+            filename: format!("{}.{}", id, format),
+            width: Some(pixels.0),
+            height: Some(pixels.1),
+            size: 100,
+            format: format.to_string(),
+            rating: 0,
+            tag_count: metadata_score,
+            annotation_count: 0,
+            has_notes: false,
+            has_capture_time: false,
+            metadata_score,
+        }
+    }
+
+    #[test]
+    fn test_suggest_keeper() {
+        assert_eq!(suggest_keeper(&[file(1, (100, 100), "jpg", 0), file(2, (200, 100), "jpg", 0)]), Some(2));
+        assert_eq!(suggest_keeper(&[file(1, (100, 100), "jpg", 5), file(2, (100, 100), "TIFF", 0)]), Some(2));
+        assert_eq!(suggest_keeper(&[file(1, (100, 100), "jpg", 0), file(2, (100, 100), "jpg", 2)]), Some(2));
+        // All else equal, the first one in the library stays
+        assert_eq!(suggest_keeper(&[file(3, (100, 100), "jpg", 0), file(2, (100, 100), "jpg", 0)]), Some(2));
+        assert_eq!(suggest_keeper(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn test_groups_and_merge() {
+        let library = crate::testkit::TestLibrary::open("duplicates").await;
+        let db = &library.db;
+        library.seed_images(&["a.jpg", "a copy.jpg", "b.jpg", "b copy.jpg", "c.jpg"]).await;
+        sqlx::query("UPDATE images SET size = CASE WHEN id <= 2 THEN 10 ELSE 50 END")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        // c.jpg has its size in common with the b copies, so it needs a hash too
+        let needing: Vec<i64> = db.get_images_needing_content_hash(10).await.unwrap().iter().map(|(id, _)| *id).collect();
+        assert_eq!(needing, vec![1, 2, 3, 4, 5]);
+        let hash = |value: &str| Some(value.to_string());
+        db.set_content_hashes(&[(1, hash("a")), (2, hash("a")), (3, hash("b")), (4, hash("b")), (5, None)]).await.unwrap();
+        assert!(db.get_images_needing_content_hash(10).await.unwrap().is_empty());
+
+        // The group wasting the most space comes first
+        let group_ids = |groups: Vec<HashGroup>| groups.into_iter().map(|group| group.ids).collect::<Vec<_>>();
+        assert_eq!(group_ids(db.get_duplicate_groups().await.unwrap()), vec![vec![3, 4], vec![1, 2]]);
+        let sets = find_duplicates(db).await.unwrap();
+        assert_eq!(sets.iter().map(|set| (set.content_hash.as_str(), set.wasted_bytes)).collect::<Vec<_>>(), vec![("b", 50), ("a", 10)]);
+        assert_eq!(sets[1].files.iter().map(|file| file.path.as_str()).collect::<Vec<_>>(), vec!["/lib/a.jpg", "/lib/a copy.jpg"]);
+
+        sqlx::query("INSERT INTO tags (id, name) VALUES (1, 'Hero')").execute(&db.pool).await.unwrap();
+        sqlx::query("INSERT INTO image_tags (image_id, tag_id) VALUES (4, 1)").execute(&db.pool).await.unwrap();
+        sqlx::query("UPDATE images SET rating = 4 WHERE id = 4").execute(&db.pool).await.unwrap();
+        let rows = db.get_duplicate_rows(&[4, 3]).await.unwrap();
+        let files: Vec<DuplicateFile> = rows.into_iter().map(DuplicateFile::from_row).collect();
+        assert_eq!(files.iter().map(|f| (f.id, f.metadata_score)).collect::<Vec<_>>(), vec![(4, 2), (3, 0)]);
+
+        for statement in [
+            "INSERT INTO collections (id, name) VALUES (1, 'Picks')",
+            "INSERT INTO collection_items (collection_id, image_id, position) VALUES (1, 1, 0), (1, 4, 1), (1, 5, 2)",
+            "INSERT INTO image_fields (image_id, name, value) VALUES (4, 'Client', 'Acme')",
+            "INSERT INTO links (image_id, url) VALUES (4, 'https://example.com')",
+            "INSERT INTO derivatives (image_id, kind, path) VALUES (4, 'export', '/out/b.png')",
+        ] {
+            sqlx::query(statement).execute(&db.pool).await.unwrap();
+        }
+        db.merge_duplicates(3, &[4]).await.unwrap();
+        let (rating, tags, fields, links, derivatives): (i32, i64, i64, i64, i64) = sqlx::query_as(
+            "SELECT rating, (SELECT COUNT(*) FROM image_tags WHERE image_id = 3), (SELECT COUNT(*) FROM image_fields WHERE image_id = 3),
+                (SELECT COUNT(*) FROM links WHERE image_id = 3), (SELECT COUNT(*) FROM derivatives WHERE image_id = 3)
+             FROM images WHERE id = 3"
+        )
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!((rating, tags, fields, links, derivatives), (4, 1, 1, 1, 1));
+        // The kept copy takes the removed one's place in the collection
+        let order: Vec<(i64, i64)> = sqlx::query_as("SELECT image_id, position FROM collection_items WHERE collection_id = 1 ORDER BY position")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(order, vec![(1, 0), (3, 1), (5, 2)]);
+        assert_eq!(group_ids(db.get_duplicate_groups().await.unwrap()), vec![vec![1, 2]]);
+
+        // A changed file is hashed again
+        let mut changed = db.get_images_by_ids(&[1]).await.unwrap().remove(0);
+        changed.modified_at = chrono::Utc::now();
+        db.save_image(1, &changed).await.unwrap();
+        assert_eq!(db.get_images_needing_content_hash(10).await.unwrap(), vec![(1, "/lib/a.jpg".to_string())]);
+    }
+}
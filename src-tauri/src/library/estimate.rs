@@ -0,0 +1,83 @@
+//! Output size forecasting for heavy operations.
+//!
+//! Estimates are intentionally conservative: they are used to warn the user
+//! before a volume fills up, not to report exact numbers.
+
+use crate::transcoding::quality::TranscodeQuality;
+use std::collections::HashMap;
+
+/// Thumbnail size assumed for formats without any generated thumbnail yet.
+pub const DEFAULT_THUMBNAIL_BYTES: u64 = 30 * 1024;
+
+/// Approximate database growth per indexed file (row, FTS and indices).
+pub const DB_BYTES_PER_FILE: u64 = 2 * 1024;
+
+/// Free space kept in reserve on top of the estimate.
+const SAFETY_MARGIN: f64 = 0.10;
+
+/// Average thumbnail size per format, computed from sampled file sizes.
+pub fn average_by_format(samples: &[(String, u64)]) -> HashMap<String, u64> {
+    let mut totals: HashMap<String, (u64, u64)> = HashMap::new();
+    for (format, size) in samples {
+        let entry = totals.entry(format.to_lowercase()).or_default();
+        entry.0 += size;
+        entry.1 += 1;
+    }
+    totals
+        .into_iter()
+        .map(|(format, (sum, count))| (format, sum / count.max(1)))
+        .collect()
+}
+
+/// Predicts the size of a transcoded file.
+///
+/// Uses the target bitrates when the duration is known. Otherwise the
+/// source size is used, since transcodes rarely come out much larger.
+pub fn estimate_transcode_bytes(duration: Option<f64>, source_size: u64, is_video: bool, quality: TranscodeQuality) -> u64 {
+    match duration {
+        Some(secs) if secs > 0.0 => {
+            let bits_per_sec = if is_video {
+                quality.video_bitrate() as f64 + quality.audio_bitrate() as f64
+            } else {
+                quality.audio_bitrate() as f64
+            };
+            (secs * bits_per_sec / 8.0) as u64
+        }
+        _ => source_size,
+    }
+}
+
+/// Returns `true` if `available` bytes leave the safety margin after `estimated`.
+pub fn has_enough_space(estimated: u64, available: u64) -> bool {
+    (estimated as f64) * (1.0 + SAFETY_MARGIN) <= available as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_average_by_format() {
+        let samples = vec![
+            ("JPG".to_string(), 10_000),
+            ("jpg".to_string(), 30_000),
+            ("png".to_string(), 5_000),
+        ];
+        let avg = average_by_format(&samples);
+        assert_eq!(avg.get("jpg"), Some(&20_000));
+        assert_eq!(avg.get("png"), Some(&5_000));
+    }
+
+    #[test]
+    fn test_transcode_estimate() {
+        // 10s of audio at 256 kbps
+        assert_eq!(estimate_transcode_bytes(Some(10.0), 0, false, TranscodeQuality::Standard), 320_000);
+        assert_eq!(estimate_transcode_bytes(None, 1234, true, TranscodeQuality::High), 1234);
+    }
+
+    #[test]
+    fn test_has_enough_space() {
+        assert!(has_enough_space(100, 110));
+        assert!(!has_enough_space(100, 109));
+    }
+}
@@ -0,0 +1,420 @@
+//! Grid windows kept by the backend, updated with row-level deltas.
+//!
+//! The grid used to run its whole filter query again after every watcher
+//! batch, and replace what it showed. Instead, the grid subscribes to its
+//! filter with the window of rows it shows; the backend keeps those rows
+//! and, after each batch, runs the window again and sends only what changed
+//! as `grid:delta` events: rows removed, inserted at an index, moved or
+//! updated. Deltas carry a version, so the frontend can tell it missed one
+//! and subscribe again. Refreshes run on their own task, at most once per
+//! [`REFRESH_DELAY`], so a burst of watcher batches costs one run.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+use crate::db::search::ImageFilter;
+use crate::db::Db;
+
+/// Event carrying deltas of subscribed windows.
+pub const DELTA_EVENT: &str = "grid:delta";
+
+/// Most rows a window holds. Grids scrolled further page as before.
+const MAX_WINDOW: i32 = 20_000;
+
+/// Most subscriptions kept; the oldest is dropped past it, in case a
+/// frontend reloaded without unsubscribing.
+const MAX_SUBSCRIPTIONS: usize = 8;
+
+/// Wait before refreshing the windows after a change, gathering the changes
+/// that follow it.
+const REFRESH_DELAY: Duration = Duration::from_millis(300);
+
+/// Rows of a filter a grid shows.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterWindow {
+    pub offset: i32,
+    pub limit: i32,
+}
+
+/// A change to a window. Applied in order, each against the rows as the
+/// previous one left them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum RowDelta {
+    /// The row left the window.
+    Remove { id: i64 },
+    /// The row entered the window at `index`.
+    Insert { index: usize, item: Value },
+    /// The row is taken out and put back at `index`.
+    Move { id: i64, index: usize },
+    /// The row changed in place.
+    Update { item: Value },
+}
+
+/// Changes to a subscribed window.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterDelta {
+    pub subscription_id: u64,
+    /// One more than the previous delta of the subscription; the first is 1.
+    pub version: u64,
+    /// Images matching the filter, in and out of the window.
+    pub total: i64,
+    pub ops: Vec<RowDelta>,
+}
+
+struct Subscription {
+    filter: ImageFilter,
+    version: u64,
+    total: i64,
+    /// Rows of the window as last sent: id and a hash of the row.
+    rows: Vec<(i64, u64)>,
+}
+
+impl Subscription {
+    /// Runs the window again and returns what changed since the last run,
+    /// and whether anything did.
+    async fn refresh(&mut self, id: u64, db: &Db) -> Result<(FilterDelta, bool), sqlx::Error> {
+        let (images, total) = db.get_filter_window(&self.filter).await?;
+        let items: Vec<Value> = images.iter().map(|image| serde_json::to_value(image).unwrap_or(Value::Null)).collect();
+        let rows: Vec<(i64, u64)> = images.iter().zip(&items).map(|(image, item)| (image.id, row_hash(item))).collect();
+        let ops = diff(&self.rows, &rows, |index| items[index].clone());
+        let changed = !ops.is_empty() || total != self.total;
+        self.rows = rows;
+        self.total = total;
+        // An unchanged window doesn't spend a version, so none goes missing
+        if changed {
+            self.version += 1;
+        }
+        Ok((FilterDelta { subscription_id: id, version: self.version, total, ops }, changed))
+    }
+
+    /// Moves or resizes the window. A full window grown at its end, as when
+    /// the grid pages, only queries the rows past its old end.
+    async fn resize(&mut self, id: u64, db: &Db, window: FilterWindow) -> Result<FilterDelta, sqlx::Error> {
+        let (old_offset, old_limit) = (self.filter.offset.unwrap_or(0), self.filter.limit.unwrap_or(0));
+        self.filter = windowed(self.filter.clone(), window);
+        let (offset, limit) = (self.filter.offset.unwrap_or(0), self.filter.limit.unwrap_or(0));
+        if offset != old_offset || limit <= old_limit || self.rows.len() != old_limit as usize {
+            return Ok(self.refresh(id, db).await?.0);
+        }
+
+        let mut slice = self.filter.clone();
+        slice.offset = Some(offset + old_limit);
+        slice.limit = Some(limit - old_limit);
+        let (images, total) = db.get_filter_window(&slice).await?;
+        // Rows shifted by a change not refreshed yet may show up again; the refresh sorts them out
+        let known: HashSet<i64> = self.rows.iter().map(|(row_id, _)| *row_id).collect();
+        let mut ops = Vec::with_capacity(images.len());
+        for image in images.iter().filter(|image| !known.contains(&image.id)) {
+            let item = serde_json::to_value(image).unwrap_or(Value::Null);
+            ops.push(RowDelta::Insert { index: self.rows.len(), item: item.clone() });
+            self.rows.push((image.id, row_hash(&item)));
+        }
+        if !ops.is_empty() || total != self.total {
+            self.version += 1;
+        }
+        self.total = total;
+        Ok(FilterDelta { subscription_id: id, version: self.version, total, ops })
+    }
+}
+
+/// Subscribed windows, managed as Tauri state.
+#[derive(Default)]
+pub struct FilterSubscriptions {
+    next_id: Mutex<u64>,
+    subscriptions: Mutex<HashMap<u64, Arc<tokio::sync::Mutex<Subscription>>>>,
+    /// Whether a refresh is scheduled and not started yet.
+    refresh_pending: AtomicBool,
+}
+
+impl FilterSubscriptions {
+    fn get(&self, id: u64) -> Option<Arc<tokio::sync::Mutex<Subscription>>> {
+        self.subscriptions.lock().unwrap_or_else(|e| e.into_inner()).get(&id).cloned()
+    }
+
+    /// Subscribes to `filter` over `window`. The returned delta inserts every
+    /// row of the window.
+    pub async fn subscribe(&self, db: &Db, filter: ImageFilter, window: FilterWindow) -> Result<FilterDelta, sqlx::Error> {
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap_or_else(|e| e.into_inner());
+            *next_id += 1;
+            *next_id
+        };
+        // A negative total makes the first refresh count as a change, even for an empty window
+        let mut subscription = Subscription { filter: windowed(filter, window), version: 0, total: -1, rows: Vec::new() };
+        let (delta, _) = subscription.refresh(id, db).await?;
+
+        let mut subscriptions = self.subscriptions.lock().unwrap_or_else(|e| e.into_inner());
+        subscriptions.insert(id, Arc::new(tokio::sync::Mutex::new(subscription)));
+        while subscriptions.len() > MAX_SUBSCRIPTIONS {
+            let Some(oldest) = subscriptions.keys().min().copied() else {
+                break;
+            };
+            subscriptions.remove(&oldest);
+        }
+        Ok(delta)
+    }
+
+    /// Moves or resizes the window of subscription `id`. `None` when there
+    /// is no such subscription.
+    pub async fn set_window(&self, db: &Db, id: u64, window: FilterWindow) -> Result<Option<FilterDelta>, sqlx::Error> {
+        let Some(subscription) = self.get(id) else {
+            return Ok(None);
+        };
+        let mut subscription = subscription.lock().await;
+        Ok(Some(subscription.resize(id, db, window).await?))
+    }
+
+    /// Drops subscription `id`; its window gets no more deltas.
+    pub fn unsubscribe(&self, id: u64) {
+        self.subscriptions.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+    }
+
+    /// Runs every window again, returning the deltas of those that changed.
+    pub async fn refresh_all(&self, db: &Db) -> Vec<FilterDelta> {
+        let subscriptions: Vec<(u64, Arc<tokio::sync::Mutex<Subscription>>)> =
+            self.subscriptions.lock().unwrap_or_else(|e| e.into_inner()).iter().map(|(id, s)| (*id, s.clone())).collect();
+        let mut deltas = Vec::new();
+        for (id, subscription) in subscriptions {
+            match subscription.lock().await.refresh(id, db).await {
+                Ok((delta, true)) => deltas.push(delta),
+                Ok(_) => {}
+                Err(e) => eprintln!("WARN: Could not refresh grid subscription {}: {}", id, e),
+            }
+        }
+        deltas
+    }
+}
+
+/// Schedules sending the deltas of every subscribed window after a batch of
+/// changes. Returns at once; changes notified before the refresh starts are
+/// covered by it.
+pub fn notify_changes<R: Runtime>(app: &AppHandle<R>) {
+    let Some(subscriptions) = app.try_state::<Arc<FilterSubscriptions>>() else {
+        return;
+    };
+    let Some(db) = app.try_state::<Arc<Db>>() else {
+        return;
+    };
+    if subscriptions.refresh_pending.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let subscriptions = subscriptions.inner().clone();
+    let db = db.inner().clone();
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(REFRESH_DELAY).await;
+        // Cleared before the run, so changes made during it schedule another
+        subscriptions.refresh_pending.store(false, Ordering::SeqCst);
+        for delta in subscriptions.refresh_all(&db).await {
+            let _ = app.emit(DELTA_EVENT, delta);
+        }
+    });
+}
+
+fn row_hash(item: &Value) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    item.to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+fn windowed(mut filter: ImageFilter, window: FilterWindow) -> ImageFilter {
+    filter.offset = Some(window.offset.max(0));
+    filter.limit = Some(window.limit.clamp(0, MAX_WINDOW));
+    filter
+}
+
+/// Rows of `current` that can stay where they are: the longest run whose
+/// order `new` keeps. Every other row present in both has moved.
+fn stable_ids(current: &[i64], new: &[(i64, u64)]) -> HashSet<i64> {
+    let positions: HashMap<i64, usize> = new.iter().enumerate().map(|(index, (id, _))| (*id, index)).collect();
+    let sequence: Vec<usize> = current.iter().map(|id| positions[id]).collect();
+
+    // Longest increasing subsequence, by patience sorting
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; sequence.len()];
+    for (i, position) in sequence.iter().enumerate() {
+        let at = tails.partition_point(|&tail| sequence[tail] < *position);
+        previous[i] = at.checked_sub(1).map(|before| tails[before]);
+        if at == tails.len() {
+            tails.push(i);
+        } else {
+            tails[at] = i;
+        }
+    }
+    let mut stable = HashSet::new();
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        stable.insert(current[i]);
+        next = previous[i];
+    }
+    stable
+}
+
+/// Changes turning the rows `old` into `new`, both as ids and row hashes.
+/// `item` gives the row at an index of `new`, for inserts and updates.
+pub fn diff(old: &[(i64, u64)], new: &[(i64, u64)], item: impl Fn(usize) -> Value) -> Vec<RowDelta> {
+    let new_ids: HashSet<i64> = new.iter().map(|(id, _)| *id).collect();
+    let old_rows: HashMap<i64, u64> = old.iter().copied().collect();
+    let mut ops = Vec::new();
+
+    let mut current = Vec::with_capacity(old.len());
+    for (id, _) in old {
+        if new_ids.contains(id) {
+            current.push(*id);
+        } else {
+            ops.push(RowDelta::Remove { id: *id });
+        }
+    }
+
+    let stable = stable_ids(&current, new);
+    for (index, (id, row)) in new.iter().enumerate() {
+        if stable.contains(id) {
+            // Rows in the way have moved further down; they wait at the end
+            while current[index] != *id {
+                let displaced = current.remove(index);
+                current.push(displaced);
+                ops.push(RowDelta::Move { id: displaced, index: current.len() - 1 });
+            }
+        } else if current.get(index) != Some(id) {
+            match current[index..].iter().position(|c| c == id) {
+                Some(offset) => {
+                    current.remove(index + offset);
+                    current.insert(index, *id);
+                    ops.push(RowDelta::Move { id: *id, index });
+                }
+                None => {
+                    current.insert(index, *id);
+                    ops.push(RowDelta::Insert { index, item: item(index) });
+                }
+            }
+        }
+        if old_rows.get(id).is_some_and(|old| old != row) {
+            ops.push(RowDelta::Update { item: item(index) });
+        }
+    }
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(ids: &[i64]) -> Vec<(i64, u64)> {
+        ids.iter().map(|id| (*id, 0)).collect()
+    }
+
+    fn item(rows: &[(i64, u64)]) -> impl Fn(usize) -> Value + '_ {
+        move |index| serde_json::json!({ "id": rows[index].0, "hash": rows[index].1 })
+    }
+
+    /// Applies `ops` the way the frontend does.
+    fn apply(old: &[(i64, u64)], ops: &[RowDelta]) -> Vec<(i64, u64)> {
+        let mut rows = old.to_vec();
+        for op in ops {
+            match op {
+                RowDelta::Remove { id } => rows.retain(|(row_id, _)| row_id != id),
+                RowDelta::Insert { index, item } => {
+                    rows.insert(*index, (item["id"].as_i64().unwrap(), item["hash"].as_u64().unwrap()))
+                }
+                RowDelta::Move { id, index } => {
+                    let from = rows.iter().position(|(row_id, _)| row_id == id).unwrap();
+                    let row = rows.remove(from);
+                    rows.insert(*index, row);
+                }
+                RowDelta::Update { item } => {
+                    let id = item["id"].as_i64().unwrap();
+                    rows.iter_mut().find(|(row_id, _)| *row_id == id).unwrap().1 = item["hash"].as_u64().unwrap();
+                }
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn test_diff_rebuilds_the_new_window() {
+        let cases: &[(&[i64], &[i64])] = &[
+            (&[], &[1, 2, 3]),
+            (&[1, 2, 3], &[]),
+            (&[1, 2, 3], &[0, 1, 2]),
+            (&[1, 2, 3, 4, 5, 6], &[2, 3, 4, 5, 6, 1]),
+            (&[1, 2, 3, 4, 5, 6], &[6, 1, 2, 3, 4, 5]),
+            (&[1, 2, 3], &[2, 9, 3, 1]),
+            (&[1, 2, 3, 4], &[4, 3, 2, 1]),
+            (&[5, 1, 7, 2, 8], &[1, 2, 3, 5, 8, 7]),
+        ];
+        for (old, new) in cases {
+            let (old, new) = (rows(old), rows(new));
+            assert_eq!(apply(&old, &diff(&old, &new, item(&new))), new, "{:?} -> {:?}", old, new);
+        }
+    }
+
+    #[test]
+    fn test_diff_is_small_for_small_changes() {
+        let old = rows(&[1, 2, 3, 4, 5, 6]);
+        assert!(diff(&old, &old, item(&old)).is_empty());
+
+        // One row moving to the end is one move, not five
+        let moved = rows(&[2, 3, 4, 5, 6, 1]);
+        assert_eq!(diff(&old, &moved, item(&moved)), vec![RowDelta::Move { id: 1, index: 5 }]);
+        let shifted = rows(&[0, 1, 2, 3, 4, 5]);
+        assert_eq!(
+            diff(&old, &shifted, item(&shifted)),
+            vec![RowDelta::Remove { id: 6 }, RowDelta::Insert { index: 0, item: serde_json::json!({ "id": 0, "hash": 0 }) }]
+        );
+
+        let mut renamed = old.clone();
+        renamed[2].1 = 7;
+        assert_eq!(
+            diff(&old, &renamed, item(&renamed)),
+            vec![RowDelta::Update { item: serde_json::json!({ "id": 3, "hash": 7 }) }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_follow_the_library() {
+        let library = &crate::testkit::TestLibrary::open("filter-subscriptions").await;
+        let db = &library.db;
+        let insert = |names: &'static [&'static str]| async move {
+            let ids = library.seed_images(names).await;
+            let images: Vec<(i64, String)> = ids.into_iter().zip(names.iter().map(|name| name.to_string())).collect();
+            db.set_filename_sort_keys(&images).await.unwrap();
+        };
+        insert(&["a.jpg", "c.jpg", "e.jpg"]).await;
+
+        let subscriptions = FilterSubscriptions::default();
+        let filter = ImageFilter { sort_by: Some("filename".into()), sort_order: Some("asc".into()), ..Default::default() };
+        let first = subscriptions.subscribe(db, filter, FilterWindow { offset: 0, limit: 2 }).await.unwrap();
+        assert_eq!((first.version, first.total, first.ops.len()), (1, 3, 2));
+        assert!(subscriptions.refresh_all(db).await.is_empty());
+
+        insert(&["b.jpg"]).await;
+        let deltas = subscriptions.refresh_all(db).await;
+        assert_eq!(deltas.len(), 1);
+        assert_eq!((deltas[0].version, deltas[0].total), (2, 4));
+        assert!(matches!(deltas[0].ops.as_slice(), [RowDelta::Remove { id: 2 }, RowDelta::Insert { index: 1, .. }]));
+
+        let grown = subscriptions
+            .set_window(db, first.subscription_id, FilterWindow { offset: 0, limit: 4 })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(grown.version, 3);
+        assert!(matches!(grown.ops.as_slice(), [RowDelta::Insert { index: 2, .. }, RowDelta::Insert { index: 3, .. }]));
+        // Only the new rows were queried, and they are the ones a full run finds
+        assert!(subscriptions.refresh_all(db).await.is_empty());
+
+        subscriptions.unsubscribe(first.subscription_id);
+        assert!(subscriptions.set_window(db, first.subscription_id, FilterWindow { offset: 0, limit: 1 }).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,540 @@
+//! Memory card offload.
+//!
+//! A detector polls the places removable volumes are mounted and, when one
+//! with a camera `DCIM` folder appears, emits `import:source-detected` with a
+//! summary of its media so the UI can offer an import (`import:source-removed`
+//! when it goes away). The import is an `ingest_card` job: files are copied
+//! to the destination, optionally into dated folders and under new names
+//! built from rename templates (see `rename`), keep their modification time,
+//! and are tagged with the shoot's session. Files copied by an earlier import
+//! of the same card are skipped, so a card can be offloaded again after more
+//! shooting. The copies are recorded in the operations ledger first, so an
+//! import cut short by a crash can be finished or undone, and a cancelled one
+//! removes what it copied (see `library::operations`).
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Runtime};
+use tokio::time::{sleep, Duration};
+
+use crate::db::models::ImportedMetadata;
+use crate::db::operations::OPERATION_COMPLETED;
+use crate::db::Db;
+use crate::formats::{FileFormat, MediaType};
+use crate::jobs::JobContext;
+use crate::library::apple_photos::unique_name;
+use crate::library::rename::{render_template, RenameContext};
+use crate::media::capture_time::{self, DefaultZone};
+
+/// Importer name recorded with queued metadata.
+pub const SOURCE: &str = "card";
+
+/// Ledger kind of the copies of an import.
+pub const COPY_OPERATION: &str = "card_ingest";
+
+/// Parent tag of the tags created for sessions, shared with capture
+/// sessions.
+pub const SESSIONS_TAG: &str = "Sessions";
+
+/// Folder cameras write to (DCF standard).
+const CAMERA_FOLDER: &str = "DCIM";
+
+/// Name template used when none is given: the camera's own names.
+const DEFAULT_NAME_TEMPLATE: &str = "{original}";
+
+/// How often mounted volumes are listed again.
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// A mounted card, as sent with `import:source-detected`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSource {
+    /// Mount point of the volume.
+    pub root: String,
+    /// Volume label, or the mount point when it has none.
+    pub name: String,
+    /// Media files under the camera folder.
+    pub count: usize,
+    /// How many of `count` are photos and videos.
+    pub photos: usize,
+    pub videos: usize,
+    /// Size of the media files, in bytes.
+    pub total_bytes: u64,
+    /// Oldest and newest modification times, as the camera's wall clock.
+    pub earliest: Option<DateTime<Utc>>,
+    pub latest: Option<DateTime<Utc>>,
+}
+
+/// What an `ingest_card` job copies, and where.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestOptions {
+    /// Mount point of the card.
+    pub source: String,
+    /// Folder receiving the copies, added as a location.
+    pub destination: String,
+    /// Template of the new filenames, `{original}` when missing.
+    pub name_template: Option<String>,
+    /// Template of the subfolder of `destination` each file goes to, with
+    /// `/` between levels, such as `{date:yyyy}/{date:yyyy-MM-dd}`. Files go
+    /// to `destination` itself when missing.
+    pub folder_template: Option<String>,
+    /// Tag applied to every file copied, under `Sessions`.
+    pub session: Option<String>,
+}
+
+/// Summary returned as the job result.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestReport {
+    /// Media files on the card.
+    pub found: usize,
+    pub copied: usize,
+    /// Files already at their destination, from an earlier import.
+    pub skipped: usize,
+    /// One message per file that couldn't be copied.
+    pub failed: Vec<String>,
+    pub copied_bytes: u64,
+    /// Folder added as a location.
+    pub location: String,
+}
+
+/// A media file on a card.
+#[derive(Debug, Clone)]
+struct CardFile {
+    path: PathBuf,
+    size: u64,
+    video: bool,
+    /// Modification time, as the camera's wall clock.
+    modified: Option<DateTime<Utc>>,
+}
+
+/// What an ingest does with one file.
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Copy(PathBuf),
+    /// Already there from an earlier import.
+    Skip(PathBuf),
+}
+
+/// Cards mounted at the last poll.
+static DETECTED: Mutex<Vec<ImportSource>> = Mutex::new(Vec::new());
+
+/// Cards mounted now, to offer an import on windows opened after the event.
+pub fn sources() -> Vec<ImportSource> {
+    DETECTED.lock().map(|detected| detected.clone()).unwrap_or_default()
+}
+
+/// Cameras write local time without a zone; read it back the same way, so
+/// dates match the ones in the EXIF data.
+fn wall_clock(time: SystemTime) -> DateTime<Utc> {
+    DateTime::<Local>::from(time).naive_local().and_utc()
+}
+
+/// The `DCIM` folder of a volume, whatever its case.
+fn camera_folder(root: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(root)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .find(|e| e.file_name().to_string_lossy().eq_ignore_ascii_case(CAMERA_FOLDER) && e.path().is_dir())
+        .map(|e| e.path())
+}
+
+/// Photos and videos under the `DCIM` folder of `root`, oldest first.
+fn list_media(root: &Path) -> Vec<CardFile> {
+    let Some(dcim) = camera_folder(root) else {
+        return Vec::new();
+    };
+    let mut files: Vec<CardFile> = walkdir::WalkDir::new(dcim)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| {
+            let video = match FileFormat::detect_extension(e.path())?.type_category {
+                MediaType::Image => false,
+                MediaType::Video => true,
+                _ => return None,
+            };
+            let metadata = e.metadata().ok()?;
+            Some(CardFile {
+                path: e.into_path(),
+                size: metadata.len(),
+                video,
+                modified: metadata.modified().ok().map(wall_clock),
+            })
+        })
+        .collect();
+    files.sort_by(|a, b| a.modified.cmp(&b.modified).then_with(|| a.path.cmp(&b.path)));
+    files
+}
+
+/// Summary of the card mounted at `root`.
+pub fn summarize(root: &Path) -> ImportSource {
+    let files = list_media(root);
+    let videos = files.iter().filter(|f| f.video).count();
+    ImportSource {
+        root: root.to_string_lossy().to_string(),
+        name: root
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| root.to_string_lossy().to_string()),
+        count: files.len(),
+        photos: files.len() - videos,
+        videos,
+        total_bytes: files.iter().map(|f| f.size).sum(),
+        earliest: files.iter().filter_map(|f| f.modified).min(),
+        latest: files.iter().filter_map(|f| f.modified).max(),
+    }
+}
+
+/// Folders removable volumes are mounted in.
+///
+/// Drive letters and types come from the volume manager, so polling never
+/// touches the drives themselves and sleeping disks stay asleep.
+#[cfg(windows)]
+fn volume_roots() -> Vec<PathBuf> {
+    use windows::core::HSTRING;
+    use windows::Win32::Storage::FileSystem::{GetDriveTypeW, GetLogicalDrives};
+
+    /// `GetDriveTypeW` result of card readers and USB sticks.
+    const DRIVE_REMOVABLE: u32 = 2;
+
+    // SAFETY: takes no arguments and only returns a bitmask
+    let drives = unsafe { GetLogicalDrives() };
+    // A: and B: are floppies
+    ('C'..='Z')
+        .enumerate()
+        .filter(|(index, _)| drives & (1 << (index + 2)) != 0)
+        .map(|(_, letter)| format!("{}:\\", letter))
+        // SAFETY: the root path is a valid, NUL-terminated wide string
+        .filter(|root| unsafe { GetDriveTypeW(&HSTRING::from(root.as_str())) } == DRIVE_REMOVABLE)
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Folders removable volumes are mounted in.
+#[cfg(target_os = "macos")]
+fn volume_roots() -> Vec<PathBuf> {
+    children(Path::new("/Volumes"))
+}
+
+/// Folders removable volumes are mounted in: udisks puts them under
+/// `/media/$USER` or `/run/media/$USER`, older setups under `/media` and
+/// `/mnt`.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn volume_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if let Ok(user) = std::env::var("USER") {
+        roots.extend(children(&Path::new("/media").join(&user)));
+        roots.extend(children(&Path::new("/run/media").join(&user)));
+    }
+    roots.extend(children(Path::new("/media")));
+    roots.extend(children(Path::new("/mnt")));
+    roots
+}
+
+#[cfg(unix)]
+fn children(dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_dir()).collect())
+        .unwrap_or_default()
+}
+
+/// Mount points of the volumes holding a `DCIM` folder.
+fn mounted_cards() -> Vec<String> {
+    volume_roots()
+        .into_iter()
+        .filter(|root| camera_folder(root).is_some())
+        .map(|root| root.to_string_lossy().to_string())
+        .collect()
+}
+
+/// Starts the card detector on the async runtime.
+///
+/// Mounted volumes are listed every few seconds; cards showing up or going
+/// away are sent as `import:source-detected` and `import:source-removed`, and
+/// the current ones are kept for `sources`.
+pub fn start<R: Runtime>(app: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let known: Vec<String> = sources().into_iter().map(|s| s.root).collect();
+            let polled = tauri::async_runtime::spawn_blocking(move || {
+                let mounted = mounted_cards();
+                // Cards are only walked once, when they show up
+                let added: Vec<ImportSource> =
+                    mounted.iter().filter(|root| !known.contains(root)).map(|root| summarize(Path::new(root))).collect();
+                let removed: Vec<String> = known.into_iter().filter(|root| !mounted.contains(root)).collect();
+                (added, removed)
+            })
+            .await;
+
+            if let Ok((added, removed)) = polled {
+                if let Ok(mut detected) = DETECTED.lock() {
+                    detected.retain(|s| !removed.contains(&s.root));
+                    detected.extend(added.iter().cloned());
+                }
+                for root in removed {
+                    println!("INFO: Card removed: {}", root);
+                    let _ = app.emit("import:source-removed", serde_json::json!({ "root": root }));
+                }
+                for source in added {
+                    println!("INFO: Card detected: {} ({} files)", source.root, source.count);
+                    let _ = app.emit("import:source-detected", &source);
+                }
+            }
+            sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+/// Capture time of a photo from its EXIF data, as the camera's wall clock
+/// like `CardFile::modified`.
+fn exif_capture_time(path: &Path) -> Option<DateTime<Utc>> {
+    let (raw, _) = crate::media::metadata_reader::read_capture_time(path)?;
+    capture_time::normalize(&raw, None, DefaultZone::utc()).map(|time| time.utc)
+}
+
+/// Decides where each file goes, in order. `dates` holds the date each
+/// file's templates render with.
+fn plan(files: &[CardFile], dates: &[DateTime<Utc>], options: &IngestOptions, destination: &Path) -> Result<Vec<Step>, String> {
+    let name_template = options.name_template.as_deref().filter(|t| !t.trim().is_empty()).unwrap_or(DEFAULT_NAME_TEMPLATE);
+    let folder_template = options.folder_template.as_deref().filter(|t| !t.trim().is_empty());
+    let mut taken: HashMap<PathBuf, HashSet<String>> = HashMap::new();
+    let mut steps = Vec::with_capacity(files.len());
+
+    for (index, (file, date)) in files.iter().zip(dates).enumerate() {
+        let stem = file.path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let extension = file.path.extension().map(|e| e.to_string_lossy().to_string());
+        let mut ctx = RenameContext {
+            original_stem: &stem,
+            extension: None,
+            seq: index as u32 + 1,
+            created_at: *date,
+            tags: &[],
+        };
+
+        let mut folder = destination.to_path_buf();
+        for level in folder_template.iter().flat_map(|t| t.split('/')).filter(|l| !l.trim().is_empty()) {
+            folder.push(render_template(level, &ctx)?);
+        }
+        ctx.extension = extension.as_deref();
+        let name = render_template(name_template, &ctx)?;
+
+        let names = taken.entry(folder.clone()).or_insert_with(|| {
+            std::fs::read_dir(&folder)
+                .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.file_name().to_string_lossy().to_lowercase()).collect())
+                .unwrap_or_default()
+        });
+        let existing = folder.join(&name);
+        if names.contains(&name.to_lowercase()) && std::fs::metadata(&existing).is_ok_and(|m| m.len() == file.size) {
+            steps.push(Step::Skip(existing));
+        } else {
+            steps.push(Step::Copy(folder.join(unique_name(&name, names))));
+        }
+    }
+    Ok(steps)
+}
+
+/// Copies `source` to `target` through a hidden partial file, so a cancelled
+/// or failed copy never looks finished, keeping its modification time.
+pub(crate) fn copy_file(source: &Path, target: &Path) -> std::io::Result<u64> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let name = target.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let partial = target.with_file_name(format!(".{}.part", name));
+    let result = (|| -> std::io::Result<u64> {
+        let bytes = std::fs::copy(source, &partial)?;
+        if let Ok(modified) = std::fs::metadata(source).and_then(|m| m.modified()) {
+            std::fs::File::options().write(true).open(&partial)?.set_modified(modified)?;
+        }
+        std::fs::rename(&partial, target)?;
+        Ok(bytes)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    result
+}
+
+/// Runs an `ingest_card` job.
+pub async fn ingest(app: &AppHandle, db: &Db, options: &IngestOptions, ctx: &JobContext) -> Result<IngestReport, String> {
+    ctx.progress(0, None, Some("Reading the card")).await;
+    let uses_dates = [&options.name_template, &options.folder_template]
+        .iter()
+        .any(|t| t.as_deref().is_some_and(|t| t.contains("{date")));
+    let planned = options.clone();
+    let (files, steps, destination) = tauri::async_runtime::spawn_blocking(move || {
+        let source = PathBuf::from(&planned.source);
+        if camera_folder(&source).is_none() {
+            return Err(format!("{} has no {} folder", source.display(), CAMERA_FOLDER));
+        }
+        std::fs::create_dir_all(&planned.destination).map_err(|e| e.to_string())?;
+        let destination = PathBuf::from(&planned.destination).canonicalize().map_err(|e| e.to_string())?;
+
+        let files = list_media(&source);
+        // Reading EXIF is only worth it when the templates show the date
+        let dates: Vec<DateTime<Utc>> = files
+            .iter()
+            .map(|f| {
+                let captured = if uses_dates && !f.video { exif_capture_time(&f.path) } else { None };
+                captured.or(f.modified).unwrap_or_else(Utc::now)
+            })
+            .collect();
+        let steps = plan(&files, &dates, &planned, &destination)?;
+        Ok((files, steps, destination))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let mut report = IngestReport { found: files.len(), location: destination.to_string_lossy().to_string(), ..Default::default() };
+    let mut entries = Vec::new();
+    let total = files.len() as i64;
+
+    let copies: Vec<(i64, String, String)> = files
+        .iter()
+        .zip(&steps)
+        .filter_map(|(file, step)| match step {
+            Step::Copy(target) => Some((0, crate::paths::to_db(&file.path), crate::paths::to_db(target))),
+            Step::Skip(_) => None,
+        })
+        .collect();
+    let operation_id = if copies.is_empty() {
+        None
+    } else {
+        Some(db.begin_operation(COPY_OPERATION, &copies).await.map_err(|e| e.to_string())?)
+    };
+    let mut copied: Vec<PathBuf> = Vec::with_capacity(copies.len());
+
+    for (index, (file, step)) in files.into_iter().zip(steps).enumerate() {
+        if ctx.is_cancelled() {
+            if let Some(operation_id) = operation_id {
+                crate::library::operations::discard_copies(db, operation_id, &copied).await;
+            }
+            return Err("Import cancelled".to_string());
+        }
+        if index % 20 == 0 {
+            ctx.progress(index as i64, Some(total), Some(&file.path.to_string_lossy())).await;
+        }
+
+        let target = match step {
+            Step::Skip(_) => {
+                report.skipped += 1;
+                continue;
+            }
+            Step::Copy(target) => target,
+        };
+        let copy_target = target.clone();
+        let copy_source = file.path.clone();
+        match tauri::async_runtime::spawn_blocking(move || copy_file(&copy_source, &copy_target)).await {
+            Ok(Ok(bytes)) => {
+                copied.push(target.clone());
+                report.copied += 1;
+                report.copied_bytes += bytes;
+                if let Some(session) = options.session.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+                    entries.push(ImportedMetadata {
+                        path: crate::paths::to_db(&target),
+                        tags: vec![(SESSIONS_TAG.to_string(), session.to_string())],
+                        ..Default::default()
+                    });
+                }
+            }
+            Ok(Err(e)) => report.failed.push(format!("{}: {}", file.path.display(), e)),
+            Err(e) => report.failed.push(format!("{}: {}", file.path.display(), e)),
+        }
+    }
+
+    // Files that failed to copy are reported, and have nothing to recover
+    if let Some(operation_id) = operation_id {
+        if let Err(e) = db.finish_operation(operation_id, OPERATION_COMPLETED).await {
+            eprintln!("WARN: Failed to close operation {}: {}", operation_id, e);
+        }
+    }
+
+    db.queue_import_metadata(SOURCE, &entries).await.map_err(|e| e.to_string())?;
+    db.apply_pending_import_metadata().await.map_err(|e| e.to_string())?;
+    crate::library::commands::folders::register_location(app, db, report.location.clone())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    ctx.progress(total, Some(total), None).await;
+    println!(
+        "INFO: Copied {} of {} files from {} into {} ({} already there, {} failed)",
+        report.copied,
+        report.found,
+        options.source,
+        report.location,
+        report.skipped,
+        report.failed.len()
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mundam-ingest-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_summarize() {
+        let card = temp_dir("card");
+        let folder = card.join("dcim").join("100CANON");
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(folder.join("IMG_0001.JPG"), vec![0u8; 100]).unwrap();
+        std::fs::write(folder.join("MVI_0002.MP4"), vec![0u8; 50]).unwrap();
+        std::fs::write(folder.join("IMG_0001.XMP"), b"sidecar").unwrap();
+        std::fs::write(folder.join(".hidden.jpg"), b"junk").unwrap();
+        std::fs::write(card.join("NOTES.JPG"), b"outside DCIM").unwrap();
+
+        let summary = summarize(&card);
+        assert_eq!((summary.count, summary.photos, summary.videos, summary.total_bytes), (2, 1, 1, 150));
+        assert!(summary.earliest.is_some() && summary.earliest <= summary.latest);
+        assert!(camera_folder(&temp_dir("not-a-card")).is_none());
+        let _ = std::fs::remove_dir_all(&card);
+    }
+
+    #[test]
+    fn test_plan() {
+        let destination = temp_dir("plan");
+        let day = destination.join("2024").join("2024-05-01");
+        std::fs::create_dir_all(&day).unwrap();
+        // From an earlier import, and a different file with a clashing name
+        std::fs::write(day.join("shoot-001.jpg"), vec![0u8; 10]).unwrap();
+        std::fs::write(day.join("shoot-002.cr3"), vec![0u8; 99]).unwrap();
+
+        let file = |name: &str, size| CardFile { path: PathBuf::from("/card/DCIM/100").join(name), size, video: false, modified: None };
+        let files = [file("IMG_1.jpg", 10), file("IMG_2.cr3", 20), file("IMG_3.jpg", 30)];
+        let date = Utc.with_ymd_and_hms(2024, 5, 1, 9, 30, 0).unwrap();
+        let options = IngestOptions {
+            source: "/card".to_string(),
+            destination: destination.to_string_lossy().to_string(),
+            name_template: Some("shoot-{seq:3}".to_string()),
+            folder_template: Some("{date:yyyy}/{date:yyyy-MM-dd}".to_string()),
+            session: None,
+        };
+        let steps = plan(&files, &[date; 3], &options, &destination).unwrap();
+        assert_eq!(
+            steps,
+            vec![
+                Step::Skip(day.join("shoot-001.jpg")),
+                Step::Copy(day.join("shoot-002 (2).cr3")),
+                Step::Copy(day.join("shoot-003.jpg")),
+            ]
+        );
+
+        let flat = IngestOptions { name_template: None, folder_template: None, ..options };
+        assert_eq!(plan(&files[..1], &[date], &flat, &destination).unwrap(), vec![Step::Copy(destination.join("IMG_1.jpg"))]);
+        let _ = std::fs::remove_dir_all(&destination);
+    }
+}
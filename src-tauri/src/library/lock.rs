@@ -0,0 +1,124 @@
+//! Single-writer lock of the library database.
+//!
+//! The first instance to open `mundam.db` takes an OS advisory lock on
+//! `mundam.db.lock` and keeps it for its whole life, and describes itself in
+//! `mundam.db.lock.json`. A second instance (or the CLI) can't take the lock,
+//! so it opens the library read-only and skips everything that writes in the
+//! background: watchers, scans, the thumbnail worker and the job runners.
+//!
+//! The OS drops the lock when its process dies, so a crash never leaves the
+//! library locked; the owner file it leaves behind is only reported as stale.
+
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+
+/// The instance holding the lock, as described in the owner file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub version: String,
+    pub started_at: DateTime<Utc>,
+}
+
+/// Lock state of this instance, managed as app state.
+#[derive(Debug, Clone, Serialize)]
+pub struct LockStatus {
+    /// Whether this instance is the library's writer.
+    pub owned: bool,
+    /// The other instance, when `owned` is false and it could be identified.
+    pub owner: Option<LockOwner>,
+}
+
+/// Held lock. Dropping it releases the library.
+pub struct LibraryLock {
+    _file: File,
+    owner_path: PathBuf,
+}
+
+impl Drop for LibraryLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.owner_path);
+    }
+}
+
+pub enum LockAttempt {
+    Acquired(LibraryLock),
+    /// Another live process holds the lock.
+    Held(Option<LockOwner>),
+}
+
+fn sibling(db_path: &Path, suffix: &str) -> PathBuf {
+    let mut path = db_path.as_os_str().to_os_string();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn read_owner(path: &Path) -> Option<LockOwner> {
+    let data = std::fs::read(path).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+/// Tries to become the writer of the library at `db_path`.
+///
+/// # Errors
+/// Returns `Err` if the lock file can't be opened or the filesystem doesn't
+/// support locking.
+pub fn acquire(db_path: &Path) -> std::io::Result<LockAttempt> {
+    let lock_path = sibling(db_path, ".lock");
+    let owner_path = sibling(db_path, ".lock.json");
+
+    let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&lock_path)?;
+    if let Err(e) = file.try_lock_exclusive() {
+        if e.kind() == fs2::lock_contended_error().kind() {
+            return Ok(LockAttempt::Held(read_owner(&owner_path)));
+        }
+        return Err(e);
+    }
+
+    if let Some(stale) = read_owner(&owner_path) {
+        println!(
+            "INFO: Replacing stale library lock of process {} (started {})",
+            stale.pid, stale.started_at
+        );
+    }
+
+    let owner = LockOwner {
+        pid: std::process::id(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        started_at: Utc::now(),
+    };
+    let json = serde_json::to_vec_pretty(&owner).map_err(std::io::Error::other)?;
+    std::fs::write(&owner_path, json)?;
+
+    Ok(LockAttempt::Acquired(LibraryLock { _file: file, owner_path }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_instance_is_locked_out() {
+        let dir = std::env::temp_dir().join(format!("mundam-lock-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("mundam.db");
+
+        let first = match acquire(&db_path).unwrap() {
+            LockAttempt::Acquired(lock) => lock,
+            LockAttempt::Held(_) => panic!("fresh library should be free"),
+        };
+        match acquire(&db_path).unwrap() {
+            LockAttempt::Held(Some(owner)) => assert_eq!(owner.pid, std::process::id()),
+            _ => panic!("second open should be locked out"),
+        }
+
+        drop(first);
+        assert!(matches!(acquire(&db_path).unwrap(), LockAttempt::Acquired(_)));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,350 @@
+//! Merging one folder into another.
+//!
+//! Every file of the source folder, subfolders included, goes to the same
+//! relative path under the target, so children with the same name merge
+//! instead of nesting. When that path is taken:
+//! * by a file with the same content, the two are one image: the target
+//!   keeps its file and takes the tags, rating and notes of the source,
+//!   whose copy is deleted;
+//! * by anything else, the file moves under a free name, `photo (2).jpg`.
+//!
+//! The moves are written to the operations ledger first, like bulk renames,
+//! and the emptied source is removed last. `rename_folder` merges in the
+//! same way when a folder is renamed onto an existing one, but without
+//! looking at the files.
+
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Runtime};
+
+use crate::db::operations::{OPERATION_COMPLETED, OPERATION_ROLLED_BACK};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::indexer::echo;
+use crate::indexer::BatchChangePayload;
+use crate::paths;
+
+/// Files the OS leaves behind, which don't keep a folder from being empty.
+const SYSTEM_FILES: [&str; 3] = [".DS_Store", "Thumbs.db", "desktop.ini"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeAction {
+    /// Moves to the same relative path under the target.
+    Move,
+    /// Moves under a free name, as the path holds another file.
+    Rename,
+    /// Same content as the file at the target path, merged into it.
+    Duplicate,
+}
+
+/// Planned fate of one file of the source folder.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeItem {
+    pub image_id: i64,
+    pub source: String,
+    pub target: String,
+    pub action: MergeAction,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderMergeResult {
+    pub items: Vec<MergeItem>,
+    /// `false` for dry runs.
+    pub applied: bool,
+    /// Whether the source folder was left empty and removed from disk.
+    pub source_removed: bool,
+}
+
+/// Plans where each `(id, path)` of `source_root` goes under `target_root`.
+/// Reads the disk to find taken paths and compare contents.
+pub fn plan_merge(source_root: &Path, target_root: &Path, images: &[(i64, String)]) -> Vec<MergeItem> {
+    // Compare case-insensitively, as the default macOS and Windows filesystems are
+    let mut planned: HashSet<String> = HashSet::new();
+    let mut items = Vec::with_capacity(images.len());
+
+    for (image_id, path) in images {
+        let source = paths::from_db(path);
+        let Ok(relative) = source.strip_prefix(source_root) else {
+            continue;
+        };
+        let wanted = target_root.join(relative);
+
+        let (target, action) = if !wanted.exists() && !planned.contains(&key(&wanted)) {
+            (wanted, MergeAction::Move)
+        } else if wanted.is_file() && same_content(&source, &wanted) {
+            (wanted, MergeAction::Duplicate)
+        } else {
+            (free_name(&wanted, &planned), MergeAction::Rename)
+        };
+
+        if action != MergeAction::Duplicate {
+            planned.insert(key(&target));
+        }
+        items.push(MergeItem {
+            image_id: *image_id,
+            source: path.clone(),
+            target: paths::to_db(&target),
+            action,
+        });
+    }
+    items
+}
+
+fn key(path: &Path) -> String {
+    path.to_string_lossy().to_lowercase()
+}
+
+/// First `stem (n).ext` next to `path` that is neither on disk nor planned.
+pub(crate) fn free_name(path: &Path, planned: &HashSet<String>) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|e| e.to_string_lossy().to_string());
+    (2..)
+        .map(|n| {
+            let name = match &extension {
+                Some(ext) => format!("{} ({}).{}", stem, n, ext),
+                None => format!("{} ({})", stem, n),
+            };
+            path.with_file_name(name)
+        })
+        .find(|candidate| !candidate.exists() && !planned.contains(&key(candidate)))
+        .expect("an unbounded range always yields a free name")
+}
+
+/// Whether two files hold the same bytes. The content key settles most
+/// pairs cheaply; a full comparison confirms a match before anything is
+/// deleted.
+pub(crate) fn same_content(a: &Path, b: &Path) -> bool {
+    match (crate::peer::content_key(a), crate::peer::content_key(b)) {
+        (Ok(key_a), Ok(key_b)) if key_a == key_b => {}
+        _ => return false,
+    }
+    let (Ok(mut file_a), Ok(mut file_b)) = (std::fs::File::open(a), std::fs::File::open(b)) else {
+        return false;
+    };
+    let mut buffer_a = vec![0u8; 64 * 1024];
+    let mut buffer_b = vec![0u8; 64 * 1024];
+    loop {
+        let Ok(read) = file_a.read(&mut buffer_a) else {
+            return false;
+        };
+        if read == 0 {
+            // Sizes are part of the key, so both files end here
+            return true;
+        }
+        if file_b.read_exact(&mut buffer_b[..read]).is_err() || buffer_a[..read] != buffer_b[..read] {
+            return false;
+        }
+    }
+}
+
+/// Removes `root` and its subfolders if they hold nothing but system files.
+fn remove_empty_dirs(root: &Path) -> bool {
+    for entry in walkdir::WalkDir::new(root).contents_first(true).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if entry.file_type().is_dir() {
+            let _ = std::fs::remove_dir(path);
+        } else if SYSTEM_FILES.iter().any(|name| entry.file_name() == *name) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+    !root.exists()
+}
+
+/// Merges folder `source_id` into `target_id` (see the module docs). With
+/// `dry_run`, only returns the plan.
+pub async fn merge<R: Runtime>(
+    app: &AppHandle<R>,
+    db: &Db,
+    source_id: i64,
+    target_id: i64,
+    dry_run: bool,
+) -> AppResult<FolderMergeResult> {
+    let source_path = db.get_folder_path(source_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Folder not found: {}", source_id)))?;
+    let target_path = db.get_folder_path(target_id).await?
+        .ok_or_else(|| AppError::NotFound(format!("Folder not found: {}", target_id)))?;
+
+    if source_id == target_id {
+        return Err(AppError::Generic("Cannot merge a folder into itself".to_string()));
+    }
+    if paths::from_db(&target_path).starts_with(paths::from_db(&source_path)) {
+        return Err(AppError::Generic("Cannot merge a folder into one of its subfolders".to_string()));
+    }
+    if db.is_root_folder(source_id).await? {
+        return Err(AppError::Generic("Locations are removed, not merged".to_string()));
+    }
+
+    let images = db.get_folder_images_recursive(source_id).await?;
+    let items = {
+        let (source_root, target_root) = (paths::from_db(&source_path), paths::from_db(&target_path));
+        tokio::task::spawn_blocking(move || plan_merge(&source_root, &target_root, &images))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?
+    };
+    if dry_run {
+        return Ok(FolderMergeResult { items, applied: false, source_removed: false });
+    }
+
+    // Subfolders come along even when empty
+    let mut folders = db.get_folders_under_root(&source_path).await?;
+    folders.sort_by(|a, b| a.1.cmp(&b.1));
+    for (_, path) in folders.iter().filter(|(id, _)| *id != source_id) {
+        if let Ok(relative) = paths::from_db(path).strip_prefix(paths::from_db(&source_path)) {
+            let target = paths::from_db(&target_path).join(relative);
+            tokio::fs::create_dir_all(&target).await?;
+            db.ensure_folder_hierarchy(&paths::to_db(&target)).await?;
+        }
+    }
+
+    // A duplicate the library doesn't know yet simply takes the source's row
+    let mut merged: Vec<(i64, i64)> = Vec::new();
+    let mut moves: Vec<&MergeItem> = Vec::new();
+    let mut duplicates: Vec<&MergeItem> = Vec::new();
+    for item in &items {
+        match item.action {
+            MergeAction::Duplicate => match db.get_image_context(&item.target).await? {
+                Some((target_image, _, _)) => {
+                    merged.push((item.image_id, target_image));
+                    duplicates.push(item);
+                }
+                None => duplicates.push(item),
+            },
+            MergeAction::Move | MergeAction::Rename => moves.push(item),
+        }
+    }
+
+    let mut repoint: Vec<(i64, String, String, i64)> = Vec::with_capacity(items.len());
+    for item in &items {
+        let indexed_duplicate = merged.iter().any(|(source, _)| *source == item.image_id);
+        if indexed_duplicate {
+            continue;
+        }
+        let target = paths::from_db(&item.target);
+        let parent = target.parent().map(paths::to_db).unwrap_or_default();
+        let filename = target.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let folder_id = db.ensure_folder_hierarchy(&parent).await?;
+        repoint.push((item.image_id, item.target.clone(), filename, folder_id));
+    }
+
+    let steps: Vec<(i64, String, String)> = moves
+        .iter()
+        .map(|i| (i.image_id, i.source.clone(), i.target.clone()))
+        .collect();
+    let operation_id = db.begin_operation("merge_folders", &steps).await?;
+    echo::expect_changes(items.iter().flat_map(|i| [i.source.clone(), i.target.clone()]));
+
+    let mut done: Vec<&MergeItem> = Vec::with_capacity(moves.len());
+    for item in &moves {
+        if let Some(parent) = paths::from_db(&item.target).parent() {
+            if let Err(e) = tokio::fs::create_dir_all(parent).await {
+                roll_back(db, operation_id, &done).await;
+                return Err(AppError::Io(e));
+            }
+        }
+        if let Err(e) = tokio::fs::rename(paths::from_db(&item.source), paths::from_db(&item.target)).await {
+            roll_back(db, operation_id, &done).await;
+            return Err(AppError::Io(e));
+        }
+        done.push(item);
+    }
+
+    if let Err(e) = db.apply_folder_merge(source_id, target_id, &repoint, &merged).await {
+        roll_back(db, operation_id, &done).await;
+        return Err(e.into());
+    }
+    if let Err(e) = db.finish_operation(operation_id, OPERATION_COMPLETED).await {
+        eprintln!("WARN: Failed to close operation {}: {}", operation_id, e);
+    }
+
+    // The target holds the same bytes, so the source copies can go
+    for item in &duplicates {
+        if let Err(e) = tokio::fs::remove_file(paths::from_db(&item.source)).await {
+            eprintln!("WARN: Could not delete duplicate {}: {}", item.source, e);
+        }
+    }
+    let source_removed = {
+        let root = paths::from_db(&source_path);
+        tokio::task::spawn_blocking(move || remove_empty_dirs(&root))
+            .await
+            .unwrap_or(false)
+    };
+
+    println!(
+        "INFO: Merged folder {} into {} ({} moved, {} duplicates)",
+        source_path,
+        target_path,
+        moves.len(),
+        duplicates.len()
+    );
+    let _ = app.emit("library:batch-change", BatchChangePayload {
+        added: vec![], removed: vec![], updated: vec![], needs_refresh: true
+    });
+
+    Ok(FolderMergeResult { items, applied: true, source_removed })
+}
+
+/// Moves the files already moved back. The operation is only closed if
+/// every file is back, otherwise it stays in the ledger for recovery.
+async fn roll_back(db: &Db, operation_id: i64, done: &[&MergeItem]) {
+    let mut reverted = true;
+    for item in done.iter().rev() {
+        if let Err(e) = tokio::fs::rename(paths::from_db(&item.target), paths::from_db(&item.source)).await {
+            eprintln!("ERROR: Failed to roll back move {} -> {}: {}", item.target, item.source, e);
+            reverted = false;
+        }
+    }
+    if reverted {
+        if let Err(e) = db.finish_operation(operation_id, OPERATION_ROLLED_BACK).await {
+            eprintln!("WARN: Failed to close operation {}: {}", operation_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_merge() {
+        let root = std::env::temp_dir().join(format!("mundam-merge-{}", std::process::id()));
+        let (source, target) = (root.join("Shoot copy"), root.join("Shoot"));
+        std::fs::create_dir_all(source.join("Selects")).unwrap();
+        std::fs::create_dir_all(target.join("Selects")).unwrap();
+
+        let write = |path: PathBuf, content: &str| {
+            std::fs::write(&path, content).unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let new_file = write(source.join("a.jpg"), "a");
+        let same = write(source.join("Selects/b.jpg"), "b");
+        write(target.join("Selects/b.jpg"), "b");
+        let clash = write(source.join("c.jpg"), "c, edited");
+        write(target.join("c.jpg"), "c");
+        write(target.join("c (2).jpg"), "c, older edit");
+
+        let items = plan_merge(&source, &target, &[(1, new_file), (2, same), (3, clash)]);
+        let planned: Vec<(MergeAction, String)> = items
+            .iter()
+            .map(|i| (i.action, Path::new(&i.target).strip_prefix(&target).unwrap().to_string_lossy().to_string()))
+            .collect();
+        assert_eq!(planned, vec![
+            (MergeAction::Move, "a.jpg".to_string()),
+            (MergeAction::Duplicate, "Selects/b.jpg".to_string()),
+            (MergeAction::Rename, "c (3).jpg".to_string()),
+        ]);
+
+        std::fs::write(source.join(".DS_Store"), "").unwrap();
+        for item in &items {
+            std::fs::remove_file(&item.source).unwrap();
+        }
+        assert!(remove_empty_dirs(&source));
+        assert!(target.join("Selects").exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -1 +1,25 @@
 pub mod commands;
+pub mod rename;
+pub mod estimate;
+pub mod auto_collections;
+pub mod slideshow;
+pub mod operations;
+pub mod read_only;
+pub mod lock;
+pub mod apple_photos;
+pub mod catalog_export;
+pub mod catalog_import;
+pub mod sync;
+pub mod stacks;
+pub mod merge;
+pub mod upkeep;
+pub mod smart_folder_history;
+pub mod smart_folder_sharing;
+pub mod filter_subscriptions;
+pub mod derivatives;
+pub mod proxies;
+pub mod working_sets;
+pub mod duplicates;
+pub mod ingest;
+pub mod capture_session;
+pub mod trash;
@@ -0,0 +1,275 @@
+//! Crash recovery of multi-file operations.
+//!
+//! Operations that move several files (bulk renames) write every planned
+//! move to the ledger before touching the disk. If the app dies half-way,
+//! the operation is flagged as interrupted on the next start and can be
+//! resumed or rolled back. The disk is the source of truth: each step is
+//! classified by which of its two paths exists, so a crash between a move
+//! and its bookkeeping can't desync the ledger.
+//!
+//! Copies (card and Photos imports) are recorded the same way, with no image
+//! yet: their source always exists, so only the target tells whether a step
+//! ran. Syncs record each file as soon as it is copied and catalog exports go
+//! through a partial file, so a crash leaves neither half-applied and they
+//! don't need the ledger.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::db::models::OperationStep;
+use crate::db::operations::{OPERATION_COMPLETED, OPERATION_IN_PROGRESS, OPERATION_INTERRUPTED, OPERATION_ROLLED_BACK};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::indexer::echo;
+
+/// Where a step's file is on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepState {
+    /// Still at its source path.
+    Pending,
+    /// Already at its target path.
+    Applied,
+    /// At neither path, moved or deleted outside the app.
+    Missing,
+    /// Both paths exist, so another file took one of them.
+    Ambiguous,
+}
+
+pub fn step_state(source_exists: bool, target_exists: bool) -> StepState {
+    match (source_exists, target_exists) {
+        (true, false) => StepState::Pending,
+        (false, true) => StepState::Applied,
+        (false, false) => StepState::Missing,
+        (true, true) => StepState::Ambiguous,
+    }
+}
+
+/// Ledger kinds whose steps copy files instead of moving them.
+const COPY_OPERATIONS: &[&str] = &[crate::library::apple_photos::COPY_OPERATION, crate::library::ingest::COPY_OPERATION];
+
+/// Outcome of resuming or rolling back an operation.
+#[derive(Debug, Serialize)]
+pub struct OperationRecovery {
+    pub operation_id: i64,
+    /// `completed` or `rolled_back`.
+    pub status: String,
+    /// Files now at the expected path.
+    pub settled: usize,
+    /// Steps left alone, with the reason.
+    pub skipped: Vec<String>,
+}
+
+/// Case-sensitive existence checks, with one directory listing per folder.
+///
+/// `Path::exists` can't tell `a.jpg` from `A.jpg` on case-insensitive
+/// filesystems, which breaks case-only renames.
+#[derive(Default)]
+struct DirListings(HashMap<PathBuf, HashSet<OsString>>);
+
+impl DirListings {
+    fn exists(&mut self, path: &Path) -> bool {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return false;
+        };
+        let names = self.0.entry(parent.to_path_buf()).or_insert_with(|| {
+            std::fs::read_dir(parent)
+                .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.file_name()).collect())
+                .unwrap_or_default()
+        });
+        names.contains(name)
+    }
+
+    fn moved(&mut self, from: &Path, to: &Path) {
+        for (path, present) in [(from, false), (to, true)] {
+            if let (Some(parent), Some(name)) = (path.parent(), path.file_name()) {
+                if let Some(names) = self.0.get_mut(parent) {
+                    if present {
+                        names.insert(name.to_os_string());
+                    } else {
+                        names.remove(name);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Finishes (`resume`) or undoes an interrupted operation, then points the
+/// images at wherever their files ended up.
+///
+/// # Errors
+/// Returns `AppError::NotFound` for unknown operations, `AppError::Busy`
+/// for operations still in progress and `AppError::Generic` for finished ones.
+pub async fn recover(db: &Db, operation_id: i64, resume: bool) -> AppResult<OperationRecovery> {
+    let operation = db
+        .get_operation(operation_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Operation {} not found", operation_id)))?;
+    if operation.status == OPERATION_IN_PROGRESS {
+        return Err(AppError::Busy(format!("Operation {} is still in progress", operation_id)));
+    }
+    if operation.status != OPERATION_INTERRUPTED {
+        return Err(AppError::Generic(format!("Operation {} is {}, not interrupted", operation_id, operation.status)));
+    }
+
+    let steps = db.get_operation_steps(operation_id).await?;
+    echo::expect_changes(steps.iter().flat_map(|s| [s.source.clone(), s.target.clone()]));
+
+    let copies = COPY_OPERATIONS.contains(&operation.kind.as_str());
+    let (paths, skipped) = tauri::async_runtime::spawn_blocking(move || {
+        if copies {
+            apply_copy_steps(&steps, resume)
+        } else {
+            apply_steps(&steps, resume)
+        }
+    })
+    .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let status = if resume { OPERATION_COMPLETED } else { OPERATION_ROLLED_BACK };
+    db.settle_operation(operation_id, &paths, status).await?;
+
+    println!(
+        "INFO: Operation {} ({}) {}: {} files settled, {} skipped",
+        operation_id, operation.kind, status, paths.len(), skipped.len()
+    );
+    Ok(OperationRecovery { operation_id, status: status.to_string(), settled: paths.len(), skipped })
+}
+
+/// Moves the files of an operation forward or back. Returns the
+/// `(image_id, path)` of every file now where it belongs, and the skipped steps.
+fn apply_steps(steps: &[OperationStep], resume: bool) -> (Vec<(i64, String)>, Vec<String>) {
+    let mut listings = DirListings::default();
+    let mut paths = Vec::with_capacity(steps.len());
+    let mut skipped = Vec::new();
+
+    for step in steps {
+        let (source, target) = (&crate::paths::from_db(&step.source), &crate::paths::from_db(&step.target));
+        let state = step_state(listings.exists(source), listings.exists(target));
+        let (from, to) = if resume { (source, target) } else { (target, source) };
+
+        let outcome = match (state, resume) {
+            (StepState::Applied, true) | (StepState::Pending, false) => Ok(()),
+            (StepState::Pending, true) | (StepState::Applied, false) => {
+                std::fs::rename(from, to).map(|()| listings.moved(from, to)).map_err(|e| e.to_string())
+            }
+            (StepState::Missing, _) => Err("file not found".to_string()),
+            (StepState::Ambiguous, _) => Err(format!("{} also exists", step.target)),
+        };
+        match outcome {
+            Ok(()) => paths.push((step.image_id, crate::paths::to_db(to))),
+            Err(e) => skipped.push(format!("{}: {}", step.source, e)),
+        }
+    }
+    (paths, skipped)
+}
+
+/// Deletes the copies made so far by a cancelled or failed copy operation.
+/// The operation is only closed if every copy is gone, otherwise it stays in
+/// the ledger for recovery.
+pub async fn discard_copies(db: &Db, operation_id: i64, copied: &[PathBuf]) {
+    let mut removed = true;
+    for path in copied.iter().rev() {
+        if let Err(e) = tokio::fs::remove_file(path).await {
+            eprintln!("ERROR: Failed to remove copy {}: {}", path.display(), e);
+            removed = false;
+        }
+    }
+    if removed {
+        if let Err(e) = db.finish_operation(operation_id, OPERATION_ROLLED_BACK).await {
+            eprintln!("WARN: Failed to close operation {}: {}", operation_id, e);
+        }
+    }
+}
+
+/// Finishes or deletes the copies of an operation. Copies are written through
+/// a partial file, so a target that exists is complete.
+fn apply_copy_steps(steps: &[OperationStep], resume: bool) -> (Vec<(i64, String)>, Vec<String>) {
+    let mut paths = Vec::with_capacity(steps.len());
+    let mut skipped = Vec::new();
+
+    for step in steps {
+        let (source, target) = (crate::paths::from_db(&step.source), crate::paths::from_db(&step.target));
+        let outcome = match (target.exists(), resume) {
+            (true, true) | (false, false) => Ok(()),
+            (false, true) => crate::library::ingest::copy_file(&source, &target).map(|_| ()).map_err(|e| e.to_string()),
+            (true, false) => std::fs::remove_file(&target).map_err(|e| e.to_string()),
+        };
+        match outcome {
+            Ok(()) => paths.push((step.image_id, step.target.clone())),
+            Err(e) => skipped.push(format!("{}: {}", step.source, e)),
+        }
+    }
+    (paths, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(dir: &Path, source: &str, target: &str) -> OperationStep {
+        OperationStep {
+            operation_id: 1,
+            position: 0,
+            image_id: 7,
+            source: dir.join(source).to_string_lossy().to_string(),
+            target: dir.join(target).to_string_lossy().to_string(),
+        }
+    }
+
+    #[test]
+    fn test_step_state() {
+        assert_eq!(step_state(true, false), StepState::Pending);
+        assert_eq!(step_state(false, true), StepState::Applied);
+        assert_eq!(step_state(false, false), StepState::Missing);
+        assert_eq!(step_state(true, true), StepState::Ambiguous);
+    }
+
+    #[test]
+    fn test_resume_and_rollback() {
+        let dir = std::env::temp_dir().join(format!("mundam-ledger-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.jpg"), b"a").unwrap();
+        std::fs::write(dir.join("new-b.jpg"), b"b").unwrap();
+
+        // a.jpg was not renamed yet, b.jpg was
+        let steps = [step(&dir, "a.jpg", "new-a.jpg"), step(&dir, "b.jpg", "new-b.jpg"), step(&dir, "c.jpg", "new-c.jpg")];
+
+        let (paths, skipped) = apply_steps(&steps, true);
+        assert_eq!(paths.len(), 2);
+        assert_eq!(skipped.len(), 1);
+        assert!(dir.join("new-a.jpg").exists() && !dir.join("a.jpg").exists());
+
+        let (paths, _) = apply_steps(&steps, false);
+        assert_eq!(paths.len(), 2);
+        assert!(dir.join("a.jpg").exists() && dir.join("b.jpg").exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_copy_resume_and_rollback() {
+        let dir = std::env::temp_dir().join(format!("mundam-ledger-copy-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("out")).unwrap();
+        std::fs::write(dir.join("a.jpg"), b"a").unwrap();
+        std::fs::write(dir.join("b.jpg"), b"b").unwrap();
+        std::fs::write(dir.join("out/b.jpg"), b"b").unwrap();
+
+        // b.jpg was copied before the crash, a.jpg was not
+        let steps = [step(&dir, "a.jpg", "out/a.jpg"), step(&dir, "b.jpg", "out/b.jpg")];
+
+        let (paths, skipped) = apply_copy_steps(&steps, true);
+        assert_eq!((paths.len(), skipped.len()), (2, 0));
+        assert_eq!(std::fs::read(dir.join("out/a.jpg")).unwrap(), b"a");
+
+        let (paths, _) = apply_copy_steps(&steps, false);
+        assert_eq!(paths.len(), 2);
+        assert!(!dir.join("out/a.jpg").exists() && !dir.join("out/b.jpg").exists());
+        assert!(dir.join("a.jpg").exists() && dir.join("b.jpg").exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
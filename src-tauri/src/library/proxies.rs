@@ -0,0 +1,289 @@
+//! Editing proxies of camera originals.
+//!
+//! Editors cut with lightweight intra-frame copies of heavy camera files and
+//! relink to the originals for the final render. When proxy generation is on,
+//! videos that appear in one of the watched folders are queued as
+//! `generate_proxy` jobs, so they can be followed, cancelled and retried
+//! with the other jobs, and run at a lower priority than interactive work.
+//! Each proxy is a 1080p QuickTime file in the proxy folder, mirroring the
+//! source's place under its watched folder, and is recorded as a derivative
+//! of the original.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::formats::{FileFormat, MediaType};
+use crate::library::derivatives::{self, DerivativeKind};
+use crate::streaming::process_manager::{self, JobKind};
+
+/// Setting holding the [`ProxySettings`].
+pub const SETTING_KEY: &str = "proxy_generation";
+
+/// Kind of the queued jobs.
+pub const JOB_KIND: &str = "generate_proxy";
+
+/// Proxies yield to playback transcodes and other queued work.
+pub const JOB_PRIORITY: i64 = -10;
+
+/// Suffix of proxy file names, which also keeps proxies from being proxied.
+const PROXY_SUFFIX: &str = "_proxy";
+
+/// Hard limit for a single proxy.
+const PROXY_TIMEOUT: Duration = Duration::from_secs(4 * 60 * 60);
+
+/// Height of the proxies; the width follows the source's aspect ratio.
+const PROXY_HEIGHT: u32 = 1080;
+
+/// Codec of the proxies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyPreset {
+    /// Apple ProRes 422 Proxy, the default of most editors.
+    #[default]
+    ProresProxy,
+    /// Avid DNxHR LB, for Avid and Resolve on Windows.
+    DnxhrLb,
+}
+
+impl ProxyPreset {
+    /// Name of the preset, as recorded with the proxies.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProxyPreset::ProresProxy => "prores_proxy",
+            ProxyPreset::DnxhrLb => "dnxhr_lb",
+        }
+    }
+
+    fn video_args(self) -> &'static [&'static str] {
+        match self {
+            ProxyPreset::ProresProxy => &["-c:v", "prores_ks", "-profile:v", "0", "-pix_fmt", "yuv422p10le"],
+            ProxyPreset::DnxhrLb => &["-c:v", "dnxhd", "-profile:v", "dnxhr_lb", "-pix_fmt", "yuv422p"],
+        }
+    }
+}
+
+/// Proxy generation settings, stored under [`SETTING_KEY`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProxySettings {
+    /// Queue proxies for videos added to the watched folders.
+    pub enabled: bool,
+    /// Where proxies are written.
+    pub folder: Option<String>,
+    /// Codec of the proxies.
+    pub preset: ProxyPreset,
+    /// Folders whose new videos get proxies, like a card offload folder.
+    pub watch_folders: Vec<String>,
+}
+
+impl ProxySettings {
+    /// The proxy folder, if one is set.
+    pub fn proxy_folder(&self) -> Option<&Path> {
+        self.folder.as_deref().map(str::trim).filter(|folder| !folder.is_empty()).map(Path::new)
+    }
+
+    /// Watched folder containing `source`, if any.
+    fn watch_folder_of(&self, source: &Path) -> Option<&Path> {
+        self.watch_folders.iter().map(Path::new).find(|folder| source.starts_with(folder))
+    }
+
+    /// Whether a video added at `source` gets a proxy on its own.
+    pub fn watches(&self, source: &Path) -> bool {
+        self.enabled && self.watch_folder_of(source).is_some() && self.accepts(source)
+    }
+
+    /// Whether `source` is a video worth a proxy: not a proxy itself, nor a
+    /// file in the proxy folder.
+    pub fn accepts(&self, source: &Path) -> bool {
+        let is_video = FileFormat::detect_extension(source).is_some_and(|f| f.type_category == MediaType::Video);
+        let is_proxy = source.file_stem().is_some_and(|stem| stem.to_string_lossy().to_lowercase().ends_with(PROXY_SUFFIX))
+            || self.proxy_folder().is_some_and(|folder| source.starts_with(folder));
+        is_video && !is_proxy
+    }
+
+    /// Where the proxy of `source` goes: the same place under the proxy folder
+    /// as the source under its watched folder, or the top of the proxy folder
+    /// for sources outside the watched folders. The source's extension is
+    /// kept in the name, so `clip.mp4` and `clip.mkv` get separate proxies.
+    pub fn proxy_path(&self, source: &Path) -> Option<PathBuf> {
+        let folder = self.proxy_folder()?;
+        let stem = source.file_stem()?.to_string_lossy();
+        let extension = source.extension().map(|ext| format!("_{}", ext.to_string_lossy())).unwrap_or_default();
+        let relative_dir = self
+            .watch_folder_of(source)
+            .and_then(|watched| source.parent()?.strip_prefix(watched).ok())
+            .unwrap_or(Path::new(""));
+        Some(folder.join(relative_dir).join(format!("{}{}{}.mov", stem, extension, PROXY_SUFFIX)))
+    }
+}
+
+/// The stored proxy settings, or the defaults when unset or unreadable.
+pub async fn load_settings(db: &Db) -> ProxySettings {
+    match db.get_setting(SETTING_KEY).await {
+        Ok(Some(value)) => serde_json::from_value(value).unwrap_or_default(),
+        _ => ProxySettings::default(),
+    }
+}
+
+/// FFmpeg arguments writing the proxy of `source` to `output`.
+pub fn build_proxy_args(source: &Path, output: &Path, preset: ProxyPreset) -> Vec<String> {
+    let mut args: Vec<String> = vec!["-hide_banner".into(), "-y".into(), "-i".into(), source.to_string_lossy().into()];
+    args.extend(["-map", "0:v:0", "-map", "0:a?"].map(String::from));
+    // Never upscaled; the width stays even, as the codecs need
+    args.extend(["-vf".to_string(), format!("scale=-2:'min({},ih)'", PROXY_HEIGHT)]);
+    args.extend(preset.video_args().iter().map(|arg| arg.to_string()));
+    // Editors relink by timecode, which the source's metadata carries
+    args.extend(["-c:a", "pcm_s16le", "-map_metadata", "0"].map(String::from));
+    args.extend(["-f".to_string(), "mov".to_string(), output.to_string_lossy().to_string()]);
+    args
+}
+
+/// Writes the proxy of `source` with the current settings and records it.
+/// A proxy already on disk is recorded again rather than rewritten.
+///
+/// FFmpeg is recorded under the queued job `job_id`, for `cancel_job` to
+/// kill it.
+pub async fn generate<R: Runtime>(app: &AppHandle<R>, db: &Db, source: &Path, job_id: Option<i64>) -> AppResult<PathBuf> {
+    let settings = load_settings(db).await;
+    let output = settings
+        .proxy_path(source)
+        .ok_or_else(|| AppError::Generic("No proxy folder is set".to_string()))?;
+    let image_id = db
+        .get_image_id_by_path(&crate::paths::to_db(source))
+        .await?
+        .ok_or_else(|| AppError::NotFound(source.to_string_lossy().to_string()))?;
+
+    if !output.is_file() {
+        let ffmpeg_path = crate::media::ffmpeg::get_ffmpeg_path(Some(app)).ok_or(AppError::FfmpegMissing)?;
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Written aside and renamed, so a cancelled run leaves no half proxy
+        let partial = output.with_extension(format!("{}.partial", uuid::Uuid::new_v4().simple()));
+        let mut cmd = Command::new(ffmpeg_path);
+        cmd.args(build_proxy_args(source, &partial, settings.preset));
+        let label = source.to_string_lossy().to_string();
+        let result = tauri::async_runtime::spawn_blocking(move || {
+            process_manager::for_job(job_id, || process_manager::run_supervised(cmd, JobKind::Render, &label, PROXY_TIMEOUT))
+        })
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+        match result {
+            Ok(result) if result.status.success() => std::fs::rename(&partial, &output)?,
+            Ok(result) => {
+                let _ = std::fs::remove_file(&partial);
+                return Err(AppError::Transcoding(format!(
+                    "FFmpeg failed: {}",
+                    String::from_utf8_lossy(&result.stderr).trim()
+                )));
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&partial);
+                return Err(e);
+            }
+        }
+        println!("INFO: Wrote {} proxy of {:?} to {:?}", settings.preset.as_str(), source, output);
+    }
+
+    derivatives::record(db, image_id, DerivativeKind::Proxy, &output, Some(settings.preset.as_str())).await?;
+    Ok(output)
+}
+
+/// Queues proxies of `sources` that don't have one queued already. Returns
+/// the ids of the jobs queued.
+pub async fn queue(db: &Db, queue: &crate::jobs::JobQueue, sources: &[String]) -> AppResult<Vec<i64>> {
+    let mut ids = Vec::new();
+    for source in sources {
+        if db.has_pending_job(JOB_KIND, source).await? {
+            continue;
+        }
+        let payload = serde_json::json!({ "path": source });
+        ids.push(queue.enqueue(JOB_KIND, source, &payload, JOB_PRIORITY).await?);
+    }
+    Ok(ids)
+}
+
+/// Queues proxies of the videos the watcher just added, when proxy
+/// generation is on and they are in a watched folder.
+pub async fn queue_added<R: Runtime>(app: &AppHandle<R>, db: &Db, added: &[String]) {
+    let settings = load_settings(db).await;
+    if !settings.enabled || settings.proxy_folder().is_none() {
+        return;
+    }
+    let sources: Vec<String> = added.iter().filter(|path| settings.watches(Path::new(path))).cloned().collect();
+    if sources.is_empty() {
+        return;
+    }
+    let Some(job_queue) = app.try_state::<Arc<crate::jobs::JobQueue>>() else {
+        return;
+    };
+    match queue(db, &job_queue, &sources).await {
+        Ok(ids) if !ids.is_empty() => println!("INFO: Queued {} proxies of new videos", ids.len()),
+        Ok(_) => {}
+        Err(e) => eprintln!("WARN: Failed to queue proxies: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> ProxySettings {
+        ProxySettings {
+            enabled: true,
+            folder: Some("/edit/proxies".to_string()),
+            preset: ProxyPreset::ProresProxy,
+            watch_folders: vec!["/cards".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_watched_sources() {
+        let settings = settings();
+        assert!(settings.watches(Path::new("/cards/A001/C0001.MP4")));
+        assert!(!settings.watches(Path::new("/elsewhere/C0001.MP4")));
+        assert!(!settings.watches(Path::new("/cards/A001/still.jpg")));
+        assert!(!settings.watches(Path::new("/cards/A001/C0001_Proxy.mov")));
+
+        let inside = ProxySettings { folder: Some("/cards/proxies".to_string()), ..settings.clone() };
+        assert!(!inside.watches(Path::new("/cards/proxies/C0001.mov")));
+        assert!(!ProxySettings { enabled: false, ..settings }.watches(Path::new("/cards/A001/C0001.MP4")));
+    }
+
+    #[test]
+    fn test_proxy_path_mirrors_watched_folder() {
+        let settings = settings();
+        assert_eq!(
+            settings.proxy_path(Path::new("/cards/A001/C0001.MP4")),
+            Some(PathBuf::from("/edit/proxies/A001/C0001_MP4_proxy.mov"))
+        );
+        assert_eq!(
+            settings.proxy_path(Path::new("/elsewhere/clip.mxf")),
+            Some(PathBuf::from("/edit/proxies/clip_mxf_proxy.mov"))
+        );
+        assert_ne!(
+            settings.proxy_path(Path::new("/cards/clip.mp4")),
+            settings.proxy_path(Path::new("/cards/clip.mkv"))
+        );
+        let unset = ProxySettings { folder: Some("  ".to_string()), ..settings };
+        assert_eq!(unset.proxy_path(Path::new("/cards/A001/C0001.MP4")), None);
+    }
+
+    #[test]
+    fn test_proxy_args() {
+        let args = build_proxy_args(Path::new("/in.mp4"), Path::new("/out.mov"), ProxyPreset::DnxhrLb);
+        let joined = args.join(" ");
+        assert!(joined.contains("-c:v dnxhd -profile:v dnxhr_lb"));
+        assert!(joined.contains("scale=-2:'min(1080,ih)'"));
+        assert!(joined.contains("-c:a pcm_s16le -map_metadata 0 -f mov"));
+        assert_eq!(args.last().map(String::as_str), Some("/out.mov"));
+    }
+}
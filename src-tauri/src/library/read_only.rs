@@ -0,0 +1,223 @@
+//! Read-only (guest) mode.
+//!
+//! When the `read_only` setting is on, every command that edits the library
+//! is rejected with `AppError::ReadOnly` before it runs, so a curated archive
+//! can be browsed on a shared machine without accidental edits. Browsing,
+//! previews, playback and exports keep working (see `READ_ONLY_COMMANDS`). The setting lives in the
+//! library database, so it travels with the library.
+//!
+//! An instance that couldn't take the library lock (see `library::lock`) is
+//! read-only for its whole session, whatever the setting says.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tauri::ipc::Invoke;
+
+use crate::error::AppError;
+
+/// Settings key holding the flag.
+pub const SETTING_KEY: &str = "read_only";
+
+/// Commands that leave the library alone, the only ones run while it is
+/// read-only: reads, in-memory session state, caches and exports. Any other
+/// command, new ones included, is assumed to change the library.
+///
+/// `set_setting` stays reachable so read-only mode can be turned off, and
+/// the playback position commands, called on every player tick, don't save
+/// anything in this mode. The job queue lives in the library, so only
+/// listing jobs is allowed.
+pub const READ_ONLY_COMMANDS: &[&str] = &[
+    "get_all_tags",
+    "get_library_stats",
+    "get_tags_for_image",
+    "get_images_filtered",
+    "get_image_count_filtered",
+    "subscribe_filter",
+    "set_filter_window",
+    "unsubscribe_filter",
+    "get_tag_shortcuts",
+    "resolve_tag_shortcut",
+    "get_selection_summary",
+    "get_review_sessions",
+    "get_review_cursor",
+    "get_image_annotations",
+    "get_related_images",
+    "get_image_links",
+    "get_folder_links",
+    "export_metadata_bundle",
+    "export_metadata",
+    "get_sync_targets",
+    "preview_sync_target",
+    "get_derivatives",
+    "get_proxy_settings",
+    "push_working_set",
+    "pop_working_set",
+    "get_working_sets",
+    "clear_working_sets",
+    "find_duplicates",
+    "get_duplicate_group",
+    "get_remote_locations",
+    "get_remote_cache_usage",
+    "clear_remote_cache",
+    "fetch_remote_file",
+    "get_peer_settings",
+    "test_peer_connection",
+    "estimate_operation_size",
+    "set_playback_position",
+    "get_playback_position",
+    "clear_playback_position",
+    "get_video_chapters",
+    "get_folder_sequences",
+    "get_image_sequence",
+    "get_sequence_frames",
+    "get_folder_version_chains",
+    "get_chain_versions",
+    "get_image_pages",
+    "render_page",
+    "get_psd_layers",
+    "render_psd_layer",
+    "get_image_exif",
+    "get_exif_batch",
+    "get_stock_info",
+    "set_thumbnail_priority",
+    "prefetch_folder",
+    "cancel_prefetch",
+    "set_active_context",
+    "get_thumbnail_worker_status",
+    "get_model_dependencies",
+    "get_image_histogram",
+    "get_similar_images",
+    "get_locations",
+    "get_location_health",
+    "get_all_subfolders",
+    "get_subfolder_counts",
+    "get_location_root_counts",
+    "get_folder_default_tags",
+    "get_smart_folders",
+    "export_smart_folder",
+    "get_smart_folder_trends",
+    "get_auto_collections",
+    "get_auto_collection_snapshots",
+    "get_auto_collection_snapshot_images",
+    "compare_images",
+    "get_playlists",
+    "get_playlist_items",
+    "export_playlist_m3u",
+    "render_slideshow",
+    "get_collections",
+    "get_image_collections",
+    "get_trash",
+    "get_interrupted_operations",
+    "get_operation_steps",
+    "get_library_lock_status",
+    "get_app_status",
+    "retry_app_init",
+    "find_apple_photos_library",
+    "get_import_sources",
+    "stop_capture_session",
+    "get_capture_session",
+    "get_setting",
+    "set_setting",
+    "get_db_status",
+    "get_library_supported_formats",
+    "get_corrupt_files",
+    "get_mismatched_extensions",
+    "explain_filter",
+    "get_audio_waveform_data",
+    "get_active_ffmpeg_jobs",
+    "needs_transcoding",
+    "is_native_format",
+    "get_stream_url",
+    "register_webview_codecs",
+    "get_stream_token",
+    "get_quality_options",
+    "transcode_file",
+    "is_cached",
+    "get_cache_stats",
+    "cleanup_cache",
+    "clear_cache",
+    "ffmpeg_available",
+    "get_transcode_stats",
+    "list_jobs",
+];
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static LOCKED_OUT: AtomicBool = AtomicBool::new(false);
+
+/// Turns read-only mode on or off (from the `read_only` setting).
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Makes the session read-only because another instance writes the library.
+pub fn set_locked_out(locked_out: bool) {
+    LOCKED_OUT.store(locked_out, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed) || LOCKED_OUT.load(Ordering::Relaxed)
+}
+
+/// Whether `command` must be rejected in the current mode.
+pub fn blocks(command: &str) -> bool {
+    is_enabled() && !READ_ONLY_COMMANDS.contains(&command)
+}
+
+/// Wraps the app's invoke handler so mutating commands are rejected while
+/// the library is read-only.
+pub fn guard(
+    handler: impl Fn(Invoke) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke) -> bool + Send + Sync + 'static {
+    move |invoke: Invoke| {
+        if blocks(invoke.message.command()) {
+            let command = invoke.message.command().to_string();
+            invoke.resolver.reject(AppError::ReadOnly(command));
+            return true;
+        }
+        handler(invoke)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks_only_mutating_commands_when_enabled() {
+        set_enabled(false);
+        assert!(!blocks("update_image_rating"));
+
+        set_enabled(true);
+        assert!(blocks("update_image_rating"));
+        assert!(blocks("rename_images_bulk"));
+        assert!(!blocks("get_images_filtered"));
+        // The flag itself must stay reachable to leave read-only mode
+        assert!(!blocks("set_setting"));
+
+        // Writers that aren't obviously edits, and commands nobody classified
+        for command in ["set_proxy_settings", "queue_proxies", "set_peer_settings", "enqueue_job", "retry_job", "not_yet_written"] {
+            assert!(blocks(command), "{} runs in read-only mode", command);
+        }
+
+        set_enabled(false);
+        set_locked_out(true);
+        assert!(blocks("update_image_rating"));
+        set_locked_out(false);
+    }
+
+    #[test]
+    fn test_read_only_commands_are_registered() {
+        let lib = include_str!("../lib.rs");
+        let handler = &lib[lib.find("generate_handler![").expect("command list")..];
+        let handler = &handler[..handler.find(']').expect("end of command list")];
+        let registered: Vec<&str> = handler
+            .lines()
+            .map(|line| line.trim().trim_end_matches(','))
+            .filter(|line| !line.starts_with("//"))
+            .filter_map(|line| line.rsplit("::").next())
+            .collect();
+        for command in READ_ONLY_COMMANDS {
+            assert!(registered.contains(command), "{} is not a registered command", command);
+        }
+    }
+}
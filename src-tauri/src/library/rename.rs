@@ -0,0 +1,165 @@
+//! Filename templates for bulk renames.
+//!
+//! A template mixes literal text with tokens:
+//! * `{original}` - the current file stem.
+//! * `{seq}` / `{seq:4}` - position in the batch, optionally zero-padded.
+//! * `{date}` / `{date:yyyyMMdd}` - file creation date, Java-style pattern.
+//! * `{tag:client}` - name of the first tag applied to the file whose parent tag is `client`.
+//!
+//! The original extension is always kept.
+
+use chrono::{DateTime, Utc};
+
+/// Data a template can reference for one file.
+pub struct RenameContext<'a> {
+    pub original_stem: &'a str,
+    pub extension: Option<&'a str>,
+    pub seq: u32,
+    pub created_at: DateTime<Utc>,
+    /// `(parent tag name, tag name)` pairs for the tags applied to the file.
+    pub tags: &'a [(Option<String>, String)],
+}
+
+/// Renders `template` for a file, returning the new filename with extension.
+///
+/// # Errors
+/// Returns a description of the problem for unknown tokens, unterminated
+/// braces, or templates that render to an empty name.
+pub fn render_template(template: &str, ctx: &RenameContext) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("Unterminated token in template: {}", template))?;
+        out.push_str(&render_token(&after[..end], ctx)?);
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+
+    let stem = crate::paths::portable_name(&sanitize_filename(out.trim()));
+    if stem.is_empty() {
+        return Err("Template renders an empty filename".to_string());
+    }
+
+    Ok(match ctx.extension {
+        Some(ext) if !ext.is_empty() => format!("{}.{}", stem, ext),
+        _ => stem,
+    })
+}
+
+fn render_token(token: &str, ctx: &RenameContext) -> Result<String, String> {
+    let (name, arg) = match token.split_once(':') {
+        Some((name, arg)) => (name, Some(arg)),
+        None => (token, None),
+    };
+
+    match name {
+        "original" => Ok(ctx.original_stem.to_string()),
+        "seq" => {
+            let width = match arg {
+                Some(w) => w.parse::<usize>().map_err(|_| format!("Invalid {{seq}} width: {}", w))?,
+                None => 1,
+            };
+            Ok(format!("{:0width$}", ctx.seq, width = width))
+        }
+        "date" => {
+            let pattern = java_date_pattern_to_chrono(arg.unwrap_or("yyyy-MM-dd"));
+            Ok(ctx.created_at.format(&pattern).to_string())
+        }
+        "tag" => {
+            let parent = arg.ok_or_else(|| "{tag} requires a parent tag name, e.g. {tag:client}".to_string())?;
+            Ok(ctx
+                .tags
+                .iter()
+                .find(|(p, _)| p.as_deref().is_some_and(|p| p.eq_ignore_ascii_case(parent)))
+                .map(|(_, name)| name.clone())
+                .unwrap_or_default())
+        }
+        _ => Err(format!("Unknown token {{{}}}", token)),
+    }
+}
+
+/// Converts `yyyyMMdd`-style patterns to chrono's `%Y%m%d` syntax.
+fn java_date_pattern_to_chrono(pattern: &str) -> String {
+    const TOKENS: [(&str, &str); 7] = [
+        ("yyyy", "%Y"),
+        ("yy", "%y"),
+        ("MM", "%m"),
+        ("dd", "%d"),
+        ("HH", "%H"),
+        ("mm", "%M"),
+        ("ss", "%S"),
+    ];
+
+    let mut out = String::new();
+    let mut rest = pattern;
+    'outer: while !rest.is_empty() {
+        for (java, chrono_fmt) in TOKENS {
+            if let Some(stripped) = rest.strip_prefix(java) {
+                out.push_str(chrono_fmt);
+                rest = stripped;
+                continue 'outer;
+            }
+        }
+        let c = rest.chars().next().unwrap_or_default();
+        if c == '%' {
+            out.push_str("%%");
+        } else {
+            out.push(c);
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+/// Replaces characters that are invalid in filenames on any supported OS.
+fn sanitize_filename(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn ctx<'a>(tags: &'a [(Option<String>, String)]) -> RenameContext<'a> {
+        RenameContext {
+            original_stem: "IMG_0001",
+            extension: Some("jpg"),
+            seq: 7,
+            created_at: Utc.with_ymd_and_hms(2025, 3, 9, 14, 5, 0).unwrap(),
+            tags,
+        }
+    }
+
+    #[test]
+    fn test_render_tokens() {
+        let tags = vec![(Some("Client".to_string()), "Acme".to_string()), (None, "Hero".to_string())];
+        let c = ctx(&tags);
+        assert_eq!(render_template("{original}", &c).unwrap(), "IMG_0001.jpg");
+        assert_eq!(render_template("{date:yyyyMMdd}_{seq:3}", &c).unwrap(), "20250309_007.jpg");
+        assert_eq!(render_template("{tag:client}-{seq}", &c).unwrap(), "Acme-7.jpg");
+        assert_eq!(render_template("{date}", &c).unwrap(), "2025-03-09.jpg");
+    }
+
+    #[test]
+    fn test_render_errors_and_sanitizing() {
+        let c = ctx(&[]);
+        assert!(render_template("{unknown}", &c).is_err());
+        assert!(render_template("{seq", &c).is_err());
+        assert!(render_template("{tag:client}", &c).is_err());
+        assert_eq!(render_template("a/b:{seq}", &c).unwrap(), "a_b_7.jpg");
+        assert_eq!(render_template("nul", &c).unwrap(), "nul_.jpg");
+        assert_eq!(render_template("draft. ", &c).unwrap(), "draft.jpg");
+    }
+}
@@ -0,0 +1,355 @@
+//! Slideshow rendering and M3U export of playlists.
+//!
+//! A slideshow is rendered by FFmpeg: every item is an input (stills are
+//! looped for their duration, videos are trimmed or held on their last frame),
+//! scaled and letterboxed to the preset size, and chained with `xfade`
+//! crossfades. Long playlists are rendered in segments of at most
+//! `MAX_INPUTS` items, which are then crossfaded together the same way, so no
+//! run opens more files than the system allows. Formats FFmpeg can't read
+//! (PSD, RAW...) go through the preview variant cache first.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use tauri::{AppHandle, Runtime};
+
+use crate::db::models::PlaylistItem;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::formats::{FileFormat, MediaType, PreviewStrategy};
+use crate::streaming::process_manager::{self, JobKind};
+
+/// Output frame rate.
+const FPS: u32 = 30;
+
+/// Hard limit for a slideshow render.
+const RENDER_TIMEOUT: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// Crossfade length used when none is given.
+const DEFAULT_TRANSITION: f64 = 1.0;
+
+/// Most inputs given to a single FFmpeg run.
+const MAX_INPUTS: usize = 32;
+
+/// Quality of the intermediate segments of long slideshows, high enough
+/// that encoding them again doesn't show.
+const SEGMENT_CRF: u32 = 14;
+
+/// Output size and quality of a slideshow.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SlideshowPreset {
+    pub width: u32,
+    pub height: u32,
+    /// x264 constant rate factor.
+    pub crf: u32,
+}
+
+impl SlideshowPreset {
+    /// `720p`, `1080p`, `4k` or `square` (1080x1080, for social media).
+    pub fn parse(name: &str) -> Option<Self> {
+        let (width, height, crf) = match name {
+            "720p" => (1280, 720, 23),
+            "1080p" => (1920, 1080, 21),
+            "4k" => (3840, 2160, 20),
+            "square" => (1080, 1080, 21),
+            _ => return None,
+        };
+        Some(Self { width, height, crf })
+    }
+}
+
+/// One resolved slideshow input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlideshowInput {
+    pub path: PathBuf,
+    /// Seconds on screen, crossfades included.
+    pub duration: f64,
+    pub is_video: bool,
+}
+
+/// Crossfade length, shortened so it never takes more than half of the shortest item.
+fn effective_transition(inputs: &[SlideshowInput], transition: f64) -> f64 {
+    let shortest = inputs.iter().map(|i| i.duration).fold(f64::INFINITY, f64::min);
+    transition.max(0.0).min(shortest / 2.0)
+}
+
+/// Length of `inputs` chained with crossfades of `transition` seconds.
+fn chained_duration(inputs: &[SlideshowInput], transition: f64) -> f64 {
+    let total: f64 = inputs.iter().map(|i| i.duration).sum();
+    total - transition * inputs.len().saturating_sub(1) as f64
+}
+
+/// Builds the FFmpeg arguments rendering `inputs` to `output`.
+pub fn build_slideshow_args(
+    inputs: &[SlideshowInput],
+    preset: SlideshowPreset,
+    transition: f64,
+    output: &Path,
+) -> Vec<String> {
+    let (w, h) = (preset.width, preset.height);
+    let transition = effective_transition(inputs, transition);
+    let mut args: Vec<String> = vec!["-y".into(), "-hide_banner".into(), "-loglevel".into(), "error".into()];
+
+    for input in inputs {
+        if !input.is_video {
+            args.extend(["-loop".into(), "1".into(), "-framerate".into(), FPS.to_string()]);
+        }
+        args.extend(["-t".into(), format!("{:.3}", input.duration), "-i".into(), input.path.to_string_lossy().to_string()]);
+    }
+
+    let mut filters: Vec<String> = Vec::new();
+    for (i, input) in inputs.iter().enumerate() {
+        // Short videos hold their last frame until the item ends
+        let hold = if input.is_video { format!("tpad=stop_mode=clone:stop_duration={:.3},", input.duration) } else { String::new() };
+        filters.push(format!(
+            "[{i}:v]scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2:color=black,setsar=1,fps={FPS},format=yuv420p,{hold}trim=duration={d:.3},setpts=PTS-STARTPTS[v{i}]",
+            d = input.duration,
+        ));
+    }
+
+    let mut last = "v0".to_string();
+    let mut offset = 0.0;
+    for (previous, input) in inputs.iter().enumerate().take(inputs.len().saturating_sub(1)) {
+        let next = previous + 1;
+        offset += input.duration - transition;
+        let label = format!("x{}", next);
+        if transition > 0.0 {
+            filters.push(format!("[{last}][v{next}]xfade=transition=fade:duration={transition:.3}:offset={offset:.3}[{label}]"));
+        } else {
+            filters.push(format!("[{last}][v{next}]concat=n=2:v=1:a=0[{label}]"));
+        }
+        last = label;
+    }
+
+    args.extend([
+        "-filter_complex".into(),
+        filters.join(";"),
+        "-map".into(),
+        format!("[{}]", last),
+        "-c:v".into(),
+        "libx264".into(),
+        "-preset".into(),
+        "medium".into(),
+        "-crf".into(),
+        preset.crf.to_string(),
+        "-pix_fmt".into(),
+        "yuv420p".into(),
+        "-movflags".into(),
+        "+faststart".into(),
+        output.to_string_lossy().to_string(),
+    ]);
+    args
+}
+
+/// Writes an extended M3U playlist. Still images carry VLC's
+/// `image-duration` option so they play for their slideshow duration.
+pub fn build_m3u(items: &[(PathBuf, f64, bool)]) -> String {
+    let mut m3u = String::from("#EXTM3U\n");
+    for (path, duration, is_still) in items {
+        let title = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        m3u.push_str(&format!("#EXTINF:{},{}\n", duration.round() as i64, title));
+        if *is_still {
+            m3u.push_str(&format!("#EXTVLCOPT:image-duration={}\n", duration.round() as i64));
+        }
+        m3u.push_str(&path.to_string_lossy());
+        m3u.push('\n');
+    }
+    m3u
+}
+
+/// Media type of a file, `None` if it is not a supported format.
+pub fn media_type(path: &Path) -> Option<MediaType> {
+    FileFormat::detect(path).map(|f| f.type_category.clone())
+}
+
+/// Resolves playlist items to slideshow inputs, skipping audio and missing files.
+pub fn resolve_inputs<R: Runtime>(
+    app: &AppHandle<R>,
+    items: &[PlaylistItem],
+    default_duration: f64,
+    preset: SlideshowPreset,
+) -> Vec<SlideshowInput> {
+    let maxdim = preset.width.max(preset.height);
+    let mut inputs = Vec::new();
+
+    for item in items {
+        let path = crate::paths::from_db(&item.image.path);
+        let Some(format) = FileFormat::detect(&path) else {
+            continue;
+        };
+        let duration = item.duration.unwrap_or(default_duration).max(0.1);
+
+        let resolved = match format.type_category {
+            MediaType::Audio => continue,
+            MediaType::Video => Some(path),
+            _ if matches!(format.preview_strategy, PreviewStrategy::BrowserNative) => Some(path),
+            _ => match crate::thumbnails::variants::get_or_create_variant(app, &path, maxdim) {
+                Ok(Some(variant)) => Some(variant),
+                Ok(None) => Some(path),
+                Err(e) => {
+                    eprintln!("WARN: Skipping {:?} in slideshow: {}", item.image.filename, e);
+                    None
+                }
+            },
+        };
+
+        if let Some(path) = resolved {
+            let is_video = format.type_category == MediaType::Video;
+            inputs.push(SlideshowInput { path, duration, is_video });
+        }
+    }
+    inputs
+}
+
+/// Renders a slideshow to an MP4 file.
+pub fn render(
+    ffmpeg_path: &Path,
+    inputs: &[SlideshowInput],
+    preset: SlideshowPreset,
+    transition: f64,
+    output: &Path,
+) -> Result<(), String> {
+    if inputs.is_empty() {
+        return Err("Playlist has no items that can be rendered".to_string());
+    }
+    if inputs.len() <= MAX_INPUTS {
+        return run_ffmpeg(ffmpeg_path, inputs, preset, transition, output);
+    }
+
+    // Every level renders groups of inputs to segments crossfaded by the next
+    // one; the shared crossfade length keeps the boundaries as in one run
+    let transition = effective_transition(inputs, transition);
+    let segment_preset = SlideshowPreset { crf: SEGMENT_CRF.min(preset.crf), ..preset };
+    let name = output.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let work_dir = output.with_file_name(format!(".{}.segments", name));
+    std::fs::create_dir_all(&work_dir).map_err(|e| e.to_string())?;
+
+    let result = (|| {
+        let mut level = inputs.to_vec();
+        let mut depth = 0;
+        while level.len() > MAX_INPUTS {
+            let mut segments = Vec::with_capacity(level.len().div_ceil(MAX_INPUTS));
+            for (i, group) in level.chunks(MAX_INPUTS).enumerate() {
+                let path = work_dir.join(format!("{}-{:04}.mp4", depth, i));
+                run_ffmpeg(ffmpeg_path, group, segment_preset, transition, &path)?;
+                segments.push(SlideshowInput { path, duration: chained_duration(group, transition), is_video: true });
+            }
+            level = segments;
+            depth += 1;
+        }
+        run_ffmpeg(ffmpeg_path, &level, preset, transition, output)
+    })();
+    let _ = std::fs::remove_dir_all(&work_dir);
+    result
+}
+
+/// Renders `inputs` to `output` in a single FFmpeg run.
+fn run_ffmpeg(
+    ffmpeg_path: &Path,
+    inputs: &[SlideshowInput],
+    preset: SlideshowPreset,
+    transition: f64,
+    output: &Path,
+) -> Result<(), String> {
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(build_slideshow_args(inputs, preset, transition, output));
+
+    let result = process_manager::run_supervised(cmd, JobKind::Render, &output.to_string_lossy(), RENDER_TIMEOUT)
+        .map_err(|e| e.to_string())?;
+    if !result.status.success() {
+        let _ = std::fs::remove_file(output);
+        return Err(format!("FFmpeg failed: {}", String::from_utf8_lossy(&result.stderr).trim()));
+    }
+    Ok(())
+}
+
+/// Renders a playlist to `output` with a named preset (see [`SlideshowPreset::parse`]).
+/// FFmpeg is recorded under the queued job `job_id`, if any, for
+/// `cancel_job` to kill it.
+pub async fn render_playlist<R: Runtime>(
+    app: &AppHandle<R>,
+    db: &Db,
+    playlist_id: i64,
+    preset: &str,
+    output: &Path,
+    transition: Option<f64>,
+    job_id: Option<i64>,
+) -> AppResult<()> {
+    let preset = SlideshowPreset::parse(preset)
+        .ok_or_else(|| AppError::Generic(format!("Unknown slideshow preset '{}'", preset)))?;
+    let playlist = db
+        .get_playlist(playlist_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Playlist {} not found", playlist_id)))?;
+    let items = db.get_playlist_items(playlist_id).await?;
+    let ffmpeg_path = crate::media::ffmpeg::get_ffmpeg_path(Some(app))
+        .ok_or(AppError::FfmpegMissing)?;
+
+    let app = app.clone();
+    let output = output.to_path_buf();
+    let transition = transition.unwrap_or(DEFAULT_TRANSITION);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let inputs = resolve_inputs(&app, &items, playlist.default_duration, preset);
+        println!("INFO: Rendering slideshow '{}' ({} items) to {:?}", playlist.name, inputs.len(), output);
+        process_manager::for_job(job_id, || render(&ffmpeg_path, &inputs, preset, transition, &output))
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+    .map_err(AppError::Transcoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(name: &str, duration: f64, is_video: bool) -> SlideshowInput {
+        SlideshowInput { path: PathBuf::from(name), duration, is_video }
+    }
+
+    #[test]
+    fn test_xfade_offsets() {
+        let inputs = [input("a.jpg", 4.0, false), input("b.mp4", 6.0, true), input("c.png", 4.0, false)];
+        let preset = SlideshowPreset::parse("720p").unwrap();
+        let args = build_slideshow_args(&inputs, preset, 1.0, Path::new("out.mp4"));
+        let filter = &args[args.iter().position(|a| a == "-filter_complex").unwrap() + 1];
+
+        assert!(filter.contains("[v0][v1]xfade=transition=fade:duration=1.000:offset=3.000[x1]"));
+        assert!(filter.contains("[x1][v2]xfade=transition=fade:duration=1.000:offset=8.000[x2]"));
+        assert!(filter.contains("tpad=stop_mode=clone:stop_duration=6.000,trim=duration=6.000"));
+        assert_eq!(args[args.iter().position(|a| a == "-map").unwrap() + 1], "[x2]");
+        // Only stills are looped
+        assert_eq!(args.iter().filter(|a| *a == "-loop").count(), 2);
+    }
+
+    #[test]
+    fn test_chained_duration() {
+        let inputs = [input("a.jpg", 4.0, false), input("b.mp4", 6.0, true), input("c.png", 4.0, false)];
+        assert_eq!(chained_duration(&inputs, 1.0), 12.0);
+        assert_eq!(chained_duration(&inputs[..1], 1.0), 4.0);
+    }
+
+    #[test]
+    fn test_transition_is_clamped() {
+        let inputs = [input("a.jpg", 1.0, false), input("b.jpg", 5.0, false)];
+        assert_eq!(effective_transition(&inputs, 2.0), 0.5);
+        assert_eq!(effective_transition(&inputs, -1.0), 0.0);
+    }
+
+    #[test]
+    fn test_single_item() {
+        let inputs = [input("a.jpg", 3.0, false)];
+        let args = build_slideshow_args(&inputs, SlideshowPreset::parse("square").unwrap(), 1.0, Path::new("o.mp4"));
+        assert_eq!(args[args.iter().position(|a| a == "-map").unwrap() + 1], "[v0]");
+    }
+
+    #[test]
+    fn test_build_m3u() {
+        let m3u = build_m3u(&[(PathBuf::from("/a/b.jpg"), 5.0, true), (PathBuf::from("/a/c.mp4"), 12.4, false)]);
+        assert_eq!(
+            m3u,
+            "#EXTM3U\n#EXTINF:5,b.jpg\n#EXTVLCOPT:image-duration=5\n/a/b.jpg\n#EXTINF:12,c.mp4\n/a/c.mp4\n"
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Statistics history of smart folders.
+//!
+//! Smart folders are evaluated live, so on their own they only tell where a
+//! backlog stands today. Once a day each one is evaluated in the background
+//! and its image count, untagged count and rating distribution recorded,
+//! which is what burn-down charts (an "untagged images" folder shrinking
+//! over the weeks) are drawn from.
+
+use std::sync::Arc;
+
+use chrono::{Duration as ChronoDuration, Utc};
+use tokio::time::{sleep, Duration};
+
+use crate::db::models::SmartFolderSnapshot;
+use crate::db::search::SearchGroup;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+
+/// Hours between two snapshots of a smart folder.
+const SNAPSHOT_HOURS: i64 = 24;
+
+/// How often due smart folders are checked; bounds how late a snapshot is.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// History returned when no period is given.
+const DEFAULT_DAYS: u32 = 90;
+
+/// Records the statistics of a smart folder now. Returns the new snapshot id.
+pub async fn snapshot(db: &Db, smart_folder_id: i64, query_json: &str) -> AppResult<i64> {
+    let query: SearchGroup = serde_json::from_str(query_json)
+        .map_err(|e| AppError::Generic(format!("Invalid smart folder query: {}", e)))?;
+    Ok(db.take_smart_folder_snapshot(smart_folder_id, &query).await?)
+}
+
+/// Snapshots of a smart folder over the last `days`, oldest first.
+pub async fn trends(db: &Db, smart_folder_id: i64, days: Option<u32>) -> AppResult<Vec<SmartFolderSnapshot>> {
+    if db.get_smart_folder(smart_folder_id).await?.is_none() {
+        return Err(AppError::NotFound(format!("Smart folder {} not found", smart_folder_id)));
+    }
+    let since = Utc::now() - ChronoDuration::days(days.unwrap_or(DEFAULT_DAYS) as i64);
+    Ok(db.get_smart_folder_snapshots(smart_folder_id, since).await?)
+}
+
+/// Starts the daily snapshots on the async runtime.
+pub fn start(db: Arc<Db>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            // Snapshots are library edits, so they wait while the library is read-only
+            if !crate::library::read_only::is_enabled() {
+                match db.get_smart_folders_due_for_snapshot(SNAPSHOT_HOURS).await {
+                    Ok(due) => {
+                        for (id, query_json) in due {
+                            if let Err(e) = snapshot(&db, id, &query_json).await {
+                                eprintln!("WARN: Smart folder {} snapshot failed: {}", id, e);
+                            }
+                        }
+                    }
+                    Err(e) => eprintln!("WARN: Could not list smart folders due for a snapshot: {}", e),
+                }
+            }
+
+            sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_snapshots() {
+        let library = crate::testkit::TestLibrary::open("smart-folder-history").await;
+        let db = &library.db;
+        library.seed_images(&["1.jpg", "2.jpg", "3.jpg", "4.jpg"]).await;
+        db.update_images_rating(&[2, 3], 3).await.unwrap();
+        db.update_image_rating(4, 5).await.unwrap();
+        sqlx::query("INSERT INTO tags (id, name) VALUES (1, 'done')").execute(&db.pool).await.unwrap();
+        sqlx::query("INSERT INTO image_tags (image_id, tag_id) VALUES (2, 1)").execute(&db.pool).await.unwrap();
+
+        let everything = r#"{"id":"root","logicalOperator":"and","items":[]}"#;
+        let id = db.save_smart_folder("All", everything).await.unwrap();
+        assert_eq!(db.get_smart_folders_due_for_snapshot(SNAPSHOT_HOURS).await.unwrap(), vec![(id, everything.to_string())]);
+
+        snapshot(db, id, everything).await.unwrap();
+        assert!(db.get_smart_folders_due_for_snapshot(SNAPSHOT_HOURS).await.unwrap().is_empty());
+        let history = trends(db, id, None).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].image_count, 4);
+        assert_eq!(history[0].untagged_count, 3);
+        assert_eq!(history[0].ratings, [1, 0, 0, 2, 0, 1]);
+
+        assert!(snapshot(db, id, "not json").await.is_err());
+        db.delete_smart_folder(id).await.unwrap();
+        assert!(trends(db, id, None).await.is_err());
+    }
+}
@@ -0,0 +1,502 @@
+//! Smart folders shared as JSON.
+//!
+//! A smart folder's query refers to tags, folders and collections by id,
+//! which mean nothing in another library. The export describes every one
+//! the query refers to next to it, and the import maps them onto this
+//! library: tags by name (tag names are unique), created under the same
+//! parents when missing, folders by path, then by name when a single folder
+//! has it, and collections by name when a single collection has it. Queries are validated before anything is saved, so a
+//! hand-edited or truncated file can't leave a smart folder that silently
+//! matches everything.
+
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::db::search::{SearchCriterion, SearchGroup, SearchItem, AUDIT_OPERATORS, CRITERION_KEYS};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+
+/// Value of `format` in a shared smart folder.
+pub const FORMAT: &str = "mundam.smart-folder";
+pub const VERSION: u32 = 1;
+
+/// Deepest nesting of groups accepted, and most criteria.
+const MAX_DEPTH: usize = 16;
+const MAX_CRITERIA: usize = 500;
+
+/// A smart folder as shared between libraries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedSmartFolder {
+    pub format: String,
+    pub version: u32,
+    pub name: String,
+    pub query: SearchGroup,
+    #[serde(default)]
+    pub tags: Vec<SharedTag>,
+    #[serde(default)]
+    pub folders: Vec<SharedFolder>,
+    #[serde(default)]
+    pub collections: Vec<SharedCollection>,
+}
+
+/// A tag the query refers to, with the names of its parents, outermost first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedTag {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub parents: Vec<String>,
+}
+
+/// A folder the query refers to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedFolder {
+    pub id: i64,
+    pub name: String,
+    pub path: String,
+}
+
+/// A collection the query refers to. Only its name is shared: membership
+/// is the target library's own.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedCollection {
+    pub id: i64,
+    pub name: String,
+}
+
+/// Outcome of [`import`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartFolderImport {
+    pub id: i64,
+    /// Name it was saved under, numbered when the shared one was taken.
+    pub name: String,
+    /// Tags the query needed that this library didn't have.
+    pub created_tags: Vec<String>,
+    /// References that pointed at another id here.
+    pub remapped_tags: usize,
+    pub remapped_folders: usize,
+    pub remapped_collections: usize,
+}
+
+/// Id a `tags` or `collection` criterion refers to, given as a number or a
+/// string.
+fn tag_id(value: &Value) -> Option<i64> {
+    value.as_i64().or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
+
+/// A value left empty in the editor, which the search ignores.
+fn is_blank(value: &Value) -> bool {
+    value.is_null() || value.as_str().is_some_and(|s| s.trim().is_empty())
+}
+
+fn criteria(group: &SearchGroup) -> Vec<&SearchCriterion> {
+    let mut found = Vec::new();
+    for item in &group.items {
+        match item {
+            SearchItem::Group(group) => found.extend(criteria(group)),
+            SearchItem::Criterion(c) => found.push(c),
+        }
+    }
+    found
+}
+
+fn criteria_mut(group: &mut SearchGroup, f: &mut impl FnMut(&mut SearchCriterion)) {
+    for item in &mut group.items {
+        match item {
+            SearchItem::Group(group) => criteria_mut(group, f),
+            SearchItem::Criterion(c) => f(c),
+        }
+    }
+}
+
+fn depth(group: &SearchGroup) -> usize {
+    1 + group
+        .items
+        .iter()
+        .map(|item| match item {
+            SearchItem::Group(group) => depth(group),
+            SearchItem::Criterion(_) => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Checks that every criterion is one the search understands.
+pub fn validate(query: &SearchGroup) -> Result<(), String> {
+    if depth(query) > MAX_DEPTH {
+        return Err(format!("Groups are nested more than {} levels deep", MAX_DEPTH));
+    }
+    let criteria = criteria(query);
+    if criteria.len() > MAX_CRITERIA {
+        return Err(format!("More than {} criteria", MAX_CRITERIA));
+    }
+    for c in criteria {
+        if c.operator.is_empty() {
+            return Err(format!("The \"{}\" criterion has no operator", c.key));
+        }
+        if AUDIT_OPERATORS.contains(&c.operator.as_str()) {
+            continue;
+        }
+        match c.key.as_str() {
+            "related_to" => return Err("A \"related to\" criterion refers to a file of the library it came from".to_string()),
+            "working_set" => return Err("A \"working set\" criterion refers to a set of the session it came from".to_string()),
+            "tags" if !is_blank(&c.value) && tag_id(&c.value).is_none() => return Err(format!("Invalid tag reference: {}", c.value)),
+            "folder" if !is_blank(&c.value) && c.value.as_i64().is_none() => return Err(format!("Invalid folder reference: {}", c.value)),
+            "collection" if !is_blank(&c.value) && tag_id(&c.value).is_none() => {
+                return Err(format!("Invalid collection reference: {}", c.value))
+            }
+            key if !CRITERION_KEYS.contains(&key) => return Err(format!("Unknown criterion \"{}\"", key)),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Ids the query refers to.
+#[derive(Debug, Default, PartialEq)]
+struct References {
+    tags: BTreeSet<i64>,
+    folders: BTreeSet<i64>,
+    collections: BTreeSet<i64>,
+}
+
+fn references(query: &SearchGroup) -> References {
+    let mut references = References::default();
+    for c in criteria(query) {
+        match c.key.as_str() {
+            "tags" => references.tags.extend(tag_id(&c.value)),
+            "folder" => references.folders.extend(c.value.as_i64()),
+            "collection" => references.collections.extend(tag_id(&c.value)),
+            _ => {}
+        }
+    }
+    references
+}
+
+/// Maps of ids from the shared library to this one.
+#[derive(Debug, Default)]
+struct IdMaps {
+    tags: HashMap<i64, i64>,
+    folders: HashMap<i64, i64>,
+    collections: HashMap<i64, i64>,
+}
+
+/// Points the query's references at the ids of this library. Tag and
+/// collection ids keep the type they had, since the editor stores them as
+/// strings.
+fn remap(query: &mut SearchGroup, maps: &IdMaps) {
+    criteria_mut(query, &mut |c| {
+        let ids = match c.key.as_str() {
+            "tags" => &maps.tags,
+            "collection" => &maps.collections,
+            "folder" => {
+                if let Some(new_id) = c.value.as_i64().and_then(|id| maps.folders.get(&id)) {
+                    c.value = Value::from(*new_id);
+                }
+                return;
+            }
+            _ => return,
+        };
+        if let Some(new_id) = tag_id(&c.value).and_then(|id| ids.get(&id)) {
+            c.value = if c.value.is_string() { Value::from(new_id.to_string()) } else { Value::from(*new_id) };
+        }
+    });
+}
+
+/// `name`, or `name (2)`, `name (3)`… when it is taken.
+fn unique_name(name: &str, taken: &[String]) -> String {
+    let is_taken = |candidate: &str| taken.iter().any(|t| t.eq_ignore_ascii_case(candidate));
+    if !is_taken(name) {
+        return name.to_string();
+    }
+    (2..).map(|n| format!("{} ({})", name, n)).find(|candidate| !is_taken(candidate)).unwrap_or_else(|| name.to_string())
+}
+
+/// Smart folder `id` as shareable JSON.
+pub async fn export(db: &Db, id: i64) -> AppResult<String> {
+    let (name, query_json) = db
+        .get_smart_folder(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Smart folder {}", id)))?;
+    let query: SearchGroup = serde_json::from_str(&query_json)
+        .map_err(|e| AppError::Internal(format!("Smart folder {} has an unreadable query: {}", id, e)))?;
+    let References { tags: tag_ids, folders: folder_ids, collections: collection_ids } = references(&query);
+
+    let all_tags: HashMap<i64, (String, Option<i64>)> =
+        db.get_all_tags().await?.into_iter().map(|tag| (tag.id, (tag.name, tag.parent_id))).collect();
+    // Deleted tags and folders are left out; the import then reports them
+    let tags = tag_ids
+        .into_iter()
+        .filter_map(|id| {
+            let (name, mut parent_id) = all_tags.get(&id).cloned()?;
+            let mut parents = Vec::new();
+            while let Some((parent_name, grandparent_id)) = parent_id.and_then(|p| all_tags.get(&p)) {
+                // Guards against a cycle in a damaged tree
+                if parents.len() >= all_tags.len() {
+                    break;
+                }
+                parents.insert(0, parent_name.clone());
+                parent_id = *grandparent_id;
+            }
+            Some(SharedTag { id, name, parents })
+        })
+        .collect();
+    let folders = db
+        .get_folder_hierarchy()
+        .await?
+        .into_iter()
+        .filter(|(folder_id, ..)| folder_ids.contains(folder_id))
+        .map(|(id, _, path, name, _)| SharedFolder { id, name, path })
+        .collect();
+    let collections = db
+        .get_collections()
+        .await?
+        .into_iter()
+        .filter(|collection| collection_ids.contains(&collection.id))
+        .map(|collection| SharedCollection { id: collection.id, name: collection.name })
+        .collect();
+
+    let shared = SharedSmartFolder { format: FORMAT.to_string(), version: VERSION, name, query, tags, folders, collections };
+    serde_json::to_string_pretty(&shared).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+/// Saves a shared smart folder in this library, with its references mapped.
+///
+/// # Errors
+/// Returns `AppError::Generic`, without saving anything, for files that
+/// aren't shared smart folders, queries that fail [`validate`], references
+/// the file doesn't describe, and folders and collections this library
+/// doesn't have.
+pub async fn import(db: &Db, json: &str) -> AppResult<SmartFolderImport> {
+    let mut shared: SharedSmartFolder =
+        serde_json::from_str(json).map_err(|e| AppError::Generic(format!("Not a shared smart folder: {}", e)))?;
+    if shared.format != FORMAT {
+        return Err(AppError::Generic("Not a shared smart folder".to_string()));
+    }
+    if shared.version > VERSION {
+        return Err(AppError::Generic("This smart folder was shared by a newer version of Mundam".to_string()));
+    }
+    let name = shared.name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::Generic("The shared smart folder has no name".to_string()));
+    }
+    validate(&shared.query).map_err(AppError::Generic)?;
+
+    let References { tags: tag_ids, folders: folder_ids, collections: collection_ids } = references(&shared.query);
+    let described_tags: HashMap<i64, &SharedTag> = shared.tags.iter().map(|tag| (tag.id, tag)).collect();
+    let described_folders: HashMap<i64, &SharedFolder> = shared.folders.iter().map(|folder| (folder.id, folder)).collect();
+    if let Some(id) = tag_ids.iter().find(|id| !described_tags.contains_key(id)) {
+        return Err(AppError::Generic(format!("The query refers to tag {}, which the file doesn't describe", id)));
+    }
+    if let Some(id) = folder_ids.iter().find(|id| !described_folders.contains_key(id)) {
+        return Err(AppError::Generic(format!("The query refers to folder {}, which the file doesn't describe", id)));
+    }
+    let described_collections: HashMap<i64, &SharedCollection> =
+        shared.collections.iter().map(|collection| (collection.id, collection)).collect();
+    if let Some(id) = collection_ids.iter().find(|id| !described_collections.contains_key(id)) {
+        return Err(AppError::Generic(format!("The query refers to collection {}, which the file doesn't describe", id)));
+    }
+
+    // Folders and collections first: a missing one fails the import before tags are created
+    let hierarchy = db.get_folder_hierarchy().await?;
+    let mut maps = IdMaps::default();
+    let mut missing_folders = Vec::new();
+    for id in &folder_ids {
+        let folder = described_folders[id];
+        let by_path = hierarchy.iter().find(|(_, _, path, ..)| *path == folder.path);
+        let by_name = || {
+            let mut named = hierarchy.iter().filter(|(_, _, _, name, _)| *name == folder.name);
+            match (named.next(), named.next()) {
+                (Some(only), None) => Some(only),
+                _ => None,
+            }
+        };
+        match by_path.or_else(by_name) {
+            Some((local_id, ..)) => {
+                maps.folders.insert(*id, *local_id);
+            }
+            None => missing_folders.push(folder.path.clone()),
+        }
+    }
+    if !missing_folders.is_empty() {
+        return Err(AppError::Generic(format!("Folders not in this library: {}", missing_folders.join(", "))));
+    }
+    let local_collections = db.get_collections().await?;
+    let mut missing_collections = Vec::new();
+    for id in &collection_ids {
+        let shared_name = &described_collections[id].name;
+        let mut named = local_collections.iter().filter(|collection| collection.name == *shared_name);
+        match (named.next(), named.next()) {
+            (Some(only), None) => {
+                maps.collections.insert(*id, only.id);
+            }
+            _ => missing_collections.push(shared_name.clone()),
+        }
+    }
+    if !missing_collections.is_empty() {
+        return Err(AppError::Generic(format!(
+            "Collections not in this library, or not under a single name: {}",
+            missing_collections.join(", ")
+        )));
+    }
+
+    let existing_tags: HashMap<String, i64> = db.get_all_tags().await?.into_iter().map(|tag| (tag.name, tag.id)).collect();
+    let mut created_tags = Vec::new();
+    for id in &tag_ids {
+        let tag = described_tags[id];
+        let local_id = match existing_tags.get(&tag.name) {
+            Some(local_id) => *local_id,
+            None => {
+                let path: Vec<String> = tag.parents.iter().chain(std::iter::once(&tag.name)).cloned().collect();
+                created_tags.push(tag.name.clone());
+                db.ensure_tag_path(&path)
+                    .await?
+                    .ok_or_else(|| AppError::Internal(format!("Could not create tag {}", tag.name)))?
+            }
+        };
+        maps.tags.insert(*id, local_id);
+    }
+
+    remap(&mut shared.query, &maps);
+    let query_json = serde_json::to_string(&shared.query).map_err(|e| AppError::Internal(e.to_string()))?;
+    let name = unique_name(&name, &db.get_smart_folder_names().await?);
+    let id = db.save_smart_folder(&name, &query_json).await?;
+
+    Ok(SmartFolderImport {
+        id,
+        name,
+        created_tags,
+        remapped_tags: maps.tags.iter().filter(|(from, to)| from != to).count(),
+        remapped_folders: maps.folders.iter().filter(|(from, to)| from != to).count(),
+        remapped_collections: maps.collections.iter().filter(|(from, to)| from != to).count(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(value: Value) -> SearchGroup {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn criterion(key: &str, operator: &str, value: Value) -> Value {
+        serde_json::json!({ "id": "c", "key": key, "operator": operator, "value": value })
+    }
+
+    #[test]
+    fn test_validate() {
+        let ok = query(serde_json::json!({
+            "id": "root", "logicalOperator": "and", "items": [
+                criterion("tags", "contains", "3".into()),
+                { "id": "g", "logicalOperator": "or", "items": [criterion("folder", "in", 7.into())] },
+                criterion("anything", "has_no_thumbnail", Value::Null),
+                criterion("collection", "in", "5".into()),
+            ]
+        }));
+        assert_eq!(validate(&ok), Ok(()));
+        assert_eq!(
+            references(&ok),
+            References { tags: BTreeSet::from([3]), folders: BTreeSet::from([7]), collections: BTreeSet::from([5]) }
+        );
+        let bad_collection = query(serde_json::json!({ "id": "r", "logicalOperator": "and", "items": [criterion("collection", "in", "picks".into())] }));
+        assert!(validate(&bad_collection).is_err());
+
+        let unknown = query(serde_json::json!({ "id": "r", "logicalOperator": "and", "items": [criterion("colour", "is", "red".into())] }));
+        assert!(validate(&unknown).unwrap_err().contains("colour"));
+        let related = query(serde_json::json!({ "id": "r", "logicalOperator": "and", "items": [criterion("related_to", "is", 4.into())] }));
+        assert!(validate(&related).is_err());
+        let bad_tag = query(serde_json::json!({ "id": "r", "logicalOperator": "and", "items": [criterion("tags", "contains", "hero".into())] }));
+        assert!(validate(&bad_tag).is_err());
+
+        let mut deep = serde_json::json!({ "id": "leaf", "logicalOperator": "and", "items": [] });
+        for _ in 0..MAX_DEPTH {
+            deep = serde_json::json!({ "id": "g", "logicalOperator": "and", "items": [deep] });
+        }
+        assert!(validate(&query(deep)).is_err());
+    }
+
+    #[test]
+    fn test_remap_keeps_value_types() {
+        let mut q = query(serde_json::json!({
+            "id": "root", "logicalOperator": "and", "items": [
+                criterion("tags", "contains", "3".into()),
+                criterion("tags", "not_contains", 4.into()),
+                criterion("folder", "is", 7.into()),
+                criterion("collection", "in", "5".into()),
+            ]
+        }));
+        let maps = IdMaps {
+            tags: HashMap::from([(3, 30), (4, 40)]),
+            folders: HashMap::from([(7, 70)]),
+            collections: HashMap::from([(5, 50)]),
+        };
+        remap(&mut q, &maps);
+        let values: Vec<Value> = criteria(&q).iter().map(|c| c.value.clone()).collect();
+        assert_eq!(values, vec![Value::from("30"), Value::from(40), Value::from(70), Value::from("50")]);
+    }
+
+    #[test]
+    fn test_unique_name() {
+        let taken = vec!["Heroes".to_string(), "heroes (2)".to_string()];
+        assert_eq!(unique_name("Villains", &taken), "Villains");
+        assert_eq!(unique_name("Heroes", &taken), "Heroes (3)");
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import_between_libraries() {
+        let source = crate::testkit::TestLibrary::open("share-source").await;
+        let target = crate::testkit::TestLibrary::open("share-target").await;
+        for (db, first_tag) in [(&source.db, 1), (&target.db, 10)] {
+            sqlx::query("INSERT INTO folders (id, path, name, is_root) VALUES (?, '/lib/shoots', 'shoots', 1)")
+                .bind(first_tag)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            sqlx::query("INSERT INTO tags (id, name) VALUES (?, 'Clients')").bind(first_tag).execute(&db.pool).await.unwrap();
+            sqlx::query("INSERT INTO collections (id, name) VALUES (?, 'Picks')").bind(first_tag).execute(&db.pool).await.unwrap();
+        }
+        sqlx::query("INSERT INTO tags (id, name, parent_id) VALUES (2, 'Acme', 1)").execute(&source.db.pool).await.unwrap();
+
+        let q = serde_json::json!({
+            "id": "root", "logicalOperator": "and", "items": [
+                criterion("tags", "contains", "1".into()),
+                criterion("tags", "contains", "2".into()),
+                criterion("folder", "in", 1.into()),
+                criterion("collection", "in", 1.into()),
+            ]
+        });
+        let id = source.db.save_smart_folder("Acme shoots", &q.to_string()).await.unwrap();
+        let json = export(&source.db, id).await.unwrap();
+
+        let imported = import(&target.db, &json).await.unwrap();
+        assert_eq!(imported.name, "Acme shoots");
+        assert_eq!(imported.created_tags, vec!["Acme".to_string()]);
+        assert_eq!((imported.remapped_tags, imported.remapped_folders, imported.remapped_collections), (2, 1, 1));
+
+        let (_, saved) = target.db.get_smart_folder(imported.id).await.unwrap().unwrap();
+        let saved: SearchGroup = serde_json::from_str(&saved).unwrap();
+        let acme_id: i64 = sqlx::query_scalar("SELECT id FROM tags WHERE name = 'Acme' AND parent_id = 10")
+            .fetch_one(&target.db.pool)
+            .await
+            .unwrap();
+        let references = references(&saved);
+        assert_eq!(references.tags, BTreeSet::from([10, acme_id]));
+        assert_eq!(references.folders, BTreeSet::from([10]));
+        assert_eq!(references.collections, BTreeSet::from([10]));
+
+        assert_eq!(import(&target.db, &json).await.unwrap().name, "Acme shoots (2)");
+        let elsewhere = json.replace("/lib/shoots", "/other/place").replace("\"shoots\"", "\"other\"");
+        assert!(import(&target.db, &elsewhere).await.is_err());
+        let unknown_collection = json.replace("\"Picks\"", "\"Rejects\"");
+        assert!(import(&target.db, &unknown_collection).await.is_err());
+        assert!(import(&target.db, "{\"format\": \"something\"}").await.is_err());
+    }
+}
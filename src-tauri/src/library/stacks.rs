@@ -0,0 +1,45 @@
+//! Propagation of ratings and tags across a stack.
+//!
+//! Cameras set to RAW+JPEG write `IMG_0001.CR3` and `IMG_0001.JPG` side by
+//! side, and both files are the same shot. With the `propagate_to_stack`
+//! setting on, the tag and rating commands apply their change to every
+//! member of the stacks they touch (see `Db::get_stack_members`).
+//!
+//! The stacks are expanded once, before the write, and the write itself
+//! doesn't propagate further: a rating set on the JPEG reaches the RAW, and
+//! the RAW's update can't bounce back or loop.
+
+use crate::db::Db;
+
+pub const SETTING_KEY: &str = "propagate_to_stack";
+
+/// Whether changes made to one member apply to the whole stack.
+pub async fn is_enabled(db: &Db) -> bool {
+    matches!(db.get_setting(SETTING_KEY).await, Ok(Some(value)) if value.as_bool() == Some(true))
+}
+
+/// `ids` followed by their stack siblings when propagation is on, each id
+/// once. Returns `ids` unchanged otherwise.
+pub async fn with_siblings(db: &Db, ids: Vec<i64>) -> Result<Vec<i64>, sqlx::Error> {
+    if ids.is_empty() || !is_enabled(db).await {
+        return Ok(ids);
+    }
+    let members = db.get_stack_members(&ids).await?;
+    Ok(merge(ids, members))
+}
+
+fn merge(ids: Vec<i64>, members: Vec<i64>) -> Vec<i64> {
+    let mut seen = std::collections::HashSet::new();
+    ids.into_iter().chain(members).filter(|id| seen.insert(*id)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_keeps_the_requested_ids_first() {
+        assert_eq!(merge(vec![5, 2], vec![1, 2, 5, 6]), vec![5, 2, 1, 6]);
+        assert_eq!(merge(vec![3, 3], vec![]), vec![3]);
+    }
+}
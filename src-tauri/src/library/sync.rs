@@ -0,0 +1,567 @@
+//! Folders kept in sync with a stored filter.
+//!
+//! A sync target mirrors the images matching a filter (say, rating ≥ 4 in a
+//! client folder) into a destination folder, flat or recreating the folders
+//! below the location. Every copied file is recorded in `sync_files`, and
+//! as an export of its image (see `library::derivatives`), so a later sync
+//! copies new members, refreshes files whose source changed and handles
+//! files whose image left the filter according to the target's deletion
+//! policy. Files a sync didn't place, and files edited at the
+//! destination since they were copied, are reported as conflicts and never
+//! touched.
+//!
+//! Targets with `auto_sync` are checked by a background loop, which queues a
+//! `sync_folder` job whenever the diff isn't empty.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use serde::Serialize;
+use tokio::time::{sleep, Duration};
+
+use crate::db::models::{SyncTarget, SyncedFile};
+use crate::db::search::ImageFilter;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::jobs::{JobContext, JobQueue};
+use crate::library::derivatives::{self, DerivativeKind};
+
+/// What happens to synced files whose image left the filter.
+pub const DELETE_POLICIES: &[&str] = &["keep", "delete", "archive"];
+
+/// Folder of the destination receiving files removed under the `archive`
+/// policy.
+pub const ARCHIVE_DIR: &str = "_removed";
+
+/// How often auto-sync targets are checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Image paths fetched per query.
+const PAGE_SIZE: usize = 500;
+
+/// An image matching the target's filter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub image_id: i64,
+    pub source: String,
+    /// Path wanted at the destination, before collisions are resolved.
+    pub relative_path: String,
+    pub size: i64,
+    /// Unix seconds.
+    pub modified: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncEntry {
+    pub relative_path: String,
+    /// `None` for removals.
+    pub image_id: Option<i64>,
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncIssue {
+    pub relative_path: String,
+    pub message: String,
+}
+
+/// Differences between a target's filter and its destination, and after a
+/// sync, what was done.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncDiff {
+    /// New members, and synced files missing from the destination.
+    pub copy: Vec<SyncEntry>,
+    /// Synced files whose source changed.
+    pub update: Vec<SyncEntry>,
+    /// Synced files whose image left the filter, deleted or archived.
+    pub remove: Vec<SyncEntry>,
+    /// Files left alone: not placed by a sync, or edited at the destination.
+    pub conflicts: Vec<SyncIssue>,
+    pub unchanged: usize,
+    /// Synced files whose image left the filter, kept under the `keep` policy.
+    pub kept: usize,
+    /// Members whose source file can't be read.
+    pub missing_sources: usize,
+    /// Copies and removals that failed during the sync.
+    pub errors: Vec<SyncIssue>,
+}
+
+impl SyncDiff {
+    pub fn is_empty(&self) -> bool {
+        self.copy.is_empty() && self.update.is_empty() && self.remove.is_empty()
+    }
+}
+
+/// Checks that the filter parses and the deletion policy is known.
+pub fn validate(filter_json: &str, delete_policy: &str) -> AppResult<()> {
+    serde_json::from_str::<ImageFilter>(filter_json)
+        .map_err(|e| AppError::Generic(format!("Invalid filter JSON: {}", e)))?;
+    if !DELETE_POLICIES.contains(&delete_policy) {
+        return Err(AppError::Generic(format!(
+            "Unknown deletion policy '{}' (expected one of {})",
+            delete_policy,
+            DELETE_POLICIES.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Path of `source` at the destination: its name, or with `keep_folders` its
+/// path below the deepest location containing it.
+pub fn destination_path(source: &str, roots: &[String], keep_folders: bool) -> String {
+    let file_name = source.rsplit(['/', '\\']).next().unwrap_or(source).to_string();
+    if !keep_folders {
+        return file_name;
+    }
+    roots
+        .iter()
+        .filter_map(|root| source.strip_prefix(root.trim_end_matches(['/', '\\'])))
+        .filter(|rest| rest.starts_with(['/', '\\']))
+        .min_by_key(|rest| rest.len())
+        .map(|rest| rest.trim_start_matches(['/', '\\']).replace('\\', "/"))
+        .unwrap_or(file_name)
+}
+
+fn split_extension(path: &str) -> (&str, &str) {
+    let name_start = path.rfind('/').map_or(0, |i| i + 1);
+    match path[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => path.split_at(name_start + dot),
+        _ => (path, ""),
+    }
+}
+
+/// `dir/name (n).ext`
+fn numbered(path: &str, n: usize) -> String {
+    let (stem, extension) = split_extension(path);
+    format!("{} ({}){}", stem, n, extension)
+}
+
+/// Whether `path` is `wanted` or a numbered copy of it.
+fn is_variant_of(path: &str, wanted: &str) -> bool {
+    if path == wanted {
+        return true;
+    }
+    let (stem, extension) = split_extension(wanted);
+    path.strip_prefix(stem)
+        .and_then(|rest| rest.strip_prefix(" ("))
+        .and_then(|rest| rest.strip_suffix(extension))
+        .and_then(|rest| rest.strip_suffix(')'))
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Gives every member a unique destination path. Members keep the path they
+/// were synced to, so renaming a collision doesn't shuffle existing files,
+/// and never take the path of a synced file whose image left the filter.
+pub fn assign_paths(members: &[Member], synced: &[SyncedFile]) -> Vec<String> {
+    let synced_by_image: HashMap<i64, &str> = synced.iter().map(|f| (f.image_id, f.relative_path.as_str())).collect();
+    let member_ids: HashSet<i64> = members.iter().map(|m| m.image_id).collect();
+    let mut taken: HashSet<String> = synced
+        .iter()
+        .filter(|f| !member_ids.contains(&f.image_id))
+        .map(|f| f.relative_path.clone())
+        .collect();
+    let mut paths: Vec<Option<String>> = members
+        .iter()
+        .map(|member| {
+            let path = synced_by_image.get(&member.image_id).filter(|p| is_variant_of(p, &member.relative_path))?;
+            taken.insert(path.to_string()).then(|| path.to_string())
+        })
+        .collect();
+
+    for (member, path) in members.iter().zip(paths.iter_mut()) {
+        if path.is_none() {
+            let mut candidate = member.relative_path.clone();
+            let mut n = 2;
+            while taken.contains(&candidate) {
+                candidate = numbered(&member.relative_path, n);
+                n += 1;
+            }
+            taken.insert(candidate.clone());
+            *path = Some(candidate);
+        }
+    }
+    paths.into_iter().flatten().collect()
+}
+
+/// Compares members with the synced files. `destination` gives the
+/// modification time of each file present at the destination.
+pub fn diff(
+    members: &[Member],
+    synced: &[SyncedFile],
+    destination: &HashMap<String, i64>,
+    delete_policy: &str,
+) -> SyncDiff {
+    let mut result = SyncDiff::default();
+    let synced_by_path: HashMap<&str, &SyncedFile> = synced.iter().map(|f| (f.relative_path.as_str(), f)).collect();
+    let paths = assign_paths(members, synced);
+    let wanted: HashSet<&str> = paths.iter().map(String::as_str).collect();
+
+    for (member, path) in members.iter().zip(&paths) {
+        let entry = SyncEntry { relative_path: path.clone(), image_id: Some(member.image_id), source: Some(member.source.clone()) };
+        match (synced_by_path.get(path.as_str()), destination.get(path)) {
+            (_, None) => result.copy.push(entry),
+            (None, Some(_)) => result.conflicts.push(SyncIssue {
+                relative_path: path.clone(),
+                message: "Not created by this sync".to_string(),
+            }),
+            (Some(file), Some(&modified)) if modified != file.destination_modified => {
+                result.conflicts.push(SyncIssue { relative_path: path.clone(), message: "Changed at the destination".to_string() })
+            }
+            (Some(file), Some(_)) => {
+                let same = file.image_id == member.image_id
+                    && file.source_size == member.size
+                    && file.source_modified == member.modified;
+                if same {
+                    result.unchanged += 1;
+                } else {
+                    result.update.push(entry);
+                }
+            }
+        }
+    }
+
+    for file in synced.iter().filter(|f| !wanted.contains(f.relative_path.as_str())) {
+        match destination.get(&file.relative_path) {
+            // Deleted at the destination; forgotten by the next sync
+            None => result.remove.push(SyncEntry { relative_path: file.relative_path.clone(), image_id: None, source: None }),
+            Some(_) if delete_policy == "keep" => result.kept += 1,
+            Some(&modified) if modified != file.destination_modified => result.conflicts.push(SyncIssue {
+                relative_path: file.relative_path.clone(),
+                message: "Changed at the destination, not removed".to_string(),
+            }),
+            Some(_) => result.remove.push(SyncEntry { relative_path: file.relative_path.clone(), image_id: None, source: None }),
+        }
+    }
+    result
+}
+
+/// Size and modification time (Unix seconds) of a file.
+fn file_stamp(metadata: &std::fs::Metadata) -> (i64, i64) {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs() as i64);
+    (metadata.len() as i64, modified)
+}
+
+fn resolve(destination: &Path, relative_path: &str) -> PathBuf {
+    relative_path.split('/').fold(destination.to_path_buf(), |path, part| path.join(part))
+}
+
+/// Reads the members of a target and the state of its destination.
+async fn gather(db: &Db, target: &SyncTarget) -> AppResult<(Vec<Member>, Vec<SyncedFile>, HashMap<String, i64>, usize)> {
+    let filter: ImageFilter = serde_json::from_str(&target.filter)
+        .map_err(|e| AppError::Generic(format!("Invalid filter JSON: {}", e)))?;
+    let group = filter.parsed_group();
+    let mut query_builder = filter.build_id_query(group.as_ref(), "");
+    let ids = query_builder.build_query_scalar::<i64>().fetch_all(&db.pool).await?;
+
+    let mut sources: Vec<(i64, String)> = Vec::with_capacity(ids.len());
+    for page in ids.chunks(PAGE_SIZE) {
+        let mut query_builder: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new("SELECT id, path FROM images WHERE id IN (");
+        let mut separated = query_builder.separated(", ");
+        for id in page {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+        sources.extend(query_builder.build_query_as::<(i64, String)>().fetch_all(&db.pool).await?);
+    }
+
+    let roots: Vec<String> = db.get_all_root_folders().await?.into_iter().map(|(_, path)| path).collect();
+    let synced = db.get_synced_files(target.id).await?;
+    let keep_folders = target.keep_folders;
+    let destination = crate::paths::from_db(&target.destination);
+
+    let stat_paths: Vec<String> = synced.iter().map(|f| f.relative_path.clone()).collect();
+    let (members, present, missing) = tokio::task::spawn_blocking(move || {
+        let mut members = Vec::with_capacity(sources.len());
+        let mut missing = 0;
+        for (image_id, source) in sources {
+            match std::fs::metadata(crate::paths::from_db(&source)) {
+                Ok(metadata) => {
+                    let (size, modified) = file_stamp(&metadata);
+                    let relative_path = destination_path(&source, &roots, keep_folders);
+                    members.push(Member { image_id, source, relative_path, size, modified });
+                }
+                Err(_) => missing += 1,
+            }
+        }
+        members.sort_by_key(|m| m.image_id);
+
+        // Every path a member may land on, and every synced file
+        let candidates = members.iter().map(|m| m.relative_path.clone()).chain(stat_paths);
+        let mut present = HashMap::new();
+        for relative_path in candidates {
+            if present.contains_key(&relative_path) {
+                continue;
+            }
+            if let Ok(metadata) = std::fs::metadata(resolve(&destination, &relative_path)) {
+                present.insert(relative_path, file_stamp(&metadata).1);
+            }
+        }
+        (members, present, missing)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    Ok((members, synced, present, missing))
+}
+
+async fn get_target(db: &Db, target_id: i64) -> AppResult<SyncTarget> {
+    db.get_sync_target(target_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Sync target {} not found", target_id)))
+}
+
+/// Diff of a target, with its members.
+async fn plan(db: &Db, target: &SyncTarget) -> AppResult<(SyncDiff, Vec<Member>)> {
+    let (members, synced, mut present, missing) = gather(db, target).await?;
+
+    // Numbered copies only exist once a collision is resolved, so stat them too
+    let paths = assign_paths(&members, &synced);
+    let destination = crate::paths::from_db(&target.destination);
+    for path in paths.iter().filter(|p| !present.contains_key(*p)) {
+        if let Ok(metadata) = tokio::fs::metadata(resolve(&destination, path)).await {
+            present.insert(path.clone(), file_stamp(&metadata).1);
+        }
+    }
+
+    let mut result = diff(&members, &synced, &present, &target.delete_policy);
+    result.missing_sources = missing;
+    Ok((result, members))
+}
+
+/// Computes what a sync of the target would do, without touching anything.
+pub async fn preview(db: &Db, target_id: i64) -> AppResult<SyncDiff> {
+    let target = get_target(db, target_id).await?;
+    Ok(plan(db, &target).await?.0)
+}
+
+async fn copy_file(source: &str, target: &Path) -> std::io::Result<i64> {
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    // Copy next to the target first so the destination never holds a partial file
+    let mut partial_name = target.file_name().unwrap_or_default().to_os_string();
+    partial_name.push(".part");
+    let partial = target.with_file_name(partial_name);
+    tokio::fs::copy(crate::paths::from_db(source), &partial).await?;
+    tokio::fs::rename(&partial, target).await?;
+    Ok(file_stamp(&tokio::fs::metadata(target).await?).1)
+}
+
+/// Moves a removed file under [`ARCHIVE_DIR`], numbering it if an earlier
+/// copy is there already.
+async fn archive_file(destination: &Path, relative_path: &str) -> std::io::Result<()> {
+    let mut archived = format!("{}/{}", ARCHIVE_DIR, relative_path);
+    let mut n = 2;
+    while tokio::fs::try_exists(resolve(destination, &archived)).await? {
+        archived = numbered(&format!("{}/{}", ARCHIVE_DIR, relative_path), n);
+        n += 1;
+    }
+    let archived = resolve(destination, &archived);
+    if let Some(parent) = archived.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::rename(resolve(destination, relative_path), archived).await
+}
+
+/// Syncs a target and returns what was done. Stops early, with the files
+/// handled so far recorded, if the job is cancelled.
+pub async fn run(db: &Db, target_id: i64, ctx: Option<&JobContext>) -> AppResult<SyncDiff> {
+    let target = get_target(db, target_id).await?;
+    let (mut result, members) = plan(db, &target).await?;
+    let members: HashMap<i64, (i64, i64)> = members.into_iter().map(|m| (m.image_id, (m.size, m.modified))).collect();
+    let destination = crate::paths::from_db(&target.destination);
+
+    let total = (result.copy.len() + result.update.len() + result.remove.len()) as i64;
+    let mut done = 0;
+    let mut errors = Vec::new();
+
+    let cancelled = || ctx.is_some_and(|c| c.is_cancelled());
+
+    for entry in result.copy.iter().chain(&result.update) {
+        if cancelled() {
+            break;
+        }
+        let (Some(image_id), Some(source)) = (entry.image_id, &entry.source) else { continue };
+        let Some(&(source_size, source_modified)) = members.get(&image_id) else { continue };
+        let copy = resolve(&destination, &entry.relative_path);
+        match copy_file(source, &copy).await {
+            Ok(destination_modified) => {
+                if let Err(e) = derivatives::record(db, image_id, DerivativeKind::Export, &copy, Some(&target.name)).await {
+                    eprintln!("WARN: Could not record the synced copy {:?}: {}", copy, e);
+                }
+                let file = SyncedFile {
+                    relative_path: entry.relative_path.clone(),
+                    image_id,
+                    source_size,
+                    source_modified,
+                    destination_modified,
+                };
+                db.record_synced_file(target_id, &file).await?;
+            }
+            Err(e) => errors.push(SyncIssue { relative_path: entry.relative_path.clone(), message: e.to_string() }),
+        }
+        done += 1;
+        if let Some(ctx) = ctx {
+            ctx.progress(done, Some(total), Some(&entry.relative_path)).await;
+        }
+    }
+
+    for entry in &result.remove {
+        if cancelled() {
+            break;
+        }
+        let path = resolve(&destination, &entry.relative_path);
+        let outcome = match tokio::fs::try_exists(&path).await {
+            // Already gone from the destination
+            Ok(false) => Ok(()),
+            _ if target.delete_policy == "archive" => archive_file(&destination, &entry.relative_path).await,
+            _ => tokio::fs::remove_file(&path).await,
+        };
+        match outcome {
+            Ok(()) => db.forget_synced_file(target_id, &entry.relative_path).await?,
+            Err(e) => errors.push(SyncIssue { relative_path: entry.relative_path.clone(), message: e.to_string() }),
+        }
+        done += 1;
+        if let Some(ctx) = ctx {
+            ctx.progress(done, Some(total), Some(&entry.relative_path)).await;
+        }
+    }
+
+    result.errors = errors;
+    if cancelled() {
+        return Ok(result);
+    }
+    db.mark_sync_target_synced(target_id).await?;
+    println!(
+        "INFO: Synced '{}': {} copied, {} updated, {} removed, {} conflicts, {} errors",
+        target.name,
+        result.copy.len(),
+        result.update.len(),
+        result.remove.len(),
+        result.conflicts.len(),
+        result.errors.len()
+    );
+    Ok(result)
+}
+
+/// Label of the `sync_folder` job of a target.
+pub fn job_label(target: &SyncTarget) -> String {
+    format!("Sync {}", target.destination)
+}
+
+/// Starts the loop queueing syncs of the auto-sync targets whose filter
+/// membership or sources changed.
+pub fn start(db: Arc<Db>, queue: Arc<JobQueue>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            sleep(CHECK_INTERVAL).await;
+            // Syncs record their files, so they wait while the library is read-only
+            if crate::library::read_only::is_enabled() {
+                continue;
+            }
+            let targets = match db.get_sync_targets().await {
+                Ok(targets) => targets,
+                Err(e) => {
+                    eprintln!("Sync scheduler DB error: {}", e);
+                    continue;
+                }
+            };
+            for target in targets.into_iter().filter(|t| t.auto_sync) {
+                let label = job_label(&target);
+                if db.has_pending_job("sync_folder", &label).await.unwrap_or(true) {
+                    continue;
+                }
+                match preview(&db, target.id).await {
+                    Ok(diff) if diff.is_empty() => {}
+                    Ok(_) => {
+                        let payload = serde_json::json!({ "targetId": target.id });
+                        if let Err(e) = queue.enqueue("sync_folder", &label, &payload, 0).await {
+                            eprintln!("WARN: Failed to queue sync of '{}': {}", target.name, e);
+                        }
+                    }
+                    Err(e) => eprintln!("WARN: Failed to check sync target '{}': {}", target.name, e),
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(image_id: i64, relative_path: &str) -> Member {
+        Member {
+            image_id,
+            source: format!("/lib/{}", relative_path),
+            relative_path: relative_path.to_string(),
+            size: 10,
+            modified: 100,
+        }
+    }
+
+    fn synced(image_id: i64, relative_path: &str) -> SyncedFile {
+        SyncedFile { relative_path: relative_path.to_string(), image_id, source_size: 10, source_modified: 100, destination_modified: 200 }
+    }
+
+    #[test]
+    fn test_destination_path() {
+        let roots = vec!["/photos".to_string(), "/photos/2024/".to_string()];
+        assert_eq!(destination_path("/photos/2024/trip/a.jpg", &roots, true), "trip/a.jpg");
+        assert_eq!(destination_path("/photos/2024/trip/a.jpg", &roots, false), "a.jpg");
+        assert_eq!(destination_path("/other/b.jpg", &roots, true), "b.jpg");
+        assert_eq!(destination_path("/photos2/c.jpg", &roots, true), "c.jpg");
+    }
+
+    #[test]
+    fn test_assign_paths() {
+        assert!(is_variant_of("dir/a (12).jpg", "dir/a.jpg"));
+        assert!(!is_variant_of("dir/a (x).jpg", "dir/a.jpg"));
+        assert_eq!(numbered("README", 2), "README (2)");
+
+        // Image 2 already owns "a (2).jpg"; image 3 takes the next number
+        let members = [member(1, "a.jpg"), member(2, "a.jpg"), member(3, "a.jpg")];
+        let paths = assign_paths(&members, &[synced(2, "a (2).jpg")]);
+        assert_eq!(paths, vec!["a.jpg", "a (2).jpg", "a (3).jpg"]);
+
+        // The file of an image that left the filter stays where it is
+        let paths = assign_paths(&[member(1, "a.jpg")], &[synced(7, "a.jpg")]);
+        assert_eq!(paths, vec!["a (2).jpg"]);
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut changed = member(2, "b.jpg");
+        changed.size = 20;
+        let members = [member(1, "a.jpg"), changed, member(3, "c.jpg"), member(4, "d.jpg"), member(5, "e.jpg")];
+        let files = [synced(1, "a.jpg"), synced(2, "b.jpg"), synced(4, "d.jpg"), synced(9, "old.jpg"), synced(8, "gone.jpg")];
+        let destination = HashMap::from([
+            ("a.jpg".to_string(), 200),
+            ("b.jpg".to_string(), 200),
+            ("d.jpg".to_string(), 300),
+            ("e.jpg".to_string(), 50),
+            ("old.jpg".to_string(), 200),
+        ]);
+
+        let result = diff(&members, &files, &destination, "delete");
+        assert_eq!(result.unchanged, 1);
+        assert_eq!(result.update.iter().map(|e| e.relative_path.as_str()).collect::<Vec<_>>(), ["b.jpg"]);
+        assert_eq!(result.copy.iter().map(|e| e.relative_path.as_str()).collect::<Vec<_>>(), ["c.jpg"]);
+        assert_eq!(result.remove.iter().map(|e| e.relative_path.as_str()).collect::<Vec<_>>(), ["old.jpg", "gone.jpg"]);
+        let conflicts: Vec<&str> = result.conflicts.iter().map(|c| c.relative_path.as_str()).collect();
+        assert_eq!(conflicts, ["d.jpg", "e.jpg"]);
+
+        let kept = diff(&members, &files, &destination, "keep");
+        assert_eq!(kept.kept, 1);
+        assert_eq!(kept.remove.iter().map(|e| e.relative_path.as_str()).collect::<Vec<_>>(), ["gone.jpg"]);
+    }
+}
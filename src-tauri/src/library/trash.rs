@@ -0,0 +1,176 @@
+//! Deleting images from the app and bringing trashed ones back (see
+//! `db::trash`).
+//!
+//! The file of an image deleted from the app is moved to the `trash` folder
+//! of the app data, under the image's id, so restoring it can put it back
+//! where it was. It is deleted for good when its entry leaves the trash.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+use crate::db::trash::{REASON_DELETED, TrashEntry};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::indexer::echo;
+use crate::indexer::BatchChangePayload;
+use crate::library::duplicates::move_file;
+use crate::paths;
+
+/// What a trash operation did, item by item.
+#[derive(Debug, Default, Serialize)]
+pub struct TrashOutcome {
+    /// Trash entries created, or images restored.
+    pub done: Vec<i64>,
+    /// Items left alone, with the reason.
+    pub skipped: Vec<String>,
+}
+
+fn trash_dir<R: Runtime>(app: &AppHandle<R>) -> AppResult<PathBuf> {
+    Ok(app.path().app_local_data_dir()?.join("trash"))
+}
+
+async fn run_blocking<T: Send + 'static>(task: impl FnOnce() -> std::io::Result<T> + Send + 'static) -> AppResult<T> {
+    tauri::async_runtime::spawn_blocking(task)
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::from)
+}
+
+fn announce<R: Runtime>(app: &AppHandle<R>) {
+    let _ = app.emit("library:batch-change", BatchChangePayload {
+        added: vec![], removed: vec![], updated: vec![], needs_refresh: true
+    });
+}
+
+/// Deletes images from the library, moving their files to the trash folder.
+/// Images whose file is already gone are trashed all the same.
+pub async fn delete_images<R: Runtime>(app: &AppHandle<R>, db: &Db, image_ids: &[i64]) -> AppResult<TrashOutcome> {
+    let dir = trash_dir(app)?;
+    let mut outcome = TrashOutcome::default();
+    for &image_id in image_ids {
+        let Some(path) = db.get_image_path(image_id).await? else {
+            outcome.skipped.push(format!("Image {} not found", image_id));
+            continue;
+        };
+        let source = paths::from_db(&path);
+        let target = dir.join(image_id.to_string()).join(source.file_name().unwrap_or_default());
+        echo::expect_changes([path.clone()]);
+        let moved = target.clone();
+        let result = run_blocking(move || {
+            if !source.exists() {
+                return Ok(false);
+            }
+            if let Some(parent) = moved.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            move_file(&source, &moved).map(|_| true)
+        })
+        .await;
+        let stored = match result {
+            Ok(true) => Some(paths::to_db(&target)),
+            Ok(false) => None,
+            Err(e) => {
+                outcome.skipped.push(format!("{}: {}", path, e));
+                continue;
+            }
+        };
+        if let Some(trash_id) = db.trash_image(image_id, REASON_DELETED, stored.as_deref()).await? {
+            outcome.done.push(trash_id);
+        }
+    }
+    println!("INFO: Moved {} images to the trash ({} skipped)", outcome.done.len(), outcome.skipped.len());
+    announce(app);
+    Ok(outcome)
+}
+
+/// Puts the file of an entry back and indexes it again. Returns the new
+/// image id.
+async fn restore_entry(db: &Db, entry: &TrashEntry) -> Result<i64, String> {
+    if db.get_location_for_path(&entry.path).await.map_err(|e| e.to_string())?.is_none() {
+        return Err("no location of the library holds it anymore".to_string());
+    }
+    let path = paths::from_db(&entry.path);
+    let stored = entry.stored_path.as_deref().map(paths::from_db);
+    echo::expect_changes([entry.path.clone()]);
+    let target = path.clone();
+    let meta = run_blocking(move || {
+        // Still in the trash folder unless an earlier attempt put it back
+        if let Some(stored) = stored.filter(|stored| stored.exists()) {
+            if target.exists() {
+                return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, "another file is there now"));
+            }
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            move_file(&stored, &target)?;
+            remove_stored_dir(&stored);
+        }
+        Ok(crate::indexer::metadata::get_image_metadata(&target))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .ok_or("the file is gone")?;
+    let parent = path.parent().map(paths::to_db).unwrap_or_default();
+    let folder_id = db.ensure_folder_hierarchy(&parent).await.map_err(|e| e.to_string())?;
+    let (image_id, _, _) = crate::indexer::watcher::save_file(db, folder_id, &meta).await.map_err(|e| e.to_string())?;
+    db.restore_trash_entry(entry.id, image_id).await.map_err(|e| e.to_string())?;
+    Ok(image_id)
+}
+
+/// Brings trash entries back into the library with their tags, rating,
+/// notes and collections. The file must be in the trash folder or back at
+/// its path, inside a location of the library.
+pub async fn restore<R: Runtime>(app: &AppHandle<R>, db: &Db, trash_ids: &[i64]) -> AppResult<TrashOutcome> {
+    let mut outcome = TrashOutcome::default();
+    for &id in trash_ids {
+        let Some(entry) = db.get_trash_entry(id).await? else {
+            outcome.skipped.push(format!("Trash entry {} not found", id));
+            continue;
+        };
+        match restore_entry(db, &entry).await {
+            Ok(image_id) => outcome.done.push(image_id),
+            Err(e) => outcome.skipped.push(format!("{}: {}", entry.path, e)),
+        }
+    }
+    println!("INFO: Restored {} images from the trash ({} skipped)", outcome.done.len(), outcome.skipped.len());
+    announce(app);
+    Ok(outcome)
+}
+
+/// Removes the folder of a file kept by the trash folder once it is empty.
+fn remove_stored_dir(stored: &Path) {
+    if let Some(parent) = stored.parent() {
+        let _ = std::fs::remove_dir(parent);
+    }
+}
+
+fn delete_stored_files(stored: Vec<String>) -> usize {
+    let mut deleted = 0;
+    for path in stored {
+        let path = paths::from_db(&path);
+        match std::fs::remove_file(&path) {
+            Ok(()) => deleted += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => eprintln!("WARN: Could not delete {} from the trash: {}", path.display(), e),
+        }
+        remove_stored_dir(&path);
+    }
+    deleted
+}
+
+/// Drops trash entries for good, `None` for all of them, deleting the files
+/// the trash folder kept for them.
+pub async fn empty(db: &Db, trash_ids: Option<&[i64]>) -> AppResult<usize> {
+    let stored = db.remove_trash_entries(trash_ids).await?;
+    let deleted = run_blocking(move || Ok(delete_stored_files(stored))).await?;
+    println!("INFO: Emptied the trash, {} files deleted", deleted);
+    Ok(deleted)
+}
+
+/// Drops the entries past the retention window, deleting their files.
+pub async fn purge_expired(db: &Db) -> AppResult<usize> {
+    let stored = db.purge_expired_trash(db.trash_retention_days().await).await?;
+    run_blocking(move || Ok(delete_stored_files(stored))).await
+}
@@ -0,0 +1,151 @@
+//! Background upkeep of the database file (see `db::vacuum`).
+//!
+//! Every few minutes the WAL is checkpointed, free pages are released in
+//! small incremental steps once they add up, and the planner statistics are
+//! refreshed after the library grows a lot, as after a large import. None of
+//! it takes the library-wide lock a full `VACUUM` does. Trash entries past
+//! their retention window are purged along the way (see `library::trash`),
+//! and so are jobs finished long ago (see `jobs`).
+
+use std::sync::Arc;
+
+use tokio::time::{sleep, Duration};
+
+use crate::db::vacuum::{DbSpace, MaintenanceKind};
+use crate::db::Db;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Free space worth reclaiming, in bytes or as a share of the file.
+const RECLAIM_MIN_BYTES: u64 = 32 * 1024 * 1024;
+const RECLAIM_MIN_SHARE: f64 = 0.2;
+
+/// Pages released per step, with a pause between steps for other writers.
+const VACUUM_STEP_PAGES: i64 = 1024;
+
+/// Library growth that makes the planner statistics stale.
+const ANALYZE_MIN_NEW_IMAGES: i64 = 5000;
+const ANALYZE_MIN_GROWTH: f64 = 0.2;
+
+fn should_reclaim(space: &DbSpace) -> bool {
+    let free = space.free_bytes();
+    free >= RECLAIM_MIN_BYTES
+        || (free > 0 && free as f64 >= space.size_bytes() as f64 * RECLAIM_MIN_SHARE && space.freelist_count >= VACUUM_STEP_PAGES)
+}
+
+/// Whether the image count went from `analyzed` to `current` by enough to
+/// analyze again.
+fn should_analyze(analyzed: i64, current: i64) -> bool {
+    let added = current - analyzed;
+    added >= ANALYZE_MIN_NEW_IMAGES || (added > 0 && added as f64 >= analyzed as f64 * ANALYZE_MIN_GROWTH && added >= 100)
+}
+
+pub fn start(db: Arc<Db>) {
+    tauri::async_runtime::spawn(async move {
+        let mut analyzed_count: Option<i64> = None;
+        loop {
+            sleep(CHECK_INTERVAL).await;
+
+            match db.checkpoint().await {
+                Ok(()) => record(&db, MaintenanceKind::Checkpoint).await,
+                Err(e) => eprintln!("WARN: WAL checkpoint failed: {}", e),
+            }
+            if let Err(e) = reclaim(&db).await {
+                eprintln!("WARN: Incremental vacuum failed: {}", e);
+            }
+            match crate::library::trash::purge_expired(&db).await {
+                Ok(0) => {}
+                Ok(count) => println!("INFO: Deleted {} expired files from the trash", count),
+                Err(e) => eprintln!("WARN: Could not purge the trash: {}", e),
+            }
+            match db.purge_finished_jobs(crate::jobs::FINISHED_JOB_RETENTION_DAYS).await {
+                Ok(0) => {}
+                Ok(count) => println!("INFO: Deleted {} finished jobs", count),
+                Err(e) => eprintln!("WARN: Could not purge finished jobs: {}", e),
+            }
+
+            let count: i64 = match sqlx::query_scalar("SELECT COUNT(*) FROM images").fetch_one(&db.pool).await {
+                Ok(count) => count,
+                Err(e) => {
+                    eprintln!("WARN: Could not count images for ANALYZE: {}", e);
+                    continue;
+                }
+            };
+            let stale = match analyzed_count {
+                Some(analyzed) => should_analyze(analyzed, count),
+                None => !db.has_statistics().await.unwrap_or(true),
+            };
+            if stale {
+                match db.analyze().await {
+                    Ok(()) => {
+                        println!("INFO: Refreshed query planner statistics for {} files", count);
+                        record(&db, MaintenanceKind::Analyze).await;
+                    }
+                    Err(e) => eprintln!("WARN: ANALYZE failed: {}", e),
+                }
+            }
+            if stale || analyzed_count.is_none() {
+                analyzed_count = Some(count);
+            }
+        }
+    });
+}
+
+/// Releases free pages in steps, while there are enough to bother.
+async fn reclaim(db: &Db) -> Result<(), sqlx::Error> {
+    if !db.is_incremental_vacuum().await? {
+        return Ok(());
+    }
+    let before = db.get_space().await?;
+    if !should_reclaim(&before) {
+        return Ok(());
+    }
+
+    let mut space = before;
+    while space.freelist_count > 0 {
+        db.incremental_vacuum(VACUUM_STEP_PAGES).await?;
+        let next = db.get_space().await?;
+        // Another connection may hold pages; stop rather than spin
+        if next.freelist_count >= space.freelist_count {
+            break;
+        }
+        space = next;
+        sleep(Duration::from_millis(100)).await;
+    }
+    println!(
+        "INFO: Incremental vacuum reclaimed {} bytes",
+        before.size_bytes().saturating_sub(space.size_bytes())
+    );
+    record(db, MaintenanceKind::Vacuum).await;
+    Ok(())
+}
+
+async fn record(db: &Db, kind: MaintenanceKind) {
+    if let Err(e) = db.record_maintenance(kind).await {
+        eprintln!("WARN: Could not record {:?} in the maintenance log: {}", kind, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_reclaim() {
+        let space = |page_count, freelist_count| DbSpace { page_size: 4096, page_count, freelist_count };
+        assert!(!should_reclaim(&space(100_000, 0)));
+        assert!(!should_reclaim(&space(100_000, 2_000)), "8 MB of 400 MB");
+        assert!(should_reclaim(&space(100_000, 10_000)), "40 MB");
+        assert!(should_reclaim(&space(5_000, 2_000)), "40% of the file");
+        assert!(!should_reclaim(&space(100, 50)), "too small to bother");
+    }
+
+    #[test]
+    fn test_should_analyze() {
+        assert!(!should_analyze(10_000, 10_050));
+        assert!(should_analyze(10_000, 15_000));
+        assert!(should_analyze(1_000, 1_300));
+        assert!(!should_analyze(100, 110));
+        assert!(!should_analyze(50_000, 49_000));
+    }
+}
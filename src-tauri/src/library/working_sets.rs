@@ -0,0 +1,137 @@
+//! Working sets: temporary scopes for narrowing a search step by step.
+//!
+//! A working set is a list of image ids pushed for the session, such as a
+//! manual selection promoted to a view. Sets form a stack, and each one
+//! pushed is intersected with the one below, so every step narrows the
+//! last; popping goes back a step. Searches intersect with a set through the
+//! `working_set` criterion (`in` or `not_in`, the set id as value), so a
+//! complex filter isn't computed again at each step.
+//!
+//! Sets live in memory, not in the library: they end with the session, are
+//! private to this instance, and work in read-only mode.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::Serialize;
+
+/// Largest number of sets on the stack.
+pub const MAX_DEPTH: usize = 32;
+
+struct Entry {
+    id: u64,
+    label: Option<String>,
+    image_ids: Vec<i64>,
+    /// The ids as a JSON array, bound as-is by the search queries.
+    ids_json: Arc<String>,
+}
+
+/// A working set as listed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkingSet {
+    pub id: u64,
+    pub label: Option<String>,
+    pub count: usize,
+    /// Position on the stack, 1 for the bottom set.
+    pub depth: usize,
+}
+
+#[derive(Default)]
+pub struct WorkingSets {
+    stack: Vec<Entry>,
+    next_id: u64,
+}
+
+impl WorkingSets {
+    fn describe(&self, index: usize) -> WorkingSet {
+        let entry = &self.stack[index];
+        WorkingSet { id: entry.id, label: entry.label.clone(), count: entry.image_ids.len(), depth: index + 1 }
+    }
+
+    /// Pushes `image_ids`, keeping only those in the current top set.
+    /// Returns `None` when the stack is full.
+    pub fn push(&mut self, image_ids: Vec<i64>, label: Option<String>) -> Option<WorkingSet> {
+        if self.stack.len() >= MAX_DEPTH {
+            return None;
+        }
+        let top: Option<HashSet<i64>> = self.stack.last().map(|entry| entry.image_ids.iter().copied().collect());
+        let in_top = |id: &i64| match &top {
+            Some(top) => top.contains(id),
+            None => true,
+        };
+        let mut seen = HashSet::new();
+        let image_ids: Vec<i64> = image_ids.into_iter().filter(|id| in_top(id) && seen.insert(*id)).collect();
+        let ids_json = Arc::new(serde_json::to_string(&image_ids).unwrap_or_else(|_| "[]".to_string()));
+
+        self.next_id += 1;
+        self.stack.push(Entry { id: self.next_id, label, image_ids, ids_json });
+        Some(self.describe(self.stack.len() - 1))
+    }
+
+    /// Removes the top set. Returns the set now on top.
+    pub fn pop(&mut self) -> Option<WorkingSet> {
+        self.stack.pop();
+        self.top()
+    }
+
+    pub fn top(&self) -> Option<WorkingSet> {
+        self.stack.len().checked_sub(1).map(|index| self.describe(index))
+    }
+
+    /// Sets on the stack, bottom first.
+    pub fn list(&self) -> Vec<WorkingSet> {
+        (0..self.stack.len()).map(|index| self.describe(index)).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.stack.clear();
+    }
+
+    /// Ids of set `id` as a JSON array, `None` once it is popped.
+    pub fn ids_json(&self, id: u64) -> Option<Arc<String>> {
+        self.stack.iter().find(|entry| entry.id == id).map(|entry| entry.ids_json.clone())
+    }
+}
+
+static WORKING_SETS: Mutex<WorkingSets> = Mutex::new(WorkingSets { stack: Vec::new(), next_id: 0 });
+
+/// The working sets of this session.
+pub fn lock() -> MutexGuard<'static, WorkingSets> {
+    WORKING_SETS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_narrows_and_pop_goes_back() {
+        let mut sets = WorkingSets::default();
+        let first = sets.push(vec![1, 2, 3, 3, 4], Some("Picks".to_string())).unwrap();
+        assert_eq!((first.count, first.depth), (4, 1));
+
+        let second = sets.push(vec![4, 2, 9], None).unwrap();
+        assert_eq!((second.count, second.depth), (2, 2));
+        assert_eq!(sets.ids_json(second.id).as_deref().map(String::as_str), Some("[4,2]"));
+
+        assert_eq!(sets.pop(), Some(first.clone()));
+        assert_eq!(sets.ids_json(second.id), None);
+        // Ids aren't reused, so a stale criterion never picks up a new set
+        assert_ne!(sets.push(vec![1], None).unwrap().id, second.id);
+        assert_eq!(sets.list().len(), 2);
+
+        sets.clear();
+        assert_eq!(sets.pop(), None);
+        assert_eq!(sets.ids_json(first.id), None);
+    }
+
+    #[test]
+    fn test_stack_depth_is_bounded() {
+        let mut sets = WorkingSets::default();
+        for _ in 0..MAX_DEPTH {
+            assert!(sets.push(vec![1], None).is_some());
+        }
+        assert!(sets.push(vec![1], None).is_none());
+    }
+}
@@ -2,5 +2,9 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    // Isolated thumbnail decoders re-launch this binary; handle that before starting the app
+    if let Some(code) = mundam_lib::run_thumbnail_helper() {
+        std::process::exit(code);
+    }
     mundam_lib::run()
 }
@@ -0,0 +1,257 @@
+//! Frame count, timing and looping of animated GIF and WebP files.
+//!
+//! Both containers are walked block by block without decoding any pixels,
+//! so the media info worker can fill them in without FFmpeg. Frame delays
+//! follow browsers: GIF delays under 20 ms play at 100 ms.
+
+use std::path::Path;
+
+/// Timing of an animated image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimationInfo {
+    /// Number of frames in one pass.
+    pub frame_count: u32,
+    /// Length of one pass, in seconds.
+    pub duration_secs: f64,
+    /// Whether it plays forever rather than a set number of times.
+    pub loops: bool,
+}
+
+impl AnimationInfo {
+    /// Whether there is more than one frame; single-frame files are stills.
+    pub fn is_animated(&self) -> bool {
+        self.frame_count > 1
+    }
+
+    /// Average frames per second of one pass.
+    pub fn fps(&self) -> Option<f64> {
+        (self.is_animated() && self.duration_secs > 0.0).then(|| self.frame_count as f64 / self.duration_secs)
+    }
+}
+
+/// Reads the animation of a GIF or WebP file. `None` for other formats and
+/// for files too damaged to walk.
+pub fn read(path: &Path) -> std::io::Result<Option<AnimationInfo>> {
+    Ok(parse(&std::fs::read(path)?))
+}
+
+/// [`read`] on a file already in memory.
+pub fn parse(data: &[u8]) -> Option<AnimationInfo> {
+    parse_gif(data).or_else(|| parse_webp(data))
+}
+
+/// Shortest GIF delay browsers honour, in hundredths of a second.
+const GIF_MIN_DELAY: u32 = 2;
+const GIF_DEFAULT_DELAY: u32 = 10;
+
+fn parse_gif(data: &[u8]) -> Option<AnimationInfo> {
+    if !(data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")) {
+        return None;
+    }
+    let flags = *data.get(10)?;
+    let mut pos = 13 + color_table_size(flags);
+
+    let mut frame_count = 0u32;
+    let mut total_delay = 0u32;
+    let mut pending_delay: Option<u32> = None;
+    let mut loops = false;
+
+    loop {
+        match *data.get(pos)? {
+            // Extension
+            0x21 => {
+                let label = *data.get(pos + 1)?;
+                let first_block = pos + 2;
+                match label {
+                    // Graphic control: delay of the next image, in hundredths
+                    0xF9 if *data.get(first_block)? >= 4 => {
+                        let delay = u16::from_le_bytes([*data.get(first_block + 2)?, *data.get(first_block + 3)?]);
+                        pending_delay = Some(delay as u32);
+                    }
+                    // Application: NETSCAPE2.0 / ANIMEXTS1.0 carry the loop count
+                    0xFF if *data.get(first_block)? == 11 => {
+                        let id = data.get(first_block + 1..first_block + 12)?;
+                        if id == b"NETSCAPE2.0" || id == b"ANIMEXTS1.0" {
+                            let sub = first_block + 12;
+                            if *data.get(sub)? >= 3 && *data.get(sub + 1)? == 1 {
+                                let count = u16::from_le_bytes([*data.get(sub + 2)?, *data.get(sub + 3)?]);
+                                loops = count == 0;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                pos = skip_sub_blocks(data, first_block)?;
+            }
+            // Image: descriptor, optional local color table, LZW size, data
+            0x2C => {
+                let flags = *data.get(pos + 9)?;
+                pos = skip_sub_blocks(data, pos + 10 + color_table_size(flags) + 1)?;
+                frame_count += 1;
+                let delay = pending_delay.take().unwrap_or(0);
+                total_delay += if delay < GIF_MIN_DELAY { GIF_DEFAULT_DELAY } else { delay };
+            }
+            // Trailer
+            0x3B => break,
+            _ => return None,
+        }
+    }
+
+    Some(AnimationInfo {
+        frame_count,
+        duration_secs: if frame_count > 1 { total_delay as f64 / 100.0 } else { 0.0 },
+        loops: loops && frame_count > 1,
+    })
+}
+
+/// Bytes of the color table announced by a GIF descriptor's flags.
+fn color_table_size(flags: u8) -> usize {
+    if flags & 0x80 == 0 {
+        0
+    } else {
+        3 * (1 << ((flags & 0x07) + 1))
+    }
+}
+
+/// Position after the sub-blocks starting at `pos`, terminator included.
+fn skip_sub_blocks(data: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let size = *data.get(pos)? as usize;
+        pos += 1 + size;
+        if size == 0 {
+            return Some(pos);
+        }
+    }
+}
+
+fn parse_webp(data: &[u8]) -> Option<AnimationInfo> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return None;
+    }
+
+    let mut pos = 12;
+    let mut frame_count = 0u32;
+    let mut total_ms = 0u64;
+    let mut loops = false;
+    let mut is_animation = false;
+
+    while pos + 8 <= data.len() {
+        let fourcc = &data[pos..pos + 4];
+        let size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().ok()?) as usize;
+        let body = data.get(pos + 8..pos + 8 + size)?;
+        match fourcc {
+            b"VP8X" => is_animation = body.first().is_some_and(|flags| flags & 0x02 != 0),
+            // Background color (4 bytes), then the loop count
+            b"ANIM" if body.len() >= 6 => loops = u16::from_le_bytes([body[4], body[5]]) == 0,
+            // x, y, width, height, then the duration, all 24-bit
+            b"ANMF" if body.len() >= 15 => {
+                frame_count += 1;
+                total_ms += u32::from_le_bytes([body[12], body[13], body[14], 0]) as u64;
+            }
+            _ => {}
+        }
+        // Chunks are padded to an even size
+        pos += 8 + size + (size & 1);
+    }
+
+    if !is_animation || frame_count == 0 {
+        return Some(AnimationInfo { frame_count: 1, duration_secs: 0.0, loops: false });
+    }
+    Some(AnimationInfo {
+        frame_count,
+        duration_secs: if frame_count > 1 { total_ms as f64 / 1000.0 } else { 0.0 },
+        loops: loops && frame_count > 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 1x1 GIF with `delays.len()` frames, looping forever when `looping`.
+    fn gif(delays: &[u16], looping: bool) -> Vec<u8> {
+        let mut data = b"GIF89a".to_vec();
+        // 1x1, global color table of 2 entries
+        data.extend([1, 0, 1, 0, 0x80, 0, 0]);
+        data.extend([0, 0, 0, 255, 255, 255]);
+        if looping {
+            data.extend([0x21, 0xFF, 11]);
+            data.extend(b"NETSCAPE2.0");
+            data.extend([3, 1, 0, 0, 0]);
+        }
+        for delay in delays {
+            let [lo, hi] = delay.to_le_bytes();
+            data.extend([0x21, 0xF9, 4, 0, lo, hi, 0, 0]);
+            data.extend([0x2C, 0, 0, 0, 0, 1, 0, 1, 0, 0]);
+            data.extend([2, 2, 0x4C, 0x01, 0]);
+        }
+        data.push(0x3B);
+        data
+    }
+
+    fn chunk(fourcc: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut data = fourcc.to_vec();
+        data.extend((body.len() as u32).to_le_bytes());
+        data.extend(body);
+        if body.len() % 2 == 1 {
+            data.push(0);
+        }
+        data
+    }
+
+    fn webp(durations_ms: &[u32], loop_count: u16) -> Vec<u8> {
+        let mut chunks = chunk(b"VP8X", &[0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let mut anim = vec![0, 0, 0, 0];
+        anim.extend(loop_count.to_le_bytes());
+        chunks.extend(chunk(b"ANIM", &anim));
+        for duration in durations_ms {
+            let mut frame = vec![0; 12];
+            frame.extend(&duration.to_le_bytes()[..3]);
+            frame.push(0);
+            chunks.extend(chunk(b"ANMF", &frame));
+        }
+        let mut data = b"RIFF".to_vec();
+        data.extend((chunks.len() as u32 + 4).to_le_bytes());
+        data.extend(b"WEBP");
+        data.extend(chunks);
+        data
+    }
+
+    #[test]
+    fn test_gif_frames_delays_and_loop() {
+        let info = parse_gif(&gif(&[50, 50, 0], true)).unwrap();
+        assert_eq!(info.frame_count, 3);
+        assert!((info.duration_secs - 1.1).abs() < 1e-9, "a zero delay plays at 100 ms");
+        assert!(info.loops);
+        assert!((info.fps().unwrap() - 3.0 / 1.1).abs() < 1e-9);
+
+        let once = parse_gif(&gif(&[10, 10], false)).unwrap();
+        assert!(!once.loops);
+
+        let still = parse_gif(&gif(&[0], true)).unwrap();
+        assert!(!still.is_animated());
+        assert_eq!(still.fps(), None);
+        assert!(!still.loops);
+    }
+
+    #[test]
+    fn test_webp_frames_and_loop() {
+        let info = parse_webp(&webp(&[100, 100, 300], 0)).unwrap();
+        assert_eq!(info.frame_count, 3);
+        assert!((info.duration_secs - 0.5).abs() < 1e-9);
+        assert!(info.loops);
+        assert!(!parse_webp(&webp(&[100, 100], 2)).unwrap().loops);
+
+        let mut still = b"RIFF\x0c\0\0\0WEBPVP8 ".to_vec();
+        still.extend([0, 0, 0, 0]);
+        assert!(!parse_webp(&still).unwrap().is_animated());
+    }
+
+    #[test]
+    fn test_truncated_and_other_files() {
+        let data = gif(&[10, 10], true);
+        assert_eq!(parse_gif(&data[..data.len() - 8]), None);
+        assert_eq!(parse_gif(b"\x89PNG\r\n\x1a\n"), None);
+        assert_eq!(parse_webp(b"RIFF\0\0\0\0WAVE"), None);
+    }
+}
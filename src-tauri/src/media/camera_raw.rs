@@ -0,0 +1,117 @@
+//! Camera RAW video (Blackmagic RAW, REDCODE RAW, ProRes RAW).
+//!
+//! FFmpeg can't decode BRAW or R3D, and only recent builds read ProRes RAW.
+//! Cameras and DIT tools usually record a lightweight proxy alongside the
+//! footage, so thumbnails and playback fall back to that proxy when present.
+
+use std::path::{Path, PathBuf};
+
+/// Extensions of camera RAW footage that FFmpeg cannot decode.
+const CAMERA_RAW_EXTENSIONS: &[&str] = &["braw", "r3d"];
+
+/// Containers proxies are recorded in.
+const PROXY_EXTENSIONS: &[&str] = &["mov", "mp4", "mxf"];
+
+/// Folders cameras put proxies in, relative to the clip's folder.
+const PROXY_DIRS: &[&str] = &["Proxy", "Proxies", "proxy"];
+
+/// Suffixes appended to the clip name by cameras and transcoding tools. A
+/// file with the bare clip name only counts inside a proxy folder: next to
+/// the clip it is as likely an unrelated export of the same name.
+const PROXY_SUFFIXES: &[&str] = &["", "_Proxy", "_proxy", "_P"];
+
+/// Whether the file is camera RAW footage that needs a proxy to be shown.
+pub fn is_camera_raw_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| CAMERA_RAW_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Clip name without the span number RED appends to each file (`_001`).
+fn clip_name(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    let is_r3d = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("r3d"));
+
+    if is_r3d {
+        if let Some((clip, span)) = stem.rsplit_once('_') {
+            if span.len() == 3 && span.chars().all(|c| c.is_ascii_digit()) {
+                return Some(clip.to_string());
+            }
+        }
+    }
+    Some(stem.to_string())
+}
+
+/// Paths where a proxy for `path` may live, most likely first: suffixed
+/// names next to the clip, then any of [`PROXY_SUFFIXES`] in a proxy folder.
+pub fn proxy_candidates(path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(clip)) = (path.parent(), clip_name(path)) else {
+        return Vec::new();
+    };
+
+    let mut dirs = vec![(dir.to_path_buf(), false)];
+    dirs.extend(PROXY_DIRS.iter().map(|d| (dir.join(d), true)));
+
+    let mut candidates = Vec::new();
+    for (dir, is_proxy_dir) in &dirs {
+        for suffix in PROXY_SUFFIXES.iter().filter(|s| *is_proxy_dir || !s.is_empty()) {
+            for ext in PROXY_EXTENSIONS {
+                let candidate = dir.join(format!("{}{}.{}", clip, suffix, ext));
+                if candidate != path {
+                    candidates.push(candidate);
+                }
+            }
+        }
+    }
+    candidates
+}
+
+/// Finds a recorded proxy for the clip, if one exists on disk.
+pub fn find_proxy(path: &Path) -> Option<PathBuf> {
+    proxy_candidates(path).into_iter().find(|p| p.is_file())
+}
+
+/// File to stream for playback: the proxy for camera RAW footage, the file itself otherwise.
+pub fn playback_source(path: &Path) -> PathBuf {
+    if is_camera_raw_video(path) {
+        if let Some(proxy) = find_proxy(path) {
+            return proxy;
+        }
+    }
+    path.to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_camera_raw_video() {
+        assert!(is_camera_raw_video(Path::new("/card/A001_08101203_C001.braw")));
+        assert!(is_camera_raw_video(Path::new("/card/A001_C002_0810XY_001.R3D")));
+        assert!(!is_camera_raw_video(Path::new("/card/clip.mov")));
+    }
+
+    #[test]
+    fn test_proxy_candidates() {
+        let braw = Path::new("/card/A001_C001.braw");
+        let candidates = proxy_candidates(braw);
+        assert_eq!(candidates[0], PathBuf::from("/card/A001_C001_Proxy.mov"));
+        assert!(candidates.contains(&PathBuf::from("/card/Proxy/A001_C001.mp4")));
+        // Same name next to the clip: an unrelated export, not a proxy
+        assert!(!candidates.contains(&PathBuf::from("/card/A001_C001.mov")));
+
+        // RED span numbers are dropped to find the clip-level proxy
+        let r3d = Path::new("/card/A001_C002.RDC/A001_C002_0810XY_001.R3D");
+        let candidates = proxy_candidates(r3d);
+        assert!(candidates.contains(&PathBuf::from("/card/A001_C002.RDC/A001_C002_0810XY_P.mov")));
+
+        // A file is never its own proxy
+        let mov = Path::new("/card/clip.mov");
+        assert!(!proxy_candidates(mov).contains(&mov.to_path_buf()));
+    }
+}
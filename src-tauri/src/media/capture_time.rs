@@ -0,0 +1,176 @@
+//! Capture times, normalized to UTC.
+//!
+//! EXIF writes the capture time as local wall-clock time (`2024:05:01
+//! 14:03:22`), with the offset in a separate tag that older cameras leave
+//! out. Sorting those strings as-is mixes up files from cameras set to
+//! different zones, so each time is converted to UTC: with the offset the
+//! camera recorded when there is one, and with the library's default time
+//! zone (the `default_timezone` setting) otherwise. The recorded offset is
+//! kept apart, so the original local time can still be shown, and the
+//! wall-clock string is kept too, so times can be converted again when the
+//! default zone changes.
+
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDateTime, Offset, TimeZone, Utc};
+
+/// Settings key holding the default time zone: `local`, `UTC` or an offset
+/// such as `+02:00`.
+pub const SETTING_KEY: &str = "default_timezone";
+
+/// Zone assumed for capture times recorded without an offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefaultZone {
+    /// The zone of this computer.
+    Local,
+    /// A fixed offset from UTC.
+    Fixed(FixedOffset),
+}
+
+impl DefaultZone {
+    /// Times without an offset taken as UTC.
+    pub fn utc() -> Self {
+        DefaultZone::Fixed(Utc.fix())
+    }
+
+    /// Reads the setting; anything unrecognized means `Local`.
+    pub fn from_setting(value: Option<&str>) -> Self {
+        match value.map(str::trim) {
+            Some(value) if value.eq_ignore_ascii_case("utc") => DefaultZone::utc(),
+            Some(value) => parse_offset(value).map(DefaultZone::Fixed).unwrap_or(DefaultZone::Local),
+            None => DefaultZone::Local,
+        }
+    }
+}
+
+/// A capture time in UTC, with the offset recorded in the file, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureTime {
+    pub utc: DateTime<Utc>,
+    /// Minutes east of UTC, as recorded by the camera.
+    pub offset_minutes: Option<i32>,
+}
+
+/// Wall-clock layouts found in EXIF and XMP dates.
+const LAYOUTS: &[&str] = &["%Y:%m:%d %H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%Y:%m:%d %H:%M"];
+
+/// Converts a recorded capture time to UTC. The offset comes from `raw`
+/// itself when it ends with one (`2024-05-01T14:03:22+02:00`), then from
+/// `offset` (as in an EXIF `OffsetTimeOriginal`), then from `zone`. Blank and
+/// zeroed dates (`0000:00:00 00:00:00`) give `None`.
+pub fn normalize(raw: &str, offset: Option<FixedOffset>, zone: DefaultZone) -> Option<CaptureTime> {
+    let raw = raw.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    let (wall_clock, inline_offset) = split_offset(raw);
+    let naive = LAYOUTS.iter().find_map(|layout| NaiveDateTime::parse_from_str(wall_clock, layout).ok())?;
+    if naive.year() < 1900 {
+        return None;
+    }
+
+    let recorded = inline_offset.or(offset);
+    let utc = match (recorded, zone) {
+        (Some(offset), _) | (None, DefaultZone::Fixed(offset)) => offset.from_local_datetime(&naive).single()?.with_timezone(&Utc),
+        // A time repeated by a DST change resolves to its first occurrence,
+        // one skipped by it to an hour later
+        (None, DefaultZone::Local) => Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .or_else(|| Local.from_local_datetime(&(naive + chrono::Duration::hours(1))).earliest())?
+            .with_timezone(&Utc),
+    };
+    Some(CaptureTime { utc, offset_minutes: recorded.map(|o| o.local_minus_utc() / 60) })
+}
+
+/// Splits a trailing `Z` or `±hh:mm` off a date.
+fn split_offset(raw: &str) -> (&str, Option<FixedOffset>) {
+    if let Some(wall_clock) = raw.strip_suffix('Z') {
+        return (wall_clock, Some(Utc.fix()));
+    }
+    // An offset sign after the time part, not the dashes of the date
+    match raw.rfind(|c: char| c == '+' || c == '-').filter(|at| *at > 10) {
+        Some(at) => match parse_offset(&raw[at..]) {
+            Some(offset) => (&raw[..at], Some(offset)),
+            None => (raw, None),
+        },
+        None => (raw, None),
+    }
+}
+
+/// Parses `+02:00`, `-0530`, `+09` or `Z`.
+pub fn parse_offset(value: &str) -> Option<FixedOffset> {
+    let value = value.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    if value == "Z" {
+        return Some(Utc.fix());
+    }
+    let (sign, digits) = match value.as_bytes().first()? {
+        b'+' => (1, &value[1..]),
+        b'-' => (-1, &value[1..]),
+        _ => return None,
+    };
+    let digits: String = digits.chars().filter(|c| *c != ':').collect();
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (digits[..2].parse::<i32>().ok()?, digits[2..].parse::<i32>().ok()?),
+        _ => return None,
+    };
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    fn fixed(value: &str) -> DefaultZone {
+        DefaultZone::Fixed(parse_offset(value).unwrap())
+    }
+
+    #[test]
+    fn test_recorded_offsets_win() {
+        let time = normalize("2024:05:01 14:03:22", parse_offset("+02:00"), fixed("-05:00")).unwrap();
+        assert_eq!(time.utc, utc("2024-05-01T12:03:22Z"));
+        assert_eq!(time.offset_minutes, Some(120));
+
+        let inline = normalize("2024-05-01T14:03:22.250-03:30", None, fixed("+09:00")).unwrap();
+        assert_eq!(inline.utc, utc("2024-05-01T17:33:22.250Z"));
+        assert_eq!(inline.offset_minutes, Some(-210));
+
+        let zulu = normalize("2024-05-01T14:03:22Z", parse_offset("+02:00"), DefaultZone::Local).unwrap();
+        assert_eq!(zulu.utc, utc("2024-05-01T14:03:22Z"));
+    }
+
+    #[test]
+    fn test_naive_times_use_the_default_zone() {
+        let time = normalize("2024:05:01 14:03:22\0", None, fixed("+09:00")).unwrap();
+        assert_eq!(time.utc, utc("2024-05-01T05:03:22Z"));
+        assert_eq!(time.offset_minutes, None);
+
+        // Two cameras, one recording its offset: the later shot sorts later
+        let tokyo = normalize("2024:05:01 20:00:00", parse_offset("+09:00"), fixed("+00:00")).unwrap();
+        let lisbon = normalize("2024:05:01 13:00:00", None, fixed("+01:00")).unwrap();
+        assert!(tokyo.utc < lisbon.utc);
+    }
+
+    #[test]
+    fn test_invalid_dates() {
+        assert_eq!(normalize("0000:00:00 00:00:00", None, DefaultZone::Local), None);
+        assert_eq!(normalize("    ", None, DefaultZone::Local), None);
+        assert_eq!(normalize("yesterday", None, DefaultZone::Local), None);
+    }
+
+    #[test]
+    fn test_default_zone_setting() {
+        assert_eq!(DefaultZone::from_setting(None), DefaultZone::Local);
+        assert_eq!(DefaultZone::from_setting(Some("local")), DefaultZone::Local);
+        assert_eq!(DefaultZone::from_setting(Some("UTC")), fixed("Z"));
+        assert_eq!(DefaultZone::from_setting(Some("-0530")), fixed("-05:30"));
+        assert_eq!(parse_offset("+25:00"), None);
+        assert_eq!(parse_offset("02:00"), None);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::error::{AppError, AppResult};
 use crate::media::ffmpeg::get_audio_waveform;
-use std::path::PathBuf;
+use crate::streaming::process_manager::{self, ActiveJob};
 use tauri::command;
 
 #[command]
@@ -8,10 +8,16 @@ pub async fn get_audio_waveform_data(
     app: tauri::AppHandle,
     path: String,
 ) -> AppResult<Vec<f32>> {
-    let input_path = PathBuf::from(&path);
+    let input_path = crate::paths::from_db(&path);
     if !input_path.exists() {
         return Err(AppError::NotFound(format!("File not found: {}", path)));
     }
 
     Ok(get_audio_waveform(&app, &input_path).map_err(|e| AppError::Generic(e.to_string()))?)
 }
+
+/// Lists the FFmpeg processes currently tracked by the supervisor.
+#[command]
+pub fn get_active_ffmpeg_jobs() -> Vec<ActiveJob> {
+    process_manager::lock_global().active_jobs()
+}
@@ -0,0 +1,241 @@
+//! XMP written into JPEG and PNG files, so ratings, notes and tags set in
+//! Mundam travel with the file.
+//!
+//! With the `write_metadata_to_files` setting on, the sidecar writer (see
+//! `media::sidecars`) puts them into the file's own XMP packet instead of a
+//! sidecar: the APP1 segment of a JPEG, the `XML:com.adobe.xmp` text chunk
+//! of a PNG. The rest of the packet and of the file are kept byte for byte,
+//! pixels included. Other formats, RAWs above all, get a sidecar.
+
+use std::path::{Path, PathBuf};
+
+use super::sidecars::{self, SidecarMetadata};
+
+/// Setting turning writing into files on, off by default: it changes the
+/// originals.
+pub const SETTING_KEY: &str = "write_metadata_to_files";
+
+/// Namespace opening the JPEG APP1 segment of an XMP packet.
+const JPEG_XMP_MARKER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// Largest packet a single APP1 segment holds.
+const JPEG_XMP_MAX: usize = 65535 - 2 - JPEG_XMP_MARKER.len();
+
+/// Keyword of the PNG `iTXt` chunk holding an XMP packet.
+const PNG_XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Formats whose files take the metadata themselves.
+pub const FORMATS: &[&str] = &["jpg", "jpeg", "png"];
+
+/// Whether files of `format` take the metadata themselves.
+pub fn is_supported(format: &str) -> bool {
+    FORMATS.contains(&format.to_lowercase().as_str())
+}
+
+/// Wraps a new packet the way Adobe applications write them.
+fn wrap_packet(xmp: &str) -> String {
+    format!("<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n{}<?xpacket end=\"w\"?>", xmp)
+}
+
+/// A JPEG's segments before the image data: `(start, end)` of each, marker
+/// included.
+fn jpeg_segments(data: &[u8]) -> Option<Vec<(usize, usize)>> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    // Up to the start of scan, after which there are no more metadata segments
+    while data.get(pos) == Some(&0xFF) && !matches!(data.get(pos + 1), Some(0xDA) | None) {
+        let length = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+        let end = pos + 2 + length;
+        if length < 2 || end > data.len() {
+            return None;
+        }
+        segments.push((pos, end));
+        pos = end;
+    }
+    Some(segments)
+}
+
+fn is_jpeg_xmp(data: &[u8], (start, end): (usize, usize)) -> bool {
+    data[start + 1] == 0xE1 && data[start + 4..end].starts_with(JPEG_XMP_MARKER)
+}
+
+/// XMP packet of a JPEG.
+fn jpeg_packet(data: &[u8]) -> Option<String> {
+    let segments = jpeg_segments(data)?;
+    let &(start, end) = segments.iter().find(|segment| is_jpeg_xmp(data, **segment))?;
+    String::from_utf8(data[start + 4 + JPEG_XMP_MARKER.len()..end].to_vec()).ok()
+}
+
+/// `data` with its XMP segment replaced by one holding `packet`, put after
+/// the JFIF and Exif segments when there was none.
+fn replace_jpeg_packet(data: &[u8], packet: &str) -> Option<Vec<u8>> {
+    if packet.len() > JPEG_XMP_MAX {
+        return None;
+    }
+    let segments = jpeg_segments(data)?;
+    let existing = segments.iter().find(|segment| is_jpeg_xmp(data, **segment)).copied();
+    let (before, after) = match existing {
+        Some((start, end)) => (start, end),
+        None => {
+            let leading = segments
+                .iter()
+                .take_while(|(start, _)| matches!(data[start + 1], 0xE0 | 0xE1))
+                .last()
+                .map_or(2, |(_, end)| *end);
+            (leading, leading)
+        }
+    };
+
+    let mut out = Vec::with_capacity(data.len() + packet.len() + 64);
+    out.extend_from_slice(&data[..before]);
+    out.extend_from_slice(&[0xFF, 0xE1]);
+    out.extend_from_slice(&((2 + JPEG_XMP_MARKER.len() + packet.len()) as u16).to_be_bytes());
+    out.extend_from_slice(JPEG_XMP_MARKER);
+    out.extend_from_slice(packet.as_bytes());
+    out.extend_from_slice(&data[after..]);
+    Some(out)
+}
+
+/// A PNG's chunks: `(start, end, type)` of each, length and CRC included.
+fn png_chunks(data: &[u8]) -> Option<Vec<(usize, usize, [u8; 4])>> {
+    if !data.starts_with(PNG_SIGNATURE) {
+        return None;
+    }
+    let mut chunks = Vec::new();
+    let mut pos = PNG_SIGNATURE.len();
+    while pos < data.len() {
+        let length = u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        let kind: [u8; 4] = data.get(pos + 4..pos + 8)?.try_into().ok()?;
+        let end = pos + 12 + length;
+        if end > data.len() {
+            return None;
+        }
+        chunks.push((pos, end, kind));
+        pos = end;
+    }
+    Some(chunks)
+}
+
+/// Text of an uncompressed `iTXt` chunk holding XMP: keyword, compression
+/// flag and method, then empty language and translated keyword.
+fn png_xmp_text(data: &[u8], (start, end, kind): (usize, usize, [u8; 4])) -> Option<&[u8]> {
+    let body = &data[start + 8..end - 4];
+    let text = body.strip_prefix(PNG_XMP_KEYWORD)?.strip_prefix(&[0, 0, 0])?;
+    let text = &text[text.iter().position(|b| *b == 0)? + 1..];
+    let text = &text[text.iter().position(|b| *b == 0)? + 1..];
+    (&kind == b"iTXt").then_some(text)
+}
+
+/// XMP packet of a PNG.
+fn png_packet(data: &[u8]) -> Option<String> {
+    let chunks = png_chunks(data)?;
+    chunks.into_iter().find_map(|chunk| png_xmp_text(data, chunk)).and_then(|text| String::from_utf8(text.to_vec()).ok())
+}
+
+/// `data` with its XMP chunk replaced by one holding `packet`, put right
+/// after `IHDR` when there was none.
+fn replace_png_packet(data: &[u8], packet: &str) -> Option<Vec<u8>> {
+    let chunks = png_chunks(data)?;
+    let header = chunks.first().filter(|(_, _, kind)| kind == b"IHDR")?;
+    let existing = chunks.iter().find(|chunk| png_xmp_text(data, **chunk).is_some()).copied();
+    let (before, after) = match existing {
+        Some((start, end, _)) => (start, end),
+        None => (header.1, header.1),
+    };
+
+    let mut body = PNG_XMP_KEYWORD.to_vec();
+    body.extend_from_slice(&[0, 0, 0, 0, 0]);
+    body.extend_from_slice(packet.as_bytes());
+    let mut crc = flate2::Crc::new();
+    crc.update(b"iTXt");
+    crc.update(&body);
+
+    let mut out = Vec::with_capacity(data.len() + body.len() + 12);
+    out.extend_from_slice(&data[..before]);
+    out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    out.extend_from_slice(b"iTXt");
+    out.extend_from_slice(&body);
+    out.extend_from_slice(&crc.sum().to_be_bytes());
+    out.extend_from_slice(&data[after..]);
+    Some(out)
+}
+
+/// `data` with `metadata` written into its XMP packet.
+fn embed(data: &[u8], metadata: &SidecarMetadata) -> Result<Vec<u8>, String> {
+    let (existing, replace): (Option<String>, fn(&[u8], &str) -> Option<Vec<u8>>) = if data.starts_with(&[0xFF, 0xD8]) {
+        (jpeg_packet(data), replace_jpeg_packet)
+    } else if data.starts_with(PNG_SIGNATURE) {
+        (png_packet(data), replace_png_packet)
+    } else {
+        return Err("Not a JPEG or PNG file".to_string());
+    };
+    let packet = match existing {
+        Some(existing) => sidecars::update(Some(&existing), metadata)?,
+        None => wrap_packet(&sidecars::update(None, metadata)?),
+    };
+    replace(data, &packet).ok_or_else(|| "Could not fit the metadata in the file".to_string())
+}
+
+/// Where the new version of `path` is written before replacing it.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp", name))
+}
+
+/// Writes `metadata` into the XMP packet of the JPEG or PNG file at `path`.
+/// The file is replaced at once, so readers never see half of it.
+pub fn write(path: &Path, metadata: &SidecarMetadata) -> Result<(), String> {
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    let embedded = embed(&data, metadata)?;
+
+    let tmp_path = temp_path(path);
+    std::fs::write(&tmp_path, embedded).map_err(|e| e.to_string())?;
+    if let Ok(permissions) = std::fs::metadata(path).map(|m| m.permissions()) {
+        let _ = std::fs::set_permissions(&tmp_path, permissions);
+    }
+    std::fs::rename(&tmp_path, path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp_path);
+        e.to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, RgbImage};
+
+    fn encoded(format: image::ImageFormat) -> Vec<u8> {
+        let mut data = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(16, 8, image::Rgb([200, 40, 90])))
+            .write_to(&mut std::io::Cursor::new(&mut data), format)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn test_embed_round_trip() {
+        let metadata = SidecarMetadata::from_library(4, Some("Quai des Orfèvres".to_string()), vec![vec!["Paris".to_string()]]);
+        for format in [image::ImageFormat::Jpeg, image::ImageFormat::Png] {
+            let original = encoded(format);
+            let once = embed(&original, &metadata).unwrap();
+            let packet = jpeg_packet(&once).or_else(|| png_packet(&once)).unwrap();
+            assert_eq!(sidecars::parse(&packet), metadata);
+
+            // Written again, the packet is replaced rather than added
+            let changed = SidecarMetadata { rating: Some(1), ..metadata.clone() };
+            let twice = embed(&once, &changed).unwrap();
+            let packet = jpeg_packet(&twice).or_else(|| png_packet(&twice)).unwrap();
+            assert_eq!(sidecars::parse(&packet).rating, Some(1));
+            assert_eq!(packet.matches("x:xmpmeta ").count(), 1);
+
+            let decoded = image::load_from_memory(&twice).unwrap();
+            assert_eq!(decoded.into_rgb8(), image::load_from_memory(&original).unwrap().into_rgb8());
+        }
+        assert!(embed(b"GIF89a", &metadata).is_err());
+    }
+}
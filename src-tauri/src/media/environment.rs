@@ -0,0 +1,89 @@
+//! Watches the runtime environment for FFmpeg appearing or disappearing.
+//!
+//! FFmpeg is looked up on every call, but the thumbnails rendered while it
+//! was missing stay as they were: a file icon, or a failure counted against
+//! the retry limit. When FFmpeg shows up mid-session (the user installs it,
+//! or mounts the drive holding it), those thumbnails are queued again and
+//! the UI is told, so it can refresh without a restart.
+
+use std::sync::Arc;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Runtime};
+use tokio::time::{sleep, Duration};
+
+use crate::db::Db;
+use crate::formats::{ThumbnailStrategy, SUPPORTED_FORMATS};
+use crate::media::ffmpeg::get_ffmpeg_path;
+
+/// How often FFmpeg is looked up again.
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Payload of the `environment:ffmpeg` event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FfmpegChange {
+    pub available: bool,
+    /// Thumbnails queued again, when FFmpeg became available.
+    pub requeued: u64,
+}
+
+/// Extensions of the formats only FFmpeg can render a thumbnail for.
+fn ffmpeg_extensions() -> Vec<&'static str> {
+    SUPPORTED_FORMATS
+        .iter()
+        .filter(|f| matches!(f.strategy, ThumbnailStrategy::Ffmpeg))
+        .flat_map(|f| f.extensions.iter().copied())
+        .collect()
+}
+
+/// Starts the environment watcher on the async runtime.
+pub fn start<R: Runtime>(db: Arc<Db>, app: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        let extensions = ffmpeg_extensions();
+        let mut available = is_available(&app).await;
+
+        loop {
+            sleep(POLL_INTERVAL).await;
+            let now_available = is_available(&app).await;
+            if now_available == available {
+                continue;
+            }
+            available = now_available;
+
+            let requeued = if available {
+                match db.requeue_thumbnails_for_formats(&extensions).await {
+                    Ok(count) => count,
+                    Err(e) => {
+                        eprintln!("WARN: Could not requeue thumbnails for FFmpeg: {}", e);
+                        0
+                    }
+                }
+            } else {
+                0
+            };
+            println!("INFO: FFmpeg became {} ({} thumbnails requeued)", if available { "available" } else { "unavailable" }, requeued);
+            let _ = app.emit("environment:ffmpeg", FfmpegChange { available, requeued });
+        }
+    });
+}
+
+/// Looks FFmpeg up off the async runtime, as it may spawn `ffmpeg -version`.
+async fn is_available<R: Runtime>(app: &AppHandle<R>) -> bool {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || get_ffmpeg_path(Some(&app)).is_some())
+        .await
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffmpeg_extensions() {
+        let extensions = ffmpeg_extensions();
+        assert!(!extensions.is_empty());
+        assert!(!extensions.contains(&"jpg"));
+    }
+}
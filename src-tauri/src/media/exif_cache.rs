@@ -0,0 +1,119 @@
+//! EXIF for the inspector, parsed once per file version.
+//!
+//! Parsing EXIF reads the file, which is slow for large RAW files and on
+//! network drives, and the inspector did it every time it opened. Parses
+//! are kept in `exif_cache` with the modification time and size of the file
+//! they came from, and a file that no longer matches is parsed again. A
+//! background warmer parses the files indexed in the last days ahead of
+//! time, since those are the ones people inspect.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use tokio::time::{sleep, Duration};
+
+use crate::db::exif::CachedExif;
+use crate::db::Db;
+use crate::media::metadata_reader;
+
+/// Formats `metadata_reader::read_exif` finds EXIF in: JPEG, and TIFF and the
+/// camera RAW formats built on it.
+pub const EXIF_FORMATS: &[&str] = &["jpg", "jpeg", "jpe", "tif", "tiff", "dng", "nef", "cr2", "arw", "orf", "pef", "srw", "rw2"];
+
+/// Files indexed this recently are warmed.
+const WARM_WINDOW_DAYS: i64 = 7;
+/// Files parsed per pass of the warmer.
+const WARM_BATCH_SIZE: i64 = 50;
+/// Pause once every recent file is cached.
+const IDLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Modification time in milliseconds and size of a file.
+fn file_stamp(path: &Path) -> Option<(i64, i64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_millis() as i64;
+    Some((mtime, metadata.len() as i64))
+}
+
+/// EXIF of the given images, keyed by id, from the cache when it is current
+/// and parsed otherwise. Files missing from disk are left out.
+pub async fn load(db: &Db, images: Vec<(i64, String)>) -> HashMap<i64, HashMap<String, String>> {
+    let ids: Vec<i64> = images.iter().map(|(id, _)| *id).collect();
+    let mut cached: HashMap<i64, CachedExif> = match db.get_cached_exif(&ids).await {
+        Ok(entries) => entries.into_iter().map(|entry| (entry.image_id, entry)).collect(),
+        Err(e) => {
+            eprintln!("WARN: Could not read cached EXIF: {}", e);
+            HashMap::new()
+        }
+    };
+
+    // Stat and parse off the async runtime; both touch the disk
+    let parsed = tauri::async_runtime::spawn_blocking(move || {
+        let mut result = HashMap::new();
+        let mut fresh = Vec::new();
+        for (id, path) in images {
+            let path = &crate::paths::from_db(&path);
+            let Some((file_mtime, file_size)) = file_stamp(path) else {
+                continue;
+            };
+            match cached.remove(&id) {
+                Some(entry) if entry.file_mtime == file_mtime && entry.file_size == file_size => {
+                    result.insert(id, entry.data);
+                }
+                _ => {
+                    let data = metadata_reader::read_exif(path);
+                    result.insert(id, data.clone());
+                    fresh.push(CachedExif { image_id: id, file_mtime, file_size, data });
+                }
+            }
+        }
+        (result, fresh)
+    })
+    .await;
+
+    let Ok((result, fresh)) = parsed else {
+        return HashMap::new();
+    };
+    // A read-only library is parsed again next time rather than written to
+    if !fresh.is_empty() && !crate::library::read_only::is_enabled() {
+        if let Err(e) = db.save_exif(&fresh).await {
+            eprintln!("WARN: Could not cache EXIF of {} files: {}", fresh.len(), e);
+        }
+    }
+    result
+}
+
+pub fn start(db: Arc<Db>) {
+    tauri::async_runtime::spawn(async move {
+        // Walks the recent files newest first, then starts over after a pause,
+        // so files that can't be parsed are only retried once per round
+        let mut before_id = i64::MAX;
+        let mut warmed: u64 = 0;
+        loop {
+            if crate::library::read_only::is_enabled() {
+                sleep(IDLE_INTERVAL).await;
+                continue;
+            }
+            match db.get_recent_images_needing_exif(EXIF_FORMATS, WARM_WINDOW_DAYS, before_id, WARM_BATCH_SIZE).await {
+                Ok(images) if !images.is_empty() => {
+                    before_id = images.iter().map(|(id, _)| *id).min().unwrap_or(before_id);
+                    warmed += load(&db, images).await.len() as u64;
+                    sleep(Duration::from_millis(200)).await;
+                }
+                Ok(_) => {
+                    if warmed > 0 {
+                        println!("INFO: Cached EXIF of {} recently indexed files", warmed);
+                        warmed = 0;
+                    }
+                    before_id = i64::MAX;
+                    sleep(IDLE_INTERVAL).await;
+                }
+                Err(e) => {
+                    eprintln!("WARN: Could not list files needing EXIF: {}", e);
+                    sleep(IDLE_INTERVAL).await;
+                }
+            }
+        }
+    });
+}
@@ -6,12 +6,11 @@
 //! - Design: psd, psb, ai, eps, svg, tiff
 
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
-use std::io::Read;
+use std::process::Command;
 use std::time::Duration;
-use wait_timeout::ChildExt;
 use tauri::Manager;
 use crate::error::{AppError, AppResult};
+use crate::streaming::process_manager::{self, JobKind};
 
 /// Get the path to the FFmpeg binary
 pub fn get_ffmpeg_path<R: tauri::Runtime>(app_handle: Option<&tauri::AppHandle<R>>) -> Option<PathBuf> {
@@ -54,29 +53,16 @@ pub fn is_ffmpeg_available() -> bool {
 }
 
 /// Helper to run a command with a timeout to avoid application freezes.
-fn run_command_with_timeout(mut cmd: Command, timeout_secs: u64) -> AppResult<std::process::Output> {
-    let mut child = cmd
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
-
-    match child.wait_timeout(Duration::from_secs(timeout_secs))? {
-        Some(status) => {
-            let mut stdout = Vec::new();
-            let mut stderr = Vec::new();
-            if let Some(mut s) = child.stdout {
-                 s.read_to_end(&mut stdout).ok();
-            }
-            if let Some(mut s) = child.stderr {
-                 s.read_to_end(&mut stderr).ok();
-            }
-            Ok(std::process::Output { status, stdout, stderr })
-        }
-        None => {
-            child.kill().ok();
-            Err(AppError::Transcoding(format!("Command timed out after {}s", timeout_secs)))
-        }
-    }
+///
+/// The process is tracked by the global FFmpeg supervisor, which also limits
+/// how many FFmpeg processes may run concurrently.
+fn run_command_with_timeout(cmd: Command, kind: JobKind, input_path: &Path, timeout_secs: u64) -> AppResult<std::process::Output> {
+    process_manager::run_supervised(
+        cmd,
+        kind,
+        &input_path.to_string_lossy(),
+        Duration::from_secs(timeout_secs),
+    )
 }
 
 pub fn generate_with_ffmpeg(
@@ -114,7 +100,7 @@ pub fn generate_with_ffmpeg(
         let mut cmd = Command::new(ffmpeg_path);
         cmd.args(&args);
 
-        let output = run_command_with_timeout(cmd, 15)?;
+        let output = run_command_with_timeout(cmd, JobKind::Thumbnail, input_path, 15)?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -150,6 +136,41 @@ pub fn generate_with_ffmpeg(
     Ok(())
 }
 
+/// Renders the embedded cover picture of a media file as a thumbnail.
+///
+/// Cover art (MP4 `covr`, MKV image attachments, ID3 pictures) is exposed by
+/// FFmpeg as an "attached_pic" video stream. Fails when the file has none.
+pub fn extract_cover_art<R: tauri::Runtime>(
+    app_handle: Option<&tauri::AppHandle<R>>,
+    input_path: &Path,
+    output_path: &Path,
+    size_px: u32,
+) -> AppResult<()> {
+    let ffmpeg_path = get_ffmpeg_path(app_handle)
+        .ok_or(AppError::FfmpegMissing)?;
+
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-hide_banner", "-loglevel", "error", "-i"])
+        .arg(input_path)
+        // All video streams minus the real ones (`V`) leaves only attached pictures
+        .args(["-map", "0:v", "-map", "-0:V"])
+        .arg("-vf")
+        .arg(format!("scale={}:-1:flags=lanczos", size_px))
+        .args(["-frames:v", "1", "-c:v", "libwebp", "-q:v", "80", "-y"])
+        .arg(output_path);
+
+    let output = run_command_with_timeout(cmd, JobKind::Thumbnail, input_path, 15)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::Transcoding(stderr.to_string()));
+    }
+    if !output_path.exists() {
+        return Err(AppError::Transcoding("FFmpeg did not create output file".to_string()));
+    }
+    Ok(())
+}
+
 pub fn generate_thumbnail_ffmpeg_full<R: tauri::Runtime>(
     app_handle: Option<&tauri::AppHandle<R>>,
     input_path: &Path,
@@ -158,7 +179,7 @@ pub fn generate_thumbnail_ffmpeg_full<R: tauri::Runtime>(
     is_video: bool,
 ) -> AppResult<()> {
     let ffmpeg_path = get_ffmpeg_path(app_handle)
-        .ok_or_else(|| AppError::Transcoding("FFmpeg not found (neither bundled nor in system PATH)".to_string()))?;
+        .ok_or(AppError::FfmpegMissing)?;
 
     generate_with_ffmpeg(&ffmpeg_path, input_path, output_path, size_px, is_video)
         .map_err(|e| AppError::Transcoding(e.to_string()))
@@ -169,7 +190,7 @@ pub fn get_audio_waveform<R: tauri::Runtime>(
     input_path: &Path,
 ) -> AppResult<Vec<f32>> {
     let ffmpeg_path = get_ffmpeg_path(Some(app_handle))
-        .ok_or_else(|| AppError::Transcoding("FFmpeg not found".to_string()))?;
+        .ok_or(AppError::FfmpegMissing)?;
 
     let mut cmd = Command::new(ffmpeg_path);
     cmd.args([
@@ -182,7 +203,7 @@ pub fn get_audio_waveform<R: tauri::Runtime>(
         "-",
     ]);
 
-    let output = run_command_with_timeout(cmd, 30)?;
+    let output = run_command_with_timeout(cmd, JobKind::Waveform, input_path, 30)?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -223,7 +244,7 @@ pub fn get_audio_waveform<R: tauri::Runtime>(
 
 pub fn extract_frame_to_memory<R: tauri::Runtime>(app_handle: Option<&tauri::AppHandle<R>>, input_path: &Path) -> AppResult<Vec<u8>> {
     let ffmpeg_path = get_ffmpeg_path(app_handle)
-        .ok_or_else(|| AppError::Transcoding("FFmpeg not found".to_string()))?;
+        .ok_or(AppError::FfmpegMissing)?;
 
     let input_str = input_path.to_string_lossy();
     let ext = input_path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
@@ -259,7 +280,7 @@ pub fn extract_frame_to_memory<R: tauri::Runtime>(app_handle: Option<&tauri::App
 
     let mut cmd = Command::new(&ffmpeg_path);
     cmd.args(&args);
-    let output = run_command_with_timeout(cmd, 15)?;
+    let output = run_command_with_timeout(cmd, JobKind::Thumbnail, input_path, 15)?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -275,7 +296,7 @@ pub fn extract_frame_to_memory<R: tauri::Runtime>(app_handle: Option<&tauri::App
              ];
             let mut retry_cmd = Command::new(&ffmpeg_path);
             retry_cmd.args(&retry_args);
-            let retry_output = run_command_with_timeout(retry_cmd, 10)?;
+            let retry_output = run_command_with_timeout(retry_cmd, JobKind::Thumbnail, input_path, 10)?;
 
             if retry_output.status.success() {
                 return Ok(retry_output.stdout);
@@ -0,0 +1,101 @@
+//! Background worker that persists duration, codecs, frame rate and bitrate
+//! of video and audio files so they can be used in smart folder criteria.
+//! GIF and WebP files get their frame count, timing and looping too, read
+//! natively so they don't wait for FFmpeg.
+
+use std::sync::Arc;
+use tauri::AppHandle;
+use tokio::time::{sleep, Duration};
+
+use crate::db::Db;
+use crate::formats::{MediaType, SUPPORTED_FORMATS};
+use crate::media::animation;
+use crate::media::ffmpeg::is_ffmpeg_available;
+use crate::streaming::probe;
+
+/// Number of files probed per pass.
+const BATCH_SIZE: i32 = 20;
+
+/// Formats that may hold an animation (see `media::animation`).
+const ANIMATED_FORMATS: [&str; 2] = ["gif", "webp"];
+
+/// Extensions of every format that carries audio or video streams.
+fn media_extensions() -> Vec<&'static str> {
+    SUPPORTED_FORMATS
+        .iter()
+        .filter(|f| matches!(f.type_category, MediaType::Video | MediaType::Audio))
+        .flat_map(|f| f.extensions.iter().copied())
+        .collect()
+}
+
+/// Starts the media info worker on the async runtime.
+pub fn start(db: Arc<Db>, app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let extensions = media_extensions();
+
+        loop {
+            let animations = read_animations(&db).await;
+
+            if !is_ffmpeg_available() {
+                sleep(if animations > 0 { Duration::from_millis(200) } else { Duration::from_secs(60) }).await;
+                continue;
+            }
+
+            let batch = match db.get_media_needing_probe(&extensions, BATCH_SIZE).await {
+                Ok(batch) => batch,
+                Err(e) => {
+                    eprintln!("Media info worker DB error: {}", e);
+                    sleep(Duration::from_secs(10)).await;
+                    continue;
+                }
+            };
+
+            if batch.is_empty() {
+                sleep(if animations > 0 { Duration::from_millis(200) } else { Duration::from_secs(10) }).await;
+                continue;
+            }
+
+            for (id, path) in batch {
+                let result = match probe::get_video_info(&app, &crate::paths::from_db(&path)).await {
+                    Ok(info) => db.update_media_info(id, &info).await,
+                    Err(e) => {
+                        eprintln!("Media probe failed for {}: {}", path, e);
+                        db.mark_media_probe_failed(id).await
+                    }
+                };
+                if let Err(e) = result {
+                    eprintln!("Failed to store media info for {}: {}", path, e);
+                }
+            }
+
+            // Yield between batches so probing never competes with the UI
+            sleep(Duration::from_millis(200)).await;
+        }
+    });
+}
+
+/// Reads one batch of GIF and WebP files. Returns how many were handled.
+async fn read_animations(db: &Db) -> usize {
+    let batch = match db.get_media_needing_probe(&ANIMATED_FORMATS, BATCH_SIZE).await {
+        Ok(batch) => batch,
+        Err(e) => {
+            eprintln!("Media info worker DB error: {}", e);
+            return 0;
+        }
+    };
+
+    for (id, path) in &batch {
+        let read = {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || animation::read(&crate::paths::from_db(&path))).await
+        };
+        let result = match read {
+            Ok(Ok(Some(info))) => db.update_animation_info(*id, &info).await,
+            Ok(Ok(None)) | Ok(Err(_)) | Err(_) => db.mark_media_probe_failed(*id).await,
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to store animation info for {}: {}", path, e);
+        }
+    }
+    batch.len()
+}
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::path::Path;
 
+use crate::media::sidecars::{self, Sidecar};
+
 pub fn read_exif(path: &Path) -> HashMap<String, String> {
     let mut result = HashMap::new();
 
@@ -20,3 +22,120 @@ pub fn read_exif(path: &Path) -> HashMap<String, String> {
 
     result
 }
+
+/// EXIF tags of the capture time, by number: rexif predates the offset tags.
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_OFFSET_TIME: u16 = 0x9010;
+const TAG_OFFSET_TIME_ORIGINAL: u16 = 0x9011;
+
+/// What the capture-time pass reads from a file's EXIF.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CaptureExif {
+    /// Capture time as written in the file and its offset, if recorded (see
+    /// `media::capture_time`).
+    pub time: Option<(String, Option<String>)>,
+    /// Latitude and longitude, in signed decimal degrees.
+    pub location: Option<(f64, f64)>,
+}
+
+/// Capture time and GPS position of the file at `path`. The time falls back
+/// to the last modification time the camera wrote when there is no
+/// `DateTimeOriginal`.
+pub fn read_capture_exif(path: &Path) -> Option<CaptureExif> {
+    let data = rexif::parse_file(path.to_string_lossy().as_ref()).ok()?;
+    let ascii = |tag: u16| {
+        data.entries.iter().find(|entry| entry.ifd.tag == tag).and_then(|entry| match &entry.value {
+            rexif::TagValue::Ascii(value) if !value.trim_matches(|c: char| c.is_whitespace() || c == '\0').is_empty() => {
+                Some(value.clone())
+            }
+            _ => None,
+        })
+    };
+
+    let time = match ascii(TAG_DATE_TIME_ORIGINAL) {
+        Some(date) => Some((date, ascii(TAG_OFFSET_TIME_ORIGINAL))),
+        None => ascii(TAG_DATE_TIME).map(|date| (date, ascii(TAG_OFFSET_TIME))),
+    };
+
+    // GPS tags share their numbers with other IFDs; rexif tells them apart
+    let rationals = |tag: rexif::ExifTag| {
+        data.entries.iter().find(|entry| entry.tag == tag).and_then(|entry| match &entry.value {
+            rexif::TagValue::URational(parts) => {
+                Some(parts.iter().map(|part| (part.numerator, part.denominator)).collect::<Vec<_>>())
+            }
+            _ => None,
+        })
+    };
+    let reference = |tag: rexif::ExifTag| {
+        data.entries.iter().find(|entry| entry.tag == tag).and_then(|entry| match &entry.value {
+            rexif::TagValue::Ascii(value) => Some(value.clone()),
+            _ => None,
+        })
+    };
+    let latitude = rationals(rexif::ExifTag::GPSLatitude)
+        .zip(reference(rexif::ExifTag::GPSLatitudeRef))
+        .and_then(|(parts, reference)| gps_coordinate(&parts, &reference, 90.0));
+    let longitude = rationals(rexif::ExifTag::GPSLongitude)
+        .zip(reference(rexif::ExifTag::GPSLongitudeRef))
+        .and_then(|(parts, reference)| gps_coordinate(&parts, &reference, 180.0));
+    // Cameras without a fix write zeroes
+    let location = latitude.zip(longitude).filter(|position| *position != (0.0, 0.0));
+
+    Some(CaptureExif { time, location })
+}
+
+/// Capture time as written in the file and its offset, if recorded (see
+/// [`read_capture_exif`]).
+pub fn read_capture_time(path: &Path) -> Option<(String, Option<String>)> {
+    read_capture_exif(path)?.time
+}
+
+/// Signed decimal degrees of an EXIF GPS coordinate: degrees, minutes and
+/// seconds as rationals, negative towards `S` or `W`. `None` when malformed
+/// or beyond `limit`.
+pub fn gps_coordinate(parts: &[(u32, u32)], reference: &str, limit: f64) -> Option<f64> {
+    if parts.is_empty() || parts.len() > 3 || parts.iter().any(|(_, denominator)| *denominator == 0) {
+        return None;
+    }
+    let degrees: f64 = parts
+        .iter()
+        .zip([1.0, 60.0, 3600.0])
+        .map(|((numerator, denominator), unit)| *numerator as f64 / *denominator as f64 / unit)
+        .sum();
+    let sign = match reference.trim_matches(|c: char| c.is_whitespace() || c == '\0').to_ascii_uppercase().as_str() {
+        "N" | "E" => 1.0,
+        "S" | "W" => -1.0,
+        _ => return None,
+    };
+    (degrees <= limit).then_some(sign * degrees)
+}
+
+/// Rating, label and keywords from the XMP sidecar next to `path`, if it
+/// has one (see `media::sidecars`).
+pub fn read_sidecar(path: &Path) -> Option<Sidecar> {
+    let sidecar = sidecars::find(path)?;
+    let modified = sidecars::modified_millis(&sidecar)?;
+    let xmp = std::fs::read_to_string(&sidecar).ok()?;
+    Some(Sidecar { metadata: sidecars::parse(&xmp), path: sidecar, modified })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gps_coordinate() {
+        // 48° 51' 29.6" N, 2° 17' 40.2" E
+        let latitude = gps_coordinate(&[(48, 1), (51, 1), (2960, 100)], "N", 90.0).unwrap();
+        let longitude = gps_coordinate(&[(2, 1), (17, 1), (402, 10)], "E", 180.0).unwrap();
+        assert!((latitude - 48.858222).abs() < 1e-6);
+        assert!((longitude - 2.294500).abs() < 1e-6);
+        // Decimal minutes, as some phones write them
+        let south = gps_coordinate(&[(33, 1), (5153, 100), (0, 1)], "S\0", 90.0).unwrap();
+        assert!((south + 33.858833).abs() < 1e-6);
+        assert_eq!(gps_coordinate(&[(12, 1), (0, 0), (0, 1)], "W", 180.0), None);
+        assert_eq!(gps_coordinate(&[(95, 1)], "N", 90.0), None);
+        assert_eq!(gps_coordinate(&[(10, 1)], "", 90.0), None);
+    }
+}
@@ -1,4 +1,14 @@
+pub mod animation;
+pub mod camera_raw;
+pub mod capture_time;
 pub mod commands;
+pub mod embedded_xmp;
+pub mod environment;
+pub mod exif_cache;
 pub mod ffmpeg;
+pub mod info_worker;
 pub mod metadata_reader;
 pub mod pdf;
+pub mod sidecars;
+pub mod stock;
+pub mod xmp;
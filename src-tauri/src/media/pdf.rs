@@ -1,8 +1,13 @@
 use pdfium_render::prelude::*;
 use image::DynamicImage;
 use std::io::Cursor;
+use std::path::PathBuf;
 use tauri::Manager;
 
+/// Resource directory handed to isolated decoder helpers (see
+/// `thumbnails::isolated`), which have no app handle to look it up.
+pub const RESOURCE_DIR_ENV: &str = "MUNDAM_RESOURCE_DIR";
+
 /// Renders a PDF (or AI with PDF stream) to a PNG image buffer.
 /// Searches for PDFium in:
 /// 1. Bundled resources (production/development)
@@ -16,17 +21,19 @@ pub fn render_pdf_data_to_image<R: tauri::Runtime>(
     // 1. Try to find the bundled library
     let mut bindings = None;
 
-    if let Some(handle) = app_handle {
-        if let Ok(resource_dir) = handle.path().resource_dir() {
-            let lib_name = Pdfium::pdfium_platform_library_name_at_path("./");
-            let bundled_path = resource_dir
-                .join("binaries")
-                .join("pdfium")
-                .join(&lib_name);
-
-            if bundled_path.exists() {
-                bindings = Pdfium::bind_to_library(bundled_path).ok();
-            }
+    let resource_dir = match app_handle {
+        Some(handle) => handle.path().resource_dir().ok(),
+        None => std::env::var_os(RESOURCE_DIR_ENV).map(PathBuf::from),
+    };
+    if let Some(resource_dir) = resource_dir {
+        let lib_name = Pdfium::pdfium_platform_library_name_at_path("./");
+        let bundled_path = resource_dir
+            .join("binaries")
+            .join("pdfium")
+            .join(&lib_name);
+
+        if bundled_path.exists() {
+            bindings = Pdfium::bind_to_library(bundled_path).ok();
         }
     }
 
@@ -0,0 +1,636 @@
+//! XMP sidecars, for Lightroom, Bridge, Capture One and darktable interop.
+//!
+//! Those applications keep the rating, colour label, caption and keywords of
+//! files they can't write into, RAWs above all, in an `.xmp` file next to
+//! them: `IMG_1.xmp` for Lightroom and Bridge, `IMG_1.CR3.xmp` for darktable
+//! and digiKam. Sidecars are read after indexing and whenever the watcher or
+//! a scan sees one change: the rating replaces Mundam's unless Mundam has
+//! changes not written out yet, the caption fills empty notes, keywords
+//! become tags (hierarchical ones as nested tags) and the label a tag under
+//! `Labels`; tags are never removed. With the `write_xmp_sidecars` setting
+//! on, ratings, notes and tags changed in Mundam are written back, and with
+//! `write_metadata_to_files` they go into JPEG and PNG files themselves (see
+//! `media::embedded_xmp`), other formats getting a sidecar. Only those
+//! properties are replaced, so develop settings and everything else in the
+//! XMP stay as they were.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use chrono::{DateTime, Utc};
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::reader::Reader;
+use quick_xml::writer::Writer;
+use tokio::sync::Notify;
+use tokio::time::{sleep, Duration};
+
+use crate::db::sidecars::{PendingWrite, SidecarRead};
+use crate::db::Db;
+use crate::indexer::echo;
+use crate::media::{embedded_xmp, metadata_reader, xmp};
+
+/// Setting turning sidecar writing on, off by default: it creates files
+/// next to the originals.
+pub const WRITE_SETTING_KEY: &str = "write_xmp_sidecars";
+
+/// Parent tag of the tags created for colour labels.
+pub const LABELS_TAG: &str = "Labels";
+
+/// Files handled per pass.
+const BATCH_SIZE: i64 = 200;
+
+/// Pause once every sidecar is read and written.
+const IDLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Cuts the pause short when Mundam changes a rating or notes.
+static WAKE: Notify = Notify::const_new();
+
+/// Properties Mundam manages; everything else in a sidecar is kept.
+const MANAGED_PROPERTIES: &[&str] =
+    &["xmp:Rating", "xmp:Label", "dc:description", "dc:subject", "lr:hierarchicalSubject"];
+
+const NAMESPACES: &[(&str, &str)] = &[
+    ("xmlns:xmp", "http://ns.adobe.com/xap/1.0/"),
+    ("xmlns:dc", "http://purl.org/dc/elements/1.1/"),
+    ("xmlns:lr", "http://ns.adobe.com/lightroom/1.0/"),
+];
+
+/// Sidecar written when the file has none.
+const EMPTY_SIDECAR: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/" x:xmptk="Mundam">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""/>
+ </rdf:RDF>
+</x:xmpmeta>
+"#;
+
+/// What Mundam reads from and writes to a sidecar.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SidecarMetadata {
+    /// 0 to 5; rejected (-1) and unrated files have none.
+    pub rating: Option<i32>,
+    /// Colour label, as the application named it (`Red`, `To Do`...).
+    pub label: Option<String>,
+    /// Caption (`dc:description`), Mundam's notes.
+    pub description: Option<String>,
+    /// Keywords as tag paths, parent first.
+    pub keywords: Vec<Vec<String>>,
+}
+
+impl SidecarMetadata {
+    /// What Mundam knows of an image, its tag paths split into its label (a
+    /// tag under `Labels`) and keywords.
+    pub fn from_library(rating: i32, notes: Option<String>, tag_paths: Vec<Vec<String>>) -> Self {
+        let mut label = None;
+        let mut keywords = Vec::new();
+        for path in tag_paths {
+            match path.as_slice() {
+                [group, name] if group == LABELS_TAG => {
+                    label.get_or_insert_with(|| name.clone());
+                }
+                _ => keywords.push(path),
+            }
+        }
+        let description = notes.filter(|notes| !notes.trim().is_empty());
+        SidecarMetadata { rating: Some(rating), label, description, keywords }
+    }
+
+    /// Tag paths to apply: the keywords, then the label under `Labels`.
+    pub fn tag_paths(&self) -> Vec<Vec<String>> {
+        let label = self.label.iter().map(|label| vec![LABELS_TAG.to_string(), label.clone()]);
+        self.keywords.iter().cloned().chain(label).collect()
+    }
+}
+
+/// A sidecar found next to a file.
+#[derive(Debug, Clone)]
+pub struct Sidecar {
+    pub path: PathBuf,
+    /// Modification time, in milliseconds since the Unix epoch.
+    pub modified: i64,
+    pub metadata: SidecarMetadata,
+}
+
+/// Whether `path` is an XMP sidecar.
+pub fn is_sidecar(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("xmp"))
+}
+
+/// Lightroom and Bridge name the sidecars of RAWs after the stem alone.
+fn is_raw(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| crate::thumbnails::RAW_EXTENSIONS.contains(&e.to_string_lossy().to_lowercase().as_str()))
+}
+
+/// Where the sidecar of `path` may be, most likely first.
+fn candidates(path: &Path) -> Vec<PathBuf> {
+    let (Some(name), Some(stem)) = (path.file_name(), path.file_stem()) else {
+        return Vec::new();
+    };
+    let named = |base: &std::ffi::OsStr| {
+        let base = base.to_string_lossy();
+        [path.with_file_name(format!("{}.xmp", base)), path.with_file_name(format!("{}.XMP", base))]
+    };
+    // A JPEG shot alongside a RAW shares the RAW's sidecar when it has none of its own
+    if is_raw(path) {
+        named(stem).into_iter().chain(named(name)).collect()
+    } else {
+        named(name).into_iter().chain(named(stem)).collect()
+    }
+}
+
+/// The sidecar next to `path`, if there is one.
+pub fn find(path: &Path) -> Option<PathBuf> {
+    candidates(path).into_iter().find(|candidate| candidate.is_file())
+}
+
+/// The sidecar written for `path`: the one it has, or a new one named the
+/// way the applications reading that kind of file expect. A JPEG never
+/// writes into the sidecar of a RAW with the same stem.
+fn write_target(path: &Path) -> Option<PathBuf> {
+    let candidates = candidates(path);
+    let own = &candidates[..candidates.len().min(2)];
+    own.iter().find(|candidate| candidate.is_file()).or(own.first()).cloned()
+}
+
+/// Modification time of `path` in milliseconds since the Unix epoch.
+pub fn modified_millis(path: &Path) -> Option<i64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_millis() as i64)
+}
+
+/// Reads the rating, label and keywords of a sidecar's XMP.
+pub fn parse(xmp: &str) -> SidecarMetadata {
+    let fields = xmp::parse_fields(xmp);
+    let rating = fields
+        .get("xmp:Rating")
+        .and_then(|rating| rating.parse::<f64>().ok())
+        .map(|rating| rating.round() as i32)
+        .filter(|rating| (0..=5).contains(rating));
+    let label = fields.get("xmp:Label").cloned();
+    let description = fields.get("dc:description").cloned();
+
+    let mut seen = HashSet::new();
+    let mut keywords: Vec<Vec<String>> = xmp::parse_list(xmp, "lr:hierarchicalSubject")
+        .iter()
+        .map(|path| path.split('|').map(str::trim).filter(|name| !name.is_empty()).map(String::from).collect::<Vec<_>>())
+        .filter(|path| !path.is_empty() && seen.insert(path.clone()))
+        .collect();
+    // Lightroom lists every level flat too; those are in the paths already
+    let named: HashSet<&String> = keywords.iter().flatten().collect();
+    let flat: Vec<Vec<String>> = xmp::parse_list(xmp, "dc:subject")
+        .into_iter()
+        .filter(|keyword| !named.contains(keyword))
+        .map(|keyword| vec![keyword])
+        .filter(|path| seen.insert(path.clone()))
+        .collect();
+    keywords.extend(flat);
+    SidecarMetadata { rating, label, description, keywords }
+}
+
+fn name_of(element: &BytesStart) -> String {
+    String::from_utf8_lossy(element.name().as_ref()).to_string()
+}
+
+/// `element` without the managed properties written as attributes, plus,
+/// when `metadata` is given, the rating, label and namespaces Mundam writes.
+fn description(element: &BytesStart, metadata: Option<&SidecarMetadata>) -> BytesStart<'static> {
+    let mut rewritten = BytesStart::new(name_of(element));
+    let mut declared = HashSet::new();
+    for attribute in element.attributes().flatten() {
+        let key = String::from_utf8_lossy(attribute.key.as_ref()).to_string();
+        if MANAGED_PROPERTIES.contains(&key.as_str()) {
+            continue;
+        }
+        rewritten.push_attribute((key.as_bytes(), attribute.value.as_ref()));
+        declared.insert(key);
+    }
+    if let Some(metadata) = metadata {
+        for (prefix, uri) in NAMESPACES {
+            if !declared.contains(*prefix) {
+                rewritten.push_attribute((*prefix, *uri));
+            }
+        }
+        if let Some(rating) = metadata.rating {
+            rewritten.push_attribute(("xmp:Rating", rating.to_string().as_str()));
+        }
+        if let Some(label) = &metadata.label {
+            rewritten.push_attribute(("xmp:Label", label.as_str()));
+        }
+    }
+    rewritten
+}
+
+/// Writes `<property><rdf:Bag><rdf:li>value</rdf:li>...</rdf:Bag></property>`.
+fn write_bag(writer: &mut Writer<Vec<u8>>, property: &str, values: &[String]) -> std::io::Result<()> {
+    if values.is_empty() {
+        return Ok(());
+    }
+    writer.write_event(Event::Start(BytesStart::new(property)))?;
+    writer.write_event(Event::Start(BytesStart::new("rdf:Bag")))?;
+    for value in values {
+        writer.write_event(Event::Start(BytesStart::new("rdf:li")))?;
+        writer.write_event(Event::Text(BytesText::new(value)))?;
+        writer.write_event(Event::End(BytesEnd::new("rdf:li")))?;
+    }
+    writer.write_event(Event::End(BytesEnd::new("rdf:Bag")))?;
+    writer.write_event(Event::End(BytesEnd::new(property)))
+}
+
+/// Writes the caption, as the default language of `dc:description`.
+fn write_caption(writer: &mut Writer<Vec<u8>>, caption: &str) -> std::io::Result<()> {
+    let mut item = BytesStart::new("rdf:li");
+    item.push_attribute(("xml:lang", "x-default"));
+    writer.write_event(Event::Start(BytesStart::new("dc:description")))?;
+    writer.write_event(Event::Start(BytesStart::new("rdf:Alt")))?;
+    writer.write_event(Event::Start(item))?;
+    writer.write_event(Event::Text(BytesText::new(caption)))?;
+    writer.write_event(Event::End(BytesEnd::new("rdf:li")))?;
+    writer.write_event(Event::End(BytesEnd::new("rdf:Alt")))?;
+    writer.write_event(Event::End(BytesEnd::new("dc:description")))
+}
+
+/// Writes the caption and the keyword lists: leaf names for `dc:subject`,
+/// which every application reads, and full paths for Lightroom's hierarchy.
+fn write_lists(writer: &mut Writer<Vec<u8>>, metadata: &SidecarMetadata) -> std::io::Result<()> {
+    if let Some(caption) = &metadata.description {
+        write_caption(writer, caption)?;
+    }
+    let mut leaves: Vec<String> = Vec::new();
+    for leaf in metadata.keywords.iter().filter_map(|path| path.last()) {
+        if !leaves.contains(leaf) {
+            leaves.push(leaf.clone());
+        }
+    }
+    let paths: Vec<String> = metadata.keywords.iter().map(|path| path.join("|")).collect();
+    write_bag(writer, "dc:subject", &leaves)?;
+    write_bag(writer, "lr:hierarchicalSubject", &paths)
+}
+
+/// Writes the description Mundam's properties go into, closing it when it
+/// was empty.
+fn write_description(
+    writer: &mut Writer<Vec<u8>>,
+    element: &BytesStart,
+    metadata: &SidecarMetadata,
+    close: bool,
+) -> std::io::Result<()> {
+    writer.write_event(Event::Start(description(element, Some(metadata))))?;
+    write_lists(writer, metadata)?;
+    if close {
+        writer.write_event(Event::End(BytesEnd::new("rdf:Description")))?;
+    }
+    Ok(())
+}
+
+/// Returns `existing` (a new sidecar when `None`) with the managed
+/// properties replaced by `metadata`, leaving everything else untouched.
+///
+/// # Errors
+/// Returns a description of the problem when `existing` isn't XMP, so a
+/// foreign file is never overwritten.
+pub fn update(existing: Option<&str>, metadata: &SidecarMetadata) -> Result<String, String> {
+    let mut reader = Reader::from_str(existing.unwrap_or(EMPTY_SIDECAR));
+    let mut writer = Writer::new(Vec::new());
+    // Elements open inside a managed property being dropped
+    let mut skipping = 0usize;
+    let mut written = false;
+
+    loop {
+        let event = reader.read_event().map_err(|e| format!("Invalid XMP: {}", e))?;
+        let result = match event {
+            Event::Eof => break,
+            Event::Start(_) if skipping > 0 => {
+                skipping += 1;
+                Ok(())
+            }
+            Event::End(_) if skipping > 0 => {
+                skipping -= 1;
+                Ok(())
+            }
+            _ if skipping > 0 => Ok(()),
+            Event::Start(element) if MANAGED_PROPERTIES.contains(&name_of(&element).as_str()) => {
+                skipping = 1;
+                Ok(())
+            }
+            Event::Empty(element) if MANAGED_PROPERTIES.contains(&name_of(&element).as_str()) => Ok(()),
+            // Other descriptions only lose the managed properties
+            Event::Start(element) if name_of(&element) == "rdf:Description" && written => {
+                writer.write_event(Event::Start(description(&element, None)))
+            }
+            Event::Empty(element) if name_of(&element) == "rdf:Description" && written => {
+                writer.write_event(Event::Empty(description(&element, None)))
+            }
+            Event::Start(element) if name_of(&element) == "rdf:Description" => {
+                written = true;
+                write_description(&mut writer, &element, metadata, false)
+            }
+            Event::Empty(element) if name_of(&element) == "rdf:Description" => {
+                written = true;
+                write_description(&mut writer, &element, metadata, true)
+            }
+            // An RDF block without any description gets one
+            Event::End(element) if element.name().as_ref() == b"rdf:RDF" && !written => {
+                written = true;
+                write_description(&mut writer, &BytesStart::new("rdf:Description"), metadata, true)
+                    .and_then(|_| writer.write_event(Event::End(element)))
+            }
+            other => writer.write_event(other),
+        };
+        result.map_err(|e| e.to_string())?;
+    }
+    if !written {
+        return Err("No RDF block in the sidecar".to_string());
+    }
+    String::from_utf8(writer.into_inner()).map_err(|e| e.to_string())
+}
+
+/// Writes `metadata` into the sidecar of `image`, creating it when there is
+/// none. Returns the sidecar's new modification time.
+pub fn write(image: &Path, metadata: &SidecarMetadata) -> Result<i64, String> {
+    if !image.is_file() {
+        return Err(format!("File not found: {}", image.display()));
+    }
+    let target = write_target(image).ok_or("The file has no name")?;
+    let existing = match std::fs::read_to_string(&target) {
+        Ok(existing) => Some(existing),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.to_string()),
+    };
+    let updated = update(existing.as_deref(), metadata)?;
+
+    let name = target.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let tmp_path = target.with_file_name(format!(".{}.tmp", name));
+    std::fs::write(&tmp_path, updated).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp_path, &target).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp_path);
+        e.to_string()
+    })?;
+    modified_millis(&target).ok_or_else(|| "Could not read the sidecar's modification time".to_string())
+}
+
+/// Looks for the sidecar of a file, parsing it unless it is unchanged since
+/// it was last read (`known` modification time).
+fn read(image_id: i64, path: &str, known: Option<i64>) -> SidecarRead {
+    let path = &crate::paths::from_db(path);
+    let modified = find(path).and_then(|sidecar| modified_millis(&sidecar));
+    if modified.is_none() || modified == known {
+        return SidecarRead { image_id, modified, metadata: None };
+    }
+    match metadata_reader::read_sidecar(path) {
+        Some(sidecar) => SidecarRead { image_id, modified: Some(sidecar.modified), metadata: Some(sidecar.metadata) },
+        None => SidecarRead { image_id, modified, metadata: None },
+    }
+}
+
+/// Reads a batch of sidecars. Returns the number of files handled.
+async fn read_batch(db: &Db) -> usize {
+    let images = match db.get_images_needing_sidecar_check(BATCH_SIZE).await {
+        Ok(images) => images,
+        Err(e) => {
+            eprintln!("WARN: Could not list files needing a sidecar check: {}", e);
+            return 0;
+        }
+    };
+    if images.is_empty() {
+        return 0;
+    }
+    let reads = tauri::async_runtime::spawn_blocking(move || {
+        images.iter().map(|(id, path, known)| read(*id, path, *known)).collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+    let parsed = reads.iter().filter(|read| read.metadata.is_some()).count();
+    if let Err(e) = db.store_sidecar_reads(&reads).await {
+        eprintln!("WARN: Could not store what was read from sidecars: {}", e);
+        return 0;
+    }
+    if parsed > 0 {
+        println!("INFO: Read {} XMP sidecars", parsed);
+    }
+    reads.len()
+}
+
+/// Where a change was written.
+enum Written {
+    /// Nowhere: the sidecar changed since it was last read, and is read
+    /// again first so what another application added to it is kept.
+    Stale,
+    /// Into the sidecar, with its new modification time.
+    Sidecar(i64),
+    /// Into the file itself, with its new size and modification time.
+    File(i64, DateTime<Utc>),
+    Failed,
+}
+
+/// Writes the metadata of a file where the settings say: into the file when
+/// `embed` is on and its format takes it, into a sidecar otherwise.
+fn write_pending(pending: &PendingWrite, metadata: &SidecarMetadata, embed: bool) -> Result<Written, String> {
+    let path = &crate::paths::from_db(&pending.path);
+    if !(embed && embedded_xmp::is_supported(&pending.format)) {
+        if find(path).and_then(|sidecar| modified_millis(&sidecar)) != pending.sidecar_modified_at {
+            return Ok(Written::Stale);
+        }
+        return write(path, metadata).map(Written::Sidecar);
+    }
+    // The library already reflects the change; the watcher needn't index it again
+    let temp = embedded_xmp::temp_path(path).to_string_lossy().to_string();
+    echo::expect_changes([pending.path.clone(), temp]);
+    embedded_xmp::write(path, metadata)?;
+    let stats = std::fs::metadata(path).map_err(|e| e.to_string())?;
+    let modified = stats.modified().map_err(|e| e.to_string())?;
+    Ok(Written::File(stats.len() as i64, modified.into()))
+}
+
+/// Writes a batch of changed ratings, notes and tags. Returns the number of
+/// files handled.
+async fn write_batch(db: &Db, embed: bool) -> usize {
+    let images = match db.get_pending_sidecar_writes(BATCH_SIZE).await {
+        Ok(images) => images,
+        Err(e) => {
+            eprintln!("WARN: Could not list metadata to write: {}", e);
+            return 0;
+        }
+    };
+    if images.is_empty() {
+        return 0;
+    }
+    let ids: Vec<i64> = images.iter().map(|pending| pending.image_id).collect();
+    let mut tag_paths = match db.get_tag_paths(&ids).await {
+        Ok(tag_paths) => tag_paths,
+        Err(e) => {
+            eprintln!("WARN: Could not read the tags to write: {}", e);
+            return 0;
+        }
+    };
+    let jobs: Vec<(PendingWrite, SidecarMetadata)> = images
+        .into_iter()
+        .map(|pending| {
+            let tags = tag_paths.remove(&pending.image_id).unwrap_or_default();
+            let metadata = SidecarMetadata::from_library(pending.rating, pending.notes.clone(), tags);
+            (pending, metadata)
+        })
+        .collect();
+    let written = tauri::async_runtime::spawn_blocking(move || {
+        jobs.into_iter()
+            .map(|(pending, metadata)| match write_pending(&pending, &metadata, embed) {
+                Ok(written) => (pending.image_id, written),
+                Err(e) => {
+                    eprintln!("WARN: Could not write the metadata of {}: {}", pending.path, e);
+                    (pending.image_id, Written::Failed)
+                }
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+
+    for (id, written) in &written {
+        if let Written::File(size, modified_at) = written {
+            if let Err(e) = db.set_rewritten_file(*id, *size, *modified_at).await {
+                eprintln!("WARN: Could not record the rewritten file of image {}: {}", id, e);
+            }
+        }
+    }
+    let stale: Vec<i64> =
+        written.iter().filter(|(_, written)| matches!(written, Written::Stale)).map(|(id, _)| *id).collect();
+    if !stale.is_empty() {
+        println!("INFO: {} sidecars changed since they were read; reading them again before writing", stale.len());
+        if let Err(e) = db.recheck_sidecars(&stale).await {
+            eprintln!("WARN: Could not queue changed sidecars to be read: {}", e);
+        }
+    }
+    let marked: Vec<(i64, Option<i64>)> = written
+        .iter()
+        .filter(|(_, written)| !matches!(written, Written::Stale))
+        .map(|(id, written)| (*id, if let Written::Sidecar(modified) = written { Some(*modified) } else { None }))
+        .collect();
+    if let Err(e) = db.mark_sidecars_written(&marked).await {
+        eprintln!("WARN: Could not record the written metadata: {}", e);
+        return 0;
+    }
+    written.len()
+}
+
+async fn is_enabled(db: &Db, key: &str) -> bool {
+    matches!(db.get_setting(key).await, Ok(Some(value)) if value.as_bool() == Some(true))
+}
+
+/// Writes changes without waiting for the next pass.
+pub fn wake() {
+    WAKE.notify_one();
+}
+
+/// Starts the background task reading sidecars, and writing changes back
+/// when the settings ask for it.
+pub fn start(db: Arc<Db>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let mut handled = read_batch(&db).await;
+            let embed = is_enabled(&db, embedded_xmp::SETTING_KEY).await;
+            // A read-only library leaves the files alone too
+            let writing = embed || is_enabled(&db, WRITE_SETTING_KEY).await;
+            if writing && !crate::library::read_only::is_enabled() {
+                handled += write_batch(&db, embed).await;
+            }
+            if handled > 0 {
+                sleep(Duration::from_millis(50)).await;
+            } else {
+                tokio::select! {
+                    _ = sleep(IDLE_INTERVAL) => {}
+                    _ = WAKE.notified() => {}
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIGHTROOM: &str = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/" x:xmptk="Adobe XMP Core 7.0">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:crs="http://ns.adobe.com/camera-raw-settings/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:lr="http://ns.adobe.com/lightroom/1.0/"
+   xmp:Rating="3"
+   xmp:Label="Red"
+   crs:Exposure2012="+0.50">
+   <dc:subject>
+    <rdf:Bag>
+     <rdf:li>Paris</rdf:li>
+     <rdf:li>Places</rdf:li>
+     <rdf:li>night</rdf:li>
+    </rdf:Bag>
+   </dc:subject>
+   <lr:hierarchicalSubject>
+    <rdf:Bag>
+     <rdf:li>Places|Paris</rdf:li>
+    </rdf:Bag>
+   </lr:hierarchicalSubject>
+   <crs:ToneCurvePV2012>
+    <rdf:Seq>
+     <rdf:li>0, 0</rdf:li>
+     <rdf:li>255, 255</rdf:li>
+    </rdf:Seq>
+   </crs:ToneCurvePV2012>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#;
+
+    fn strings(paths: &[&[&str]]) -> Vec<Vec<String>> {
+        paths.iter().map(|path| path.iter().map(|name| name.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let metadata = parse(LIGHTROOM);
+        assert_eq!(metadata.rating, Some(3));
+        assert_eq!(metadata.label.as_deref(), Some("Red"));
+        assert_eq!(metadata.keywords, strings(&[&["Places", "Paris"], &["night"]]));
+        assert_eq!(metadata.tag_paths().last(), Some(&vec![LABELS_TAG.to_string(), "Red".to_string()]));
+        assert_eq!(parse(&LIGHTROOM.replace("xmp:Rating=\"3\"", "xmp:Rating=\"-1\"")).rating, None);
+    }
+
+    #[test]
+    fn test_update_keeps_foreign_properties() {
+        let metadata = SidecarMetadata::from_library(
+            5,
+            Some("Lyon, quai Saint-Antoine".to_string()),
+            strings(&[&["Labels", "Green"], &["Places", "Lyon"], &["portfolio"]]),
+        );
+        assert_eq!(metadata.label.as_deref(), Some("Green"));
+        let updated = update(Some(LIGHTROOM), &metadata).unwrap();
+        assert!(updated.contains(r#"crs:Exposure2012="+0.50""#));
+        assert!(updated.contains("<rdf:li>255, 255</rdf:li>"));
+        assert!(updated.starts_with("<?xpacket"));
+        assert!(!updated.contains("night"));
+        assert_eq!(updated.matches("xmlns:xmp=").count(), 1);
+        assert_eq!(parse(&updated), metadata);
+    }
+
+    #[test]
+    fn test_update_creates_a_sidecar() {
+        let metadata =
+            SidecarMetadata { rating: Some(2), label: None, description: None, keywords: strings(&[&["a & b"]]) };
+        let created = update(None, &metadata).unwrap();
+        assert_eq!(parse(&created), metadata);
+        assert!(update(Some("<html><body/></html>"), &metadata).is_err());
+    }
+
+    #[test]
+    fn test_candidates() {
+        let names = |path: &str| -> Vec<String> {
+            candidates(Path::new(path)).iter().map(|c| c.file_name().unwrap().to_string_lossy().to_string()).collect()
+        };
+        assert_eq!(names("/shoot/IMG_1.CR3"), ["IMG_1.xmp", "IMG_1.XMP", "IMG_1.CR3.xmp", "IMG_1.CR3.XMP"]);
+        assert_eq!(names("/shoot/IMG_1.jpg"), ["IMG_1.jpg.xmp", "IMG_1.jpg.XMP", "IMG_1.xmp", "IMG_1.XMP"]);
+        assert_eq!(write_target(Path::new("/missing/IMG_1.CR3")), Some(PathBuf::from("/missing/IMG_1.xmp")));
+    }
+}
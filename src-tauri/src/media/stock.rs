@@ -0,0 +1,163 @@
+//! Stock asset detection.
+//!
+//! Files bought or downloaded from stock sites are recognised by the
+//! provider's download name (`AdobeStock_123.jpeg`, `shutterstock_123.jpg`,
+//! `john-doe-AbCdEfGhIjK-unsplash.jpg`...) or by the credit, supplier and
+//! rights fields of their embedded XMP (which IPTC-aware tools mirror from
+//! the IPTC block). The license fields are kept so that assets lacking them
+//! can be found by the `stock` search key.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::db::models::StockInfo;
+use crate::media::xmp;
+
+/// Download names of each provider; the first group is the asset id.
+const NAME_PATTERNS: &[(&str, &str)] = &[
+    ("Adobe Stock", r"(?i)^AdobeStock_(\d+)"),
+    ("Shutterstock", r"(?i)^shutterstock_(\d+)"),
+    ("iStock", r"(?i)^iStock-(\d+)"),
+    ("Getty Images", r"(?i)^GettyImages-(\d+)"),
+    ("Unsplash", r"(?i)-([A-Za-z0-9_-]{11})-unsplash\.[a-z0-9]+$"),
+    ("Pexels", r"(?i)^pexels-.*?(\d+)\.[a-z0-9]+$"),
+    ("Depositphotos", r"(?i)^depositphotos_(\d+)"),
+    ("Dreamstime", r"(?i)^dreamstime_[a-z]+_(\d+)"),
+];
+
+/// Lowercase markers of each provider in credit, supplier or rights fields.
+/// iStock comes before Getty, whose name appears in iStock credits.
+const FIELD_MARKERS: &[(&str, &[&str])] = &[
+    ("Adobe Stock", &["adobe stock", "stock.adobe.com"]),
+    ("Shutterstock", &["shutterstock"]),
+    ("iStock", &["istock"]),
+    ("Getty Images", &["getty images", "gettyimages"]),
+    ("Unsplash", &["unsplash"]),
+    ("Pexels", &["pexels"]),
+    ("Depositphotos", &["depositphotos"]),
+    ("Dreamstime", &["dreamstime"]),
+    ("Alamy", &["alamy"]),
+];
+
+/// XMP fields searched for provider markers.
+const PROVIDER_FIELDS: &[&str] = &[
+    "plus:ImageSupplierName",
+    "photoshop:Credit",
+    "photoshop:Source",
+    "plus:LicensorName",
+    "plus:LicensorURL",
+    "xmpRights:WebStatement",
+    "dc:rights",
+];
+
+/// XMP fields holding the provider's asset id.
+const ASSET_ID_FIELDS: &[&str] = &["plus:ImageSupplierImageID", "GettyImagesGIFT:AssetID"];
+
+fn name_patterns() -> &'static [(&'static str, Regex)] {
+    static PATTERNS: OnceLock<Vec<(&'static str, Regex)>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        NAME_PATTERNS
+            .iter()
+            .map(|(provider, pattern)| (*provider, Regex::new(pattern).expect("valid stock name pattern")))
+            .collect()
+    })
+}
+
+/// Provider and asset id from a stock site's download name.
+pub fn detect_from_name(file_name: &str) -> Option<(&'static str, String)> {
+    name_patterns()
+        .iter()
+        .find_map(|(provider, regex)| regex.captures(file_name).map(|c| (*provider, c[1].to_string())))
+}
+
+fn detect_from_fields(fields: &HashMap<String, String>) -> Option<&'static str> {
+    // Getty's own schema is unambiguous
+    if fields.contains_key("GettyImagesGIFT:AssetID") && !fields.values().any(|v| v.to_lowercase().contains("istock")) {
+        return Some("Getty Images");
+    }
+    let values: Vec<String> = PROVIDER_FIELDS
+        .iter()
+        .filter_map(|key| fields.get(*key))
+        .map(|v| v.to_lowercase())
+        .collect();
+    FIELD_MARKERS
+        .iter()
+        .find(|(_, markers)| values.iter().any(|v| markers.iter().any(|m| v.contains(m))))
+        .map(|(provider, _)| *provider)
+}
+
+/// Stock details of a file from its name and flattened XMP fields, or `None`
+/// if nothing points to a stock provider.
+pub fn detect(file_name: &str, fields: &HashMap<String, String>) -> Option<StockInfo> {
+    let from_name = detect_from_name(file_name);
+    let provider = from_name.as_ref().map(|(p, _)| *p).or_else(|| detect_from_fields(fields))?;
+    let field = |key: &str| fields.get(key).cloned();
+
+    Some(StockInfo {
+        provider: provider.to_string(),
+        asset_id: from_name.map(|(_, id)| id).or_else(|| ASSET_ID_FIELDS.iter().find_map(|key| field(*key))),
+        usage_terms: field("xmpRights:UsageTerms"),
+        license_url: field("xmpRights:WebStatement").or_else(|| field("plus:LicensorURL")),
+        credit: field("photoshop:Credit"),
+        copyright: field("dc:rights"),
+    })
+}
+
+/// Reads the stock details of the file at `path`. Blocking.
+pub fn read_stock_info(path: &Path) -> Option<StockInfo> {
+    let file_name = path.file_name()?.to_string_lossy().to_string();
+    let fields = xmp::read_packet(path).map(|packet| xmp::parse_fields(&packet)).unwrap_or_default();
+    detect(&file_name, &fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_detect_from_name() {
+        assert_eq!(detect_from_name("AdobeStock_123456789.jpeg"), Some(("Adobe Stock", "123456789".to_string())));
+        assert_eq!(detect_from_name("iStock-987654.jpg"), Some(("iStock", "987654".to_string())));
+        assert_eq!(
+            detect_from_name("jane-doe-AbC_dEf-1Jk-unsplash.jpg"),
+            Some(("Unsplash", "AbC_dEf-1Jk".to_string()))
+        );
+        assert_eq!(detect_from_name("pexels-photo-2014422.jpeg"), Some(("Pexels", "2014422".to_string())));
+        assert_eq!(detect_from_name("holiday_123.jpg"), None);
+    }
+
+    #[test]
+    fn test_detect_from_xmp() {
+        let info = detect(
+            "IMG_1.jpg",
+            &fields(&[
+                ("photoshop:Credit", "Getty Images/iStockphoto"),
+                ("plus:ImageSupplierImageID", "1234"),
+                ("xmpRights:WebStatement", "https://www.istockphoto.com/legal/license-agreement"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(info.provider, "iStock");
+        assert_eq!(info.asset_id.as_deref(), Some("1234"));
+        assert!(info.license_url.is_some());
+        assert_eq!(info.usage_terms, None);
+
+        let getty = detect("a.jpg", &fields(&[("GettyImagesGIFT:AssetID", "555")])).unwrap();
+        assert_eq!((getty.provider.as_str(), getty.asset_id.as_deref()), ("Getty Images", Some("555")));
+
+        assert!(detect("a.jpg", &fields(&[("dc:rights", "© Jane Doe")])).is_none());
+    }
+
+    #[test]
+    fn test_name_id_wins_over_xmp() {
+        let info = detect("shutterstock_42.jpg", &fields(&[("plus:ImageSupplierImageID", "99")])).unwrap();
+        assert_eq!((info.provider.as_str(), info.asset_id.as_deref()), ("Shutterstock", Some("42")));
+    }
+}
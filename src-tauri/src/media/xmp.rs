@@ -0,0 +1,176 @@
+//! Minimal XMP reader.
+//!
+//! Finds the XMP packet embedded in a file and flattens its properties to
+//! `prefix:Name -> value`, keeping the first value of lists (`rdf:Alt`,
+//! `rdf:Seq`, `rdf:Bag`) and the fields of structures (`plus:Licensor` gives
+//! `plus:LicensorURL`); `parse_list` reads every value of a list. Prefixes
+//! are taken as written, which holds for the conventional prefixes every
+//! writer uses.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+
+/// Bytes searched for the packet. JPEG, PNG and most TIFF writers put it in
+/// the first few hundred kilobytes.
+const SCAN_LIMIT: u64 = 1024 * 1024;
+
+const PACKET_START: &[u8] = b"<x:xmpmeta";
+const PACKET_END: &[u8] = b"</x:xmpmeta>";
+
+/// RDF containers, whose values belong to the enclosing property.
+const RDF_CONTAINERS: &[&str] = &["rdf:RDF", "rdf:Description", "rdf:Alt", "rdf:Seq", "rdf:Bag", "rdf:li", "x:xmpmeta"];
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack.get(from..)?.windows(needle.len()).position(|w| w == needle).map(|p| p + from)
+}
+
+/// Extracts the XMP packet (`<x:xmpmeta>...</x:xmpmeta>`) from raw bytes.
+pub fn extract_packet(bytes: &[u8]) -> Option<&str> {
+    let start = find(bytes, PACKET_START, 0)?;
+    let end = find(bytes, PACKET_END, start)? + PACKET_END.len();
+    std::str::from_utf8(&bytes[start..end]).ok()
+}
+
+/// Reads the XMP packet embedded in the file at `path`.
+pub fn read_packet(path: &Path) -> Option<String> {
+    let mut bytes = Vec::new();
+    std::fs::File::open(path).ok()?.take(SCAN_LIMIT).read_to_end(&mut bytes).ok()?;
+    extract_packet(&bytes).map(String::from)
+}
+
+fn record_attributes(element: &BytesStart, fields: &mut HashMap<String, String>) {
+    for attribute in element.attributes().flatten() {
+        let key = String::from_utf8_lossy(attribute.key.as_ref()).to_string();
+        if key.starts_with("xmlns") || key.starts_with("rdf:") || key.starts_with("x:") || key.starts_with("xml:") {
+            continue;
+        }
+        let value = attribute.unescape_value().unwrap_or_default().trim().to_string();
+        if !value.is_empty() {
+            fields.entry(key).or_insert(value);
+        }
+    }
+}
+
+/// Flattens an XMP packet to `prefix:Name -> first value`.
+pub fn parse_fields(xmp: &str) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    let mut reader = Reader::from_str(xmp);
+    reader.config_mut().trim_text(true);
+    let mut path: Vec<String> = Vec::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) => {
+                record_attributes(&element, &mut fields);
+                path.push(String::from_utf8_lossy(element.name().as_ref()).to_string());
+            }
+            Ok(Event::Empty(element)) => {
+                record_attributes(&element, &mut fields);
+                // `<xmpRights:WebStatement rdf:resource="..."/>`
+                if let Some(resource) = element.try_get_attribute("rdf:resource").ok().flatten() {
+                    let name = String::from_utf8_lossy(element.name().as_ref()).to_string();
+                    let value = resource.unescape_value().unwrap_or_default().trim().to_string();
+                    if !value.is_empty() {
+                        fields.entry(name).or_insert(value);
+                    }
+                }
+            }
+            Ok(Event::End(_)) => {
+                path.pop();
+            }
+            Ok(Event::Text(text)) => {
+                let property = path.iter().rev().find(|name| !RDF_CONTAINERS.contains(&name.as_str()));
+                if let (Some(property), Ok(value)) = (property, text.unescape()) {
+                    let value = value.trim();
+                    if !value.is_empty() {
+                        fields.entry(property.clone()).or_insert_with(|| value.to_string());
+                    }
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    fields
+}
+
+/// Every value of the list property `property` (`dc:subject` gives all the
+/// keywords of its `rdf:Bag`), in order.
+pub fn parse_list(xmp: &str, property: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut reader = Reader::from_str(xmp);
+    reader.config_mut().trim_text(true);
+    // Elements open inside the property, 0 outside it
+    let mut depth = 0usize;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) => {
+                if depth > 0 || element.name().as_ref() == property.as_bytes() {
+                    depth += 1;
+                }
+            }
+            Ok(Event::End(_)) if depth > 0 => depth -= 1,
+            Ok(Event::Text(text)) if depth > 0 => {
+                if let Ok(value) = text.unescape() {
+                    let value = value.trim();
+                    if !value.is_empty() {
+                        values.push(value.to_string());
+                    }
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKET: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+      <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+        <rdf:Description rdf:about="" xmlns:photoshop="http://ns.adobe.com/photoshop/1.0/"
+            photoshop:Credit="Getty Images" xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:rights><rdf:Alt><rdf:li xml:lang="x-default">© Jane &amp; Co</rdf:li></rdf:Alt></dc:rights>
+          <plus:Licensor><rdf:Seq><rdf:li rdf:parseType="Resource">
+            <plus:LicensorURL>https://www.gettyimages.com/</plus:LicensorURL>
+          </rdf:li></rdf:Seq></plus:Licensor>
+          <xmpRights:WebStatement rdf:resource="https://example.com/license"/>
+        </rdf:Description>
+      </rdf:RDF>
+    </x:xmpmeta>"#;
+
+    #[test]
+    fn test_extract_packet() {
+        let bytes = [b"\xFF\xD8junk".as_slice(), PACKET.as_bytes(), b"\xFF\xD9"].concat();
+        assert_eq!(extract_packet(&bytes), Some(PACKET));
+        assert_eq!(extract_packet(b"<x:xmpmeta unterminated"), None);
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        let fields = parse_fields(PACKET);
+        assert_eq!(fields.get("photoshop:Credit").map(String::as_str), Some("Getty Images"));
+        assert_eq!(fields.get("dc:rights").map(String::as_str), Some("© Jane & Co"));
+        assert_eq!(fields.get("plus:LicensorURL").map(String::as_str), Some("https://www.gettyimages.com/"));
+        assert_eq!(fields.get("xmpRights:WebStatement").map(String::as_str), Some("https://example.com/license"));
+        assert!(!fields.contains_key("rdf:about"));
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let packet = r#"<x:xmpmeta><rdf:RDF><rdf:Description>
+          <dc:subject><rdf:Bag><rdf:li>beach</rdf:li><rdf:li>sunset &amp; sea</rdf:li></rdf:Bag></dc:subject>
+          <dc:title><rdf:Alt><rdf:li>Not a keyword</rdf:li></rdf:Alt></dc:title>
+        </rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        assert_eq!(parse_list(packet, "dc:subject"), vec!["beach", "sunset & sea"]);
+        assert!(parse_list(packet, "lr:hierarchicalSubject").is_empty());
+    }
+}
@@ -0,0 +1,403 @@
+//! How file paths are stored in the database and turned back into paths.
+//!
+//! Paths are TEXT in the database, but an OS path is not always valid
+//! Unicode: Linux names are arbitrary bytes (a Latin-1 `caf\xe9.jpg` copied
+//! from an old disk), Windows names can hold unpaired UTF-16 surrogates.
+//! `to_string_lossy` turned those into `U+FFFD`, so the file could neither be
+//! opened again nor told apart from its neighbours. [`to_db`] keeps them by
+//! writing each byte (or surrogate) that isn't Unicode as a private-use
+//! character, and [`from_db`] turns the text back into the exact OS path.
+//! Valid names are stored unchanged, except for names already holding those
+//! private-use characters, which are escaped too so decoding stays
+//! unambiguous.
+//!
+//! On Windows, paths are stored without the `\\?\` prefix `canonicalize`
+//! adds, so a location added by hand and the same location scanned are one
+//! folder. The prefix is put back when a path is opened if Win32 would
+//! otherwise mangle it: past `MAX_PATH`, or with a component Win32 treats
+//! specially (`CON`, `aux.jpg`, a trailing dot or space).
+//!
+//! Whether names differing only by case are one file depends on the
+//! filesystem, not the OS: [`is_case_sensitive`] probes a location once and
+//! the result is kept on its folders.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+/// Longest path, in UTF-16 units, Win32 opens without the `\\?\` prefix.
+const MAX_PATH: usize = 259;
+
+/// Device names Win32 resolves in every folder, with or without extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2",
+    "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+#[cfg(unix)]
+mod os {
+    //! Bytes 0x80-0xFF that aren't part of a UTF-8 sequence are written as
+    //! U+F780-U+F7FF.
+
+    use std::ffi::{OsStr, OsString};
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+    const BASE: u32 = 0xF700;
+
+    pub fn is_escape(c: char) -> bool {
+        (BASE + 0x80..=BASE + 0xFF).contains(&(c as u32))
+    }
+
+    fn escape(byte: u8) -> char {
+        char::from_u32(BASE + byte as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
+
+    pub fn encode(name: &OsStr, out: &mut String) {
+        for chunk in name.as_bytes().utf8_chunks() {
+            for c in chunk.valid().chars() {
+                if is_escape(c) {
+                    out.extend(c.to_string().bytes().map(escape));
+                } else {
+                    out.push(c);
+                }
+            }
+            out.extend(chunk.invalid().iter().map(|b| escape(*b)));
+        }
+    }
+
+    pub fn decode(text: &str) -> OsString {
+        let mut bytes = Vec::with_capacity(text.len());
+        for c in text.chars() {
+            if is_escape(c) {
+                bytes.push((c as u32 - BASE) as u8);
+            } else {
+                bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+        }
+        OsString::from_vec(bytes)
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    //! Unpaired surrogates U+D800-U+DFFF are written as U+10F800-U+10FFFF.
+
+    use std::ffi::{OsStr, OsString};
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+
+    const BASE: u32 = 0x10F800 - 0xD800;
+
+    pub fn is_escape(c: char) -> bool {
+        c as u32 >= BASE + 0xD800
+    }
+
+    fn escape(unit: u16) -> char {
+        char::from_u32(BASE + unit as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
+
+    pub fn encode(name: &OsStr, out: &mut String) {
+        for c in char::decode_utf16(name.encode_wide()) {
+            match c {
+                Ok(c) if is_escape(c) => out.extend(c.encode_utf16(&mut [0; 2]).iter().map(|u| escape(*u))),
+                Ok(c) => out.push(c),
+                Err(e) => out.push(escape(e.unpaired_surrogate())),
+            }
+        }
+    }
+
+    pub fn decode(text: &str) -> OsString {
+        let mut units = Vec::with_capacity(text.len());
+        for c in text.chars() {
+            if is_escape(c) {
+                units.push((c as u32 - BASE) as u16);
+            } else {
+                units.extend_from_slice(c.encode_utf16(&mut [0; 2]));
+            }
+        }
+        OsString::from_wide(&units)
+    }
+}
+
+/// Text of a file or folder name, as stored in the database.
+pub fn name_to_db(name: &OsStr) -> String {
+    match name.to_str() {
+        Some(text) if !text.chars().any(os::is_escape) => text.to_string(),
+        _ => {
+            let mut out = String::with_capacity(name.len());
+            os::encode(name, &mut out);
+            out
+        }
+    }
+}
+
+/// Text of a path, as stored in the database.
+pub fn to_db(path: &Path) -> String {
+    let text = name_to_db(path.as_os_str());
+    if cfg!(windows) {
+        strip_verbatim(&text)
+    } else {
+        text
+    }
+}
+
+/// The path a database path stands for, ready to be opened.
+pub fn from_db(text: &str) -> PathBuf {
+    if cfg!(windows) && needs_verbatim(text) {
+        PathBuf::from(with_verbatim(text))
+    } else {
+        PathBuf::from(decode(text))
+    }
+}
+
+fn decode(text: &str) -> OsString {
+    if text.chars().any(os::is_escape) {
+        os::decode(text)
+    } else {
+        OsString::from(text)
+    }
+}
+
+/// `C:\a` for `\\?\C:\a`, `\\server\share` for `\\?\UNC\server\share`.
+fn strip_verbatim(text: &str) -> String {
+    if let Some(rest) = text.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", rest)
+    } else if let Some(rest) = text.strip_prefix(r"\\?\").filter(|rest| is_drive_path(rest)) {
+        rest.to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+fn with_verbatim(text: &str) -> OsString {
+    let text = text.replace('/', r"\");
+    match text.strip_prefix(r"\\") {
+        Some(rest) => decode(&format!(r"\\?\UNC\{}", rest)),
+        None => decode(&format!(r"\\?\{}", text)),
+    }
+}
+
+fn is_drive_path(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && matches!(bytes[2], b'\\' | b'/')
+}
+
+/// Whether Win32 would only open `text`, an absolute Windows path, under
+/// the `\\?\` prefix. Paths with `.` or `..` can't take it, as the prefix
+/// turns off their resolution.
+fn needs_verbatim(text: &str) -> bool {
+    if !(is_drive_path(text) || text.starts_with(r"\\")) || text.starts_with(r"\\?\") {
+        return false;
+    }
+    let components: Vec<&str> = text.split(['\\', '/']).filter(|c| !c.is_empty()).collect();
+    if components.iter().any(|c| *c == "." || *c == "..") {
+        return false;
+    }
+    text.encode_utf16().count() > MAX_PATH || components.iter().skip(1).any(|c| !is_portable_name(c))
+}
+
+/// Whether Win32 treats `name` as a device (`CON`, `nul.txt`, `COM1 .jpg`).
+pub fn is_reserved_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or_default().trim_end_matches(' ');
+    RESERVED_NAMES.iter().any(|reserved| stem.eq_ignore_ascii_case(reserved))
+}
+
+/// Whether `name` can be created and opened under its own name everywhere:
+/// not a Windows device name and not ending in a dot or a space, which
+/// Win32 silently drops.
+pub fn is_portable_name(name: &str) -> bool {
+    !is_reserved_name(name) && !name.ends_with(['.', ' '])
+}
+
+/// `name` changed just enough to be portable: trailing dots and spaces
+/// dropped, device names suffixed with `_`.
+pub fn portable_name(name: &str) -> String {
+    let mut name = name.trim_end_matches(['.', ' ']).to_string();
+    if is_reserved_name(&name) {
+        match name.find('.') {
+            Some(dot) => name.insert(dot, '_'),
+            None => name.push('_'),
+        }
+    }
+    name
+}
+
+/// Whether the filesystem holding `dir` tells names apart by case, so that
+/// `a.jpg` and `A.jpg` are two files.
+///
+/// Looks for an entry of `dir`, or `dir` itself, whose name changes with
+/// its case and checks whether the other spelling opens it too. Without one,
+/// assumes the default filesystem of the platform.
+pub fn is_case_sensitive(dir: &Path) -> bool {
+    let entries = std::fs::read_dir(dir).into_iter().flatten().filter_map(|e| e.ok()).map(|e| e.path());
+    entries
+        .take(64)
+        .chain(std::iter::once(dir.to_path_buf()))
+        .find_map(|path| probe_case(&path))
+        .unwrap_or_else(default_case_sensitive)
+}
+
+/// Whether the default filesystem of the platform tells names apart by
+/// case: not APFS and NTFS.
+pub fn default_case_sensitive() -> bool {
+    !cfg!(any(target_os = "macos", windows))
+}
+
+fn probe_case(path: &Path) -> Option<bool> {
+    let name = path.file_name()?.to_str()?;
+    let swapped: String = name
+        .chars()
+        .flat_map(|c| if c.is_lowercase() { c.to_uppercase().collect::<Vec<_>>() } else { c.to_lowercase().collect() })
+        .collect();
+    if swapped == name {
+        return None;
+    }
+    let other = path.with_file_name(&swapped);
+    // The other spelling is either missing, a file of its own, or this one
+    Some(std::fs::symlink_metadata(&other).is_err() || exists_with_case(&other))
+}
+
+/// Whether `path` exists under exactly this name, which `Path::exists`
+/// can't tell on a case-insensitive filesystem.
+pub fn exists_with_case(path: &Path) -> bool {
+    FolderListings::default().exists_with_case(path)
+}
+
+/// `path` with its name spelled as on disk: `Photo.JPG` for `photo.jpg` when
+/// the file was renamed on a case-insensitive filesystem. `None` when no
+/// entry of its folder matches, even ignoring case.
+pub fn on_disk_path(path: &Path) -> Option<PathBuf> {
+    FolderListings::default().on_disk_path(path)
+}
+
+/// Names in folders, each folder read once: [`exists_with_case`] and
+/// [`on_disk_path`] for the many paths of a batch, which would otherwise
+/// read the same folder again for every path. Kept for one batch only, as
+/// the listings go stale.
+#[derive(Default)]
+pub struct FolderListings {
+    folders: HashMap<PathBuf, Option<Listing>>,
+}
+
+struct Listing {
+    names: HashSet<OsString>,
+    /// The first name found for each case-folded spelling.
+    folded: HashMap<String, OsString>,
+}
+
+impl FolderListings {
+    fn listing(&mut self, dir: &Path) -> Option<&Listing> {
+        self.folders
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                let mut listing = Listing { names: HashSet::new(), folded: HashMap::new() };
+                for name in std::fs::read_dir(dir).ok()?.filter_map(|e| e.ok()).map(|e| e.file_name()) {
+                    listing.folded.entry(name_to_db(&name).to_lowercase()).or_insert_with(|| name.clone());
+                    listing.names.insert(name);
+                }
+                Some(listing)
+            })
+            .as_ref()
+    }
+
+    /// See [`exists_with_case`].
+    pub fn exists_with_case(&mut self, path: &Path) -> bool {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return false;
+        };
+        self.listing(parent).is_some_and(|listing| listing.names.contains(name))
+    }
+
+    /// See [`on_disk_path`].
+    pub fn on_disk_path(&mut self, path: &Path) -> Option<PathBuf> {
+        let (parent, name) = (path.parent()?, path.file_name()?);
+        let listing = self.listing(parent)?;
+        if listing.names.contains(name) {
+            return Some(path.to_path_buf());
+        }
+        listing.folded.get(&name_to_db(name).to_lowercase()).map(|actual| parent.join(actual))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips_unicode_paths() {
+        for text in ["/photos/Äpfel/img 01.jpg", "/photos/\u{f780}\u{f7ff}.jpg", "relative/日本.png"] {
+            let stored = to_db(Path::new(text));
+            assert_eq!(from_db(&stored), PathBuf::from(text));
+        }
+        assert_eq!(to_db(Path::new("/photos/Äpfel.jpg")), "/photos/Äpfel.jpg");
+        // Escaped even when valid, or it would decode to other bytes
+        assert_ne!(to_db(Path::new("/photos/\u{f780}.jpg")), "/photos/\u{f780}.jpg");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_round_trips_non_utf8_names() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let latin1 = Path::new(OsStr::from_bytes(b"/photos/caf\xe9.jpg"));
+        let other = Path::new(OsStr::from_bytes(b"/photos/caf\xe8.jpg"));
+        let stored = to_db(latin1);
+        assert_ne!(stored, to_db(other));
+        assert_eq!(from_db(&stored), latin1);
+        assert_eq!(name_to_db(latin1.file_name().unwrap()), Path::new(&stored).file_name().unwrap().to_str().unwrap());
+    }
+
+    #[test]
+    fn test_verbatim_prefix() {
+        assert_eq!(strip_verbatim(r"\\?\C:\Photos\a.jpg"), r"C:\Photos\a.jpg");
+        assert_eq!(strip_verbatim(r"\\?\UNC\nas\share\a.jpg"), r"\\nas\share\a.jpg");
+        assert_eq!(strip_verbatim(r"\\?\Volume{1234}\a.jpg"), r"\\?\Volume{1234}\a.jpg");
+
+        assert!(!needs_verbatim(r"C:\Photos\a.jpg"));
+        assert!(needs_verbatim(r"C:\Photos\CON\a.jpg"));
+        assert!(needs_verbatim(r"C:\Photos\trailing. \a.jpg"));
+        assert!(needs_verbatim(&format!(r"C:\{}\a.jpg", "x".repeat(300))));
+        assert!(!needs_verbatim(&format!(r"C:\{}\..\a.jpg", "x".repeat(300))));
+        assert!(!needs_verbatim("/photos/CON"));
+        assert_eq!(with_verbatim(r"\\nas\share\aux.jpg"), OsString::from(r"\\?\UNC\nas\share\aux.jpg"));
+        assert_eq!(with_verbatim("C:/Photos/nul"), OsString::from(r"\\?\C:\Photos\nul"));
+    }
+
+    #[test]
+    fn test_portable_names() {
+        assert!(is_reserved_name("CON"));
+        assert!(is_reserved_name("aux.jpg"));
+        assert!(is_reserved_name("com1 .tar.gz"));
+        assert!(!is_reserved_name("CONSOLE.jpg"));
+        assert!(!is_portable_name("draft."));
+        assert!(!is_portable_name("draft "));
+        assert_eq!(portable_name("nul.jpg"), "nul_.jpg");
+        assert_eq!(portable_name("LPT1"), "LPT1_");
+        assert_eq!(portable_name("draft. ."), "draft");
+        assert_eq!(portable_name("IMG_0001.jpg"), "IMG_0001.jpg");
+    }
+
+    #[test]
+    fn test_case_lookups() {
+        let dir = std::env::temp_dir().join(format!("mundam-paths-case-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Photo.JPG"), b"x").unwrap();
+
+        let sensitive = is_case_sensitive(&dir);
+        assert_eq!(sensitive, !dir.join("PHOTO.jpg").exists());
+        assert!(exists_with_case(&dir.join("Photo.JPG")));
+        assert!(!exists_with_case(&dir.join("photo.jpg")));
+        assert_eq!(on_disk_path(&dir.join("photo.jpg")), Some(dir.join("Photo.JPG")));
+        assert_eq!(on_disk_path(&dir.join("Photo.JPG")), Some(dir.join("Photo.JPG")));
+        assert_eq!(on_disk_path(&dir.join("other.jpg")), None);
+
+        // One listing answers every path of the folder, as of when it was read
+        let mut listings = FolderListings::default();
+        assert!(listings.exists_with_case(&dir.join("Photo.JPG")));
+        std::fs::write(dir.join("late.jpg"), b"x").unwrap();
+        assert!(!listings.exists_with_case(&dir.join("late.jpg")));
+        assert_eq!(listings.on_disk_path(&dir.join("PHOTO.jpg")), Some(dir.join("Photo.JPG")));
+        assert!(FolderListings::default().exists_with_case(&dir.join("late.jpg")));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
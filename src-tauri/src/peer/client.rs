@@ -0,0 +1,179 @@
+//! Client side of peer sharing: asking the configured peer for thumbnails
+//! and transcodes before generating them.
+//!
+//! Every failure falls back to generating locally. A peer that can't be
+//! reached isn't asked again for a minute, so an offline machine doesn't
+//! slow down every batch.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri_plugin_http::reqwest::{Client, StatusCode, Url};
+
+use super::{load_settings, SECRET_HEADER};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::thumbnails::{thumbnail_filename, thumbnail_key, GRID_THUMBNAIL_SIZE};
+use crate::transcoding::cache::TranscodeCache;
+use crate::transcoding::quality::TranscodeQuality;
+
+const RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Thumbnails downloaded from the peer at once.
+const FETCH_CONCURRENCY: usize = 4;
+
+static UNREACHABLE_UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Outcome of a connection test.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerStatus {
+    /// The peer answered and accepted the secret.
+    pub reachable: bool,
+    /// "Connected", or why the peer can't be used.
+    pub message: String,
+}
+
+pub struct PeerClient {
+    base: Url,
+    secret: String,
+    client: Client,
+}
+
+impl PeerClient {
+    /// Client for the configured peer, `None` if there is none or it was
+    /// unreachable a moment ago.
+    pub async fn from_settings(db: &Db) -> Option<Self> {
+        if UNREACHABLE_UNTIL.lock().unwrap_or_else(|e| e.into_inner()).is_some_and(|until| Instant::now() < until) {
+            return None;
+        }
+        let settings = load_settings(db).await;
+        let url = settings.peer_url.filter(|url| !url.trim().is_empty())?;
+        match Self::new(&url, settings.peer_secret.unwrap_or_default()) {
+            Ok(client) => Some(client),
+            Err(e) => {
+                eprintln!("WARN: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn new(url: &str, secret: String) -> AppResult<Self> {
+        let base = Url::parse(&format!("{}/", url.trim().trim_end_matches('/')))
+            .map_err(|e| AppError::Remote(format!("Invalid peer URL '{}': {}", url, e)))?;
+        let client = Client::builder()
+            .connect_timeout(Duration::from_secs(2))
+            .build()
+            .map_err(|e| AppError::Remote(e.to_string()))?;
+        Ok(Self { base, secret, client })
+    }
+
+    async fn get(&self, route: &str) -> AppResult<tauri_plugin_http::reqwest::Response> {
+        let url = self.base.join(route).map_err(|e| AppError::Remote(e.to_string()))?;
+        self.client.get(url).header(SECRET_HEADER, &self.secret).send().await.map_err(|e| {
+            if e.is_connect() || e.is_timeout() {
+                *UNREACHABLE_UNTIL.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now() + RETRY_AFTER);
+            }
+            AppError::Remote(e.to_string())
+        })
+    }
+
+    /// Downloads `route` to `target`. `Ok(false)` if the peer doesn't have it.
+    async fn fetch(&self, route: &str, target: &Path) -> AppResult<bool> {
+        let response = self.get(route).await?;
+        match response.status() {
+            StatusCode::OK => {
+                crate::storage::write_response(response, target).await?;
+                Ok(true)
+            }
+            StatusCode::NOT_FOUND => Ok(false),
+            status => Err(AppError::Remote(format!("Peer returned {} for {}", status, route))),
+        }
+    }
+
+    /// Downloads the thumbnail of the file with `key` and `modified` time.
+    pub async fn fetch_thumbnail(&self, key: &str, modified: u64, target: &Path) -> AppResult<bool> {
+        self.fetch(&format!("peer/v1/thumbnail/{}?modified={}", key, modified), target).await
+    }
+
+    /// Downloads the transcode of the file with `key` and `modified` time.
+    pub async fn fetch_transcode(&self, key: &str, modified: u64, quality: TranscodeQuality, target: &Path) -> AppResult<bool> {
+        let quality = format!("{:?}", quality).to_lowercase();
+        self.fetch(&format!("peer/v1/transcode/{}?quality={}&modified={}", key, quality, modified), target).await
+    }
+
+    pub async fn status(&self) -> PeerStatus {
+        let (reachable, message) = match self.get("peer/v1/health").await {
+            Ok(response) if response.status().is_success() => (true, "Connected".to_string()),
+            Ok(response) if response.status() == StatusCode::UNAUTHORIZED => (false, "The peer refused the secret".to_string()),
+            Ok(response) => (false, format!("The peer returned {}", response.status())),
+            Err(e) => (false, e.to_string()),
+        };
+        PeerStatus { reachable, message }
+    }
+}
+
+/// Runs a key computation off the async runtime, `None` if it fails.
+async fn key_of<T: Send + 'static>(task: impl FnOnce() -> std::io::Result<T> + Send + 'static) -> Option<T> {
+    tokio::task::spawn_blocking(move || task().ok()).await.ok().flatten()
+}
+
+/// Fetches from the peer the thumbnails it has for `images` (`(id, path)`).
+/// Returns the images served, with their thumbnail filename.
+pub async fn fetch_thumbnails(db: &Db, thumbnails_dir: &Path, images: &[(i64, String)]) -> Vec<(i64, String)> {
+    let Some(peer) = PeerClient::from_settings(db).await else {
+        return Vec::new();
+    };
+    let peer = Arc::new(peer);
+    let mut fetched = Vec::new();
+    for chunk in images.chunks(FETCH_CONCURRENCY) {
+        let mut tasks = tokio::task::JoinSet::new();
+        for (id, path) in chunk.iter().cloned() {
+            let peer = peer.clone();
+            let thumbnails_dir = thumbnails_dir.to_path_buf();
+            tasks.spawn(async move {
+                let source = crate::paths::from_db(&path);
+                let (key, modified) = key_of(move || thumbnail_key(&source)).await?;
+                let filename = thumbnail_filename(&key, modified, GRID_THUMBNAIL_SIZE);
+                let result = peer.fetch_thumbnail(&key, modified, &thumbnails_dir.join(&filename)).await;
+                Some((id, filename, result))
+            });
+        }
+
+        let mut failed = None;
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok(Some((id, filename, Ok(true)))) => fetched.push((id, filename)),
+                Ok(Some((_, _, Err(e)))) => failed = Some(e),
+                _ => {}
+            }
+        }
+        // Unreachable or misconfigured: the rest of the batch would fail the same way
+        if let Some(e) = failed {
+            eprintln!("WARN: Peer thumbnail fetch failed: {}", e);
+            break;
+        }
+    }
+    if !fetched.is_empty() {
+        println!("INFO: Got {} thumbnails from the peer", fetched.len());
+    }
+    fetched
+}
+
+/// Fills the transcode cache from the peer if it has already transcoded
+/// `source`, so the transcoder finds it there.
+pub async fn fetch_transcode(db: &Db, cache: &TranscodeCache, source: &Path, quality: TranscodeQuality) {
+    if cache.get(source, quality).is_some() {
+        return;
+    }
+    let Some(peer) = PeerClient::from_settings(db).await else { return };
+    let path = source.to_path_buf();
+    let Some((key, modified)) = key_of(move || thumbnail_key(&path)).await else { return };
+    match peer.fetch_transcode(&key, modified, quality, &cache.get_cache_path(source, quality)).await {
+        Ok(true) => println!("INFO: Got the transcode of {:?} from the peer", source.file_name().unwrap_or_default()),
+        Ok(false) => {}
+        Err(e) => eprintln!("WARN: Peer transcode fetch failed: {}", e),
+    }
+}
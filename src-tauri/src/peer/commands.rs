@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use tauri::{AppHandle, Manager, State};
+
+use super::client::{PeerClient, PeerStatus};
+use super::{load_settings, PeerSettings, SETTING_KEY};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+
+/// Peer settings of the library, defaults if they were never saved.
+#[tauri::command]
+pub async fn get_peer_settings(db: State<'_, Arc<Db>>) -> AppResult<PeerSettings> {
+    Ok(load_settings(&db).await)
+}
+
+/// Saves the peer settings and restarts the server to match. A secret is
+/// generated the first time serving is turned on. Refused while the library
+/// is read-only (see `library::read_only`), as the settings live in it.
+#[tauri::command]
+pub async fn set_peer_settings(
+    app: AppHandle,
+    db: State<'_, Arc<Db>>,
+    mut settings: PeerSettings,
+) -> AppResult<PeerSettings> {
+    if let Some(url) = settings.peer_url.as_deref().filter(|url| !url.trim().is_empty()) {
+        PeerClient::new(url, String::new())?;
+    }
+    if settings.serve && settings.secret.is_empty() {
+        settings.secret = uuid::Uuid::new_v4().simple().to_string();
+    }
+    let value = serde_json::to_value(&settings).map_err(|e| AppError::Internal(e.to_string()))?;
+    db.set_setting(SETTING_KEY, &value).await?;
+
+    let app_data = app.path().app_local_data_dir()?;
+    super::server::apply(db.inner().clone(), &app_data).await;
+    Ok(settings)
+}
+
+/// Checks that the configured peer answers and accepts the secret.
+#[tauri::command]
+pub async fn test_peer_connection(db: State<'_, Arc<Db>>) -> AppResult<PeerStatus> {
+    let settings = load_settings(&db).await;
+    let url = settings
+        .peer_url
+        .filter(|url| !url.trim().is_empty())
+        .ok_or_else(|| AppError::NotFound("No peer is configured".to_string()))?;
+    Ok(PeerClient::new(&url, settings.peer_secret.unwrap_or_default())?.status().await)
+}
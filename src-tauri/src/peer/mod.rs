@@ -0,0 +1,134 @@
+//! Thumbnail and transcode sharing between instances on a LAN.
+//!
+//! Two machines browsing the same NAS library each render every thumbnail
+//! and transcode the same videos. With peer sharing, one instance serves
+//! what it has already generated and the other asks it first, falling back
+//! to generating locally when the peer is unreachable or doesn't have it.
+//!
+//! The machines usually mount the library under different paths, so files
+//! are matched by a content key (see [`content_key`]) rather than by path.
+//! The serving instance keys the images it has thumbnails for in the
+//! background; the asking one keys a file right before asking for it. The
+//! key only samples the file, so requests also carry its modification time
+//! and the peer only answers for the same version of the file. Requests
+//! carry a shared secret, as the server listens on the network.
+
+pub mod client;
+pub mod commands;
+pub mod server;
+
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::db::Db;
+
+pub const SETTING_KEY: &str = "peer_sharing";
+
+/// Header carrying the shared secret.
+pub const SECRET_HEADER: &str = "x-mundam-peer-secret";
+
+pub const DEFAULT_PORT: u16 = 9877;
+
+/// Bytes read from each end of a file for its key.
+const KEY_SAMPLE: u64 = 64 * 1024;
+
+/// Peer sharing settings, stored under [`SETTING_KEY`]: what this instance
+/// serves, and which peer it asks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PeerSettings {
+    /// Serve this instance's thumbnails and transcodes to peers.
+    pub serve: bool,
+    /// Port the server listens on, on every interface.
+    pub port: u16,
+    /// Secret peers must present, generated when serving is turned on.
+    pub secret: String,
+    /// Instance to ask before generating, such as `http://studio-mac:9877`.
+    pub peer_url: Option<String>,
+    /// Secret of that instance, as shown in its settings.
+    pub peer_secret: Option<String>,
+}
+
+impl Default for PeerSettings {
+    fn default() -> Self {
+        Self { serve: false, port: DEFAULT_PORT, secret: String::new(), peer_url: None, peer_secret: None }
+    }
+}
+
+pub async fn load_settings(db: &Db) -> PeerSettings {
+    match db.get_setting(SETTING_KEY).await {
+        Ok(Some(value)) => serde_json::from_value(value).unwrap_or_default(),
+        _ => PeerSettings::default(),
+    }
+}
+
+/// Key identifying a file by its content: SHA-256 of its size and of its
+/// first and last 64 KB. Reading only the ends keeps it cheap over a network
+/// share, and media files with the same size and ends are the same file in
+/// practice.
+pub fn content_key(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let size = file.metadata()?.len();
+
+    let mut hasher = Sha256::new();
+    hasher.update(size.to_le_bytes());
+    let mut buffer = vec![0u8; KEY_SAMPLE.min(size) as usize];
+    file.read_exact(&mut buffer)?;
+    hasher.update(&buffer);
+    if size > KEY_SAMPLE {
+        let tail = KEY_SAMPLE.min(size - KEY_SAMPLE);
+        file.seek(SeekFrom::Start(size - tail))?;
+        buffer.truncate(tail as usize);
+        file.read_exact(&mut buffer)?;
+        hasher.update(&buffer);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Whether `key` looks like a [`content_key`], checked before any lookup.
+pub fn is_valid_key(key: &str) -> bool {
+    key.len() == 64 && key.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, data: &[u8]) -> std::path::PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_content_key() {
+        let dir = std::env::temp_dir().join(format!("mundam-peer-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        let a = content_key(&write(&dir, "a.mov", &data)).unwrap();
+        let b = content_key(&write(&dir, "b.mov", &data)).unwrap();
+        assert_eq!(a, b);
+        assert!(is_valid_key(&a));
+
+        let mut edited = data.clone();
+        *edited.last_mut().unwrap() ^= 1;
+        assert_ne!(content_key(&write(&dir, "c.mov", &edited)).unwrap(), a);
+
+        let small = content_key(&write(&dir, "d.jpg", b"tiny")).unwrap();
+        assert_ne!(small, content_key(&write(&dir, "e.jpg", b"tinY")).unwrap());
+        assert!(is_valid_key(&content_key(&write(&dir, "f.jpg", b"")).unwrap()));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_is_valid_key() {
+        assert!(is_valid_key(&"ab".repeat(32)));
+        assert!(!is_valid_key(&"AB".repeat(32)));
+        assert!(!is_valid_key("../../etc/passwd"));
+        assert!(!is_valid_key(&"ab".repeat(31)));
+    }
+}
@@ -0,0 +1,227 @@
+//! Server side of peer sharing.
+//!
+//! Routes, all requiring the shared secret:
+//! - /peer/v1/health - Checks the secret
+//! - /peer/v1/thumbnail/:key?modified= - Thumbnail of the file with that content key
+//! - /peer/v1/transcode/:key?quality=&modified= - Cached transcode of that file
+//!
+//! Only what is already generated is served; a miss is a 404 and the peer
+//! generates it itself. `modified` is the file's modification time in
+//! seconds; a file with the same key but another modification time is a
+//! different version, and is a miss too.
+
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::Response,
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+use tokio_util::io::ReaderStream;
+
+use super::{content_key, is_valid_key, load_settings, SECRET_HEADER};
+use crate::db::Db;
+use crate::transcoding::cache::TranscodeCache;
+use crate::transcoding::quality::TranscodeQuality;
+
+/// Images keyed per pass of the background keying.
+const KEYING_BATCH: i64 = 200;
+
+#[derive(Clone)]
+struct PeerState {
+    db: Arc<Db>,
+    thumbnails_dir: PathBuf,
+    transcodes: Arc<TranscodeCache>,
+    secret: Arc<str>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ThumbnailQuery {
+    modified: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TranscodeQuery {
+    quality: Option<String>,
+    modified: Option<i64>,
+}
+
+static RUNNING: Mutex<Option<tauri::async_runtime::JoinHandle<()>>> = Mutex::new(None);
+
+/// Starts, restarts or stops the server to match the saved settings.
+pub async fn apply(db: Arc<Db>, app_data: &FsPath) {
+    let settings = load_settings(&db).await;
+    if let Some(running) = RUNNING.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        running.abort();
+        println!("INFO: Peer sharing server stopped");
+    }
+    if !settings.serve || settings.secret.is_empty() {
+        return;
+    }
+
+    let state = PeerState {
+        db,
+        thumbnails_dir: app_data.join("thumbnails"),
+        transcodes: Arc::new(TranscodeCache::new(app_data)),
+        secret: Arc::from(settings.secret.as_str()),
+    };
+    let port = settings.port;
+    let handle = tauri::async_runtime::spawn(async move {
+        // Keying runs with the server so stopping one stops the other
+        tokio::select! {
+            result = serve(state.clone(), port) => {
+                if let Err(e) = result {
+                    eprintln!("ERROR: Peer sharing server failed: {}", e);
+                }
+            }
+            _ = key_images(state.db.clone()) => {}
+        }
+    });
+    *RUNNING.lock().unwrap_or_else(|e| e.into_inner()) = Some(handle);
+}
+
+async fn serve(state: PeerState, port: u16) -> std::io::Result<()> {
+    let app = Router::new()
+        .route("/peer/v1/health", get(|| async { (StatusCode::OK, "OK") }))
+        .route("/peer/v1/thumbnail/:key", get(thumbnail_handler))
+        .route("/peer/v1/transcode/:key", get(transcode_handler))
+        .layer(middleware::from_fn_with_state(state.clone(), require_secret))
+        .with_state(state);
+
+    // Peers are other machines, so unlike the streaming server this one
+    // listens on every interface
+    let addr = format!("0.0.0.0:{}", port);
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    println!("INFO: Peer sharing server started on http://{}", addr);
+    axum::serve(listener, app).await
+}
+
+/// Computes the content key of images that have a thumbnail, as they come.
+async fn key_images(db: Arc<Db>) {
+    loop {
+        let images = match db.get_images_needing_content_keys(KEYING_BATCH).await {
+            Ok(images) => images,
+            Err(e) => {
+                eprintln!("WARN: Failed to load images to key: {}", e);
+                Vec::new()
+            }
+        };
+        if images.is_empty() {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            continue;
+        }
+
+        let keyed = tokio::task::spawn_blocking(move || {
+            images
+                .into_iter()
+                .map(|(id, path, size)| (id, size, content_key(&crate::paths::from_db(&path))))
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap_or_default();
+
+        let mut failed = 0;
+        for (id, size, key) in keyed {
+            // Unreadable files get an empty key so they aren't retried until they change
+            let key = key.unwrap_or_else(|_| {
+                failed += 1;
+                String::new()
+            });
+            if let Err(e) = db.set_content_key(id, &key, size).await {
+                eprintln!("WARN: Failed to save content key of image {}: {}", id, e);
+            }
+        }
+        if failed > 0 {
+            eprintln!("WARN: Could not read {} files to key them for peers", failed);
+        }
+    }
+}
+
+async fn require_secret(State(state): State<PeerState>, req: Request, next: Next) -> Response {
+    let presented = req.headers().get(SECRET_HEADER).and_then(|v| v.to_str().ok());
+    match presented {
+        Some(secret) if crate::streaming::auth::tokens_match(secret, &state.secret) => next.run(req).await,
+        _ => status(StatusCode::UNAUTHORIZED, "Missing or invalid peer secret"),
+    }
+}
+
+fn status(code: StatusCode, message: &'static str) -> Response {
+    Response::builder().status(code).body(Body::from(message)).unwrap()
+}
+
+async fn send_file(path: &FsPath, content_type: &str) -> Response {
+    let file = match tokio::fs::File::open(path).await {
+        Ok(file) => file,
+        Err(_) => return status(StatusCode::NOT_FOUND, "Not generated"),
+    };
+    let length = file.metadata().await.map(|m| m.len()).unwrap_or(0);
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_LENGTH, length)
+        .body(Body::from_stream(ReaderStream::new(file)))
+        .unwrap()
+}
+
+/// Path and thumbnail of the image with `key` and, when the peer sent it,
+/// the `modified` time; or the response to send.
+async fn lookup(state: &PeerState, key: &str, modified: Option<i64>) -> Result<(String, Option<String>), Response> {
+    if !is_valid_key(key) {
+        return Err(status(StatusCode::BAD_REQUEST, "Invalid content key"));
+    }
+    match state.db.find_image_by_content_key(key).await {
+        Ok(Some((_, _, modified_at))) if modified.is_some_and(|m| m != modified_at.timestamp()) => {
+            Err(status(StatusCode::NOT_FOUND, "Different version"))
+        }
+        Ok(Some((path, thumbnail, _))) => Ok((path, thumbnail)),
+        Ok(None) => Err(status(StatusCode::NOT_FOUND, "Unknown content key")),
+        Err(e) => {
+            eprintln!("WARN: Peer lookup failed: {}", e);
+            Err(status(StatusCode::INTERNAL_SERVER_ERROR, "Lookup failed"))
+        }
+    }
+}
+
+async fn thumbnail_handler(
+    State(state): State<PeerState>,
+    Path(key): Path<String>,
+    Query(query): Query<ThumbnailQuery>,
+) -> Response {
+    let thumbnail = match lookup(&state, &key, query.modified).await {
+        Ok((_, thumbnail)) => thumbnail,
+        Err(response) => return response,
+    };
+    // Shared type icons are cheaper to draw than to fetch
+    match thumbnail.filter(|name| name.ends_with(".webp")) {
+        Some(name) => send_file(&state.thumbnails_dir.join(name), "image/webp").await,
+        None => status(StatusCode::NOT_FOUND, "No thumbnail"),
+    }
+}
+
+async fn transcode_handler(
+    State(state): State<PeerState>,
+    Path(key): Path<String>,
+    Query(query): Query<TranscodeQuery>,
+) -> Response {
+    let path = match lookup(&state, &key, query.modified).await {
+        Ok((path, _)) => path,
+        Err(response) => return response,
+    };
+    let quality = query.quality.and_then(|q| TranscodeQuality::from_str(&q)).unwrap_or_default();
+    let transcodes = state.transcodes.clone();
+    let cached = tokio::task::spawn_blocking(move || transcodes.get(&crate::paths::from_db(&path), quality))
+        .await
+        .ok()
+        .flatten();
+    match cached {
+        Some(file) => send_file(&file, "application/octet-stream").await,
+        None => status(StatusCode::NOT_FOUND, "Not transcoded"),
+    }
+}
@@ -3,6 +3,8 @@ use percent_encoding::percent_decode_str;
 use std::path::Path;
 use tauri::http::{header, Response, StatusCode};
 
+use crate::error::AppError;
+
 pub fn error_response(status: StatusCode, body: Vec<u8>) -> Response<Vec<u8>> {
     Response::builder()
         .status(status)
@@ -11,6 +13,19 @@ pub fn error_response(status: StatusCode, body: Vec<u8>) -> Response<Vec<u8>> {
         .unwrap_or_else(|_| Response::default())
 }
 
+/// Answers with the error's status and the same `{ code, message, context }`
+/// object commands reject with.
+pub fn app_error_response(error: &AppError) -> Response<Vec<u8>> {
+    let status = StatusCode::from_u16(error.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let body = serde_json::to_vec(error).unwrap_or_default();
+    Response::builder()
+        .status(status)
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .unwrap_or_else(|_| Response::default())
+}
+
 pub fn extract_path_part(uri: &str, scheme: &str) -> String {
     let prefix_with_host = format!("{}://localhost/", scheme);
     let prefix_simple = format!("{}://", scheme);
@@ -35,20 +50,16 @@ pub fn serve_file(path: &Path, range: Option<&header::HeaderValue>) -> Result<Re
     use std::io::{Read, Seek};
     
     if !path.exists() {
-        return Err(error_response(StatusCode::NOT_FOUND, b"File not found".to_vec()));
+        return Err(app_error_response(&AppError::NotFound(path.to_string_lossy().to_string())));
     }
 
     if path.is_dir() {
-        return Err(error_response(StatusCode::FORBIDDEN, b"Cannot serve directory".to_vec()));
+        return Err(app_error_response(&AppError::Unsupported("Cannot serve a directory".to_string())));
     }
 
-    let mut file = std::fs::File::open(path).map_err(|e| {
-        error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string().into_bytes())
-    })?;
+    let mut file = std::fs::File::open(path).map_err(|e| app_error_response(&AppError::Io(e)))?;
 
-    let metadata = file.metadata().map_err(|_| {
-        error_response(StatusCode::INTERNAL_SERVER_ERROR, Vec::new())
-    })?;
+    let metadata = file.metadata().map_err(|e| app_error_response(&AppError::Io(e)))?;
     
     let file_size = metadata.len();
     
@@ -149,8 +160,8 @@ pub fn serve_file(path: &Path, range: Option<&header::HeaderValue>) -> Result<Re
     }
 
     let mut all_data = Vec::with_capacity(file_size as usize);
-    if file.read_to_end(&mut all_data).is_err() {
-        return Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, b"Read error".to_vec()));
+    if let Err(e) = file.read_to_end(&mut all_data) {
+        return Err(app_error_response(&AppError::Io(e)));
     }
 
     Ok(builder
@@ -1,18 +1,15 @@
-use super::common::{decode_path, extract_path_part, serve_file};
+use super::common::{extract_path_part, serve_file};
+use super::scope;
 use tauri::http::{header, Response, Request};
-use std::path::PathBuf;
+use tauri::AppHandle;
 
-pub fn handler(request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+pub fn handler<R: tauri::Runtime>(app: &AppHandle<R>, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
     let uri = request.uri().to_string();
     let path_part = extract_path_part(&uri, "font");
-    let decoded_path = decode_path(&path_part);
-    let mut full_path = PathBuf::from(&decoded_path);
-
-    if !full_path.is_absolute() && cfg!(unix) {
-        if !path_part.starts_with('/') {
-            full_path = PathBuf::from("/").join(full_path);
-        }
-    }
+    let full_path = match scope::resolve(app, &path_part) {
+        Ok(path) => path,
+        Err(res) => return res,
+    };
 
     let range = request.headers().get(header::RANGE);
     match serve_file(&full_path, range) {
@@ -1,31 +1,90 @@
-use super::common::{decode_path, extract_path_part, serve_file};
+use super::common::{extract_path_part, serve_file};
+use super::scope;
 use tauri::http::{header, Response, StatusCode, Request};
-use std::path::PathBuf;
 use tauri::AppHandle;
 
+/// Longest side used when AI/EPS artwork is opened without `?maxdim=`.
+const VECTOR_RENDER_MAXDIM: u32 = 4096;
+
 pub fn handler<R: tauri::Runtime>(app: &AppHandle<R>, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
     let uri = request.uri().to_string();
-    let path_part = extract_path_part(&uri, "image");
-    let decoded_path = decode_path(&path_part);
-    let mut full_path = PathBuf::from(&decoded_path);
+    let full_part = extract_path_part(&uri, "image");
+    let (path_part, query) = match full_part.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (full_part, None),
+    };
+    let full_path = match scope::resolve(app, &path_part) {
+        Ok(path) => path,
+        Err(res) => return res,
+    };
+
+    // PAGES: `?page=2` serves one page of a multi-page TIFF or one EXR layer
+    if let Some(page) = query.as_deref().and_then(|q| parse_query_u32(q, "page")) {
+        let maxdim = query
+            .as_deref()
+            .and_then(parse_maxdim)
+            .unwrap_or(crate::thumbnails::variants::MAX_MAXDIM)
+            .min(crate::thumbnails::variants::MAX_MAXDIM);
+        match crate::thumbnails::pages::get_or_render_page(app, &full_path, page, maxdim) {
+            Ok(page_path) => {
+                let range = request.headers().get(header::RANGE);
+                return match serve_file(&page_path, range) {
+                    Ok(res) => res,
+                    Err(res) => res,
+                };
+            }
+            Err(e) => eprintln!("WARN: Failed to render page {} of {:?}: {}", page, full_path, e),
+        }
+    }
 
-    if !full_path.is_absolute() && cfg!(unix) {
-        if !path_part.starts_with('/') {
-            full_path = PathBuf::from("/").join(full_path);
+    // DISPLAY VARIANTS: `?maxdim=3000` serves a downscaled, cached decode
+    if let Some(maxdim) = query.as_deref().and_then(parse_maxdim) {
+        match crate::thumbnails::variants::get_or_create_variant(app, &full_path, maxdim) {
+            Ok(Some(variant_path)) => {
+                let range = request.headers().get(header::RANGE);
+                return match serve_file(&variant_path, range) {
+                    Ok(res) => res,
+                    Err(res) => res,
+                };
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("WARN: Failed to build {}px variant for {:?}: {}", maxdim, full_path, e),
+        }
+    }
+
+    // VECTOR DOCUMENTS: WebViews can't show the PDF stream of AI/EPS files in <img>,
+    // so the artwork is rasterized once into the variant cache
+    if crate::thumbnails::extractors::is_vector_document(&full_path) {
+        match crate::thumbnails::variants::get_or_create_variant(app, &full_path, VECTOR_RENDER_MAXDIM) {
+            Ok(Some(variant_path)) => {
+                let range = request.headers().get(header::RANGE);
+                return match serve_file(&variant_path, range) {
+                    Ok(res) => res,
+                    Err(res) => res,
+                };
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("WARN: Failed to rasterize vector preview for {:?}: {}", full_path, e),
         }
     }
 
     // NATIVE EXTRACTORS: Handle formats the browser cannot render natively (RAW, etc)
     // We pass the app handle to allow extractors to find bundled binaries (like PDFium)
-    if let Ok((preview_data, mime)) = crate::thumbnails::extractors::extract_preview(Some(app), &full_path) {
-        let len = preview_data.len();
-        return Response::builder()
-            .status(StatusCode::OK)
-            .header(header::CONTENT_TYPE, mime)
-            .header(header::CONTENT_LENGTH, len)
-            .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
-            .body(preview_data)
-            .unwrap_or_else(|_| Response::default());
+    if let Ok((mut preview_data, mut mime)) = crate::thumbnails::extractors::extract_preview(Some(app), &full_path) {
+        if let Some(flattened) = crate::thumbnails::matte::flatten_encoded(&preview_data) {
+            preview_data = flattened;
+            mime = "image/png".to_string();
+        }
+        // Converting drops the profile; wide-gamut art would shift towards sRGB
+        let preview_data = crate::thumbnails::icc::keep_profile(preview_data, &full_path);
+        return image_response(preview_data, &mime);
+    }
+
+    // TRANSPARENT ORIGINALS: flattened onto the matte like every preview.
+    // Animations are left alone, as only their first frame would survive.
+    if let Some(flattened) = flatten_original(&full_path) {
+        let flattened = crate::thumbnails::icc::keep_profile(flattened, &full_path);
+        return image_response(flattened, "image/png");
     }
 
     let range = request.headers().get(header::RANGE);
@@ -34,3 +93,46 @@ pub fn handler<R: tauri::Runtime>(app: &AppHandle<R>, request: &Request<Vec<u8>>
         Err(res) => res,
     }
 }
+
+/// PNG of `path` flattened onto the matte, `None` when it is served as is:
+/// no matte, a format without transparency, an opaque image or an animation.
+fn flatten_original(path: &std::path::Path) -> Option<Vec<u8>> {
+    use crate::thumbnails::matte;
+
+    if matte::current() == matte::Matte::None {
+        return None;
+    }
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    if !matte::TRANSPARENT_FORMATS.contains(&ext.as_str()) {
+        return None;
+    }
+    let data = std::fs::read(path).ok()?;
+    if crate::media::animation::parse(&data).is_some_and(|info| info.is_animated()) {
+        return None;
+    }
+    matte::flatten_encoded(&data)
+}
+
+fn image_response(data: Vec<u8>, mime: &str) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, mime)
+        .header(header::CONTENT_LENGTH, data.len())
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .body(data)
+        .unwrap_or_else(|_| Response::default())
+}
+
+/// Reads `maxdim` from a query string such as `maxdim=3000&v=2`.
+fn parse_maxdim(query: &str) -> Option<u32> {
+    parse_query_u32(query, "maxdim").filter(|v| *v > 0)
+}
+
+/// Reads a numeric query parameter.
+fn parse_query_u32(query: &str, name: &str) -> Option<u32> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| value.parse::<u32>().ok())
+}
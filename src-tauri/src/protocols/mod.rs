@@ -8,6 +8,7 @@ pub mod model;
 pub mod placeholders;
 pub mod audio_stream;
 pub mod video_stream;
+pub mod scope;
 
 
 /// Registration helper to keep lib.rs clean
@@ -19,11 +20,11 @@ pub fn register_all<R: tauri::Runtime>(builder: tauri::Builder<R>) -> tauri::Bui
         .register_uri_scheme_protocol("image", move |ctx, request| {
             image::handler(ctx.app_handle(), &request)
         })
-        .register_uri_scheme_protocol("audio", move |_ctx, request| {
-            audio::handler(&request)
+        .register_uri_scheme_protocol("audio", move |ctx, request| {
+            audio::handler(ctx.app_handle(), &request)
         })
-        .register_uri_scheme_protocol("video", move |_ctx, request| {
-            video::handler(&request)
+        .register_uri_scheme_protocol("video", move |ctx, request| {
+            video::handler(ctx.app_handle(), &request)
         })
         .register_uri_scheme_protocol("audio-stream", move |ctx, request| {
             audio_stream::handler(&ctx.app_handle(), &request)
@@ -31,19 +32,20 @@ pub fn register_all<R: tauri::Runtime>(builder: tauri::Builder<R>) -> tauri::Bui
         .register_uri_scheme_protocol("video-stream", move |ctx, request| {
             video_stream::handler(&ctx.app_handle(), &request)
         })
-        .register_uri_scheme_protocol("font", move |_ctx, request| {
-            font::handler(&request)
+        .register_uri_scheme_protocol("font", move |ctx, request| {
+            font::handler(ctx.app_handle(), &request)
         })
-        .register_uri_scheme_protocol("model", move |_ctx, request| {
-            model::handler(&request)
+        .register_asynchronous_uri_scheme_protocol("model", move |ctx, request, responder| {
+            let app = ctx.app_handle().clone();
+            tauri::async_runtime::spawn_blocking(move || responder.respond(model::handler(&app, &request)));
         })
-        .register_uri_scheme_protocol("document", move |_ctx, request| {
-            placeholders::document_handler(&request)
+        .register_uri_scheme_protocol("document", move |ctx, request| {
+            placeholders::document_handler(ctx.app_handle(), &request)
         })
-        .register_uri_scheme_protocol("ebook", move |_ctx, request| {
-            placeholders::ebook_handler(&request)
+        .register_uri_scheme_protocol("ebook", move |ctx, request| {
+            placeholders::ebook_handler(ctx.app_handle(), &request)
         })
-        .register_uri_scheme_protocol("code", move |_ctx, request| {
-            placeholders::code_handler(&request)
+        .register_uri_scheme_protocol("code", move |ctx, request| {
+            placeholders::code_handler(ctx.app_handle(), &request)
         })
 }
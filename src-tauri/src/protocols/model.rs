@@ -1,21 +1,44 @@
-use super::common::{decode_path, extract_path_part, serve_file};
+use super::common::{app_error_response, extract_path_part, serve_file};
+use super::scope;
+use crate::error::AppError;
+use crate::library::derivatives::{self, DerivativeKind};
+use crate::thumbnails::model;
 use tauri::http::{header, Response, Request};
-use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
 
-pub fn handler(request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+/// Handler for model:// protocol
+/// Serves glTF as is, and other model formats converted to a cached GLB the
+/// 3D viewer can load. Converting can take a while, so it is registered as
+/// an asynchronous protocol and run on a blocking thread
+pub fn handler<R: tauri::Runtime>(app: &AppHandle<R>, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
     let uri = request.uri().to_string();
     let path_part = extract_path_part(&uri, "model");
-    let decoded_path = decode_path(&path_part);
-    let mut full_path = PathBuf::from(&decoded_path);
+    let full_path = match scope::resolve(app, &path_part) {
+        Ok(path) => path,
+        Err(res) => return res,
+    };
 
-    if !full_path.is_absolute() && cfg!(unix) {
-        if !path_part.starts_with('/') {
-            full_path = PathBuf::from("/").join(full_path);
+    let served_path = if model::needs_conversion(&full_path) && full_path.is_file() {
+        let cache_dir = match app.path().app_local_data_dir() {
+            Ok(dir) => dir.join(model::CACHE_DIR),
+            Err(e) => return app_error_response(&AppError::Tauri(e)),
+        };
+        match model::viewer_glb(&cache_dir, &full_path) {
+            Ok(glb) => {
+                derivatives::record_for_source(app, &full_path, DerivativeKind::Preview, &glb, Some("GLB"));
+                glb
+            }
+            Err(e) => {
+                eprintln!("WARN: Could not convert {:?} for the 3D viewer: {}", full_path, e);
+                return app_error_response(&AppError::Unsupported(format!("Could not convert model: {}", e)));
+            }
         }
-    }
+    } else {
+        full_path
+    };
 
     let range = request.headers().get(header::RANGE);
-    match serve_file(&full_path, range) {
+    match serve_file(&served_path, range) {
         Ok(res) => res,
         Err(res) => res,
     }
@@ -1,34 +1,31 @@
-use super::common::{decode_path, extract_path_part, serve_file};
+use super::common::{extract_path_part, serve_file};
+use super::scope;
 use tauri::http::{header, Response, Request};
-use std::path::PathBuf;
+use tauri::AppHandle;
 
-pub fn document_handler(request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+pub fn document_handler<R: tauri::Runtime>(app: &AppHandle<R>, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
     let uri = request.uri().to_string();
     let path_part = extract_path_part(&uri, "document");
-    handle_generic(path_part, request)
+    handle_generic(app, &path_part, request)
 }
 
-pub fn ebook_handler(request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+pub fn ebook_handler<R: tauri::Runtime>(app: &AppHandle<R>, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
     let uri = request.uri().to_string();
     let path_part = extract_path_part(&uri, "ebook");
-    handle_generic(path_part, request)
+    handle_generic(app, &path_part, request)
 }
 
-pub fn code_handler(request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+pub fn code_handler<R: tauri::Runtime>(app: &AppHandle<R>, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
     let uri = request.uri().to_string();
     let path_part = extract_path_part(&uri, "code");
-    handle_generic(path_part, request)
+    handle_generic(app, &path_part, request)
 }
 
-fn handle_generic(path_part: String, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
-    let decoded_path = decode_path(&path_part);
-    let mut full_path = PathBuf::from(&decoded_path);
-
-    if !full_path.is_absolute() && cfg!(unix) {
-        if !path_part.starts_with('/') {
-            full_path = PathBuf::from("/").join(full_path);
-        }
-    }
+fn handle_generic<R: tauri::Runtime>(app: &AppHandle<R>, path_part: &str, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let full_path = match scope::resolve(app, path_part) {
+        Ok(path) => path,
+        Err(res) => return res,
+    };
 
     let range = request.headers().get(header::RANGE);
     match serve_file(&full_path, range) {
@@ -36,3 +33,27 @@ fn handle_generic(path_part: String, request: &Request<Vec<u8>>) -> Response<Vec
         Err(res) => res,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tauri::Manager;
+
+    #[test]
+    fn test_paths_outside_the_library_are_refused() {
+        let library = tauri::async_runtime::block_on(crate::testkit::TestLibrary::open("placeholder-scope"));
+        let secret = library.dir.join("secret.txt");
+        std::fs::write(&secret, "secret").unwrap();
+        let app = crate::testkit::mock_app();
+        app.manage(library.db.clone());
+
+        let _lock = scope::tests::ROOTS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let handlers: [(&str, fn(&AppHandle<tauri::test::MockRuntime>, &Request<Vec<u8>>) -> Response<Vec<u8>>); 3] =
+            [("document", document_handler), ("ebook", ebook_handler), ("code", code_handler)];
+        for (scheme, handler) in handlers {
+            let uri = format!("{}://localhost/{}", scheme, secret.to_string_lossy().trim_start_matches('/'));
+            let request = Request::builder().uri(uri).body(Vec::new()).unwrap();
+            assert_eq!(handler(app.handle(), &request).status(), 403, "{} served a file outside the library", scheme);
+        }
+    }
+}
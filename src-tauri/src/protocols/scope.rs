@@ -0,0 +1,144 @@
+//! Which files the media protocols may serve.
+//!
+//! The WebView asks for originals either by library id
+//! (`image://localhost/id/42`) or by path (`image://localhost/<path>`). Ids
+//! are looked up in the database. Paths are only served from inside one of
+//! the library's locations, after resolving `..` and symlinks, so the UI, or
+//! a script injected into it, can't read `~/.ssh/id_rsa` through `image://`.
+//! Paths stay supported for files that reference their neighbours, like a
+//! glTF and its buffers, which the WebView requests relative to its URL.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use tauri::http::Response;
+use tauri::{AppHandle, Manager, Runtime};
+
+use super::common::{app_error_response, decode_path};
+use crate::db::Db;
+use crate::error::AppError;
+use crate::paths;
+
+/// Shortest time between two reloads of the locations on a refused path,
+/// which is how locations added since the last load are picked up.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+struct Roots {
+    paths: Vec<PathBuf>,
+    loaded_at: Option<Instant>,
+}
+
+static ROOTS: RwLock<Roots> = RwLock::new(Roots { paths: Vec::new(), loaded_at: None });
+
+/// Resolves the path part of a protocol URL to a file the library holds.
+/// Unknown ids answer 404 and paths outside the library 403.
+pub fn resolve<R: Runtime>(app: &AppHandle<R>, path_part: &str) -> Result<PathBuf, Response<Vec<u8>>> {
+    let not_found = || app_error_response(&AppError::NotFound(decode_path(path_part)));
+    let db = app.try_state::<Arc<Db>>().ok_or_else(not_found)?;
+
+    if let Some(id) = path_part.strip_prefix("id/").and_then(|id| id.parse::<i64>().ok()) {
+        let db = db.inner().clone();
+        return match tauri::async_runtime::block_on(async move { db.get_image_path(id).await }) {
+            Ok(Some(path)) => Ok(paths::from_db(&path)),
+            _ => Err(not_found()),
+        };
+    }
+
+    let path = absolute_path(path_part);
+    if is_allowed(&path) || (is_stale() && reload(db.inner()) && is_allowed(&path)) {
+        Ok(path)
+    } else {
+        eprintln!("WARN: Refused to serve {:?}, which is outside the library", path);
+        let denied = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "File is outside the library");
+        Err(app_error_response(&AppError::Io(denied)))
+    }
+}
+
+/// Decodes a URL path, making it absolute on Unix where the leading `/` may
+/// have been eaten by the URL.
+fn absolute_path(path_part: &str) -> PathBuf {
+    let decoded = decode_path(path_part);
+    let path = paths::from_db(&decoded);
+    if !path.is_absolute() && cfg!(unix) && !path_part.starts_with('/') {
+        PathBuf::from("/").join(path)
+    } else {
+        path
+    }
+}
+
+/// Only the resolved path counts: `<root>/../x` looks like it is inside
+/// `<root>` until `..` is resolved.
+fn is_allowed(path: &Path) -> bool {
+    let Ok(canonical) = path.canonicalize() else {
+        return false;
+    };
+    is_inside(&canonical, &ROOTS.read().unwrap_or_else(|e| e.into_inner()).paths)
+}
+
+/// Whether `path` is one of `roots` or inside one. Both are canonical.
+fn is_inside(path: &Path, roots: &[PathBuf]) -> bool {
+    roots.iter().any(|root| path.starts_with(root))
+}
+
+fn is_stale() -> bool {
+    match ROOTS.read().unwrap_or_else(|e| e.into_inner()).loaded_at {
+        Some(at) => at.elapsed() >= RELOAD_INTERVAL,
+        None => true,
+    }
+}
+
+/// Loads the locations from the database. Returns whether it succeeded.
+fn reload(db: &Arc<Db>) -> bool {
+    let db = db.clone();
+    let Ok(roots) = tauri::async_runtime::block_on(async move { db.get_all_root_folders().await }) else {
+        return false;
+    };
+    // Remote mirrors live in the app's cache, outside every location. A root
+    // that can't be resolved doesn't exist, so it holds no file to serve.
+    let paths = roots
+        .into_iter()
+        .map(|(_, path)| paths::from_db(&path))
+        .chain(crate::storage::mirror_root().ok().map(Path::to_path_buf))
+        .filter_map(|path| path.canonicalize().ok())
+        .collect();
+    *ROOTS.write().unwrap_or_else(|e| e.into_inner()) = Roots { paths, loaded_at: Some(Instant::now()) };
+    true
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// Held by tests that replace the loaded locations.
+    pub(in crate::protocols) static ROOTS_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn test_is_inside() {
+        let roots = vec![PathBuf::from("/Users/ana/Pictures"), PathBuf::from("/Volumes/Archive")];
+        assert!(is_inside(Path::new("/Users/ana/Pictures/2024/a.jpg"), &roots));
+        assert!(is_inside(Path::new("/Volumes/Archive"), &roots));
+        assert!(!is_inside(Path::new("/Users/ana/.ssh/id_rsa"), &roots));
+        assert!(!is_inside(Path::new("/Users/ana/Pictures2/a.jpg"), &roots));
+    }
+
+    #[test]
+    fn test_traversal_is_resolved_before_checking() {
+        let root = std::env::temp_dir().join(format!("mundam-scope-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("library")).unwrap();
+        std::fs::write(root.join("secret.txt"), "secret").unwrap();
+        std::fs::write(root.join("library/a.jpg"), "a").unwrap();
+        let roots = vec![root.join("library").canonicalize().unwrap()];
+        let _lock = ROOTS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let inside = root.join("library/a.jpg").canonicalize().unwrap();
+        let escaped = root.join("library/../secret.txt").canonicalize().unwrap();
+        assert!(is_inside(&inside, &roots));
+        assert!(!is_inside(&escaped, &roots));
+
+        *ROOTS.write().unwrap() = Roots { paths: roots, loaded_at: Some(Instant::now()) };
+        assert!(is_allowed(&root.join("library/a.jpg")));
+        assert!(!is_allowed(&root.join("library/../secret.txt")));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
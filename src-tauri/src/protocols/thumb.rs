@@ -1,5 +1,6 @@
-use super::common::{decode_path, extract_path_part, serve_file, error_response};
-use tauri::{http::{header, Response, StatusCode, Request}, Manager, AppHandle};
+use super::common::{app_error_response, decode_path, extract_path_part, serve_file};
+use crate::error::AppError;
+use tauri::{http::{header, Response, Request}, Manager, AppHandle};
 
 
 pub fn handler<R: tauri::Runtime>(app: &AppHandle<R>, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
@@ -9,10 +10,17 @@ pub fn handler<R: tauri::Runtime>(app: &AppHandle<R>, request: &Request<Vec<u8>>
 
     let thumb_dir = match app.path().app_local_data_dir() {
         Ok(dir) => dir.join("thumbnails"),
-        Err(_) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, b"Data dir not found".to_vec()),
+        Err(e) => return app_error_response(&AppError::Tauri(e)),
     };
 
     let decoded_filename = decode_path(path_part);
+    // Only names inside the thumbnails folder, never a way out of it
+    let escapes = std::path::Path::new(&decoded_filename)
+        .components()
+        .any(|c| !matches!(c, std::path::Component::Normal(_)));
+    if escapes {
+        return app_error_response(&AppError::NotFound(decoded_filename));
+    }
     let mut full_path = thumb_dir.join(&decoded_filename);
 
     if !full_path.exists() && decoded_filename.starts_with("icon_") {
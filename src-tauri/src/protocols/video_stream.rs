@@ -1,8 +1,10 @@
-use std::path::PathBuf;
-use tauri::http::{header, Response, StatusCode, Request};
+use tauri::http::{header, Response, Request};
 use tauri::{AppHandle, Manager};
 
-use super::common::{decode_path, extract_path_part, error_response};
+use super::common::{app_error_response, extract_path_part};
+use super::scope;
+use crate::error::AppError;
+use crate::library::derivatives::{self, DerivativeKind};
 use crate::transcoding::cache::TranscodeCache;
 use crate::transcoding::detector;
 use crate::transcoding::ffmpeg_pipe::FfmpegTranscoder;
@@ -16,22 +18,19 @@ pub fn handler<R: tauri::Runtime>(app: &AppHandle<R>, request: &Request<Vec<u8>>
     // Parse path and quality from URI
     // Format: video-stream://localhost/path/to/file.mkv?quality=preview
     let (path_str, quality) = parse_stream_uri(&uri, "video-stream");
-    let decoded_path = decode_path(&path_str);
-    let mut full_path = PathBuf::from(&decoded_path);
-
-    if !full_path.is_absolute() && cfg!(unix) {
-        if !path_str.starts_with('/') {
-            full_path = PathBuf::from("/").join(full_path);
-        }
-    }
+    let full_path = match scope::resolve(app, &path_str) {
+        Ok(path) => path,
+        Err(res) => return res,
+    };
 
     // Verify file exists
     if !full_path.exists() {
-        return error_response(StatusCode::NOT_FOUND, b"File not found".to_vec());
+        return app_error_response(&AppError::NotFound(full_path.to_string_lossy().to_string()));
     }
 
-    // Check if this format needs transcoding
-    if !detector::needs_transcoding(&full_path) {
+    // Check if this format needs transcoding. Once the WebView has registered
+    // its codecs, a native container only gets here for codecs it can't decode
+    if crate::transcoding::capabilities::registered().is_none() && !detector::needs_transcoding(&full_path) {
         // Fallback to regular video serving for native formats
         let range = request.headers().get(header::RANGE);
         return match crate::protocols::common::serve_file(&full_path, range) {
@@ -43,16 +42,23 @@ pub fn handler<R: tauri::Runtime>(app: &AppHandle<R>, request: &Request<Vec<u8>>
     // Get cache directory
     let app_data = match app.path().app_local_data_dir() {
         Ok(d) => d,
-        Err(_) => {
-            return error_response(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                b"Failed to get app data directory".to_vec(),
-            );
-        }
+        Err(e) => return app_error_response(&AppError::Tauri(e)),
     };
 
     let cache = TranscodeCache::new(&app_data);
 
+    // Remux fast path: the probe found codecs MP4 can carry as-is
+    let remux = is_remux_request(&uri);
+    if remux {
+        if let Some(cached_path) = cache.get_remux(&full_path) {
+            let range = request.headers().get(header::RANGE);
+            return match crate::protocols::common::serve_file(&cached_path, range) {
+                Ok(res) => res,
+                Err(res) => res,
+            };
+        }
+    }
+
     // Check cache first
     if let Some(cached_path) = cache.get(&full_path, quality) {
         // Serve from cache with full range support
@@ -67,16 +73,29 @@ pub fn handler<R: tauri::Runtime>(app: &AppHandle<R>, request: &Request<Vec<u8>>
     let transcoder = FfmpegTranscoder::new(cache);
 
     if !transcoder.is_available() {
-        return error_response(
-            StatusCode::SERVICE_UNAVAILABLE,
-            b"FFmpeg is not available for transcoding".to_vec(),
-        );
+        return app_error_response(&AppError::FfmpegMissing);
+    }
+
+    if remux {
+        match transcoder.remux_sync(&full_path) {
+            Ok(output_path) => {
+                derivatives::record_for_source(app, &full_path, DerivativeKind::Transcode, &output_path, Some("Remux"));
+                let range = request.headers().get(header::RANGE);
+                return match crate::protocols::common::serve_file(&output_path, range) {
+                    Ok(res) => res,
+                    Err(res) => res,
+                };
+            }
+            // Odd streams can still fail to copy; a full transcode always works
+            Err(e) => eprintln!("WARN: Remux failed, falling back to transcode: {}", e),
+        }
     }
 
     // For video, we always transcode to cache first (streaming is complex with seeking)
     // This may take a while for long videos, but provides better seeking experience
     match transcoder.transcode_sync(&full_path, quality) {
         Ok(output_path) => {
+            derivatives::record_for_source(app, &full_path, DerivativeKind::Transcode, &output_path, Some(quality.label()));
             // Serve the transcoded file with range support
             let range = request.headers().get(header::RANGE);
             match crate::protocols::common::serve_file(&output_path, range) {
@@ -86,14 +105,18 @@ pub fn handler<R: tauri::Runtime>(app: &AppHandle<R>, request: &Request<Vec<u8>>
         }
         Err(e) => {
             eprintln!("TRANSCODE_ERROR: {:?}", e);
-            error_response(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Transcoding failed: {}", e).into_bytes(),
-            )
+            app_error_response(&AppError::Transcoding(e.to_string()))
         }
     }
 }
 
+/// Whether the URI asks for a stream-copy remux (`mode=remux`) instead of a transcode
+fn is_remux_request(uri: &str) -> bool {
+    uri.split_once('?')
+        .map(|(_, query)| query.split('&').any(|p| p == "mode=remux"))
+        .unwrap_or(false)
+}
+
 /// Parse the stream URI to extract path and quality
 fn parse_stream_uri(uri: &str, scheme: &str) -> (String, TranscodeQuality) {
     // First, extract the path part using the common function
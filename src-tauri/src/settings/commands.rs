@@ -1,4 +1,5 @@
 use tauri::State;
+use crate::db::vacuum::{DbStatus, MaintenanceKind, MaintenanceReport};
 use crate::db::Db;
 use crate::error::AppResult;
 use serde_json::Value;
@@ -10,10 +11,53 @@ pub async fn get_setting(key: String, db: State<'_, std::sync::Arc<Db>>) -> AppR
 
 #[tauri::command]
 pub async fn set_setting(key: String, value: Value, db: State<'_, std::sync::Arc<Db>>) -> AppResult<()> {
-    Ok(db.set_setting(&key, &value).await?)
+    db.set_setting(&key, &value).await?;
+    // The matte is read by every render, so it takes effect without a restart.
+    // Thumbnails are cached, so those that may be transparent are rendered again.
+    if key == crate::thumbnails::matte::SETTING_KEY {
+        let previous = crate::thumbnails::matte::current();
+        crate::thumbnails::matte::set_from_setting(value.as_str().unwrap_or_default());
+        if crate::thumbnails::matte::current() != previous {
+            let requeued = db.reset_thumbnails_for_formats(crate::thumbnails::matte::TRANSPARENT_FORMATS).await?;
+            println!("INFO: Preview matte changed, {} thumbnails requeued", requeued);
+        }
+    }
+    if key == crate::library::read_only::SETTING_KEY {
+        crate::library::read_only::set_enabled(value.as_bool().unwrap_or(false));
+    }
+    if key == crate::db::collation::SETTING_KEY {
+        crate::db::collation::set_from_setting(value.as_str().unwrap_or_default());
+        db.refresh_filename_sort_keys().await?;
+    }
+    if key == crate::thumbnails::memory::SETTING_KEY {
+        crate::thumbnails::memory::set_limit_mb(value.as_u64().unwrap_or(0));
+    }
+    // Times recorded without an offset were converted with the previous zone
+    if key == crate::media::capture_time::SETTING_KEY {
+        let zone = crate::media::capture_time::DefaultZone::from_setting(value.as_str());
+        crate::indexer::capture_times::renormalize(&db, zone).await?;
+    }
+    Ok(())
 }
 
+/// Reclaims free space and refreshes planner statistics (see `db::vacuum`).
+/// Only the first run on an older library does a full, blocking `VACUUM`.
 #[tauri::command]
-pub async fn run_db_maintenance(db: State<'_, std::sync::Arc<Db>>) -> AppResult<()> {
+pub async fn run_db_maintenance(db: State<'_, std::sync::Arc<Db>>) -> AppResult<MaintenanceReport> {
     Ok(db.run_maintenance().await?)
 }
+
+/// Size, free space, page cache efficiency and last maintenance times of
+/// the library database, to judge whether `run_db_maintenance` is due.
+#[tauri::command]
+pub async fn get_db_status(db: State<'_, std::sync::Arc<Db>>) -> AppResult<DbStatus> {
+    Ok(db.get_status().await?)
+}
+
+/// Moves the whole WAL into the database file and truncates it.
+#[tauri::command]
+pub async fn checkpoint_db(db: State<'_, std::sync::Arc<Db>>) -> AppResult<DbStatus> {
+    db.truncate_wal().await?;
+    db.record_maintenance(MaintenanceKind::Checkpoint).await?;
+    Ok(db.get_status().await?)
+}
@@ -6,6 +6,22 @@ use crate::db::Db;
 pub struct AppConfig {
     pub thumbnail_threads: usize,
     pub indexer_batch_size: i32,
+    /// Concurrent metadata extraction tasks during a scan (0 = auto).
+    pub scan_workers: usize,
+    /// Render crash-prone native decoders in a helper subprocess.
+    pub isolate_decoders: bool,
+    /// Background for transparent previews (see `thumbnails::matte`).
+    pub preview_matte: String,
+    /// Language filenames sort by (see `db::collation`).
+    pub collation_locale: String,
+    /// Reject commands that edit the library (see `library::read_only`).
+    pub read_only: bool,
+    /// Cap on decoded image data held by concurrent renders, in MB (see
+    /// `thumbnails::memory`).
+    pub thumbnail_memory_mb: u64,
+    /// Render RAW and PSD thumbnails again from the full-resolution source
+    /// when idle (see `thumbnails::upgrade`).
+    pub upgrade_thumbnails: bool,
 }
 
 impl Default for AppConfig {
@@ -13,6 +29,13 @@ impl Default for AppConfig {
         Self {
             thumbnail_threads: 0, // 0 = Auto-detect
             indexer_batch_size: 6,
+            scan_workers: 0, // 0 = Auto-detect
+            isolate_decoders: false,
+            preview_matte: "none".to_string(),
+            collation_locale: String::new(),
+            read_only: false,
+            thumbnail_memory_mb: crate::thumbnails::memory::DEFAULT_LIMIT_MB,
+            upgrade_thumbnails: true,
         }
     }
 }
@@ -30,6 +53,44 @@ pub async fn load_config(db: &Db) -> AppConfig {
         }
     }
 
+    config.scan_workers = scan_worker_count(db).await;
+
+    if let Ok(Some(val)) = db.get_setting("isolate_thumbnail_decoders").await {
+        if let Some(v) = val.as_bool() {
+            config.isolate_decoders = v;
+        }
+    }
+
+    if let Ok(Some(val)) = db.get_setting(crate::thumbnails::matte::SETTING_KEY).await {
+        if let Some(v) = val.as_str() {
+            config.preview_matte = v.to_string();
+        }
+    }
+
+    if let Ok(Some(val)) = db.get_setting(crate::db::collation::SETTING_KEY).await {
+        if let Some(v) = val.as_str() {
+            config.collation_locale = v.to_string();
+        }
+    }
+
+    if let Ok(Some(val)) = db.get_setting(crate::library::read_only::SETTING_KEY).await {
+        if let Some(v) = val.as_bool() {
+            config.read_only = v;
+        }
+    }
+
+    if let Ok(Some(val)) = db.get_setting(crate::thumbnails::memory::SETTING_KEY).await {
+        if let Some(v) = val.as_u64().filter(|v| *v > 0) {
+            config.thumbnail_memory_mb = v;
+        }
+    }
+
+    if let Ok(Some(val)) = db.get_setting(crate::thumbnails::upgrade::SETTING_KEY).await {
+        if let Some(v) = val.as_bool() {
+            config.upgrade_thumbnails = v;
+        }
+    }
+
     // Auto-detect if set to 0
     if config.thumbnail_threads == 0 {
          let available = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
@@ -40,3 +101,41 @@ pub async fn load_config(db: &Db) -> AppConfig {
 
     config
 }
+
+/// Upper bound for metadata workers; past this the disk is the bottleneck.
+const MAX_SCAN_WORKERS: usize = 64;
+
+/// Number of concurrent metadata extraction tasks for a scan, from the
+/// `scan_workers` setting (0 or missing = auto-detect).
+pub async fn scan_worker_count(db: &Db) -> usize {
+    let configured = match db.get_setting("scan_workers").await {
+        Ok(Some(val)) => val.as_u64().unwrap_or(0) as usize,
+        _ => 0,
+    };
+    let available = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+    resolve_scan_workers(configured, available)
+}
+
+fn resolve_scan_workers(configured: usize, available: usize) -> usize {
+    if configured == 0 {
+        // Scans are mostly I/O bound, so a few workers beyond the core count
+        // don't help and just hold more files in flight.
+        available.clamp(2, 8)
+    } else {
+        configured.min(MAX_SCAN_WORKERS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_scan_workers() {
+        assert_eq!(resolve_scan_workers(0, 1), 2);
+        assert_eq!(resolve_scan_workers(0, 4), 4);
+        assert_eq!(resolve_scan_workers(0, 32), 8);
+        assert_eq!(resolve_scan_workers(3, 32), 3);
+        assert_eq!(resolve_scan_workers(1000, 4), MAX_SCAN_WORKERS);
+    }
+}
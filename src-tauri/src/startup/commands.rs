@@ -0,0 +1,40 @@
+use std::path::PathBuf;
+
+use tauri::{AppHandle, Manager, State};
+
+use super::{initialize, AppStatus, StartupState, LIBRARY_FILE};
+use crate::error::{AppError, AppResult};
+
+/// Where startup stands. The frontend calls it once on load, then follows
+/// the `app:init-progress` events.
+#[tauri::command]
+pub fn get_app_status(state: State<'_, StartupState>) -> AppStatus {
+    state.status()
+}
+
+/// Starts over after a failed startup. `db_path` opens another library
+/// instead: a database file, or a folder to hold one.
+#[tauri::command]
+pub async fn retry_app_init(
+    app: AppHandle,
+    state: State<'_, StartupState>,
+    db_path: Option<String>,
+) -> AppResult<AppStatus> {
+    let db_path = match db_path.filter(|path| !path.trim().is_empty()) {
+        Some(path) => {
+            let path = PathBuf::from(path);
+            if path.is_dir() { path.join(LIBRARY_FILE) } else { path }
+        }
+        None => PathBuf::from(state.status().db_path),
+    };
+    if let Some(parent) = db_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let app_data = app.path().app_local_data_dir()?;
+
+    if !state.begin_retry(&db_path) {
+        return Err(AppError::Busy("The library is already open or opening".to_string()));
+    }
+    tauri::async_runtime::spawn(initialize(app.clone(), db_path, app_data));
+    Ok(state.status())
+}
@@ -0,0 +1,254 @@
+//! Startup sequence of the backend.
+//!
+//! The database, the workers and the watchers are brought up in a background
+//! task after the window opens. Each step is reported as an
+//! `app:init-progress` event carrying the [`AppStatus`], which the frontend
+//! can also poll with `get_app_status`, so it waits for the library instead
+//! of calling commands whose state isn't managed yet. When a step fails the
+//! status says why and stays `failed` until `retry_app_init` starts over,
+//! with the same library or another one.
+
+pub mod commands;
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::db::Db;
+use crate::indexer::Indexer;
+use crate::library::lock::{self, LibraryLock, LockAttempt, LockStatus};
+
+/// Name of the database file inside a library folder.
+pub const LIBRARY_FILE: &str = "mundam.db";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupStage {
+    Starting,
+    Database,
+    Configuration,
+    Workers,
+    Watchers,
+    Ready,
+    Failed,
+}
+
+/// Where startup stands, as sent with `app:init-progress`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppStatus {
+    pub stage: StartupStage,
+    /// Why startup failed, when `stage` is `failed`.
+    pub error: Option<String>,
+    /// Database being opened.
+    pub db_path: String,
+    /// Whether another instance writes the library.
+    pub read_only: bool,
+}
+
+/// Startup status, managed as app state from the first moment.
+pub struct StartupState {
+    status: Mutex<AppStatus>,
+    /// Writer lock of the open library, held for the life of the app.
+    lock: Mutex<Option<LibraryLock>>,
+}
+
+impl StartupState {
+    pub fn new(db_path: &Path) -> Self {
+        Self {
+            status: Mutex::new(AppStatus {
+                stage: StartupStage::Starting,
+                error: None,
+                db_path: db_path.to_string_lossy().to_string(),
+                read_only: false,
+            }),
+            lock: Mutex::new(None),
+        }
+    }
+
+    pub fn status(&self) -> AppStatus {
+        self.status.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Starts a new attempt on `db_path`. Only a failed startup can be
+    /// retried; returns `false` while one is running or once ready.
+    fn begin_retry(&self, db_path: &Path) -> bool {
+        let mut status = self.status.lock().unwrap_or_else(|e| e.into_inner());
+        if status.stage != StartupStage::Failed {
+            return false;
+        }
+        *status = AppStatus {
+            stage: StartupStage::Starting,
+            error: None,
+            db_path: db_path.to_string_lossy().to_string(),
+            read_only: false,
+        };
+        true
+    }
+
+    fn set_stage(&self, stage: StartupStage, error: Option<String>) -> AppStatus {
+        let mut status = self.status.lock().unwrap_or_else(|e| e.into_inner());
+        status.stage = stage;
+        status.error = error;
+        status.clone()
+    }
+}
+
+fn advance(app: &AppHandle, stage: StartupStage) {
+    let status = app.state::<StartupState>().set_stage(stage, None);
+    let _ = app.emit("app:init-progress", status);
+}
+
+fn fail(app: &AppHandle, error: String) {
+    eprintln!("ERROR: Startup failed: {}", error);
+    let state = app.state::<StartupState>();
+    // Release the library so another instance, or the next attempt, can take it
+    *state.lock.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    let status = state.set_stage(StartupStage::Failed, Some(error));
+    let _ = app.emit("app:init-progress", status);
+}
+
+/// Only one instance may write the library, the others open it read-only.
+fn lock_library(app: &AppHandle, db_path: &Path) -> LockStatus {
+    let state = app.state::<StartupState>();
+    let lock_status = match lock::acquire(db_path) {
+        Ok(LockAttempt::Acquired(lock)) => {
+            *state.lock.lock().unwrap_or_else(|e| e.into_inner()) = Some(lock);
+            LockStatus { owned: true, owner: None }
+        }
+        Ok(LockAttempt::Held(owner)) => LockStatus { owned: false, owner },
+        Err(e) => {
+            eprintln!("WARN: Could not lock the library, continuing without a lock: {}", e);
+            LockStatus { owned: true, owner: None }
+        }
+    };
+    state.status.lock().unwrap_or_else(|e| e.into_inner()).read_only = !lock_status.owned;
+    lock_status
+}
+
+/// Opens the library at `db_path` and starts everything that depends on it.
+pub async fn initialize(app: AppHandle, db_path: PathBuf, app_data: PathBuf) {
+    advance(&app, StartupStage::Database);
+    let lock_status = lock_library(&app, &db_path);
+    let is_writer = lock_status.owned;
+    crate::library::read_only::set_locked_out(!is_writer);
+
+    let db_arc = match Db::new(db_path.clone()).await {
+        Ok(db) => Arc::new(db),
+        Err(e) => {
+            fail(&app, format!("Could not open the library database {:?}: {}", db_path, e));
+            return;
+        }
+    };
+    app.manage(lock_status);
+
+    // Nothing can be running yet, so whatever is still in progress was cut short by a crash
+    if is_writer {
+        match db_arc.mark_interrupted_operations().await {
+            Ok(0) => {}
+            Ok(count) => println!("INFO: {} operations were interrupted and can be resumed or rolled back", count),
+            Err(e) => eprintln!("WARN: Failed to check the operations ledger: {}", e),
+        }
+    }
+    let watcher_registry = Arc::new(tokio::sync::Mutex::new(crate::indexer::WatcherRegistry::default()));
+
+    // Load Config
+    advance(&app, StartupStage::Configuration);
+    let app_config = crate::settings::config::load_config(&db_arc).await;
+    crate::thumbnails::isolated::set_enabled(app_config.isolate_decoders);
+    crate::thumbnails::matte::set_from_setting(&app_config.preview_matte);
+    crate::db::collation::set_from_setting(&app_config.collation_locale);
+    crate::thumbnails::memory::set_limit_mb(app_config.thumbnail_memory_mb);
+    crate::library::read_only::set_enabled(app_config.read_only);
+    let config_state = crate::settings::config::ConfigState(std::sync::Mutex::new(app_config.clone()));
+
+    let priority_state = Arc::new(crate::thumbnails::priority::ThumbnailPriorityState::default());
+
+    app.manage(db_arc.clone());
+    app.manage(watcher_registry.clone());
+    app.manage(config_state);
+    app.manage(priority_state.clone());
+    app.manage(Arc::new(crate::library::filter_subscriptions::FilterSubscriptions::default()));
+
+    let job_queue = crate::jobs::JobQueue::new(db_arc.clone(), app.clone());
+    app.manage(job_queue.clone());
+
+    // A read-only instance leaves background writes to the writer
+    if !is_writer {
+        advance(&app, StartupStage::Ready);
+        return;
+    }
+
+    advance(&app, StartupStage::Workers);
+    job_queue.start().await;
+
+    let worker = crate::thumbnails::worker::ThumbnailWorker::new(
+        db_arc.clone(),
+        app_data.join("thumbnails"),
+        app.clone(),
+        app_config,
+        priority_state,
+    );
+    worker.start().await;
+
+    crate::media::info_worker::start(db_arc.clone(), app.clone());
+    crate::media::environment::start(db_arc.clone(), app.clone());
+    crate::indexer::keywords::start(db_arc.clone());
+    crate::indexer::sort_keys::start(db_arc.clone());
+    crate::indexer::capture_times::start(db_arc.clone());
+    crate::indexer::content_hashes::start(db_arc.clone());
+    crate::media::sidecars::start(db_arc.clone());
+    crate::media::exif_cache::start(db_arc.clone());
+    crate::library::upkeep::start(db_arc.clone());
+    crate::library::auto_collections::start(db_arc.clone());
+    crate::library::smart_folder_history::start(db_arc.clone());
+    crate::library::sync::start(db_arc.clone(), job_queue.clone());
+    crate::library::ingest::start(app.clone());
+    crate::peer::server::apply(db_arc.clone(), &app_data).await;
+
+    // Start Watchers for Existing Roots
+    advance(&app, StartupStage::Watchers);
+    match db_arc.get_all_root_folders().await {
+        Ok(roots) => {
+            println!("INFO: Starting watchers for {} roots", roots.len());
+            for (_id, path) in roots {
+                // Remote mirrors follow their listing, not the disk
+                if crate::storage::is_remote_path(&path) {
+                    continue;
+                }
+                let indexer = Indexer::new(app.clone(), &db_arc, watcher_registry.clone());
+                let root_path = crate::paths::from_db(&path);
+                indexer.start_scan(root_path).await;
+            }
+        }
+        Err(e) => eprintln!("WARN: Could not list the library roots to watch: {}", e),
+    }
+
+    advance(&app, StartupStage::Ready);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_a_failed_startup_can_be_retried() {
+        let state = StartupState::new(Path::new("/library/mundam.db"));
+        assert!(!state.begin_retry(Path::new("/other/mundam.db")), "already starting");
+
+        state.set_stage(StartupStage::Ready, None);
+        assert!(!state.begin_retry(Path::new("/other/mundam.db")), "already open");
+
+        state.set_stage(StartupStage::Failed, Some("disk full".to_string()));
+        assert_eq!(state.status().error.as_deref(), Some("disk full"));
+        assert!(state.begin_retry(Path::new("/other/mundam.db")));
+
+        let status = state.status();
+        assert_eq!(status.stage, StartupStage::Starting);
+        assert_eq!(status.db_path, "/other/mundam.db");
+        assert_eq!(status.error, None);
+        assert!(!state.begin_retry(Path::new("/other/mundam.db")), "retry already running");
+    }
+}
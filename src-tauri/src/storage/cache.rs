@@ -0,0 +1,93 @@
+//! Size limit of the remote mirrors.
+//!
+//! The modification time of a mirrored file is bumped whenever it is used,
+//! so evicting the oldest files first drops the least recently used ones.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use walkdir::WalkDir;
+
+/// Marks a mirrored file as just used.
+pub fn touch(path: &Path) {
+    if let Ok(file) = std::fs::File::options().write(true).open(path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+/// Files to delete, oldest first, so that `files` fit in `max_bytes`.
+pub fn pick_evictions(mut files: Vec<(PathBuf, u64, SystemTime)>, max_bytes: u64) -> Vec<PathBuf> {
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    files.sort_by_key(|(_, _, used)| *used);
+    let mut evicted = Vec::new();
+    for (path, size, _) in files {
+        if total <= max_bytes {
+            break;
+        }
+        total -= size;
+        evicted.push(path);
+    }
+    evicted
+}
+
+/// Files of the mirror at `dir` with their size and last use. Also used for
+/// the model cache (see `thumbnails::model`).
+pub(crate) fn inventory(dir: &Path) -> Vec<(PathBuf, u64, SystemTime)> {
+    WalkDir::new(dir)
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.file_type().is_file())
+        // Downloads in progress
+        .filter(|entry| !entry.path().extension().is_some_and(|e| e == "part"))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some((entry.into_path(), metadata.len(), metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)))
+        })
+        .collect()
+}
+
+/// Bytes used by the mirror at `dir`.
+pub async fn usage(dir: &Path) -> u64 {
+    let dir = dir.to_path_buf();
+    tokio::task::spawn_blocking(move || inventory(&dir).iter().map(|(_, size, _)| size).sum())
+        .await
+        .unwrap_or(0)
+}
+
+/// Evicts the least recently used files of the mirror at `dir` until it fits
+/// in `max_bytes`.
+pub async fn enforce_limit(dir: &Path, max_bytes: i64) {
+    let dir = dir.to_path_buf();
+    let max_bytes = max_bytes.max(0) as u64;
+    let _ = tokio::task::spawn_blocking(move || {
+        let evicted = pick_evictions(inventory(&dir), max_bytes);
+        for path in &evicted {
+            if let Err(e) = std::fs::remove_file(path) {
+                eprintln!("WARN: Failed to evict {:?} from the remote cache: {}", path, e);
+            }
+        }
+        if !evicted.is_empty() {
+            println!("INFO: Evicted {} files from the remote cache {:?}", evicted.len(), dir);
+        }
+    })
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_pick_evictions() {
+        let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let files = vec![
+            (PathBuf::from("new"), 40, at(300)),
+            (PathBuf::from("old"), 50, at(100)),
+            (PathBuf::from("mid"), 30, at(200)),
+        ];
+        assert_eq!(pick_evictions(files.clone(), 70), vec![PathBuf::from("old")]);
+        assert_eq!(pick_evictions(files.clone(), 30), vec![PathBuf::from("old"), PathBuf::from("mid")]);
+        assert!(pick_evictions(files, 120).is_empty());
+    }
+}
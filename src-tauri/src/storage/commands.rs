@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use tauri::State;
+
+use super::{cache, secrets, RemoteConfig, RemoteRefreshReport};
+use crate::db::models::RemoteLocation;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+
+/// Cache limit of new remote locations, in megabytes.
+const DEFAULT_CACHE_MAX_MB: i64 = 2048;
+
+async fn get_remote(db: &Db, id: i64) -> AppResult<RemoteLocation> {
+    db.get_remote_location(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Remote location {} not found", id)))
+}
+
+#[tauri::command]
+pub async fn get_remote_locations(db: State<'_, Arc<Db>>) -> AppResult<Vec<RemoteLocation>> {
+    Ok(db.get_remote_locations().await?)
+}
+
+/// Adds an S3 (`{ kind: "s3", endpoint, region, bucket, prefix?,
+/// accessKeyId, secretAccessKey, pathStyle? }`) or WebDAV (`{ kind:
+/// "webdav", url, username?, password? }`) location and lists it. The
+/// password or secret key goes to the OS keychain. Nothing is kept if the
+/// first listing fails.
+#[tauri::command]
+pub async fn add_remote_location(
+    db: State<'_, Arc<Db>>,
+    name: String,
+    config: RemoteConfig,
+    cache_max_mb: Option<i64>,
+) -> AppResult<RemoteLocation> {
+    let mut config = config;
+    let secret_ref = match config.take_secret() {
+        Some(secret) => {
+            let secret_ref = secrets::new_ref();
+            secrets::store(&secret_ref, secret).await?;
+            Some(secret_ref)
+        }
+        None => None,
+    };
+    let config_json = config.to_stored_json()?;
+    let id = db
+        .create_remote_location(&name, config.kind(), &config_json, secret_ref.as_deref(), cache_max_mb.unwrap_or(DEFAULT_CACHE_MAX_MB))
+        .await?;
+    if let Err(e) = super::refresh(&db, id).await {
+        db.delete_remote_location(id).await?;
+        if let Some(secret_ref) = &secret_ref {
+            secrets::delete(secret_ref).await?;
+        }
+        return Err(e);
+    }
+    get_remote(&db, id).await
+}
+
+/// Lists a remote location again, adding, updating and removing images.
+#[tauri::command]
+pub async fn refresh_remote_location(db: State<'_, Arc<Db>>, id: i64) -> AppResult<RemoteRefreshReport> {
+    super::refresh(&db, id).await
+}
+
+/// Removes a remote location, its images and its local cache. The bucket or
+/// share is not touched.
+#[tauri::command]
+pub async fn remove_remote_location(db: State<'_, Arc<Db>>, id: i64) -> AppResult<()> {
+    get_remote(&db, id).await?;
+    let secret_ref = db.get_remote_location_config(id).await?.and_then(|(_, _, secret_ref)| secret_ref);
+    db.delete_remote_location(id).await?;
+    if let Some(secret_ref) = secret_ref {
+        if let Err(e) = secrets::delete(&secret_ref).await {
+            eprintln!("WARN: Failed to delete the credentials of remote location {}: {}", id, e);
+        }
+    }
+    let dir = super::mirror_dir(id)?;
+    if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+        eprintln!("WARN: Failed to delete the remote cache {:?}: {}", dir, e);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_remote_cache_limit(db: State<'_, Arc<Db>>, id: i64, cache_max_mb: i64) -> AppResult<()> {
+    get_remote(&db, id).await?;
+    db.set_remote_location_cache_limit(id, cache_max_mb).await?;
+    cache::enforce_limit(&super::mirror_dir(id)?, cache_max_mb * 1024 * 1024).await;
+    Ok(())
+}
+
+/// Bytes used by the local cache of a remote location.
+#[tauri::command]
+pub async fn get_remote_cache_usage(id: i64) -> AppResult<u64> {
+    Ok(cache::usage(&super::mirror_dir(id)?).await)
+}
+
+/// Empties the local cache of a remote location. Thumbnails are kept.
+#[tauri::command]
+pub async fn clear_remote_cache(id: i64) -> AppResult<()> {
+    cache::enforce_limit(&super::mirror_dir(id)?, 0).await;
+    Ok(())
+}
+
+/// Makes sure the file of an image is on disk, downloading it from its
+/// remote location if needed, and returns its path. Called before opening a
+/// full-size preview.
+#[tauri::command]
+pub async fn fetch_remote_file(db: State<'_, Arc<Db>>, image_id: i64) -> AppResult<String> {
+    let path = db
+        .get_image_path(image_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Image {} not found", image_id)))?;
+    if !super::ensure_local(&db, &path).await? {
+        return Err(AppError::NotFound(format!("File not found: {}", path)));
+    }
+    Ok(path)
+}
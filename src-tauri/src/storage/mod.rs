@@ -0,0 +1,400 @@
+//! Remote locations backed by object storage.
+//!
+//! A remote location points at an S3 bucket (or any S3-compatible service)
+//! or a WebDAV share through a [`StorageProvider`]. Its files are listed
+//! through the provider's API and indexed as if they lived in a local mirror
+//! folder under the app data dir (`remote/<id>/<key>`), which is the
+//! location's root folder. Nothing is downloaded up front: files are fetched
+//! into the mirror when a thumbnail or a preview needs them, and the least
+//! recently used ones are evicted once the location's cache is over its
+//! size limit. Thumbnails survive eviction, so a browsed library stays
+//! browsable.
+//!
+//! Remote roots are never scanned or watched like local ones; the listing
+//! is refreshed with `refresh_remote_location`.
+
+pub mod cache;
+pub mod commands;
+pub mod s3;
+pub mod secrets;
+pub mod webdav;
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::ImageMetadata;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::formats::FileFormat;
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A file listed by a provider.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteEntry {
+    /// Path of the file in the bucket or share, with `/` separators and no
+    /// leading slash.
+    pub key: String,
+    pub size: i64,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+/// Access to a bucket or share.
+pub trait StorageProvider: Send + Sync {
+    /// Lists every file of the location.
+    fn list(&self) -> BoxFuture<'_, AppResult<Vec<RemoteEntry>>>;
+
+    /// Downloads the file at `key` to `target`.
+    fn download<'a>(&'a self, key: &'a str, target: &'a Path) -> BoxFuture<'a, AppResult<()>>;
+}
+
+/// Connection settings of a remote location, stored as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum RemoteConfig {
+    S3(s3::S3Config),
+    WebDav(webdav::WebDavConfig),
+}
+
+impl RemoteConfig {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RemoteConfig::S3(_) => "s3",
+            RemoteConfig::WebDav(_) => "webdav",
+        }
+    }
+
+    /// Takes the password or secret key out of the settings, `None` if
+    /// they hold none.
+    pub fn take_secret(&mut self) -> Option<String> {
+        let secret = match self {
+            RemoteConfig::S3(config) => std::mem::take(&mut config.secret_access_key),
+            RemoteConfig::WebDav(config) => config.password.take().unwrap_or_default(),
+        };
+        (!secret.is_empty()).then_some(secret)
+    }
+
+    /// Puts the password or secret key loaded from the keychain back.
+    pub fn set_secret(&mut self, secret: String) {
+        match self {
+            RemoteConfig::S3(config) => config.secret_access_key = secret,
+            RemoteConfig::WebDav(config) => config.password = Some(secret),
+        }
+    }
+
+    /// The settings as stored in the database, without credentials.
+    pub fn to_stored_json(&self) -> AppResult<String> {
+        let mut stored = self.clone();
+        stored.take_secret();
+        serde_json::to_string(&stored).map_err(|e| AppError::Internal(e.to_string()))
+    }
+
+    pub fn provider(&self) -> Box<dyn StorageProvider> {
+        match self {
+            RemoteConfig::S3(config) => Box::new(s3::S3Provider::new(config.clone())),
+            RemoteConfig::WebDav(config) => Box::new(webdav::WebDavProvider::new(config.clone())),
+        }
+    }
+}
+
+static MIRROR_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Sets the folder holding the mirrors of remote locations. Called once at
+/// startup.
+pub fn init(app_data: &Path) {
+    let _ = MIRROR_ROOT.set(app_data.join("remote"));
+}
+
+/// Folder holding the mirrors of all remote locations.
+pub(crate) fn mirror_root() -> AppResult<&'static Path> {
+    MIRROR_ROOT
+        .get()
+        .map(PathBuf::as_path)
+        .ok_or_else(|| AppError::Internal("Remote storage is not initialized".to_string()))
+}
+
+/// Mirror folder of a remote location, its root folder in the library.
+pub fn mirror_dir(remote_id: i64) -> AppResult<PathBuf> {
+    Ok(mirror_root()?.join(remote_id.to_string()))
+}
+
+/// Whether every segment of a `/` separated key is a plain file or folder
+/// name. Keys come from the server, which could otherwise send `..`, `C:`
+/// or an absolute path to write outside the mirror.
+pub fn is_safe_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.split('/').all(|part| {
+            let mut components = Path::new(part).components();
+            matches!(
+                (components.next(), components.next()),
+                (Some(Component::Normal(name)), None) if name == std::ffi::OsStr::new(part)
+            )
+        })
+}
+
+/// Local path of `key` in the mirror of a remote location.
+pub fn mirror_path(remote_id: i64, key: &str) -> AppResult<PathBuf> {
+    let dir = mirror_dir(remote_id)?;
+    let path = key.split('/').fold(dir.clone(), |path, part| path.join(part));
+    if !is_safe_key(key) || !path.starts_with(&dir) {
+        return Err(AppError::Remote(format!("Refused the unsafe remote key '{}'", key)));
+    }
+    Ok(path)
+}
+
+/// Remote location and key of a path inside a mirror, `None` for local paths
+/// and paths that would step out of the mirror.
+pub fn split_mirror_path(path: &Path) -> Option<(i64, String)> {
+    let relative = path.strip_prefix(MIRROR_ROOT.get()?).ok()?;
+    if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    let mut parts = relative.iter().map(|p| p.to_string_lossy());
+    let remote_id = parts.next()?.parse().ok()?;
+    let key = parts.collect::<Vec<_>>().join("/");
+    (!key.is_empty()).then_some((remote_id, key))
+}
+
+/// Whether `path` is a remote mirror, or inside one.
+pub fn is_remote_path(path: &str) -> bool {
+    MIRROR_ROOT.get().is_some_and(|root| Path::new(path).starts_with(root))
+}
+
+/// Connection settings, credentials included, and cache limit (MB) of a
+/// remote location.
+async fn load_config(db: &Db, remote_id: i64) -> AppResult<(RemoteConfig, i64)> {
+    let (config, cache_max_mb, secret_ref) = db
+        .get_remote_location_config(remote_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Remote location {} not found", remote_id)))?;
+    let mut config: RemoteConfig = serde_json::from_str(&config)
+        .map_err(|e| AppError::Internal(format!("Invalid remote location config: {}", e)))?;
+
+    if let Some(secret_ref) = secret_ref {
+        if let Some(secret) = secrets::load(&secret_ref).await? {
+            config.set_secret(secret);
+        }
+    }
+    Ok((config, cache_max_mb))
+}
+
+/// Makes sure the file at `path` is on disk, downloading it if it belongs
+/// to a remote location. Returns `false` if it's missing and not remote.
+pub async fn ensure_local(db: &Db, path: &str) -> AppResult<bool> {
+    let local = Path::new(path);
+    if tokio::fs::try_exists(local).await.unwrap_or(false) {
+        cache::touch(local);
+        return Ok(true);
+    }
+    let Some((remote_id, key)) = split_mirror_path(local) else {
+        return Ok(false);
+    };
+    let (config, cache_max_mb) = load_config(db, remote_id).await?;
+
+    if let Some(parent) = local.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    config.provider().download(&key, local).await?;
+    cache::enforce_limit(&mirror_dir(remote_id)?, cache_max_mb * 1024 * 1024).await;
+    Ok(true)
+}
+
+/// Downloads the remote files among `paths` that aren't cached. Failures are
+/// logged; the caller sees the file as missing.
+pub async fn fetch_missing<'a>(db: &Db, paths: impl IntoIterator<Item = &'a str>) {
+    for path in paths.into_iter().filter(|p| is_remote_path(p)) {
+        if let Err(e) = ensure_local(db, path).await {
+            eprintln!("WARN: Failed to fetch remote file {}: {}", path, e);
+        }
+    }
+}
+
+/// Outcome of a listing refresh.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteRefreshReport {
+    /// Supported files in the listing.
+    pub files: usize,
+    pub added: usize,
+    pub changed: usize,
+    pub removed: usize,
+}
+
+
+/// Lists a remote location and brings its images in line with the listing.
+/// Changed files lose their cached copy and thumbnail.
+pub async fn refresh(db: &Db, remote_id: i64) -> AppResult<RemoteRefreshReport> {
+    let (config, _) = load_config(db, remote_id).await?;
+    let entries: Vec<RemoteEntry> = config
+        .provider()
+        .list()
+        .await?
+        .into_iter()
+        .filter(|entry| FileFormat::is_supported_extension(Path::new(&entry.key)))
+        .filter(|entry| {
+            let safe = is_safe_key(&entry.key);
+            if !safe {
+                eprintln!("WARN: Skipped the unsafe key '{}' listed by remote location {}", entry.key, remote_id);
+            }
+            safe
+        })
+        .collect();
+
+    let root = mirror_dir(remote_id)?;
+    tokio::fs::create_dir_all(&root).await?;
+    let root_str = root.to_string_lossy().to_string();
+
+    let mut report = RemoteRefreshReport { files: entries.len(), ..Default::default() };
+    let mut folders: HashSet<String> = HashSet::from([root_str.clone()]);
+    for entry in &entries {
+        let mut parent = mirror_path(remote_id, &entry.key)?;
+        while parent.pop() && parent.starts_with(&root) && parent != root {
+            folders.insert(parent.to_string_lossy().to_string());
+        }
+    }
+    let folder_ids = crate::indexer::scan::ensure_folder_hierarchy(db, folders, &root_str)
+        .await
+        .map_err(AppError::Internal)?;
+    if let Some(&folder_id) = folder_ids.get(&root_str) {
+        db.set_remote_location_folder(remote_id, folder_id).await?;
+    }
+
+    let existing = db.get_all_files_comparison_data(&format!("{}{}", root_str, std::path::MAIN_SEPARATOR)).await?;
+    let mut listed = HashSet::with_capacity(entries.len());
+    let mut batch = Vec::new();
+    let mut changed = Vec::new();
+
+    for entry in entries {
+        let path = mirror_path(remote_id, &entry.key)?;
+        let path_str = path.to_string_lossy().to_string();
+        listed.insert(path_str.clone());
+        let modified = entry.modified.unwrap_or_else(Utc::now);
+
+        match existing.get(&path_str) {
+            Some(&(size, at)) => {
+                let same_time = match entry.modified {
+                    Some(listed_at) => listed_at.timestamp() == at.timestamp(),
+                    None => true,
+                };
+                if size == entry.size && same_time {
+                    continue;
+                }
+                changed.push(path_str.clone());
+            }
+            None => report.added += 1,
+        }
+        let Some(&folder_id) = path.parent().and_then(|p| folder_ids.get(p.to_string_lossy().as_ref())) else {
+            continue;
+        };
+        let filename = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let format = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        batch.push((folder_id, ImageMetadata {
+            id: 0,
+            path: path_str,
+            filename,
+            width: None,
+            height: None,
+            size: entry.size,
+            format,
+            thumbnail_path: None,
+            rating: 0,
+            notes: None,
+            modified_at: modified,
+            created_at: modified,
+            added_at: None,
+            playback_position: None,
+            playback_completed: false,
+            sequence_id: None,
+            latitude: None,
+            longitude: None,
+            duration: None,
+            frame_count: None,
+            loops: None,
+            captured_at: None,
+            capture_offset: None,
+        }));
+    }
+
+    db.save_images_batch(batch).await?;
+    for path in changed.iter().filter(|path| split_mirror_path(Path::new(path)).is_some()) {
+        let _ = tokio::fs::remove_file(path).await;
+    }
+    db.clear_thumbnails_by_paths(&changed).await?;
+    report.changed = changed.len();
+
+    let removed: Vec<String> = existing.into_keys().filter(|path| !listed.contains(path)).collect();
+    for path in removed.iter().filter(|path| split_mirror_path(Path::new(path)).is_some()) {
+        let _ = tokio::fs::remove_file(path).await;
+    }
+    db.delete_images_by_paths(&removed).await?;
+    report.removed = removed.len();
+
+    db.mark_remote_location_listed(remote_id).await?;
+    println!(
+        "INFO: Refreshed remote location {}: {} files, {} added, {} changed, {} removed",
+        remote_id, report.files, report.added, report.changed, report.removed
+    );
+    Ok(report)
+}
+
+/// Percent-encodes a path segment or query value (RFC 3986 unreserved
+/// characters are kept, as S3 and WebDAV servers expect).
+pub fn encode_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Encodes every segment of a `/` separated key.
+pub fn encode_key(key: &str) -> String {
+    key.split('/').map(encode_component).collect::<Vec<_>>().join("/")
+}
+
+/// Streams a response body to `target` through a `.part` file.
+pub(crate) async fn write_response(mut response: tauri_plugin_http::reqwest::Response, target: &Path) -> AppResult<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut partial_name = target.file_name().unwrap_or_default().to_os_string();
+    partial_name.push(".part");
+    let partial = target.with_file_name(partial_name);
+
+    let mut file = tokio::fs::File::create(&partial).await?;
+    while let Some(chunk) = response.chunk().await.map_err(|e| AppError::Remote(e.to_string()))? {
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    drop(file);
+    tokio::fs::rename(&partial, target).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsafe_keys() {
+        assert!(is_safe_key("2024/Trip photos/a.jpg"));
+        assert!(is_safe_key("..hidden/a..b.jpg"));
+        for key in ["", "../etc/passwd", "a/../../b.jpg", "/etc/passwd", "a//b.jpg", "./a.jpg", "a/."] {
+            assert!(!is_safe_key(key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_encode_key() {
+        assert_eq!(encode_key("2024/Trip photos/é 1+1.jpg"), "2024/Trip%20photos/%C3%A9%201%2B1.jpg");
+        assert_eq!(encode_component("a/b~c"), "a%2Fb~c");
+    }
+}
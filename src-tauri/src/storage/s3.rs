@@ -0,0 +1,300 @@
+//! S3 provider: AWS S3 and compatible services (MinIO, R2, B2, Wasabi...).
+//!
+//! Requests are signed with Signature Version 4 and an unsigned payload,
+//! which every S3-compatible service accepts for GET requests.
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri_plugin_http::reqwest::{Client, Url};
+
+use super::{encode_component, encode_key, write_response, BoxFuture, RemoteEntry, StorageProvider};
+use crate::error::{AppError, AppResult};
+
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct S3Config {
+    /// Service URL, such as `https://s3.eu-west-1.amazonaws.com`.
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    /// Only keys below this prefix are part of the location.
+    #[serde(default)]
+    pub prefix: String,
+    pub access_key_id: String,
+    /// Kept in the keychain, empty in the stored settings (see
+    /// `storage::secrets`).
+    #[serde(default)]
+    pub secret_access_key: String,
+    /// `endpoint/bucket/key` URLs instead of `bucket.endpoint/key`, as most
+    /// self-hosted services expect.
+    #[serde(default)]
+    pub path_style: bool,
+}
+
+pub struct S3Provider {
+    config: S3Config,
+    client: Client,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Signature Version 4 signing key.
+pub fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(format!("AWS4{}", secret).as_bytes(), date);
+    let key = hmac(&key, region);
+    let key = hmac(&key, service);
+    hmac(&key, "aws4_request")
+}
+
+/// `host[:port]` as sent in the Host header.
+fn host_header(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    }
+}
+
+/// Canonical request of a GET with an unsigned payload. The URL path and
+/// query must already be encoded, with the query parameters sorted.
+pub fn canonical_request(url: &Url, amz_date: &str) -> String {
+    format!(
+        "GET\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\nhost;x-amz-content-sha256;x-amz-date\n{}",
+        url.path(),
+        url.query().unwrap_or(""),
+        host_header(url),
+        UNSIGNED_PAYLOAD,
+        amz_date,
+        UNSIGNED_PAYLOAD
+    )
+}
+
+/// Headers authenticating a GET of `url` at `now`.
+pub fn sign(config: &S3Config, url: &Url, now: DateTime<Utc>) -> Vec<(&'static str, String)> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let scope = format!("{}/{}/s3/aws4_request", date, config.region);
+
+    let request_hash = hex(&Sha256::digest(canonical_request(url, &amz_date).as_bytes()));
+    let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", amz_date, scope, request_hash);
+    let signature = hex(&hmac(&signing_key(&config.secret_access_key, &date, &config.region, "s3"), &string_to_sign));
+
+    vec![
+        ("x-amz-date", amz_date),
+        ("x-amz-content-sha256", UNSIGNED_PAYLOAD.to_string()),
+        (
+            "authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={}",
+                config.access_key_id, scope, signature
+            ),
+        ),
+    ]
+}
+
+/// One page of a ListObjectsV2 response.
+#[derive(Debug, Default, PartialEq)]
+pub struct ListPage {
+    pub entries: Vec<RemoteEntry>,
+    pub next_token: Option<String>,
+}
+
+/// Parses a ListObjectsV2 response. Keys are made relative to `prefix`, and
+/// folder markers (keys ending with `/`) are skipped.
+pub fn parse_list(xml: &str, prefix: &str) -> ListPage {
+    let mut page = ListPage::default();
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut element = String::new();
+    let mut key = String::new();
+    let mut size = 0;
+    let mut modified = None;
+    let mut truncated = false;
+    let mut token = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => {
+                element = String::from_utf8_lossy(start.local_name().as_ref()).to_string();
+                if element == "Contents" {
+                    key.clear();
+                    size = 0;
+                    modified = None;
+                }
+            }
+            Ok(Event::Text(text)) => {
+                let value = text.unescape().map(|v| v.to_string()).unwrap_or_default();
+                match element.as_str() {
+                    "Key" => key = value,
+                    "Size" => size = value.parse().unwrap_or(0),
+                    "LastModified" => modified = DateTime::parse_from_rfc3339(&value).ok().map(|d| d.with_timezone(&Utc)),
+                    "IsTruncated" => truncated = value == "true",
+                    "NextContinuationToken" => token = Some(value),
+                    _ => {}
+                }
+            }
+            Ok(Event::End(end)) => {
+                if end.local_name().as_ref() == b"Contents" && !key.ends_with('/') {
+                    if let Some(relative) = key.strip_prefix(prefix) {
+                        let relative = relative.trim_start_matches('/');
+                        if !relative.is_empty() {
+                            page.entries.push(RemoteEntry { key: relative.to_string(), size, modified });
+                        }
+                    }
+                }
+                element.clear();
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    page.next_token = token.filter(|_| truncated);
+    page
+}
+
+impl S3Provider {
+    pub fn new(config: S3Config) -> Self {
+        Self { config, client: Client::new() }
+    }
+
+    /// URL of `key` (encoded, may be empty for the bucket) with the given
+    /// query, whose parameters must be sorted.
+    fn url(&self, key: &str, query: &[(&str, &str)]) -> AppResult<Url> {
+        let endpoint = Url::parse(self.config.endpoint.trim_end_matches('/'))
+            .map_err(|e| AppError::Remote(format!("Invalid endpoint '{}': {}", self.config.endpoint, e)))?;
+        let base = if self.config.path_style {
+            format!("{}/{}", endpoint.as_str().trim_end_matches('/'), encode_component(&self.config.bucket))
+        } else {
+            format!("{}://{}.{}", endpoint.scheme(), self.config.bucket, host_header(&endpoint))
+        };
+        let query: Vec<String> = query
+            .iter()
+            .map(|(name, value)| format!("{}={}", encode_component(name), encode_component(value)))
+            .collect();
+        let url = if query.is_empty() {
+            format!("{}/{}", base, key)
+        } else {
+            format!("{}/{}?{}", base, key, query.join("&"))
+        };
+        Url::parse(&url).map_err(|e| AppError::Remote(e.to_string()))
+    }
+
+    async fn get(&self, url: Url) -> AppResult<tauri_plugin_http::reqwest::Response> {
+        let mut request = self.client.get(url.clone());
+        for (name, value) in sign(&self.config, &url, Utc::now()) {
+            request = request.header(name, value);
+        }
+        let response = request.send().await.map_err(|e| AppError::Remote(e.to_string()))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(AppError::Remote(format!("S3 returned {} for {}: {}", status, url.path(), body.trim())));
+        }
+        Ok(response)
+    }
+
+    /// Full key of a location-relative key.
+    fn full_key(&self, key: &str) -> String {
+        let prefix = self.config.prefix.trim_matches('/');
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}/{}", prefix, key)
+        }
+    }
+}
+
+impl StorageProvider for S3Provider {
+    fn list(&self) -> BoxFuture<'_, AppResult<Vec<RemoteEntry>>> {
+        Box::pin(async move {
+            let prefix = self.config.prefix.trim_matches('/').to_string();
+            let list_prefix = if prefix.is_empty() { String::new() } else { format!("{}/", prefix) };
+            let mut entries = Vec::new();
+            let mut token: Option<String> = None;
+            loop {
+                let mut query = vec![];
+                if let Some(token) = &token {
+                    query.push(("continuation-token", token.as_str()));
+                }
+                query.push(("list-type", "2"));
+                if !list_prefix.is_empty() {
+                    query.push(("prefix", list_prefix.as_str()));
+                }
+                let body = self
+                    .get(self.url("", &query)?)
+                    .await?
+                    .text()
+                    .await
+                    .map_err(|e| AppError::Remote(e.to_string()))?;
+                let page = parse_list(&body, &list_prefix);
+                entries.extend(page.entries);
+                match page.next_token {
+                    Some(next) => token = Some(next),
+                    None => break,
+                }
+            }
+            Ok(entries)
+        })
+    }
+
+    fn download<'a>(&'a self, key: &'a str, target: &'a Path) -> BoxFuture<'a, AppResult<()>> {
+        Box::pin(async move {
+            let response = self.get(self.url(&encode_key(&self.full_key(key)), &[])?).await?;
+            write_response(response, target).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signing_key() {
+        // Example from the AWS Signature Version 4 documentation
+        let key = signing_key("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "20120215", "us-east-1", "iam");
+        assert_eq!(hex(&key), "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d");
+    }
+
+    #[test]
+    fn test_canonical_request() {
+        let url = Url::parse("http://localhost:9000/photos?list-type=2&prefix=2024%2F").unwrap();
+        let request = canonical_request(&url, "20240101T000000Z");
+        assert!(request.starts_with("GET\n/photos\nlist-type=2&prefix=2024%2F\nhost:localhost:9000\n"));
+        assert!(request.ends_with("\nhost;x-amz-content-sha256;x-amz-date\nUNSIGNED-PAYLOAD"));
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+              <IsTruncated>true</IsTruncated>
+              <Contents><Key>shoot/</Key><Size>0</Size></Contents>
+              <Contents><Key>shoot/a &amp; b.jpg</Key><LastModified>2024-05-01T10:00:00.000Z</LastModified><Size>1234</Size></Contents>
+              <NextContinuationToken>abc</NextContinuationToken>
+            </ListBucketResult>"#;
+        let page = parse_list(xml, "shoot/");
+        assert_eq!(page.next_token.as_deref(), Some("abc"));
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].key, "a & b.jpg");
+        assert_eq!(page.entries[0].size, 1234);
+        assert!(page.entries[0].modified.is_some());
+    }
+}
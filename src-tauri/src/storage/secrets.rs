@@ -0,0 +1,54 @@
+//! Credentials of remote locations, kept in the OS keychain (Keychain on
+//! macOS, Credential Manager on Windows, Secret Service on Linux) instead of
+//! the library database.
+//!
+//! Each location's password or secret key is one entry, named by a random
+//! reference stored with the location, so two libraries never share one.
+
+use crate::error::{AppError, AppResult};
+
+const SERVICE: &str = "Mundam remote location";
+
+fn keychain_error(e: keyring::Error) -> AppError {
+    AppError::Remote(format!("Keychain: {}", e))
+}
+
+async fn with_entry<T: Send + 'static>(
+    secret_ref: &str,
+    task: impl FnOnce(keyring::Entry) -> keyring::Result<T> + Send + 'static,
+) -> AppResult<T> {
+    let secret_ref = secret_ref.to_string();
+    tokio::task::spawn_blocking(move || keyring::Entry::new(SERVICE, &secret_ref).and_then(task))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(keychain_error)
+}
+
+/// A new, unused entry reference.
+pub fn new_ref() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Saves `secret` under `secret_ref`, replacing what was there.
+pub async fn store(secret_ref: &str, secret: String) -> AppResult<()> {
+    with_entry(secret_ref, move |entry| entry.set_password(&secret)).await
+}
+
+/// The secret saved under `secret_ref`, `None` if there is none.
+pub async fn load(secret_ref: &str) -> AppResult<Option<String>> {
+    with_entry(secret_ref, |entry| match entry.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e),
+    })
+    .await
+}
+
+/// Deletes the secret saved under `secret_ref`, if any.
+pub async fn delete(secret_ref: &str) -> AppResult<()> {
+    with_entry(secret_ref, |entry| match entry.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e),
+    })
+    .await
+}
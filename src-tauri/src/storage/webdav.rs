@@ -0,0 +1,226 @@
+//! WebDAV provider (Nextcloud, ownCloud, NAS shares...).
+//!
+//! Folders are listed one level at a time (`Depth: 1`), as many servers
+//! refuse infinite-depth PROPFIND requests.
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use serde::{Deserialize, Serialize};
+use tauri_plugin_http::reqwest::{Client, Method, RequestBuilder, Url};
+
+use super::{encode_key, write_response, BoxFuture, RemoteEntry, StorageProvider};
+use crate::error::{AppError, AppResult};
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/><d:getcontentlength/><d:getlastmodified/></d:prop></d:propfind>"#;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebDavConfig {
+    /// URL of the shared folder.
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Kept in the keychain, absent from the stored settings (see
+    /// `storage::secrets`).
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+pub struct WebDavProvider {
+    config: WebDavConfig,
+    client: Client,
+}
+
+/// One `<d:response>` of a multistatus body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DavResource {
+    /// Decoded path from the `href`.
+    pub path: String,
+    pub is_collection: bool,
+    pub size: i64,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+/// Decodes the `%XX` escapes of an href path.
+fn decode_href(href: &str) -> String {
+    // Servers may answer with absolute URLs
+    let path = match Url::parse(href) {
+        Ok(url) => url.path().to_string(),
+        Err(_) => href.to_string(),
+    };
+    urlencoding::decode(&path).map(|p| p.into_owned()).unwrap_or(path)
+}
+
+/// Parses a PROPFIND multistatus response.
+pub fn parse_multistatus(xml: &str) -> Vec<DavResource> {
+    let mut resources = Vec::new();
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut element = String::new();
+    let mut current: Option<DavResource> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => {
+                element = String::from_utf8_lossy(start.local_name().as_ref()).to_string();
+                match element.as_str() {
+                    "response" => {
+                        current = Some(DavResource { path: String::new(), is_collection: false, size: 0, modified: None });
+                    }
+                    "collection" => {
+                        if let Some(resource) = current.as_mut() {
+                            resource.is_collection = true;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Event::Empty(empty)) => {
+                if empty.local_name().as_ref() == b"collection" {
+                    if let Some(resource) = current.as_mut() {
+                        resource.is_collection = true;
+                    }
+                }
+            }
+            Ok(Event::Text(text)) => {
+                let Some(resource) = current.as_mut() else { continue };
+                let value = text.unescape().map(|v| v.to_string()).unwrap_or_default();
+                match element.as_str() {
+                    "href" => resource.path = decode_href(&value),
+                    "getcontentlength" => resource.size = value.parse().unwrap_or(0),
+                    "getlastmodified" => {
+                        resource.modified = DateTime::parse_from_rfc2822(&value).ok().map(|d| d.with_timezone(&Utc));
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Event::End(end)) => {
+                if end.local_name().as_ref() == b"response" {
+                    resources.extend(current.take());
+                }
+                element.clear();
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    resources
+}
+
+impl WebDavProvider {
+    pub fn new(config: WebDavConfig) -> Self {
+        Self { config, client: Client::new() }
+    }
+
+    fn base(&self) -> AppResult<Url> {
+        let url = format!("{}/", self.config.url.trim_end_matches('/'));
+        Url::parse(&url).map_err(|e| AppError::Remote(format!("Invalid WebDAV URL '{}': {}", self.config.url, e)))
+    }
+
+    fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        let request = self.client.request(method, url);
+        match &self.config.username {
+            Some(username) => request.basic_auth(username, self.config.password.as_ref()),
+            None => request,
+        }
+    }
+
+    /// Lists a folder, given relative to the share with a trailing `/`.
+    async fn propfind(&self, base: &Url, folder: &str) -> AppResult<Vec<DavResource>> {
+        let url = base.join(&encode_key(folder)).map_err(|e| AppError::Remote(e.to_string()))?;
+        let method = Method::from_bytes(b"PROPFIND").expect("valid method");
+        let response = self
+            .request(method, url.clone())
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(PROPFIND_BODY)
+            .send()
+            .await
+            .map_err(|e| AppError::Remote(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(AppError::Remote(format!("WebDAV server returned {} for {}", response.status(), url.path())));
+        }
+        let body = response.text().await.map_err(|e| AppError::Remote(e.to_string()))?;
+        Ok(parse_multistatus(&body))
+    }
+}
+
+impl StorageProvider for WebDavProvider {
+    fn list(&self) -> BoxFuture<'_, AppResult<Vec<RemoteEntry>>> {
+        Box::pin(async move {
+            let base = self.base()?;
+            let base_path = urlencoding::decode(base.path()).map(|p| p.into_owned()).unwrap_or_default();
+            let mut entries = Vec::new();
+            let mut pending = vec![String::new()];
+
+            while let Some(folder) = pending.pop() {
+                for resource in self.propfind(&base, &folder).await? {
+                    // Paths are absolute on the server; the share's own entry comes back too
+                    let Some(relative) = resource.path.strip_prefix(&base_path) else { continue };
+                    let relative = relative.trim_end_matches('/');
+                    if relative.is_empty() || relative == folder.trim_end_matches('/') {
+                        continue;
+                    }
+                    // `%2e%2e` decodes to `..`: never follow or list what leaves the share
+                    if !super::is_safe_key(relative) {
+                        eprintln!("WARN: Skipped the unsafe WebDAV path '{}'", resource.path);
+                        continue;
+                    }
+                    if resource.is_collection {
+                        pending.push(format!("{}/", relative));
+                    } else {
+                        entries.push(RemoteEntry { key: relative.to_string(), size: resource.size, modified: resource.modified });
+                    }
+                }
+            }
+            Ok(entries)
+        })
+    }
+
+    fn download<'a>(&'a self, key: &'a str, target: &'a Path) -> BoxFuture<'a, AppResult<()>> {
+        Box::pin(async move {
+            let url = self.base()?.join(&encode_key(key)).map_err(|e| AppError::Remote(e.to_string()))?;
+            let response = self.request(Method::GET, url).send().await.map_err(|e| AppError::Remote(e.to_string()))?;
+            if !response.status().is_success() {
+                return Err(AppError::Remote(format!("WebDAV server returned {} for {}", response.status(), key)));
+            }
+            write_response(response, target).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_multistatus() {
+        let xml = r#"<?xml version="1.0"?>
+            <d:multistatus xmlns:d="DAV:">
+              <d:response>
+                <d:href>/remote.php/dav/files/jo/Shoots/</d:href>
+                <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+              </d:response>
+              <d:response>
+                <d:href>/remote.php/dav/files/jo/Shoots/Day%201.jpg</d:href>
+                <d:propstat><d:prop>
+                  <d:resourcetype/>
+                  <d:getcontentlength>2048</d:getcontentlength>
+                  <d:getlastmodified>Wed, 01 May 2024 10:00:00 GMT</d:getlastmodified>
+                </d:prop></d:propstat>
+              </d:response>
+            </d:multistatus>"#;
+        let resources = parse_multistatus(xml);
+        assert_eq!(resources.len(), 2);
+        assert!(resources[0].is_collection);
+        assert_eq!(resources[1].path, "/remote.php/dav/files/jo/Shoots/Day 1.jpg");
+        assert_eq!(resources[1].size, 2048);
+        assert!(!resources[1].is_collection);
+        assert!(resources[1].modified.is_some());
+    }
+}
@@ -0,0 +1,156 @@
+//! Access control for the streaming server.
+//!
+//! The server listens on 127.0.0.1, which any local process can reach. Every
+//! request must therefore carry the token generated at startup, either as an
+//! `Authorization: Bearer` header or as a `token` query parameter (the video
+//! element can't set headers), and may only read files inside library roots.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use tauri::Manager;
+
+use crate::db::Db;
+
+/// Name of the query parameter carrying the token.
+pub const TOKEN_PARAM: &str = "token";
+
+static ACCESS_TOKEN: OnceLock<String> = OnceLock::new();
+
+/// Token for this app session, generated on first use.
+pub fn access_token() -> &'static str {
+    ACCESS_TOKEN.get_or_init(|| uuid::Uuid::new_v4().simple().to_string())
+}
+
+/// Rejects requests that don't present the session token.
+pub async fn require_token(req: Request, next: Next) -> Response {
+    let bearer = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::to_string);
+    let presented = bearer.or_else(|| req.uri().query().and_then(query_token));
+
+    match presented {
+        Some(token) if tokens_match(&token, access_token()) => next.run(req).await,
+        _ => Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .body(Body::from("Missing or invalid stream token"))
+            .unwrap(),
+    }
+}
+
+fn query_token(query: &str) -> Option<String> {
+    query.split('&').find_map(|pair| {
+        let value = pair.strip_prefix(TOKEN_PARAM)?.strip_prefix('=')?;
+        urlencoding::decode(value).ok().map(|v| v.into_owned())
+    })
+}
+
+/// Compares without returning early on the first differing byte.
+pub(crate) fn tokens_match(presented: &str, expected: &str) -> bool {
+    presented.len() == expected.len()
+        && presented
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Whether `file_path` lies inside one of the registered library roots.
+///
+/// Both sides are resolved first, so a link inside a root can't point
+/// outside it and a root reached through a symlink still holds its files.
+/// A root that can't be resolved doesn't exist and holds nothing.
+pub async fn is_path_allowed(app_handle: &tauri::AppHandle, file_path: &Path) -> bool {
+    let Some(db) = app_handle.try_state::<Arc<Db>>() else {
+        return false;
+    };
+    let roots = match db.get_all_root_folders().await {
+        Ok(roots) => roots,
+        Err(e) => {
+            eprintln!("WARN: Could not load library roots for stream access: {}", e);
+            return false;
+        }
+    };
+    let Ok(resolved) = tokio::fs::canonicalize(file_path).await else {
+        return false;
+    };
+
+    let mut resolved_roots: Vec<PathBuf> = Vec::with_capacity(roots.len());
+    for (_, path) in roots {
+        if let Ok(root) = tokio::fs::canonicalize(crate::paths::from_db(&path)).await {
+            resolved_roots.push(root);
+        }
+    }
+    is_within_roots(&resolved, &resolved_roots)
+}
+
+/// Whether `path` is one of `roots` or inside one, component by component
+/// (`/photos2` is not inside `/photos`). Both are canonical.
+fn is_within_roots(path: &Path, roots: &[PathBuf]) -> bool {
+    roots.iter().any(|root| path.starts_with(root))
+}
+
+/// Appends the token to every URI line of a playlist, so segment requests
+/// made by the player are authorized too.
+pub fn tokenize_playlist(playlist: &str) -> String {
+    let token = urlencoding::encode(access_token());
+    let mut out = String::with_capacity(playlist.len());
+    for line in playlist.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            out.push_str(line);
+        } else {
+            let separator = if trimmed.contains('?') { '&' } else { '?' };
+            out.push_str(&format!("{}{}{}={}", trimmed, separator, TOKEN_PARAM, token));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_token() {
+        assert_eq!(query_token("quality=high&token=abc"), Some("abc".to_string()));
+        assert_eq!(query_token("tokenx=abc"), None);
+        assert_eq!(query_token("quality=high"), None);
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("abc", "abc"));
+        assert!(!tokens_match("abd", "abc"));
+        assert!(!tokens_match("ab", "abc"));
+    }
+
+    #[test]
+    fn test_is_within_roots() {
+        let roots = vec![PathBuf::from("/library/photos")];
+        assert!(is_within_roots(Path::new("/library/photos/a/b.mp4"), &roots));
+        assert!(!is_within_roots(Path::new("/library/photos-private/b.mp4"), &roots));
+        assert!(!is_within_roots(Path::new("/etc/passwd"), &roots));
+    }
+
+    #[test]
+    fn test_tokenize_playlist() {
+        let playlist = "#EXTM3U\n#EXTINF:4.000,\nsegment_00000.ts\n#EXTINF:4.000,\n/segment/a/1.ts?quality=high\n";
+        let out = tokenize_playlist(playlist);
+        let token = access_token();
+        assert!(out.contains(&format!("segment_00000.ts?token={}\n", token)));
+        assert!(out.contains(&format!("/segment/a/1.ts?quality=high&token={}\n", token)));
+        assert!(out.starts_with("#EXTM3U\n#EXTINF:4.000,\n"));
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::path::{Path, PathBuf};
 
 use std::sync::Arc;
@@ -7,6 +7,23 @@ use tokio::process::{Child, Command};
 use tokio::sync::RwLock;
 
 use crate::media::ffmpeg::get_ffmpeg_path;
+use super::process_manager::{self, JobKind};
+
+/// Duration of each linear HLS segment in seconds.
+const LINEAR_SEGMENT_SECS: f64 = 4.0;
+
+/// Segments ahead of the encoder a request may be before we restart FFmpeg
+/// at the requested position instead of waiting for it.
+const SEEK_LOOKAHEAD: i64 = 3;
+
+/// How long a segment request waits for FFmpeg to produce it.
+const SEGMENT_WAIT: Duration = Duration::from_secs(15);
+
+/// Playlist served to the player.
+const PLAYLIST: &str = "index.m3u8";
+
+/// Playlist FFmpeg writes in seekable sessions, used to track finished segments.
+const FFMPEG_PLAYLIST: &str = "ffmpeg.m3u8";
 
 /// Manage linear transcoding sessions (Live HLS)
 #[derive(Clone)]
@@ -16,11 +33,45 @@ pub struct LinearManager {
 }
 
 struct LinearSession {
-    #[allow(dead_code)]
     process_id: Option<u32>,
     temp_dir: PathBuf,
     last_access: Instant,
     child: Option<Child>,
+    quality: String,
+    is_audio: bool,
+    /// Number of segments in the published playlist; `None` for live playlists.
+    total_segments: Option<u32>,
+    /// First segment written by the current FFmpeg process.
+    run_start: u32,
+    /// Segment at which the current process stops (exclusive).
+    run_end: u32,
+    /// Segments fully written by any run of this session.
+    completed: BTreeSet<u32>,
+    /// Set while the current process is being stopped for a seek, so other
+    /// requests wait for the new one instead of starting their own.
+    restarting: bool,
+}
+
+impl LinearSession {
+    fn is_running(&mut self) -> bool {
+        matches!(self.child.as_mut().map(|c| c.try_wait()), Some(Ok(None)))
+    }
+
+    /// Highest finished segment of the current run, or `run_start - 1`.
+    fn produced_head(&self) -> i64 {
+        self.completed
+            .range(self.run_start..self.run_end)
+            .next_back()
+            .map(|&i| i64::from(i))
+            .unwrap_or(i64::from(self.run_start) - 1)
+    }
+
+    /// FFmpeg only lists a segment once it has been closed.
+    async fn refresh_completed(&mut self) {
+        if let Ok(content) = tokio::fs::read_to_string(self.temp_dir.join(FFMPEG_PLAYLIST)).await {
+            self.completed.extend(parse_listed_segments(&content));
+        }
+    }
 }
 
 impl LinearManager {
@@ -39,6 +90,12 @@ impl LinearManager {
         {
             let mut sessions = self.sessions.write().await;
             if let Some(session) = sessions.get_mut(&key) {
+                // Seekable sessions own their playlist; FFmpeg is restarted per seek as needed
+                if session.total_segments.is_some() {
+                    session.last_access = Instant::now();
+                    return Ok(session.temp_dir.clone());
+                }
+
                 // Check if process is still running
                 if let Some(child) = &mut session.child {
                     // Try_wait returns Ok(None) if running, Ok(Some(status)) if exited, Err if error
@@ -50,12 +107,9 @@ impl LinearManager {
                         }
                         Ok(Some(status)) => {
                             eprintln!("Linear ffmpeg exited prematurely: {}", status);
-                            // It exited, so we need to restart it? Or maybe it finished?
-                            // For HLS Live of a file, if it finished, the playlist is complete.
-                            // We can still serve files from temp dir.
-                            // But usually we treat "session" as "active transcoding".
-                            // If index.m3u8 exists and has ENDLIST, we are good.
-                            if session.temp_dir.join("index.m3u8").exists() {
+                            // For HLS Live of a file, if it finished, the playlist is complete
+                            // and we can still serve files from the temp dir.
+                            if session.temp_dir.join(PLAYLIST).exists() {
                                 session.last_access = Instant::now();
                                 return Ok(session.temp_dir.clone());
                             }
@@ -83,21 +137,184 @@ impl LinearManager {
         let temp_dir = temp_dir_base.join(&session_id);
         tokio::fs::create_dir_all(&temp_dir).await.map_err(|e| e.to_string())?;
 
-        let ffmpeg_path = get_ffmpeg_path(Some(&self.app_handle))
-            .ok_or("FFmpeg not found")?;
-
         // Detect media type
         let media_type = crate::transcoding::detector::get_media_type(file_path);
         let is_audio = media_type == crate::transcoding::detector::MediaType::Audio;
 
+        // With a known duration we publish the full VOD playlist up front so the
+        // player can seek anywhere; otherwise FFmpeg writes a growing live playlist.
+        let duration = super::probe::get_video_info(&self.app_handle, file_path)
+            .await
+            .ok()
+            .map(|info| info.duration_secs)
+            .filter(|d| d.is_finite() && *d > 0.0);
+
+        let (total_segments, ffmpeg_playlist) = match duration {
+            Some(duration_secs) => {
+                let playlist = build_seekable_playlist(duration_secs, LINEAR_SEGMENT_SECS);
+                tokio::fs::write(temp_dir.join(PLAYLIST), playlist)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                (Some(segment_count(duration_secs, LINEAR_SEGMENT_SECS)), FFMPEG_PLAYLIST)
+            }
+            None => (None, PLAYLIST),
+        };
+
+        let child = self.spawn_ffmpeg(&key, &temp_dir, quality, is_audio, 0, total_segments, ffmpeg_playlist)?;
+
+        let session = LinearSession {
+            process_id: child.id(),
+            temp_dir: temp_dir.clone(),
+            last_access: Instant::now(),
+            child: Some(child),
+            quality: quality.to_string(),
+            is_audio,
+            total_segments,
+            run_start: 0,
+            run_end: total_segments.unwrap_or(u32::MAX),
+            completed: BTreeSet::new(),
+            restarting: false,
+        };
+
+        let replaced = self.sessions.write().await.insert(key.clone(), session);
+        // A dead live session is being replaced; don't leak its files
+        if let Some(mut old) = replaced {
+            if let Some(mut child) = old.child.take() {
+                let _ = child.kill().await;
+            }
+            let _ = tokio::fs::remove_dir_all(&old.temp_dir).await;
+        }
+
+        Ok(temp_dir)
+    }
+
+    /// Returns the path of a finished segment, restarting FFmpeg at that
+    /// segment when it is outside the range the current process will reach soon.
+    ///
+    /// Segments produced by earlier runs are reused, and a restarted process
+    /// stops where the next already-produced range begins.
+    pub async fn ensure_segment(&self, file_path: &Path, index: u32) -> Result<PathBuf, String> {
+        let key = file_path.to_string_lossy().to_string();
+        let deadline = Instant::now() + SEGMENT_WAIT;
+
+        loop {
+            let stopping = {
+                let mut sessions = self.sessions.write().await;
+                let session = sessions.get_mut(&key).ok_or("Session not active for this file")?;
+                session.last_access = Instant::now();
+
+                let path = session.temp_dir.join(segment_name(index));
+                let Some(total) = session.total_segments else {
+                    // Live playlist: only what FFmpeg already listed can be requested
+                    return Ok(path);
+                };
+                if index >= total {
+                    return Err(format!("Segment {} out of range ({} segments)", index, total));
+                }
+
+                session.refresh_completed().await;
+                if session.completed.contains(&index) {
+                    return Ok(path);
+                }
+
+                let head = session.produced_head();
+                let reachable = session.is_running()
+                    && index >= session.run_start
+                    && index < session.run_end
+                    && i64::from(index) <= head + SEEK_LOOKAHEAD;
+
+                if reachable || session.restarting {
+                    None
+                } else {
+                    session.restarting = true;
+                    process_manager::lock_global().finish_process(&linear_job_key(&key), session.process_id);
+                    // The segment the old process was in the middle of writing
+                    let partial = u32::try_from(head + 1)
+                        .ok()
+                        .filter(|partial| !session.completed.contains(partial))
+                        .map(|partial| session.temp_dir.join(segment_name(partial)));
+                    Some((session.child.take(), partial))
+                }
+            };
+
+            // Killing and reaping FFmpeg takes a while; other streams must not wait on it
+            if let Some((child, partial)) = stopping {
+                if let Some(mut child) = child {
+                    let _ = child.kill().await;
+                    let _ = child.wait().await;
+                }
+                if let Some(partial) = partial {
+                    let _ = tokio::fs::remove_file(partial).await;
+                }
+
+                let mut sessions = self.sessions.write().await;
+                let session = sessions.get_mut(&key).ok_or("Session not active for this file")?;
+                session.restarting = false;
+                self.restart_at(&key, session, index)?;
+            }
+
+            if Instant::now() >= deadline {
+                return Err(format!("Timed out waiting for segment {}", index));
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Starts the session's FFmpeg process again at `index`, once the
+    /// previous one was stopped.
+    fn restart_at(&self, key: &str, session: &mut LinearSession, index: u32) -> Result<(), String> {
+        let total = session.total_segments.unwrap_or(u32::MAX);
+        let run_end = session
+            .completed
+            .range(index..)
+            .next()
+            .copied()
+            .unwrap_or(total);
+
+        println!("INFO: Linear seek to segment {} (until {}) for {}", index, run_end, key);
+
+        let child = self.spawn_ffmpeg(
+            key,
+            &session.temp_dir,
+            &session.quality,
+            session.is_audio,
+            index,
+            Some(run_end),
+            FFMPEG_PLAYLIST,
+        )?;
+        session.process_id = child.id();
+        session.child = Some(child);
+        session.run_start = index;
+        session.run_end = run_end;
+        Ok(())
+    }
+
+    /// Spawns FFmpeg writing segments `start..end` of `file_key` into `temp_dir`.
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_ffmpeg(
+        &self,
+        file_key: &str,
+        temp_dir: &Path,
+        quality: &str,
+        is_audio: bool,
+        start: u32,
+        end: Option<u32>,
+        playlist_name: &str,
+    ) -> Result<Child, String> {
+        let ffmpeg_path = get_ffmpeg_path(Some(&self.app_handle))
+            .ok_or("FFmpeg not found")?;
+
+        let start_secs = f64::from(start) * LINEAR_SEGMENT_SECS;
+
         // Spawn FFmpeg
         let mut cmd = Command::new(ffmpeg_path);
 
-        cmd.args([
-            "-hide_banner",
-            "-loglevel", "error",
-            "-i", &key,
-        ]);
+        cmd.args(["-hide_banner", "-loglevel", "error"]);
+        if start > 0 {
+            // Input seeking jumps to the nearest keyframe, then decodes accurately
+            cmd.arg("-ss").arg(format!("{:.3}", start_secs));
+        }
+        cmd.args(["-i", file_key]);
 
         if is_audio {
             cmd.args([
@@ -124,71 +341,80 @@ impl LinearManager {
                 "-b:a", "128k",
                 "-b:v", video_bitrate,
             ]);
+            // Keyframe on every segment boundary so restarted runs line up with the playlist
+            cmd.arg("-force_key_frames")
+                .arg(format!("expr:gte(t,n_forced*{})", LINEAR_SEGMENT_SECS));
+        }
+
+        if let Some(end) = end.filter(|e| *e > start) {
+            cmd.arg("-t").arg(format!("{:.3}", f64::from(end - start) * LINEAR_SEGMENT_SECS));
+        }
+        if start > 0 {
+            cmd.arg("-output_ts_offset").arg(format!("{:.3}", start_secs));
         }
 
+        let hls_time = LINEAR_SEGMENT_SECS.to_string();
+        let start_number = start.to_string();
         cmd.args([
             "-f", "hls",
-            "-hls_time", "4",
+            "-hls_time", hls_time.as_str(),
             "-hls_list_size", "0",
+            "-start_number", start_number.as_str(),
             "-hls_segment_filename", "segment_%05d.ts",
-            // "-hls_flags", "delete_segments", // Don't delete, we might want to seek back
-            "index.m3u8"
+            // Segments are never deleted, we reuse them when seeking back
+            playlist_name,
         ]);
 
-        cmd.current_dir(&temp_dir);
+        cmd.current_dir(temp_dir);
 
         // Setup log file for stderr to debug failures
         let log_file_path = temp_dir.join("ffmpeg.log");
         if let Ok(log_file) = std::fs::File::create(&log_file_path) {
             cmd.stderr(log_file);
-        } else {
-             // Fallback
-             // cmd.stderr(Stdio::null());
         }
 
-        let child = cmd.spawn().map_err(|e| format!("Failed to spawn ffmpeg: {}", e))?;
-        let pid = child.id();
+        cmd.kill_on_drop(true);
 
-        let session = LinearSession {
-            process_id: pid,
-            temp_dir: temp_dir.clone(),
-            last_access: Instant::now(),
-            child: Some(child),
-        };
+        let child = cmd.spawn().map_err(|e| format!("Failed to spawn ffmpeg: {}", e))?;
 
-        {
-            let mut sessions = self.sessions.write().await;
-            sessions.insert(key, session);
-        }
+        // Track the session so it shows up in the active job list. No hard
+        // timeout: the session lives as long as the player keeps polling it.
+        process_manager::lock_global().register_job(
+            &linear_job_key(file_key),
+            JobKind::Linear,
+            file_key,
+            child.id(),
+            None,
+        );
 
-        Ok(temp_dir)
+        Ok(child)
     }
 
     /// Clean up stale sessions
     pub async fn cleanup(&self, timeout: Duration) {
-        let mut sessions = self.sessions.write().await;
         let now = Instant::now();
+        let removed: Vec<(String, LinearSession)> = {
+            let mut sessions = self.sessions.write().await;
+            let stale: Vec<String> = sessions
+                .iter()
+                .filter(|(_, session)| now.duration_since(session.last_access) > timeout)
+                .map(|(key, _)| key.clone())
+                .collect();
+            stale.into_iter().filter_map(|key| sessions.remove(&key).map(|session| (key, session))).collect()
+        };
 
-        let mut to_remove = Vec::new();
-
-        for (key, session) in sessions.iter() {
-            if now.duration_since(session.last_access) > timeout {
-                to_remove.push(key.clone());
+        // Stopped after the lock is released, so active streams keep being served
+        for (key, mut session) in removed {
+            println!("INFO: Cleaning up linear session for {}", key);
+            process_manager::lock_global().finish_process(&linear_job_key(&key), session.process_id);
+            // Kill process
+            if let Some(mut child) = session.child.take() {
+                let _ = child.kill().await;
+                let _ = child.wait().await;
             }
-        }
 
-        for key in to_remove {
-            if let Some(mut session) = sessions.remove(&key) {
-                println!("INFO: Cleaning up linear session for {}", key);
-                // Kill process
-                if let Some(mut child) = session.child.take() {
-                    let _ = child.kill().await;
-                    let _ = child.wait().await;
-                }
-
-                // Remove temp dir
-                let _ = tokio::fs::remove_dir_all(&session.temp_dir).await;
-            }
+            // Remove temp dir
+            let _ = tokio::fs::remove_dir_all(&session.temp_dir).await;
         }
     }
 
@@ -199,3 +425,73 @@ impl LinearManager {
         sessions.get(&key).map(|s| s.temp_dir.clone())
     }
 }
+
+/// Key of a linear session inside the FFmpeg supervisor
+fn linear_job_key(file_key: &str) -> String {
+    format!("linear:{}", file_key)
+}
+
+fn segment_name(index: u32) -> String {
+    format!("segment_{:05}.ts", index)
+}
+
+/// Parses the index out of a `segment_00042.ts` file name.
+pub fn segment_index(name: &str) -> Option<u32> {
+    name.strip_prefix("segment_")?.strip_suffix(".ts")?.parse().ok()
+}
+
+fn segment_count(duration_secs: f64, segment_secs: f64) -> u32 {
+    ((duration_secs / segment_secs).ceil() as u32).max(1)
+}
+
+/// Segment indices listed in an FFmpeg-written playlist.
+fn parse_listed_segments(playlist: &str) -> impl Iterator<Item = u32> + '_ {
+    playlist
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| segment_index(line.trim()))
+}
+
+/// Full VOD playlist with fixed-length segments named like FFmpeg's output.
+fn build_seekable_playlist(duration_secs: f64, segment_secs: f64) -> String {
+    let mut playlist = String::new();
+    playlist.push_str("#EXTM3U\n");
+    playlist.push_str("#EXT-X-VERSION:3\n");
+    // Keyframes may land slightly past the boundary, so leave a second of headroom
+    playlist.push_str(&format!("#EXT-X-TARGETDURATION:{}\n", segment_secs.ceil() as u32 + 1));
+    playlist.push_str("#EXT-X-MEDIA-SEQUENCE:0\n");
+    playlist.push_str("#EXT-X-PLAYLIST-TYPE:VOD\n");
+
+    for i in 0..segment_count(duration_secs, segment_secs) {
+        let seg_start = f64::from(i) * segment_secs;
+        let seg_duration = (duration_secs - seg_start).min(segment_secs);
+        playlist.push_str(&format!("#EXTINF:{:.3},\n", seg_duration));
+        playlist.push_str(&segment_name(i));
+        playlist.push('\n');
+    }
+
+    playlist.push_str("#EXT-X-ENDLIST\n");
+    playlist
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seekable_playlist_covers_duration() {
+        let playlist = build_seekable_playlist(10.0, 4.0);
+        let segments: Vec<u32> = parse_listed_segments(&playlist).collect();
+        assert_eq!(segments, vec![0, 1, 2]);
+        assert!(playlist.contains("#EXTINF:2.000,\nsegment_00002.ts"));
+        assert!(playlist.ends_with("#EXT-X-ENDLIST\n"));
+    }
+
+    #[test]
+    fn test_segment_index() {
+        assert_eq!(segment_index("segment_00042.ts"), Some(42));
+        assert_eq!(segment_index(&segment_name(7)), Some(7));
+        assert_eq!(segment_index("index.m3u8"), None);
+        assert_eq!(segment_index("segment_../x.ts"), None);
+    }
+}
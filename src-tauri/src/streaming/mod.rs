@@ -7,5 +7,9 @@ pub mod server;
 pub mod probe;
 pub mod playlist;
 pub mod segment;
+pub mod prefetch;
 pub mod process_manager;
 pub mod linear;
+pub mod auth;
+pub mod progressive;
+pub mod stats;
@@ -0,0 +1,304 @@
+//! Segment prefetching ahead of playback.
+//!
+//! Segments are transcoded when the player asks for them, so on codecs that
+//! transcode near realtime playback stalls at every segment boundary. After
+//! serving a segment, the next few are transcoded in the background. Each
+//! playback session (a file at a quality) has at most one prefetch running,
+//! which follows the playback position and stops when the player seeks
+//! elsewhere, goes idle, or when every FFmpeg slot is busy: requested
+//! segments and thumbnails come first. A seek also kills the segment being
+//! prefetched when the new position leaves it behind. Requested and
+//! prefetched segments are claimed while they are transcoded, so neither
+//! transcodes a segment the other is working on.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::transcoding::cache::TranscodeCache;
+use super::{process_manager, segment};
+
+/// Segments transcoded ahead of the last one requested.
+const PREFETCH_AHEAD: u32 = 3;
+
+/// A session without requests for this long is over.
+const SESSION_IDLE: Duration = Duration::from_secs(60);
+
+/// Longest a request waits for a prefetch of the same segment.
+const PREFETCH_WAIT: Duration = Duration::from_secs(60);
+
+/// Where playback of a session is.
+#[derive(Debug)]
+struct Session {
+    /// Last segment requested.
+    position: u32,
+    last_request: Instant,
+    prefetching: bool,
+    /// Segment the prefetch is on.
+    current: Option<u32>,
+}
+
+/// Playback sessions, keyed by file and quality.
+#[derive(Debug, Default)]
+struct Sessions {
+    sessions: HashMap<String, Session>,
+}
+
+impl Sessions {
+    /// Records a request for segment `index`. Returns whether a prefetch
+    /// should start, which it shouldn't when one is already following the
+    /// session.
+    fn note_request(&mut self, key: &str, index: u32) -> bool {
+        self.sessions
+            .retain(|_, session| session.prefetching || session.last_request.elapsed() < SESSION_IDLE);
+        let session = self.sessions.entry(key.to_string()).or_insert(Session {
+            position: index,
+            last_request: Instant::now(),
+            prefetching: false,
+            current: None,
+        });
+        session.position = index;
+        session.last_request = Instant::now();
+        !std::mem::replace(&mut session.prefetching, true)
+    }
+
+    /// Moves the session to segment `index` as soon as it is requested.
+    /// Returns the segment being prefetched when the move leaves it outside
+    /// the window, to be cancelled.
+    fn seek(&mut self, key: &str, index: u32) -> Option<u32> {
+        let session = self.sessions.get_mut(key)?;
+        session.position = index;
+        session.last_request = Instant::now();
+        // The requested segment itself is waited for rather than cancelled
+        session.current.filter(|current| *current < index || *current > index + PREFETCH_AHEAD)
+    }
+
+    /// Segment to prefetch after `done` (the last one prefetched, if any),
+    /// or `None` when the prefetch should stop, which ends it.
+    fn next_target(&mut self, key: &str, done: Option<u32>) -> Option<u32> {
+        let session = self.sessions.get_mut(key)?;
+        // Continue the window, or start it over after a seek either way
+        let next = match done {
+            Some(done) if done >= session.position && done <= session.position + PREFETCH_AHEAD => done + 1,
+            _ => session.position + 1,
+        };
+        if next > session.position + PREFETCH_AHEAD || session.last_request.elapsed() >= SESSION_IDLE {
+            session.prefetching = false;
+            session.current = None;
+            return None;
+        }
+        session.current = Some(next);
+        Some(next)
+    }
+
+    /// Ends the prefetch of a session early.
+    fn stop(&mut self, key: &str) {
+        if let Some(session) = self.sessions.get_mut(key) {
+            session.prefetching = false;
+            session.current = None;
+        }
+    }
+}
+
+type InFlight = Arc<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>>;
+
+/// A segment being transcoded, released when dropped.
+pub struct Claim {
+    in_flight: InFlight,
+    cache_path: PathBuf,
+    _gate: tokio::sync::OwnedMutexGuard<()>,
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        // Taken out before the gate opens, so whoever waited finds the segment cached
+        self.in_flight.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.cache_path);
+    }
+}
+
+/// Prefetches segments ahead of each playback session.
+#[derive(Clone, Default)]
+pub struct Prefetcher {
+    sessions: Arc<Mutex<Sessions>>,
+    /// Segments being transcoded, requested or prefetched, by cache path.
+    /// Each lock is held until the segment is in the cache.
+    in_flight: InFlight,
+}
+
+impl Prefetcher {
+    fn sessions(&self) -> MutexGuard<'_, Sessions> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records a request for segment `index` before it is served, killing
+    /// a prefetch the request seeks away from.
+    pub fn note_request(&self, file_path: &Path, index: u32, quality: &str) {
+        let key = session_key(file_path, quality);
+        if let Some(abandoned) = self.sessions().seek(&key, index) {
+            process_manager::lock_global().cancel(&prefetch_key(file_path, abandoned));
+        }
+    }
+
+    /// Waits for a running transcode of the segment cached at `cache_path`.
+    pub async fn wait_for(&self, cache_path: &Path) {
+        let running = self.in_flight.lock().unwrap_or_else(|e| e.into_inner()).get(cache_path).cloned();
+        if let Some(running) = running {
+            tokio::time::timeout(PREFETCH_WAIT, running.lock()).await.ok();
+        }
+    }
+
+    /// Claims the segment cached at `cache_path` until the claim is dropped.
+    /// `None` when it is claimed already.
+    pub fn claim(&self, cache_path: &Path) -> Option<Claim> {
+        let gate = Arc::new(tokio::sync::Mutex::new(()));
+        let guard = gate.clone().try_lock_owned().ok()?;
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        if in_flight.contains_key(cache_path) {
+            return None;
+        }
+        in_flight.insert(cache_path.to_path_buf(), gate);
+        Some(Claim { in_flight: self.in_flight.clone(), cache_path: cache_path.to_path_buf(), _gate: guard })
+    }
+
+    /// Records that segment `index` was served, and starts prefetching the
+    /// ones after it unless a prefetch already follows this session.
+    pub fn schedule(
+        &self,
+        app_handle: &tauri::AppHandle,
+        cache: &Arc<TranscodeCache>,
+        file_path: &Path,
+        index: u32,
+        segment_duration: f64,
+        quality: &str,
+    ) {
+        let key = session_key(file_path, quality);
+        if !self.sessions().note_request(&key, index) {
+            return;
+        }
+
+        let prefetcher = self.clone();
+        let app_handle = app_handle.clone();
+        let cache = cache.clone();
+        let file_path = file_path.to_path_buf();
+        let quality = quality.to_string();
+        tokio::spawn(async move {
+            prefetcher.run(&key, &app_handle, &cache, &file_path, segment_duration, &quality).await;
+        });
+    }
+
+    async fn run(
+        &self,
+        key: &str,
+        app_handle: &tauri::AppHandle,
+        cache: &TranscodeCache,
+        file_path: &Path,
+        segment_duration: f64,
+        quality: &str,
+    ) {
+        let mut done = None;
+        loop {
+            let Some(index) = self.sessions().next_target(key, done) else {
+                return;
+            };
+            done = Some(index);
+
+            let cache_path = segment::get_segment_cache_path(cache, file_path, index, quality);
+            if cache_path.exists() {
+                continue;
+            }
+            if !process_manager::lock_global().has_free_slot() {
+                self.sessions().stop(key);
+                return;
+            }
+
+            // Held until the segment is cached, so a request for it waits
+            let Some(claim) = self.claim(&cache_path) else {
+                continue;
+            };
+            let result = segment::transcode_and_cache(
+                app_handle, &prefetch_key(file_path, index), &cache_path, file_path, index, segment_duration, quality,
+            )
+            .await;
+            drop(claim);
+
+            // Past the end of the file, a file FFmpeg can't seek in, or cancelled by a seek
+            if result.is_err() {
+                self.sessions().stop(key);
+                return;
+            }
+        }
+    }
+}
+
+/// Key of the playback session of `file_path` at `quality`.
+fn session_key(file_path: &Path, quality: &str) -> String {
+    format!("{}|{}", file_path.display(), quality)
+}
+
+/// Process key of the prefetch of segment `index`.
+fn prefetch_key(file_path: &Path, index: u32) -> String {
+    format!("prefetch:{}:{}", file_path.display(), index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefetch_follows_playback() {
+        let mut sessions = Sessions::default();
+        assert!(sessions.note_request("clip", 4));
+        assert_eq!(sessions.next_target("clip", None), Some(5));
+        assert_eq!(sessions.next_target("clip", Some(5)), Some(6));
+
+        // A request while prefetching moves the window instead of starting another
+        assert!(!sessions.note_request("clip", 6));
+        assert_eq!(sessions.next_target("clip", Some(6)), Some(7));
+        assert_eq!(sessions.next_target("clip", Some(8)), Some(9));
+        assert_eq!(sessions.next_target("clip", Some(9)), None);
+
+        // Once stopped, the next request starts a new prefetch
+        assert!(sessions.note_request("clip", 9));
+    }
+
+    #[test]
+    fn test_seeking_restarts_the_window() {
+        let mut sessions = Sessions::default();
+        assert!(sessions.note_request("clip", 40));
+        assert_eq!(sessions.next_target("clip", None), Some(41));
+
+        assert!(!sessions.note_request("clip", 2));
+        assert_eq!(sessions.next_target("clip", Some(41)), Some(3));
+        assert_eq!(sessions.next_target("clip", Some(5)), None);
+
+        assert!(sessions.note_request("clip", 80));
+        assert_eq!(sessions.next_target("clip", Some(5)), Some(81));
+        assert_eq!(sessions.next_target("other", None), None);
+    }
+
+    #[test]
+    fn test_seeking_abandons_the_prefetched_segment() {
+        let mut sessions = Sessions::default();
+        assert_eq!(sessions.seek("clip", 0), None);
+        assert!(sessions.note_request("clip", 10));
+        assert_eq!(sessions.next_target("clip", None), Some(11));
+        // Playing on keeps it, as does asking for it
+        assert_eq!(sessions.seek("clip", 11), None);
+        assert_eq!(sessions.seek("clip", 9), None);
+        assert_eq!(sessions.seek("clip", 50), Some(11));
+        assert_eq!(sessions.seek("clip", 2), Some(11));
+        sessions.stop("clip");
+        assert_eq!(sessions.seek("clip", 50), None);
+    }
+
+    #[test]
+    fn test_claims_are_exclusive() {
+        let prefetcher = Prefetcher::default();
+        let path = Path::new("/cache/segment_3.ts");
+        let claim = prefetcher.claim(path).expect("unclaimed");
+        assert!(prefetcher.claim(path).is_none());
+        drop(claim);
+        assert!(prefetcher.claim(path).is_some());
+    }
+}
@@ -27,6 +27,32 @@ pub struct VideoInfo {
     pub width: Option<u32>,
     /// Resolution height
     pub height: Option<u32>,
+    /// Average frame rate of the first video stream
+    pub fps: Option<f64>,
+    /// Frames in the first video stream, counted by the container or
+    /// estimated from duration and frame rate
+    pub frame_count: Option<i64>,
+    /// Overall bitrate in bits per second
+    pub bitrate: Option<i64>,
+    /// Whether the file has at least one audio stream
+    pub has_audio: bool,
+    /// Whether the streams can be copied into MP4 without re-encoding
+    pub can_remux: bool,
+    /// Chapter markers, in file order
+    pub chapters: Vec<ChapterInfo>,
+    /// Whether the file embeds a cover picture (MP4 covr atom, MKV attachment, ID3 APIC)
+    pub has_cover_art: bool,
+}
+
+/// A chapter marker as reported by ffprobe
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ChapterInfo {
+    /// Start of the chapter, in seconds
+    pub start_secs: f64,
+    /// End of the chapter, in seconds
+    pub end_secs: f64,
+    /// Title from the chapter's tags, if not blank
+    pub title: Option<String>,
 }
 
 /// Get video information using ffprobe
@@ -62,6 +88,7 @@ pub async fn get_video_info(
             "-print_format", "json",
             "-show_format",
             "-show_streams",
+            "-show_chapters",
             &path.to_string_lossy(),
         ])
         .output()?;
@@ -84,6 +111,11 @@ pub async fn get_video_info(
         .as_str()
         .map(|s| s.split(',').next().unwrap_or(s).to_string());
 
+    // Extract overall bitrate (bits per second)
+    let bitrate = json["format"]["bit_rate"]
+        .as_str()
+        .and_then(|s| s.parse::<i64>().ok());
+
     // Find video and audio streams
     let streams = json["streams"].as_array();
 
@@ -91,17 +123,29 @@ pub async fn get_video_info(
     let mut audio_codec = None;
     let mut width = None;
     let mut height = None;
+    let mut fps = None;
+    let mut frame_count = None;
+    let mut has_cover_art = false;
 
     if let Some(streams) = streams {
         for stream in streams {
             let codec_type = stream["codec_type"].as_str().unwrap_or("");
             let codec_name = stream["codec_name"].as_str();
 
+            // Cover pictures show up as single-frame video streams; they must not
+            // be mistaken for the actual video track (e.g. mjpeg in an M4B)
+            if is_attached_picture(stream) {
+                has_cover_art = true;
+                continue;
+            }
+
             match codec_type {
                 "video" if video_codec.is_none() => {
                     video_codec = codec_name.map(String::from);
                     width = stream["width"].as_u64().map(|v| v as u32);
                     height = stream["height"].as_u64().map(|v| v as u32);
+                    fps = stream["avg_frame_rate"].as_str().and_then(parse_frame_rate);
+                    frame_count = stream["nb_frames"].as_str().and_then(|n| n.parse::<i64>().ok());
                 }
                 "audio" if audio_codec.is_none() => {
                     audio_codec = codec_name.map(String::from);
@@ -114,9 +158,21 @@ pub async fn get_video_info(
     // Determine if native using existing detector (extension-based)
     // Plus additional codec-based check for better accuracy
     // Also mark HLS-problematic formats as "native" to use fallback transcoding
-    let is_native = (detector::is_native_format(path) && is_codec_native(&video_codec, &audio_codec))
+    let codecs_native = match crate::transcoding::capabilities::registered() {
+        Some(codecs) => codecs.plays_codecs(video_codec.as_deref(), audio_codec.as_deref()),
+        None => is_codec_native(&video_codec, &audio_codec),
+    };
+    let is_native = (detector::is_native_format(path) && codecs_native)
         || is_hls_problematic(path, &container);
 
+    let has_audio = audio_codec.is_some();
+    let can_remux = !is_native && is_remux_compatible(&container, &video_codec, &audio_codec);
+    let chapters = parse_chapters(&json["chapters"]);
+    let frame_count = frame_count.or_else(|| {
+        fps.filter(|fps| *fps > 0.0 && duration_secs > 0.0)
+            .map(|fps| (fps * duration_secs).round() as i64)
+    });
+
     Ok(VideoInfo {
         duration_secs,
         is_native,
@@ -125,10 +181,55 @@ pub async fn get_video_info(
         container,
         width,
         height,
+        fps,
+        frame_count,
+        bitrate,
+        has_audio,
+        can_remux,
+        chapters,
+        has_cover_art,
     })
 }
 
-/// Check if video/audio codecs are natively supported in WebView
+/// Whether an ffprobe stream entry is an embedded picture rather than a track
+fn is_attached_picture(stream: &serde_json::Value) -> bool {
+    stream["disposition"]["attached_pic"].as_i64() == Some(1)
+}
+
+/// Parse the `chapters` array of ffprobe JSON output
+fn parse_chapters(chapters: &serde_json::Value) -> Vec<ChapterInfo> {
+    let Some(chapters) = chapters.as_array() else {
+        return Vec::new();
+    };
+
+    chapters
+        .iter()
+        .filter_map(|chapter| {
+            let start_secs = chapter["start_time"].as_str()?.parse::<f64>().ok()?;
+            let end_secs = chapter["end_time"].as_str()?.parse::<f64>().ok()?;
+            let title = chapter["tags"]["title"]
+                .as_str()
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(String::from);
+            Some(ChapterInfo { start_secs, end_secs, title })
+        })
+        .collect()
+}
+
+/// Parse an ffprobe rational frame rate ("30000/1001") into frames per second
+fn parse_frame_rate(rate: &str) -> Option<f64> {
+    let (num, den) = rate.split_once('/').unwrap_or((rate, "1"));
+    let num: f64 = num.parse().ok()?;
+    let den: f64 = den.parse().ok()?;
+    if den == 0.0 || num == 0.0 {
+        return None;
+    }
+    Some(num / den)
+}
+
+/// Check if video/audio codecs are natively supported in WebView, before the
+/// frontend has registered what its WebView actually plays
 fn is_codec_native(video_codec: &Option<String>, audio_codec: &Option<String>) -> bool {
     // Native video codecs
     let native_video = match video_codec {
@@ -155,6 +256,26 @@ fn is_codec_native(video_codec: &Option<String>, audio_codec: &Option<String>) -
     native_video && native_audio
 }
 
+/// Check if the streams only need a container change (e.g. MKV with H.264/AAC)
+///
+/// MP4 can carry these codecs as-is, so a `-c copy` remux gives a natively
+/// playable, seekable file without any encoding work.
+fn is_remux_compatible(
+    container: &Option<String>,
+    video_codec: &Option<String>,
+    audio_codec: &Option<String>,
+) -> bool {
+    // MPEG-PS and friends have broken timestamps that don't survive a plain copy
+    if matches!(container.as_deref(), Some("mpeg" | "mpegps" | "mpegvideo" | "swf")) {
+        return false;
+    }
+
+    let video_ok = matches!(video_codec.as_deref(), Some("h264" | "avc1" | "avc"));
+    let audio_ok = matches!(audio_codec.as_deref(), None | Some("aac" | "mp3"));
+
+    video_ok && audio_ok
+}
+
 /// Check if a format has issues with HLS streaming and should use fallback
 /// These formats either don't seek well or have FFmpeg processing issues
 fn is_hls_problematic(path: &Path, container: &Option<String>) -> bool {
@@ -190,4 +311,42 @@ mod tests {
         assert!(!is_codec_native(&Some("h264".to_string()), &Some("opus".to_string())));
         assert!(!is_codec_native(&Some("vp9".to_string()), &Some("opus".to_string())));
     }
+
+    #[test]
+    fn test_remux_compatible() {
+        let mkv = Some("matroska".to_string());
+        assert!(is_remux_compatible(&mkv, &Some("h264".to_string()), &Some("aac".to_string())));
+        assert!(is_remux_compatible(&mkv, &Some("h264".to_string()), &None));
+        assert!(!is_remux_compatible(&mkv, &Some("hevc".to_string()), &Some("aac".to_string())));
+        assert!(!is_remux_compatible(&mkv, &Some("h264".to_string()), &Some("dts".to_string())));
+        assert!(!is_remux_compatible(&Some("mpegps".to_string()), &Some("h264".to_string()), &None));
+    }
+
+    #[test]
+    fn test_parse_chapters() {
+        let json: serde_json::Value = serde_json::json!([
+            { "id": 0, "start_time": "0.000000", "end_time": "62.500000", "tags": { "title": "Intro" } },
+            { "id": 1, "start_time": "62.500000", "end_time": "300.000000", "tags": { "title": " " } },
+            { "id": 2, "start_time": "bogus", "end_time": "310.000000" }
+        ]);
+        let chapters = parse_chapters(&json);
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0], ChapterInfo { start_secs: 0.0, end_secs: 62.5, title: Some("Intro".to_string()) });
+        assert_eq!(chapters[1].title, None);
+        assert!(parse_chapters(&serde_json::Value::Null).is_empty());
+    }
+
+    #[test]
+    fn test_is_attached_picture() {
+        assert!(is_attached_picture(&serde_json::json!({ "codec_type": "video", "disposition": { "attached_pic": 1 } })));
+        assert!(!is_attached_picture(&serde_json::json!({ "codec_type": "video", "disposition": { "attached_pic": 0 } })));
+        assert!(!is_attached_picture(&serde_json::json!({ "codec_type": "audio" })));
+    }
+
+    #[test]
+    fn test_parse_frame_rate() {
+        assert_eq!(parse_frame_rate("25/1"), Some(25.0));
+        assert!((parse_frame_rate("30000/1001").unwrap() - 29.97).abs() < 0.01);
+        assert_eq!(parse_frame_rate("0/0"), None);
+    }
 }
@@ -1,28 +1,93 @@
 //! Process Manager for FFmpeg Transcoding
 //!
 //! Tracks active FFmpeg processes and allows cancellation for rapid seeking.
+//!
+//! Every FFmpeg invocation in the application (thumbnails, waveforms, full
+//! transcodes, HLS segments and linear sessions) goes through the shared
+//! supervisor returned by [`global`]. The supervisor enforces a hard timeout
+//! per job, kills the process when a job is cancelled, and caps how many
+//! short-lived FFmpeg processes may run at the same time so that corrupt files
+//! cannot pile up zombie processes.
 
+use std::cell::Cell;
 use std::collections::HashMap;
-use std::time::Instant;
+use std::io::Read;
+use std::process::{Command, Output, Stdio};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use wait_timeout::ChildExt;
+
+use crate::error::{AppError, AppResult};
+
+/// Interval used when waiting for a free process slot.
+const SLOT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Category of an FFmpeg job, used for reporting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    /// Single-frame thumbnail or preview extraction.
+    Thumbnail,
+    /// Audio waveform extraction.
+    Waveform,
+    /// Full-file transcode into the transcode cache.
+    Transcode,
+    /// On-demand HLS segment.
+    Segment,
+    /// Long-running linear HLS session.
+    Linear,
+    /// Native decoder run in an isolated helper process.
+    Decoder,
+    /// Slideshow or other video rendered for export.
+    Render,
+}
 
 /// Manages active FFmpeg transcoding processes
 pub struct ProcessManager {
     /// Active processes keyed by segment identifier
     processes: HashMap<String, ProcessInfo>,
+    /// Maximum number of throttled jobs allowed to run at once
+    max_concurrent: usize,
+    /// Monotonic counter used to build unique job keys
+    next_job_id: u64,
 }
 
 /// Information about an active process
 struct ProcessInfo {
-    /// Process handle (if we're tracking it)
-    #[allow(dead_code)]
+    /// Process handle (None while the slot is reserved but not yet spawned)
     process_id: Option<u32>,
     /// When the process started
     started_at: Instant,
+    /// What the process is doing
+    kind: JobKind,
+    /// Human readable description (usually the input file)
+    label: String,
+    /// Hard limit after which the process is killed
+    timeout: Option<Duration>,
+    /// Queued job the process was started for (see [`for_job`])
+    job_id: Option<i64>,
+}
+
+/// Snapshot of a running job, returned to the frontend.
+#[derive(Debug, Clone, Serialize)]
+pub struct ActiveJob {
+    /// Unique key of the job inside the supervisor.
+    pub key: String,
+    /// Category of the job.
+    pub kind: JobKind,
+    /// Human readable description (usually the input file).
+    pub label: String,
+    /// OS process id, if the process has been spawned.
+    pub process_id: Option<u32>,
+    /// Seconds since the job started.
+    pub elapsed_secs: f64,
+    /// Hard timeout in seconds, if any.
+    pub timeout_secs: Option<u64>,
 }
 
 #[cfg(unix)]
 fn kill_process(pid: u32) {
-    use std::process::Command;
     Command::new("kill")
         .arg("-9")
         .arg(pid.to_string())
@@ -32,7 +97,6 @@ fn kill_process(pid: u32) {
 
 #[cfg(windows)]
 fn kill_process(pid: u32) {
-    use std::process::Command;
     Command::new("taskkill")
         .arg("/F")
         .arg("/PID")
@@ -41,30 +105,140 @@ fn kill_process(pid: u32) {
         .ok();
 }
 
+/// Default concurrency limit: half the available cores, between 2 and 8.
+fn default_max_concurrent() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get() / 2)
+        .unwrap_or(2)
+        .clamp(2, 8)
+}
+
+thread_local! {
+    /// Queued job the processes started on this thread belong to.
+    static CURRENT_JOB: Cell<Option<i64>> = const { Cell::new(None) };
+}
+
+/// Restores the job of the thread when a [`for_job`] task ends, even by panic.
+struct JobScope(Option<i64>);
+
+impl Drop for JobScope {
+    fn drop(&mut self) {
+        CURRENT_JOB.with(|current| current.set(self.0));
+    }
+}
+
+/// Runs `task` with the processes it starts on this thread recorded under
+/// the queued job `job_id`, so cancelling the job kills them and nothing
+/// else. Call it inside the blocking task, where the processes are started.
+pub fn for_job<T>(job_id: Option<i64>, task: impl FnOnce() -> T) -> T {
+    let _scope = JobScope(CURRENT_JOB.with(|current| current.replace(job_id)));
+    task()
+}
+
+/// Returns the process-wide FFmpeg supervisor.
+pub fn global() -> &'static Mutex<ProcessManager> {
+    static SUPERVISOR: OnceLock<Mutex<ProcessManager>> = OnceLock::new();
+    SUPERVISOR.get_or_init(|| Mutex::new(ProcessManager::new()))
+}
+
+/// Locks the global supervisor, recovering from a poisoned lock.
+///
+/// A panic while holding the lock only leaves bookkeeping behind, which the
+/// stale reaper cleans up, so it is safe to keep going.
+pub fn lock_global() -> MutexGuard<'static, ProcessManager> {
+    global().lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
 impl ProcessManager {
     /// Create a new process manager
     pub fn new() -> Self {
         Self {
             processes: HashMap::new(),
+            max_concurrent: default_max_concurrent(),
+            next_job_id: 0,
         }
     }
 
     /// Register a new transcoding process
     #[allow(dead_code)]
     pub fn register(&mut self, key: &str, pid: u32) {
+        self.register_job(key, JobKind::Segment, key, Some(pid), None);
+    }
+
+    /// Register a process with its kind, label and optional hard timeout.
+    pub fn register_job(
+        &mut self,
+        key: &str,
+        kind: JobKind,
+        label: &str,
+        pid: Option<u32>,
+        timeout: Option<Duration>,
+    ) {
         let info = ProcessInfo {
-            process_id: Some(pid),
+            process_id: pid,
             started_at: Instant::now(),
+            kind,
+            label: label.to_string(),
+            timeout,
+            job_id: CURRENT_JOB.with(Cell::get),
         };
         self.processes.insert(key.to_string(), info);
     }
 
+    /// Reserves a slot for a throttled job if the concurrency limit allows it.
+    ///
+    /// Returns the unique job key on success. Linear sessions are not counted
+    /// because they live for the whole playback and would starve other jobs.
+    pub fn try_reserve(&mut self, kind: JobKind, label: &str, timeout: Duration) -> Option<String> {
+        if !self.has_free_slot() {
+            return None;
+        }
+
+        self.next_job_id += 1;
+        let key = format!("{:?}#{}", kind, self.next_job_id).to_lowercase();
+        self.register_job(&key, kind, label, None, Some(timeout));
+        Some(key)
+    }
+
+    /// Whether a throttled job could start right away.
+    pub fn has_free_slot(&self) -> bool {
+        let throttled = self
+            .processes
+            .values()
+            .filter(|info| info.kind != JobKind::Linear)
+            .count();
+        throttled < self.max_concurrent
+    }
+
+    /// Attach the OS process id to a previously reserved job.
+    pub fn attach_pid(&mut self, key: &str, pid: u32) {
+        if let Some(info) = self.processes.get_mut(key) {
+            info.process_id = Some(pid);
+        }
+    }
+
+    /// Remove a finished job without killing it.
+    pub fn finish(&mut self, key: &str) {
+        self.processes.remove(key);
+    }
+
+    /// Remove a finished job only if it still belongs to the given process.
+    ///
+    /// Used by callers whose key can be reused by a newer job (HLS segments
+    /// re-requested after a seek), so a late finisher doesn't drop the entry
+    /// of its replacement.
+    pub fn finish_process(&mut self, key: &str, pid: Option<u32>) {
+        if self.processes.get(key).map(|info| info.process_id) == Some(pid) {
+            self.processes.remove(key);
+        }
+    }
+
     /// Cancel a transcoding process by key
     pub fn cancel(&mut self, key: &str) {
         if let Some(info) = self.processes.remove(key) {
             // Log cancellation
             let elapsed = info.started_at.elapsed();
-            println!("INFO: Cancelled segment {} after {:?}", key, elapsed);
+            println!("INFO: Cancelled {:?} job {} after {:?}", info.kind, key, elapsed);
 
             // Kill the process if we have an ID
             if let Some(pid) = info.process_id {
@@ -73,21 +247,50 @@ impl ProcessManager {
         }
     }
 
+    /// Cancels every process started for the queued job `job_id`, returning
+    /// how many were killed.
+    pub fn cancel_job(&mut self, job_id: i64) -> usize {
+        let keys: Vec<String> = self
+            .processes
+            .iter()
+            .filter(|(_, info)| info.job_id == Some(job_id))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &keys {
+            self.cancel(key);
+        }
+        keys.len()
+    }
+
+    /// Whether no supervised process is running.
+    pub fn is_idle(&self) -> bool {
+        self.processes.is_empty()
+    }
+
     /// Check if a segment is currently being processed
     #[allow(dead_code)]
     pub fn is_processing(&self, key: &str) -> bool {
         self.processes.contains_key(key)
     }
 
-    /// Clean up old/orphaned processes (older than timeout)
-    #[allow(dead_code)]
+    /// Clean up old/orphaned processes.
+    ///
+    /// Jobs with their own hard timeout are killed once it expires; jobs
+    /// without one (linear sessions) are left alone, since their owner
+    /// manages their lifetime. `timeout_secs` is the grace period for
+    /// reserved slots that never received a process id.
     pub fn cleanup_stale(&mut self, timeout_secs: u64) {
-        let timeout = std::time::Duration::from_secs(timeout_secs);
+        let grace = Duration::from_secs(timeout_secs);
         let now = Instant::now();
         let mut to_remove = Vec::new();
 
         for (key, info) in &self.processes {
-            if now.duration_since(info.started_at) > timeout {
+            let limit = match (info.timeout, info.process_id) {
+                (Some(timeout), _) => timeout + grace,
+                (None, None) => grace,
+                (None, Some(_)) => continue,
+            };
+            if now.duration_since(info.started_at) > limit {
                 to_remove.push(key.clone());
             }
         }
@@ -103,6 +306,24 @@ impl ProcessManager {
     pub fn active_count(&self) -> usize {
         self.processes.len()
     }
+
+    /// Snapshot of every tracked job, oldest first.
+    pub fn active_jobs(&self) -> Vec<ActiveJob> {
+        let mut jobs: Vec<ActiveJob> = self
+            .processes
+            .iter()
+            .map(|(key, info)| ActiveJob {
+                key: key.clone(),
+                kind: info.kind,
+                label: info.label.clone(),
+                process_id: info.process_id,
+                elapsed_secs: info.started_at.elapsed().as_secs_f64(),
+                timeout_secs: info.timeout.map(|t| t.as_secs()),
+            })
+            .collect();
+        jobs.sort_by(|a, b| b.elapsed_secs.total_cmp(&a.elapsed_secs));
+        jobs
+    }
 }
 
 impl Default for ProcessManager {
@@ -111,6 +332,93 @@ impl Default for ProcessManager {
     }
 }
 
+/// Blocks until the global supervisor grants a slot for a new job.
+fn reserve_blocking(kind: JobKind, label: &str, timeout: Duration) -> String {
+    loop {
+        if let Some(key) = lock_global().try_reserve(kind, label, timeout) {
+            return key;
+        }
+        std::thread::sleep(SLOT_POLL_INTERVAL);
+    }
+}
+
+/// Waits asynchronously until the global supervisor grants a slot.
+pub async fn reserve_async(kind: JobKind, label: &str, timeout: Duration) -> String {
+    loop {
+        if let Some(key) = lock_global().try_reserve(kind, label, timeout) {
+            return key;
+        }
+        tokio::time::sleep(SLOT_POLL_INTERVAL).await;
+    }
+}
+
+/// Runs a blocking FFmpeg command under supervision.
+///
+/// Waits for a free slot, captures stdout/stderr, and kills the process if it
+/// exceeds `timeout` or is cancelled through the supervisor.
+///
+/// # Errors
+/// Returns `AppError::Io` if the process cannot be spawned and
+/// `AppError::Transcoding` if it times out.
+pub fn run_supervised(
+    mut cmd: Command,
+    kind: JobKind,
+    label: &str,
+    timeout: Duration,
+) -> AppResult<Output> {
+    let key = reserve_blocking(kind, label, timeout);
+
+    let spawned = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            lock_global().finish(&key);
+            return Err(e.into());
+        }
+    };
+    lock_global().attach_pid(&key, child.id());
+
+    // Drain the pipes on helper threads so a chatty process can't block on a full pipe.
+    let stdout_reader = child.stdout.take().map(|mut stream| {
+        std::thread::spawn(move || {
+            let mut buffer = Vec::new();
+            stream.read_to_end(&mut buffer).ok();
+            buffer
+        })
+    });
+    let stderr_reader = child.stderr.take().map(|mut stream| {
+        std::thread::spawn(move || {
+            let mut buffer = Vec::new();
+            stream.read_to_end(&mut buffer).ok();
+            buffer
+        })
+    });
+
+    let wait_result = child.wait_timeout(timeout);
+    lock_global().finish(&key);
+
+    let status = match wait_result {
+        Ok(Some(status)) => status,
+        Ok(None) => {
+            child.kill().ok();
+            child.wait().ok();
+            return Err(AppError::Transcoding(format!(
+                "FFmpeg timed out after {}s: {}",
+                timeout.as_secs(),
+                label
+            )));
+        }
+        Err(e) => {
+            child.kill().ok();
+            return Err(e.into());
+        }
+    };
+
+    let stdout = stdout_reader.and_then(|h| h.join().ok()).unwrap_or_default();
+    let stderr = stderr_reader.and_then(|h| h.join().ok()).unwrap_or_default();
+    Ok(Output { status, stdout, stderr })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -122,4 +430,43 @@ mod tests {
         assert_eq!(pm.active_count(), 0);
         assert!(!pm.is_processing("test:0"));
     }
+
+    #[test]
+    fn test_reserve_respects_limit() {
+        let mut pm = ProcessManager::new();
+        pm.max_concurrent = 2;
+        let timeout = Duration::from_secs(5);
+
+        let first = pm.try_reserve(JobKind::Thumbnail, "a.mp4", timeout).unwrap();
+        let _second = pm.try_reserve(JobKind::Thumbnail, "b.mp4", timeout).unwrap();
+        assert!(pm.try_reserve(JobKind::Thumbnail, "c.mp4", timeout).is_none());
+
+        // Linear sessions do not count against the limit
+        pm.register_job("linear", JobKind::Linear, "d.swf", None, None);
+        pm.finish(&first);
+        assert!(pm.try_reserve(JobKind::Thumbnail, "c.mp4", timeout).is_some());
+        assert_eq!(pm.active_jobs().len(), 3);
+    }
+
+    #[test]
+    fn test_cleanup_keeps_untimed_sessions() {
+        let mut pm = ProcessManager::new();
+        pm.register_job("linear", JobKind::Linear, "d.swf", Some(u32::MAX), None);
+        pm.cleanup_stale(0);
+        assert!(pm.is_processing("linear"));
+    }
+
+    #[test]
+    fn test_cancel_job_spares_other_processes() {
+        let mut pm = ProcessManager::new();
+        // A playback transcode and a queued job working on the same file
+        pm.register_job("playback", JobKind::Transcode, "/v/a.mkv", None, None);
+        for_job(Some(7), || pm.register_job("job", JobKind::Transcode, "/v/a.mkv", None, None));
+        pm.register_job("after", JobKind::Transcode, "/v/a.mkv", None, None);
+
+        assert_eq!(pm.cancel_job(7), 1);
+        assert!(!pm.is_processing("job"));
+        assert!(pm.is_processing("playback"));
+        assert!(pm.is_processing("after"));
+    }
 }
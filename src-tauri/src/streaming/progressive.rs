@@ -0,0 +1,317 @@
+//! Progressive Audio Transcoding
+//!
+//! A lighter alternative to HLS for audio formats the WebView can't decode
+//! (ape, wv, dts...). FFmpeg pipes the file to ADTS AAC in a single pass and
+//! the player starts reading while the output is still growing. Finished
+//! files stay in the transcode cache and are then served with full Range
+//! support.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    http::{header, StatusCode},
+    response::Response,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, ReadBuf};
+use tokio::process::Command;
+use tokio_util::io::ReaderStream;
+
+use super::process_manager::{self, JobKind};
+use crate::media::ffmpeg::get_ffmpeg_path;
+use crate::transcoding::cache::TranscodeCache;
+use crate::transcoding::quality::TranscodeQuality;
+
+/// How often a reader polls a growing file for new bytes.
+const GROW_POLL: Duration = Duration::from_millis(50);
+
+/// How long a range request past the written bytes waits for them.
+const RANGE_WAIT: Duration = Duration::from_secs(10);
+
+/// Hard limit for one progressive transcode.
+const PROGRESSIVE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Tracks in-flight progressive transcodes so concurrent requests share one FFmpeg.
+#[derive(Clone, Default)]
+pub struct ProgressiveAudio {
+    jobs: Arc<Mutex<HashMap<PathBuf, Arc<AtomicBool>>>>,
+}
+
+/// Where the bytes for a request come from.
+enum Source {
+    /// Finished cache file with a known length.
+    Complete(PathBuf),
+    /// File still being written; the flag flips once FFmpeg exits.
+    Growing(PathBuf, Arc<AtomicBool>),
+}
+
+impl ProgressiveAudio {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves `source` as AAC, starting a transcode if needed.
+    pub async fn serve(
+        &self,
+        app_handle: &tauri::AppHandle,
+        cache: &TranscodeCache,
+        source: &Path,
+        quality: TranscodeQuality,
+        range: Option<&str>,
+    ) -> Result<Response, String> {
+        let output = cache.get_progressive_path(source, quality);
+        match self.ensure_started(app_handle, source, &output, quality)? {
+            Source::Complete(path) => serve_complete(&path, range).await,
+            Source::Growing(path, done) => serve_growing(path, done, range).await,
+        }
+    }
+
+    fn ensure_started(
+        &self,
+        app_handle: &tauri::AppHandle,
+        source: &Path,
+        output: &Path,
+        quality: TranscodeQuality,
+    ) -> Result<Source, String> {
+        let partial = partial_path(output);
+        let mut jobs = self.jobs.lock().unwrap_or_else(|p| p.into_inner());
+
+        if let Some(done) = jobs.get(output) {
+            return Ok(Source::Growing(partial, done.clone()));
+        }
+        if output.is_file() {
+            return Ok(Source::Complete(output.to_path_buf()));
+        }
+
+        let ffmpeg_path = get_ffmpeg_path(Some(app_handle)).ok_or("FFmpeg not found")?;
+        let bitrate = format!("{}k", quality.audio_bitrate() / 1000);
+
+        let mut cmd = Command::new(ffmpeg_path);
+        cmd.args(["-y", "-hide_banner", "-loglevel", "error", "-i"])
+            .arg(source)
+            .args([
+                "-vn",
+                "-map", "0:a:0",
+                "-c:a", "aac",
+                "-b:a", bitrate.as_str(),
+                "-ar", "48000",
+                "-ac", "2",
+                // ADTS frames are self-delimiting, so a reader can start before the end exists
+                "-f", "adts",
+            ])
+            .arg(&partial)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .kill_on_drop(true);
+
+        let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn ffmpeg: {}", e))?;
+        let pid = child.id();
+        let job_key = format!("progressive:{}", output.display());
+        let label = source.to_string_lossy().to_string();
+        process_manager::lock_global().register_job(&job_key, JobKind::Transcode, &label, pid, Some(PROGRESSIVE_TIMEOUT));
+
+        let done = Arc::new(AtomicBool::new(false));
+        jobs.insert(output.to_path_buf(), done.clone());
+
+        let jobs_handle = self.jobs.clone();
+        let output = output.to_path_buf();
+        let partial_for_task = partial.clone();
+        let done_for_task = done.clone();
+        tokio::spawn(async move {
+            let status = child.wait().await;
+            process_manager::lock_global().finish_process(&job_key, pid);
+
+            match status {
+                Ok(status) if status.success() => {
+                    // Keep the partial path readable for clients still streaming it;
+                    // new requests get the finished file from the cache.
+                    if let Err(e) = tokio::fs::copy(&partial_for_task, &output).await {
+                        eprintln!("WARN: Failed to store progressive transcode: {}", e);
+                    }
+                }
+                other => eprintln!("WARN: Progressive audio transcode failed for {}: {:?}", label, other),
+            }
+
+            done_for_task.store(true, Ordering::Release);
+            jobs_handle.lock().unwrap_or_else(|p| p.into_inner()).remove(&output);
+
+            // Give in-flight readers time to drain before dropping the partial file
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            let _ = tokio::fs::remove_file(&partial_for_task).await;
+        });
+
+        Ok(Source::Growing(partial, done))
+    }
+}
+
+fn partial_path(output: &Path) -> PathBuf {
+    output.with_extension("aac.part")
+}
+
+/// Parses `bytes=start-end` (end optional). Suffix ranges aren't supported
+/// for growing files, so they are treated as "no range".
+pub fn parse_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let spec = value.strip_prefix("bytes=")?;
+    let (start, end) = spec.split_once('-')?;
+    let start = start.trim().parse::<u64>().ok()?;
+    let end = match end.trim() {
+        "" => None,
+        e => Some(e.parse::<u64>().ok()?),
+    };
+    if end.is_some_and(|e| e < start) {
+        return None;
+    }
+    Some((start, end))
+}
+
+async fn serve_complete(path: &Path, range: Option<&str>) -> Result<Response, String> {
+    let mut file = tokio::fs::File::open(path).await.map_err(|e| e.to_string())?;
+    let size = file.metadata().await.map_err(|e| e.to_string())?.len();
+
+    let (start, end) = match range.and_then(parse_range) {
+        Some((start, _)) if start >= size => {
+            return Ok(Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+                .body(Body::empty())
+                .unwrap());
+        }
+        Some((start, end)) => (start, end.unwrap_or(size - 1).min(size - 1)),
+        None => {
+            return Ok(base_response(StatusCode::OK)
+                .header(header::CONTENT_LENGTH, size)
+                .header(header::ACCEPT_RANGES, "bytes")
+                .body(Body::from_stream(ReaderStream::new(file)))
+                .unwrap());
+        }
+    };
+
+    file.seek(SeekFrom::Start(start)).await.map_err(|e| e.to_string())?;
+    let length = end - start + 1;
+    Ok(base_response(StatusCode::PARTIAL_CONTENT)
+        .header(header::CONTENT_LENGTH, length)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, size))
+        .body(Body::from_stream(ReaderStream::new(file.take(length))))
+        .unwrap())
+}
+
+async fn serve_growing(path: PathBuf, done: Arc<AtomicBool>, range: Option<&str>) -> Result<Response, String> {
+    // FFmpeg creates the file shortly after spawning
+    let mut waited = Duration::ZERO;
+    let mut file = loop {
+        match tokio::fs::File::open(&path).await {
+            Ok(file) => break file,
+            Err(_) if waited < RANGE_WAIT && !done.load(Ordering::Acquire) => {
+                tokio::time::sleep(GROW_POLL).await;
+                waited += GROW_POLL;
+            }
+            Err(e) => return Err(format!("Transcode output unavailable: {}", e)),
+        }
+    };
+
+    let Some((start, end)) = range.and_then(parse_range) else {
+        // Whole stream, delivered as it is produced
+        return Ok(base_response(StatusCode::OK)
+            .header(header::ACCEPT_RANGES, "bytes")
+            .body(Body::from_stream(ReaderStream::new(GrowingFile::new(file, done))))
+            .unwrap());
+    };
+
+    // Wait until the start of the range has been written
+    let mut waited = Duration::ZERO;
+    loop {
+        let finished = done.load(Ordering::Acquire);
+        let written = file.metadata().await.map_err(|e| e.to_string())?.len();
+        if start < written {
+            let end = end.unwrap_or(written - 1).min(written - 1);
+            file.seek(SeekFrom::Start(start)).await.map_err(|e| e.to_string())?;
+            let length = end - start + 1;
+            // Total length is unknown while transcoding (RFC 7233 allows `*`)
+            let total = if finished { written.to_string() } else { "*".to_string() };
+            return Ok(base_response(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_LENGTH, length)
+                .header(header::ACCEPT_RANGES, "bytes")
+                .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, total))
+                .body(Body::from_stream(ReaderStream::new(file.take(length))))
+                .unwrap());
+        }
+        if finished || waited >= RANGE_WAIT {
+            return Ok(Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", if finished { written.to_string() } else { "*".to_string() }))
+                .body(Body::empty())
+                .unwrap());
+        }
+        tokio::time::sleep(GROW_POLL).await;
+        waited += GROW_POLL;
+    }
+}
+
+fn base_response(status: StatusCode) -> axum::http::response::Builder {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "audio/aac")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .header(header::ACCESS_CONTROL_EXPOSE_HEADERS, "Content-Range, Content-Length, Accept-Ranges")
+}
+
+/// Reader over a file that is still being appended to. At end of file it
+/// waits for more data until `done` is set.
+struct GrowingFile {
+    file: tokio::fs::File,
+    done: Arc<AtomicBool>,
+    sleep: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+impl GrowingFile {
+    fn new(file: tokio::fs::File, done: Arc<AtomicBool>) -> Self {
+        Self { file, done, sleep: None }
+    }
+}
+
+impl AsyncRead for GrowingFile {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        loop {
+            if let Some(sleep) = self.sleep.as_mut() {
+                if sleep.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                self.sleep = None;
+            }
+
+            // Read the flag first: if FFmpeg was already done, an empty read is the real end
+            let was_done = self.done.load(Ordering::Acquire);
+            let before = buf.filled().len();
+            match Pin::new(&mut self.file).poll_read(cx, buf) {
+                Poll::Ready(Ok(())) if buf.filled().len() == before && !was_done => {
+                    self.sleep = Some(Box::pin(tokio::time::sleep(GROW_POLL)));
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-"), Some((0, None)));
+        assert_eq!(parse_range("bytes=100-199"), Some((100, Some(199))));
+        assert_eq!(parse_range("bytes=-500"), None);
+        assert_eq!(parse_range("bytes=200-100"), None);
+        assert_eq!(parse_range("items=0-1"), None);
+    }
+}
@@ -6,21 +6,31 @@
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::io::AsyncReadExt;
 use tokio::process::Command;
-use tokio::sync::RwLock;
 
 use crate::media::ffmpeg::get_ffmpeg_path;
 use crate::transcoding::cache::TranscodeCache;
-use super::process_manager::ProcessManager;
+use super::prefetch::Prefetcher;
+use super::process_manager::{self, JobKind};
+use super::stats::{self, SegmentTiming};
+
+/// Hard limit for transcoding a single segment before FFmpeg is killed.
+const SEGMENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Encoders of the main stream of segments.
+const VIDEO_ENCODER: &str = "libx264";
+const AUDIO_ENCODER: &str = "aac";
 
 /// Get or generate a video segment
 ///
 /// Returns cached segment if available, otherwise transcodes on-demand.
+/// Either way, the next segments are then prefetched in the background.
 pub async fn get_segment(
     app_handle: &tauri::AppHandle,
     cache: &Arc<TranscodeCache>,
-    process_manager: &Arc<RwLock<ProcessManager>>,
+    prefetcher: &Prefetcher,
     file_path: &Path,
     segment_index: u32,
     segment_duration: f64,
@@ -29,42 +39,81 @@ pub async fn get_segment(
     // Check if segment is already cached
     let cache_path = get_segment_cache_path(cache, file_path, segment_index, quality);
 
-    if cache_path.exists() {
+    prefetcher.note_request(file_path, segment_index, quality);
+    // A prefetch of this segment may be running; wait for it rather than
+    // transcoding the segment twice
+    prefetcher.wait_for(&cache_path).await;
+
+    let data = if cache_path.exists() {
         // Serve from cache
-        let data = tokio::fs::read(&cache_path).await?;
-        return Ok(data);
-    }
+        tokio::fs::read(&cache_path).await?
+    } else {
+        // Generate segment key for process management
+        let segment_key = format!("{}:{}", file_path.display(), segment_index);
 
-    // Generate segment key for process management
-    let segment_key = format!("{}:{}", file_path.display(), segment_index);
+        // Cancel any previous transcoding for this segment (in case of rapid seeking)
+        process_manager::lock_global().cancel(&segment_key);
 
-    // Cancel any previous transcoding for this segment (in case of rapid seeking)
-    {
-        let mut pm = process_manager.write().await;
-        pm.cancel(&segment_key);
-    }
+        // Claimed so a prefetch doesn't transcode it as well
+        let _claim = prefetcher.claim(&cache_path);
+        transcode_and_cache(app_handle, &segment_key, &cache_path, file_path, segment_index, segment_duration, quality).await?
+    };
 
-    // Transcode the segment
-    let data = transcode_segment(app_handle, process_manager, &segment_key, file_path, segment_index, segment_duration, quality).await?;
+    prefetcher.schedule(app_handle, cache, file_path, segment_index, segment_duration, quality);
+    Ok(data)
+}
+
+/// Transcodes a segment into `cache_path`, recording how long it took.
+pub(super) async fn transcode_and_cache(
+    app_handle: &tauri::AppHandle,
+    segment_key: &str,
+    cache_path: &Path,
+    file_path: &Path,
+    segment_index: u32,
+    segment_duration: f64,
+    quality: &str,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let started = Instant::now();
+    let mut queued = Duration::ZERO;
+    let result = transcode_segment(app_handle, segment_key, file_path, segment_index, segment_duration, quality, &mut queued).await;
+    stats::record(app_handle, file_path, SegmentTiming {
+        segment_index,
+        quality: quality.to_string(),
+        encoder: segment_encoder(file_path).to_string(),
+        queued,
+        encoding: started.elapsed().saturating_sub(queued),
+        media_secs: segment_duration,
+        error: result.as_ref().err().map(|e| e.to_string()),
+    });
+    let data = result?;
 
     // Cache the segment to disk
     if let Some(parent) = cache_path.parent() {
         tokio::fs::create_dir_all(parent).await.ok();
     }
-    tokio::fs::write(&cache_path, &data).await.ok();
+    tokio::fs::write(cache_path, &data).await.ok();
 
     Ok(data)
 }
 
-/// Transcode a single segment using FFmpeg
+/// Encoder of the main stream of `file_path`'s segments.
+fn segment_encoder(file_path: &Path) -> &'static str {
+    match crate::transcoding::detector::get_media_type(file_path) {
+        crate::transcoding::detector::MediaType::Audio => AUDIO_ENCODER,
+        _ => VIDEO_ENCODER,
+    }
+}
+
+/// Transcode a single segment using FFmpeg. `queued` is set to the time
+/// spent waiting for a free FFmpeg slot.
 async fn transcode_segment(
     app_handle: &tauri::AppHandle,
-    process_manager: &Arc<RwLock<ProcessManager>>,
     segment_key: &str,
     file_path: &Path,
     segment_index: u32,
     segment_duration: f64,
     quality: &str,
+    queued: &mut Duration,
 ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
     let ffmpeg_path = get_ffmpeg_path(Some(app_handle))
         .ok_or("FFmpeg not found")?;
@@ -99,7 +148,7 @@ async fn transcode_segment(
         cmd.args([
             "-map", "0:a:0?",           // Map first audio stream
             "-vn",                     // No video
-            "-c:a", "aac",             // AAC codec
+            "-c:a", AUDIO_ENCODER,     // AAC codec
             "-b:a", "192k",            // Good quality audio
             "-ar", "48000",            // Standard sample rate
             "-ac", "2",                // Stereo
@@ -112,7 +161,7 @@ async fn transcode_segment(
             "-map", "0:a:0?",
             "-sn", // Disable subtitles (source of many seek errors)
             // Video encoding
-            "-c:v", "libx264",
+            "-c:v", VIDEO_ENCODER,
             "-preset", "ultrafast",
         ]);
 
@@ -149,24 +198,49 @@ async fn transcode_segment(
 
     cmd.stdout(Stdio::piped());
     cmd.stderr(Stdio::piped());
+    cmd.kill_on_drop(true);
+
+    // Wait for a free FFmpeg slot before spawning
+    let label = file_path.to_string_lossy().to_string();
+    let waiting = Instant::now();
+    let slot_key = process_manager::reserve_async(JobKind::Segment, &label, SEGMENT_TIMEOUT).await;
+    *queued = waiting.elapsed();
+
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            process_manager::lock_global().finish(&slot_key);
+            return Err(e.into());
+        }
+    };
 
-    let mut child = cmd.spawn()?;
-
-    // Register process for cancellation
-    if let Some(id) = child.id() {
-        let mut pm = process_manager.write().await;
-        pm.register(segment_key, id);
+    // Re-register the slot under the segment key so seeking can cancel it
+    let pid = child.id();
+    {
+        let mut pm = process_manager::lock_global();
+        pm.finish(&slot_key);
+        pm.register_job(segment_key, JobKind::Segment, &label, pid, Some(SEGMENT_TIMEOUT));
     }
 
     let mut stdout = child.stdout.take().ok_or("Failed to capture stdout")?;
     let mut stderr = child.stderr.take().ok_or("Failed to capture stderr")?;
 
-    // Read output
+    // Read output, killing FFmpeg if it hangs past the hard timeout
     let mut output_data = Vec::new();
-    stdout.read_to_end(&mut output_data).await?;
-
-    // Wait for process
-    let status = child.wait().await?;
+    let run = async {
+        stdout.read_to_end(&mut output_data).await?;
+        child.wait().await
+    };
+    let result = tokio::time::timeout(SEGMENT_TIMEOUT, run).await;
+    process_manager::lock_global().finish_process(segment_key, pid);
+
+    let status = match result {
+        Ok(status) => status?,
+        Err(_) => {
+            child.kill().await.ok();
+            return Err(format!("FFmpeg timed out on segment {}", segment_index).into());
+        }
+    };
 
     if !status.success() {
         let mut err_output = String::new();
@@ -182,7 +256,7 @@ async fn transcode_segment(
 }
 
 /// Get the cache path for a segment
-fn get_segment_cache_path(cache: &TranscodeCache, file_path: &Path, segment_index: u32, quality: &str) -> PathBuf {
+pub(super) fn get_segment_cache_path(cache: &TranscodeCache, file_path: &Path, segment_index: u32, quality: &str) -> PathBuf {
     // Use the cache directory from TranscodeCache
     // Create a subdirectory for HLS segments
     let cache_dir = cache.dir().join("hls_segments");
@@ -4,7 +4,11 @@
 //! - /health - Health check
 //! - /probe/{path} - Get video metadata and native format detection
 //! - /playlist/{path} - Generate M3U8 playlist dynamically
-//! - /segment/{path}/{index} - Transcode and serve video segments
+//! - /segment/{path}/{index} - Transcode and serve video segments, prefetching the next ones
+//! - /audio/{path} - Progressive AAC transcode for audio (Range aware)
+//!
+//! Every route requires the session token (see [`auth`]) and only serves
+//! files inside registered library roots.
 
 use axum::{
     routing::get,
@@ -20,11 +24,12 @@ use tower_http::cors::{CorsLayer, Any};
 use std::time::Duration;
 use std::sync::Arc;
 use std::path::PathBuf;
-use tokio::sync::RwLock;
 use tauri::Manager;
 
-use super::{probe, playlist, segment, process_manager::ProcessManager, linear::LinearManager};
+use super::{auth, probe, playlist, segment, process_manager, linear::{self, LinearManager}, prefetch::Prefetcher, progressive::ProgressiveAudio};
+use crate::media::camera_raw;
 use crate::transcoding::cache::TranscodeCache;
+use crate::transcoding::quality::TranscodeQuality;
 
 /// Default port for the HLS streaming server
 pub const DEFAULT_PORT: u16 = 9876;
@@ -36,8 +41,9 @@ pub const SEGMENT_DURATION: f64 = 10.0;
 #[derive(Clone)]
 pub struct AppState {
     pub cache: Arc<TranscodeCache>,
-    pub process_manager: Arc<RwLock<ProcessManager>>,
     pub linear_manager: LinearManager,
+    pub progressive: ProgressiveAudio,
+    pub prefetcher: Prefetcher,
     pub app_handle: tauri::AppHandle,
 }
 
@@ -61,24 +67,23 @@ impl StreamingServer {
             .map_err(|e| format!("Failed to get app data dir: {}", e))?;
 
         let cache = Arc::new(TranscodeCache::new(&app_data));
-        let process_manager = Arc::new(RwLock::new(ProcessManager::new()));
         let linear_manager = LinearManager::new(self.app_handle.clone());
 
         let state = AppState {
             cache,
-            process_manager: process_manager.clone(),
             linear_manager: linear_manager.clone(),
+            progressive: ProgressiveAudio::new(),
+            prefetcher: Prefetcher::default(),
             app_handle: self.app_handle.clone(),
         };
 
         // Spawn cleanup task
-        let pm_clone = process_manager.clone();
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(10));
             loop {
                 interval.tick().await;
-                let mut pm = pm_clone.write().await;
-                pm.cleanup_stale(30); // 30 seconds timeout
+                // Kill FFmpeg jobs past their hard timeout (30 seconds grace)
+                process_manager::lock_global().cleanup_stale(30);
             }
         });
 
@@ -105,6 +110,8 @@ impl StreamingServer {
             .route("/segment/*path", get(segment_handler))
             // New routes for linear HLS
             .route("/hls-live/*path", get(linear_hls_handler))
+            .route("/audio/*path", get(progressive_audio_handler))
+            .layer(axum::middleware::from_fn(auth::require_token))
             .layer(cors)
             .with_state(state);
 
@@ -129,8 +136,11 @@ async fn probe_handler(
     State(state): State<AppState>,
     Path(path): Path<String>,
 ) -> Response {
-    let file_path = decode_path(&path);
+    let file_path = camera_raw::playback_source(&decode_path(&path));
     println!("DEBUG: Probe request for: {:?}", file_path);
+    if !auth::is_path_allowed(&state.app_handle, &file_path).await {
+        return forbidden();
+    }
 
     match probe::get_video_info(&state.app_handle, &file_path).await {
         Ok(info) => {
@@ -158,8 +168,11 @@ async fn playlist_handler(
     Path(path): Path<String>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Response {
-    let file_path = decode_path(&path);
+    let file_path = camera_raw::playback_source(&decode_path(&path));
     let quality = params.get("quality").map(|s| s.as_str()).unwrap_or("standard");
+    if !auth::is_path_allowed(&state.app_handle, &file_path).await {
+        return forbidden();
+    }
 
     // First, probe the video to get duration
     let info = match probe::get_video_info(&state.app_handle, &file_path).await {
@@ -172,7 +185,7 @@ async fn playlist_handler(
         }
     };
 
-    let m3u8 = playlist::generate_m3u8(&path, info.duration_secs, SEGMENT_DURATION, quality);
+    let m3u8 = auth::tokenize_playlist(&playlist::generate_m3u8(&path, info.duration_secs, SEGMENT_DURATION, quality));
 
     Response::builder()
         .status(StatusCode::OK)
@@ -192,7 +205,8 @@ async fn segment_handler(
     // Path format: /segment/{encoded_file_path}/{index}
     // We need to parse out the index from the end
     let (file_path, index) = match parse_segment_path(&path) {
-        Some((p, i)) => (p, i),
+        // Camera RAW footage is streamed from its recorded proxy
+        Some((p, i)) => (camera_raw::playback_source(&p), i),
         None => {
             return Response::builder()
                 .status(StatusCode::BAD_REQUEST)
@@ -200,11 +214,14 @@ async fn segment_handler(
                 .unwrap();
         }
     };
+    if !auth::is_path_allowed(&state.app_handle, &file_path).await {
+        return forbidden();
+    }
 
     match segment::get_segment(
         &state.app_handle,
         &state.cache,
-        &state.process_manager,
+        &state.prefetcher,
         &file_path,
         index,
         SEGMENT_DURATION,
@@ -228,6 +245,36 @@ async fn segment_handler(
     }
 }
 
+/// Progressive audio endpoint - pipes the file through FFmpeg to AAC
+async fn progressive_audio_handler(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: axum::http::HeaderMap,
+) -> Response {
+    let file_path = decode_path(&path);
+    if !auth::is_path_allowed(&state.app_handle, &file_path).await {
+        return forbidden();
+    }
+
+    let quality = params
+        .get("quality")
+        .and_then(|q| TranscodeQuality::from_str(q))
+        .unwrap_or_default();
+    let range = headers.get(header::RANGE).and_then(|v| v.to_str().ok());
+
+    match state.progressive.serve(&state.app_handle, &state.cache, &file_path, quality, range).await {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("PROGRESSIVE_AUDIO_ERROR for {:?}: {}", file_path, e);
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from(format!("Audio transcode failed: {}", e)))
+                .unwrap()
+        }
+    }
+}
+
 /// Linear HLS Handler using /hls-live/*path
 /// Request can be:
 /// 1. .../video.swf/index.m3u8 -> Starts transcode, returns playlist
@@ -250,7 +297,10 @@ async fn linear_hls_handler(
             .map(|s| s.into_owned())
             .unwrap_or_else(|_| raw_path.to_string());
 
-        let file_path = PathBuf::from(decoded_path);
+        let file_path = crate::paths::from_db(&decoded_path);
+        if !auth::is_path_allowed(&state.app_handle, &file_path).await {
+            return forbidden();
+        }
         if !file_path.exists() {
              return Response::builder()
                 .status(StatusCode::NOT_FOUND)
@@ -279,7 +329,7 @@ async fn linear_hls_handler(
                                 .status(StatusCode::OK)
                                 .header(header::CONTENT_TYPE, "application/vnd.apple.mpegurl")
                                 .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
-                                .body(Body::from(content))
+                                .body(Body::from(auth::tokenize_playlist(&content)))
                                 .unwrap()
                          }
                          Err(e) => {
@@ -316,11 +366,21 @@ async fn linear_hls_handler(
                 .map(|s| s.into_owned())
                 .unwrap_or_else(|_| file_part_raw.to_string());
 
-            let file_path = PathBuf::from(decoded_file_path);
+            let file_path = crate::paths::from_db(&decoded_file_path);
+            if !auth::is_path_allowed(&state.app_handle, &file_path).await {
+                return forbidden();
+            }
+
+            // Known segments may need FFmpeg to seek there first; anything else is served as-is
+            let segment_path = match linear::segment_index(segment_name) {
+                Some(index) => state.linear_manager.ensure_segment(&file_path, index).await,
+                None => state.linear_manager.get_temp_dir(&file_path).await
+                    .map(|temp_dir| temp_dir.join(segment_name))
+                    .ok_or_else(|| "Session not active for this file".to_string()),
+            };
 
-            if let Some(temp_dir) = state.linear_manager.get_temp_dir(&file_path).await {
-                 let segment_path = temp_dir.join(segment_name);
-                 if segment_path.exists() {
+            match segment_path {
+                Ok(segment_path) if segment_path.exists() => {
                      match tokio::fs::read(&segment_path).await {
                          Ok(data) => {
                              Response::builder()
@@ -336,11 +396,13 @@ async fn linear_hls_handler(
                              Response::builder().status(StatusCode::NOT_FOUND).body(Body::empty()).unwrap()
                          }
                      }
-                 } else {
+                }
+                Ok(_) => {
                      Response::builder().status(StatusCode::NOT_FOUND).body(Body::from("Segment file not found")).unwrap()
-                 }
-            } else {
-                 Response::builder().status(StatusCode::NOT_FOUND).body(Body::from("Session not active for this file")).unwrap()
+                }
+                Err(e) => {
+                     Response::builder().status(StatusCode::NOT_FOUND).body(Body::from(e)).unwrap()
+                }
             }
         } else {
              Response::builder().status(StatusCode::BAD_REQUEST).body(Body::from("Invalid segment path")).unwrap()
@@ -353,6 +415,14 @@ async fn linear_hls_handler(
     }
 }
 
+/// Response for files outside the library roots
+fn forbidden() -> Response {
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .body(Body::from("Path is outside the library"))
+        .unwrap()
+}
+
 /// Decode URL-encoded path
 fn decode_path(path: &str) -> PathBuf {
     // URL decode the path first
@@ -371,12 +441,12 @@ fn decode_path(path: &str) -> PathBuf {
         let parts: Vec<&str> = decoded.splitn(3, '/').collect();
         if parts.len() >= 2 && !parts[1].is_empty() {
             // Valid Unix path like /Users/...
-            return PathBuf::from(&decoded);
+            return crate::paths::from_db(&decoded);
         }
     }
 
     // Fallback: treat as relative path
-    PathBuf::from(decoded)
+    crate::paths::from_db(&decoded)
 }
 
 /// Parse segment path to extract file path and index
@@ -395,7 +465,7 @@ fn parse_segment_path(path: &str) -> Option<(PathBuf, u32)> {
         // Try to parse index (might have .ts extension)
         let index_str = index_part.trim_end_matches(".ts");
         if let Ok(index) = index_str.parse::<u32>() {
-            return Some((PathBuf::from(file_part), index));
+            return Some((crate::paths::from_db(file_part), index));
         }
     }
 
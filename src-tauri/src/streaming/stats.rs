@@ -0,0 +1,201 @@
+//! Per-segment transcode timing.
+//!
+//! When a file stutters, the player has outrun the transcoder, and the
+//! causes differ: the encoder is too slow for the source, every FFmpeg slot
+//! is busy with thumbnails, or the source itself is slow to decode. Each
+//! on-demand segment records how long it waited for a slot and how long it
+//! took to encode, with the encoder used, and `get_transcode_stats` sums
+//! them up per file. Segments are encoded on the CPU (`libx264`, or `aac`
+//! for audio files). The realtime factor is media seconds per second of
+//! encoding; below 1 playback catches up with the transcoder.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::Manager;
+
+use crate::db::transcode_stats::SegmentStatRow;
+use crate::db::Db;
+
+/// Files whose library id is kept; past it the ids are looked up again.
+const MAX_KNOWN_FILES: usize = 32;
+
+/// Timing of one segment transcode.
+#[derive(Debug, Clone)]
+pub struct SegmentTiming {
+    pub segment_index: u32,
+    pub quality: String,
+    /// FFmpeg encoder of the main stream: the video one, or the audio one
+    /// for audio files.
+    pub encoder: String,
+    /// Time spent waiting for a free FFmpeg slot.
+    pub queued: Duration,
+    pub encoding: Duration,
+    /// Length of the segment. The last segment of a file is usually shorter,
+    /// so its realtime factor reads high.
+    pub media_secs: f64,
+    pub error: Option<String>,
+}
+
+/// A recorded segment, as returned to the frontend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentStat {
+    pub segment_index: i64,
+    pub quality: String,
+    pub encoder: String,
+    pub queued_ms: i64,
+    pub encode_ms: i64,
+    pub media_secs: f64,
+    /// `None` for failed segments.
+    pub realtime_factor: Option<f64>,
+    pub error: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Recorded segments of a file, summed up.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscodeStats {
+    pub image_id: i64,
+    /// Newest first.
+    pub segments: Vec<SegmentStat>,
+    pub transcoded: usize,
+    pub failed: usize,
+    /// Media seconds per second of encoding, over every transcoded segment.
+    pub realtime_factor: Option<f64>,
+    pub slowest_realtime_factor: Option<f64>,
+    pub average_queued_ms: Option<f64>,
+    /// Encoders used, most recent first.
+    pub encoders: Vec<String>,
+}
+
+/// Library ids of the files being played, looked up on their first segment
+/// rather than for every one. `None` for files outside the library.
+fn known_files() -> &'static Mutex<HashMap<PathBuf, Option<i64>>> {
+    static KNOWN: OnceLock<Mutex<HashMap<PathBuf, Option<i64>>>> = OnceLock::new();
+    KNOWN.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn realtime_factor(media_secs: f64, encode_ms: i64) -> Option<f64> {
+    (encode_ms > 0).then(|| media_secs / (encode_ms as f64 / 1000.0))
+}
+
+/// Sums up the recorded segments of `image_id`, given newest first.
+pub fn summarize(image_id: i64, rows: Vec<SegmentStatRow>) -> TranscodeStats {
+    let segments: Vec<SegmentStat> = rows
+        .into_iter()
+        .map(|row| SegmentStat {
+            realtime_factor: if row.error.is_none() { realtime_factor(row.media_secs, row.encode_ms) } else { None },
+            segment_index: row.segment_index,
+            quality: row.quality,
+            encoder: row.encoder,
+            queued_ms: row.queued_ms,
+            encode_ms: row.encode_ms,
+            media_secs: row.media_secs,
+            error: row.error,
+            recorded_at: row.recorded_at,
+        })
+        .collect();
+
+    let succeeded: Vec<&SegmentStat> = segments.iter().filter(|s| s.error.is_none()).collect();
+    let media_secs: f64 = succeeded.iter().map(|s| s.media_secs).sum();
+    let encode_ms: i64 = succeeded.iter().map(|s| s.encode_ms).sum();
+    let mut encoders: Vec<String> = Vec::new();
+    for segment in &segments {
+        if !encoders.contains(&segment.encoder) {
+            encoders.push(segment.encoder.clone());
+        }
+    }
+
+    TranscodeStats {
+        image_id,
+        transcoded: succeeded.len(),
+        failed: segments.len() - succeeded.len(),
+        realtime_factor: realtime_factor(media_secs, encode_ms),
+        slowest_realtime_factor: succeeded.iter().filter_map(|s| s.realtime_factor).reduce(f64::min),
+        average_queued_ms: (!segments.is_empty())
+            .then(|| segments.iter().map(|s| s.queued_ms as f64).sum::<f64>() / segments.len() as f64),
+        encoders,
+        segments,
+    }
+}
+
+/// Records `timing` for the library file at `file_path` in the background.
+/// Files that aren't in the library (camera RAW proxies) aren't recorded.
+pub fn record(app_handle: &tauri::AppHandle, file_path: &Path, timing: SegmentTiming) {
+    let Some(db) = app_handle.try_state::<Arc<Db>>().map(|db| db.inner().clone()) else {
+        return;
+    };
+    let known = known_files().lock().unwrap_or_else(|e| e.into_inner()).get(file_path).copied();
+    let file_path = file_path.to_path_buf();
+    tauri::async_runtime::spawn(async move {
+        let image_id = match known {
+            Some(image_id) => image_id,
+            None => match db.get_image_id_by_path(&crate::paths::to_db(&file_path)).await {
+                Ok(image_id) => {
+                    let mut known = known_files().lock().unwrap_or_else(|e| e.into_inner());
+                    if known.len() >= MAX_KNOWN_FILES {
+                        known.clear();
+                    }
+                    known.insert(file_path.clone(), image_id);
+                    image_id
+                }
+                Err(e) => {
+                    eprintln!("WARN: Could not look up {:?} to record its transcode timing: {}", file_path, e);
+                    return;
+                }
+            },
+        };
+        let Some(image_id) = image_id else {
+            return;
+        };
+        if let Err(e) = db.record_segment_timing(image_id, &timing).await {
+            eprintln!("WARN: Could not record transcode timing of {:?}: {}", file_path, e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(segment_index: u32, encoder: &str, encode_ms: u64, error: Option<&str>) -> SegmentTiming {
+        SegmentTiming {
+            segment_index,
+            quality: "standard".to_string(),
+            encoder: encoder.to_string(),
+            queued: Duration::from_millis(100),
+            encoding: Duration::from_millis(encode_ms),
+            media_secs: 10.0,
+            error: error.map(str::to_string),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recorded_segments_are_summed_up() {
+        let library = crate::testkit::TestLibrary::open("transcode-stats").await;
+        let db = &library.db;
+        library.seed_images(&["clip.mkv"]).await;
+
+        db.record_segment_timing(1, &timing(0, "libx264", 5000, None)).await.unwrap();
+        db.record_segment_timing(1, &timing(1, "libx264", 20000, None)).await.unwrap();
+        db.record_segment_timing(1, &timing(2, "libx264", 60000, Some("FFmpeg timed out on segment 2"))).await.unwrap();
+        db.record_segment_timing(1, &timing(3, "h264_videotoolbox", 5000, None)).await.unwrap();
+
+        let stats = summarize(1, db.get_segment_timings(1).await.unwrap());
+        assert_eq!(stats.segments.iter().map(|s| s.segment_index).collect::<Vec<_>>(), vec![3, 2, 1, 0]);
+        assert_eq!((stats.transcoded, stats.failed), (3, 1));
+        assert_eq!(stats.realtime_factor, Some(1.0), "30 media seconds in 30 seconds");
+        assert_eq!(stats.slowest_realtime_factor, Some(0.5));
+        assert_eq!(stats.segments[1].realtime_factor, None);
+        assert_eq!(stats.average_queued_ms, Some(100.0));
+        assert_eq!(stats.encoders, vec!["h264_videotoolbox".to_string(), "libx264".to_string()]);
+
+        assert!(summarize(2, db.get_segment_timings(2).await.unwrap()).realtime_factor.is_none());
+    }
+}
@@ -0,0 +1,246 @@
+//! Synthetic fixture library.
+//!
+//! Every sample is generated in code, so the tests don't depend on binary
+//! files in the repository, and the output is byte-for-byte the same on
+//! every run: pixels follow fixed patterns, archive entries carry a fixed
+//! date and files get a fixed modification time.
+
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use image::{DynamicImage, ImageFormat, RgbImage};
+use zip::write::SimpleFileOptions;
+
+/// Modification time given to every generated file (2024-01-01).
+pub const FIXED_MTIME: Duration = Duration::from_secs(1_704_067_200);
+
+fn pattern(width: u32, height: u32, seed: u8) -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+        image::Rgb([(x * 4) as u8 ^ seed, (y * 4) as u8, seed.wrapping_mul(37)])
+    }))
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image.write_to(&mut Cursor::new(&mut bytes), format).expect("encoding a fixture");
+    bytes
+}
+
+pub fn jpeg(width: u32, height: u32, seed: u8) -> Vec<u8> {
+    encode(&pattern(width, height, seed), ImageFormat::Jpeg)
+}
+
+pub fn png(width: u32, height: u32, seed: u8) -> Vec<u8> {
+    encode(&pattern(width, height, seed), ImageFormat::Png)
+}
+
+fn atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut atom = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+    atom.extend_from_slice(kind);
+    atom.extend_from_slice(body);
+    atom
+}
+
+/// MP4 with a valid `ftyp`/`moov` structure and no tracks: enough for the
+/// indexer, and for the thumbnailer to take its no-frame path.
+pub fn mp4() -> Vec<u8> {
+    let mut mvhd = vec![0u8; 12]; // version, flags, creation and modification times
+    mvhd.extend_from_slice(&1000u32.to_be_bytes()); // timescale
+    mvhd.extend_from_slice(&0u32.to_be_bytes()); // duration
+    mvhd.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // rate 1.0
+    mvhd.extend_from_slice(&0x0100u16.to_be_bytes()); // volume 1.0
+    mvhd.extend_from_slice(&[0u8; 10]);
+    for value in [0x0001_0000u32, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000] {
+        mvhd.extend_from_slice(&value.to_be_bytes()); // identity matrix
+    }
+    mvhd.extend_from_slice(&[0u8; 24]);
+    mvhd.extend_from_slice(&1u32.to_be_bytes()); // next track id
+
+    let mut file = atom(b"ftyp", b"isom\x00\x00\x02\x00isommp41");
+    file.extend(atom(b"moov", &atom(b"mvhd", &mvhd)));
+    file.extend(atom(b"mdat", &[]));
+    file
+}
+
+/// Flat 8-bit RGB Photoshop file with raw image data and no layers.
+pub fn psd(width: u32, height: u32, seed: u8) -> Vec<u8> {
+    let mut file = b"8BPS".to_vec();
+    file.extend_from_slice(&1u16.to_be_bytes());
+    file.extend_from_slice(&[0u8; 6]);
+    file.extend_from_slice(&3u16.to_be_bytes()); // channels
+    file.extend_from_slice(&height.to_be_bytes());
+    file.extend_from_slice(&width.to_be_bytes());
+    file.extend_from_slice(&8u16.to_be_bytes()); // depth
+    file.extend_from_slice(&3u16.to_be_bytes()); // RGB
+    file.extend_from_slice(&0u32.to_be_bytes()); // color mode data
+    file.extend_from_slice(&0u32.to_be_bytes()); // image resources
+    file.extend_from_slice(&0u32.to_be_bytes()); // layer and mask info
+    file.extend_from_slice(&0u16.to_be_bytes()); // raw image data, planar
+    let pixels = pattern(width, height, seed).into_rgb8();
+    for channel in 0..3 {
+        file.extend(pixels.pixels().map(|p| p[channel]));
+    }
+    file
+}
+
+/// ZIP archive with the given entries.
+pub fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let options = SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored)
+        .last_modified_time(zip::DateTime::default());
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, data) in entries {
+        writer.start_file(*name, options).expect("zip entry");
+        writer.write_all(data).expect("zip data");
+    }
+    writer.finish().expect("zip archive").into_inner()
+}
+
+/// Krita document: a ZIP-based project whose preview is `mergedimage.png`.
+pub fn kra(width: u32, height: u32, seed: u8) -> Vec<u8> {
+    zip(&[
+        ("mimetype", b"application/x-krita"),
+        ("maindoc.xml", b"<?xml version=\"1.0\"?><DOC/>"),
+        ("mergedimage.png", &png(width, height, seed)),
+    ])
+}
+
+/// Affinity document whose file table lists `entries` as
+/// `(name, data, compressed)`, in the layout `thumbnails::affinity` reads.
+pub fn affinity(entries: &[(&str, &[u8], bool)]) -> Vec<u8> {
+    const HEADER_LEN: usize = 32;
+    let mut body = Vec::new();
+    let mut table = b"#Fil".to_vec();
+    table.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for (name, data, compressed) in entries {
+        table.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        table.extend_from_slice(name.as_bytes());
+        table.extend_from_slice(&((HEADER_LEN + body.len()) as u64).to_le_bytes());
+        table.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        table.push(u8::from(*compressed));
+        body.extend_from_slice(data);
+    }
+
+    let mut file = b"\x00\xFFKAnrsP".to_vec();
+    file.extend_from_slice(&11u32.to_le_bytes()); // format version
+    file.extend_from_slice(b"#Inf");
+    file.extend_from_slice(&((HEADER_LEN + body.len()) as u64).to_le_bytes());
+    file.extend_from_slice(&(table.len() as u64).to_le_bytes());
+    file.extend(body);
+    file.extend(table);
+    file
+}
+
+/// A generated library in a temporary folder, deleted on drop.
+pub struct FixtureLibrary {
+    pub root: PathBuf,
+}
+
+impl FixtureLibrary {
+    /// Files the indexer should pick up, relative to the root.
+    pub const MEDIA: &'static [&'static str] = &[
+        "cover.jpg",
+        "2024/trip/beach.jpg",
+        "2024/trip/sunset.png",
+        "2024/trip/clip.mp4",
+        "2024/trip/raw/IMG_0001.jpg",
+        "2024/design/poster.psd",
+        "2024/design/sketch.kra",
+        "inbox/scan.png",
+    ];
+
+    /// Folders of the library, the root (`""`) included.
+    pub const FOLDERS: &'static [&'static str] = &["", "2024", "2024/trip", "2024/trip/raw", "2024/design", "inbox"];
+
+    /// Creates the library under a fresh temporary folder named after `name`.
+    pub fn generate(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("mundam-fixture-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).expect("fixture root");
+        // Paths are compared with what the indexer stores, which is canonical
+        let library = Self { root: root.canonicalize().expect("fixture root") };
+
+        library.write("cover.jpg", &jpeg(64, 48, 1));
+        library.write("2024/trip/beach.jpg", &jpeg(80, 60, 2));
+        library.write("2024/trip/sunset.png", &png(60, 80, 3));
+        library.write("2024/trip/clip.mp4", &mp4());
+        library.write("2024/trip/raw/IMG_0001.jpg", &jpeg(40, 40, 4));
+        library.write("2024/design/poster.psd", &psd(32, 32, 5));
+        library.write("2024/design/sketch.kra", &kra(48, 32, 6));
+        library.write("inbox/scan.png", &png(30, 50, 7));
+        // Not media: the indexer must skip these
+        library.write("inbox/notes.zip", &zip(&[("notes.txt", b"fixture")]));
+        library.write("inbox/readme.txt", b"fixture");
+        library
+    }
+
+    pub fn path(&self, relative: &str) -> PathBuf {
+        relative.split('/').filter(|part| !part.is_empty()).fold(self.root.clone(), |path, part| path.join(part))
+    }
+
+    /// Path of a file as stored in the library.
+    pub fn db_path(&self, relative: &str) -> String {
+        self.path(relative).to_string_lossy().to_string()
+    }
+
+    /// Writes a file with the fixed modification time, creating its folders.
+    pub fn write(&self, relative: &str, data: &[u8]) -> PathBuf {
+        let path = self.path(relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("fixture folder");
+        }
+        std::fs::write(&path, data).expect("fixture file");
+        let file = std::fs::File::options().write(true).open(&path).expect("fixture file");
+        file.set_modified(SystemTime::UNIX_EPOCH + FIXED_MTIME).expect("fixture mtime");
+        path
+    }
+
+    pub fn rename(&self, from: &str, to: &str) {
+        let target = self.path(to);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).expect("fixture folder");
+        }
+        std::fs::rename(self.path(from), target).expect("fixture rename");
+    }
+
+    pub fn remove(&self, relative: &str) {
+        std::fs::remove_file(self.path(relative)).expect("fixture removal");
+    }
+}
+
+impl Drop for FixtureLibrary {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_are_valid() {
+        assert_eq!(image::load_from_memory(&jpeg(64, 48, 1)).unwrap().width(), 64);
+        assert_eq!(image::load_from_memory(&png(30, 50, 7)).unwrap().height(), 50);
+
+        let psd = psd::Psd::from_bytes(&psd(32, 16, 5)).unwrap();
+        assert_eq!((psd.width(), psd.height()), (32, 16));
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(kra(48, 32, 6))).unwrap();
+        assert!(archive.by_name("mergedimage.png").is_ok());
+
+        let mp4 = mp4();
+        assert_eq!(&mp4[4..8], b"ftyp");
+        let moov = u32::from_be_bytes(mp4[24..28].try_into().unwrap()) as usize;
+        assert_eq!(&mp4[28..32], b"moov");
+        assert_eq!(moov, 8 + 8 + 100);
+    }
+
+    #[test]
+    fn test_generation_is_deterministic() {
+        assert_eq!(jpeg(20, 20, 3), jpeg(20, 20, 3));
+        assert_eq!(kra(16, 16, 1), kra(16, 16, 1));
+        assert_ne!(png(16, 16, 1), png(16, 16, 2));
+    }
+}
@@ -0,0 +1,121 @@
+//! Test harness for the indexing pipeline.
+//!
+//! [`fixtures`] generates a small library of valid media files, and the
+//! helpers below give a test its own database, seeded with images when it
+//! doesn't need real files, a mock Tauri app to hand to the indexer, watcher
+//! and thumbnail worker, and a way to wait for their background tasks. The
+//! end-to-end tests live in `pipeline`.
+
+pub mod fixtures;
+mod pipeline;
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::db::Db;
+use crate::indexer::WatcherRegistry;
+
+/// Database and scratch folder of one test, deleted on drop.
+pub struct TestLibrary {
+    pub db: Arc<Db>,
+    pub dir: PathBuf,
+    pub registry: Arc<tokio::sync::Mutex<WatcherRegistry>>,
+}
+
+impl TestLibrary {
+    /// Opens a migrated library in a fresh folder named after `name`.
+    pub async fn open(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("mundam-testkit-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("test library folder");
+        let db = Db::new(dir.join("mundam.db")).await.expect("test library");
+        Self { db: Arc::new(db), dir, registry: Arc::default() }
+    }
+
+    pub fn thumbnails_dir(&self) -> PathBuf {
+        let dir = self.dir.join("thumbnails");
+        std::fs::create_dir_all(&dir).expect("thumbnails folder");
+        dir
+    }
+
+    /// Adds `filenames` under a `/lib` root folder, without touching the
+    /// disk: size 100, format from the extension, all dated 2024-01-01.
+    /// Returns their ids, which in a fresh library are `1..=filenames.len()`.
+    pub async fn seed_images<S: AsRef<str>>(&self, filenames: &[S]) -> Vec<i64> {
+        sqlx::query("INSERT OR IGNORE INTO folders (id, path, name, is_root) VALUES (1, '/lib', 'lib', 1)")
+            .execute(&self.db.pool)
+            .await
+            .expect("library folder");
+        let mut ids = Vec::with_capacity(filenames.len());
+        for filename in filenames.iter().map(AsRef::as_ref) {
+            let format = filename.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()).unwrap_or_default();
+            let id = sqlx::query(
+                "INSERT INTO images (folder_id, path, filename, size, format, created_at, modified_at)
+                 VALUES (1, ?, ?, 100, ?, '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')",
+            )
+            .bind(format!("/lib/{}", filename))
+            .bind(filename)
+            .bind(format)
+            .execute(&self.db.pool)
+            .await
+            .expect("seeded image")
+            .last_insert_rowid();
+            ids.push(id);
+        }
+        ids
+    }
+
+    pub async fn image_paths(&self) -> Vec<String> {
+        let rows: Vec<(String,)> = sqlx::query_as("SELECT path FROM images ORDER BY path")
+            .fetch_all(&self.db.pool)
+            .await
+            .expect("image paths");
+        rows.into_iter().map(|(path,)| path).collect()
+    }
+
+    pub async fn folder_paths(&self) -> Vec<String> {
+        let rows: Vec<(String,)> = sqlx::query_as("SELECT path FROM folders ORDER BY path")
+            .fetch_all(&self.db.pool)
+            .await
+            .expect("folder paths");
+        rows.into_iter().map(|(path,)| path).collect()
+    }
+
+    /// Stops the watchers started by scans.
+    pub async fn stop_watchers(&self) {
+        for (_, stop) in self.registry.lock().await.watchers.drain() {
+            let _ = stop.send(());
+        }
+    }
+}
+
+impl Drop for TestLibrary {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// App handle for code that emits events or looks up managed state.
+pub fn mock_app() -> tauri::App<tauri::test::MockRuntime> {
+    tauri::test::mock_app()
+}
+
+/// Polls `check` until it holds or `timeout` runs out. Returns whether it held.
+pub async fn wait_until<F, Fut>(timeout: Duration, mut check: F) -> bool
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if check().await {
+            return true;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
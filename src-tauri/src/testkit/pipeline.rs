@@ -0,0 +1,173 @@
+//! End-to-end tests of the indexer, watcher and thumbnail worker against a
+//! generated library.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::fixtures::{jpeg, FixtureLibrary};
+use super::{mock_app, wait_until, TestLibrary};
+use crate::indexer::scan::run_scan;
+use crate::thumbnails::{get_thumbnail_filename, GRID_THUMBNAIL_SIZE};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+async fn image_count(library: &TestLibrary) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM images")
+        .fetch_one(&library.db.pool)
+        .await
+        .unwrap()
+}
+
+async fn image_size(library: &TestLibrary, path: &str) -> Option<(i64, Option<i32>)> {
+    sqlx::query_as("SELECT size, width FROM images WHERE path = ?")
+        .bind(path)
+        .fetch_optional(&library.db.pool)
+        .await
+        .unwrap()
+}
+
+/// Scans the fixture and waits until every media file is saved.
+async fn scan(app: &tauri::App<tauri::test::MockRuntime>, library: &TestLibrary, fixture: &FixtureLibrary) {
+    run_scan(app.handle().clone(), library.db.clone(), library.registry.clone(), fixture.root.clone()).await;
+    let expected = FixtureLibrary::MEDIA.len() as i64;
+    assert!(wait_until(TIMEOUT, || async move { image_count(library).await == expected }).await, "scan did not finish");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_scan_indexes_fixture_library() {
+    let fixture = FixtureLibrary::generate("scan");
+    let library = &TestLibrary::open("scan").await;
+    let app = mock_app();
+    scan(&app, library, &fixture).await;
+
+    let mut expected: Vec<String> = FixtureLibrary::MEDIA.iter().map(|f| fixture.db_path(f)).collect();
+    expected.sort();
+    assert_eq!(library.image_paths().await, expected);
+
+    let mut folders: Vec<String> = FixtureLibrary::FOLDERS.iter().map(|f| fixture.db_path(f)).collect();
+    folders.sort();
+    assert_eq!(library.folder_paths().await, folders);
+
+    let beach = fixture.db_path("2024/trip/beach.jpg");
+    let dimensions: (Option<i32>, Option<i32>) = sqlx::query_as("SELECT width, height FROM images WHERE path = ?")
+        .bind(&beach)
+        .fetch_one(&library.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(dimensions, (Some(80), Some(60)));
+
+    library.stop_watchers().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rescan_reindexes_changed_files() {
+    let fixture = FixtureLibrary::generate("rescan");
+    let library = &TestLibrary::open("rescan").await;
+    let app = mock_app();
+    scan(&app, library, &fixture).await;
+    library.stop_watchers().await;
+
+    let beach = fixture.db_path("2024/trip/beach.jpg");
+    let before: Vec<(i64, String)> = sqlx::query_as("SELECT id, path FROM images ORDER BY id")
+        .fetch_all(&library.db.pool)
+        .await
+        .unwrap();
+
+    // Same modification time, different size: still picked up
+    fixture.write("2024/trip/beach.jpg", &jpeg(120, 90, 2));
+    scan(&app, library, &fixture).await;
+    let beach = &beach;
+    assert!(wait_until(TIMEOUT, || async move { image_size(library, beach).await.is_some_and(|(_, w)| w == Some(120)) }).await);
+
+    let after: Vec<(i64, String)> = sqlx::query_as("SELECT id, path FROM images ORDER BY id")
+        .fetch_all(&library.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(after, before, "a rescan must keep image ids");
+
+    library.stop_watchers().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_watcher_follows_renames_additions_and_removals() {
+    let fixture = FixtureLibrary::generate("watch");
+    let library = &TestLibrary::open("watch").await;
+    let app = mock_app();
+    scan(&app, library, &fixture).await;
+    let root = &fixture.db_path("");
+    assert!(wait_until(TIMEOUT, || async move { library.registry.lock().await.watchers.contains_key(root) }).await);
+    // Let the watcher settle so the changes below aren't mixed with the scan's own writes
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let old_path = &fixture.db_path("inbox/scan.png");
+    let new_path = &fixture.db_path("inbox/scan-renamed.png");
+    let (id,): (i64,) = sqlx::query_as("SELECT id FROM images WHERE path = ?")
+        .bind(old_path)
+        .fetch_one(&library.db.pool)
+        .await
+        .unwrap();
+
+    fixture.rename("inbox/scan.png", "inbox/scan-renamed.png");
+    fixture.write("inbox/new.jpg", &jpeg(16, 16, 9));
+    fixture.remove("cover.jpg");
+
+    let added = &fixture.db_path("inbox/new.jpg");
+    let removed = &fixture.db_path("cover.jpg");
+    let settled = wait_until(TIMEOUT, || async move {
+        let paths = library.image_paths().await;
+        paths.contains(new_path) && paths.contains(added) && !paths.contains(old_path) && !paths.contains(removed)
+    })
+    .await;
+    assert!(settled, "watcher did not apply the changes: {:?}", library.image_paths().await);
+
+    let (renamed_id,): (i64,) = sqlx::query_as("SELECT id FROM images WHERE path = ?")
+        .bind(new_path)
+        .fetch_one(&library.db.pool)
+        .await
+        .unwrap();
+    assert_eq!(renamed_id, id, "a rename must keep the image, tags and all");
+
+    library.stop_watchers().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_thumbnail_worker_renders_fixture_library() {
+    let fixture = FixtureLibrary::generate("thumbs");
+    let library = &TestLibrary::open("thumbs").await;
+    let app = mock_app();
+    scan(&app, library, &fixture).await;
+    library.stop_watchers().await;
+
+    let thumbnails_dir = library.thumbnails_dir();
+    let config = crate::settings::config::AppConfig { thumbnail_threads: 2, ..Default::default() };
+    let priority = Arc::new(crate::thumbnails::priority::ThumbnailPriorityState::default());
+    crate::thumbnails::worker::ThumbnailWorker::new(library.db.clone(), thumbnails_dir.clone(), app.handle().clone(), config, priority)
+        .start()
+        .await;
+
+    // Every image ends up with a thumbnail or a recorded failure
+    let settled = wait_until(Duration::from_secs(60), || async move {
+        let pending: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM images WHERE thumbnail_path IS NULL AND thumbnail_attempts < 3 AND corrupt_suspected = 0",
+        )
+        .fetch_one(&library.db.pool)
+        .await
+        .unwrap();
+        pending == 0
+    })
+    .await;
+    assert!(settled, "thumbnail worker did not finish");
+
+    for file in FixtureLibrary::MEDIA.iter().filter(|f| f.ends_with(".jpg") || f.ends_with(".png")) {
+        let path = fixture.db_path(file);
+        let thumbnail: Option<String> = sqlx::query_scalar("SELECT thumbnail_path FROM images WHERE path = ?")
+            .bind(&path)
+            .fetch_one(&library.db.pool)
+            .await
+            .unwrap();
+        assert_eq!(thumbnail, Some(get_thumbnail_filename(&path, GRID_THUMBNAIL_SIZE)), "{}", file);
+
+        let rendered = image::open(thumbnails_dir.join(thumbnail.unwrap())).unwrap();
+        assert!(rendered.width() <= 300 && rendered.height() <= 300, "{}", file);
+    }
+}
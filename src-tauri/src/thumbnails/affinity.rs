@@ -1,66 +1,344 @@
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 
 const PNG_SIGNATURE: &[u8; 8] = b"\x89\x50\x4e\x47\x0d\x0a\x1a\x0a";
-const PNG_IEND: &[u8; 4] = b"IEND";
-
-/// Extract the largest PNG preview from Affinity files (.afphoto, .afdesign, .afpub)
-/// using binary signature scanning.
-pub fn extract_largest_png(input_path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let mut file = File::open(input_path)?;
-    let metadata = file.metadata()?;
-    let file_size = metadata.len();
-
-    // Affinity files can be huge. We'll try to find the PNG at the end of the file first.
-    // Most Affinity previews are stored in the last 15-20MB.
-    let scan_size = 15 * 1024 * 1024; // 15MB
-    let start_offset = if file_size > scan_size {
-        file_size - scan_size
-    } else {
-        0
-    };
-
-    file.seek(SeekFrom::Start(start_offset))?;
-    let mut buffer = Vec::with_capacity((file_size - start_offset) as usize);
-    file.read_to_end(&mut buffer)?;
-
-    let mut best_png: Option<(usize, usize)> = None; // (start, length)
 
+/// Every Affinity document (v1 and v2, all three apps) starts with these bytes.
+const AFFINITY_MAGIC: &[u8; 4] = b"\x00\xFFKA";
+
+/// Tags of the container header; they are stored as little-endian integers,
+/// which is why they read backwards.
+const PERSONA_TAG: &[u8; 4] = b"nrsP";
+const INFO_TAG: &[u8; 4] = b"#Inf";
+const FILE_TABLE_TAG: &[u8; 4] = b"#Fil";
+
+/// Name of the file table entry that holds the document preview.
+const PREVIEW_ENTRY: &str = "preview.png";
+
+/// File table flag of entries whose data is zstd-compressed.
+const ENTRY_COMPRESSED: u8 = 0x01;
+
+/// Size of the windows scanned at each end of the file before falling back to a full scan.
+const SCAN_WINDOW: usize = 15 * 1024 * 1024;
+
+/// Longest side of a PNG that can be the document preview. Raster layers and
+/// placed images are stored as PNGs too and are usually much bigger.
+const MAX_PREVIEW_SIDE: u32 = 2048;
+
+/// A PNG stream found inside the container.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct EmbeddedPng {
+    start: usize,
+    len: usize,
+    width: u32,
+    height: u32,
+}
+
+/// An entry of the container's file table.
+#[derive(Debug, Clone, PartialEq)]
+struct ContainerEntry {
+    name: String,
+    start: usize,
+    len: usize,
+    compressed: bool,
+}
+
+/// Extract the document preview from Affinity files (.afphoto, .afdesign, .afpub).
+///
+/// The preview is the `preview.png` entry of the container's file table. When
+/// the table can't be read (a layout this parser doesn't know), the embedded
+/// PNG streams are located by walking their chunks instead: v1 files keep the
+/// preview near the end, and v2 files can also hold large raster layers as
+/// PNGs, which is why the preview is then picked by dimensions.
+pub fn extract_preview_png(input_path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let file = File::open(input_path)?;
+    // SAFETY: the file is only read, and a concurrent truncation can at worst
+    // fail this one preview extraction.
+    let data = unsafe { memmap2::Mmap::map(&file)? };
+
+    if !data.starts_with(AFFINITY_MAGIC) {
+        return Err("Not an Affinity document".into());
+    }
+
+    if let Some(preview) = read_file_table(&data).and_then(|entries| table_preview(&data, &entries)) {
+        return Ok(preview.to_vec());
+    }
+
+    // The tail holds the preview in most files; the head is checked next, and
+    // only then the whole file, which can be several gigabytes.
+    let size = data.len();
+    let window = SCAN_WINDOW.min(size);
+    let mut windows = vec![size - window..size];
+    if size > window {
+        windows.push(0..window.min(size - window));
+        windows.push(0..size);
+    }
+
+    for range in windows {
+        let buffer = &data[range];
+        if let Some(png) = select_preview(&find_pngs(buffer)) {
+            return Ok(buffer[png.start..png.start + png.len].to_vec());
+        }
+    }
+
+    Err("No PNG preview found in Affinity file".into())
+}
+
+/// Reads the file table the container header points at.
+///
+/// The header is the magic, the persona tag and format version, then the
+/// info tag followed by the offset and length of the file table (u64 LE).
+/// The table is its tag, an entry count (u32 LE), and for each entry its name
+/// (u16 LE length and UTF-8 bytes), data offset and length (u64 LE) and flags.
+fn read_file_table(data: &[u8]) -> Option<Vec<ContainerEntry>> {
+    let mut header = Reader::new(data);
+    header.tag(AFFINITY_MAGIC)?;
+    header.tag(PERSONA_TAG)?;
+    header.u32()?;
+    header.tag(INFO_TAG)?;
+    let table_start = usize::try_from(header.u64()?).ok()?;
+    let table_len = usize::try_from(header.u64()?).ok()?;
+
+    let table = data.get(table_start..table_start.checked_add(table_len)?)?;
+    let mut reader = Reader::new(table);
+    reader.tag(FILE_TABLE_TAG)?;
+    let count = reader.u32()?;
+
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let name_len = reader.u16()? as usize;
+        let name = std::str::from_utf8(reader.bytes(name_len)?).ok()?.to_string();
+        let start = usize::try_from(reader.u64()?).ok()?;
+        let len = usize::try_from(reader.u64()?).ok()?;
+        let flags = reader.bytes(1)?[0];
+        if start.checked_add(len)? > data.len() {
+            return None;
+        }
+        entries.push(ContainerEntry { name, start, len, compressed: flags & ENTRY_COMPRESSED != 0 });
+    }
+    Some(entries)
+}
+
+/// Returns the preview entry's data, if it is a stored, complete PNG.
+fn table_preview<'a>(data: &'a [u8], entries: &[ContainerEntry]) -> Option<&'a [u8]> {
+    let entry = entries.iter().find(|entry| {
+        !entry.compressed && entry.name.rsplit('/').next() == Some(PREVIEW_ENTRY)
+    })?;
+    let stream = &data[entry.start..entry.start + entry.len];
+    if !stream.starts_with(PNG_SIGNATURE) {
+        return None;
+    }
+    parse_png_at(stream, 0).map(|png| &stream[..png.len])
+}
+
+/// Little-endian cursor over the container's structures.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn tag(&mut self, tag: &[u8; 4]) -> Option<()> {
+        (self.bytes(4)? == tag).then_some(())
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.bytes(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.bytes(8)?.try_into().ok()?))
+    }
+}
+
+/// Finds every complete PNG stream in `buffer`.
+fn find_pngs(buffer: &[u8]) -> Vec<EmbeddedPng> {
+    let mut pngs = Vec::new();
     let mut i = 0;
-    while i <= buffer.len().saturating_sub(8) {
-        if &buffer[i..i + 8] == PNG_SIGNATURE {
-            // Found a PNG signature!
-            // Search limit: 50MB (like the JS script) or until end of buffer.
-            let search_limit = (i + 50 * 1024 * 1024).min(buffer.len());
-            
-            if let Some(iend_rel_offset) = find_iend(&buffer[i + 8..search_limit]) {
-                // The chunk ends after "IEND" (4 bytes) and CRC (4 bytes).
-                let png_length = iend_rel_offset + 8 + 4 + 4; 
-                
-                // We want the largest PNG found (assuming it's the high-res one)
-                let current_length = png_length;
-                if best_png.map_or(true, |(_, best_len)| current_length > best_len) {
-                    best_png = Some((i, current_length));
-                }
-                
-                i += png_length;
+    while i + PNG_SIGNATURE.len() <= buffer.len() {
+        if &buffer[i..i + PNG_SIGNATURE.len()] == PNG_SIGNATURE {
+            if let Some(png) = parse_png_at(buffer, i) {
+                pngs.push(png);
+                i += png.len;
                 continue;
             }
         }
         i += 1;
     }
+    pngs
+}
+
+/// Walks the chunks of a PNG starting at `start` and returns its extent and size.
+///
+/// Following chunk lengths (rather than searching for the `IEND` bytes) keeps
+/// compressed data that happens to contain "IEND" from cutting the stream short.
+fn parse_png_at(buffer: &[u8], start: usize) -> Option<EmbeddedPng> {
+    let mut pos = start + PNG_SIGNATURE.len();
+    let mut size = None;
 
-    if let Some((start, length)) = best_png {
-        let end = (start + length).min(buffer.len());
-        Ok(buffer[start..end].to_vec())
-    } else {
-        Err("No PNG preview found in Affinity file".into())
+    loop {
+        let header = buffer.get(pos..pos + 8)?;
+        let len = u32::from_be_bytes(header[0..4].try_into().ok()?) as usize;
+        let chunk_type = &header[4..8];
+        // Length, type, data and CRC
+        let end = pos.checked_add(12)?.checked_add(len)?;
+        if end > buffer.len() {
+            return None;
+        }
+
+        if size.is_none() {
+            // IHDR must come first and carries the dimensions
+            if chunk_type != b"IHDR" || len != 13 {
+                return None;
+            }
+            let data = &buffer[pos + 8..pos + 16];
+            let width = u32::from_be_bytes(data[0..4].try_into().ok()?);
+            let height = u32::from_be_bytes(data[4..8].try_into().ok()?);
+            size = Some((width, height));
+        } else if chunk_type == b"IEND" {
+            let (width, height) = size?;
+            return Some(EmbeddedPng { start, len: end - start, width, height });
+        }
+        pos = end;
     }
 }
 
-fn find_iend(data: &[u8]) -> Option<usize> {
-    // Search for "IEND" string
-    data.windows(4).position(|window| window == PNG_IEND)
+/// Picks the largest PNG that fits the preview size, or the largest one overall.
+fn select_preview(pngs: &[EmbeddedPng]) -> Option<EmbeddedPng> {
+    let area = |p: &&EmbeddedPng| p.width as u64 * p.height as u64;
+    pngs.iter()
+        .filter(|p| p.width.max(p.height) <= MAX_PREVIEW_SIDE)
+        .max_by_key(area)
+        .or_else(|| pngs.iter().max_by_key(area))
+        .copied()
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::fixtures;
+
+    /// Encodes a PNG; `noisy` images barely compress, like real raster layers.
+    fn png(width: u32, height: u32, noisy: bool) -> Vec<u8> {
+        let mut seed: u32 = 0x1234_5678;
+        let img = image::RgbaImage::from_fn(width, height, |_, _| {
+            if noisy {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                image::Rgba(seed.to_le_bytes())
+            } else {
+                image::Rgba([200, 100, 50, 255])
+            }
+        });
+        let mut out = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut out, image::ImageFormat::Png).unwrap();
+        out.into_inner()
+    }
+
+    /// A container without a readable file table, for the chunk walk.
+    fn container(parts: &[&[u8]]) -> Vec<u8> {
+        let mut data = AFFINITY_MAGIC.to_vec();
+        data.extend_from_slice(b"nrsP\x0b\x00\x00\x00#Inf");
+        for part in parts {
+            data.extend_from_slice(&[0u8; 64]);
+            data.extend_from_slice(part);
+        }
+        data
+    }
+
+    fn extract(data: &[u8]) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!("mundam_affinity_{}.afphoto", uuid::Uuid::new_v4()));
+        std::fs::write(&path, data).unwrap();
+        let result = extract_preview_png(&path);
+        let _ = std::fs::remove_file(&path);
+        result.unwrap()
+    }
+
+    #[test]
+    fn test_file_table_preview() {
+        let preview = fixtures::png(256, 192, 1);
+        // v1 documents store the preview after the document data
+        let v1 = fixtures::affinity(&[("doc.dat", b"document data", false), ("preview.png", &preview, false)]);
+        assert_eq!(extract(&v1), preview);
+
+        // v2 documents also store raster layers as PNGs; a preview-sized one
+        // would win the chunk walk, so only the table tells them apart
+        let layer = fixtures::png(512, 512, 2);
+        let v2 = fixtures::affinity(&[
+            ("Thumbnails/preview.png", &preview, false),
+            ("Layers/0001.png", &layer, false),
+            ("doc.dat", b"(\xb5/\xfd compressed", true),
+        ]);
+        let entries = read_file_table(&v2).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].name, "Thumbnails/preview.png");
+        assert!(entries[2].compressed);
+        assert_eq!(extract(&v2), preview);
+    }
+
+    #[test]
+    fn test_truncated_file_table_falls_back_to_chunk_walk() {
+        let preview = fixtures::png(64, 48, 3);
+        let mut data = fixtures::affinity(&[("preview.png", &preview, false)]);
+        data.truncate(data.len() - 4);
+        assert!(read_file_table(&data).is_none());
+        assert_eq!(extract(&data), preview);
+    }
+
+    #[test]
+    fn test_v1_layout_preview_at_end() {
+        let preview = png(512, 384, false);
+        let data = container(&[b"document data", &preview]);
+        assert_eq!(extract(&data), preview);
+    }
+
+    #[test]
+    fn test_v2_layout_skips_large_raster_layer() {
+        // The raster layer is bigger in bytes, but not a preview-sized image
+        let preview = png(512, 384, false);
+        let layer = png(3000, 40, true);
+        assert!(layer.len() > preview.len());
+
+        let data = container(&[&preview, &layer]);
+        assert_eq!(extract(&data), preview);
+    }
+
+    #[test]
+    fn test_iend_bytes_inside_chunk_data() {
+        let preview = png(64, 64, false);
+        // Insert a private chunk whose payload spells IEND right after IHDR
+        let ihdr_end = 8 + 12 + 13;
+        let mut tricky = preview[..ihdr_end].to_vec();
+        tricky.extend_from_slice(&4u32.to_be_bytes());
+        tricky.extend_from_slice(b"prVt");
+        tricky.extend_from_slice(b"IEND");
+        tricky.extend_from_slice(&[0u8; 4]);
+        tricky.extend_from_slice(&preview[ihdr_end..]);
+
+        let pngs = find_pngs(&tricky);
+        assert_eq!(pngs.len(), 1);
+        assert_eq!(pngs[0].len, tricky.len());
+        assert_eq!((pngs[0].width, pngs[0].height), (64, 64));
+    }
+
+    #[test]
+    fn test_rejects_non_affinity_files() {
+        let path = std::env::temp_dir().join(format!("mundam_affinity_{}.afdesign", uuid::Uuid::new_v4()));
+        std::fs::write(&path, png(16, 16, false)).unwrap();
+        assert!(extract_preview_png(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -2,7 +2,6 @@ use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use fast_image_resize as fr;
-use crate::thumbnails::native::encode_webp_native;
 
 /// Extract preview from ZIP-based formats (Affinity, XMind, etc.)
 pub fn generate_thumbnail_zip_preview(
@@ -28,33 +27,19 @@ pub fn generate_thumbnail_zip_preview(
             entry.read_to_end(&mut buf)?;
             
             // Decode the preview image
+            let _reservation = crate::thumbnails::memory::reserve_blob(&buf);
             let img = image::load_from_memory(&buf)?;
             let width = img.width();
             let height = img.height();
             
-            let aspect = width as f32 / height as f32;
-            let (new_w, new_h) = if aspect > 1.0 {
-                (size_px, (size_px as f32 / aspect).max(1.0) as u32)
-            } else {
-                (((size_px as f32 * aspect).max(1.0)) as u32, size_px)
-            };
-            
-            let src_image = fr::images::Image::from_vec_u8(
+            crate::thumbnails::native::resize_and_encode(
+                &img.into_rgba8().into_raw(),
                 width,
                 height,
-                img.to_rgba8().into_raw(),
-                fr::PixelType::U8x4,
-            )
-            .map_err(|e| e.to_string())?;
-            
-            let mut dst_image = fr::images::Image::new(new_w, new_h, fr::PixelType::U8x4);
-            let mut resizer = fr::Resizer::new();
-            resizer
-                .resize(&src_image, &mut dst_image, None)
-                .map_err(|e| e.to_string())?;
-            
-            let buffer = dst_image.buffer();
-            encode_webp_native(buffer, new_w, new_h, output_path)?;
+                size_px,
+                fr::ResizeAlg::Convolution(fr::FilterType::Lanczos3),
+                output_path,
+            )?;
             
             return Ok(());
         }
@@ -1,7 +1,10 @@
 use crate::db::Db;
-use crate::error::AppResult;
+use crate::error::{AppError, AppResult};
+use crate::thumbnails::histogram::{self, Histogram};
+use crate::thumbnails::model_deps::{self, ModelDependency};
+use crate::thumbnails::perceptual::{self, SimilarImage};
 use std::sync::Arc;
-use tauri::State;
+use tauri::{AppHandle, State};
 
 /// Request regeneration of a thumbnail by clearing its path in the database.
 /// The thumbnail worker will automatically pick it up and regenerate.
@@ -21,3 +24,135 @@ pub async fn set_thumbnail_priority(
     state.set_priority(ids);
     Ok(())
 }
+
+/// Maximum number of thumbnails queued by a single prefetch.
+const PREFETCH_QUOTA: i32 = 120;
+
+/// Warms the first page of a folder the user is hovering in the tree.
+///
+/// Runs the grid queries once so SQLite has the pages cached, and queues the
+/// missing thumbnails of that page behind the visible ones. A new call, or
+/// `cancel_prefetch`, replaces the pending prefetch. Returns the number of
+/// thumbnails queued.
+#[tauri::command]
+pub async fn prefetch_folder(
+    folder_id: i64,
+    recursive: Option<bool>,
+    db: State<'_, Arc<Db>>,
+    state: State<'_, Arc<crate::thumbnails::priority::ThumbnailPriorityState>>,
+) -> AppResult<usize> {
+    let generation = state.begin_prefetch();
+    let recursive = recursive.unwrap_or(false);
+
+    let images = db
+        .get_images_filtered(PREFETCH_QUOTA, 0, vec![], false, None, Some(folder_id), recursive, None, None, None, None, None)
+        .await?;
+    db.get_image_count_filtered(vec![], false, None, Some(folder_id), recursive, None, None, None).await?;
+
+    let ids: Vec<i64> = images
+        .into_iter()
+        .filter(|img| img.thumbnail_path.is_none())
+        .map(|img| img.id)
+        .collect();
+    let queued = ids.len();
+
+    if !state.set_prefetch(generation, ids) {
+        // Navigation moved on while the queries ran
+        return Ok(0);
+    }
+    Ok(queued)
+}
+
+/// Drops the thumbnails queued by `prefetch_folder`, e.g. when the pointer
+/// leaves the folder before it is opened.
+#[tauri::command]
+pub async fn cancel_prefetch(
+    state: State<'_, Arc<crate::thumbnails::priority::ThumbnailPriorityState>>,
+) -> AppResult<()> {
+    state.begin_prefetch();
+    Ok(())
+}
+
+/// Tells the worker which grid the user is looking at, so its missing
+/// thumbnails are rendered before the rest of the library. `None` clears it.
+#[tauri::command]
+pub async fn set_active_context(
+    filter: Option<crate::db::search::ImageFilter>,
+    state: State<'_, Arc<crate::thumbnails::priority::ThumbnailPriorityState>>,
+) -> AppResult<()> {
+    state.set_active_context(filter);
+    Ok(())
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThumbnailWorkerStatus {
+    pub threads: usize,
+    /// Pooled resize buffers reused and grown since startup.
+    pub buffer_reuses: u64,
+    pub buffer_grows: u64,
+    /// Decoded image data in flight (see `thumbnails::memory`).
+    pub memory: crate::thumbnails::memory::MemoryStats,
+}
+
+/// Threads, buffer reuse and in-flight decode memory of the thumbnail worker.
+#[tauri::command]
+pub fn get_thumbnail_worker_status(
+    config: State<'_, crate::settings::config::ConfigState>,
+) -> ThumbnailWorkerStatus {
+    let (buffer_reuses, buffer_grows) = crate::thumbnails::native::encoder_pool_stats();
+    ThumbnailWorkerStatus {
+        threads: config.0.lock().unwrap().thumbnail_threads,
+        buffer_reuses,
+        buffer_grows,
+        memory: crate::thumbnails::memory::stats(),
+    }
+}
+
+/// Materials and textures a 3D model references, and where each was found,
+/// so missing ones can be told apart from untextured models.
+#[tauri::command]
+pub async fn get_model_dependencies(
+    image_id: i64,
+    db: State<'_, Arc<Db>>,
+) -> AppResult<Vec<ModelDependency>> {
+    let path = db
+        .get_image_path(image_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Image {}", image_id)))?;
+    tauri::async_runtime::spawn_blocking(move || model_deps::dependencies(&crate::paths::from_db(&path)))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))
+}
+
+/// RGB and luminance histograms of an image, with clipping statistics,
+/// computed from its display variant and cached.
+#[tauri::command]
+pub async fn get_image_histogram(
+    app: AppHandle,
+    id: i64,
+    db: State<'_, Arc<Db>>,
+) -> AppResult<Histogram> {
+    let path = db
+        .get_image_path(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Image {}", id)))?;
+    tauri::async_runtime::spawn_blocking(move || {
+        histogram::get_or_compute(&app, &crate::paths::from_db(&path)).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+    .map_err(AppError::Unsupported)
+}
+
+/// Images that look like `image_id`: resized or re-encoded copies, burst
+/// shots. `threshold` is the largest perceptual hash distance accepted, in
+/// bits out of 64 (10 by default, at most 24); closest images come first.
+#[tauri::command]
+pub async fn get_similar_images(
+    image_id: i64,
+    threshold: Option<u32>,
+    db: State<'_, Arc<Db>>,
+) -> AppResult<Vec<SimilarImage>> {
+    perceptual::find_similar(&db, image_id, threshold).await
+}
@@ -0,0 +1,164 @@
+//! Pixel-difference heatmaps for A/B comparison.
+//!
+//! Meant for re-exports of the same artwork: both images are decoded at
+//! display size, the second one is resampled onto the first one's grid, and
+//! the per-pixel difference is rendered as a heatmap (black for identical,
+//! through red and yellow, to white for the largest differences). Heatmaps are
+//! cached in the preview cache next to their statistics.
+
+use std::path::Path;
+
+use image::{imageops::FilterType, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Runtime};
+
+/// Per-pixel differences at or below this (0-255) are treated as encoder
+/// noise when counting changed pixels.
+const CHANGED_THRESHOLD: u8 = 8;
+
+/// Statistics of a pixel comparison, with the cached heatmap.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PixelDiff {
+    /// Heatmap file in the preview cache.
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    /// Mean per-pixel difference, 0-1.
+    pub mean_difference: f64,
+    /// Largest per-pixel difference, 0-1.
+    pub max_difference: f64,
+    /// Share of pixels differing by more than the noise threshold, 0-1.
+    pub changed_ratio: f64,
+    /// Whether the second image had to be stretched to a different aspect ratio.
+    pub aspect_mismatch: bool,
+}
+
+/// Largest channel difference between two pixels, alpha included.
+fn pixel_difference(a: &Rgba<u8>, b: &Rgba<u8>) -> u8 {
+    a.0.iter().zip(b.0.iter()).map(|(x, y)| x.abs_diff(*y)).max().unwrap_or(0)
+}
+
+/// Black -> red -> yellow -> white ramp.
+fn heat_color(difference: u8) -> Rgba<u8> {
+    let v = difference as u32 * 3;
+    let r = v.min(255) as u8;
+    let g = v.saturating_sub(255).min(255) as u8;
+    let b = v.saturating_sub(510).min(255) as u8;
+    Rgba([r, g, b, 255])
+}
+
+/// Renders the heatmap of two same-sized images.
+///
+/// Returns the heatmap with the mean, max and changed-ratio statistics.
+fn diff_images(a: &RgbaImage, b: &RgbaImage) -> (RgbaImage, f64, f64, f64) {
+    let (width, height) = a.dimensions();
+    let mut heatmap = RgbaImage::new(width, height);
+    let (mut total, mut max, mut changed) = (0u64, 0u8, 0u64);
+
+    for ((pa, pb), out) in a.pixels().zip(b.pixels()).zip(heatmap.pixels_mut()) {
+        let difference = pixel_difference(pa, pb);
+        total += difference as u64;
+        max = max.max(difference);
+        if difference > CHANGED_THRESHOLD {
+            changed += 1;
+        }
+        *out = heat_color(difference);
+    }
+
+    let pixels = (width as u64 * height as u64).max(1) as f64;
+    (heatmap, total as f64 / pixels / 255.0, max as f64 / 255.0, changed as f64 / pixels)
+}
+
+/// Whether the aspect ratios differ by more than a pixel's worth of rounding.
+fn aspect_mismatch(a: (u32, u32), b: (u32, u32)) -> bool {
+    let (aw, ah) = (a.0 as f64, a.1 as f64);
+    let (bw, bh) = (b.0 as f64, b.1 as f64);
+    // Height of b scaled to a's width, compared with a's height
+    (bh * aw / bw - ah).abs() > 1.0
+}
+
+/// Returns the cached heatmap of `a` against `b`, rendering it if needed.
+pub fn get_or_render_diff<R: Runtime>(
+    app: &AppHandle<R>,
+    a: &Path,
+    b: &Path,
+    maxdim: u32,
+) -> Result<PixelDiff, Box<dyn std::error::Error>> {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let cache_dir = super::variants::preview_cache_dir(app).ok_or("Preview cache directory unavailable")?;
+    // Key on both sources, so editing either one invalidates the heatmap
+    let mut hasher = DefaultHasher::new();
+    super::variants::part_variant_filename(b, "diffsrc", maxdim)?.hash(&mut hasher);
+    let heatmap_path = cache_dir.join(super::variants::part_variant_filename(a, &format!("diff{:x}", hasher.finish()), maxdim)?);
+    let stats_path = heatmap_path.with_extension("json");
+
+    if heatmap_path.exists() {
+        if let Some(cached) = std::fs::read(&stats_path).ok().and_then(|d| serde_json::from_slice::<PixelDiff>(&d).ok()) {
+            return Ok(cached);
+        }
+    }
+    std::fs::create_dir_all(&cache_dir)?;
+
+    let img_a = super::variants::decode_source(app, a, maxdim)?;
+    let img_a = if img_a.width() > maxdim || img_a.height() > maxdim {
+        img_a.resize(maxdim, maxdim, FilterType::CatmullRom)
+    } else {
+        img_a
+    };
+    let img_b = super::variants::decode_source(app, b, maxdim)?;
+
+    let (width, height) = (img_a.width(), img_a.height());
+    let mismatch = aspect_mismatch((width, height), (img_b.width(), img_b.height()));
+    let rgba_a = img_a.to_rgba8();
+    let rgba_b = img_b.resize_exact(width, height, FilterType::CatmullRom).to_rgba8();
+
+    let (heatmap, mean_difference, max_difference, changed_ratio) = diff_images(&rgba_a, &rgba_b);
+
+    super::variants::write_webp(heatmap.as_raw(), width, height, None, &heatmap_path)?;
+
+    let diff = PixelDiff {
+        path: heatmap_path.to_string_lossy().to_string(),
+        width,
+        height,
+        mean_difference,
+        max_difference,
+        changed_ratio,
+        aspect_mismatch: mismatch,
+    };
+    std::fs::write(&stats_path, serde_json::to_vec(&diff)?)?;
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heat_color() {
+        assert_eq!(heat_color(0), Rgba([0, 0, 0, 255]));
+        assert_eq!(heat_color(85), Rgba([255, 0, 0, 255]));
+        assert_eq!(heat_color(170), Rgba([255, 255, 0, 255]));
+        assert_eq!(heat_color(255), Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_diff_images() {
+        let a = RgbaImage::from_raw(2, 1, vec![10, 10, 10, 255, 200, 0, 0, 255]).unwrap();
+        let b = RgbaImage::from_raw(2, 1, vec![12, 10, 10, 255, 200, 255, 0, 255]).unwrap();
+        let (heatmap, mean, max, changed) = diff_images(&a, &b);
+        assert_eq!(heatmap.get_pixel(1, 0), &Rgba([255, 255, 255, 255]));
+        assert!((mean - 257.0 / 2.0 / 255.0).abs() < 1e-9);
+        assert_eq!(max, 1.0);
+        // The first pixel is within the noise threshold
+        assert_eq!(changed, 0.5);
+    }
+
+    #[test]
+    fn test_aspect_mismatch() {
+        assert!(!aspect_mismatch((3000, 2000), (1500, 1000)));
+        assert!(!aspect_mismatch((1024, 683), (3000, 2000)));
+        assert!(aspect_mismatch((1000, 1000), (1600, 900)));
+    }
+}
@@ -1,8 +1,10 @@
 use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 use memmap2::Mmap;
 
+use super::bounded::read_vec;
+
 const JPEG_SOI: &[u8; 2] = b"\xff\xd8";
 const JPEG_EOI: &[u8; 2] = b"\xff\xd9";
 const PNG_HEADER: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
@@ -11,37 +13,46 @@ const PNG_FOOTER: &[u8; 4] = b"IEND";
 const TIFF_LE: &[u8; 4] = b"II\x2a\x00";
 const TIFF_BE: &[u8; 4] = b"MM\x00\x2a";
 
+/// XMP packets are near the top of the file; only this much is searched.
+const XMP_SCAN_LIMIT: usize = 1024 * 1024;
+
 /// Scans for any embedded image (JPEG, PNG or TIFF), returning the largest one.
 pub fn extract_any_embedded(path: &Path) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
     let file = File::open(path)?;
     let mmap = unsafe { Mmap::map(&file)? };
+    find_any_embedded(&mmap).ok_or_else(|| "No embedded image found".into())
+}
 
+/// Same as [`extract_any_embedded`], over a buffer already in memory.
+pub fn find_any_embedded(mmap: &[u8]) -> Option<(Vec<u8>, String)> {
     let mut best: Option<(Vec<u8>, String)> = None;
 
-    if let Ok(data) = scan_mmap_for_jpeg(&mmap) {
+    if let Ok(data) = scan_mmap_for_jpeg(mmap) {
         best = Some((data, "image/jpeg".to_string()));
     }
 
-    if let Ok(data) = scan_mmap_for_png(&mmap) {
+    if let Ok(data) = scan_mmap_for_png(mmap) {
          if best.as_ref().map_or(true, |(old_data, _)| data.len() > old_data.len()) {
              best = Some((data, "image/png".to_string()));
          }
     }
 
-    if let Ok(data) = scan_mmap_for_tiff(&mmap) {
+    if let Ok(data) = scan_mmap_for_tiff(mmap) {
          if best.as_ref().map_or(true, |(old_data, _)| data.len() > old_data.len()) {
              best = Some((data, "image/tiff".to_string()));
          }
     }
 
-    if let Ok(data) = extract_xmp_thumbnail(path) {
+    // Like reading the head of the file as a string: no XMP unless it is valid UTF-8
+    let head = &mmap[..mmap.len().min(XMP_SCAN_LIMIT)];
+    if let Some(data) = std::str::from_utf8(head).ok().and_then(|text| find_xmp_thumbnail(text).ok()) {
          if best.as_ref().map_or(true, |(old_data, _)| data.len() > old_data.len()) {
              // XMP thumbnails are almost always JPEG
              best = Some((data, "image/jpeg".to_string()));
          }
     }
 
-    best.ok_or_else(|| "No embedded image found".into())
+    best
 }
 
 pub fn extract_embedded_jpeg(path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
@@ -78,6 +89,11 @@ fn scan_mmap_for_jpeg(mmap: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>
                 i = end;
                 continue;
             }
+            // No EOI up to the end of the file: no later SOI can find one either,
+            // and searching again for each of them would be quadratic
+            if eoi_limit == mmap.len() {
+                break;
+            }
             i = start + 2;
         } else {
             break;
@@ -110,7 +126,8 @@ fn scan_mmap_for_png(mmap: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>>
                 i = end.min(mmap.len());
                 continue;
             }
-            i = start + 8;
+            // The search above ran to the end of the file, so no later header can succeed
+            break;
         } else {
             break;
         }
@@ -142,8 +159,12 @@ fn scan_mmap_for_tiff(mmap: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>
 
 /// Extracts a preview from an EPS file specifically using the binary header pointers (if present).
 pub fn extract_eps_binary_pointer(path: &Path) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
-    use std::io::{Read, Seek};
     let mut file = File::open(path)?;
+    extract_eps_binary_pointer_from_reader(&mut file)
+}
+
+/// Same as [`extract_eps_binary_pointer`], reading from any seekable source.
+pub fn extract_eps_binary_pointer_from_reader<R: Read + Seek>(file: &mut R) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
     let mut header = [0u8; 32];
     if file.read_exact(&mut header).is_err() {
         return Err("File too small for EPS header".into());
@@ -156,9 +177,12 @@ pub fn extract_eps_binary_pointer(path: &Path) -> Result<(Vec<u8>, String), Box<
         let tiff_len = u32::from_le_bytes(header[24..28].try_into()?) as u64;
 
         if tiff_len > 0 {
-            file.seek(std::io::SeekFrom::Start(tiff_offset))?;
-            let mut data = vec![0u8; tiff_len as usize];
-            file.read_exact(&mut data)?;
+            let file_len = file.seek(SeekFrom::End(0))?;
+            if tiff_offset + tiff_len > file_len {
+                return Err("EPS preview pointer runs past the end of the file".into());
+            }
+            file.seek(SeekFrom::Start(tiff_offset))?;
+            let data = read_vec(file, tiff_len)?;
             return Ok((data, "image/tiff".to_string()));
         }
     }
@@ -168,12 +192,16 @@ pub fn extract_eps_binary_pointer(path: &Path) -> Result<(Vec<u8>, String), Box<
 
 /// Scans for XMP metadata containing a base64 encoded thumbnail.
 /// Common in Adobe Illustrator and EPS files.
+#[allow(dead_code)]
 pub fn extract_xmp_thumbnail(path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
     let file = File::open(path)?;
     let mut buffer = String::new();
     // XMP is usually near the top, read first 1MB
-    file.take(1024 * 1024).read_to_string(&mut buffer).ok();
+    file.take(XMP_SCAN_LIMIT as u64).read_to_string(&mut buffer).ok();
+    find_xmp_thumbnail(&buffer)
+}
 
+fn find_xmp_thumbnail(buffer: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
     if let Some(start_tag) = buffer.find("<xmpGImg:image>") {
         let start = start_tag + "<xmpGImg:image>".len();
         if let Some(end_tag) = buffer[start..].find("</xmpGImg:image>") {
@@ -187,3 +215,49 @@ pub fn extract_xmp_thumbnail(path: &Path) -> Result<Vec<u8>, Box<dyn std::error:
 
     Err("No XMP thumbnail found".into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn eps_header(tiff_offset: u32, tiff_len: u32) -> Vec<u8> {
+        let mut file = vec![0xC5, 0xD0, 0xD3, 0xC6];
+        file.resize(32, 0);
+        file[20..24].copy_from_slice(&tiff_offset.to_le_bytes());
+        file[24..28].copy_from_slice(&tiff_len.to_le_bytes());
+        file
+    }
+
+    #[test]
+    fn test_eps_pointer_reads_the_preview() {
+        let mut file = eps_header(32, 4);
+        file.extend_from_slice(TIFF_LE);
+        let (data, mime) = extract_eps_binary_pointer_from_reader(&mut Cursor::new(file)).unwrap();
+        assert_eq!((data.as_slice(), mime.as_str()), (&TIFF_LE[..], "image/tiff"));
+    }
+
+    #[test]
+    fn test_eps_pointer_past_the_end_is_rejected() {
+        let file = eps_header(32, u32::MAX);
+        assert!(extract_eps_binary_pointer_from_reader(&mut Cursor::new(file)).is_err());
+        let file = eps_header(u32::MAX, 4);
+        assert!(extract_eps_binary_pointer_from_reader(&mut Cursor::new(file)).is_err());
+        assert!(extract_eps_binary_pointer_from_reader(&mut Cursor::new(vec![0xC5, 0xD0])).is_err());
+    }
+
+    #[test]
+    fn test_scanners_handle_truncated_images() {
+        // Markers with nothing after them, many times over
+        let soi_run: Vec<u8> = JPEG_SOI.iter().copied().cycle().take(64 * 1024).collect();
+        assert!(find_any_embedded(&soi_run).is_none());
+        assert!(find_any_embedded(PNG_HEADER).is_none());
+        assert!(find_any_embedded(&[]).is_none());
+
+        // IEND right at the end, without its CRC
+        let mut png = PNG_HEADER.to_vec();
+        png.extend_from_slice(PNG_FOOTER);
+        let (data, mime) = find_any_embedded(&png).unwrap();
+        assert_eq!((data.len(), mime.as_str()), (png.len(), "image/png"));
+    }
+}
@@ -0,0 +1,81 @@
+//! Bounds-checked reading helpers for the binary extractors.
+//!
+//! Every length and offset read from a project file is untrusted: a block
+//! claiming 4 GB in a 1 KB file must fail instead of allocating, and a field
+//! past the end of a buffer must not panic. The MDP, SAI and EPS parsers go
+//! through these helpers instead of `vec![0; len]` and direct slicing.
+
+use std::io::{self, Read};
+
+use flate2::read::ZlibDecoder;
+
+/// Reads exactly `length` bytes. The buffer grows as data arrives, so a bogus
+/// length costs no more memory than the reader actually holds.
+pub fn read_vec<R: Read>(reader: &mut R, length: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.by_ref().take(length).read_to_end(&mut data)?;
+    if (data.len() as u64) < length {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "length exceeds the available data"));
+    }
+    Ok(data)
+}
+
+/// Inflates at most `limit` bytes of a zlib stream; anything beyond is dropped.
+pub fn inflate_zlib(data: &[u8], limit: u64) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    ZlibDecoder::new(data).take(limit).read_to_end(&mut output)?;
+    Ok(output)
+}
+
+/// Little-endian `u32` at `offset`, `None` if it runs past the end.
+pub fn le_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// `width * height * 4`, `None` if it overflows or exceeds `max_pixels`.
+pub fn rgba_len(width: u32, height: u32, max_pixels: u64) -> Option<usize> {
+    let pixels = (width as u64).checked_mul(height as u64)?;
+    if pixels > max_pixels {
+        return None;
+    }
+    usize::try_from(pixels.checked_mul(4)?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_vec_rejects_lengths_past_the_end() {
+        let mut reader = Cursor::new(vec![1u8, 2, 3]);
+        assert!(read_vec(&mut reader, u32::MAX as u64).is_err());
+
+        let mut reader = Cursor::new(vec![1u8, 2, 3]);
+        assert_eq!(read_vec(&mut reader, 2).unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_inflate_zlib_stops_at_the_limit() {
+        use flate2::write::ZlibEncoder;
+        use std::io::Write;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&vec![0u8; 1 << 20]).unwrap();
+        let bomb = encoder.finish().unwrap();
+
+        assert_eq!(inflate_zlib(&bomb, 1024).unwrap().len(), 1024);
+    }
+
+    #[test]
+    fn test_field_helpers_never_overflow() {
+        assert_eq!(le_u32(&[1, 0, 0, 0], 0), Some(1));
+        assert_eq!(le_u32(&[1, 0, 0], 0), None);
+        assert_eq!(le_u32(&[1, 0, 0, 0], usize::MAX - 1), None);
+
+        assert_eq!(rgba_len(2, 3, 100), Some(24));
+        assert_eq!(rgba_len(u32::MAX, u32::MAX, u64::MAX), None);
+        assert_eq!(rgba_len(20_000, 20_000, 64 * 1024 * 1024), None);
+    }
+}
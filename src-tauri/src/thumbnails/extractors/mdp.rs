@@ -13,9 +13,10 @@ use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 use byteorder::{LittleEndian, ReadBytesExt};
 use quick_xml::reader::Reader;
-use flate2::read::ZlibDecoder;
 use image::ImageEncoder;
 
+use super::bounded::{inflate_zlib, le_u32, read_vec, rgba_len};
+
 /// Magic bytes at the start of every MDP file.
 const MDP_MAGIC: &[u8; 7] = b"mdipack";
 
@@ -25,6 +26,18 @@ const PAC_MAGIC: &[u8; 4] = b"PAC ";
 /// Size of a PAC block header in bytes.
 const PAC_HEADER_SIZE: u32 = 132;
 
+/// Largest XML metadata section accepted (real files stay well under 1 MB).
+const MAX_XML_SIZE: u32 = 16 * 1024 * 1024;
+
+/// Largest decompressed PAC block kept in memory.
+const MAX_BLOCK_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Largest canvas or thumbnail rendered, in pixels (a 256 MB RGBA buffer).
+const MAX_CANVAS_PIXELS: u64 = 64 * 1024 * 1024;
+
+/// Largest tile edge accepted. MediBang and FireAlpaca write 128 or 256.
+const MAX_TILE_DIMENSION: u32 = 1024;
+
 /// Error type for MDP parsing.
 #[derive(Debug, thiserror::Error)]
 pub enum MdpError {
@@ -43,6 +56,14 @@ pub enum MdpError {
     /// Failed to parse the XML metadata section.
     #[error("XML parse error in MDP metadata: {0}")]
     Xml(String),
+
+    /// The canvas or thumbnail is larger than the renderer accepts.
+    #[error("MDP image of {0}x{1} exceeds the size limit")]
+    TooLarge(u32, u32),
+
+    /// A PAC block holds less data than its metadata describes.
+    #[error("MDP block '{0}' is truncated")]
+    Truncated(String),
 }
 
 /// Metadata of a single layer parsed from the XML header.
@@ -102,8 +123,14 @@ struct MdpHeader {
 /// Returns error if the file is not a valid MDP or if both thumbnail extraction
 /// and canvas rendering fail.
 pub fn extract_mdp_preview(path: &Path) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
+    let file = std::fs::File::open(path)?;
+    extract_mdp_preview_from_reader(&mut std::io::BufReader::new(file))
+}
+
+/// Same as [`extract_mdp_preview`], reading the project from any seekable source.
+pub fn extract_mdp_preview_from_reader<R: Read + Seek>(reader: &mut R) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
     // Prefer the embedded thumbnail — it matches what the artist sees in the app.
-    match extract_mdp_thumbnail(path) {
+    match read_mdp_thumbnail(reader) {
         Ok(result) => return Ok(result),
         Err(thumbnail_error) => {
             println!("MDP: Thumbnail extraction failed ({thumbnail_error}), trying full render.");
@@ -111,18 +138,22 @@ pub fn extract_mdp_preview(path: &Path) -> Result<(Vec<u8>, String), Box<dyn std
     }
 
     // Fallback: render the full canvas by compositing visible layers.
-    render_mdp_canvas(path)
+    reader.seek(SeekFrom::Start(0))?;
+    render_mdp_canvas_from_reader(reader)
 }
 
 /// Renders the full canvas by compositing all visible layers.
 ///
 /// # Errors
 /// Returns error if layer data cannot be decoded or composited.
+#[allow(dead_code)]
 fn render_mdp_canvas(path: &Path) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
     let file = std::fs::File::open(path)?;
-    let mut reader = std::io::BufReader::new(file);
+    render_mdp_canvas_from_reader(&mut std::io::BufReader::new(file))
+}
 
-    let header = parse_mdp_header(&mut reader)?;
+fn render_mdp_canvas_from_reader<R: Read + Seek>(reader: &mut R) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
+    let header = parse_mdp_header(reader)?;
 
     if header.visible_layers.is_empty() {
         return Err(MdpError::NoPreview.into());
@@ -134,12 +165,14 @@ fn render_mdp_canvas(path: &Path) -> Result<(Vec<u8>, String), Box<dyn std::erro
         .map(|layer| layer.binary_block_name.clone())
         .collect();
 
+    let canvas_len = rgba_len(header.canvas_width, header.canvas_height, MAX_CANVAS_PIXELS)
+        .ok_or(MdpError::TooLarge(header.canvas_width, header.canvas_height))?;
+
     // Read all needed binary blocks from the file.
-    let binary_blocks = read_pac_blocks(&mut reader, &needed_block_names)?;
+    let binary_blocks = read_pac_blocks(reader, &needed_block_names)?;
 
     // Create transparent canvas (RGBA).
-    let canvas_pixel_count = (header.canvas_width as usize) * (header.canvas_height as usize);
-    let mut canvas_buffer = vec![0u8; canvas_pixel_count * 4];
+    let mut canvas_buffer = vec![0u8; canvas_len];
 
     // Composite layers from bottom (last in XML) to top (first in XML).
     // The visible_layers vec is already in bottom-to-top order.
@@ -175,21 +208,32 @@ fn render_mdp_canvas(path: &Path) -> Result<(Vec<u8>, String), Box<dyn std::erro
 ///
 /// # Errors
 /// Returns error if the thumbnail PAC block is missing or corrupted.
+#[allow(dead_code)]
 fn extract_mdp_thumbnail(path: &Path) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
     let file = std::fs::File::open(path)?;
-    let mut reader = std::io::BufReader::new(file);
+    read_mdp_thumbnail(&mut std::io::BufReader::new(file))
+}
 
-    let header = parse_mdp_header(&mut reader)?;
+fn read_mdp_thumbnail<R: Read + Seek>(reader: &mut R) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
+    let header = parse_mdp_header(reader)?;
 
     let thumb_info = header.thumbnail
         .ok_or(MdpError::NoPreview)?;
+    let expected_len = rgba_len(thumb_info.width, thumb_info.height, MAX_CANVAS_PIXELS)
+        .ok_or(MdpError::TooLarge(thumb_info.width, thumb_info.height))?;
 
-    let blocks = read_pac_blocks(&mut reader, &[thumb_info.binary_block_name.clone()])?;
-    let thumb_data = blocks.get(&thumb_info.binary_block_name)
+    let mut blocks = read_pac_blocks(reader, &[thumb_info.binary_block_name.clone()])?;
+    let thumb_data = blocks.remove(&thumb_info.binary_block_name)
         .ok_or(MdpError::NoPreview)?;
 
+    // The encoder panics unless the buffer matches the dimensions exactly.
+    let mut rgba_pixels = thumb_data;
+    if rgba_pixels.len() < expected_len {
+        return Err(MdpError::Truncated(thumb_info.binary_block_name).into());
+    }
+    rgba_pixels.truncate(expected_len);
+
     // Thumbnail is raw BGRA pixels. Swap to RGBA.
-    let mut rgba_pixels = thumb_data.clone();
     for pixel_chunk in rgba_pixels.chunks_exact_mut(4) {
         pixel_chunk.swap(0, 2);
     }
@@ -228,10 +272,12 @@ fn parse_mdp_header<R: Read + Seek>(reader: &mut R) -> Result<MdpHeader, Box<dyn
     // 3. Read XML size and Pack size (both u32 LE).
     let xml_length = reader.read_u32::<LittleEndian>()?;
     let _pack_size = reader.read_u32::<LittleEndian>()?;
+    if xml_length > MAX_XML_SIZE {
+        return Err(MdpError::InvalidFormat.into());
+    }
 
     // 4. Extract XML Metadata.
-    let mut xml_buffer = vec![0u8; xml_length as usize];
-    reader.read_exact(&mut xml_buffer)?;
+    let xml_buffer = read_vec(reader, xml_length as u64)?;
     let xml_string = String::from_utf8(xml_buffer)
         .map_err(|error| MdpError::Xml(error.to_string()))?;
 
@@ -368,15 +414,11 @@ fn read_pac_blocks<R: Read + Seek>(
         let data_length = item_total_size.saturating_sub(PAC_HEADER_SIZE);
 
         if needed_names.contains(&item_name) {
-            let mut raw_data = vec![0u8; data_length as usize];
-            reader.read_exact(&mut raw_data)?;
+            let raw_data = read_vec(reader, data_length as u64)?;
 
             // Decompress if the block is zlib-compressed (type flag 1).
             let decompressed_data = if item_type_flag == 1 {
-                let mut zlib_decoder = ZlibDecoder::new(&raw_data[..]);
-                let mut decompressed = Vec::new();
-                zlib_decoder.read_to_end(&mut decompressed)?;
-                decompressed
+                inflate_zlib(&raw_data, MAX_BLOCK_SIZE)?
             } else {
                 raw_data
             };
@@ -411,36 +453,42 @@ fn composite_layer_onto_canvas(
     block_data: &[u8],
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Empty layers may contain only 4 bytes (tile_count = 0) with no tile dimension.
-    if block_data.len() < 4 {
+    let Some(tile_count) = le_u32(block_data, 0) else {
+        return Ok(());
+    };
+    if tile_count == 0 {
         return Ok(());
     }
 
-    let tile_count = u32::from_le_bytes(block_data[0..4].try_into().unwrap());
-    if tile_count == 0 || block_data.len() < 8 {
+    let Some(tile_dimension) = le_u32(block_data, 4) else {
+        return Ok(());
+    };
+    if tile_dimension == 0 || tile_dimension > MAX_TILE_DIMENSION {
+        // Not something MediBang writes; leave the layer out rather than fail the render.
         return Ok(());
     }
-
-    let tile_dimension = u32::from_le_bytes(block_data[4..8].try_into().unwrap());
+    let total_tile_pixels = (tile_dimension * tile_dimension) as usize;
 
     let mut offset = 8usize;
 
     for _tile_index in 0..tile_count {
-        if offset + 16 > block_data.len() {
+        let (Some(tile_column), Some(tile_row), Some(compression_type), Some(compressed_data_size)) = (
+            le_u32(block_data, offset),
+            le_u32(block_data, offset + 4),
+            le_u32(block_data, offset + 8),
+            le_u32(block_data, offset + 12),
+        ) else {
             break;
-        }
-
-        let tile_column = u32::from_le_bytes(block_data[offset..offset + 4].try_into().unwrap());
-        let tile_row = u32::from_le_bytes(block_data[offset + 4..offset + 8].try_into().unwrap());
-        let compression_type = u32::from_le_bytes(block_data[offset + 8..offset + 12].try_into().unwrap());
-        let compressed_data_size = u32::from_le_bytes(block_data[offset + 12..offset + 16].try_into().unwrap());
+        };
 
         offset += 16;
 
-        if offset + compressed_data_size as usize > block_data.len() {
+        let Some(tile_raw_data) = offset
+            .checked_add(compressed_data_size as usize)
+            .and_then(|end| block_data.get(offset..end))
+        else {
             break;
-        }
-
-        let tile_raw_data = &block_data[offset..offset + compressed_data_size as usize];
+        };
         offset += compressed_data_size as usize;
 
         // Align to 4-byte boundary.
@@ -449,22 +497,16 @@ fn composite_layer_onto_canvas(
 
         // Decompress tile data.
         let decompressed_tile = match compression_type {
-            0 => {
-                // Zlib compressed.
-                let mut zlib_decoder = ZlibDecoder::new(tile_raw_data);
-                let mut decompressed = Vec::new();
-                zlib_decoder.read_to_end(&mut decompressed)?;
-                decompressed
-            }
+            // Zlib compressed. A tile never holds more than 4 bytes per pixel.
+            0 => inflate_zlib(tile_raw_data, total_tile_pixels as u64 * 4)?,
             // Snappy (1) and FastLZ (2) are rare; skip unsupported tiles gracefully.
             _ => continue,
         };
 
         // Tiles are ALWAYS tile_dim × tile_dim in the binary data, even at canvas edges.
         // The blit function handles clipping at the canvas boundary.
-        let tile_pixel_x = tile_column * tile_dimension;
-        let tile_pixel_y = tile_row * tile_dimension;
-        let total_tile_pixels = (tile_dimension * tile_dimension) as usize;
+        let tile_pixel_x = tile_column as i64 * tile_dimension as i64;
+        let tile_pixel_y = tile_row as i64 * tile_dimension as i64;
 
         // Decode tile pixels based on the layer type.
         let tile_rgba = decode_tile_to_rgba(
@@ -482,8 +524,8 @@ fn composite_layer_onto_canvas(
             &tile_rgba,
             tile_dimension,
             tile_dimension,
-            layer.offset_x + tile_pixel_x as i32,
-            layer.offset_y + tile_pixel_y as i32,
+            layer.offset_x as i64 + tile_pixel_x,
+            layer.offset_y as i64 + tile_pixel_y,
             layer.alpha,
         );
     }
@@ -556,10 +598,11 @@ fn decode_tile_to_rgba(
 fn parse_layer_color(color_hex: &Option<String>) -> (u8, u8, u8) {
     match color_hex {
         Some(hex) if hex.len() == 8 => {
-            let red = u8::from_str_radix(&hex[2..4], 16).unwrap_or(0);
-            let green = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
-            let blue = u8::from_str_radix(&hex[6..8], 16).unwrap_or(0);
-            (red, green, blue)
+            // `get` rather than slicing: a multi-byte character would split a boundary.
+            let channel = |range: std::ops::Range<usize>| {
+                hex.get(range).and_then(|digits| u8::from_str_radix(digits, 16).ok()).unwrap_or(0)
+            };
+            (channel(2..4), channel(4..6), channel(6..8))
         }
         _ => (0, 0, 0),
     }
@@ -576,21 +619,21 @@ fn blit_tile_onto_canvas(
     tile_rgba: &[u8],
     tile_width: u32,
     tile_height: u32,
-    global_x: i32,
-    global_y: i32,
+    global_x: i64,
+    global_y: i64,
     layer_alpha: u8,
 ) {
     for local_y in 0..tile_height {
         for local_x in 0..tile_width {
-            let dest_x = global_x + local_x as i32;
-            let dest_y = global_y + local_y as i32;
+            let dest_x = global_x + local_x as i64;
+            let dest_y = global_y + local_y as i64;
 
-            if dest_x < 0 || dest_y < 0 || dest_x >= canvas_width as i32 || dest_y >= canvas_height as i32 {
+            if dest_x < 0 || dest_y < 0 || dest_x >= canvas_width as i64 || dest_y >= canvas_height as i64 {
                 continue;
             }
 
-            let tile_pixel_index = ((local_y * tile_width + local_x) * 4) as usize;
-            let canvas_pixel_index = ((dest_y as u32 * canvas_width + dest_x as u32) * 4) as usize;
+            let tile_pixel_index = (local_y as usize * tile_width as usize + local_x as usize) * 4;
+            let canvas_pixel_index = (dest_y as usize * canvas_width as usize + dest_x as usize) * 4;
 
             if tile_pixel_index + 3 >= tile_rgba.len() || canvas_pixel_index + 3 >= canvas_buffer.len() {
                 continue;
@@ -696,4 +739,107 @@ mod tests {
         assert_eq!(mime, "image/png");
         assert!(!data.is_empty());
     }
+
+    /// Builds an MDP file from its XML and `(name, type flag, data)` PAC blocks.
+    fn mdp_bytes(xml: &str, blocks: &[(&str, u32, &[u8])]) -> Vec<u8> {
+        let mut file = MDP_MAGIC.to_vec();
+        file.extend_from_slice(&[0u8; 5]);
+        file.extend_from_slice(&(xml.len() as u32).to_le_bytes());
+        file.extend_from_slice(&0u32.to_le_bytes());
+        file.extend_from_slice(xml.as_bytes());
+        for (name, type_flag, data) in blocks {
+            let mut header = [0u8; PAC_HEADER_SIZE as usize];
+            header[0..4].copy_from_slice(PAC_MAGIC);
+            header[4..8].copy_from_slice(&(PAC_HEADER_SIZE + data.len() as u32).to_le_bytes());
+            header[8..12].copy_from_slice(&type_flag.to_le_bytes());
+            header[68..68 + name.len()].copy_from_slice(name.as_bytes());
+            file.extend_from_slice(&header);
+            file.extend_from_slice(data);
+        }
+        file
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn preview(data: Vec<u8>) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
+        extract_mdp_preview_from_reader(&mut std::io::Cursor::new(data))
+    }
+
+    fn render(data: Vec<u8>) -> image::RgbaImage {
+        let (png, _) = render_mdp_canvas_from_reader(&mut std::io::Cursor::new(data)).unwrap();
+        image::load_from_memory(&png).unwrap().to_rgba8()
+    }
+
+    /// Layer block holding one 4×4 tile at `(column, row)`.
+    fn layer_block(tile_dimension: u32, column: u32, row: u32) -> Vec<u8> {
+        let pixels = zlib(&[255u8; 4 * 4 * 4]);
+        let mut block = Vec::new();
+        for value in [1, tile_dimension, column, row, 0, pixels.len() as u32] {
+            block.extend_from_slice(&value.to_le_bytes());
+        }
+        block.extend_from_slice(&pixels);
+        block
+    }
+
+    #[test]
+    fn test_thumbnail_from_memory() {
+        let xml = r#"<Mdipack><Thumb bin="thumb" width="2" height="1"/></Mdipack>"#;
+        let file = mdp_bytes(xml, &[("thumb", 0, &[0, 0, 255, 255, 255, 0, 0, 255])]);
+
+        let (data, mime) = preview(file).unwrap();
+        assert_eq!(mime, "image/png");
+        let decoded = image::load_from_memory(&data).unwrap().to_rgba8();
+        assert_eq!(decoded.get_pixel(0, 0).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_hostile_lengths_fail_without_allocating() {
+        let mut file = mdp_bytes("<Mdipack/>", &[]);
+        file[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(preview(file).is_err());
+
+        // A PAC block claiming far more data than the file holds
+        let xml = r#"<Mdipack><Thumb bin="thumb" width="2" height="1"/></Mdipack>"#;
+        let mut file = mdp_bytes(xml, &[("thumb", 0, &[0; 8])]);
+        let size_at = file.len() - 8 - PAC_HEADER_SIZE as usize + 4;
+        file[size_at..size_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(preview(file).is_err());
+    }
+
+    #[test]
+    fn test_thumbnail_smaller_than_its_dimensions_is_an_error() {
+        let xml = r#"<Mdipack><Thumb bin="thumb" width="4000" height="4000"/></Mdipack>"#;
+        assert!(preview(mdp_bytes(xml, &[("thumb", 0, &[0; 16])])).is_err());
+
+        let xml = r#"<Mdipack><Thumb bin="thumb" width="4294967295" height="4294967295"/></Mdipack>"#;
+        assert!(preview(mdp_bytes(xml, &[("thumb", 0, &[0; 16])])).is_err());
+    }
+
+    #[test]
+    fn test_hostile_tiles_leave_the_canvas_untouched() {
+        let xml = r#"<Mdipack><Layer bin="layer" width="4" height="4" type="32bpp"/></Mdipack>"#;
+        assert_eq!(render(mdp_bytes(xml, &[("layer", 0, &layer_block(4, 0, 0))])).get_pixel(0, 0).0[3], 255);
+
+        // Far off the canvas, and far past what an i32 position holds
+        let far = render(mdp_bytes(xml, &[("layer", 0, &layer_block(4, u32::MAX, u32::MAX))]));
+        assert!(far.pixels().all(|p| p.0[3] == 0));
+
+        // An absurd tile size leaves the layer out instead of allocating it
+        let huge = render(mdp_bytes(xml, &[("layer", 0, &layer_block(u32::MAX, 0, 0))]));
+        assert!(huge.pixels().all(|p| p.0[3] == 0));
+
+        let xml = r#"<Mdipack><Layer bin="layer" width="4" height="4" ofsx="2147483647" type="32bpp"/></Mdipack>"#;
+        assert!(render_mdp_canvas_from_reader(&mut std::io::Cursor::new(mdp_bytes(xml, &[("layer", 0, &layer_block(4, 0, 0))]))).is_err());
+    }
+
+    #[test]
+    fn test_parse_layer_color_handles_non_ascii() {
+        assert_eq!(parse_layer_color(&Some("FF09151F".to_string())), (0x09, 0x15, 0x1F));
+        assert_eq!(parse_layer_color(&Some("FF€€".to_string())), (0, 0, 0));
+    }
 }
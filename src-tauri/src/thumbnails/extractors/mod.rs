@@ -1,4 +1,5 @@
 pub mod binary_jpeg;
+pub mod bounded;
 pub mod aseprite;
 pub mod xcf;
 pub mod sketch;
@@ -12,11 +13,42 @@ use std::io::Read;
 use image::ImageEncoder;
 use tauri::{AppHandle, Runtime};
 
+/// AI and EPS files, whose artwork is rendered through PDFium when they carry a PDF stream.
+pub fn is_vector_document(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case("ai") || e.eq_ignore_ascii_case("eps"))
+        .unwrap_or(false)
+}
+
 /// Central registry for on-the-fly preview extraction.
+///
+/// When no extractor can read the file, the OS thumbnail handlers are tried last.
 pub fn extract_preview<R: Runtime>(app_handle: Option<&AppHandle<R>>, path: &Path) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
     let format = crate::formats::FileFormat::detect(path)
         .ok_or_else(|| "Unsupported format")?;
 
+    match extract_native_preview(app_handle, path, format) {
+        Ok(result) => Ok(result),
+        // Served directly by the WebView, nothing to fall back to
+        Err(e) if matches!(format.preview_strategy, crate::formats::PreviewStrategy::BrowserNative) => Err(e),
+        Err(e) => {
+            match super::system::extract_system_thumbnail(path, format.mime_types, super::system::SYSTEM_THUMBNAIL_PX) {
+                Ok(data) => {
+                    println!("DEBUG: Using system thumbnail for {:?}", path.file_name().unwrap_or_default());
+                    Ok((data, "image/png".to_string()))
+                }
+                Err(_) => Err(e),
+            }
+        }
+    }
+}
+
+fn extract_native_preview<R: Runtime>(
+    app_handle: Option<&AppHandle<R>>,
+    path: &Path,
+    format: &crate::formats::FileFormat,
+) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
     match format.preview_strategy {
         crate::formats::PreviewStrategy::BrowserNative => {
              Err("Browser native format - serve directly".into())
@@ -52,7 +84,7 @@ pub fn extract_preview<R: Runtime>(app_handle: Option<&AppHandle<R>>, path: &Pat
             match ext.as_str() {
                 // Affinity Suite
                 "afphoto" | "afdesign" | "afpub" => {
-                    let data = super::affinity::extract_largest_png(path)?;
+                    let data = super::affinity::extract_preview_png(path)?;
                     Ok((data, "image/png".to_string()))
                 },
                 // Adobe Photoshop
@@ -258,6 +290,7 @@ fn extract_figma_preview(path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Err
 }
 
 fn convert_to_png(path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let _reservation = crate::thumbnails::memory::reserve_file(path);
     let img = image::open(path)?;
     let sdr_img = img.to_rgb8();
     let mut png_data = Vec::new();
@@ -282,18 +315,23 @@ fn extract_ai_pdf(path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
     Err("Not a PDF-compatible AI file".into())
 }
 
-fn extract_psd_composite(path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+/// Full-resolution composite of a PSD or PSB file.
+pub fn decode_psd_composite(path: &Path) -> Result<image::RgbaImage, Box<dyn std::error::Error>> {
     let bytes = std::fs::read(path)?;
     let psd = psd::Psd::from_bytes(&bytes).map_err(|e| format!("PSD parse error: {}", e))?;
 
-    let rgba = psd.rgba();
-    let width = psd.width() as u32;
-    let height = psd.height() as u32;
+    let width = psd.width();
+    let height = psd.height();
+    image::RgbaImage::from_raw(width, height, psd.rgba()).ok_or_else(|| "PSD composite has an unexpected size".into())
+}
+
+fn extract_psd_composite(path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let composite = decode_psd_composite(path)?;
 
     let mut png_data = Vec::new();
     let mut cursor = std::io::Cursor::new(&mut png_data);
     image::codecs::png::PngEncoder::new(&mut cursor)
-        .write_image(&rgba, width, height, image::ExtendedColorType::Rgba8)
+        .write_image(composite.as_raw(), composite.width(), composite.height(), image::ExtendedColorType::Rgba8)
         .map_err(|e| format!("PNG encode error: {}", e))?;
 
     Ok(png_data)
@@ -306,22 +344,39 @@ pub fn generate_thumbnail_extracted<R: Runtime>(
     output_path: &Path,
     size_px: u32,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let (mut data, mime) = extract_preview(app_handle, input_path)?;
-
-    // If it's a PDF (AI/EPS), we use PDFium for high-quality multiplatform rendering.
-    if mime == "application/pdf" {
-        if let Ok(rendered_data) = crate::media::pdf::render_pdf_data_to_image(app_handle, &data, size_px) {
-            data = rendered_data;
-        } else if let Ok((embedded_data, _)) = binary_jpeg::extract_any_embedded(input_path) {
-            data = embedded_data;
-        } else if let Ok(rendered_data) = extract_ffmpeg_frame(app_handle, input_path) {
-            data = rendered_data;
-        } else {
-            return Err("No PDF rendering or raster preview available for this file".into());
-        }
+    let (data, _) = extract_raster_preview(app_handle, input_path, size_px)?;
+    process_extracted_image(&data, output_path, size_px)
+}
+
+/// Like [`extract_preview`], but PDF streams (AI/EPS) are rasterized so the
+/// result can always be decoded by the `image` crate.
+///
+/// PDFium renders the vector artwork on every platform; the embedded raster
+/// preview and FFmpeg (which needs a Ghostscript-enabled build) are fallbacks.
+pub fn extract_raster_preview<R: Runtime>(
+    app_handle: Option<&AppHandle<R>>,
+    input_path: &Path,
+    size_px: u32,
+) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
+    let (data, mime) = extract_preview(app_handle, input_path)?;
+    if mime != "application/pdf" {
+        return Ok((data, mime));
     }
 
-    process_extracted_image(&data, output_path, size_px)
+    match crate::media::pdf::render_pdf_data_to_image(app_handle, &data, size_px) {
+        Ok(rendered_data) => return Ok((rendered_data, "image/png".to_string())),
+        Err(e) => eprintln!("WARN: PDFium could not render {:?}: {}", input_path.file_name().unwrap_or_default(), e),
+    }
+    if let Ok((embedded_data, mime)) = binary_jpeg::extract_any_embedded(input_path) {
+        if mime == "image/tiff" {
+            return Ok((convert_to_png_from_memory(&embedded_data)?, "image/png".to_string()));
+        }
+        return Ok((embedded_data, mime));
+    }
+    if let Ok(rendered_data) = extract_ffmpeg_frame(app_handle, input_path) {
+        return Ok((rendered_data, "image/jpeg".to_string()));
+    }
+    Err("No PDF rendering or raster preview available for this file".into())
 }
 
 fn process_extracted_image(
@@ -331,33 +386,19 @@ fn process_extracted_image(
 ) -> Result<(), Box<dyn std::error::Error>> {
     use fast_image_resize as fr;
 
+    let _reservation = crate::thumbnails::memory::reserve_blob(data);
     let img = image::load_from_memory(data)?;
     let width = img.width();
     let height = img.height();
 
-    let aspect = width as f32 / height as f32;
-    let (new_w, new_h) = if aspect > 1.0 {
-        (size_px, (size_px as f32 / aspect).max(1.0) as u32)
-    } else {
-        (((size_px as f32 * aspect).max(1.0)) as u32, size_px)
-    };
-
-    let src_image = fr::images::Image::from_vec_u8(
+    crate::thumbnails::native::resize_and_encode(
+        &img.into_rgba8().into_raw(),
         width,
         height,
-        img.to_rgba8().into_raw(),
-        fr::PixelType::U8x4,
-    )
-    .map_err(|e| e.to_string())?;
-
-    let mut dst_image = fr::images::Image::new(new_w, new_h, fr::PixelType::U8x4);
-    let mut resizer = fr::Resizer::new();
-    resizer
-        .resize(&src_image, &mut dst_image, None)
-        .map_err(|e| e.to_string())?;
-
-    let buffer = dst_image.buffer();
-    crate::thumbnails::native::encode_webp_native(buffer, new_w, new_h, output_path)?;
+        size_px,
+        fr::ResizeAlg::Convolution(fr::FilterType::Lanczos3),
+        output_path,
+    )?;
 
     Ok(())
 }
@@ -372,6 +413,7 @@ fn extract_ffmpeg_frame<R: Runtime>(app_handle: Option<&AppHandle<R>>, path: &Pa
 }
 
 fn convert_to_png_from_memory(data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let _reservation = crate::thumbnails::memory::reserve_blob(data);
     let img = image::load_from_memory(data)?;
     let mut png_data = Vec::new();
     let mut cursor = std::io::Cursor::new(&mut png_data);
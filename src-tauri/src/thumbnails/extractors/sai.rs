@@ -16,6 +16,8 @@ use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 use image::ImageEncoder;
 
+use super::bounded::rgba_len;
+
 // ---------------------------------------------------------------------------
 // Constants
 // ---------------------------------------------------------------------------
@@ -38,6 +40,9 @@ const FAT_ENTRY_SIZE: usize = 64;
 /// Magic value expected in the thumbnail header (little-endian "BM32").
 const THUMBNAIL_MAGIC_BM32: u32 = 0x3233_4D42; // "BM32" as LE u32
 
+/// Largest thumbnail decoded, in pixels. SAI itself writes at most 256×256.
+const MAX_THUMBNAIL_PIXELS: u64 = 4096 * 4096;
+
 // ---------------------------------------------------------------------------
 // Errors
 // ---------------------------------------------------------------------------
@@ -61,6 +66,10 @@ pub enum SaiError {
     #[error("SAI thumbnail header has invalid magic (expected BM32)")]
     InvalidThumbnailMagic,
 
+    /// The thumbnail header describes more pixels than the file holds.
+    #[error("SAI thumbnail of {0}x{1} does not match its data")]
+    InvalidThumbnailSize(u32, u32),
+
     /// Generic I/O error.
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
@@ -421,6 +430,8 @@ impl<R: Read + Seek> SaiPageReader<R> {
         start_page_index: usize,
         total_size: usize,
     ) -> Result<Vec<u8>, SaiError> {
+        // A file can't hold more than its own pages, whatever the FAT entry claims
+        let total_size = total_size.min(self.page_count * PAGE_SIZE);
         let mut result_buffer = Vec::with_capacity(total_size);
         let mut current_page_index = start_page_index;
         let mut bytes_remaining = total_size;
@@ -491,7 +502,10 @@ fn find_root_entry<R: Read + Seek>(
     let table_entries = parse_table_entries(&table_data);
     let mut next_page = table_entries[2 % TABLE_SPAN].next_page_index as usize;
 
-    while next_page != 0 {
+    // A chain longer than the file has pages is a loop
+    let mut pages_left = page_reader.page_count;
+    while next_page != 0 && pages_left > 0 {
+        pages_left -= 1;
         let page_data = page_reader.fetch_page(next_page)?;
         let page_bytes = SaiPageReader::<R>::page_to_bytes(&page_data);
         let fat_entries = parse_fat_entries(&page_bytes);
@@ -587,7 +601,12 @@ fn encode_rgba_to_png(
 /// structure is corrupt, or no thumbnail entry exists.
 pub fn extract_sai_preview(sai_file_path: &Path) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
     let file = std::fs::File::open(sai_file_path)?;
-    let mut page_reader = SaiPageReader::new(file)?;
+    extract_sai_preview_from_reader(file)
+}
+
+/// Same as [`extract_sai_preview`], reading the container from any seekable source.
+pub fn extract_sai_preview_from_reader<R: Read + Seek>(reader: R) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
+    let mut page_reader = SaiPageReader::new(reader)?;
 
     // Locate the "thumbnail" entry in the root directory
     let thumbnail_entry = find_root_entry(&mut page_reader, "thumbnail")?
@@ -602,9 +621,10 @@ pub fn extract_sai_preview(sai_file_path: &Path) -> Result<(Vec<u8>, String), Bo
     // Parse the 12-byte header (width, height, magic)
     let header = parse_thumbnail_header(&thumbnail_raw_data)?;
 
-    let pixel_count = (header.width as usize) * (header.height as usize);
-    let expected_pixel_data_size = pixel_count * 4;
     let header_size = 12;
+    let expected_pixel_data_size = rgba_len(header.width, header.height, MAX_THUMBNAIL_PIXELS)
+        .filter(|&size| size > 0)
+        .ok_or(SaiError::InvalidThumbnailSize(header.width, header.height))?;
 
     if thumbnail_raw_data.len() < header_size + expected_pixel_data_size {
         return Err(format!(
@@ -621,3 +641,33 @@ pub fn extract_sai_preview(sai_file_path: &Path) -> Result<(Vec<u8>, String), Bo
     let png_data = encode_rgba_to_png(&pixel_data, header.width, header.height)?;
     Ok((png_data, "image/png".to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_rejects_unaligned_and_empty_input() {
+        assert!(extract_sai_preview_from_reader(Cursor::new(Vec::new())).is_err());
+        assert!(extract_sai_preview_from_reader(Cursor::new(vec![0u8; PAGE_SIZE + 1])).is_err());
+    }
+
+    #[test]
+    fn test_garbage_pages_fail_cleanly() {
+        let garbage: Vec<u8> = (0..PAGE_SIZE * 4).map(|index| (index * 31 % 251) as u8).collect();
+        assert!(extract_sai_preview_from_reader(Cursor::new(garbage)).is_err());
+    }
+
+    #[test]
+    fn test_parse_thumbnail_header_checks_length_and_magic() {
+        assert!(parse_thumbnail_header(&[0u8; 11]).is_err());
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&2u32.to_le_bytes());
+        header.extend_from_slice(&3u32.to_le_bytes());
+        header.extend_from_slice(&THUMBNAIL_MAGIC_BM32.to_le_bytes());
+        let parsed = parse_thumbnail_header(&header).unwrap();
+        assert_eq!((parsed.width, parsed.height), (2, 3));
+    }
+}
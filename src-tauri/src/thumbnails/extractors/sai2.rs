@@ -20,6 +20,8 @@ use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 
+use super::bounded::read_vec;
+
 // ---------------------------------------------------------------------------
 // Constants
 // ---------------------------------------------------------------------------
@@ -247,7 +249,7 @@ fn iterate_canvas_data<R: Read + Seek>(
 ) -> Result<Vec<CanvasDataEntry>, Sai2Error> {
     let mut entries = Vec::new();
     let mut cursor_position = chunk_descriptor.data_offset;
-    let chunk_end = chunk_descriptor.data_offset + chunk_descriptor.data_size;
+    let chunk_end = chunk_descriptor.data_offset.saturating_add(chunk_descriptor.data_size);
 
     while cursor_position < chunk_end {
         reader.seek(SeekFrom::Start(cursor_position))?;
@@ -277,7 +279,7 @@ fn iterate_canvas_data<R: Read + Seek>(
             data_offset,
         });
 
-        cursor_position = data_offset + data_size;
+        cursor_position = data_offset.saturating_add(data_size);
     }
 
     Ok(entries)
@@ -322,8 +324,7 @@ fn extract_jpeg_from_jssf<R: Read + Seek>(
     ]) as usize;
 
     // Read the actual JPEG data
-    let mut jpeg_data = vec![0u8; jpeg_size];
-    reader.read_exact(&mut jpeg_data)?;
+    let jpeg_data = read_vec(reader, jpeg_size as u64)?;
 
     // Validate JPEG SOI marker
     if jpeg_data.len() >= 2 && jpeg_data[0..2] != JPEG_SOI_MARKER {
@@ -359,12 +360,17 @@ fn extract_jpeg_from_jssf<R: Read + Seek>(
 /// or the thumbnail uses DPCM encoding (not yet supported).
 pub fn extract_sai2_preview(sai2_file_path: &Path) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
     let mut file = File::open(sai2_file_path)?;
-    let header = parse_sai2_header(&mut file)?;
+    extract_sai2_preview_from_reader(&mut file)
+}
+
+/// Same as [`extract_sai2_preview`], reading the canvas from any seekable source.
+pub fn extract_sai2_preview_from_reader<R: Read + Seek>(file: &mut R) -> Result<(Vec<u8>, String), Box<dyn std::error::Error>> {
+    let header = parse_sai2_header(file)?;
 
     // Log the header to verify offsets are correct now
     println!("DEBUG: SAI2 Header Parsed: {}x{}, chunks: {}", header.canvas_width, header.canvas_height, header.chunk_count);
 
-    let chunk_descriptors = match parse_chunk_list(&mut file, header.chunk_count) {
+    let chunk_descriptors = match parse_chunk_list(file, header.chunk_count) {
         Ok(d) => d,
         Err(e) => {
             println!("DEBUG: SAI2 Chunk List Parse Error: {}", e);
@@ -380,12 +386,12 @@ pub fn extract_sai2_preview(sai2_file_path: &Path) -> Result<(Vec<u8>, String),
             continue;
         }
 
-        let canvas_entries = iterate_canvas_data(&mut file, descriptor)?;
+        let canvas_entries = iterate_canvas_data(file, descriptor)?;
 
         for entry in &canvas_entries {
             match entry.canvas_type {
                 CANVAS_TYPE_THUMBNAIL_LOSSY => {
-                    let jpeg_data = extract_jpeg_from_jssf(&mut file, entry)?;
+                    let jpeg_data = extract_jpeg_from_jssf(file, entry)?;
                     return Ok((jpeg_data, "image/jpeg".to_string()));
                 }
                 CANVAS_TYPE_THUMBNAIL_LOSSLESS => {
@@ -402,3 +408,50 @@ pub fn extract_sai2_preview(sai2_file_path: &Path) -> Result<(Vec<u8>, String),
     println!("DEBUG: SAI2 No usable thumbnail found (checked {} chunks)", chunk_descriptors.len());
     Err(Box::new(Sai2Error::ThumbnailNotFound))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// SAI2 file with a single `thum` chunk holding a lossy thumbnail entry.
+    fn sai2_bytes(jssf_size: u32, jpeg: &[u8]) -> Vec<u8> {
+        let mut jssf = JSSF_MAGIC.to_vec();
+        jssf.extend_from_slice(&0u32.to_le_bytes());
+        jssf.extend_from_slice(&jssf_size.to_le_bytes());
+        jssf.extend_from_slice(&0u32.to_le_bytes());
+        jssf.extend_from_slice(jpeg);
+
+        let mut chunk = CANVAS_TYPE_THUMBNAIL_LOSSY.to_le_bytes().to_vec();
+        chunk.extend_from_slice(&(jssf.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(&jssf);
+
+        let mut file = vec![0u8; HEADER_SIZE];
+        file[..10].copy_from_slice(SAI2_MAGIC);
+        file[40..44].copy_from_slice(&1u32.to_le_bytes());
+        file.extend_from_slice(b"thum\0\0\0\0");
+        file.extend_from_slice(&(chunk.len() as u64).to_le_bytes());
+        file.extend_from_slice(&chunk);
+        file
+    }
+
+    #[test]
+    fn test_extracts_lossy_thumbnail() {
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xD9];
+        let (data, mime) = extract_sai2_preview_from_reader(&mut Cursor::new(sai2_bytes(4, &jpeg))).unwrap();
+        assert_eq!((data.as_slice(), mime.as_str()), (&jpeg[..], "image/jpeg"));
+    }
+
+    #[test]
+    fn test_hostile_sizes_fail_cleanly() {
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xD9];
+        assert!(extract_sai2_preview_from_reader(&mut Cursor::new(sai2_bytes(u32::MAX, &jpeg))).is_err());
+
+        // Chunk size pointing past the end of the address space
+        let mut file = sai2_bytes(4, &jpeg);
+        file[HEADER_SIZE + 8..HEADER_SIZE + 16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(extract_sai2_preview_from_reader(&mut Cursor::new(file)).is_err());
+
+        assert!(extract_sai2_preview_from_reader(&mut Cursor::new(b"SAI-CANVAS".to_vec())).is_err());
+    }
+}
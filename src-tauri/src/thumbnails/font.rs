@@ -1,16 +1,135 @@
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use resvg::usvg;
 use tiny_skia::Pixmap;
 
-const FONT_SVG_TEMPLATE: &str = "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 400 500\">\
+/// Family the previewed font is registered under, so it can't be confused
+/// with an installed font of the same name.
+const PREVIEW_FAMILY: &str = "Mundam Font Preview";
+
+/// Samples of each script, in order of preference: a headline and a few
+/// lines. A font is previewed with the first script it covers.
+const SCRIPT_SAMPLES: &[(&str, &[&str])] = &[
+    ("Aa", &["ABCDEFGHIJKLMNOPQRSTUVWXYZ", "abcdefghijklmnopqrstuvwxyz", "0123456789"]),
+    ("Аа", &["АБВГДЕЖЗИКЛМНОПРСТУФХЦЧШЭЮЯ", "абвгдежзиклмнопрстуфхцчшэюя"]),
+    ("Αα", &["ΑΒΓΔΕΖΗΘΙΚΛΜΝΞΟΠΡΣΤΥΦΧΨΩ", "αβγδεζηθικλμνξοπρστυφχψω"]),
+    ("אב", &["אבגדהוזחטיכלמנסעפצקרשת"]),
+    ("اب", &["ابتثجحخدذرزسشصضطظعغفقكلمنهوي"]),
+    ("कख", &["अआइईउऊएऐओऔ", "कखगघचछजझटठडढ"]),
+    ("กข", &["กขคงจฉชซญฎฏฐฑฒณดตถทธนบปผฝพฟภมยรลวศษสหฬอฮ"]),
+    ("あア", &["あいうえおかきくけこさしすせそ", "アイウエオカキクケコサシスセソ"]),
+    ("永字", &["天地玄黄宇宙洪荒日月盈昃辰宿列张"]),
+    ("한글", &["가나다라마바사아자차카타파하"]),
+    ("😀🎨", &["😀😂😍🤔😎🥳", "🐶🐱🦊🐻🐼🐸", "🍎🍕🎉🚀🌈⭐"]),
+];
+
+/// Share of a sample line a font must cover for the script to be chosen.
+const MIN_COVERAGE: f32 = 0.8;
+
+/// Characters per line when sampling straight from the cmap.
+const CMAP_LINE: usize = 12;
+
+/// What the preview shows in the font.
+#[derive(Debug, PartialEq)]
+struct Sample {
+    headline: String,
+    lines: Vec<String>,
+}
+
+/// Picks the sample a font can render: the first script it covers, with
+/// emoji first for color fonts, or else characters straight from its cmap
+/// (symbol and icon fonts).
+fn choose_sample(covers: impl Fn(char) -> bool, color: bool, codepoints: impl FnOnce() -> Vec<char>) -> Sample {
+    let emoji = SCRIPT_SAMPLES.len() - 1;
+    let order = if color { vec![emoji] } else { Vec::new() }.into_iter().chain(0..SCRIPT_SAMPLES.len());
+
+    for index in order {
+        let (headline, lines) = SCRIPT_SAMPLES[index];
+        if !headline.chars().all(&covers) {
+            continue;
+        }
+        let covered: Vec<String> = lines.iter().map(|line| line.chars().filter(|c| covers(*c)).collect()).collect();
+        let total: usize = lines.iter().map(|line| line.chars().count()).sum();
+        let found: usize = covered.iter().map(|line| line.chars().count()).sum();
+        if found as f32 >= total as f32 * MIN_COVERAGE {
+            return Sample {
+                headline: headline.to_string(),
+                lines: covered.into_iter().filter(|line| !line.is_empty()).collect(),
+            };
+        }
+    }
+
+    let chars: Vec<char> = codepoints()
+        .into_iter()
+        .filter(|c| !c.is_control() && !c.is_whitespace())
+        .take(CMAP_LINE * 3)
+        .collect();
+    Sample {
+        headline: chars.iter().take(2).collect(),
+        lines: chars.chunks(CMAP_LINE).map(|line| line.iter().collect()).collect(),
+    }
+}
+
+/// Sample for a parsed font, from the glyphs its cmap maps.
+fn font_sample(face: &ttf_parser::Face) -> Sample {
+    let raw = face.raw_face();
+    let color = [b"COLR", b"SVG ", b"sbix", b"CBDT"]
+        .iter()
+        .any(|tag| raw.table(ttf_parser::Tag::from_bytes(tag)).is_some());
+    let covers = |c: char| face.glyph_index(c).is_some_and(|glyph| glyph.0 != 0);
+
+    choose_sample(covers, color, || {
+        let mut codepoints = Vec::new();
+        if let Some(cmap) = face.tables().cmap {
+            for subtable in cmap.subtables.into_iter().filter(|subtable| subtable.is_unicode()) {
+                subtable.codepoints(|codepoint| codepoints.extend(char::from_u32(codepoint)));
+            }
+        }
+        codepoints.sort_unstable();
+        codepoints.dedup();
+        codepoints.retain(|c| covers(*c));
+        codepoints
+    })
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// The preview card: the sample in the font, and its name in a system font.
+fn preview_svg(family_name: &str, sample: &Sample) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 400 500\">\
   <rect width=\"400\" height=\"500\" fill=\"#f8f9fa\"/>\
-  <text x=\"200\" y=\"220\" font-family=\"{family}\" font-size=\"160\" text-anchor=\"middle\" fill=\"#1f2937\">Aa</text>\
-  <text x=\"200\" y=\"330\" font-family=\"{family}\" font-size=\"32\" text-anchor=\"middle\" fill=\"#4b5563\">{family}</text>\
-  <text x=\"200\" y=\"380\" font-family=\"{family}\" font-size=\"20\" text-anchor=\"middle\" fill=\"#9ca3af\">ABCDEFGHIJKLMNOPQRSTUVWXYZ</text>\
-  <text x=\"200\" y=\"410\" font-family=\"{family}\" font-size=\"20\" text-anchor=\"middle\" fill=\"#9ca3af\">abcdefghijklmnopqrstuvwxyz</text>\
-  <text x=\"200\" y=\"440\" font-family=\"{family}\" font-size=\"20\" text-anchor=\"middle\" fill=\"#9ca3af\">0123456789</text>\
-</svg>";
+  <text x=\"200\" y=\"220\" font-family=\"{preview}\" font-size=\"160\" text-anchor=\"middle\" fill=\"#1f2937\">{headline}</text>\
+  <text x=\"200\" y=\"330\" font-family=\"sans-serif\" font-size=\"32\" text-anchor=\"middle\" fill=\"#4b5563\">{family}</text>",
+        preview = PREVIEW_FAMILY,
+        headline = escape_xml(&sample.headline),
+        family = escape_xml(family_name),
+    );
+    for (index, line) in sample.lines.iter().take(3).enumerate() {
+        svg.push_str(&format!(
+            "<text x=\"200\" y=\"{}\" font-family=\"{}\" font-size=\"20\" text-anchor=\"middle\" fill=\"#9ca3af\">{}</text>",
+            380 + index * 30,
+            PREVIEW_FAMILY,
+            escape_xml(line),
+        ));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// System fonts for SVG text, loaded once since scanning them is slow.
+pub fn system_fontdb() -> Arc<usvg::fontdb::Database> {
+    static FONTDB: OnceLock<Arc<usvg::fontdb::Database>> = OnceLock::new();
+    FONTDB
+        .get_or_init(|| {
+            let mut fontdb = usvg::fontdb::Database::new();
+            fontdb.load_system_fonts();
+            Arc::new(fontdb)
+        })
+        .clone()
+}
 
 /// Generates a thumbnail for a font file by rendering a sample SVG using the font itself.
 pub fn generate_font_thumbnail(
@@ -42,15 +161,23 @@ pub fn generate_font_thumbnail(
     // We take the last face added (or the first one found in the file).
     let face = fontdb.faces().last().ok_or("No font faces found in file")?;
     let family_name = face.families.first().map(|(name, _)| name.clone()).unwrap_or_else(|| face.post_script_name.clone());
-    
-    // 3. Prepare options with the custom fontdb
-    let mut opt = usvg::Options::default();
-    opt.fontdb = Arc::new(fontdb);
 
-    // 4. Inject family name into SVG
-    // Escape simple characters to avoid breaking SVG XML
-    let safe_family = family_name.replace("\"", "&quot;").replace("'", "&apos;"); 
-    let svg_content = FONT_SVG_TEMPLATE.replace("{family}", &safe_family);
+    // 3. Pick sample characters the font actually has glyphs for
+    let sample = fontdb
+        .with_face_data(face.id, |data, index| ttf_parser::Face::parse(data, index).ok().map(|face| font_sample(&face)))
+        .flatten()
+        .ok_or("Failed to parse font tables")?;
+
+    // 4. Register the face under its own family next to the system fonts,
+    // which render the name of fonts that can't
+    let mut preview_face = face.clone();
+    preview_face.families = vec![(PREVIEW_FAMILY.to_string(), usvg::fontdb::Language::English_UnitedStates)];
+    let mut preview_db = (*system_fontdb()).clone();
+    preview_db.push_face_info(preview_face);
+
+    let mut opt = usvg::Options::default();
+    opt.fontdb = Arc::new(preview_db);
+    let svg_content = preview_svg(&family_name, &sample);
 
     // 5. Parse SVG
     let tree = usvg::Tree::from_str(&svg_content, &opt)
@@ -99,3 +226,41 @@ pub fn generate_font_thumbnail(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn covering(text: &str) -> impl Fn(char) -> bool + '_ {
+        move |c| text.contains(c)
+    }
+
+    #[test]
+    fn test_sample_follows_coverage() {
+        let latin = SCRIPT_SAMPLES[0].1.concat() + "Aa";
+        let sample = choose_sample(covering(&latin), false, Vec::new);
+        assert_eq!(sample.headline, "Aa");
+        assert_eq!(sample.lines.len(), 3);
+
+        // A Cyrillic font without Latin letters
+        let cyrillic = SCRIPT_SAMPLES[1].1.concat() + "Аа";
+        assert_eq!(choose_sample(covering(&cyrillic), false, Vec::new).headline, "Аа");
+
+        // Emoji fonts often map digits, which isn't enough for the Latin sample
+        let emoji = SCRIPT_SAMPLES[SCRIPT_SAMPLES.len() - 1].1.concat() + "😀🎨0123456789";
+        let sample = choose_sample(covering(&emoji), true, Vec::new);
+        assert_eq!(sample.headline, "😀🎨");
+        assert_eq!(sample.lines[0], "😀😂😍🤔😎🥳");
+    }
+
+    #[test]
+    fn test_icon_fonts_are_sampled_from_the_cmap() {
+        let icons: Vec<char> = (0xE000..0xE030).filter_map(char::from_u32).collect();
+        let sample = choose_sample(|c| icons.contains(&c), false, || [' '].into_iter().chain(icons.clone()).collect());
+        assert_eq!(sample.headline, "\u{E000}\u{E001}");
+        assert_eq!(sample.lines.len(), 3);
+        assert!(sample.lines.iter().all(|line| line.chars().count() == CMAP_LINE));
+
+        assert!(preview_svg("A & B", &sample).contains(">A &amp; B<"));
+    }
+}
@@ -0,0 +1,147 @@
+//! Histograms for exposure checks in the viewer.
+//!
+//! Computed from the display variant of the image (see `variants`), so a
+//! RAW or PSD is decoded once for both, and cached as JSON next to it. The
+//! WebView then draws the curves without decoding anything itself. The
+//! matte is left out, so transparent areas don't count as its color.
+
+use std::path::Path;
+
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Runtime};
+
+/// Size of the variant the histogram is computed from.
+pub const HISTOGRAM_MAXDIM: u32 = 1024;
+
+/// Bumped when histograms are computed differently.
+/// 2: computed without the matte.
+const HISTOGRAM_REVISION: u32 = 2;
+
+/// Share of pixels at either end of a channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Clipping {
+    /// At 0.
+    pub shadows: f64,
+    /// At 255.
+    pub highlights: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelClipping {
+    pub red: Clipping,
+    pub green: Clipping,
+    pub blue: Clipping,
+    pub luminance: Clipping,
+}
+
+/// 256-bin histograms of an image.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Histogram {
+    pub red: Vec<u32>,
+    pub green: Vec<u32>,
+    pub blue: Vec<u32>,
+    /// Rec. 709 luma of the encoded values.
+    pub luminance: Vec<u32>,
+    /// Pixels counted; fully transparent ones are left out.
+    pub pixels: u64,
+    pub clipping: ChannelClipping,
+    /// Mean luminance, 0-255.
+    pub mean_luminance: f64,
+}
+
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    (0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64).round().min(255.0) as u8
+}
+
+fn clipping(bins: &[u32], pixels: u64) -> Clipping {
+    let share = |count: u32| if pixels == 0 { 0.0 } else { count as f64 / pixels as f64 };
+    Clipping { shadows: share(bins[0]), highlights: share(bins[255]) }
+}
+
+/// Counts every visible pixel of `image`.
+pub fn compute(image: &RgbaImage) -> Histogram {
+    let (mut red, mut green, mut blue, mut luminance) = (vec![0u32; 256], vec![0u32; 256], vec![0u32; 256], vec![0u32; 256]);
+    let (mut pixels, mut luminance_total) = (0u64, 0u64);
+    for pixel in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+        if a == 0 {
+            continue;
+        }
+        let y = luma(r, g, b);
+        red[r as usize] += 1;
+        green[g as usize] += 1;
+        blue[b as usize] += 1;
+        luminance[y as usize] += 1;
+        pixels += 1;
+        luminance_total += y as u64;
+    }
+    let clipping = ChannelClipping {
+        red: clipping(&red, pixels),
+        green: clipping(&green, pixels),
+        blue: clipping(&blue, pixels),
+        luminance: clipping(&luminance, pixels),
+    };
+    let mean_luminance = if pixels == 0 { 0.0 } else { luminance_total as f64 / pixels as f64 };
+    Histogram { red, green, blue, luminance, pixels, clipping, mean_luminance }
+}
+
+/// Returns the cached histogram of `source`, computing it if needed.
+pub fn get_or_compute<R: Runtime>(app: &AppHandle<R>, source: &Path) -> Result<Histogram, Box<dyn std::error::Error>> {
+    // Variant names include the matte, so this also reads the unflattened variant
+    super::matte::without(|| compute_cached(app, source))
+}
+
+fn compute_cached<R: Runtime>(app: &AppHandle<R>, source: &Path) -> Result<Histogram, Box<dyn std::error::Error>> {
+    let cache_dir = super::variants::preview_cache_dir(app).ok_or("Preview cache directory unavailable")?;
+    let part = format!("histogram{}", HISTOGRAM_REVISION);
+    let cache_path = cache_dir.join(super::variants::part_variant_filename(source, &part, HISTOGRAM_MAXDIM)?).with_extension("json");
+    if let Some(cached) = std::fs::read(&cache_path).ok().and_then(|d| serde_json::from_slice::<Histogram>(&d).ok()) {
+        return Ok(cached);
+    }
+
+    // Small browser-native files have no variant and are read as they are
+    let image = match super::variants::get_or_create_variant(app, source, HISTOGRAM_MAXDIM)? {
+        Some(variant) => image::open(variant)?,
+        None => image::open(source)?,
+    };
+    let histogram = compute(&image.to_rgba8());
+
+    std::fs::create_dir_all(&cache_dir)?;
+    let tmp_path = cache_path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, serde_json::to_vec(&histogram)?)?;
+    std::fs::rename(&tmp_path, &cache_path)?;
+    Ok(histogram)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute() {
+        let image = RgbaImage::from_raw(
+            4,
+            1,
+            vec![
+                255, 255, 255, 255, // clipped white
+                0, 0, 0, 255, // crushed black
+                255, 0, 0, 255, // pure red
+                9, 9, 9, 0, // transparent, ignored
+            ],
+        )
+        .unwrap();
+        let histogram = compute(&image);
+        assert_eq!(histogram.pixels, 3);
+        assert_eq!((histogram.red[255], histogram.red[0]), (2, 1));
+        assert_eq!(histogram.luminance[luma(255, 0, 0) as usize], 1);
+        assert_eq!(histogram.luminance.iter().sum::<u32>(), 3);
+        assert_eq!(histogram.clipping.red, Clipping { shadows: 1.0 / 3.0, highlights: 2.0 / 3.0 });
+        assert_eq!(histogram.clipping.luminance, Clipping { shadows: 1.0 / 3.0, highlights: 1.0 / 3.0 });
+        assert!((histogram.mean_luminance - (255.0 + luma(255, 0, 0) as f64) / 3.0).abs() < 1e-9);
+
+        assert_eq!(compute(&RgbaImage::new(2, 2)).clipping, ChannelClipping::default());
+    }
+}
@@ -0,0 +1,361 @@
+//! Embedded ICC color profiles, kept across conversions.
+//!
+//! Originals are served byte for byte, profile included. What the `image://`
+//! protocol converts instead (a HEIC frame rendered by FFmpeg, a PSD
+//! composite, a downscaled WebP variant) is re-encoded by code that drops
+//! the profile, and the WebView would then show wide-gamut artwork as if it
+//! were sRGB. The profile of the original is written back into those
+//! outputs, for JPEG, PNG and WebP.
+
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use image::ImageDecoder;
+
+/// Marker of the JPEG APP2 segments holding a profile.
+const JPEG_ICC_MARKER: &[u8] = b"ICC_PROFILE\0";
+/// Profile bytes per APP2 segment: 65535 minus length, marker and numbering.
+const JPEG_ICC_CHUNK: usize = 65535 - 2 - JPEG_ICC_MARKER.len() - 2;
+
+/// Returns `encoded` with the profile of `source` embedded, unless `encoded`
+/// has its own or `source` has none.
+pub fn keep_profile(encoded: Vec<u8>, source: &Path) -> Vec<u8> {
+    if profile_of_encoded(&encoded).is_some() {
+        return encoded;
+    }
+    match read_profile(source) {
+        Some(profile) => embed(encoded, &profile),
+        None => encoded,
+    }
+}
+
+/// Profile embedded in an image file. Only the part of the file holding it
+/// is read: the `meta` box of HEIF/AVIF files, the image resources of PSDs.
+pub fn read_profile(path: &Path) -> Option<Vec<u8>> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    match extension.as_str() {
+        "heic" | "heif" | "avif" => isobmff_profile(&mut BufReader::new(File::open(path).ok()?)),
+        "psd" | "psb" => psd_profile(&mut BufReader::new(File::open(path).ok()?)),
+        _ => {
+            let mut decoder = image::ImageReader::open(path).ok()?.with_guessed_format().ok()?.into_decoder().ok()?;
+            decoder.icc_profile().ok().flatten()
+        }
+    }
+}
+
+/// Profile embedded in encoded image data.
+pub fn profile_of_encoded(data: &[u8]) -> Option<Vec<u8>> {
+    let mut decoder = image::ImageReader::new(Cursor::new(data)).with_guessed_format().ok()?.into_decoder().ok()?;
+    decoder.icc_profile().ok().flatten()
+}
+
+/// Embeds `profile` into JPEG, PNG or WebP data. Other data is returned as is.
+pub fn embed(data: Vec<u8>, profile: &[u8]) -> Vec<u8> {
+    let embedded = if data.starts_with(&[0xFF, 0xD8]) {
+        embed_jpeg(&data, profile)
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        embed_png(&data, profile)
+    } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        embed_webp(&data, profile)
+    } else {
+        None
+    };
+    embedded.unwrap_or(data)
+}
+
+/// Adds APP2 segments after the JFIF and Exif segments.
+fn embed_jpeg(data: &[u8], profile: &[u8]) -> Option<Vec<u8>> {
+    let mut pos = 2;
+    while data.get(pos) == Some(&0xFF) && matches!(data.get(pos + 1), Some(0xE0 | 0xE1)) {
+        let length = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+        pos += 2 + length;
+    }
+    if pos > data.len() {
+        return None;
+    }
+
+    let chunks: Vec<&[u8]> = profile.chunks(JPEG_ICC_CHUNK).collect();
+    if chunks.is_empty() || chunks.len() > 255 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(data.len() + profile.len() + chunks.len() * 18);
+    out.extend_from_slice(&data[..pos]);
+    for (index, chunk) in chunks.iter().enumerate() {
+        let length = (2 + JPEG_ICC_MARKER.len() + 2 + chunk.len()) as u16;
+        out.extend_from_slice(&[0xFF, 0xE2]);
+        out.extend_from_slice(&length.to_be_bytes());
+        out.extend_from_slice(JPEG_ICC_MARKER);
+        out.extend_from_slice(&[index as u8 + 1, chunks.len() as u8]);
+        out.extend_from_slice(chunk);
+    }
+    out.extend_from_slice(&data[pos..]);
+    Some(out)
+}
+
+/// Adds an `iCCP` chunk right after `IHDR`.
+fn embed_png(data: &[u8], profile: &[u8]) -> Option<Vec<u8>> {
+    // Signature (8) and IHDR: length, type, 13 bytes, CRC
+    const AFTER_IHDR: usize = 8 + 4 + 4 + 13 + 4;
+    if data.len() < AFTER_IHDR || &data[12..16] != b"IHDR" {
+        return None;
+    }
+
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(profile).ok()?;
+    let mut body = b"ICC Profile\0\0".to_vec();
+    body.extend(encoder.finish().ok()?);
+
+    let mut crc = flate2::Crc::new();
+    crc.update(b"iCCP");
+    crc.update(&body);
+
+    let mut out = Vec::with_capacity(data.len() + body.len() + 12);
+    out.extend_from_slice(&data[..AFTER_IHDR]);
+    out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    out.extend_from_slice(b"iCCP");
+    out.extend_from_slice(&body);
+    out.extend_from_slice(&crc.sum().to_be_bytes());
+    out.extend_from_slice(&data[AFTER_IHDR..]);
+    Some(out)
+}
+
+/// Adds an `ICCP` chunk, switching simple WebP files to the extended
+/// format that can carry one.
+fn embed_webp(data: &[u8], profile: &[u8]) -> Option<Vec<u8>> {
+    let first = data.get(12..16)?;
+    let first_size = u32::from_le_bytes(data.get(16..20)?.try_into().ok()?) as usize;
+    let first_end = 20 + first_size + (first_size & 1);
+
+    let mut chunks = Vec::with_capacity(data.len() + profile.len() + 32);
+    if first == b"VP8X" {
+        let mut vp8x = data.get(12..first_end)?.to_vec();
+        vp8x[8] |= 0x20;
+        chunks.extend(vp8x);
+        push_chunk(&mut chunks, b"ICCP", profile);
+        chunks.extend_from_slice(data.get(first_end..)?);
+    } else {
+        let body = data.get(20..20 + first_size)?;
+        let (width, height, alpha) = match first {
+            b"VP8 " if body.len() >= 10 => (
+                (u16::from_le_bytes([body[6], body[7]]) & 0x3FFF) as u32,
+                (u16::from_le_bytes([body[8], body[9]]) & 0x3FFF) as u32,
+                false,
+            ),
+            b"VP8L" if body.len() >= 5 => {
+                let bits = u32::from_le_bytes([body[1], body[2], body[3], body[4]]);
+                ((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1, (bits >> 28) & 1 == 1)
+            }
+            _ => return None,
+        };
+        let mut vp8x = vec![0x20 | if alpha { 0x10 } else { 0 }, 0, 0, 0];
+        vp8x.extend_from_slice(&width.saturating_sub(1).to_le_bytes()[..3]);
+        vp8x.extend_from_slice(&height.saturating_sub(1).to_le_bytes()[..3]);
+        push_chunk(&mut chunks, b"VP8X", &vp8x);
+        push_chunk(&mut chunks, b"ICCP", profile);
+        chunks.extend_from_slice(data.get(12..)?);
+    }
+
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&(chunks.len() as u32 + 4).to_le_bytes());
+    out.extend_from_slice(b"WEBP");
+    out.extend(chunks);
+    Some(out)
+}
+
+fn push_chunk(out: &mut Vec<u8>, fourcc: &[u8; 4], body: &[u8]) {
+    out.extend_from_slice(fourcc);
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(body);
+    if body.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// ISOBMFF boxes of `data`, as `(type, body)`.
+fn boxes(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> + '_ {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        let header = data.get(pos..pos + 8)?;
+        let (size, header_len) = match u32::from_be_bytes(header[0..4].try_into().ok()?) {
+            0 => (data.len() - pos, 8),
+            1 => (u64::from_be_bytes(data.get(pos + 8..pos + 16)?.try_into().ok()?) as usize, 16),
+            size => (size as usize, 8),
+        };
+        let body = data.get(pos + header_len..pos.checked_add(size)?)?;
+        let kind = &header[4..8];
+        pos += size.max(header_len);
+        Some((kind, body))
+    })
+}
+
+fn child<'a>(data: &'a [u8], kind: &[u8]) -> Option<&'a [u8]> {
+    boxes(data).find(|(k, _)| *k == kind).map(|(_, body)| body)
+}
+
+/// Body of the top-level box `kind` of an ISOBMFF file, seeking past the
+/// others (the media data of a large file).
+fn read_top_level_box(reader: &mut (impl Read + Seek), kind: &[u8]) -> Option<Vec<u8>> {
+    let mut pos = 0u64;
+    loop {
+        reader.seek(SeekFrom::Start(pos)).ok()?;
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).ok()?;
+        let (size, header_len) = match u32::from_be_bytes(header[0..4].try_into().ok()?) {
+            // To the end of the file: the last box
+            0 => (reader.seek(SeekFrom::End(0)).ok()? - pos, 8),
+            1 => {
+                let mut large = [0u8; 8];
+                reader.read_exact(&mut large).ok()?;
+                (u64::from_be_bytes(large), 16)
+            }
+            size => (size as u64, 8),
+        };
+        if size < header_len {
+            return None;
+        }
+        if &header[4..8] == kind {
+            reader.seek(SeekFrom::Start(pos + header_len)).ok()?;
+            let mut body = Vec::new();
+            reader.by_ref().take(size - header_len).read_to_end(&mut body).ok()?;
+            return Some(body);
+        }
+        pos += size;
+    }
+}
+
+/// Profile of a HEIF/AVIF file: the `colr` property of type `prof` or `rICC`.
+fn isobmff_profile(reader: &mut (impl Read + Seek)) -> Option<Vec<u8>> {
+    let meta = read_top_level_box(reader, b"meta")?;
+    // `meta` is a full box: version and flags come first
+    let meta = meta.get(4..)?;
+    let properties = child(child(meta, b"iprp")?, b"ipco")?;
+    boxes(properties)
+        .filter(|(kind, _)| *kind == b"colr")
+        .find(|(_, body)| matches!(body.get(0..4), Some(b"prof" | b"rICC")))
+        .map(|(_, body)| body[4..].to_vec())
+}
+
+fn read_be_u32(reader: &mut impl Read) -> Option<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes).ok()?;
+    Some(u32::from_be_bytes(bytes))
+}
+
+/// Profile of a PSD/PSB file: image resource 1039. Reads the header and
+/// the image resources, not the layers and pixels after them.
+fn psd_profile(reader: &mut (impl Read + Seek)) -> Option<Vec<u8>> {
+    const ICC_RESOURCE: u16 = 0x040F;
+    let mut header = [0u8; 26];
+    reader.read_exact(&mut header).ok()?;
+    if !header.starts_with(b"8BPS") {
+        return None;
+    }
+    let color_mode_len = read_be_u32(reader)?;
+    reader.seek(SeekFrom::Current(color_mode_len as i64)).ok()?;
+    let resources_len = read_be_u32(reader)?;
+    let mut data = Vec::new();
+    reader.by_ref().take(resources_len as u64).read_to_end(&mut data).ok()?;
+
+    let read_u32 = |pos: usize| -> Option<usize> { Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?) as usize) };
+    let mut pos = 0;
+    while pos + 12 <= data.len() {
+        if data.get(pos..pos + 4)? != b"8BIM" {
+            return None;
+        }
+        let id = u16::from_be_bytes([*data.get(pos + 4)?, *data.get(pos + 5)?]);
+        // Pascal name, padded to an even length
+        let name_len = *data.get(pos + 6)? as usize;
+        pos += 6 + (1 + name_len).next_multiple_of(2);
+        let size = read_u32(pos)?;
+        pos += 4;
+        if id == ICC_RESOURCE {
+            return data.get(pos..pos + size).map(<[u8]>::to_vec);
+        }
+        pos += size.next_multiple_of(2);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile() -> Vec<u8> {
+        b"fake display p3 profile ".repeat(8)
+    }
+
+    fn encoded(format: image::ImageFormat) -> Vec<u8> {
+        let img = image::RgbImage::from_pixel(3, 2, image::Rgb([200, 40, 10]));
+        let mut data = Vec::new();
+        img.write_to(&mut Cursor::new(&mut data), format).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_embed_round_trips() {
+        for format in [image::ImageFormat::Jpeg, image::ImageFormat::Png] {
+            let data = encoded(format);
+            assert_eq!(profile_of_encoded(&data), None);
+            let embedded = embed(data, &profile());
+            assert_eq!(profile_of_encoded(&embedded), Some(profile()), "{:?}", format);
+            assert!(image::load_from_memory(&embedded).is_ok());
+        }
+
+        let rgb = [200u8, 40, 10].repeat(6);
+        let webp = webp::Encoder::from_rgb(&rgb, 3, 2).encode(90.0).to_vec();
+        let embedded = embed(webp, &profile());
+        assert_eq!(profile_of_encoded(&embedded), Some(profile()));
+        assert_eq!(image::load_from_memory(&embedded).unwrap().width(), 3);
+    }
+
+    #[test]
+    fn test_large_profiles_span_jpeg_segments() {
+        let large: Vec<u8> = (0..150_000u32).map(|i| (i % 251) as u8).collect();
+        let embedded = embed(encoded(image::ImageFormat::Jpeg), &large);
+        assert_eq!(profile_of_encoded(&embedded), Some(large));
+    }
+
+    #[test]
+    fn test_isobmff_profile() {
+        let bx = |kind: &[u8; 4], body: &[u8]| {
+            let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+            out.extend_from_slice(kind);
+            out.extend_from_slice(body);
+            out
+        };
+        let mut colr = b"prof".to_vec();
+        colr.extend(profile());
+        let ipco = bx(b"ipco", &[bx(b"colr", b"nclx\0\x01\0\x0d\0\x06\0"), bx(b"colr", &colr)].concat());
+        let mut meta = vec![0, 0, 0, 0];
+        meta.extend(bx(b"hdlr", &[0; 24]));
+        meta.extend(bx(b"iprp", &ipco));
+        let file = [bx(b"ftyp", b"heicmif1heic"), bx(b"meta", &meta)].concat();
+        assert_eq!(isobmff_profile(&mut Cursor::new(&file)), Some(profile()));
+        // Boxes before `meta` are skipped, not read
+        let file = [bx(b"ftyp", b"heicmif1heic"), bx(b"mdat", &[0; 4096]), bx(b"meta", &meta)].concat();
+        assert_eq!(isobmff_profile(&mut Cursor::new(&file)), Some(profile()));
+    }
+
+    #[test]
+    fn test_psd_profile() {
+        let resource = |id: u16, data: &[u8]| {
+            let mut out = b"8BIM".to_vec();
+            out.extend(id.to_be_bytes());
+            out.extend([0, 0]);
+            out.extend((data.len() as u32).to_be_bytes());
+            out.extend(data);
+            if data.len() % 2 == 1 {
+                out.push(0);
+            }
+            out
+        };
+        let resources = [resource(0x0409, b"odd"), resource(0x040F, &profile())].concat();
+        let mut psd = b"8BPS\0\x01".to_vec();
+        psd.extend([0; 20]);
+        psd.extend(0u32.to_be_bytes());
+        psd.extend((resources.len() as u32).to_be_bytes());
+        psd.extend(resources);
+        assert_eq!(psd_profile(&mut Cursor::new(&psd)), Some(profile()));
+    }
+}
@@ -1,9 +1,19 @@
-use std::path::Path;
+//! Fallback icons for files without a usable preview.
+//!
+//! Each media type has its own glyph and color, with the extension as a
+//! badge. Icons are rendered once per extension and size into
+//! `thumbnails/extensions/`. Users can replace any of them by dropping an
+//! `.svg`, `.png` or `.webp` named after the extension (`blend.svg`) or the
+//! category (`video.png`) into the `icons/` folder of the app data directory.
+
+use std::path::{Path, PathBuf};
 use resvg::usvg;
 use tiny_skia::Pixmap;
 
+use crate::formats::{FileFormat, MediaType};
+
 /// Icon category for unsupported file types
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum IconCategory {
     File3D,
     Font,
@@ -16,6 +26,23 @@ pub enum IconCategory {
     Generic,
 }
 
+impl IconCategory {
+    /// Name used for user icon overrides (`icons/<name>.svg`).
+    fn name(self) -> &'static str {
+        match self {
+            IconCategory::File3D => "model",
+            IconCategory::Font => "font",
+            IconCategory::Design => "design",
+            IconCategory::Code => "code",
+            IconCategory::Video => "video",
+            IconCategory::Audio => "audio",
+            IconCategory::Image => "image",
+            IconCategory::Archive => "archive",
+            IconCategory::Generic => "generic",
+        }
+    }
+}
+
 /// Determine icon category based on file extension
 fn get_icon_category(path: &Path) -> IconCategory {
     let ext = path
@@ -66,7 +93,17 @@ fn get_icon_category(path: &Path) -> IconCategory {
             IconCategory::Image
         }
 
-        _ => IconCategory::Generic,
+        // Everything else follows the media type of its registered format
+        _ => match FileFormat::detect_extension(path).map(|f| &f.type_category) {
+            Some(MediaType::Image) => IconCategory::Image,
+            Some(MediaType::Video) => IconCategory::Video,
+            Some(MediaType::Audio) => IconCategory::Audio,
+            Some(MediaType::Project) => IconCategory::Design,
+            Some(MediaType::Archive) => IconCategory::Archive,
+            Some(MediaType::Model3D) => IconCategory::File3D,
+            Some(MediaType::Font) => IconCategory::Font,
+            Some(MediaType::Unknown) | None => IconCategory::Generic,
+        },
     }
 }
 
@@ -84,79 +121,134 @@ fn get_category_color(category: IconCategory) -> String {
     }
 }
 
-// SVG Template from docs/idea/logo/file-icon.svg
+/// Glyph drawn in the middle of the page, in the 400x500 template space.
+fn get_category_glyph(category: IconCategory) -> &'static str {
+    match category {
+        IconCategory::Image => r#"<circle cx="265" cy="145" r="34"/>
+    <path d="M 80 320 L 165 185 L 225 270 L 265 220 L 320 320 Z"/>"#,
+        IconCategory::Video => r#"<path d="M 150 120 C 150 105 162 98 175 106 L 300 198 C 312 207 312 223 300 232 L 175 324 C 162 332 150 325 150 310 Z"/>"#,
+        IconCategory::Audio => r#"<path d="M 175 130 L 300 100 L 300 270 C 300 297 275 315 250 315 C 225 315 210 300 210 282 C 210 262 230 247 255 247 C 262 247 268 248 275 251 L 275 158 L 200 176 L 200 300 C 200 327 175 345 150 345 C 125 345 110 330 110 312 C 110 292 130 277 155 277 C 162 277 168 278 175 281 Z"/>"#,
+        IconCategory::Archive => r#"<rect x="170" y="80" width="30" height="24"/>
+    <rect x="200" y="104" width="30" height="24"/>
+    <rect x="170" y="128" width="30" height="24"/>
+    <rect x="200" y="152" width="30" height="24"/>
+    <rect x="170" y="176" width="30" height="24"/>
+    <rect x="160" y="210" width="80" height="110" rx="14"/>"#,
+        IconCategory::File3D => r#"<path d="M 200 105 L 295 158 L 200 211 L 105 158 Z"/>
+    <path d="M 105 174 L 192 223 L 192 330 L 105 281 Z" style="opacity: 0.8;"/>
+    <path d="M 208 223 L 295 174 L 295 281 L 208 330 Z" style="opacity: 0.6;"/>"#,
+        IconCategory::Font => r#"<text x="200" y="290" style="font-family: serif; font-size: 200px; text-anchor: middle;">Aa</text>"#,
+        IconCategory::Code => r#"<path d="M 145 140 L 85 220 L 145 300 M 255 140 L 315 220 L 255 300 M 225 115 L 175 325" style="fill: none; stroke: rgb(128, 128, 128); stroke-width: 28px; stroke-linecap: round; stroke-linejoin: round;"/>"#,
+        IconCategory::Design => r#"<path d="M 90 300 C 120 130 280 130 310 300" style="fill: none; stroke: rgb(128, 128, 128); stroke-width: 20px; stroke-linecap: round;"/>
+    <path d="M 120 130 L 280 130" style="fill: none; stroke: rgb(128, 128, 128); stroke-width: 8px;"/>
+    <rect x="70" y="280" width="40" height="40" rx="6"/>
+    <rect x="290" y="280" width="40" height="40" rx="6"/>
+    <circle cx="120" cy="130" r="18"/>
+    <circle cx="280" cy="130" r="18"/>"#,
+        IconCategory::Generic => GENERIC_GLYPH,
+    }
+}
+
+// Mundam symbol, from docs/idea/logo/file-icon.svg
+const GENERIC_GLYPH: &str = r#"<g transform="matrix(0.802483, -0.463314, 0.463314, 0.802483, -41.736465, -2.234888)" style="filter: none; transform-origin: 244.285px 216.65px;" id="logo">
+    <path d="M 81.325 172.932 L 155.054 130.364 L 155.054 248.022 C 155.49 258.538 160.157 269.976 166.969 276.788 C 173.782 283.6 185.22 288.267 195.735 288.703 L 214.26 288.703 L 164.689 317.323 C 159.34 320.916 154.859 320.96 148.996 319.389 C 143.133 317.818 139.275 315.539 136.438 309.753 L 73.755 201.183 C 70.162 195.834 70.118 191.353 71.689 185.49 C 73.26 179.627 75.539 175.769 81.325 172.932 Z" style="fill-rule: nonzero; stroke-width: 20; stroke-linejoin: round; stroke-linecap: round; fill: rgb(128, 128, 128);"/>
+    <path d="M 195.735 101.975 L 283.755 101.975 C 290.184 101.538 294.087 103.74 298.379 108.032 C 299.49 109.143 300.46 110.227 301.281 111.336 C 295.053 114.98 289.29 120.43 285.447 126.493 L 222.764 235.063 C 217.884 244.387 216.207 256.626 218.7 265.933 C 218.947 266.853 219.243 267.778 219.585 268.703 L 195.735 268.703 C 189.306 269.14 185.403 266.938 181.111 262.646 C 176.819 258.354 174.618 254.451 175.054 248.022 L 175.054 122.656 C 174.617 116.227 176.819 112.324 181.111 108.032 C 185.403 103.74 189.306 101.539 195.735 101.975 Z" style="fill-rule: nonzero; stroke-width: 20px; stroke-linejoin: round; stroke-linecap: round; fill: rgb(128, 128, 128);"/>
+    <path d="M 416.882 185.491 C 418.453 191.354 418.409 195.835 414.816 201.184 L 352.133 309.754 C 349.296 315.54 345.438 317.819 339.575 319.39 C 333.712 320.961 329.231 320.917 323.882 317.324 L 247.654 273.314 C 241.869 270.477 239.589 266.619 238.018 260.756 C 236.447 254.893 236.492 250.412 240.085 245.063 L 302.768 136.493 C 305.604 130.707 309.462 128.428 315.325 126.857 C 321.188 125.286 325.669 125.33 331.018 128.923 L 407.246 172.933 C 413.032 175.77 415.311 179.628 416.882 185.491 Z" style="fill-rule: nonzero; stroke-width: 20; stroke-linejoin: round; stroke-linecap: round; fill: rgb(128, 128, 128);"/>
+  </g>"#;
+
+// Page shape from docs/idea/logo/file-icon.svg
 const SVG_TEMPLATE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
-<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 400 500" xmlns:bx="https://boxy-svg.com">
-  <path d="M 382.426 17.574 C 392.734 27.882 399.563 44.15 400 60 L 400 440 C 399.564 455.85 392.735 472.118 382.426 482.426 C 372.118 492.734 355.85 499.563 340 500 L 60 500 C 44.15 499.564 27.882 492.735 17.574 482.426 C 7.266 472.118 0.437 455.85 0 440 L 0 60 C 0.436 44.15 7.265 27.882 17.574 17.574 C 27.882 7.266 44.15 0.437 60 0 L 340 0 C 355.85 0.436 372.118 7.265 382.426 17.574 Z" style="stroke-linecap: round; stroke-linejoin: round; stroke-width: 20px; paint-order: fill; fill: rgb(0, 160, 169);" id="background"/>
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 400 500">
+  <path d="M 382.426 17.574 C 392.734 27.882 399.563 44.15 400 60 L 400 440 C 399.564 455.85 392.735 472.118 382.426 482.426 C 372.118 492.734 355.85 499.563 340 500 L 60 500 C 44.15 499.564 27.882 492.735 17.574 482.426 C 7.266 472.118 0.437 455.85 0 440 L 0 60 C 0.436 44.15 7.265 27.882 17.574 17.574 C 27.882 7.266 44.15 0.437 60 0 L 340 0 C 355.85 0.436 372.118 7.265 382.426 17.574 Z" style="stroke-linecap: round; stroke-linejoin: round; stroke-width: 20px; paint-order: fill; fill: {color};" id="background"/>
   <path d="M 60 0 L 340 0 C 355.85 0.436 372.118 7.265 382.426 17.574 C 392.734 27.882 399.563 44.15 400 60 L 400 440 C 399.564 455.85 392.735 472.118 382.426 482.426 C 372.118 492.734 355.85 499.563 340 500 L 60 500 C 44.15 499.564 27.882 492.735 17.574 482.426 C 7.266 472.118 0.437 455.85 0 440 L 0 60 C 0.436 44.15 7.265 27.882 17.574 17.574 C 27.882 7.266 44.15 0.437 60 0 Z M 31.716 31.716 C 23.928 39.504 19.564 48.236 20 60 L 20 440 C 19.564 451.764 23.928 460.496 31.716 468.284 C 39.504 476.072 48.236 480.436 60 480 L 340 480 C 351.764 480.436 360.496 476.072 368.284 468.284 C 376.072 460.496 380.436 451.764 380 440 L 380 60 C 380.436 48.236 376.072 39.504 368.284 31.716 C 360.496 23.928 351.764 19.564 340 20 L 60 20 C 48.236 19.564 39.504 23.928 31.716 31.716 Z" style="mix-blend-mode: color-burn; fill: rgb(128, 128, 128);"/>
-  <g id="content" style="mix-blend-mode: color-burn;">
-    <text style="fill: rgb(128, 128, 128); font-family: sans-serif; font-weight: bold; font-size: 80px; text-anchor: middle;" id="extension" x="200" y="440">.generic</text>
-    <g transform="matrix(0.802483, -0.463314, 0.463314, 0.802483, -41.736465, -2.234888)" style="filter: none; transform-origin: 244.285px 216.65px;" id="logo">
-      <path d="M 81.325 172.932 L 155.054 130.364 L 155.054 248.022 C 155.49 258.538 160.157 269.976 166.969 276.788 C 173.782 283.6 185.22 288.267 195.735 288.703 L 214.26 288.703 L 164.689 317.323 C 159.34 320.916 154.859 320.96 148.996 319.389 C 143.133 317.818 139.275 315.539 136.438 309.753 L 73.755 201.183 C 70.162 195.834 70.118 191.353 71.689 185.49 C 73.26 179.627 75.539 175.769 81.325 172.932 Z" style="fill-rule: nonzero; stroke-width: 20; stroke-linejoin: round; stroke-linecap: round; fill: rgb(128, 128, 128);"/>
-      <path d="M 195.735 101.975 L 283.755 101.975 C 290.184 101.538 294.087 103.74 298.379 108.032 C 299.49 109.143 300.46 110.227 301.281 111.336 C 295.053 114.98 289.29 120.43 285.447 126.493 L 222.764 235.063 C 217.884 244.387 216.207 256.626 218.7 265.933 C 218.947 266.853 219.243 267.778 219.585 268.703 L 195.735 268.703 C 189.306 269.14 185.403 266.938 181.111 262.646 C 176.819 258.354 174.618 254.451 175.054 248.022 L 175.054 122.656 C 174.617 116.227 176.819 112.324 181.111 108.032 C 185.403 103.74 189.306 101.539 195.735 101.975 Z" style="fill-rule: nonzero; stroke-width: 20px; stroke-linejoin: round; stroke-linecap: round; fill: rgb(128, 128, 128);"/>
-      <path d="M 416.882 185.491 C 418.453 191.354 418.409 195.835 414.816 201.184 L 352.133 309.754 C 349.296 315.54 345.438 317.819 339.575 319.39 C 333.712 320.961 329.231 320.917 323.882 317.324 L 247.654 273.314 C 241.869 270.477 239.589 266.619 238.018 260.756 C 236.447 254.893 236.492 250.412 240.085 245.063 L 302.768 136.493 C 305.604 130.707 309.462 128.428 315.325 126.857 C 321.188 125.286 325.669 125.33 331.018 128.923 L 407.246 172.933 C 413.032 175.77 415.311 179.628 416.882 185.491 Z" style="fill-rule: nonzero; stroke-width: 20; stroke-linejoin: round; stroke-linecap: round; fill: rgb(128, 128, 128);"/>
-    </g>
+  <g id="glyph" style="mix-blend-mode: color-burn; fill: rgb(128, 128, 128);">
+    {glyph}
+  </g>
+  <g id="badge">
+    <rect x="{badge_x}" y="380" width="{badge_width}" height="76" rx="18" style="fill: rgba(0, 0, 0, 0.28);"/>
+    <text x="200" y="{text_y}" style="fill: rgb(255, 255, 255); font-family: sans-serif; font-weight: bold; font-size: {font_size}px; text-anchor: middle;">{badge}</text>
   </g>
 </svg>"#;
 
-/// Get or generate a shared generic file icon
-///
-/// Returns the relative path to the icon file (extensions/icon_<ext>.webp)
-pub fn get_or_generate_icon(
-    input_path: &Path,
-    thumbnails_dir: &Path,
-    size_px: u32,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let ext = input_path
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("generic")
-        .to_lowercase();
-    let start_total = std::time::Instant::now();
+/// Longest extension shown in full on the badge.
+const MAX_BADGE_CHARS: usize = 6;
+
+/// Badge label: the upper-cased extension, truncated and XML-escaped.
+fn badge_text(ext: &str) -> String {
+    ext.chars()
+        .take(MAX_BADGE_CHARS)
+        .flat_map(char::to_uppercase)
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
 
-    // We store icons in a subfolder "extensions" to separate them from file hashes
-    let icons_dir = thumbnails_dir.join("extensions");
-    if !icons_dir.exists() {
-        std::fs::create_dir_all(&icons_dir)?;
+/// Font size and badge width that fit `chars` characters on the page.
+fn badge_metrics(chars: usize) -> (u32, u32) {
+    match chars {
+        0..=3 => (64, 180),
+        4 => (60, 220),
+        5 => (52, 240),
+        _ => (46, 260),
     }
+}
 
-    let icon_filename = format!("icon_{}.webp", ext);
-    let icon_path = icons_dir.join(&icon_filename);
-
-    // Return the relative path string that will be stored in DB
-    // e.g., "extensions/icon_pdf.webp"
-    // Since Windows uses backslash, we should ensure forward slash for DB consistency if possible,
-    // but the app handles PathBuf join, so reusing the returned string is tricky if it has separators.
-    // The DB stores whatever we return here. The frontend thumb:// protocol just needs valid relative or absolute.
-    // Actually, `thumb_handler` probably joins `thumbnails_dir` with the DB string.
-    let relative_path_string = format!("extensions/{}", icon_filename);
-
-    if icon_path.exists() {
-        return Ok(relative_path_string);
-    }
+fn build_svg(category: IconCategory, ext: &str) -> String {
+    let chars = ext.chars().count().min(MAX_BADGE_CHARS);
+    let (font_size, badge_width) = badge_metrics(chars);
+    SVG_TEMPLATE
+        .replace("{color}", &get_category_color(category))
+        .replace("{glyph}", get_category_glyph(category))
+        .replace("{badge_x}", &(200 - badge_width / 2).to_string())
+        .replace("{badge_width}", &badge_width.to_string())
+        // Baseline roughly centered in the badge
+        .replace("{text_y}", &(418 + font_size * 36 / 100).to_string())
+        .replace("{font_size}", &font_size.to_string())
+        .replace("{badge}", &badge_text(ext))
+}
 
-    // Generate logical (Code from previous generate_thumbnail_icon)
-    let category = get_icon_category(input_path);
-    let color = get_category_color(category);
+/// Directory holding user icon overrides, next to the thumbnails directory.
+fn user_icons_dir(thumbnails_dir: &Path) -> Option<PathBuf> {
+    thumbnails_dir.parent().map(|dir| dir.join("icons"))
+}
 
-    // Customize the SVG template
-    // 1. Replace background color
-    let mut svg_content = SVG_TEMPLATE.replace("rgb(0, 160, 169)", &color);
+/// Finds a user override for the extension, then for the category.
+fn find_user_icon(thumbnails_dir: &Path, ext: &str, category: IconCategory) -> Option<PathBuf> {
+    let dir = user_icons_dir(thumbnails_dir)?;
+    [ext, category.name()]
+        .iter()
+        .flat_map(|name| ["svg", "png", "webp"].map(|kind| dir.join(format!("{}.{}", name, kind))))
+        .find(|candidate| candidate.is_file())
+}
 
-    // 2. Replace extension text
-    let display_ext = if ext.len() > 4 {
-        format!(".{}", &ext[0..4])
-    } else {
-        format!(".{}", ext)
-    };
-    svg_content = svg_content.replace(".generic", &display_ext);
+/// Cache name of an icon. Overrides get a stamp so editing them renders a new file.
+fn icon_filename(ext: &str, size_px: u32, user_icon: Option<&Path>) -> String {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    match user_icon {
+        Some(path) => {
+            let mut hasher = DefaultHasher::new();
+            path.hash(&mut hasher);
+            if let Ok(modified) = std::fs::metadata(path).and_then(|m| m.modified()) {
+                modified.hash(&mut hasher);
+            }
+            format!("icon_{}_{}_{:x}.webp", ext, size_px, hasher.finish())
+        }
+        None => format!("icon_{}_{}.webp", ext, size_px),
+    }
+}
 
-    // Render using resvg
-    let opt = usvg::Options::default();
-    let mut fontdb = usvg::fontdb::Database::new();
-    fontdb.load_system_fonts();
+/// Renders an SVG centered on a transparent `size_px` square.
+fn render_svg(svg_content: &str, size_px: u32) -> Result<Pixmap, Box<dyn std::error::Error>> {
+    let mut opt = usvg::Options::default();
+    opt.fontdb = super::font::system_fontdb();
 
-    let tree = usvg::Tree::from_str(&svg_content, &opt)
+    let tree = usvg::Tree::from_str(svg_content, &opt)
         .map_err(|e| format!("SVG template parse error: {}", e))?;
 
     let size = tree.size();
@@ -190,15 +282,147 @@ pub fn get_or_generate_icon(
         &mut pixmap.as_mut()
     );
 
-    // Encode to WebP
-    let encoder = webp::Encoder::from_rgba(
-        pixmap.data(),
-        size_px,
-        size_px,
-    );
+    Ok(pixmap)
+}
+
+/// Renders a user icon into an RGBA `size_px` square.
+fn render_user_icon(path: &Path, size_px: u32) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let is_svg = path.extension().and_then(|e| e.to_str()).map(|e| e.eq_ignore_ascii_case("svg")).unwrap_or(false);
+    if is_svg {
+        let svg_content = std::fs::read_to_string(path)?;
+        // Pixmap data is premultiplied; the WebP encoder expects straight alpha
+        let pixmap = render_svg(&svg_content, size_px)?;
+        return Ok(pixmap.pixels().iter().flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        }).collect());
+    }
+
+    let img = image::open(path)?.resize(size_px, size_px, image::imageops::FilterType::Lanczos3).to_rgba8();
+    let mut canvas = image::RgbaImage::new(size_px, size_px);
+    let x = (size_px - img.width()) / 2;
+    let y = (size_px - img.height()) / 2;
+    image::imageops::overlay(&mut canvas, &img, x as i64, y as i64);
+    Ok(canvas.into_raw())
+}
+
+/// Get or generate the shared icon for a file's extension
+///
+/// Returns the relative path to the icon file (extensions/icon_<ext>_<size>.webp)
+pub fn get_or_generate_icon(
+    input_path: &Path,
+    thumbnails_dir: &Path,
+    size_px: u32,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let ext = input_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("generic")
+        .to_lowercase();
+    let start_total = std::time::Instant::now();
+
+    // We store icons in a subfolder "extensions" to separate them from file hashes
+    let icons_dir = thumbnails_dir.join("extensions");
+    if !icons_dir.exists() {
+        std::fs::create_dir_all(&icons_dir)?;
+    }
+
+    let category = get_icon_category(input_path);
+    let user_icon = find_user_icon(thumbnails_dir, &ext, category);
+
+    let icon_filename = icon_filename(&ext, size_px, user_icon.as_deref());
+    let icon_path = icons_dir.join(&icon_filename);
+
+    // Return the relative path string that will be stored in DB
+    // e.g., "extensions/icon_pdf_256.webp"
+    // Since Windows uses backslash, we should ensure forward slash for DB consistency if possible,
+    // but the app handles PathBuf join, so reusing the returned string is tricky if it has separators.
+    // The DB stores whatever we return here. The frontend thumb:// protocol just needs valid relative or absolute.
+    // Actually, `thumb_handler` probably joins `thumbnails_dir` with the DB string.
+    let relative_path_string = format!("extensions/{}", icon_filename);
+
+    if icon_path.exists() {
+        return Ok(relative_path_string);
+    }
+
+    let rgba = match user_icon.as_deref().map(|path| (path, render_user_icon(path, size_px))) {
+        Some((_, Ok(rgba))) => rgba,
+        other => {
+            if let Some((path, Err(e))) = other {
+                eprintln!("WARN: Could not render custom icon {:?}: {}", path, e);
+            }
+            let pixmap = render_svg(&build_svg(category, &ext), size_px)?;
+            pixmap.pixels().iter().flat_map(|p| {
+                let c = p.demultiply();
+                [c.red(), c.green(), c.blue(), c.alpha()]
+            }).collect()
+        }
+    };
+
+    // Encode to WebP, through a temp file so concurrent workers never read a partial icon
+    let encoder = webp::Encoder::from_rgba(&rgba, size_px, size_px);
     let webp_data = encoder.encode(85.0);
-    std::fs::write(&icon_path, &*webp_data)?;
+    let tmp_path = icon_path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    std::fs::write(&tmp_path, &*webp_data)?;
+    std::fs::rename(&tmp_path, &icon_path)?;
 
     println!("DEBUG: Icon fallback Total took: {:?}", start_total.elapsed());
     Ok(relative_path_string)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_icon_category() {
+        assert_eq!(get_icon_category(Path::new("a.MP4")), IconCategory::Video);
+        assert_eq!(get_icon_category(Path::new("a.psd")), IconCategory::Design);
+        assert_eq!(get_icon_category(Path::new("a.unknownext")), IconCategory::Generic);
+        assert_eq!(get_icon_category(Path::new("noextension")), IconCategory::Generic);
+    }
+
+    #[test]
+    fn test_badge_text() {
+        assert_eq!(badge_text("psd"), "PSD");
+        assert_eq!(badge_text("afdesign"), "AFDESI");
+        assert_eq!(badge_text("a&b"), "A&amp;B");
+    }
+
+    #[test]
+    fn test_template_is_valid_for_every_category() {
+        let categories = [
+            IconCategory::File3D, IconCategory::Font, IconCategory::Design, IconCategory::Code,
+            IconCategory::Video, IconCategory::Audio, IconCategory::Image, IconCategory::Archive,
+            IconCategory::Generic,
+        ];
+        for category in categories {
+            let svg = build_svg(category, "ext");
+            assert!(!svg.contains('{'), "unfilled placeholder for {:?}", category);
+            assert!(usvg::Tree::from_str(&svg, &usvg::Options::default()).is_ok(), "invalid SVG for {:?}", category);
+        }
+    }
+
+    #[test]
+    fn test_icons_are_cached_per_size_and_override() {
+        let root = std::env::temp_dir().join(format!("mundam_icons_{}", uuid::Uuid::new_v4()));
+        let thumbnails_dir = root.join("thumbnails");
+
+        let small = get_or_generate_icon(Path::new("a.xyz"), &thumbnails_dir, 64).unwrap();
+        let large = get_or_generate_icon(Path::new("b.xyz"), &thumbnails_dir, 128).unwrap();
+        assert_eq!(small, "extensions/icon_xyz_64.webp");
+        assert_eq!(large, "extensions/icon_xyz_128.webp");
+        assert!(thumbnails_dir.join(&small).exists());
+
+        // A user icon for the extension replaces the built-in one
+        std::fs::create_dir_all(root.join("icons")).unwrap();
+        image::RgbaImage::from_pixel(32, 16, image::Rgba([255, 0, 0, 255]))
+            .save(root.join("icons").join("xyz.png"))
+            .unwrap();
+        let custom = get_or_generate_icon(Path::new("c.xyz"), &thumbnails_dir, 64).unwrap();
+        assert_ne!(custom, small);
+        assert!(thumbnails_dir.join(&custom).exists());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,175 @@
+//! Crash isolation for native decoders.
+//!
+//! The psd, image and rsraw decoders occasionally panic or segfault on
+//! malformed files. When isolation is enabled, the risky strategies are run in
+//! a short-lived copy of this executable (started with [`HELPER_FLAG`]) under
+//! the process supervisor, so a crash or hang only costs that one thumbnail and
+//! the caller falls back to the file icon.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::formats::{FileFormat, ThumbnailStrategy};
+use crate::streaming::process_manager::{self, JobKind};
+
+/// First argument that switches the executable into helper mode.
+pub const HELPER_FLAG: &str = "--thumbnail-helper";
+
+/// Hard limit for a single isolated render.
+const HELPER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Last helper argument asking for a render from the full-resolution source.
+const FULL_ARG: &str = "--full";
+
+/// Hard limit for a full-resolution render, which develops a whole RAW.
+const FULL_HELPER_TIMEOUT: Duration = Duration::from_secs(120);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns subprocess rendering on or off (from the `isolate_thumbnail_decoders` setting).
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether risky decoders run in a helper process.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Strategies backed by native decoders that are known to crash on bad input.
+pub fn is_risky(strategy: &ThumbnailStrategy) -> bool {
+    matches!(
+        strategy,
+        ThumbnailStrategy::NativeImage | ThumbnailStrategy::NativeExtractor | ThumbnailStrategy::Raw
+    )
+}
+
+/// Renders a thumbnail in a helper process. The helper is told where the
+/// app's resources are, to find the bundled PDFium.
+///
+/// # Errors
+/// Returns an error if the helper can't be started, exits with a failure,
+/// crashes, or exceeds [`HELPER_TIMEOUT`].
+pub fn generate_isolated<R: Runtime>(
+    app_handle: Option<&AppHandle<R>>,
+    input_path: &Path,
+    output_path: &Path,
+    size_px: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let resource_dir = app_handle.and_then(|handle| handle.path().resource_dir().ok());
+    run_helper(input_path, output_path, size_px, resource_dir, false)
+}
+
+/// Like [`generate_isolated`], but renders from the full-resolution source
+/// (see `upgrade::render_full`), within [`FULL_HELPER_TIMEOUT`].
+pub fn generate_isolated_full(
+    input_path: &Path,
+    output_path: &Path,
+    size_px: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    run_helper(input_path, output_path, size_px, None, true)
+}
+
+fn run_helper(
+    input_path: &Path,
+    output_path: &Path,
+    size_px: u32,
+    resource_dir: Option<PathBuf>,
+    full: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let exe = std::env::current_exe()?;
+    let mut cmd = Command::new(exe);
+    cmd.arg(HELPER_FLAG)
+        .arg(input_path)
+        .arg(output_path)
+        .arg(size_px.to_string())
+        .env(super::matte::HELPER_ENV, super::matte::current().as_setting());
+    if let Some(dir) = resource_dir {
+        cmd.env(crate::media::pdf::RESOURCE_DIR_ENV, dir);
+    }
+    if full {
+        cmd.arg(FULL_ARG);
+    }
+
+    let label = input_path.to_string_lossy();
+    let timeout = if full { FULL_HELPER_TIMEOUT } else { HELPER_TIMEOUT };
+    let output = process_manager::run_supervised(cmd, JobKind::Decoder, &label, timeout)
+        .map_err(|e| format!("Isolated decoder failed: {}", e))?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let reason = stderr.lines().last().unwrap_or("").trim().to_string();
+    match output.status.code() {
+        Some(code) => Err(format!("Isolated decoder exited with {}: {}", code, reason).into()),
+        // No exit code means the helper was killed by a signal (e.g. a segfault)
+        None => Err(format!("Isolated decoder crashed: {}", reason).into()),
+    }
+}
+
+/// Entry point for helper mode. Returns `None` when the process was not
+/// started as a helper, otherwise the exit code to terminate with.
+pub fn run_helper_from_args() -> Option<i32> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) != Some(HELPER_FLAG) {
+        return None;
+    }
+
+    let (Some(input), Some(output), Some(size)) = (args.get(2), args.get(3), args.get(4)) else {
+        eprintln!("usage: {} <input> <output> <size>", HELPER_FLAG);
+        return Some(2);
+    };
+    let Ok(size_px) = size.parse::<u32>() else {
+        eprintln!("Invalid thumbnail size: {}", size);
+        return Some(2);
+    };
+
+    if let Ok(matte) = std::env::var(super::matte::HELPER_ENV) {
+        super::matte::set_from_setting(&matte);
+    }
+
+    let result = if args.get(5).map(String::as_str) == Some(FULL_ARG) {
+        super::upgrade::render_full(Path::new(input), Path::new(output), size_px)
+    } else {
+        render_in_process(Path::new(input), Path::new(output), size_px)
+    };
+    match result {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("{}", e);
+            Some(1)
+        }
+    }
+}
+
+/// Runs the risky strategy for `input_path` directly in this process.
+fn render_in_process(
+    input_path: &Path,
+    output_path: &Path,
+    size_px: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let strategy = FileFormat::detect(input_path)
+        .map(|f| f.strategy.clone())
+        .unwrap_or(ThumbnailStrategy::Icon);
+
+    match strategy {
+        ThumbnailStrategy::NativeImage => {
+            super::native::generate_thumbnail_fast(input_path, output_path, size_px, None)
+        }
+        // No app handle here: PDFium is found through `pdf::RESOURCE_DIR_ENV`
+        ThumbnailStrategy::NativeExtractor => super::extractors::generate_thumbnail_extracted(
+            None::<&AppHandle<tauri::Wry>>,
+            input_path,
+            output_path,
+            size_px,
+        ),
+        ThumbnailStrategy::Raw => super::raw::generate_raw_thumbnail(input_path, output_path, size_px),
+        other => Err(format!("Strategy {:?} is not rendered in isolation", other).into()),
+    }
+}
@@ -0,0 +1,239 @@
+//! Background matte for transparent previews.
+//!
+//! Transparent images are flattened onto the matte chosen in the
+//! `preview_matte` setting before thumbnails and preview variants are encoded,
+//! and when the originals are served, so they look the same whatever the
+//! WebView draws behind them. Accepted
+//! values are `none`, `checkerboard`, `white`, `black` and `#rrggbb` colors.
+
+use std::cell::Cell;
+use std::sync::RwLock;
+
+/// Settings key holding the matte.
+pub const SETTING_KEY: &str = "preview_matte";
+
+/// Passes the matte on to isolated decoder helpers.
+pub const HELPER_ENV: &str = "MUNDAM_PREVIEW_MATTE";
+
+/// Side of a checkerboard square, in output pixels.
+const CHECKER_SIZE: u32 = 8;
+const CHECKER_LIGHT: [u8; 3] = [255, 255, 255];
+const CHECKER_DARK: [u8; 3] = [204, 204, 204];
+
+/// Formats that can hold transparency, whose thumbnails are rendered again
+/// when the matte changes.
+pub const TRANSPARENT_FORMATS: &[&str] = &[
+    "png", "webp", "gif", "ico", "cur", "tif", "tiff", "tga", "heic", "heif", "avif", "jxl", "icns", "svg", "psd",
+    "psb", "ai", "pdf", "xcf", "aseprite", "ase", "kra",
+];
+
+/// What transparent pixels are flattened onto.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Matte {
+    /// Keep the alpha channel.
+    #[default]
+    None,
+    Checkerboard,
+    Solid([u8; 3]),
+}
+
+impl Matte {
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_lowercase();
+        match value.as_str() {
+            "" | "none" | "transparent" => Some(Matte::None),
+            "checkerboard" => Some(Matte::Checkerboard),
+            "white" => Some(Matte::Solid([255, 255, 255])),
+            "black" => Some(Matte::Solid([0, 0, 0])),
+            _ => parse_hex(&value).map(Matte::Solid),
+        }
+    }
+
+    /// Canonical setting value, also used in cache keys.
+    pub fn as_setting(self) -> String {
+        match self {
+            Matte::None => "none".to_string(),
+            Matte::Checkerboard => "checkerboard".to_string(),
+            Matte::Solid([r, g, b]) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        }
+    }
+
+    /// Suffix told apart in thumbnail names, empty without a matte so those
+    /// keep their names.
+    pub fn cache_tag(self) -> String {
+        match self {
+            Matte::None => String::new(),
+            Matte::Checkerboard => "-checker".to_string(),
+            Matte::Solid([r, g, b]) => format!("-m{:02x}{:02x}{:02x}", r, g, b),
+        }
+    }
+}
+
+/// Parses `#rgb` and `#rrggbb`.
+fn parse_hex(value: &str) -> Option<[u8; 3]> {
+    let hex = value.strip_prefix('#')?;
+    if !hex.is_ascii() {
+        return None;
+    }
+    match hex.len() {
+        3 => {
+            let mut rgb = [0u8; 3];
+            for (i, c) in hex.chars().enumerate() {
+                let v = c.to_digit(16)? as u8;
+                rgb[i] = v * 17;
+            }
+            Some(rgb)
+        }
+        6 => {
+            let mut rgb = [0u8; 3];
+            for (i, channel) in rgb.iter_mut().enumerate() {
+                *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+            }
+            Some(rgb)
+        }
+        _ => None,
+    }
+}
+
+static MATTE: RwLock<Matte> = RwLock::new(Matte::None);
+
+thread_local! {
+    /// Set while [`without`] runs on this thread.
+    static SUSPENDED: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn set(matte: Matte) {
+    *MATTE.write().unwrap_or_else(|e| e.into_inner()) = matte;
+}
+
+/// The configured matte, or none inside [`without`].
+pub fn current() -> Matte {
+    if SUSPENDED.with(Cell::get) {
+        return Matte::None;
+    }
+    *MATTE.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Runs `f` with the matte turned off on this thread, for renders measured
+/// rather than shown (histograms).
+pub fn without<T>(f: impl FnOnce() -> T) -> T {
+    let previous = SUSPENDED.with(|s| s.replace(true));
+    let result = f();
+    SUSPENDED.with(|s| s.set(previous));
+    result
+}
+
+/// Applies the `preview_matte` setting value, falling back to no matte.
+pub fn set_from_setting(value: &str) {
+    let matte = Matte::parse(value).unwrap_or_else(|| {
+        eprintln!("WARN: Invalid {} value {:?}, using none", SETTING_KEY, value);
+        Matte::None
+    });
+    set(matte);
+}
+
+/// Flattens straight-alpha RGBA pixels onto `matte`, in place.
+pub fn apply(matte: Matte, rgba: &mut [u8], width: u32, height: u32) {
+    if matte == Matte::None || width == 0 {
+        return;
+    }
+
+    for (i, px) in rgba.chunks_exact_mut(4).enumerate().take(width as usize * height as usize) {
+        let alpha = px[3] as u32;
+        if alpha == 255 {
+            continue;
+        }
+
+        let background = match matte {
+            Matte::Solid(color) => color,
+            _ => {
+                let (x, y) = (i as u32 % width, i as u32 / width);
+                if (x / CHECKER_SIZE + y / CHECKER_SIZE) % 2 == 0 { CHECKER_LIGHT } else { CHECKER_DARK }
+            }
+        };
+        for (channel, bg) in px[..3].iter_mut().zip(background) {
+            *channel = ((*channel as u32 * alpha + bg as u32 * (255 - alpha) + 127) / 255) as u8;
+        }
+        px[3] = 255;
+    }
+}
+
+/// Applies the configured matte.
+pub fn apply_current(rgba: &mut [u8], width: u32, height: u32) {
+    apply(current(), rgba, width, height);
+}
+
+/// Flattens an encoded image onto the configured matte and returns it as PNG.
+///
+/// Returns `None` when there is no matte or the image is not transparent, so
+/// the original bytes can be served untouched.
+pub fn flatten_encoded(data: &[u8]) -> Option<Vec<u8>> {
+    let matte = current();
+    if matte == Matte::None {
+        return None;
+    }
+    let img = image::load_from_memory(data).ok()?;
+    if !img.color().has_alpha() {
+        return None;
+    }
+
+    let mut rgba = img.into_rgba8();
+    let (width, height) = rgba.dimensions();
+    apply(matte, &mut rgba, width, height);
+
+    let mut png_data = Vec::new();
+    image::DynamicImage::ImageRgba8(rgba)
+        .to_rgb8()
+        .write_to(&mut std::io::Cursor::new(&mut png_data), image::ImageFormat::Png)
+        .ok()?;
+    Some(png_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Matte::parse("none"), Some(Matte::None));
+        assert_eq!(Matte::parse("Checkerboard"), Some(Matte::Checkerboard));
+        assert_eq!(Matte::parse("white"), Some(Matte::Solid([255, 255, 255])));
+        assert_eq!(Matte::parse("#1A2b3C"), Some(Matte::Solid([0x1a, 0x2b, 0x3c])));
+        assert_eq!(Matte::parse("#f80"), Some(Matte::Solid([255, 136, 0])));
+        assert_eq!(Matte::parse("#12345"), None);
+        assert_eq!(Matte::parse("purple"), None);
+        assert_eq!(Matte::Solid([0x1a, 0x2b, 0x3c]).as_setting(), "#1a2b3c");
+    }
+
+    #[test]
+    fn test_apply_solid() {
+        // Opaque, half transparent and fully transparent red pixels
+        let mut rgba = vec![255, 0, 0, 255, 255, 0, 0, 128, 255, 0, 0, 0];
+        apply(Matte::Solid([0, 0, 255]), &mut rgba, 3, 1);
+        assert_eq!(rgba, vec![255, 0, 0, 255, 128, 0, 127, 255, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_apply_checkerboard() {
+        let width = CHECKER_SIZE * 2;
+        let mut rgba = vec![0u8; (width * 4) as usize];
+        apply(Matte::Checkerboard, &mut rgba, width, 1);
+        assert_eq!(&rgba[0..4], &[255, 255, 255, 255]);
+        let second = (CHECKER_SIZE * 4) as usize;
+        assert_eq!(&rgba[second..second + 4], &[204, 204, 204, 255]);
+    }
+
+    #[test]
+    fn test_none_keeps_alpha() {
+        let mut rgba = vec![10, 20, 30, 40];
+        apply(Matte::None, &mut rgba, 1, 1);
+        assert_eq!(rgba, vec![10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn test_cache_tag() {
+        assert_eq!(Matte::None.cache_tag(), "");
+        assert_eq!(Matte::Checkerboard.cache_tag(), "-checker");
+        assert_eq!(Matte::Solid([0x1a, 0x2b, 0x3c]).cache_tag(), "-m1a2b3c");
+    }
+}
@@ -0,0 +1,203 @@
+//! Budget for decoded image data held by concurrent thumbnail renders.
+//!
+//! Every worker thread may be decoding a 100 MP image at once, and each one
+//! holds the full decoded frame plus its RGBA copy until it is resized. The
+//! decoders reserve their estimated size here before decoding; once the
+//! reservations in flight would pass the cap, further decodes wait for one
+//! to finish instead of pushing the process into gigabytes of RSS. A single
+//! image larger than the cap still runs, alone.
+
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+
+use serde::Serialize;
+
+/// Setting holding the cap in MB.
+pub const SETTING_KEY: &str = "thumbnail_memory_mb";
+
+/// Default cap on decoded data in flight.
+pub const DEFAULT_LIMIT_MB: u64 = 1024;
+
+/// Bytes held per pixel while decoding: the decoded frame (up to 4 bytes)
+/// and its RGBA copy.
+const BYTES_PER_PIXEL: u64 = 8;
+
+/// Estimated memory for decoding a `width` x `height` image.
+pub fn decoded_bytes(width: u32, height: u32) -> u64 {
+    (width as u64 * height as u64).saturating_mul(BYTES_PER_PIXEL)
+}
+
+/// Snapshot of a budget, shown in the thumbnail worker status.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryStats {
+    /// Cap on reserved bytes.
+    pub limit_bytes: u64,
+    /// Bytes reserved by running decodes.
+    pub in_flight_bytes: u64,
+    /// Highest `in_flight_bytes` since startup.
+    pub peak_bytes: u64,
+    /// Decodes holding a reservation.
+    pub active: usize,
+    /// Decodes waiting for room.
+    pub waiting: usize,
+    /// Decodes that had to wait, since startup.
+    pub queued_total: u64,
+}
+
+/// Cap on the decoded bytes held at once, shared by the decoding threads.
+pub struct MemoryBudget {
+    stats: Mutex<MemoryStats>,
+    released: Condvar,
+}
+
+/// Reserved bytes, given back on drop.
+pub struct Reservation<'a> {
+    budget: &'a MemoryBudget,
+    bytes: u64,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        let mut stats = self.budget.stats.lock().unwrap_or_else(|e| e.into_inner());
+        stats.in_flight_bytes = stats.in_flight_bytes.saturating_sub(self.bytes);
+        stats.active -= 1;
+        drop(stats);
+        self.budget.released.notify_all();
+    }
+}
+
+impl MemoryBudget {
+    /// A budget admitting `limit_bytes` in flight.
+    pub const fn new(limit_bytes: u64) -> Self {
+        Self {
+            stats: Mutex::new(MemoryStats {
+                limit_bytes,
+                in_flight_bytes: 0,
+                peak_bytes: 0,
+                active: 0,
+                waiting: 0,
+                queued_total: 0,
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Reserves `bytes`, blocking until they fit under the cap.
+    pub fn reserve(&self, bytes: u64) -> Reservation<'_> {
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        let fits = |stats: &MemoryStats| stats.active == 0 || stats.in_flight_bytes.saturating_add(bytes) <= stats.limit_bytes;
+        if !fits(&stats) {
+            stats.waiting += 1;
+            stats.queued_total += 1;
+            while !fits(&stats) {
+                stats = self.released.wait(stats).unwrap_or_else(|e| e.into_inner());
+            }
+            stats.waiting -= 1;
+        }
+        stats.in_flight_bytes = stats.in_flight_bytes.saturating_add(bytes);
+        stats.active += 1;
+        stats.peak_bytes = stats.peak_bytes.max(stats.in_flight_bytes);
+        Reservation { budget: self, bytes }
+    }
+
+    /// Changes the cap; waiting decodes are checked again against it.
+    pub fn set_limit(&self, limit_bytes: u64) {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner()).limit_bytes = limit_bytes;
+        self.released.notify_all();
+    }
+
+    /// Current reservations and counters.
+    pub fn stats(&self) -> MemoryStats {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+static BUDGET: MemoryBudget = MemoryBudget::new(DEFAULT_LIMIT_MB * 1024 * 1024);
+
+/// Reserves room for decoding a `width` x `height` image in the app budget.
+pub fn reserve_decode(width: u32, height: u32) -> Reservation<'static> {
+    BUDGET.reserve(decoded_bytes(width, height))
+}
+
+/// Reserves room for decoding the encoded image in `data`, when its size
+/// can be read from the header.
+pub fn reserve_blob(data: &[u8]) -> Option<Reservation<'static>> {
+    imagesize::blob_size(data)
+        .ok()
+        .map(|size| reserve_decode(size.width as u32, size.height as u32))
+}
+
+/// Reserves room for decoding the image file at `path`, when its size can
+/// be read from the header.
+pub fn reserve_file(path: &Path) -> Option<Reservation<'static>> {
+    imagesize::size(path)
+        .ok()
+        .map(|size| reserve_decode(size.width as u32, size.height as u32))
+}
+
+/// Sets the app budget from the setting, in MB (0 = default).
+pub fn set_limit_mb(limit_mb: u64) {
+    let limit_mb = if limit_mb == 0 { DEFAULT_LIMIT_MB } else { limit_mb };
+    BUDGET.set_limit(limit_mb.saturating_mul(1024 * 1024));
+}
+
+/// Current state of the app budget.
+pub fn stats() -> MemoryStats {
+    BUDGET.stats()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_reservations_are_accounted_and_released() {
+        let budget = MemoryBudget::new(100);
+        let a = budget.reserve(40);
+        let b = budget.reserve(60);
+        assert_eq!(budget.stats().in_flight_bytes, 100);
+        assert_eq!(budget.stats().active, 2);
+        drop(a);
+        drop(b);
+
+        let stats = budget.stats();
+        assert_eq!((stats.in_flight_bytes, stats.active, stats.peak_bytes), (0, 0, 100));
+
+        // Larger than the whole cap: admitted when nothing else is in flight
+        let huge = budget.reserve(500);
+        assert_eq!(budget.stats().in_flight_bytes, 500);
+        drop(huge);
+    }
+
+    #[test]
+    fn test_reserve_waits_for_room() {
+        let budget = Arc::new(MemoryBudget::new(100));
+        let held = budget.reserve(80);
+
+        let waiter = {
+            let budget = budget.clone();
+            std::thread::spawn(move || {
+                let _reservation = budget.reserve(50);
+                budget.stats().in_flight_bytes
+            })
+        };
+        while budget.stats().waiting == 0 {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(budget.stats().in_flight_bytes, 80, "the second decode must wait");
+
+        drop(held);
+        assert_eq!(waiter.join().unwrap(), 50);
+        assert_eq!(budget.stats().queued_total, 1);
+        assert_eq!(budget.stats().peak_bytes, 80);
+    }
+
+    #[test]
+    fn test_decoded_bytes() {
+        assert_eq!(decoded_bytes(10_000, 10_000), 800_000_000);
+        assert_eq!(decoded_bytes(u32::MAX, u32::MAX), u64::MAX);
+    }
+}
@@ -11,10 +11,30 @@ pub mod icon;
 pub mod svg;
 pub mod font;
 pub mod model;
+pub mod model_deps;
 pub mod commands;
 pub mod worker;
 pub mod priority;
 pub mod raw;
+pub mod variants;
+pub mod isolated;
+pub mod pages;
+pub mod psd_layers;
+pub mod system;
+pub mod matte;
+pub mod icc;
+pub mod memory;
+pub mod compare;
+pub mod histogram;
+pub mod upgrade;
+pub mod perceptual;
+
+/// Camera RAW extensions, whose thumbnails come from the embedded preview.
+pub const RAW_EXTENSIONS: &[&str] = &[
+    "cr2", "cr3", "crw", "nef", "nrw", "arw", "srf", "sr2", "dng", "raf", "orf", "rw2", "pef", "erf",
+    "3fr", "fff", "dcr", "k25", "kdc", "dc2", "kc2", "srw", "x3f", "iiq", "cap", "mos", "rwl", "mrw", "mdc",
+    "cine", "bay", "cs1", "sti", "qtk", "pxn", "bmq", "rwz", "rdc", "raw", "mef",
+];
 
 /// Determines the best strategy for generating a thumbnail based on file detection.
 ///
@@ -51,6 +71,8 @@ pub fn get_thumbnail_strategy(path: &Path) -> ThumbnailStrategy {
 /// * `input_path` - Path to the source file.
 /// * `output_path` - Path where the resulting WebP thumbnail will be saved.
 /// * `size_px` - The target maximum dimension (width or height) in pixels.
+/// * `has_cover_art` - Whether the media probe found an embedded cover picture,
+///   rendered instead of a frame.
 ///
 /// Returns
 ///
@@ -61,21 +83,24 @@ pub fn generate_thumbnail<R: tauri::Runtime>(
     thumbnails_dir: &Path,
     hashed_filename: &str,
     size_px: u32,
+    has_cover_art: bool,
 ) -> Result<String, Box<dyn std::error::Error>> {
     let output_path = thumbnails_dir.join(hashed_filename);
 
     // OPTIMIZATION: Open file handle ONCE here to avoid re-opening in detection and native generation
     let mut open_file = std::fs::File::open(input_path).ok();
 
-    let (strategy, is_video) = if let Some(ref mut file) = open_file {
+    let (strategy, media_type) = if let Some(ref mut file) = open_file {
         FileFormat::detect_header(file, input_path)
-            .map(|f| (f.strategy.clone(), f.type_category == crate::formats::MediaType::Video))
-            .unwrap_or_else(|| (get_thumbnail_strategy(input_path), false))
+            .map(|f| (f.strategy.clone(), Some(f.type_category.clone())))
+            .unwrap_or_else(|| (get_thumbnail_strategy(input_path), None))
     } else {
         FileFormat::detect(input_path)
-            .map(|f| (f.strategy.clone(), f.type_category == crate::formats::MediaType::Video))
-            .unwrap_or_else(|| (ThumbnailStrategy::Icon, false))
+            .map(|f| (f.strategy.clone(), Some(f.type_category.clone())))
+            .unwrap_or_else(|| (ThumbnailStrategy::Icon, None))
     };
+    let is_video = media_type == Some(crate::formats::MediaType::Video);
+    let is_audio = media_type == Some(crate::formats::MediaType::Audio);
 
     let start = std::time::Instant::now();
 
@@ -86,13 +111,24 @@ pub fn generate_thumbnail<R: tauri::Runtime>(
     let is_special_project = ["afphoto", "afdesign", "afpub", "clip", "xmind", "xcf", "aseprite", "ase", "mdp", "sketch", "fig", "sai", "sai2"].contains(&ext.as_str());
 
     // Explicitly exclude RAW formats from FFmpeg priority
-    let is_raw_format = matches!(strategy, ThumbnailStrategy::Raw) || [
-        "cr2", "cr3", "crw", "nef", "nrw", "arw", "srf", "sr2", "dng", "raf", "orf", "rw2", "pef", "erf",
-        "3fr", "fff", "dcr", "k25", "kdc", "dc2", "kc2", "srw", "x3f", "iiq", "cap", "mos", "rwl", "mrw", "mdc",
-        "cine", "bay", "cs1", "sti", "qtk", "pxn", "bmq", "rwz", "rdc", "raw", "mef"
-    ].contains(&ext.as_str());
+    let is_raw_format = matches!(strategy, ThumbnailStrategy::Raw) || RAW_EXTENSIONS.contains(&ext.as_str());
+
+    // Embedded cover art (music videos, M4B audiobooks) beats a random frame or the file icon
+    if ffmpeg_available && has_cover_art && (is_video || is_audio) {
+        if crate::media::ffmpeg::extract_cover_art(app_handle, input_path, &output_path, size_px).is_ok() {
+            let elapsed = start.elapsed();
+            println!("THUMB (Cover Art): SUCCESS | {:?} | {:?}", elapsed, input_path.file_name().unwrap_or_default());
+            return Ok(hashed_filename.to_string());
+        }
+    }
+
+    // Camera RAW footage (BRAW, R3D) can't be decoded, go straight to the recorded proxy
+    let is_camera_raw = is_video && crate::media::camera_raw::is_camera_raw_video(input_path);
 
-    if ffmpeg_available && !is_special_project && !is_raw_format && matches!(strategy, ThumbnailStrategy::Ffmpeg | ThumbnailStrategy::NativeImage | ThumbnailStrategy::NativeExtractor) {
+    // AI/EPS go through PDFium first; FFmpeg only renders them with a Ghostscript-enabled build
+    let is_vector_document = extractors::is_vector_document(input_path);
+
+    if ffmpeg_available && !is_camera_raw && !is_special_project && !is_raw_format && !is_vector_document && matches!(strategy, ThumbnailStrategy::Ffmpeg | ThumbnailStrategy::NativeImage | ThumbnailStrategy::NativeExtractor) {
          if let Ok(_) = crate::media::ffmpeg::generate_thumbnail_ffmpeg_full(app_handle, input_path, &output_path, size_px, is_video) {
              let elapsed = start.elapsed();
              println!("THUMB (FFmpeg Priority): SUCCESS | {:?} | {:?}", elapsed, input_path.file_name().unwrap_or_default());
@@ -101,21 +137,37 @@ pub fn generate_thumbnail<R: tauri::Runtime>(
          println!("THUMB (FFmpeg Priority): FAILED - Falling back to Native");
     }
 
-    let result = match strategy {
-        ThumbnailStrategy::Ffmpeg => {
-            println!("THUMB: Ffmpeg Strategy Final Failure for {:?}", input_path.file_name());
-            Err("FFmpeg strategy failed or unavailable".into())
-        },
-        ThumbnailStrategy::NativeImage => native::generate_thumbnail_fast(input_path, &output_path, size_px, open_file.as_mut()).map(|_| hashed_filename.to_string()),
-        ThumbnailStrategy::ZipPreview => archive::generate_thumbnail_zip_preview(input_path, &output_path, size_px).map(|_| hashed_filename.to_string()),
-        ThumbnailStrategy::NativeExtractor => extractors::generate_thumbnail_extracted(app_handle, input_path, &output_path, size_px).map(|_| hashed_filename.to_string()),
-        ThumbnailStrategy::Raw => raw::generate_raw_thumbnail(input_path, &output_path, size_px).map(|_| hashed_filename.to_string()),
-        ThumbnailStrategy::Webview => svg::generate_thumbnail_svg(input_path, &output_path, size_px).map(|_| hashed_filename.to_string()),
-        ThumbnailStrategy::Font => font::generate_font_thumbnail(input_path, &output_path, size_px).map(|_| hashed_filename.to_string()),
-        ThumbnailStrategy::Model3D => model::generate_model_preview(input_path, thumbnails_dir, hashed_filename, size_px),
-        ThumbnailStrategy::Icon | ThumbnailStrategy::None => {
-            icon::get_or_generate_icon(input_path, thumbnails_dir, size_px)
-        },
+    // Undecodable video (camera RAW, ProRes RAW on older FFmpeg): use a proxy recorded next to it
+    if ffmpeg_available && is_video {
+        if let Some(proxy) = crate::media::camera_raw::find_proxy(input_path) {
+            if crate::media::ffmpeg::generate_thumbnail_ffmpeg_full(app_handle, &proxy, &output_path, size_px, true).is_ok() {
+                let elapsed = start.elapsed();
+                println!("THUMB (Proxy): SUCCESS | {:?} | {:?}", elapsed, input_path.file_name().unwrap_or_default());
+                return Ok(hashed_filename.to_string());
+            }
+        }
+    }
+
+    let result = if isolated::is_enabled() && isolated::is_risky(&strategy) {
+        // Decoders that may crash run in a helper process; failures fall back to the icon below
+        isolated::generate_isolated(app_handle, input_path, &output_path, size_px).map(|_| hashed_filename.to_string())
+    } else {
+        match strategy {
+            ThumbnailStrategy::Ffmpeg => {
+                println!("THUMB: Ffmpeg Strategy Final Failure for {:?}", input_path.file_name());
+                Err("FFmpeg strategy failed or unavailable".into())
+            },
+            ThumbnailStrategy::NativeImage => native::generate_thumbnail_fast(input_path, &output_path, size_px, open_file.as_mut()).map(|_| hashed_filename.to_string()),
+            ThumbnailStrategy::ZipPreview => archive::generate_thumbnail_zip_preview(input_path, &output_path, size_px).map(|_| hashed_filename.to_string()),
+            ThumbnailStrategy::NativeExtractor => extractors::generate_thumbnail_extracted(app_handle, input_path, &output_path, size_px).map(|_| hashed_filename.to_string()),
+            ThumbnailStrategy::Raw => raw::generate_raw_thumbnail(input_path, &output_path, size_px).map(|_| hashed_filename.to_string()),
+            ThumbnailStrategy::Webview => svg::generate_thumbnail_svg(input_path, &output_path, size_px).map(|_| hashed_filename.to_string()),
+            ThumbnailStrategy::Font => font::generate_font_thumbnail(input_path, &output_path, size_px).map(|_| hashed_filename.to_string()),
+            ThumbnailStrategy::Model3D => model::generate_model_preview(input_path, thumbnails_dir, hashed_filename, size_px),
+            ThumbnailStrategy::Icon | ThumbnailStrategy::None => {
+                icon::get_or_generate_icon(input_path, thumbnails_dir, size_px)
+            },
+        }
     };
 
     let final_result = match result {
@@ -135,7 +187,69 @@ pub fn generate_thumbnail<R: tauri::Runtime>(
     final_result
 }
 
-pub fn get_thumbnail_filename(image_path: &str) -> String {
+/// Size of the grid thumbnails rendered by the worker.
+pub const GRID_THUMBNAIL_SIZE: u32 = 300;
+
+/// Version of the thumbnail generators. Bump it when their output changes
+/// (new decoder, different resize filter, ...) so every cached thumbnail gets
+/// a new name and is rendered again.
+pub const THUMBNAIL_VERSION: u32 = 1;
+
+/// Cache filename of a thumbnail:
+/// `{content key}-{modified}-{size}-v{version}{matte}.webp`.
+///
+/// Keying on the content rather than the path means an edited file gets a
+/// new thumbnail instead of the stale one, and two sizes of the same file
+/// never collide. The content key covers the size and both ends of the file;
+/// `modified`, its modification time in seconds, catches edits that leave the
+/// ends alone. Copies that kept their modification time share one file.
+/// The matte tag (see [`matte::Matte::cache_tag`]) keeps thumbnails
+/// flattened onto another matte from being reused.
+pub fn thumbnail_filename(content_key: &str, modified: u64, size_px: u32) -> String {
+    format!(
+        "{}-{:x}-{}-v{}{}.webp",
+        content_key,
+        modified,
+        size_px,
+        THUMBNAIL_VERSION,
+        matte::current().cache_tag()
+    )
+}
+
+/// Name of a thumbnail rendered from embedded cover art, so it doesn't reuse
+/// the one rendered from a frame before the probe found the cover.
+pub fn cover_art_filename(thumbnail: &str) -> String {
+    match thumbnail.strip_suffix(".webp") {
+        Some(stem) => format!("{}-cover.webp", stem),
+        None => format!("{}-cover", thumbnail),
+    }
+}
+
+/// Content key and modification time (seconds since the epoch) of `path`,
+/// what its thumbnail name is made of.
+pub fn thumbnail_key(path: &Path) -> std::io::Result<(String, u64)> {
+    let modified = std::fs::metadata(path)?
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    Ok((crate::peer::content_key(path)?, modified))
+}
+
+/// Cache filename of the thumbnail of `image_path` at `size_px`.
+///
+/// Falls back to the legacy path-based name when the file can't be read to
+/// compute its content key.
+pub fn get_thumbnail_filename(image_path: &str, size_px: u32) -> String {
+    match thumbnail_key(&crate::paths::from_db(image_path)) {
+        Ok((key, modified)) => thumbnail_filename(&key, modified, size_px),
+        Err(_) => legacy_thumbnail_filename(image_path),
+    }
+}
+
+/// Name given to thumbnails before they were keyed on content: a hash of the
+/// path alone.
+pub fn legacy_thumbnail_filename(image_path: &str) -> String {
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
 
@@ -143,3 +257,70 @@ pub fn get_thumbnail_filename(image_path: &str) -> String {
     image_path.hash(&mut hasher);
     format!("{:x}.webp", hasher.finish())
 }
+
+/// Whether `filename` is a path-keyed thumbnail still waiting for migration.
+/// Shared icons (`extensions/...`) are never migrated.
+pub fn is_legacy_thumbnail_filename(filename: &str) -> bool {
+    filename
+        .strip_suffix(".webp")
+        .is_some_and(|stem| !stem.is_empty() && stem.len() <= 16 && stem.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thumbnail_filenames() {
+        let key = "ab".repeat(32);
+        assert_eq!(thumbnail_filename(&key, 255, 300), format!("{}-ff-300-v{}.webp", key, THUMBNAIL_VERSION));
+        assert_ne!(thumbnail_filename(&key, 255, 300), thumbnail_filename(&key, 255, 1024));
+        assert_ne!(thumbnail_filename(&key, 255, 300), thumbnail_filename(&key, 256, 300));
+        assert!(!is_legacy_thumbnail_filename(&thumbnail_filename(&key, 255, 300)));
+        assert_eq!(cover_art_filename("ab-ff-300-v1.webp"), "ab-ff-300-v1-cover.webp");
+
+        assert!(is_legacy_thumbnail_filename(&legacy_thumbnail_filename("/photos/a.jpg")));
+        assert!(!is_legacy_thumbnail_filename("extensions/icon_jpg_300.webp"));
+        assert!(!is_legacy_thumbnail_filename("icon_jpg_300.webp"));
+    }
+
+    #[test]
+    fn test_thumbnail_filename_follows_content() {
+        let dir = std::env::temp_dir().join(format!("mundam-thumbname-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.jpg");
+        let b = dir.join("b.jpg");
+        std::fs::write(&a, b"first version").unwrap();
+        std::fs::write(&b, b"first version").unwrap();
+        let (a, b) = (a.to_string_lossy().to_string(), b.to_string_lossy().to_string());
+
+        let touch = |path: &str, secs: u64| {
+            let file = std::fs::File::options().write(true).open(path).unwrap();
+            file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs)).unwrap();
+        };
+        touch(&a, 1_000);
+        touch(&b, 1_000);
+
+        let before = get_thumbnail_filename(&a, 300);
+        assert_eq!(before, get_thumbnail_filename(&b, 300), "duplicates share a thumbnail");
+
+        std::fs::write(&a, b"second version").unwrap();
+        touch(&a, 1_000);
+        assert_ne!(get_thumbnail_filename(&a, 300), before, "an edit must change the name");
+
+        // Same size and same ends, edited in the middle
+        let mut data = vec![0u8; 256 * 1024];
+        std::fs::write(&b, &data).unwrap();
+        touch(&b, 1_000);
+        let unedited = get_thumbnail_filename(&b, 300);
+        data[128 * 1024] = 1;
+        std::fs::write(&b, &data).unwrap();
+        touch(&b, 2_000);
+        assert_ne!(get_thumbnail_filename(&b, 300), unedited, "a middle edit must change the name");
+
+        let missing = dir.join("missing.jpg").to_string_lossy().to_string();
+        assert_eq!(get_thumbnail_filename(&missing, 300), legacy_thumbnail_filename(&missing));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,6 +1,12 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use crate::thumbnails::icon;
+use std::time::{Duration, SystemTime};
+use crate::storage::cache;
+use crate::streaming::process_manager::{self, JobKind};
+use crate::thumbnails::{icon, model_deps};
 // use tauri::Manager;
 
 /// Entry point for 3D model thumbnail generation.
@@ -8,7 +14,7 @@ use crate::thumbnails::icon;
 /// This pipeline follows the "Universal Pipeline" strategy:
 /// 1. **Ingest & Convert**: Uses `assimp` CLI (bundled or system) to convert the proprietary model (FBX, OBJ, BLEND) 
 ///    into a standardized **Binary GLTF (.glb)**.
-/// 2. **Cache**: The .glb is saved in the thumbnails directory alongside the image thumbnail.
+/// 2. **Cache**: The .glb is saved in the viewer's model cache (see [`viewer_glb`]), so opening the model reuses it.
 /// 3. **Thumbnail**: Currently generates a generic file type icon for the grid view.
 /// 
 /// # Returns
@@ -20,28 +26,11 @@ pub fn generate_model_preview(
     size_px: u32,
 ) -> Result<String, Box<dyn std::error::Error>> {
     
-    // 1. Derive GLB cache path: "{hash}.glb"
-    let stem = Path::new(hashed_filename)
-        .file_stem()
-        .ok_or("Invalid filename structure")?
-        .to_str()
-        .ok_or("Invalid filename encoding")?;
-        
-    let glb_filename = format!("{}.glb", stem);
-    let glb_path = thumbnails_dir.join(&glb_filename);
-
-    // 2. Convert to GLB if cache doesn't exist
-    if !glb_path.exists() {
-        // Find Assimp binary (Bundled -> System)
-        let assimp_bin = get_assimp_path_best_effort();
-        
-        match convert_to_glb(&assimp_bin, input_path, &glb_path) {
-            Ok(_) => {
-                // Success
-            },
-            Err(e) => {
-                eprintln!("Model3D Warning: Could not create 3D Preview (GLB) for {:?}. Reason: {}", input_path.file_name(), e);
-            }
+    // 1-2. Convert to GLB into the viewer's cache, unless it is there already
+    if needs_conversion(input_path) {
+        let cache_dir = thumbnails_dir.parent().unwrap_or(thumbnails_dir).join(CACHE_DIR);
+        if let Err(e) = viewer_glb(&cache_dir, input_path) {
+            eprintln!("Model3D Warning: Could not create 3D Preview (GLB) for {:?}. Reason: {}", input_path.file_name(), e);
         }
     }
 
@@ -77,6 +66,78 @@ pub fn generate_model_preview(
     Ok(hashed_filename.to_string())
 }
 
+/// Formats the 3D viewer loads as they are; the rest are converted to GLB.
+const VIEWER_NATIVE: &[&str] = &["gltf", "glb"];
+
+/// Directory of the app data, next to `thumbnails`, holding the viewer's
+/// GLB conversions.
+pub const CACHE_DIR: &str = "models";
+
+/// Size the model cache is trimmed to after a conversion, least recently
+/// viewed first.
+const CACHE_MAX_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// How long Assimp may take on one model before it is killed.
+const CONVERSION_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Whether the viewer needs `path` converted to glTF first.
+pub fn needs_conversion(path: &Path) -> bool {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    !VIEWER_NATIVE.contains(&ext.as_str())
+}
+
+/// GLB conversion of a model for the 3D viewer, cached in `cache_dir` by
+/// path and modification time of the model and the files it references, so
+/// it's converted again once a missing texture turns up. Blocks for as long
+/// as the conversion takes; the thumbnail worker converts ahead of time.
+pub fn viewer_glb(cache_dir: &Path, source: &Path) -> Result<PathBuf, String> {
+    let cached = cache_dir.join(format!("{}.glb", cache_key(source)));
+    if fs::metadata(&cached).is_ok_and(|metadata| metadata.len() > 0) {
+        cache::touch(&cached);
+        return Ok(cached);
+    }
+    fs::create_dir_all(cache_dir).map_err(|e| format!("Failed to create model cache: {}", e))?;
+
+    // Converted aside, so a concurrent request never serves half a file
+    let partial = cache_dir.join(format!("{}.glb.part", uuid::Uuid::new_v4().simple()));
+    let result = export_glb(&get_assimp_path_best_effort(), source, &partial)
+        .and_then(|_| fs::rename(&partial, &cached).map_err(|e| format!("Failed to cache conversion: {}", e)));
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    let cached = result.map(|_| cached)?;
+    trim_cache(cache_dir, &cached);
+    Ok(cached)
+}
+
+/// Deletes the least recently viewed conversions beyond [`CACHE_MAX_BYTES`],
+/// sparing `keep`.
+fn trim_cache(cache_dir: &Path, keep: &Path) {
+    let files = cache::inventory(cache_dir).into_iter().filter(|(path, _, _)| path != keep).collect();
+    let kept = fs::metadata(keep).map_or(0, |metadata| metadata.len());
+    for path in cache::pick_evictions(files, CACHE_MAX_BYTES.saturating_sub(kept)) {
+        if let Err(e) = fs::remove_file(&path) {
+            eprintln!("WARN: Failed to evict {:?} from the model cache: {}", path, e);
+        }
+    }
+}
+
+fn cache_key(source: &Path) -> String {
+    let mut hasher = DefaultHasher::new();
+    let dependencies = model_deps::dependencies(source);
+    let files = [source.to_path_buf()].into_iter().chain(dependencies.into_iter().filter_map(|d| d.resolved));
+    for file in files {
+        file.to_string_lossy().hash(&mut hasher);
+        if let Ok(metadata) = fs::metadata(&file) {
+            metadata.len().hash(&mut hasher);
+            if let Ok(modified) = metadata.modified() {
+                modified.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs().hash(&mut hasher);
+            }
+        }
+    }
+    format!("{:016x}", hasher.finish())
+}
+
 /// Helper to find Assimp path without AppHandle (Best Effort)
 /// Replicates the logic from ffmpeg.rs but for assimp
 fn get_assimp_path_best_effort() -> PathBuf {
@@ -126,16 +187,29 @@ fn get_assimp_path_best_effort() -> PathBuf {
     PathBuf::from("assimp")
 }
 
-/// Wraps the `assimp export` CLI command.
+/// Converts a model to GLB with its textures embedded, found next to the
+/// model even when it names them by paths from another machine.
+fn export_glb(binary: &Path, input: &Path, output: &Path) -> Result<(), String> {
+    convert_to_glb(binary, input, output)?;
+    if let Err(e) = model_deps::embed_textures(output, input) {
+        eprintln!("WARN: Could not embed the textures of {:?}: {}", input.file_name(), e);
+    }
+    Ok(())
+}
+
+/// Wraps the `assimp export` CLI command, supervised like the decoders so a
+/// model that hangs Assimp is killed after [`CONVERSION_TIMEOUT`].
 fn convert_to_glb(binary: &Path, input: &Path, output: &Path) -> Result<(), String> {
-    // Command: assimp export <input> <output>
+    // Command: assimp export <input> <output> -fglb2
+    // Asked for by id: the `.glb` extension alone may pick the glTF 1.0 exporter
     let output_str = output.to_str().ok_or("Invalid output path")?;
     
-    let result = Command::new(binary)
-        .arg("export")
+    let mut cmd = Command::new(binary);
+    cmd.arg("export")
         .arg(input)
         .arg(output_str)
-        .output();
+        .arg("-fglb2");
+    let result = process_manager::run_supervised(cmd, JobKind::Decoder, &input.to_string_lossy(), CONVERSION_TIMEOUT);
         
     match result {
         Ok(output) => {
@@ -151,3 +225,32 @@ fn convert_to_glb(binary: &Path, input: &Path, output: &Path) -> Result<(), Stri
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_viewer_glb_is_cached_per_version() {
+        assert!(needs_conversion(Path::new("/models/robot.FBX")));
+        assert!(needs_conversion(Path::new("/models/room.3ds")));
+        assert!(!needs_conversion(Path::new("/models/robot.glb")));
+        assert!(!needs_conversion(Path::new("/models/scene.GLTF")));
+
+        let dir = std::env::temp_dir().join(format!("mundam-models-{}", std::process::id()));
+        let source = dir.join("robot.fbx");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&source, b"Kaydara FBX Binary").unwrap();
+
+        // A conversion already cached is served without running Assimp
+        let key = cache_key(&source);
+        let cached = dir.join("cache").join(format!("{}.glb", key));
+        fs::create_dir_all(cached.parent().unwrap()).unwrap();
+        fs::write(&cached, b"glTF").unwrap();
+        assert_eq!(viewer_glb(&dir.join("cache"), &source).unwrap(), cached);
+
+        fs::write(&source, b"Kaydara FBX Binary, edited").unwrap();
+        assert_ne!(cache_key(&source), key);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
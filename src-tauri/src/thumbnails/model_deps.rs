@@ -0,0 +1,438 @@
+//! Materials and textures 3D models reference.
+//!
+//! OBJ files name their materials in `.mtl` libraries, and materials and FBX
+//! files name texture images, often by a path from the author's machine.
+//! References are looked up next to the model the way 3D tools do, and the
+//! textures found are embedded in the GLB converted for previews, which
+//! otherwise points at images the viewer can't reach and renders gray.
+//!
+//! The references read from a model are kept while its size and
+//! modification time stay the same, since a large FBX takes a while to scan
+//! and the viewer asks on every open. They're resolved again each time, so
+//! a texture copied next to the model is picked up.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Texture statements of `.mtl` files.
+const MTL_TEXTURES: &[&str] = &[
+    "map_Ka", "map_Kd", "map_Ks", "map_Ke", "map_Ns", "map_d", "map_bump", "map_Bump", "bump", "disp", "decal",
+    "norm", "refl", "map_Pr", "map_Pm", "map_Ps",
+];
+
+/// Folders next to a model where textures are commonly kept.
+const TEXTURE_FOLDERS: &[&str] = &["textures", "Textures", "tex", "maps"];
+
+/// Node names of texture paths in FBX files.
+const FBX_TEXTURE_NODES: &[&str] = &["RelativeFilename", "FileName", "Filename"];
+
+/// Models whose references are kept; the cache starts over beyond that.
+const CACHED_MODELS: usize = 256;
+
+/// What a model references.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DependencyKind {
+    /// An OBJ material library (`.mtl`).
+    Material,
+    /// An image a material or FBX file maps onto the model.
+    Texture,
+}
+
+/// Size and modification time a model's cached references were read at.
+type Stamp = (u64, Option<SystemTime>);
+
+fn cached() -> &'static Mutex<HashMap<PathBuf, (Stamp, Vec<String>)>> {
+    static CACHED: OnceLock<Mutex<HashMap<PathBuf, (Stamp, Vec<String>)>>> = OnceLock::new();
+    CACHED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// References `parse` reads from `model`, from the cache while the model is
+/// unchanged.
+fn references(model: &Path, parse: impl FnOnce() -> Option<Vec<String>>) -> Vec<String> {
+    let Ok(metadata) = fs::metadata(model) else { return Vec::new() };
+    let stamp = (metadata.len(), metadata.modified().ok());
+    if let Some((cached_stamp, references)) = cached().lock().unwrap_or_else(|e| e.into_inner()).get(model) {
+        if *cached_stamp == stamp {
+            return references.clone();
+        }
+    }
+    let Some(references) = parse() else { return Vec::new() };
+    let mut cache = cached().lock().unwrap_or_else(|e| e.into_inner());
+    if cache.len() >= CACHED_MODELS {
+        cache.clear();
+    }
+    cache.insert(model.to_path_buf(), (stamp, references.clone()));
+    references
+}
+
+/// Little-endian `u32` at `offset`, if `data` is long enough.
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// A file a model references.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelDependency {
+    pub kind: DependencyKind,
+    /// The path as written in the file.
+    pub reference: String,
+    /// Where it was found, if anywhere.
+    pub resolved: Option<PathBuf>,
+}
+
+/// Finds the file `reference` names, written relative to one of `dirs` or as
+/// an absolute path, possibly from another machine: tried as written, then
+/// by file name in each of `dirs` and their texture folders.
+pub fn resolve_reference(dirs: &[&Path], reference: &str) -> Option<PathBuf> {
+    let normalized = reference.trim().trim_matches('"').replace('\\', "/");
+    let path = Path::new(&normalized);
+    let file_name = path.file_name()?;
+
+    let mut candidates = Vec::new();
+    if path.is_absolute() {
+        candidates.push(path.to_path_buf());
+    }
+    for dir in dirs {
+        if !path.is_absolute() {
+            candidates.push(dir.join(path));
+        }
+        candidates.push(dir.join(file_name));
+        candidates.extend(TEXTURE_FOLDERS.iter().map(|folder| dir.join(folder).join(file_name)));
+    }
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+/// Material libraries an OBJ file loads.
+fn obj_materials(reader: impl BufRead) -> Vec<String> {
+    reader
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| line.trim().strip_prefix("mtllib ").map(|names| names.to_string()))
+        .flat_map(|names| names.split_whitespace().map(String::from).collect::<Vec<_>>())
+        .collect()
+}
+
+/// Textures a `.mtl` file uses.
+fn mtl_textures(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| {
+            let (statement, args) = line.trim().split_once(char::is_whitespace)?;
+            MTL_TEXTURES.contains(&statement).then(|| texture_file(args)).flatten()
+        })
+        .collect()
+}
+
+/// The file name of a texture statement, after its options (`-s 1 1 1`,
+/// `-bm 0.5`, `-clamp on`, ...).
+fn texture_file(args: &str) -> Option<String> {
+    let tokens: Vec<&str> = args.split_whitespace().collect();
+    let mut i = 0;
+    while i < tokens.len() && tokens[i].starts_with('-') {
+        let option = tokens[i];
+        i += 1;
+        if option == "-imfchan" || option == "-type" {
+            i += 1;
+            continue;
+        }
+        while i < tokens.len() && (tokens[i].parse::<f64>().is_ok() || matches!(tokens[i], "on" | "off")) {
+            i += 1;
+        }
+    }
+    let name = tokens.get(i..)?.join(" ");
+    (!name.is_empty()).then_some(name)
+}
+
+/// Texture paths of an FBX file, binary or ASCII.
+fn fbx_textures(data: &[u8]) -> Vec<String> {
+    let mut textures = Vec::new();
+    if data.starts_with(b"Kaydara FBX Binary") {
+        // A node record ends with its name, then its first property: a
+        // string, as `S` and a 32-bit length
+        for name in FBX_TEXTURE_NODES {
+            let mut marker = vec![name.len() as u8];
+            marker.extend_from_slice(name.as_bytes());
+            marker.push(b'S');
+            let mut from = 0;
+            while let Some(at) = find(&data[from..], &marker) {
+                let start = from + at + marker.len();
+                from = start;
+                let Some(length) = read_u32(data, start) else { break };
+                let length = length as usize;
+                if let Some(text) = data.get(start + 4..start + 4 + length) {
+                    textures.push(String::from_utf8_lossy(text).into_owned());
+                }
+            }
+        }
+    } else {
+        for line in String::from_utf8_lossy(data).lines() {
+            let Some((name, value)) = line.trim().split_once(':') else { continue };
+            if FBX_TEXTURE_NODES.contains(&name.trim()) {
+                textures.push(value.trim().trim_matches('"').to_string());
+            }
+        }
+    }
+    textures.retain(|texture| !texture.is_empty());
+    textures
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// Materials and textures `model` references, each once, in the order
+/// found. Only OBJ and FBX files are read.
+pub fn dependencies(model: &Path) -> Vec<ModelDependency> {
+    let dir = model.parent().unwrap_or(Path::new("."));
+    let ext = model.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let mut found = Vec::new();
+
+    match ext.as_str() {
+        "obj" => {
+            let materials = references(model, || fs::File::open(model).ok().map(|file| obj_materials(BufReader::new(file))));
+            for material in materials {
+                let resolved = resolve_reference(&[dir], &material);
+                let textures = resolved
+                    .as_ref()
+                    .and_then(|mtl| fs::read(mtl).ok())
+                    .map(|text| mtl_textures(&String::from_utf8_lossy(&text)))
+                    .unwrap_or_default();
+                let mtl_dir = resolved.as_ref().and_then(|mtl| mtl.parent().map(Path::to_path_buf));
+                found.push(ModelDependency { kind: DependencyKind::Material, reference: material, resolved });
+
+                let dirs: Vec<&Path> = mtl_dir.as_deref().into_iter().chain([dir]).collect();
+                for texture in textures {
+                    let resolved = resolve_reference(&dirs, &texture);
+                    found.push(ModelDependency { kind: DependencyKind::Texture, reference: texture, resolved });
+                }
+            }
+        }
+        "fbx" => {
+            for texture in references(model, || fs::read(model).ok().map(|data| fbx_textures(&data))) {
+                let resolved = resolve_reference(&[dir], &texture);
+                found.push(ModelDependency { kind: DependencyKind::Texture, reference: texture, resolved });
+            }
+        }
+        _ => {}
+    }
+    dedup(found)
+}
+
+/// Keeps one dependency per file name (FBX names each texture by a relative
+/// and an absolute path), preferring one that was found.
+fn dedup(dependencies: Vec<ModelDependency>) -> Vec<ModelDependency> {
+    let mut kept: Vec<ModelDependency> = Vec::new();
+    let mut by_name: HashMap<(String, bool), usize> = HashMap::new();
+    for dependency in dependencies {
+        let name = dependency.reference.replace('\\', "/");
+        let name = name.rsplit('/').next().unwrap_or_default().to_lowercase();
+        let key = (name, dependency.kind == DependencyKind::Material);
+        match by_name.get(&key) {
+            Some(&index) => {
+                if kept[index].resolved.is_none() && dependency.resolved.is_some() {
+                    kept[index] = dependency;
+                }
+            }
+            None => {
+                by_name.insert(key, kept.len());
+                kept.push(dependency);
+            }
+        }
+    }
+    kept
+}
+
+/// Texture contents for glTF: PNG and JPEG as they are, other images
+/// converted to PNG.
+fn texture_bytes(path: &Path) -> Option<(Vec<u8>, &'static str)> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    match ext.as_str() {
+        "png" => Some((fs::read(path).ok()?, "image/png")),
+        "jpg" | "jpeg" => Some((fs::read(path).ok()?, "image/jpeg")),
+        _ => {
+            let image = image::open(path).ok()?;
+            let mut png = Vec::new();
+            image.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png).ok()?;
+            Some((png, "image/png"))
+        }
+    }
+}
+
+fn split_glb(data: &[u8]) -> Result<(Value, Vec<u8>), String> {
+    if data.len() < 12 || &data[0..4] != b"glTF" {
+        return Err("Not a GLB file".to_string());
+    }
+    let mut json = None;
+    let mut bin = Vec::new();
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let Some(length) = read_u32(data, offset) else { break };
+        let length = length as usize;
+        let chunk = data.get(offset + 8..offset + 8 + length).ok_or("Truncated GLB chunk")?;
+        match &data[offset + 4..offset + 8] {
+            b"JSON" => json = Some(serde_json::from_slice(chunk).map_err(|e| format!("Invalid GLB JSON: {}", e))?),
+            b"BIN\0" => bin = chunk.to_vec(),
+            _ => {}
+        }
+        offset += 8 + length;
+    }
+    Ok((json.ok_or("GLB without JSON chunk")?, bin))
+}
+
+fn pad(bytes: &mut Vec<u8>, with: u8) {
+    while bytes.len() % 4 != 0 {
+        bytes.push(with);
+    }
+}
+
+fn join_glb(json: &Value, mut bin: Vec<u8>) -> Vec<u8> {
+    let mut json = serde_json::to_vec(json).unwrap_or_default();
+    pad(&mut json, b' ');
+    pad(&mut bin, 0);
+    let total = 12 + 8 + json.len() + if bin.is_empty() { 0 } else { 8 + bin.len() };
+
+    let mut glb = Vec::with_capacity(total);
+    glb.extend_from_slice(b"glTF");
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&(total as u32).to_le_bytes());
+    glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    glb.extend_from_slice(b"JSON");
+    glb.extend_from_slice(&json);
+    if !bin.is_empty() {
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&bin);
+    }
+    glb
+}
+
+/// Embeds the textures a GLB converted from `model` points at, resolved
+/// next to the model, so the GLB renders textured on its own. Returns how
+/// many were embedded.
+pub fn embed_textures(glb: &Path, model: &Path) -> Result<usize, String> {
+    let data = fs::read(glb).map_err(|e| format!("Failed to read GLB: {}", e))?;
+    let (mut json, mut bin) = split_glb(&data)?;
+    // The binary chunk only belongs to a first buffer without a URI
+    if json["buffers"].get(0).is_some_and(|buffer| buffer.get("uri").is_some()) {
+        return Ok(0);
+    }
+
+    let model_dir = model.parent().unwrap_or(Path::new("."));
+    let material_dirs: Vec<PathBuf> = dependencies(model)
+        .into_iter()
+        .filter(|dependency| dependency.kind == DependencyKind::Material)
+        .filter_map(|dependency| dependency.resolved?.parent().map(Path::to_path_buf))
+        .collect();
+    let dirs: Vec<&Path> = material_dirs.iter().map(PathBuf::as_path).chain([model_dir]).collect();
+
+    let image_count = json["images"].as_array().map_or(0, Vec::len);
+    let mut embedded = 0;
+    for index in 0..image_count {
+        let Some(uri) = json["images"][index]["uri"].as_str().filter(|uri| !uri.starts_with("data:")) else {
+            continue;
+        };
+        let reference = percent_decode_str(uri).decode_utf8_lossy().into_owned();
+        let Some((bytes, mime_type)) = resolve_reference(&dirs, &reference).and_then(|path| texture_bytes(&path)) else {
+            continue;
+        };
+
+        pad(&mut bin, 0);
+        let view = json!({ "buffer": 0, "byteOffset": bin.len(), "byteLength": bytes.len() });
+        bin.extend_from_slice(&bytes);
+        if !json["bufferViews"].is_array() {
+            json["bufferViews"] = json!([]);
+        }
+        let views = json["bufferViews"].as_array_mut().unwrap();
+        views.push(view);
+        let view_index = views.len() - 1;
+
+        let image = json["images"][index].as_object_mut().unwrap();
+        image.remove("uri");
+        image.insert("bufferView".to_string(), json!(view_index));
+        image.insert("mimeType".to_string(), json!(mime_type));
+        embedded += 1;
+    }
+    if embedded == 0 {
+        return Ok(0);
+    }
+
+    pad(&mut bin, 0);
+    match json["buffers"].as_array_mut() {
+        Some(buffers) if !buffers.is_empty() => buffers[0]["byteLength"] = json!(bin.len()),
+        _ => json["buffers"] = json!([{ "byteLength": bin.len() }]),
+    }
+    fs::write(glb, join_glb(&json, bin)).map_err(|e| format!("Failed to write GLB: {}", e))?;
+    Ok(embedded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references_are_parsed() {
+        let obj = "# Exported\nmtllib robot.mtl extra.mtl\nv 0 0 0\n";
+        assert_eq!(obj_materials(obj.as_bytes()), vec!["robot.mtl", "extra.mtl"]);
+
+        let mtl = "newmtl body\nKd 1 1 1\nmap_Kd -s 1 1 1 -clamp on textures/body diffuse.png\nbump -bm 0.5 body_n.tga\nmap_d -imfchan m mask.png\n";
+        assert_eq!(mtl_textures(mtl), vec!["textures/body diffuse.png", "body_n.tga", "mask.png"]);
+
+        let ascii = "Texture: 1, \"Texture::wood\" {\n\tFileName: \"C:\\Users\\art\\wood.png\"\n\tRelativeFilename: \"tex\\wood.png\"\n}";
+        assert_eq!(fbx_textures(ascii.as_bytes()), vec!["C:\\Users\\art\\wood.png", "tex\\wood.png"]);
+
+        let mut binary = b"Kaydara FBX Binary  \0".to_vec();
+        binary.push(16);
+        binary.extend_from_slice(b"RelativeFilenameS");
+        binary.extend_from_slice(&8u32.to_le_bytes());
+        binary.extend_from_slice(b"wood.jpg");
+        assert_eq!(fbx_textures(&binary), vec!["wood.jpg"]);
+    }
+
+    #[test]
+    fn test_textures_resolve_next_to_the_model_and_embed() {
+        let dir = std::env::temp_dir().join(format!("mundam-model-deps-{}", std::process::id()));
+        fs::create_dir_all(dir.join("textures")).unwrap();
+        let model = dir.join("robot.obj");
+        fs::write(&model, "mtllib robot.mtl\nmtllib missing.mtl\n").unwrap();
+        fs::write(dir.join("robot.mtl"), "map_Kd C:\\Work\\robot\\albedo.png\nmap_Ks gone.png\n").unwrap();
+        fs::write(dir.join("textures").join("albedo.png"), b"\x89PNG").unwrap();
+
+        let found = dependencies(&model);
+        let summary: Vec<(&str, bool)> = found.iter().map(|d| (d.reference.as_str(), d.resolved.is_some())).collect();
+        assert_eq!(
+            summary,
+            vec![("robot.mtl", true), ("C:\\Work\\robot\\albedo.png", true), ("gone.png", false), ("missing.mtl", false)]
+        );
+        // Read from the cache the second time, but resolved again
+        fs::write(dir.join("gone.png"), b"\x89PNG").unwrap();
+        assert!(dependencies(&model)[2].resolved.is_some());
+        fs::remove_file(dir.join("gone.png")).unwrap();
+
+        let glb = dir.join("robot.glb");
+        let gltf = json!({
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": 4 }],
+            "bufferViews": [{ "buffer": 0, "byteOffset": 0, "byteLength": 4 }],
+            "images": [{ "uri": "C:\\Work\\robot\\albedo.png" }, { "uri": "gone.png" }]
+        });
+        fs::write(&glb, join_glb(&gltf, vec![1, 2, 3, 4])).unwrap();
+        assert_eq!(embed_textures(&glb, &model).unwrap(), 1);
+
+        let (json, bin) = split_glb(&fs::read(&glb).unwrap()).unwrap();
+        assert_eq!(json["images"][0], json!({ "bufferView": 1, "mimeType": "image/png" }));
+        assert_eq!(json["images"][1]["uri"], "gone.png");
+        assert_eq!(json["bufferViews"][1]["byteOffset"], 4);
+        assert_eq!(&bin[4..8], b"\x89PNG");
+        assert_eq!(json["buffers"][0]["byteLength"], bin.len());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
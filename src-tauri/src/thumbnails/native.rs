@@ -1,7 +1,88 @@
+use std::cell::RefCell;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use fast_image_resize as fr;
 use zune_jpeg::JpegDecoder;
 
+/// Bilinear is much faster than the default Lanczos3, especially in debug builds.
+pub const THUMBNAIL_RESIZE_ALG: fr::ResizeAlg = fr::ResizeAlg::Convolution(fr::FilterType::Bilinear);
+
+/// Per-thread resize state, reused across thumbnails to avoid allocation churn.
+///
+/// The thumbnail worker runs on a fixed rayon pool, so each worker thread
+/// keeps one resizer and one destination buffer for the whole scan.
+struct EncoderState {
+    resizer: fr::Resizer,
+    dst: Vec<u8>,
+}
+
+thread_local! {
+    static ENCODER_STATE: RefCell<EncoderState> = RefCell::new(EncoderState {
+        resizer: fr::Resizer::new(),
+        dst: Vec::new(),
+    });
+}
+
+static BUFFER_REUSES: AtomicU64 = AtomicU64::new(0);
+static BUFFER_GROWS: AtomicU64 = AtomicU64::new(0);
+
+/// Returns `(reuses, grows)` of the pooled destination buffers since startup.
+pub fn encoder_pool_stats() -> (u64, u64) {
+    (BUFFER_REUSES.load(Ordering::Relaxed), BUFFER_GROWS.load(Ordering::Relaxed))
+}
+
+/// Target dimensions fitting `size_px` while keeping the aspect ratio.
+fn thumbnail_dimensions(width: u32, height: u32, size_px: u32) -> (u32, u32) {
+    let aspect = width as f32 / height as f32;
+    if aspect > 1.0 {
+        (size_px, (size_px as f32 / aspect).max(1.0) as u32)
+    } else {
+        (((size_px as f32 * aspect).max(1.0)) as u32, size_px)
+    }
+}
+
+/// Resizes RGBA pixels to fit `size_px` and writes them as WebP.
+///
+/// Uses the calling thread's pooled resizer and buffer, so callers running
+/// on the thumbnail pool don't allocate a new destination image per file.
+pub fn resize_and_encode(
+    rgba_data: &[u8],
+    width: u32,
+    height: u32,
+    size_px: u32,
+    alg: fr::ResizeAlg,
+    output_path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (new_w, new_h) = thumbnail_dimensions(width, height, size_px);
+    let src_image = fr::images::ImageRef::new(width, height, rgba_data, fr::PixelType::U8x4)
+        .map_err(|e| e.to_string())?;
+
+    ENCODER_STATE.with(|cell| {
+        let mut guard = cell.borrow_mut();
+        let state = &mut *guard;
+
+        let needed = new_w as usize * new_h as usize * 4;
+        if state.dst.capacity() < needed {
+            BUFFER_GROWS.fetch_add(1, Ordering::Relaxed);
+        } else {
+            BUFFER_REUSES.fetch_add(1, Ordering::Relaxed);
+        }
+        state.dst.clear();
+        state.dst.resize(needed, 0);
+
+        let mut dst_image = fr::images::Image::from_slice_u8(new_w, new_h, &mut state.dst, fr::PixelType::U8x4)
+            .map_err(|e| e.to_string())?;
+        let options = fr::ResizeOptions::new().resize_alg(alg);
+        state
+            .resizer
+            .resize(&src_image, &mut dst_image, Some(&options))
+            .map_err(|e| e.to_string())?;
+
+        super::matte::apply_current(&mut state.dst, new_w, new_h);
+        encode_webp_native(&state.dst, new_w, new_h, output_path)
+    })
+}
+
 /// Generates a thumbnail using native Rust libraries.
 ///
 /// Optimized for performance using:
@@ -31,6 +112,10 @@ pub fn generate_thumbnail_fast(
         .unwrap_or("")
         .to_lowercase();
 
+    // Hold the decoded size against the in-flight budget; parallel 100 MP
+    // decodes wait here rather than all allocating at once
+    let _reservation = super::memory::reserve_file(input_path);
+
     // Decode based on format - use optimized decoder for JPEG
     let start_decode = std::time::Instant::now();
     let (rgba_data, width, height) = match ext.as_str() {
@@ -55,46 +140,13 @@ pub fn generate_thumbnail_fast(
             
             let w = img.width();
             let h = img.height();
-            (img.to_rgba8().into_raw(), w, h)
+            (img.into_rgba8().into_raw(), w, h)
         }
     };
     println!("DEBUG: Native Decode took: {:?}", start_decode.elapsed());
 
-    // Calculate new dimensions maintaining aspect ratio
-    let aspect = width as f32 / height as f32;
-    let (new_w, new_h) = if aspect > 1.0 {
-        (size_px, (size_px as f32 / aspect).max(1.0) as u32)
-    } else {
-        (((size_px as f32 * aspect).max(1.0)) as u32, size_px)
-    };
-
-    // Resize using fast_image_resize (SIMD optimized)
-    let start_resize = std::time::Instant::now();
-    let src_image = fr::images::Image::from_vec_u8(
-        width,
-        height,
-        rgba_data,
-        fr::PixelType::U8x4,
-    )
-    .map_err(|e| e.to_string())?;
-
-    let mut dst_image = fr::images::Image::new(new_w, new_h, fr::PixelType::U8x4);
-    let mut resizer = fr::Resizer::new();
-    
-    // Use Bilinear filter which is much faster than the default Lanczos3
-    // Especially important for debug builds or large images
-    let options = fr::ResizeOptions::new().resize_alg(fr::ResizeAlg::Convolution(fr::FilterType::Bilinear));
-    
-    resizer
-        .resize(&src_image, &mut dst_image, Some(&options))
-        .map_err(|e| e.to_string())?;
-    println!("DEBUG: Native Resize took: {:?}", start_resize.elapsed());
-
-    // Encode to WebP using native webp crate
-    let start_encode = std::time::Instant::now();
-    let buffer = dst_image.buffer();
-    encode_webp_native(buffer, new_w, new_h, output_path)?;
-    println!("DEBUG: Native Encode took: {:?}", start_encode.elapsed());
+    // Resize (SIMD) and encode using this thread's pooled buffers
+    resize_and_encode(&rgba_data, width, height, size_px, THUMBNAIL_RESIZE_ALG, output_path)?;
     
     println!("DEBUG: Native Total took: {:?}", start_total.elapsed());
 
@@ -151,3 +203,17 @@ pub fn encode_webp_native(
     std::fs::write(output_path, &*webp_data)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thumbnail_dimensions_keep_aspect() {
+        assert_eq!(thumbnail_dimensions(4000, 2000, 300), (300, 150));
+        assert_eq!(thumbnail_dimensions(2000, 4000, 300), (150, 300));
+        assert_eq!(thumbnail_dimensions(100, 100, 300), (300, 300));
+        // Extreme panoramas never collapse to zero pixels
+        assert_eq!(thumbnail_dimensions(100_000, 10, 300), (300, 1));
+    }
+}
@@ -0,0 +1,327 @@
+//! Pages of multi-page TIFFs and parts/layers of multi-layer EXRs.
+//!
+//! The regular decoders only ever read the first page. Scanned documents keep
+//! one page per TIFF directory, and renders store AOVs either as separate EXR
+//! parts or as prefixed channels (`diffuse.R`, `specular.R`) in one part.
+//! Each of those is exposed here as a page that can be rendered on its own.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, RgbaImage};
+use serde::Serialize;
+use tauri::{AppHandle, Runtime};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+use tiff::ColorType;
+
+/// Upper bound on directories walked, in case of a corrupt IFD chain.
+const MAX_PAGES: usize = 10_000;
+
+/// A page as listed by `get_image_pages`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PageInfo {
+    /// Position of the page, as passed to `render_page`.
+    pub index: u32,
+    pub width: u32,
+    pub height: u32,
+    /// EXR part or layer name; `None` for TIFF pages and plain RGBA.
+    pub name: Option<String>,
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+/// Whether the format can hold more than one page.
+pub fn supports_pages(path: &Path) -> bool {
+    matches!(extension(path).as_str(), "tif" | "tiff" | "exr")
+}
+
+/// Lists the pages of a TIFF or EXR file.
+///
+/// # Errors
+/// Returns `Err` if the format has no pages or the header can't be read.
+pub fn list_pages(path: &Path) -> Result<Vec<PageInfo>, Box<dyn std::error::Error>> {
+    match extension(path).as_str() {
+        "tif" | "tiff" => list_tiff_pages(path),
+        "exr" => list_exr_pages(path),
+        _ => Err("Format has no pages".into()),
+    }
+}
+
+/// Number of pages, or `None` for formats without pages and unreadable files.
+pub fn count_pages(path: &Path) -> Option<i32> {
+    if !supports_pages(path) {
+        return None;
+    }
+    list_pages(path).ok().map(|pages| pages.len() as i32)
+}
+
+/// Decodes one page at full resolution.
+///
+/// # Errors
+/// Returns `Err` if the page doesn't exist or its pixel layout is unsupported.
+pub fn render_page(path: &Path, index: u32) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    match extension(path).as_str() {
+        "tif" | "tiff" => render_tiff_page(path, index),
+        "exr" => render_exr_page(path, index),
+        _ => Err("Format has no pages".into()),
+    }
+}
+
+/// Renders a page into the preview cache as WebP and returns its path.
+pub fn get_or_render_page<R: Runtime>(
+    app: &AppHandle<R>,
+    path: &Path,
+    index: u32,
+    maxdim: u32,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let cache_dir = super::variants::preview_cache_dir(app).ok_or("Preview cache directory unavailable")?;
+    let page_path = cache_dir.join(super::variants::part_variant_filename(path, &format!("p{}", index), maxdim)?);
+    if page_path.exists() {
+        return Ok(page_path);
+    }
+    std::fs::create_dir_all(&cache_dir)?;
+
+    let img = render_page(path, index)?;
+    let img = if img.width() > maxdim || img.height() > maxdim {
+        img.resize(maxdim, maxdim, image::imageops::FilterType::CatmullRom)
+    } else {
+        img
+    };
+    let mut rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    super::matte::apply_current(&mut rgba, width, height);
+
+    super::variants::write_webp(rgba.as_raw(), width, height, Some(path), &page_path)?;
+    Ok(page_path)
+}
+
+// --- TIFF ---
+
+fn open_tiff(path: &Path) -> Result<Decoder<BufReader<File>>, Box<dyn std::error::Error>> {
+    Ok(Decoder::new(BufReader::new(File::open(path)?))?)
+}
+
+/// Whether the current directory is a reduced-resolution copy (an embedded
+/// thumbnail) rather than a real page.
+fn is_reduced_resolution(decoder: &mut Decoder<BufReader<File>>) -> bool {
+    decoder
+        .find_tag(Tag::NewSubfileType)
+        .ok()
+        .flatten()
+        .and_then(|v| v.into_u32().ok())
+        .is_some_and(|flags| flags & 1 != 0)
+}
+
+/// Directory indexes of the real pages, in order.
+fn tiff_page_directories(decoder: &mut Decoder<BufReader<File>>) -> Result<Vec<(usize, u32, u32)>, Box<dyn std::error::Error>> {
+    let mut pages = Vec::new();
+    let mut directory = 0;
+    loop {
+        if !is_reduced_resolution(decoder) {
+            let (width, height) = decoder.dimensions()?;
+            pages.push((directory, width, height));
+        }
+        if !decoder.more_images() || directory + 1 >= MAX_PAGES {
+            break;
+        }
+        decoder.next_image()?;
+        directory += 1;
+    }
+    Ok(pages)
+}
+
+fn list_tiff_pages(path: &Path) -> Result<Vec<PageInfo>, Box<dyn std::error::Error>> {
+    let mut decoder = open_tiff(path)?;
+    Ok(tiff_page_directories(&mut decoder)?
+        .into_iter()
+        .enumerate()
+        .map(|(index, (_, width, height))| PageInfo { index: index as u32, width, height, name: None })
+        .collect())
+}
+
+fn render_tiff_page(path: &Path, index: u32) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    let mut decoder = open_tiff(path)?;
+    let directories = tiff_page_directories(&mut decoder)?;
+    let &(directory, width, height) = directories
+        .get(index as usize)
+        .ok_or_else(|| format!("Page {} out of range ({} pages)", index, directories.len()))?;
+
+    decoder.seek_to_image(directory)?;
+    let color_type = decoder.colortype()?;
+    let samples = match decoder.read_image()? {
+        DecodingResult::U8(data) => data,
+        // Keep the high byte; previews don't need more precision
+        DecodingResult::U16(data) => data.into_iter().map(|v| (v >> 8) as u8).collect(),
+        _ => return Err("Unsupported TIFF sample format".into()),
+    };
+
+    let channels = match color_type {
+        ColorType::Gray(_) => 1,
+        ColorType::GrayA(_) => 2,
+        ColorType::RGB(_) => 3,
+        ColorType::RGBA(_) => 4,
+        other => return Err(format!("Unsupported TIFF color type {:?}", other).into()),
+    };
+    Ok(DynamicImage::ImageRgba8(to_rgba(&samples, width, height, channels)?))
+}
+
+/// Expands interleaved 8-bit samples with 1 to 4 channels to RGBA.
+fn to_rgba(samples: &[u8], width: u32, height: u32, channels: usize) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+    let pixels = width as usize * height as usize;
+    if samples.len() < pixels * channels {
+        return Err("Truncated page data".into());
+    }
+
+    let mut rgba = Vec::with_capacity(pixels * 4);
+    for px in samples.chunks_exact(channels).take(pixels) {
+        let pixel = match channels {
+            1 => [px[0], px[0], px[0], 255],
+            2 => [px[0], px[0], px[0], px[1]],
+            3 => [px[0], px[1], px[2], 255],
+            _ => [px[0], px[1], px[2], px[3]],
+        };
+        rgba.extend_from_slice(&pixel);
+    }
+    RgbaImage::from_raw(width, height, rgba).ok_or_else(|| "Invalid page dimensions".into())
+}
+
+// --- EXR ---
+
+/// Groups channel names by layer prefix (`diffuse.R` -> `diffuse`), keeping
+/// first-seen order. Unprefixed channels form the unnamed default layer.
+fn group_exr_channels(names: &[String]) -> Vec<(String, Vec<usize>)> {
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    for (i, name) in names.iter().enumerate() {
+        let layer = name.rsplit_once('.').map(|(layer, _)| layer).unwrap_or("").to_string();
+        match groups.iter_mut().find(|(l, _)| *l == layer) {
+            Some((_, channels)) => channels.push(i),
+            None => groups.push((layer, vec![i])),
+        }
+    }
+    groups
+}
+
+/// Pages of an EXR: one per channel layer of every part, as `(part, layer)`.
+fn exr_pages(path: &Path) -> Result<Vec<(usize, String, Option<String>, u32, u32)>, Box<dyn std::error::Error>> {
+    let meta = exr::meta::MetaData::read_from_file(path, false)?;
+    let mut pages = Vec::new();
+
+    for (part, header) in meta.headers.iter().enumerate() {
+        let names: Vec<String> = header.channels.list.iter().map(|c| c.name.to_string()).collect();
+        let part_name = header.own_attributes.layer_name.as_ref().map(|n| n.to_string());
+        let (width, height) = (header.layer_size.width() as u32, header.layer_size.height() as u32);
+
+        for (layer, _) in group_exr_channels(&names) {
+            let name = match (&part_name, layer.is_empty()) {
+                (Some(part_name), true) => Some(part_name.clone()),
+                (Some(part_name), false) => Some(format!("{}/{}", part_name, layer)),
+                (None, true) => None,
+                (None, false) => Some(layer.clone()),
+            };
+            pages.push((part, layer, name, width, height));
+        }
+    }
+    Ok(pages)
+}
+
+fn list_exr_pages(path: &Path) -> Result<Vec<PageInfo>, Box<dyn std::error::Error>> {
+    Ok(exr_pages(path)?
+        .into_iter()
+        .enumerate()
+        .map(|(index, (_, _, name, width, height))| PageInfo { index: index as u32, width, height, name })
+        .collect())
+}
+
+fn render_exr_page(path: &Path, index: u32) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    let pages = exr_pages(path)?;
+    let (part, layer, _, width, height) = pages
+        .into_iter()
+        .nth(index as usize)
+        .ok_or_else(|| format!("Page {} out of range", index))?;
+
+    let image = exr::prelude::read_all_flat_layers_from_file(path)?;
+    let part_data = image.layer_data.get(part).ok_or("EXR part missing")?;
+    let pixels = width as usize * height as usize;
+
+    // Channels of the requested layer, keyed by their last name component
+    let channels: Vec<(String, &exr::image::FlatSamples)> = part_data
+        .channel_data
+        .list
+        .iter()
+        .filter_map(|c| {
+            let name = c.name.to_string();
+            let (channel_layer, short) = name.rsplit_once('.').unwrap_or(("", name.as_str()));
+            (channel_layer == layer && c.sample_data.len() == pixels)
+                .then(|| (short.to_uppercase(), &c.sample_data))
+        })
+        .collect();
+
+    let find = |key: &str| channels.iter().find(|(name, _)| name == key).map(|(_, s)| *s);
+    let (r, g, b, a) = match (find("R"), find("G"), find("B")) {
+        (Some(r), Some(g), Some(b)) => (r, g, b, find("A")),
+        // AOVs such as depth or masks: show the first channel as grayscale
+        _ => {
+            let first = channels.first().map(|(_, s)| *s).ok_or("Layer has no channels")?;
+            (first, first, first, None)
+        }
+    };
+
+    let mut rgba = Vec::with_capacity(pixels * 4);
+    for i in 0..pixels {
+        rgba.push(linear_to_srgb8(r.value_by_flat_index(i).to_f32()));
+        rgba.push(linear_to_srgb8(g.value_by_flat_index(i).to_f32()));
+        rgba.push(linear_to_srgb8(b.value_by_flat_index(i).to_f32()));
+        rgba.push(a.map(|a| (a.value_by_flat_index(i).to_f32().clamp(0.0, 1.0) * 255.0) as u8).unwrap_or(255));
+    }
+    let img = RgbaImage::from_raw(width, height, rgba).ok_or("Invalid EXR dimensions")?;
+    Ok(DynamicImage::ImageRgba8(img))
+}
+
+/// Clamps a linear value and applies the sRGB transfer curve.
+fn linear_to_srgb8(v: f32) -> u8 {
+    let v = if v.is_finite() { v.clamp(0.0, 1.0) } else { 0.0 };
+    let s = if v <= 0.003_130_8 { v * 12.92 } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 };
+    (s * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_exr_channels() {
+        let names: Vec<String> = ["A", "B", "G", "R", "diffuse.B", "diffuse.G", "diffuse.R", "depth.Z"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let groups = group_exr_channels(&names);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0], (String::new(), vec![0, 1, 2, 3]));
+        assert_eq!(groups[1], ("diffuse".to_string(), vec![4, 5, 6]));
+        assert_eq!(groups[2], ("depth".to_string(), vec![7]));
+    }
+
+    #[test]
+    fn test_to_rgba() {
+        let img = to_rgba(&[10, 20, 30, 40], 2, 1, 2).unwrap();
+        assert_eq!(img.get_pixel(0, 0).0, [10, 10, 10, 20]);
+        assert_eq!(img.get_pixel(1, 0).0, [30, 30, 30, 40]);
+        assert!(to_rgba(&[1, 2], 2, 2, 3).is_err());
+    }
+
+    #[test]
+    fn test_linear_to_srgb8() {
+        assert_eq!(linear_to_srgb8(0.0), 0);
+        assert_eq!(linear_to_srgb8(1.0), 255);
+        assert_eq!(linear_to_srgb8(4.0), 255);
+        assert_eq!(linear_to_srgb8(f32::NAN), 0);
+        assert_eq!(linear_to_srgb8(0.18), 118);
+    }
+}
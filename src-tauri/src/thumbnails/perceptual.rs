@@ -0,0 +1,169 @@
+//! Perceptual hashes, to find near-duplicates: resized or re-encoded copies
+//! and burst shots, which byte comparisons miss.
+//!
+//! The hash is a 64-bit difference hash (dHash): the picture shrunk to 9x8
+//! grey pixels, one bit per horizontal neighbour pair telling whether
+//! brightness falls. It survives scaling, recompression and small exposure
+//! changes, and two pictures are as different as the number of bits their
+//! hashes differ by. It is computed from the thumbnail right after the
+//! worker renders it, so the file is never decoded twice; thumbnails
+//! rendered before are hashed when the worker has nothing else to do.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use image::imageops::FilterType;
+use serde::Serialize;
+
+use crate::db::models::ImageMetadata;
+use crate::db::Db;
+use crate::error::AppResult;
+
+/// Distance up to which pictures count as similar when none is given.
+pub const DEFAULT_THRESHOLD: u32 = 10;
+
+/// Largest distance accepted; past it, unrelated pictures match.
+pub const MAX_THRESHOLD: u32 = 24;
+
+/// Most similar images returned.
+const MAX_RESULTS: usize = 200;
+
+/// Thumbnails hashed per idle pass of the worker.
+const BACKFILL_BATCH: i64 = 200;
+
+/// Difference hash of `image`.
+pub fn dhash(image: &image::DynamicImage) -> u64 {
+    let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+/// Number of bits `a` and `b` differ by, 0 for the same picture.
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Hashes the given thumbnails and stores the hashes: `(image id, thumbnail
+/// filename)`.
+pub async fn hash_thumbnails(db: &Db, thumbnails_dir: &Path, thumbnails: Vec<(i64, String)>) {
+    if thumbnails.is_empty() {
+        return;
+    }
+    let dir = thumbnails_dir.to_path_buf();
+    let hashes = tauri::async_runtime::spawn_blocking(move || {
+        thumbnails
+            .into_iter()
+            .map(|(id, thumbnail)| {
+                let hash = image::open(dir.join(&thumbnail)).ok().map(|image| dhash(&image));
+                (id, thumbnail, hash)
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+    if let Err(e) = db.set_perceptual_hashes(&hashes).await {
+        eprintln!("WARN: Could not store perceptual hashes: {}", e);
+    }
+}
+
+/// Hashes a batch of thumbnails rendered before hashes were stored, or
+/// replaced since. Returns the number of images handled.
+pub async fn hash_missing(db: &Db, thumbnails_dir: &Path) -> usize {
+    match db.get_thumbnails_needing_perceptual_hash(BACKFILL_BATCH).await {
+        Ok(thumbnails) => {
+            let handled = thumbnails.len();
+            hash_thumbnails(db, thumbnails_dir, thumbnails).await;
+            handled
+        }
+        Err(e) => {
+            eprintln!("WARN: Could not list thumbnails to hash: {}", e);
+            0
+        }
+    }
+}
+
+/// An image similar to another.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimilarImage {
+    pub image: ImageMetadata,
+    /// Bits the hashes differ by, 0 for the same picture.
+    pub distance: u32,
+}
+
+/// Ids of the images whose hash is within `threshold` of `hash`, closest
+/// first, leaving `image_id` out.
+fn closest(hashes: &[(i64, u64)], image_id: i64, hash: u64, threshold: u32) -> Vec<(i64, u32)> {
+    let mut matches: Vec<(i64, u32)> = hashes
+        .iter()
+        .filter(|(id, _)| *id != image_id)
+        .map(|(id, other)| (*id, distance(hash, *other)))
+        .filter(|(_, distance)| *distance <= threshold)
+        .collect();
+    matches.sort_by_key(|(id, distance)| (*distance, *id));
+    matches.truncate(MAX_RESULTS);
+    matches
+}
+
+/// Images that look like `image_id`, closest first. Empty until its
+/// thumbnail is hashed.
+pub async fn find_similar(db: &Db, image_id: i64, threshold: Option<u32>) -> AppResult<Vec<SimilarImage>> {
+    let threshold = threshold.unwrap_or(DEFAULT_THRESHOLD).min(MAX_THRESHOLD);
+    let Some(hash) = db.get_perceptual_hash(image_id).await? else {
+        return Ok(Vec::new());
+    };
+    let matches = closest(&db.get_perceptual_hashes().await?, image_id, hash, threshold);
+    let ids: Vec<i64> = matches.iter().map(|(id, _)| *id).collect();
+    let distances: HashMap<i64, u32> = matches.into_iter().collect();
+    // Images come back in the order of `ids`
+    Ok(db
+        .get_images_by_ids(&ids)
+        .await?
+        .into_iter()
+        .filter_map(|image| distances.get(&image.id).map(|distance| SimilarImage { distance: *distance, image }))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    /// Brightens left to right, or right to left when flipped.
+    fn gradient(width: u32, height: u32, flip: bool) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let x = if flip { width - 1 - x } else { x };
+            let value = (x * 200 / width + y * 40 / height) as u8;
+            Rgb([value, value / 2, 255 - value])
+        }))
+    }
+
+    #[test]
+    fn test_dhash_survives_resizing() {
+        let original = gradient(640, 480, false);
+        let resized = original.resize_exact(200, 150, FilterType::Lanczos3);
+        let recompressed = {
+            let mut jpeg = Vec::new();
+            resized.write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg).unwrap();
+            image::load_from_memory(&jpeg).unwrap()
+        };
+        assert!(distance(dhash(&original), dhash(&resized)) <= 4);
+        assert!(distance(dhash(&original), dhash(&recompressed)) <= 6);
+        assert!(distance(dhash(&original), dhash(&gradient(640, 480, true))) > MAX_THRESHOLD);
+    }
+
+    #[test]
+    fn test_closest() {
+        let hashes = [(1, 0b0000), (2, 0b0001), (3, 0b0111), (4, u64::MAX), (5, 0b0000)];
+        assert_eq!(closest(&hashes, 1, 0, 3), vec![(5, 0), (2, 1), (3, 3)]);
+        assert_eq!(closest(&hashes, 1, 0, 0), vec![(5, 0)]);
+    }
+}
@@ -1,20 +1,43 @@
 use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+
+use crate::db::search::ImageFilter;
 
 pub struct ThumbnailPriorityState {
     pub priority_ids: Mutex<HashSet<i64>>,
+    /// Thumbnails warmed ahead of navigation, processed after `priority_ids`.
+    prefetch_ids: Mutex<Vec<i64>>,
+    /// Incremented on every prefetch request so stale requests can be dropped.
+    prefetch_generation: AtomicU64,
+    /// Filter of the grid the user is looking at. Its missing thumbnails go
+    /// before the rest of the library.
+    active_context: Mutex<Option<ImageFilter>>,
+    /// Last time the user asked for thumbnails, by scrolling or navigating.
+    last_request: Mutex<Instant>,
+    /// Wakes the worker when it waits for work and urgent thumbnails come in.
+    wake: Notify,
 }
 
 impl Default for ThumbnailPriorityState {
     fn default() -> Self {
         Self {
             priority_ids: Mutex::new(HashSet::new()),
+            prefetch_ids: Mutex::new(Vec::new()),
+            prefetch_generation: AtomicU64::new(0),
+            active_context: Mutex::new(None),
+            last_request: Mutex::new(Instant::now()),
+            wake: Notify::new(),
         }
     }
 }
 
 impl ThumbnailPriorityState {
     pub fn set_priority(&self, ids: Vec<i64>) {
+        self.touch();
         if let Ok(mut set) = self.priority_ids.lock() {
             set.clear();
             for id in ids {
@@ -22,4 +45,147 @@ impl ThumbnailPriorityState {
             }
         }
     }
+
+    /// Puts `ids` at the head of the queue, next to the ones the grid asked
+    /// for, and wakes the worker. Not a user request, so not counted by
+    /// `idle_for`.
+    pub fn add_priority(&self, ids: &[i64]) {
+        if let Ok(mut set) = self.priority_ids.lock() {
+            set.extend(ids);
+        }
+        self.wake.notify_one();
+    }
+
+    /// Sleeps for `timeout`, or until `add_priority` is called.
+    pub async fn wait_for_work(&self, timeout: Duration) {
+        let _ = tokio::time::timeout(timeout, self.wake.notified()).await;
+    }
+
+    /// Cancels any pending prefetch and returns the generation of the new one.
+    pub fn begin_prefetch(&self) -> u64 {
+        self.touch();
+        let mut ids = self.prefetch_ids.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        ids.clear();
+        self.prefetch_generation.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Queues prefetch ids, unless a newer prefetch started in the meantime.
+    ///
+    /// Returns `false` if the request was superseded.
+    pub fn set_prefetch(&self, generation: u64, ids: Vec<i64>) -> bool {
+        let mut current = self.prefetch_ids.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // `begin_prefetch` bumps the generation under this lock, so a newer
+        // prefetch can't start between the check and the write
+        if self.prefetch_generation.load(Ordering::SeqCst) != generation {
+            return false;
+        }
+        *current = ids;
+        true
+    }
+
+    /// The queued prefetch ids, with the generation they belong to.
+    pub fn prefetch_ids(&self) -> (u64, Vec<i64>) {
+        let ids = self.prefetch_ids.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        (self.prefetch_generation.load(Ordering::SeqCst), ids.clone())
+    }
+
+    /// Drops the prefetch ids once their thumbnails are done, unless a newer
+    /// prefetch replaced them in the meantime.
+    pub fn finish_prefetch(&self, generation: u64) {
+        let mut ids = self.prefetch_ids.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if self.prefetch_generation.load(Ordering::SeqCst) == generation {
+            ids.clear();
+        }
+    }
+
+    /// Replaces the active context. `None`, or a filter matching the whole
+    /// library, leaves the regular order.
+    pub fn set_active_context(&self, filter: Option<ImageFilter>) {
+        let filter = filter.filter(|f| !is_whole_library(f)).map(|mut f| {
+            // The worker takes its own batches
+            f.limit = None;
+            f.offset = None;
+            f
+        });
+        *self.active_context.lock().unwrap() = filter;
+    }
+
+    pub fn active_context(&self) -> Option<ImageFilter> {
+        self.active_context.lock().unwrap().clone()
+    }
+
+    fn touch(&self) {
+        *self.last_request.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Instant::now();
+    }
+
+    /// Time since the user last asked for thumbnails.
+    pub fn idle_for(&self) -> Duration {
+        self.last_request.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).elapsed()
+    }
+}
+
+/// Whether `filter` selects nothing narrower than the library.
+fn is_whole_library(filter: &ImageFilter) -> bool {
+    filter.folder_id.is_none()
+        && filter.tag_ids.is_empty()
+        && filter.untagged != Some(true)
+        && filter.parsed_group().is_none()
+        && filter.search_query.as_deref().unwrap_or("").is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_superseded_prefetch_is_dropped() {
+        let state = ThumbnailPriorityState::default();
+        let first = state.begin_prefetch();
+        let second = state.begin_prefetch();
+        assert!(!state.set_prefetch(first, vec![1, 2]));
+        assert!(state.set_prefetch(second, vec![3]));
+        assert_eq!(state.prefetch_ids(), (second, vec![3]));
+
+        state.begin_prefetch();
+        assert!(state.prefetch_ids().1.is_empty());
+    }
+
+    #[test]
+    fn test_finished_prefetch_is_cleared() {
+        let state = ThumbnailPriorityState::default();
+        let first = state.begin_prefetch();
+        state.set_prefetch(first, vec![1]);
+        let second = state.begin_prefetch();
+        state.set_prefetch(second, vec![2]);
+
+        // A worker finishing the first prefetch late keeps the second
+        state.finish_prefetch(first);
+        assert_eq!(state.prefetch_ids().1, vec![2]);
+        state.finish_prefetch(second);
+        assert!(state.prefetch_ids().1.is_empty());
+    }
+
+    #[test]
+    fn test_active_context() {
+        let state = ThumbnailPriorityState::default();
+        state.set_active_context(Some(ImageFilter {
+            limit: Some(100),
+            offset: Some(200),
+            folder_id: Some(7),
+            recursive: true,
+            ..Default::default()
+        }));
+        let context = state.active_context().unwrap();
+        assert_eq!((context.folder_id, context.recursive), (Some(7), true));
+        assert_eq!((context.limit, context.offset), (None, None));
+
+        // The whole library is the regular order
+        state.set_active_context(Some(ImageFilter::default()));
+        assert!(state.active_context().is_none());
+
+        state.set_active_context(Some(ImageFilter { search_query: Some("beach".to_string()), ..Default::default() }));
+        assert!(state.active_context().is_some());
+        state.set_active_context(None);
+        assert!(state.active_context().is_none());
+    }
 }
@@ -0,0 +1,143 @@
+//! Layer tree and per-layer previews of Photoshop documents.
+//!
+//! Only the composite is used for thumbnails; this module backs the layer
+//! inspector. Layers and groups are returned as a flat list with parent ids,
+//! in document order (bottom-most first), so the frontend can build the tree.
+
+use std::path::{Path, PathBuf};
+
+use image::RgbaImage;
+use serde::Serialize;
+use tauri::{AppHandle, Runtime};
+
+/// Whether a [`PsdNode`] is a pixel layer or a group of nodes.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PsdNodeKind {
+    Layer,
+    Group,
+}
+
+/// A layer or group of a PSD.
+#[derive(Debug, Clone, Serialize)]
+pub struct PsdNode {
+    pub kind: PsdNodeKind,
+    /// Layer index (for `render_psd_layer`) or group id.
+    pub id: u32,
+    pub name: String,
+    /// Visibility flag as saved in the document.
+    pub visible: bool,
+    /// 0-255
+    pub opacity: u8,
+    /// Group containing this node, `None` at the top level.
+    pub parent_id: Option<u32>,
+    /// `[left, top, right, bottom]` in canvas pixels, for layers.
+    pub bounds: Option<[i32; 4]>,
+}
+
+/// Reads and parses the whole document.
+fn open_psd(path: &Path) -> Result<psd::Psd, Box<dyn std::error::Error>> {
+    let bytes = std::fs::read(path)?;
+    Ok(psd::Psd::from_bytes(&bytes).map_err(|e| format!("PSD parse error: {}", e))?)
+}
+
+/// Lists the groups and layers of a PSD.
+pub fn list_layers(path: &Path) -> Result<Vec<PsdNode>, Box<dyn std::error::Error>> {
+    let psd = open_psd(path)?;
+
+    let mut groups: Vec<&psd::PsdGroup> = psd.groups().values().collect();
+    groups.sort_by_key(|g| g.id());
+
+    let mut nodes: Vec<PsdNode> = groups
+        .into_iter()
+        .map(|group| PsdNode {
+            kind: PsdNodeKind::Group,
+            id: group.id(),
+            name: group.name().to_string(),
+            visible: group.visible(),
+            opacity: group.opacity(),
+            parent_id: group.parent_id(),
+            bounds: None,
+        })
+        .collect();
+
+    nodes.extend(psd.layers().iter().enumerate().map(|(index, layer)| PsdNode {
+        kind: PsdNodeKind::Layer,
+        id: index as u32,
+        name: layer.name().to_string(),
+        visible: layer.visible(),
+        opacity: layer.opacity(),
+        parent_id: layer.parent_id(),
+        bounds: Some([layer.layer_left(), layer.layer_top(), layer.layer_right(), layer.layer_bottom()]),
+    }));
+
+    Ok(nodes)
+}
+
+/// Clamps layer bounds to the canvas, returning `(x, y, width, height)`.
+fn crop_rect(bounds: [i32; 4], canvas_width: u32, canvas_height: u32) -> Option<(u32, u32, u32, u32)> {
+    let [left, top, right, bottom] = bounds;
+    let left = left.clamp(0, canvas_width as i32) as u32;
+    let top = top.clamp(0, canvas_height as i32) as u32;
+    let right = right.clamp(0, canvas_width as i32) as u32;
+    let bottom = bottom.clamp(0, canvas_height as i32) as u32;
+    (right > left && bottom > top).then(|| (left, top, right - left, bottom - top))
+}
+
+/// Renders a single layer, cropped to its bounds, regardless of its visibility.
+pub fn render_layer(path: &Path, layer_index: u32) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+    let psd = open_psd(path)?;
+    let layer = psd
+        .layers()
+        .get(layer_index as usize)
+        .ok_or_else(|| format!("Layer {} out of range ({} layers)", layer_index, psd.layers().len()))?;
+
+    let (width, height) = (psd.width(), psd.height());
+    // The psd crate places the layer on a canvas-sized buffer
+    let canvas = RgbaImage::from_raw(width, height, layer.rgba()).ok_or("Invalid layer buffer")?;
+
+    let bounds = [layer.layer_left(), layer.layer_top(), layer.layer_right(), layer.layer_bottom()];
+    let (x, y, w, h) = crop_rect(bounds, width, height).ok_or("Layer is empty or outside the canvas")?;
+    Ok(image::imageops::crop_imm(&canvas, x, y, w, h).to_image())
+}
+
+/// Renders a layer into the preview cache as WebP and returns its path.
+pub fn get_or_render_layer<R: Runtime>(
+    app: &AppHandle<R>,
+    path: &Path,
+    layer_index: u32,
+    maxdim: u32,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let cache_dir = super::variants::preview_cache_dir(app).ok_or("Preview cache directory unavailable")?;
+    let layer_path = cache_dir.join(super::variants::part_variant_filename(path, &format!("l{}", layer_index), maxdim)?);
+    if layer_path.exists() {
+        return Ok(layer_path);
+    }
+    std::fs::create_dir_all(&cache_dir)?;
+
+    let mut img = render_layer(path, layer_index)?;
+    if img.width() > maxdim || img.height() > maxdim {
+        img = image::DynamicImage::ImageRgba8(img)
+            .resize(maxdim, maxdim, image::imageops::FilterType::CatmullRom)
+            .to_rgba8();
+    }
+    let (width, height) = img.dimensions();
+    super::matte::apply_current(&mut img, width, height);
+
+    super::variants::write_webp(img.as_raw(), width, height, Some(path), &layer_path)?;
+    Ok(layer_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crop_rect() {
+        assert_eq!(crop_rect([10, 20, 110, 70], 200, 100), Some((10, 20, 100, 50)));
+        // Partially off-canvas layers are clipped
+        assert_eq!(crop_rect([-50, -10, 50, 40], 200, 100), Some((0, 0, 50, 40)));
+        assert_eq!(crop_rect([300, 0, 400, 50], 200, 100), None);
+        assert_eq!(crop_rect([0, 0, 0, 0], 200, 100), None);
+    }
+}
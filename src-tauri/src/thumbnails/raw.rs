@@ -21,6 +21,9 @@ pub fn generate_raw_thumbnail(
 ) -> Result<(), Box<dyn std::error::Error>> {
     let data = extract_raw_preview_data(input_path)?;
 
+    // The embedded preview can be full resolution: account for it before decoding
+    let _reservation = super::memory::reserve_blob(&data);
+
     use image::load_from_memory;
     let img = load_from_memory(&data)
         .map_err(|e| format!("Failed to decode extracted RAW preview: {}", e))?;
@@ -55,6 +58,26 @@ pub fn extract_raw_preview_data(path: &Path) -> Result<Vec<u8>, Box<dyn std::err
     }
 }
 
+/// Develops the sensor data of a RAW file with LibRaw, at full resolution.
+///
+/// Far slower than the embedded preview, which some cameras only write at a
+/// few hundred pixels, so it is kept for background upgrades (see `upgrade`).
+pub fn develop_raw(path: &Path) -> Result<image::RgbImage, Box<dyn std::error::Error>> {
+    let file = std::fs::File::open(path)?;
+    let mmap = unsafe { memmap2::MmapOptions::new().map(&file)? };
+
+    let mut raw = rsraw::RawImage::open(&mmap)
+        .map_err(|e| format!("LibRaw open error: {:?}", e))?;
+    raw.unpack()
+        .map_err(|e| format!("LibRaw unpack error: {:?}", e))?;
+    let processed = raw.process::<{ rsraw::BIT_DEPTH_8 }>()
+        .map_err(|e| format!("LibRaw process error: {:?}", e))?;
+
+    let (width, height) = (processed.width(), processed.height());
+    image::RgbImage::from_raw(width, height, processed.to_vec())
+        .ok_or_else(|| "LibRaw returned an unexpected image size".into())
+}
+
 /// Helper to resize and save the image
 fn process_image(
     img: image::DynamicImage,
@@ -43,6 +43,7 @@ pub fn generate_raw_thumbnail(
             }
 
         use image::load_from_memory;
+        let _reservation = crate::thumbnails::memory::reserve_blob(&thumb.data);
         let img = load_from_memory(&thumb.data)
             .map_err(|e| format!("Failed to decode extracted RAW preview: {}", e))?;
 
@@ -62,41 +63,17 @@ fn process_image(
     output_path: &Path,
     size_px: u32,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    use fast_image_resize as fr;
-    use crate::thumbnails::native::encode_webp_native;
-
     let width = img.width();
     let height = img.height();
 
-    // Calculate target dimensions
-    let aspect = width as f32 / height as f32;
-    let (new_w, new_h) = if aspect > 1.0 {
-        (size_px, (size_px as f32 / aspect).max(1.0) as u32)
-    } else {
-        (((size_px as f32 * aspect).max(1.0)) as u32, size_px)
-    };
-
-    // Prepare source for resizing
-    let src_image = fr::images::Image::from_vec_u8(
+    crate::thumbnails::native::resize_and_encode(
+        &img.into_rgba8().into_raw(),
         width,
         height,
-        img.to_rgba8().into_raw(),
-        fr::PixelType::U8x4,
-    )
-    .map_err(|e| e.to_string())?;
-
-    // Resize
-    let mut dst_image = fr::images::Image::new(new_w, new_h, fr::PixelType::U8x4);
-    let mut resizer = fr::Resizer::new();
-    let options = fr::ResizeOptions::new().resize_alg(fr::ResizeAlg::Convolution(fr::FilterType::Bilinear));
-
-    resizer
-        .resize(&src_image, &mut dst_image, Some(&options))
-        .map_err(|e| e.to_string())?;
-
-    // Save as WebP
-    let buffer = dst_image.buffer();
-    encode_webp_native(buffer, new_w, new_h, output_path)?;
+        size_px,
+        crate::thumbnails::native::THUMBNAIL_RESIZE_ALG,
+        output_path,
+    )?;
 
     Ok(())
 }
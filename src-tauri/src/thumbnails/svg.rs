@@ -53,14 +53,15 @@ pub fn generate_thumbnail_svg(
     );
 
     // 5. Encode to WebP
-    // tiny-skia produces RGBA8 (premultiplied?). resvg docs say standard RGBA8 usually.
-    // The webp encoder expects [u8] RGBA.
-    
-    // We can use the webp crate directly.
-    // Safety: pixmap.data() is guaranteed to be correct size.
-    
+    // tiny-skia pixels are premultiplied, the webp encoder expects straight RGBA.
+    let mut rgba: Vec<u8> = pixmap.pixels().iter().flat_map(|p| {
+        let c = p.demultiply();
+        [c.red(), c.green(), c.blue(), c.alpha()]
+    }).collect();
+    super::matte::apply_current(&mut rgba, target_width, target_height);
+
     let encoder = webp::Encoder::from_rgba(
-        pixmap.data(),
+        &rgba,
         target_width,
         target_height,
     );
@@ -0,0 +1,415 @@
+//! Thumbnails rendered by the operating system.
+//!
+//! Last resort for files Mundam can't read itself: the thumbnail handlers that
+//! installed applications register with the OS usually can. macOS asks
+//! QuickLook, Windows the Shell thumbnail cache (which runs the registered
+//! `IThumbnailProvider`) and Linux the freedesktop thumbnailers.
+
+use std::path::Path;
+#[cfg(not(target_os = "windows"))]
+use std::process::{Command, Stdio};
+#[cfg(not(target_os = "windows"))]
+use std::time::Duration;
+
+/// Size requested from the OS (the freedesktop "xx-large" size).
+pub const SYSTEM_THUMBNAIL_PX: u32 = 1024;
+
+/// Third-party thumbnailers can hang on unusual files.
+#[cfg(not(target_os = "windows"))]
+const THUMBNAILER_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Asks the OS for a thumbnail of `path` and returns it as PNG.
+///
+/// `mime_types` are used on Linux to find a matching thumbnailer.
+pub fn extract_system_thumbnail(
+    path: &Path,
+    mime_types: &[&str],
+    size_px: u32,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let data = platform_thumbnail(path, mime_types, size_px)?;
+    // Re-encode: thumbnailers may write other formats, and this also validates the output
+    let img = image::load_from_memory(&data)?;
+    let mut png_data = Vec::new();
+    img.write_to(&mut std::io::Cursor::new(&mut png_data), image::ImageFormat::Png)?;
+    Ok(png_data)
+}
+
+/// Runs a helper process, killing it after [`THUMBNAILER_TIMEOUT`].
+#[cfg(not(target_os = "windows"))]
+fn run_with_timeout(command: &mut Command) -> Result<(), Box<dyn std::error::Error>> {
+    use wait_timeout::ChildExt;
+
+    let mut child = command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn()?;
+    match child.wait_timeout(THUMBNAILER_TIMEOUT)? {
+        Some(status) if status.success() => Ok(()),
+        Some(status) => Err(format!("Thumbnailer exited with {}", status).into()),
+        None => {
+            child.kill().ok();
+            child.wait().ok();
+            Err("Thumbnailer timed out".into())
+        }
+    }
+}
+
+/// A scratch directory removed when dropped.
+#[cfg(not(target_os = "windows"))]
+struct ScratchDir(std::path::PathBuf);
+
+#[cfg(not(target_os = "windows"))]
+impl ScratchDir {
+    fn new() -> std::io::Result<Self> {
+        let dir = std::env::temp_dir().join(format!("mundam_system_thumb_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self(dir))
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// --- macOS: QuickLook ---
+
+#[cfg(target_os = "macos")]
+fn platform_thumbnail(path: &Path, _mime_types: &[&str], size_px: u32) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let scratch = ScratchDir::new()?;
+    run_with_timeout(
+        Command::new("qlmanage")
+            .arg("-t")
+            .arg("-s")
+            .arg(size_px.to_string())
+            .arg("-o")
+            .arg(&scratch.0)
+            .arg(path),
+    )?;
+
+    // qlmanage names the output after the input file and exits 0 even when it fails
+    let file_name = path.file_name().ok_or("Invalid path")?.to_string_lossy();
+    let output = scratch.0.join(format!("{}.png", file_name));
+    std::fs::read(&output).map_err(|_| "QuickLook produced no thumbnail".into())
+}
+
+// --- Windows: Shell thumbnail cache ---
+
+#[cfg(target_os = "windows")]
+fn platform_thumbnail(path: &Path, _mime_types: &[&str], size_px: u32) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    use windows::core::HSTRING;
+    use windows::Win32::Foundation::SIZE;
+    use windows::Win32::Graphics::Gdi::DeleteObject;
+    use windows::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_MULTITHREADED};
+    use windows::Win32::UI::Shell::{
+        IShellItemImageFactory, SHCreateItemFromParsingName, SIIGBF_BIGGERSIZEOK, SIIGBF_THUMBNAILONLY,
+    };
+
+    // SAFETY: plain COM calls on this thread; the bitmap is owned here and deleted below.
+    unsafe {
+        let com = CoInitializeEx(None, COINIT_MULTITHREADED);
+        let result = (|| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+            let factory: IShellItemImageFactory =
+                SHCreateItemFromParsingName(&HSTRING::from(path.as_os_str()), None)?;
+            let size = SIZE { cx: size_px as i32, cy: size_px as i32 };
+            // THUMBNAILONLY: never fall back to the file type icon
+            let bitmap = factory.GetImage(size, SIIGBF_THUMBNAILONLY | SIIGBF_BIGGERSIZEOK)?;
+            let image = hbitmap_to_png(bitmap);
+            let _ = DeleteObject(bitmap.into());
+            image
+        })();
+        if com.is_ok() {
+            CoUninitialize();
+        }
+        result
+    }
+}
+
+/// Copies a 32-bit HBITMAP into a PNG.
+#[cfg(target_os = "windows")]
+unsafe fn hbitmap_to_png(
+    bitmap: windows::Win32::Graphics::Gdi::HBITMAP,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    use windows::Win32::Graphics::Gdi::{
+        GetDC, GetDIBits, GetObjectW, ReleaseDC, BITMAP, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS,
+    };
+
+    let mut info = BITMAP::default();
+    if GetObjectW(bitmap.into(), std::mem::size_of::<BITMAP>() as i32, Some(&mut info as *mut _ as *mut _)) == 0 {
+        return Err("Invalid thumbnail bitmap".into());
+    }
+    let (width, height) = (info.bmWidth.unsigned_abs(), info.bmHeight.unsigned_abs());
+
+    let mut bmi = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: width as i32,
+            // Negative height: top-down rows
+            biHeight: -(height as i32),
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    let dc = GetDC(None);
+    let lines = GetDIBits(dc, bitmap, 0, height, Some(pixels.as_mut_ptr() as *mut _), &mut bmi, DIB_RGB_COLORS);
+    ReleaseDC(None, dc);
+    if lines == 0 {
+        return Err("Could not read thumbnail bitmap".into());
+    }
+
+    // BGRA -> RGBA. Providers that ignore alpha leave it at 0 for opaque images.
+    let has_alpha = pixels.chunks_exact(4).any(|px| px[3] != 0);
+    for px in pixels.chunks_exact_mut(4) {
+        px.swap(0, 2);
+        if !has_alpha {
+            px[3] = 255;
+        }
+    }
+
+    let img = image::RgbaImage::from_raw(width, height, pixels).ok_or("Invalid thumbnail bitmap size")?;
+    let mut png_data = Vec::new();
+    img.write_to(&mut std::io::Cursor::new(&mut png_data), image::ImageFormat::Png)?;
+    Ok(png_data)
+}
+
+// --- Linux: freedesktop thumbnailers ---
+
+#[cfg(target_os = "linux")]
+fn platform_thumbnail(path: &Path, mime_types: &[&str], size_px: u32) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let thumbnailers = freedesktop::find_thumbnailers(mime_types);
+    if thumbnailers.is_empty() {
+        return Err("No freedesktop thumbnailer for this file type".into());
+    }
+
+    let scratch = ScratchDir::new()?;
+    let output = scratch.0.join("thumbnail.png");
+    for thumbnailer in thumbnailers {
+        let Some(args) = freedesktop::expand_exec(&thumbnailer.exec, path, &output, size_px) else {
+            continue;
+        };
+        let Some((program, rest)) = args.split_first() else {
+            continue;
+        };
+        match run_with_timeout(Command::new(program).args(rest)) {
+            Ok(()) if output.exists() => return Ok(std::fs::read(&output)?),
+            Ok(()) => {}
+            Err(e) => println!("DEBUG: Thumbnailer {:?} failed for {:?}: {}", program, path.file_name().unwrap_or_default(), e),
+        }
+    }
+    Err("Freedesktop thumbnailers produced no thumbnail".into())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn platform_thumbnail(_path: &Path, _mime_types: &[&str], _size_px: u32) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Err("System thumbnails are not supported on this platform".into())
+}
+
+#[cfg(target_os = "linux")]
+mod freedesktop {
+    use std::path::{Path, PathBuf};
+
+    use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+    /// Characters kept as is in `file://` URIs.
+    const URI_PATH: &AsciiSet = &NON_ALPHANUMERIC.remove(b'/').remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+    /// A `.thumbnailer` entry.
+    #[derive(Debug, PartialEq)]
+    pub struct Thumbnailer {
+        pub try_exec: Option<String>,
+        pub exec: String,
+        pub mime_types: Vec<String>,
+    }
+
+    /// Parses the `[Thumbnailer Entry]` group of a `.thumbnailer` file.
+    pub fn parse_thumbnailer(contents: &str) -> Option<Thumbnailer> {
+        let mut in_entry = false;
+        let (mut try_exec, mut exec, mut mime_types) = (None, None, Vec::new());
+
+        for line in contents.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_entry = line == "[Thumbnailer Entry]";
+                continue;
+            }
+            if !in_entry || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key.trim() {
+                "TryExec" => try_exec = Some(value.trim().to_string()),
+                "Exec" => exec = Some(value.trim().to_string()),
+                "MimeType" => {
+                    mime_types = value.split(';').map(str::trim).filter(|m| !m.is_empty()).map(String::from).collect()
+                }
+                _ => {}
+            }
+        }
+
+        Some(Thumbnailer { try_exec, exec: exec?, mime_types })
+    }
+
+    /// Splits an `Exec` line into arguments and substitutes the field codes.
+    ///
+    /// Returns `None` for unknown field codes, which the spec asks to reject.
+    pub fn expand_exec(exec: &str, input: &Path, output: &Path, size_px: u32) -> Option<Vec<String>> {
+        let input_str = input.to_string_lossy();
+        let uri = format!("file://{}", utf8_percent_encode(&input_str, URI_PATH));
+
+        let mut args = Vec::new();
+        for token in split_exec(exec) {
+            let mut arg = String::new();
+            let mut chars = token.chars();
+            while let Some(c) = chars.next() {
+                if c != '%' {
+                    arg.push(c);
+                    continue;
+                }
+                match chars.next()? {
+                    'i' => arg.push_str(&input_str),
+                    'u' => arg.push_str(&uri),
+                    'o' => arg.push_str(&output.to_string_lossy()),
+                    's' => arg.push_str(&size_px.to_string()),
+                    '%' => arg.push('%'),
+                    _ => return None,
+                }
+            }
+            args.push(arg);
+        }
+        (!args.is_empty()).then_some(args)
+    }
+
+    /// Splits on whitespace, keeping double-quoted arguments together.
+    fn split_exec(exec: &str) -> Vec<String> {
+        let mut tokens = Vec::new();
+        let mut current = String::new();
+        let (mut quoted, mut has_token) = (false, false);
+        let mut chars = exec.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => {
+                    quoted = !quoted;
+                    has_token = true;
+                }
+                '\\' if quoted => {
+                    if let Some(next) = chars.next() {
+                        current.push(next);
+                    }
+                }
+                c if c.is_whitespace() && !quoted => {
+                    if has_token {
+                        tokens.push(std::mem::take(&mut current));
+                        has_token = false;
+                    }
+                }
+                c => {
+                    current.push(c);
+                    has_token = true;
+                }
+            }
+        }
+        if has_token {
+            tokens.push(current);
+        }
+        tokens
+    }
+
+    /// `thumbnailers` directories, user ones first.
+    fn thumbnailer_dirs() -> Vec<PathBuf> {
+        let env_dirs = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+
+        let mut dirs = Vec::new();
+        match env_dirs("XDG_DATA_HOME") {
+            Some(home) => dirs.push(PathBuf::from(home)),
+            None => {
+                if let Ok(home) = std::env::var("HOME") {
+                    dirs.push(Path::new(&home).join(".local/share"));
+                }
+            }
+        }
+        let system = env_dirs("XDG_DATA_DIRS").unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+        dirs.extend(system.split(':').filter(|d| !d.is_empty()).map(PathBuf::from));
+
+        dirs.into_iter().map(|d| d.join("thumbnailers")).collect()
+    }
+
+    /// Whether `program` is an existing path or can be found on `PATH`.
+    fn program_exists(program: &str) -> bool {
+        if program.contains('/') {
+            return Path::new(program).exists();
+        }
+        std::env::var_os("PATH")
+            .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).exists()))
+            .unwrap_or(false)
+    }
+
+    /// Installed thumbnailers that handle one of `mime_types`.
+    pub fn find_thumbnailers(mime_types: &[&str]) -> Vec<Thumbnailer> {
+        let mut found = Vec::new();
+        for dir in thumbnailer_dirs() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("thumbnailer") {
+                    continue;
+                }
+                let Some(thumbnailer) = std::fs::read_to_string(&path).ok().and_then(|c| parse_thumbnailer(&c)) else {
+                    continue;
+                };
+                let handles = thumbnailer.mime_types.iter().any(|m| mime_types.contains(&m.as_str()));
+                let runnable = thumbnailer.try_exec.as_deref().map(program_exists).unwrap_or(true);
+                if handles && runnable && !found.contains(&thumbnailer) {
+                    found.push(thumbnailer);
+                }
+            }
+        }
+        found
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_thumbnailer() {
+            let contents = "[Thumbnailer Entry]\n\
+                TryExec=gdk-pixbuf-thumbnailer\n\
+                Exec=gdk-pixbuf-thumbnailer -s %s %u %o\n\
+                MimeType=image/png;image/x-tga;\n\
+                \n\
+                [Other Group]\n\
+                Exec=ignored\n";
+            let thumbnailer = parse_thumbnailer(contents).unwrap();
+            assert_eq!(thumbnailer.try_exec.as_deref(), Some("gdk-pixbuf-thumbnailer"));
+            assert_eq!(thumbnailer.exec, "gdk-pixbuf-thumbnailer -s %s %u %o");
+            assert_eq!(thumbnailer.mime_types, vec!["image/png", "image/x-tga"]);
+
+            assert!(parse_thumbnailer("[Thumbnailer Entry]\nMimeType=image/png;\n").is_none());
+        }
+
+        #[test]
+        fn test_expand_exec() {
+            let args = expand_exec(
+                "thumb --size=%s \"%i\" %o 100%%",
+                Path::new("/tmp/My File.kra"),
+                Path::new("/tmp/out.png"),
+                1024,
+            )
+            .unwrap();
+            assert_eq!(args, vec!["thumb", "--size=1024", "/tmp/My File.kra", "/tmp/out.png", "100%"]);
+
+            let args = expand_exec("thumb %u", Path::new("/tmp/My File#1.kra"), Path::new("/o.png"), 256).unwrap();
+            assert_eq!(args[1], "file:///tmp/My%20File%231.kra");
+
+            assert!(expand_exec("thumb %x", Path::new("/a"), Path::new("/b"), 256).is_none());
+        }
+    }
+}
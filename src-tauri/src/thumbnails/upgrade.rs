@@ -0,0 +1,197 @@
+//! Quality upgrade of quick RAW and PSD thumbnails.
+//!
+//! First-pass thumbnails of RAW files come from the preview the camera
+//! embedded, which some cameras only write at a few hundred pixels, and
+//! those of PSDs the parser can't read from their embedded thumbnail. Once
+//! the library has its thumbnails and the machine is idle (no thumbnail asked
+//! for by the user for a while, no FFmpeg or decoder process running), the
+//! worker renders them again from the full-resolution source, a few at a
+//! time, and swaps them in. Upgraded thumbnails have a `-hq` name, so the
+//! grid loads them anew and copies of a file share the upgrade. Like any
+//! thumbnail an image stops using, the replaced quick one and an upgraded one
+//! whose file changed are deleted by the worker (see `stale_thumbnails`).
+
+use std::path::Path;
+use std::time::Duration;
+
+use fast_image_resize as fr;
+use tauri::{AppHandle, Emitter, Runtime};
+
+use super::priority::ThumbnailPriorityState;
+use super::{native, RAW_EXTENSIONS};
+use crate::db::Db;
+use crate::streaming::process_manager;
+
+/// Setting turning the upgrades off, on by default.
+pub const SETTING_KEY: &str = "upgrade_thumbnails";
+
+/// How long the user must leave the grid alone before upgrades start.
+pub const IDLE_DELAY: Duration = Duration::from_secs(120);
+
+/// Thumbnails upgraded per idle pass of the worker, one after the other.
+const UPGRADE_BATCH: i64 = 4;
+
+const PSD_FORMATS: &[&str] = &["psd", "psb"];
+
+/// Suffix of upgraded thumbnail names.
+const UPGRADED_SUFFIX: &str = "-hq";
+
+/// Formats whose thumbnails are upgraded.
+pub fn formats() -> Vec<&'static str> {
+    RAW_EXTENSIONS.iter().chain(PSD_FORMATS).copied().collect()
+}
+
+/// Name of the upgraded version of the thumbnail `thumbnail`, `None` when it
+/// is upgraded already.
+pub fn upgraded_filename(thumbnail: &str) -> Option<String> {
+    thumbnail
+        .strip_suffix(".webp")
+        .filter(|stem| !stem.ends_with(UPGRADED_SUFFIX))
+        .map(|stem| format!("{}{}.webp", stem, UPGRADED_SUFFIX))
+}
+
+/// Whether the user and the other background work leave room for upgrades.
+pub fn is_machine_idle(priority_state: &ThumbnailPriorityState) -> bool {
+    priority_state.idle_for() >= IDLE_DELAY && process_manager::lock_global().is_idle()
+}
+
+/// Renders the thumbnail of `source` from its full-resolution data: the
+/// developed sensor data of a RAW, the composite of a PSD.
+pub fn render_full(source: &Path, output: &Path, size_px: u32) -> Result<(), Box<dyn std::error::Error>> {
+    let ext = source.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    // Sized from the header where it can be, to wait for room before decoding
+    let _reservation = super::memory::reserve_file(source);
+    let image = if PSD_FORMATS.contains(&ext.as_str()) {
+        super::extractors::decode_psd_composite(source)?
+    } else {
+        image::DynamicImage::ImageRgb8(super::raw::develop_raw(source)?).into_rgba8()
+    };
+
+    // Unlike the quick pass, this shrinks a full-size image, so use a proper filter
+    let alg = fr::ResizeAlg::Convolution(fr::FilterType::CatmullRom);
+    let tmp_path = output.with_extension("webp.tmp");
+    native::resize_and_encode(image.as_raw(), image.width(), image.height(), size_px, alg, &tmp_path)?;
+    std::fs::rename(&tmp_path, output)?;
+    Ok(())
+}
+
+/// Upgrades the thumbnail `quick` of `source`. Returns the upgraded name.
+fn upgrade(thumbnails_dir: &Path, source: &str, quick: &str) -> Result<String, String> {
+    let upgraded = upgraded_filename(quick).ok_or("Already upgraded")?;
+    let output = thumbnails_dir.join(&upgraded);
+    // A copy of this file may have been upgraded already
+    if output.exists() {
+        return Ok(upgraded);
+    }
+    let source = &crate::paths::from_db(source);
+    let result = if super::isolated::is_enabled() {
+        super::isolated::generate_isolated_full(source, &output, super::GRID_THUMBNAIL_SIZE)
+    } else {
+        render_full(source, &output, super::GRID_THUMBNAIL_SIZE)
+    };
+    result.map(|_| upgraded).map_err(|e| e.to_string())
+}
+
+/// Upgrades a batch of thumbnails and swaps them in. Returns the number of
+/// images handled.
+pub async fn upgrade_batch<R: Runtime>(app: &AppHandle<R>, db: &Db, thumbnails_dir: &Path) -> usize {
+    let images = match db.get_thumbnails_to_upgrade(&formats(), UPGRADE_BATCH).await {
+        Ok(images) => images,
+        Err(e) => {
+            eprintln!("WARN: Could not list thumbnails to upgrade: {}", e);
+            return 0;
+        }
+    };
+    if images.is_empty() {
+        return 0;
+    }
+
+    let dir = thumbnails_dir.to_path_buf();
+    let upgraded = tauri::async_runtime::spawn_blocking(move || {
+        images
+            .into_iter()
+            .map(|(id, path, quick)| {
+                let result = upgrade(&dir, &path, &quick);
+                (id, quick, result)
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+
+    let handled = upgraded.len();
+    let mut done = 0;
+    for (id, quick, result) in upgraded {
+        match result {
+            Ok(filename) => match db.replace_thumbnail_path(id, &quick, &filename).await {
+                Ok(true) => {
+                    done += 1;
+                    // The quick one is deleted by the worker once no copy uses it
+                    let _ = app.emit("thumbnail:ready", serde_json::json!({ "id": id, "path": filename }));
+                }
+                Ok(false) => {}
+                Err(e) => eprintln!("WARN: Could not store the upgraded thumbnail of image {}: {}", id, e),
+            },
+            Err(e) => {
+                eprintln!("WARN: Could not upgrade the thumbnail of image {}: {}", id, e);
+                if let Err(e) = db.mark_thumbnail_upgrade_failed(id, &quick).await {
+                    eprintln!("WARN: Could not record the failed upgrade of image {}: {}", id, e);
+                }
+            }
+        }
+    }
+    if done > 0 {
+        println!("INFO: Upgraded {} thumbnails from their full-resolution source", done);
+    }
+    handled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upgraded_filename() {
+        let quick = crate::thumbnails::thumbnail_filename(&"ab".repeat(32), 0, 300);
+        let upgraded = upgraded_filename(&quick).unwrap();
+        assert_eq!(upgraded, quick.replace(".webp", "-hq.webp"));
+        assert_eq!(upgraded_filename(&upgraded), None);
+    }
+
+    async fn pending(db: &Db) -> Vec<i64> {
+        db.get_thumbnails_to_upgrade(&formats(), 10).await.unwrap().into_iter().map(|(id, ..)| id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_thumbnails_to_upgrade() {
+        let library = crate::testkit::TestLibrary::open("thumbnail-upgrades").await;
+        let db = &library.db;
+        library.seed_images(&["a.cr3", "b.psd", "c.jpg", "d.nef", "e.nef"]).await;
+        let quick = |key: &str| crate::thumbnails::thumbnail_filename(&key.repeat(64), 0, 300);
+        for (id, thumbnail) in [
+            (1, quick("a")),
+            (2, quick("b")),
+            (3, quick("c")),
+            (4, "extensions/icon_nef_300.webp".to_string()),
+        ] {
+            sqlx::query("UPDATE images SET thumbnail_path = ? WHERE id = ?")
+                .bind(thumbnail)
+                .bind(id)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+        assert_eq!(pending(db).await, vec![1, 2]);
+
+        let upgraded = upgraded_filename(&quick("a")).unwrap();
+        assert!(db.replace_thumbnail_path(1, &quick("a"), &upgraded).await.unwrap());
+        assert!(!db.replace_thumbnail_path(1, &quick("a"), &upgraded).await.unwrap());
+        db.mark_thumbnail_upgrade_failed(2, &quick("b")).await.unwrap();
+        assert!(pending(db).await.is_empty());
+
+        // A new quick thumbnail is worth another try
+        db.update_thumbnail_path(2, &quick("f")).await.unwrap();
+        assert_eq!(pending(db).await, vec![2]);
+        assert!(!db.is_thumbnail_used(&quick("a")).await.unwrap());
+    }
+}
@@ -0,0 +1,167 @@
+//! Display-sized variants served by the `image://` protocol (`?maxdim=`).
+//!
+//! Large TIFFs, PSDs or RAW previews are decoded once, downscaled so their
+//! longest side fits `maxdim`, and cached as WebP in the preview cache. The
+//! viewer then never hands a 12000px bitmap to the WebView.
+
+use fast_image_resize as fr;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, Runtime};
+
+/// Smallest variant that can be requested.
+pub const MIN_MAXDIM: u32 = 256;
+/// Largest variant; WebP cannot encode more than 16383px per side.
+pub const MAX_MAXDIM: u32 = 8192;
+
+/// Directory of the preview cache, under the app data directory.
+pub fn preview_cache_dir<R: Runtime>(app: &AppHandle<R>) -> Option<PathBuf> {
+    app.path().app_local_data_dir().ok().map(|d| d.join("previews"))
+}
+
+/// Returns a cached variant of `source` no larger than `maxdim`, creating it if needed.
+///
+/// Returns `Ok(None)` when the source already fits and can be served as is.
+///
+/// # Errors
+/// Returns `Err` if the source cannot be decoded or the variant cannot be written.
+pub fn get_or_create_variant<R: Runtime>(
+    app: &AppHandle<R>,
+    source: &Path,
+    maxdim: u32,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let maxdim = maxdim.clamp(MIN_MAXDIM, MAX_MAXDIM);
+    let format = crate::formats::FileFormat::detect(source).ok_or("Unsupported format")?;
+    let browser_native = matches!(format.preview_strategy, crate::formats::PreviewStrategy::BrowserNative);
+
+    // Cheap header read: small native images are served untouched
+    if browser_native {
+        if let Ok(size) = imagesize::size(source) {
+            if size.width as u32 <= maxdim && size.height as u32 <= maxdim {
+                return Ok(None);
+            }
+        }
+    }
+
+    let cache_dir = preview_cache_dir(app).ok_or("Preview cache directory unavailable")?;
+    let variant_path = cache_dir.join(variant_filename(source, maxdim)?);
+    if variant_path.exists() {
+        return Ok(Some(variant_path));
+    }
+    std::fs::create_dir_all(&cache_dir)?;
+
+    let img = decode_source(app, source, maxdim)?;
+
+    let (width, height) = (img.width(), img.height());
+    let (new_w, new_h) = fit_within(width, height, maxdim);
+    let rgba = img.to_rgba8().into_raw();
+
+    let (mut buffer, out_w, out_h) = if (new_w, new_h) == (width, height) {
+        (rgba, width, height)
+    } else {
+        let src_image = fr::images::Image::from_vec_u8(width, height, rgba, fr::PixelType::U8x4)
+            .map_err(|e| e.to_string())?;
+        let mut dst_image = fr::images::Image::new(new_w, new_h, fr::PixelType::U8x4);
+        // Unlike thumbnails, this is viewed at full size, so use a proper filter
+        let options = fr::ResizeOptions::new().resize_alg(fr::ResizeAlg::Convolution(fr::FilterType::CatmullRom));
+        fr::Resizer::new()
+            .resize(&src_image, &mut dst_image, Some(&options))
+            .map_err(|e| e.to_string())?;
+        (dst_image.into_vec(), new_w, new_h)
+    };
+
+    super::matte::apply_current(&mut buffer, out_w, out_h);
+
+    write_webp(&buffer, out_w, out_h, Some(source), &variant_path)?;
+    Ok(Some(variant_path))
+}
+
+/// Encodes RGBA pixels as WebP into the preview cache file `output`,
+/// embedding the color profile of `source` when given.
+///
+/// The file is written under a temporary name and renamed, so a concurrent
+/// request never serves a partial file.
+pub fn write_webp(
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    source: Option<&Path>,
+    output: &Path,
+) -> std::io::Result<()> {
+    let webp_data = webp::Encoder::from_rgba(rgba, width, height).encode(90.0).to_vec();
+    let webp_data = match source {
+        Some(source) => super::icc::keep_profile(webp_data, source),
+        None => webp_data,
+    };
+    let tmp_path = output.with_extension("webp.tmp");
+    std::fs::write(&tmp_path, &webp_data)?;
+    std::fs::rename(&tmp_path, output)
+}
+
+/// Decodes `source` for display, through the preview extractors for formats
+/// the WebView can't show. The result is not resized to `maxdim`.
+pub fn decode_source<R: Runtime>(
+    app: &AppHandle<R>,
+    source: &Path,
+    maxdim: u32,
+) -> Result<image::DynamicImage, Box<dyn std::error::Error>> {
+    let format = crate::formats::FileFormat::detect(source).ok_or("Unsupported format")?;
+    if matches!(format.preview_strategy, crate::formats::PreviewStrategy::BrowserNative) {
+        Ok(image::open(source)?)
+    } else {
+        let (data, _) = crate::thumbnails::extractors::extract_raster_preview(Some(app), source, maxdim)?;
+        Ok(image::load_from_memory(&data)?)
+    }
+}
+
+/// Bumped when variants are encoded differently, so older ones are rebuilt.
+/// Page and layer renders are named after variants (see
+/// [`part_variant_filename`]), so they're rebuilt too.
+/// 2: the source's color profile is embedded.
+const VARIANT_REVISION: u32 = 2;
+
+/// Cache key: source path, size and modification time, so edited files get a new variant.
+fn variant_filename(source: &Path, maxdim: u32) -> std::io::Result<String> {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let metadata = std::fs::metadata(source)?;
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    metadata.modified().ok().hash(&mut hasher);
+    // A new matte must not reuse renders flattened onto the previous one
+    super::matte::current().as_setting().hash(&mut hasher);
+    VARIANT_REVISION.hash(&mut hasher);
+    Ok(format!("{:x}_{}.webp", hasher.finish(), maxdim))
+}
+
+/// Cache key of a rendering of part of a file (a page, a layer), told apart by `part`.
+pub fn part_variant_filename(source: &Path, part: &str, maxdim: u32) -> std::io::Result<String> {
+    let base = variant_filename(source, maxdim)?;
+    Ok(base.replacen(".webp", &format!("_{}.webp", part), 1))
+}
+
+/// Scales `(width, height)` down so the longest side is at most `maxdim`.
+fn fit_within(width: u32, height: u32, maxdim: u32) -> (u32, u32) {
+    if width <= maxdim && height <= maxdim {
+        return (width, height);
+    }
+    if width >= height {
+        (maxdim, ((height as u64 * maxdim as u64) / width as u64).max(1) as u32)
+    } else {
+        (((width as u64 * maxdim as u64) / height as u64).max(1) as u32, maxdim)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_within() {
+        assert_eq!(fit_within(12000, 8000, 3000), (3000, 2000));
+        assert_eq!(fit_within(8000, 12000, 3000), (2000, 3000));
+        assert_eq!(fit_within(1200, 800, 3000), (1200, 800));
+        assert_eq!(fit_within(100_000, 10, 3000), (3000, 1));
+    }
+}
@@ -1,24 +1,24 @@
 use crate::db::Db;
-use crate::thumbnails::{generate_thumbnail, get_thumbnail_filename};
+use crate::thumbnails::{cover_art_filename, generate_thumbnail, get_thumbnail_filename, GRID_THUMBNAIL_SIZE};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Emitter, Manager, Runtime, Wry};
 use tokio::time::{sleep, Duration};
 use crate::thumbnails::priority::ThumbnailPriorityState;
 
-pub struct ThumbnailWorker {
+pub struct ThumbnailWorker<R: Runtime = Wry> {
     db: Arc<Db>,
     thumbnails_dir: PathBuf,
-    app_handle: AppHandle,
+    app_handle: AppHandle<R>,
     config: crate::settings::config::AppConfig,
     priority_state: Arc<ThumbnailPriorityState>,
 }
 
-impl ThumbnailWorker {
+impl<R: Runtime> ThumbnailWorker<R> {
     pub fn new(
         db: Arc<Db>,
         thumbnails_dir: PathBuf,
-        app_handle: AppHandle,
+        app_handle: AppHandle<R>,
         config: crate::settings::config::AppConfig,
         priority_state: Arc<ThumbnailPriorityState>,
     ) -> Self {
@@ -38,7 +38,26 @@ impl ThumbnailWorker {
         let config = self.config.clone();
         let priority_state = self.priority_state.clone();
 
+        // Build the pool once so its threads (and their pooled resize buffers)
+        // survive across batches instead of being recreated every loop.
+        let pool = match rayon::ThreadPoolBuilder::new()
+            .num_threads(config.thumbnail_threads)
+            .thread_name(|i| format!("thumbnail-{}", i))
+            .build()
+        {
+            Ok(pool) => Arc::new(pool),
+            Err(e) => {
+                eprintln!("Failed to build thumbnail thread pool: {}", e);
+                return;
+            }
+        };
+        println!("INFO: Thumbnail pool started with {} threads", config.thumbnail_threads);
+
         tauri::async_runtime::spawn(async move {
+            // Job reporting the regular backlog, from its first batch until it is empty
+            let mut backlog_job: Option<crate::jobs::JobContext> = None;
+            let mut backlog_done: i64 = 0;
+
             loop {
                 // 1. Check Priority Queue First
                 let priority_ids = priority_state.priority_ids.lock().unwrap().iter().cloned().collect::<Vec<i64>>();
@@ -56,7 +75,32 @@ impl ThumbnailWorker {
                     }
                 }
 
-                // 2. If no priority work, check regular queue
+                // 2. Then thumbnails prefetched for the folder the user is about to open
+                if images.is_empty() {
+                    let (generation, prefetch_ids) = priority_state.prefetch_ids();
+                    if !prefetch_ids.is_empty() {
+                        if let Ok(prefetch_imgs) = db.get_images_needing_thumbnails_by_ids(&prefetch_ids).await {
+                            if prefetch_imgs.is_empty() {
+                                priority_state.finish_prefetch(generation);
+                            } else {
+                                images = prefetch_imgs;
+                                is_priority_batch = true;
+                            }
+                        }
+                    }
+                }
+
+                // 3. Then the rest of the folder or filter the user has open
+                if images.is_empty() {
+                    if let Some(context) = priority_state.active_context() {
+                        match db.get_images_needing_thumbnails_in_filter(&context, config.indexer_batch_size).await {
+                            Ok(imgs) => images = imgs,
+                            Err(e) => eprintln!("WARN: Could not query the active context for thumbnails: {}", e),
+                        }
+                    }
+                }
+
+                // 4. If no priority work, check regular queue
                 if images.is_empty() {
                      match db.get_images_needing_thumbnails(config.indexer_batch_size).await {
                         Ok(imgs) => {
@@ -71,8 +115,31 @@ impl ThumbnailWorker {
                 }
 
                 if images.is_empty() {
+                    if let Some(job) = backlog_job.take() {
+                        job.finish(Ok(Some(serde_json::json!({ "thumbnails": backlog_done })))).await;
+                        backlog_done = 0;
+                    }
+                    // Nothing to render: rename a few path-keyed thumbnails meanwhile
+                    if migrate_legacy_thumbnails(&db, &thumb_dir).await > 0 {
+                        continue;
+                    }
+                    // Delete the thumbnails of files that changed or left the library
+                    if delete_stale_thumbnails(&db, &thumb_dir).await > 0 {
+                        continue;
+                    }
+                    // Hash thumbnails rendered before perceptual hashes were kept
+                    if crate::thumbnails::perceptual::hash_missing(&db, &thumb_dir).await > 0 {
+                        continue;
+                    }
+                    // Then render quick RAW and PSD thumbnails again, while nothing else runs
+                    if config.upgrade_thumbnails
+                        && crate::thumbnails::upgrade::is_machine_idle(&priority_state)
+                        && crate::thumbnails::upgrade::upgrade_batch(&app, &db, &thumb_dir).await > 0
+                    {
+                        continue;
+                    }
                     // No work at all
-                    sleep(Duration::from_secs(2)).await;
+                    priority_state.wait_for_work(Duration::from_secs(2)).await;
                     continue;
                 }
 
@@ -81,43 +148,60 @@ impl ThumbnailWorker {
                         "DEBUG: Found {} images needing thumbnails. Starting batch...",
                         images.len()
                     );
+                    if backlog_job.is_none() {
+                        if let Some(queue) = app.try_state::<Arc<crate::jobs::JobQueue>>() {
+                            backlog_job = queue.track("thumbnails", "Thumbnail backlog").await;
+                        }
+                    }
                 }
+                let batch_len = images.len() as i64;
+
+                // Files of remote locations are fetched into their mirror first
+                crate::storage::fetch_missing(&db, images.iter().map(|(_, path)| path.as_str())).await;
+
+                // Thumbnails the peer instance already rendered are downloaded instead
+                let from_peer = crate::peer::client::fetch_thumbnails(&db, &thumb_dir, &images).await;
+                images.retain(|(id, _)| !from_peer.iter().any(|(peer_id, _)| peer_id == id));
+
+                // Media the probe found cover art in is rendered from it
+                let ids: Vec<i64> = images.iter().map(|(id, _)| *id).collect();
+                let cover_art: std::collections::HashSet<i64> =
+                    db.get_cover_art_ids(&ids).await.unwrap_or_default().into_iter().collect();
 
                 // Clone thumb_dir for the move closure
                 let thumb_dir_clone = thumb_dir.clone();
-                let num_threads = config.thumbnail_threads;
+                let pool_for_blocking = pool.clone();
                 let app_for_blocking = app.clone();
 
                 // Use a blocking thread for CPU-intensive work
-                let db_updates = tauri::async_runtime::spawn_blocking(move || {
+                let mut db_updates = tauri::async_runtime::spawn_blocking(move || {
                     use rayon::prelude::*;
-                    use rayon::ThreadPoolBuilder;
-
-                    // Create a limited thread pool
-                    let pool = ThreadPoolBuilder::new()
-                        .num_threads(num_threads)
-                        .build()
-                        .unwrap();
 
-                    pool.install(|| {
+                    pool_for_blocking.install(|| {
                         images
                             .par_iter()
                             .map(|(id, img_path)| {
-                                let input_path = Path::new(&img_path);
+                                let input_path = &crate::paths::from_db(img_path);
                                 if !input_path.exists() {
-                                    return (*id, Err("File not found".to_string()));
+                                    return (*id, Err(Failure::new("File not found".to_string())));
                                 }
 
-                                let thumb_name = get_thumbnail_filename(&img_path);
+                                let has_cover_art = cover_art.contains(id);
+                                let thumb_name = get_thumbnail_filename(img_path, GRID_THUMBNAIL_SIZE);
+                                let thumb_name = if has_cover_art { cover_art_filename(&thumb_name) } else { thumb_name };
 
+                                // A duplicate of this file may have rendered it already
+                                if thumb_dir_clone.join(&thumb_name).exists() {
+                                    return (*id, Ok(thumb_name));
+                                }
 
                                 // Generate thumbnail
-                                match generate_thumbnail(Some(&app_for_blocking), input_path, &thumb_dir_clone, &thumb_name, 300) {
+                                match generate_thumbnail(Some(&app_for_blocking), input_path, &thumb_dir_clone, &thumb_name, GRID_THUMBNAIL_SIZE, has_cover_art) {
                                     Ok(generated_filename) => {
                                         (*id, Ok(generated_filename))
                                     }
                                     Err(e) => {
-                                        (*id, Err(e.to_string()))
+                                        (*id, Err(Failure::check_header(input_path, e.to_string())))
                                     }
                                 }
                             })
@@ -129,6 +213,12 @@ impl ThumbnailWorker {
                     eprintln!("Blocking task failed: {}", e);
                     Vec::new()
                 });
+                db_updates.extend(from_peer.into_iter().map(|(id, filename)| (id, Ok(filename))));
+
+                if !is_priority_batch {
+                    let (reuses, grows) = crate::thumbnails::native::encoder_pool_stats();
+                    println!("DEBUG: Thumbnail buffers reused {} times, grown {} times", reuses, grows);
+                }
 
                 #[derive(serde::Serialize, Clone)]
                 struct ThumbnailPayload {
@@ -137,12 +227,17 @@ impl ThumbnailWorker {
                 }
 
                 // Perform DB updates sequentially (async)
+                let mut rendered = Vec::new();
                 for (id, result) in db_updates {
                     match result {
                         Ok(filename) => {
                             if let Err(e) = db.update_thumbnail_path(id, &filename).await {
                                 eprintln!("Error updating DB for thumbnail: {}", e);
                             } else {
+                                // Shared file icons say nothing about the picture
+                                if !filename.contains('/') {
+                                    rendered.push((id, filename.clone()));
+                                }
                                 let payload = ThumbnailPayload {
                                     id,
                                     path: filename.clone(),
@@ -150,7 +245,14 @@ impl ThumbnailWorker {
                                 let _ = app.emit("thumbnail:ready", payload);
                             }
                         }
-                        Err(err_msg) => {
+                        Err(failure) if failure.header_mismatch || looks_like_corruption(&failure.message) => {
+                            match db.record_corrupt_failure(id, &failure.message, failure.header_mismatch).await {
+                                Ok(true) => eprintln!("Thumbnail error for ID {} (corrupt suspected): {}", id, failure.message),
+                                Ok(false) => eprintln!("Thumbnail error for ID {}: {}", id, failure.message),
+                                Err(e) => eprintln!("Failed to record thumbnail error in DB: {}", e),
+                            }
+                        }
+                        Err(Failure { message: err_msg, .. }) => {
                             eprintln!("Thumbnail error for ID {}: {}", id, err_msg);
                             if let Err(e) = db.record_thumbnail_error(id, err_msg).await {
                                 eprintln!("Failed to record thumbnail error in DB: {}", e);
@@ -159,6 +261,15 @@ impl ThumbnailWorker {
                     }
                 }
 
+                crate::thumbnails::perceptual::hash_thumbnails(&db, &thumb_dir, rendered).await;
+
+                if !is_priority_batch {
+                    backlog_done += batch_len;
+                    if let Some(job) = &backlog_job {
+                        job.progress(backlog_done, None, None).await;
+                    }
+                }
+
                 // If we processed a priority batch, we loop immediately to check for more or resume normal work.
                 // If it was a normal batch, we also loop immediately but maybe yield.
                 if !is_priority_batch {
@@ -171,3 +282,271 @@ impl ThumbnailWorker {
         });
     }
 }
+
+/// Path-keyed thumbnails migrated per idle pass of the worker.
+const MIGRATION_BATCH: i64 = 50;
+
+/// Moves a batch of thumbnails from their path-keyed name to their content
+/// key, so existing caches migrate gradually instead of being rendered
+/// again all at once. A thumbnail older than its file may be stale and is
+/// dropped for regeneration instead. Returns the number of images handled.
+async fn migrate_legacy_thumbnails(db: &Db, thumbnails_dir: &Path) -> usize {
+    let images = match db.get_legacy_thumbnails(MIGRATION_BATCH).await {
+        Ok(images) => images,
+        Err(e) => {
+            eprintln!("WARN: Could not list path-keyed thumbnails: {}", e);
+            return 0;
+        }
+    };
+    let images: Vec<_> = images
+        .into_iter()
+        .filter(|(_, _, thumbnail)| crate::thumbnails::is_legacy_thumbnail_filename(thumbnail))
+        .collect();
+    if images.is_empty() {
+        return 0;
+    }
+
+    let dir = thumbnails_dir.to_path_buf();
+    let migrated = tauri::async_runtime::spawn_blocking(move || {
+        images
+            .into_iter()
+            .map(|(id, path, legacy)| (id, migrate_legacy_thumbnail(&dir, &path, &legacy)))
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+
+    let handled = migrated.len();
+    for (id, filename) in migrated {
+        let result = match filename {
+            Some(filename) => db.update_thumbnail_path(id, &filename).await,
+            None => db.clear_thumbnail_path(id).await,
+        };
+        if let Err(e) = result {
+            eprintln!("WARN: Could not migrate the thumbnail of image {}: {}", id, e);
+        }
+    }
+    println!("INFO: Migrated {} path-keyed thumbnails", handled);
+    handled
+}
+
+/// Stale thumbnails looked at per idle pass of the worker.
+const STALE_BATCH: i64 = 200;
+
+/// Deletes a batch of thumbnail files no image uses anymore (see
+/// `Db::take_stale_thumbnails`). Returns the number of files deleted.
+async fn delete_stale_thumbnails(db: &Db, thumbnails_dir: &Path) -> usize {
+    let names = match db.take_stale_thumbnails(STALE_BATCH).await {
+        Ok(names) => names,
+        Err(e) => {
+            eprintln!("WARN: Could not list stale thumbnails: {}", e);
+            return 0;
+        }
+    };
+    if names.is_empty() {
+        return 0;
+    }
+    let dir = thumbnails_dir.to_path_buf();
+    tauri::async_runtime::spawn_blocking(move || {
+        names
+            .iter()
+            .filter(|name| match std::fs::remove_file(dir.join(name)) {
+                Ok(()) => true,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+                Err(e) => {
+                    eprintln!("WARN: Could not delete stale thumbnail {}: {}", name, e);
+                    false
+                }
+            })
+            .count()
+    })
+    .await
+    .unwrap_or(0)
+}
+
+/// Renames one legacy thumbnail. `None` when it must be rendered again:
+/// missing, older than its file, or the file can't be keyed.
+fn migrate_legacy_thumbnail(thumbnails_dir: &Path, image_path: &str, legacy: &str) -> Option<String> {
+    let legacy_path = thumbnails_dir.join(legacy);
+    let fresh = match (std::fs::metadata(&legacy_path), std::fs::metadata(crate::paths::from_db(image_path))) {
+        (Ok(thumbnail), Ok(source)) => match (thumbnail.modified(), source.modified()) {
+            (Ok(rendered), Ok(changed)) => rendered >= changed,
+            _ => false,
+        },
+        _ => false,
+    };
+    let key = crate::thumbnails::thumbnail_key(&crate::paths::from_db(image_path)).ok().filter(|_| fresh);
+    let Some((key, modified)) = key else {
+        let _ = std::fs::remove_file(&legacy_path);
+        return None;
+    };
+
+    let filename = crate::thumbnails::thumbnail_filename(&key, modified, GRID_THUMBNAIL_SIZE);
+    let target = thumbnails_dir.join(&filename);
+    let moved = if target.exists() {
+        // A duplicate got there first
+        std::fs::remove_file(&legacy_path)
+    } else {
+        std::fs::rename(&legacy_path, &target)
+    };
+    match moved {
+        Ok(()) => Some(filename),
+        Err(e) => {
+            eprintln!("WARN: Could not rename thumbnail {:?}: {}", legacy_path, e);
+            None
+        }
+    }
+}
+
+/// A thumbnail that could not be rendered.
+struct Failure {
+    message: String,
+    /// The file's header belongs to another format than its extension, so
+    /// the failure is down to the file rather than the decoder.
+    header_mismatch: bool,
+}
+
+impl Failure {
+    fn new(message: String) -> Self {
+        Failure { message, header_mismatch: false }
+    }
+
+    /// Failure of a decode, checked against the magic bytes of `path`.
+    fn check_header(path: &Path, message: String) -> Self {
+        match crate::formats::FileFormat::detect_mismatch(path) {
+            Some(format) => Failure {
+                message: format!("{} (the file's header is {}, not its extension)", message, format.name),
+                header_mismatch: true,
+            },
+            None => Failure::new(message),
+        }
+    }
+}
+
+/// Decoder messages that point at a damaged file rather than a missing
+/// dependency or a transient failure. Matched case-insensitively. Timeouts
+/// aren't among them: a busy machine makes healthy files time out too.
+const CORRUPTION_MARKERS: &[&str] = &[
+    "corrupt",
+    "truncated",
+    "unexpected end of file",
+    "unexpected eof",
+    "invalid data found when processing input",
+    "moov atom not found",
+    "invalid jpeg",
+    "invalid png",
+    "invalid signature",
+    "bad huffman",
+    "failed to fill whole buffer",
+];
+
+/// Decides whether a thumbnail error means the source file is probably corrupt.
+///
+/// Files failing this way repeatedly are flagged and skipped afterwards (see
+/// `Db::record_corrupt_failure`), instead of being retried on every pass.
+fn looks_like_corruption(error: &str) -> bool {
+    let error = error.to_lowercase();
+    CORRUPTION_MARKERS.iter().any(|marker| error.contains(marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_legacy_thumbnail() {
+        let dir = std::env::temp_dir().join(format!("mundam-thumb-migrate-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("photo.jpg");
+        std::fs::write(&source, b"pixels").unwrap();
+        let source = source.to_string_lossy().to_string();
+
+        // Rendered after the file was written: renamed to its content key
+        let legacy = crate::thumbnails::legacy_thumbnail_filename(&source);
+        std::fs::write(dir.join(&legacy), b"webp").unwrap();
+        let migrated = migrate_legacy_thumbnail(&dir, &source, &legacy).unwrap();
+        assert_eq!(migrated, get_thumbnail_filename(&source, GRID_THUMBNAIL_SIZE));
+        assert!(dir.join(&migrated).exists());
+        assert!(!dir.join(&legacy).exists());
+
+        // Missing thumbnail or missing file: rendered again
+        assert_eq!(migrate_legacy_thumbnail(&dir, &source, &legacy), None);
+        std::fs::write(dir.join(&legacy), b"webp").unwrap();
+        assert_eq!(migrate_legacy_thumbnail(&dir, "/missing/photo.jpg", &legacy), None);
+        assert!(!dir.join(&legacy).exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_delete_stale_thumbnails() {
+        let library = crate::testkit::TestLibrary::open("stale-thumbnails").await;
+        let db = &library.db;
+        let dir = std::env::temp_dir().join(format!("mundam-stale-thumbs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        library.seed_images(&["1.jpg", "2.jpg", "3.jpg", "4.jpg"]).await;
+        for (id, thumbnail) in [(1, "a.webp"), (2, "shared.webp"), (3, "shared.webp"), (4, "extensions/icon_nef_300.webp")] {
+            sqlx::query("UPDATE images SET thumbnail_path = ? WHERE id = ?")
+                .bind(thumbnail)
+                .bind(id)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            std::fs::write(dir.join(thumbnail.replace("extensions/", "")), b"webp").unwrap();
+        }
+
+        // The file of image 1 changed, images 2 and 4 left the library
+        sqlx::query("UPDATE images SET thumbnail_path = NULL WHERE id = 1").execute(&db.pool).await.unwrap();
+        sqlx::query("DELETE FROM images WHERE id IN (2, 4)").execute(&db.pool).await.unwrap();
+        assert_eq!(delete_stale_thumbnails(db, &dir).await, 1);
+        assert!(!dir.join("a.webp").exists());
+        assert!(dir.join("shared.webp").exists(), "a copy still uses it");
+        assert!(dir.join("icon_nef_300.webp").exists());
+
+        sqlx::query("DELETE FROM images WHERE id = 3").execute(&db.pool).await.unwrap();
+        assert_eq!(delete_stale_thumbnails(db, &dir).await, 1);
+        assert!(!dir.join("shared.webp").exists());
+        assert_eq!(delete_stale_thumbnails(db, &dir).await, 0);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_after_repeated_failures() {
+        let library = crate::testkit::TestLibrary::open("corrupt-failures").await;
+        let db = &library.db;
+        sqlx::query("INSERT INTO folders (id, path, name) VALUES (1, '/lib', 'lib')").execute(&db.pool).await.unwrap();
+        for id in [1, 2] {
+            sqlx::query(
+                "INSERT INTO images (id, folder_id, path, filename, created_at, modified_at)
+                 VALUES (?, 1, ?, 'x.png', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')"
+            )
+            .bind(id)
+            .bind(format!("/lib/{}.png", id))
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        }
+
+        assert!(!db.record_corrupt_failure(1, "truncated", false).await.unwrap(), "one failure is not enough");
+        assert!(db.record_corrupt_failure(1, "truncated", false).await.unwrap());
+        assert!(db.record_corrupt_failure(2, "invalid png signature", true).await.unwrap());
+
+        // Format and size were never read
+        let corrupt = db.get_corrupt_files().await.unwrap();
+        assert_eq!(corrupt.len(), 2);
+        assert!(corrupt.iter().all(|file| file.format.is_none() && file.size.is_none()));
+        assert_eq!(corrupt.iter().find(|file| file.id == 1).unwrap().thumbnail_attempts, 2);
+    }
+
+    #[test]
+    fn test_corruption_classification() {
+        assert!(looks_like_corruption("FFmpeg failed: moov atom not found"));
+        assert!(looks_like_corruption("Format error decoding Png: Invalid PNG signature."));
+        assert!(!looks_like_corruption("FFmpeg timed out after 15s: /a/b.mov"));
+        assert!(!looks_like_corruption("File not found"));
+        assert!(!looks_like_corruption("FFmpeg not found (neither bundled nor in system PATH)"));
+    }
+}
@@ -31,17 +31,30 @@ impl TranscodeCache {
         let mut hasher = DefaultHasher::new();
         source.to_string_lossy().hash(&mut hasher);
         (quality as u8).hash(&mut hasher);
+        Self::hash_modified(source, &mut hasher);
 
-        // Also hash the file modification time for cache invalidation
+        format!("{:016x}", hasher.finish())
+    }
+
+    /// Cache key for a stream-copy remux (quality doesn't apply)
+    fn generate_remux_key(source: &Path) -> String {
+        let mut hasher = DefaultHasher::new();
+        source.to_string_lossy().hash(&mut hasher);
+        "remux".hash(&mut hasher);
+        Self::hash_modified(source, &mut hasher);
+
+        format!("{:016x}", hasher.finish())
+    }
+
+    /// Also hash the file modification time for cache invalidation
+    fn hash_modified(source: &Path, hasher: &mut DefaultHasher) {
         if let Ok(metadata) = fs::metadata(source) {
             if let Ok(modified) = metadata.modified() {
                 if let Ok(duration) = modified.duration_since(SystemTime::UNIX_EPOCH) {
-                    duration.as_secs().hash(&mut hasher);
+                    duration.as_secs().hash(hasher);
                 }
             }
         }
-
-        format!("{:016x}", hasher.finish())
     }
 
     /// Get the cache file path for a source file
@@ -71,6 +84,24 @@ impl TranscodeCache {
         None
     }
 
+    /// Get the cache path of the progressive (ADTS AAC) audio transcode
+    pub fn get_progressive_path(&self, source: &Path, quality: TranscodeQuality) -> PathBuf {
+        let key = Self::generate_cache_key(source, quality);
+        self.cache_dir.join(format!("{}.aac", key))
+    }
+
+    /// Get the cache path of the MP4 remux of a source file
+    pub fn get_remux_path(&self, source: &Path) -> PathBuf {
+        self.cache_dir.join(format!("{}.remux.mp4", Self::generate_remux_key(source)))
+    }
+
+    /// Get the cached remux if it exists
+    pub fn get_remux(&self, source: &Path) -> Option<PathBuf> {
+        let cache_path = self.get_remux_path(source);
+        let metadata = fs::metadata(&cache_path).ok()?;
+        (metadata.is_file() && metadata.len() > 1024).then_some(cache_path)
+    }
+
     /// Clean up old cache entries
     /// Returns number of files deleted
     pub fn cleanup(&self, max_age_days: u64) -> usize {
@@ -230,6 +261,11 @@ mod tests {
         // Different qualities should produce different keys
         assert_ne!(key1, key2);
 
+        // Remuxes never collide with transcodes of the same file
+        let remux = TranscodeCache::generate_remux_key(Path::new("/test/file.mkv"));
+        assert_ne!(remux, key1);
+        assert_ne!(remux, key2);
+
         // Same input should produce same key
         let key3 = TranscodeCache::generate_cache_key(
             Path::new("/test/file.mkv"),
@@ -0,0 +1,97 @@
+//! Containers and codecs the WebView can play, as reported by the frontend.
+//!
+//! WebViews differ: WKWebView plays HEVC and WebView2 usually doesn't, while
+//! WebKitGTK depends on the GStreamer plugins installed. The formats table
+//! can only say what plays *somewhere*, so the frontend probes its WebView
+//! once with `canPlayType` and registers the result. Until it has, decisions
+//! fall back to the table's `PlaybackStrategy`.
+
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::formats::FileFormat;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WebviewCodecs {
+    /// Container MIME types the WebView plays, such as `video/mp4`.
+    pub containers: Vec<String>,
+    /// Codecs it decodes, by FFprobe name: `h264`, `hevc`, `opus`, ...
+    /// `pcm` stands for every `pcm_*` variant.
+    pub codecs: Vec<String>,
+}
+
+impl WebviewCodecs {
+    /// Whether the WebView plays the container of `format`.
+    pub fn plays_container(&self, format: &FileFormat) -> bool {
+        format
+            .mime_types
+            .iter()
+            .any(|mime| self.containers.iter().any(|c| c.eq_ignore_ascii_case(mime)))
+    }
+
+    /// Whether the WebView decodes every stream. A missing stream is fine.
+    pub fn plays_codecs(&self, video: Option<&str>, audio: Option<&str>) -> bool {
+        [video, audio].into_iter().flatten().all(|codec| self.plays_codec(codec))
+    }
+
+    fn plays_codec(&self, codec: &str) -> bool {
+        let codec = codec.to_lowercase();
+        let codec = match codec.as_str() {
+            "avc" | "avc1" => "h264",
+            "h265" | "hvc1" | "hev1" => "hevc",
+            c if c.starts_with("pcm_") => "pcm",
+            c => c,
+        };
+        self.codecs.iter().any(|c| c.eq_ignore_ascii_case(codec))
+    }
+}
+
+static REGISTERED: RwLock<Option<WebviewCodecs>> = RwLock::new(None);
+
+/// Records what the WebView plays, replacing any earlier report.
+pub fn register(codecs: WebviewCodecs) {
+    println!(
+        "INFO: WebView plays {} containers and codecs {:?}",
+        codecs.containers.len(),
+        codecs.codecs
+    );
+    *REGISTERED.write().unwrap_or_else(|e| e.into_inner()) = Some(codecs);
+}
+
+/// What the WebView plays, once the frontend has reported it.
+pub fn registered() -> Option<WebviewCodecs> {
+    REGISTERED.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wkwebview() -> WebviewCodecs {
+        WebviewCodecs {
+            containers: vec!["video/mp4".into(), "video/quicktime".into(), "audio/mpeg".into()],
+            codecs: vec!["h264".into(), "hevc".into(), "aac".into(), "pcm".into()],
+        }
+    }
+
+    #[test]
+    fn test_plays_container() {
+        let mp4 = FileFormat::detect(std::path::Path::new("clip.mp4")).unwrap();
+        let mkv = FileFormat::detect(std::path::Path::new("clip.mkv")).unwrap();
+        assert!(wkwebview().plays_container(mp4));
+        assert!(!wkwebview().plays_container(mkv));
+    }
+
+    #[test]
+    fn test_plays_codecs() {
+        let codecs = wkwebview();
+        assert!(codecs.plays_codecs(Some("hevc"), Some("aac")));
+        assert!(codecs.plays_codecs(Some("avc1"), None));
+        assert!(codecs.plays_codecs(None, Some("pcm_s24le")));
+        assert!(!codecs.plays_codecs(Some("vp9"), Some("aac")));
+        assert!(!codecs.plays_codecs(Some("h264"), Some("opus")));
+        assert!(!WebviewCodecs::default().plays_codecs(Some("hevc"), None));
+    }
+}
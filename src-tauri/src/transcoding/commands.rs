@@ -1,33 +1,60 @@
-use std::path::{Path, PathBuf};
-use tauri::{AppHandle, Manager};
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
 
+use crate::db::Db;
 use crate::error::{AppError, AppResult};
+use crate::library::derivatives::{self, DerivativeKind};
+use crate::streaming::stats::{self, TranscodeStats};
 use super::cache::TranscodeCache;
+use super::capabilities::{self, WebviewCodecs};
 use super::detector;
 use super::ffmpeg_pipe::FfmpegTranscoder;
 use super::quality::TranscodeQuality;
 
+/// Records the containers and codecs the frontend's WebView plays. Called
+/// once on startup; playback decisions use it from then on.
+#[tauri::command]
+pub fn register_webview_codecs(codecs: WebviewCodecs) {
+    capabilities::register(codecs);
+}
+
+/// Whether `path` must be transcoded: its container isn't played by the
+/// WebView, or its probed codecs aren't decoded by it.
+async fn must_transcode(db: &Db, path: &Path) -> AppResult<bool> {
+    if detector::needs_transcoding(path) {
+        return Ok(true);
+    }
+    let Some(codecs) = capabilities::registered() else {
+        return Ok(false);
+    };
+    Ok(match db.get_media_codecs(&path.to_string_lossy()).await? {
+        Some((video, audio)) => !codecs.plays_codecs(video.as_deref(), audio.as_deref()),
+        None => false,
+    })
+}
+
 /// Check if a file needs transcoding for playback
 #[tauri::command]
-pub fn needs_transcoding(path: String) -> bool {
-    detector::needs_transcoding(Path::new(&path))
+pub async fn needs_transcoding(path: String, db: State<'_, Arc<Db>>) -> AppResult<bool> {
+    must_transcode(&db, &crate::paths::from_db(&path)).await
 }
 
 /// Check if a file is natively supported
 #[tauri::command]
 pub fn is_native_format(path: String) -> bool {
-    detector::is_native_format(Path::new(&path))
+    detector::is_native_format(&crate::paths::from_db(&path))
 }
 
 /// Get the appropriate stream URL for a file
 /// Returns `audio://` or `video://` for native formats
 /// Returns `audio-stream://` or `video-stream://` for transcoded formats
 #[tauri::command]
-pub fn get_stream_url(path: String, quality: Option<String>) -> String {
-    let file_path = Path::new(&path);
+pub async fn get_stream_url(path: String, quality: Option<String>, db: State<'_, Arc<Db>>) -> AppResult<String> {
+    let file_path = &crate::paths::from_db(&path);
     let quality_param = quality.unwrap_or_else(|| "preview".to_string());
 
-    if detector::needs_transcoding(file_path) {
+    let url = if must_transcode(&db, file_path).await? {
         // Use streaming protocol
         let media_type = detector::get_media_type(file_path);
         match media_type {
@@ -51,7 +78,14 @@ pub fn get_stream_url(path: String, quality: Option<String>) -> String {
                 format!("video://localhost/{}", urlencoding::encode(&path))
             }
         }
-    }
+    };
+    Ok(url)
+}
+
+/// Token the frontend must send to the HLS streaming server
+#[tauri::command]
+pub fn get_stream_token() -> String {
+    crate::streaming::auth::access_token().to_string()
 }
 
 /// Get available quality options
@@ -73,10 +107,11 @@ pub fn get_quality_options() -> Vec<QualityOption> {
 #[tauri::command]
 pub async fn transcode_file(
     app: AppHandle,
+    db: tauri::State<'_, std::sync::Arc<crate::db::Db>>,
     path: String,
     quality: Option<String>,
 ) -> AppResult<String> {
-    let file_path = PathBuf::from(&path);
+    let file_path = crate::paths::from_db(&path);
     let quality = quality
         .and_then(|q| TranscodeQuality::from_str(&q))
         .unwrap_or_default();
@@ -87,14 +122,16 @@ pub async fn transcode_file(
         .app_local_data_dir()?;
 
     let cache = TranscodeCache::new(&app_data);
+    crate::peer::client::fetch_transcode(&db, &cache, &file_path, quality).await;
     let transcoder = FfmpegTranscoder::new_with_app(cache, &app);
 
     // Check if FFmpeg is available
     if !transcoder.is_available() {
-        return Err(AppError::Transcoding("FFmpeg is not installed or not found in PATH".to_string()));
+        return Err(AppError::FfmpegMissing);
     }
 
     // Transcode synchronously (in background thread)
+    let source = file_path.clone();
     let result = tokio::task::spawn_blocking(move || {
         transcoder.transcode_sync(&file_path, quality)
     })
@@ -102,7 +139,10 @@ pub async fn transcode_file(
     .map_err(|e| AppError::Internal(e.to_string()))?;
 
     match result {
-        Ok(output_path) => Ok(output_path.to_string_lossy().to_string()),
+        Ok(output_path) => {
+            derivatives::record_for_source(&app, &source, DerivativeKind::Transcode, &output_path, Some(quality.label()));
+            Ok(output_path.to_string_lossy().to_string())
+        }
         Err(e) => Err(AppError::Transcoding(e.to_string())),
     }
 }
@@ -110,7 +150,7 @@ pub async fn transcode_file(
 /// Check if a transcoded version is already cached
 #[tauri::command]
 pub fn is_cached(app: AppHandle, path: String, quality: Option<String>) -> bool {
-    let file_path = Path::new(&path);
+    let file_path = &crate::paths::from_db(&path);
     let quality = quality
         .and_then(|q| TranscodeQuality::from_str(&q))
         .unwrap_or_default();
@@ -154,6 +194,13 @@ pub fn clear_cache(app: AppHandle) -> AppResult<usize> {
     Ok(cache.clear_all())
 }
 
+/// Timing of the segments streamed for file `id`: realtime factor, time
+/// waiting for FFmpeg, failures and the encoders used
+#[tauri::command]
+pub async fn get_transcode_stats(db: State<'_, Arc<Db>>, id: i64) -> AppResult<TranscodeStats> {
+    Ok(stats::summarize(id, db.get_segment_timings(id).await?))
+}
+
 /// Check if FFmpeg is available
 #[tauri::command]
 pub fn ffmpeg_available(app: AppHandle) -> bool {
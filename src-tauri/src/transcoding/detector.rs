@@ -15,14 +15,25 @@ pub fn needs_transcoding(path: &Path) -> bool {
 }
 
 /// Check if a file is natively supported (no transcoding needed)
+///
+/// Once the frontend has registered what its WebView plays, that decides for
+/// audio and video containers; until then the format's `PlaybackStrategy` does.
 pub fn is_native_format(path: &Path) -> bool {
-    if let Some(format) = crate::formats::FileFormat::detect(path) {
-        matches!(format.playback, PlaybackStrategy::Native)
-    } else {
-        false
+    let Some(format) = crate::formats::FileFormat::detect(path) else {
+        return false;
+    };
+    match super::capabilities::registered() {
+        Some(codecs) if is_playable_media(format) => codecs.plays_container(format),
+        _ => matches!(format.playback, PlaybackStrategy::Native),
     }
 }
 
+/// Audio and video formats that play at all, natively or transcoded.
+fn is_playable_media(format: &crate::formats::FileFormat) -> bool {
+    matches!(format.type_category, FormatMediaType::Audio | FormatMediaType::Video)
+        && !matches!(format.playback, PlaybackStrategy::None)
+}
+
 /// Determine the media type from file extension
 pub fn get_media_type(path: &Path) -> MediaType {
     if let Some(format) = crate::formats::FileFormat::detect(path) {
@@ -37,7 +48,6 @@ pub fn get_media_type(path: &Path) -> MediaType {
 }
 
 /// Check if a file extension needs audio-only transcoding
-#[allow(dead_code)]
 pub fn is_audio_transcode(path: &Path) -> bool {
     if let Some(format) = crate::formats::FileFormat::detect(path) {
         matches!(format.type_category, FormatMediaType::Audio) &&
@@ -48,7 +58,6 @@ pub fn is_audio_transcode(path: &Path) -> bool {
 }
 
 /// Check if a file extension needs video transcoding
-#[allow(dead_code)]
 pub fn is_video_transcode(path: &Path) -> bool {
     if let Some(format) = crate::formats::FileFormat::detect(path) {
         matches!(format.type_category, FormatMediaType::Video) &&
@@ -1,9 +1,14 @@
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::time::Duration;
 
 use super::quality::TranscodeQuality;
 use super::detector::{MediaType, get_media_type};
 use super::cache::TranscodeCache;
+use crate::streaming::process_manager::{self, JobKind};
+
+/// Hard limit for a full-file transcode before the process is killed.
+const TRANSCODE_TIMEOUT: Duration = Duration::from_secs(2 * 60 * 60);
 
 // TranscodeStatus removed as it was unused
 
@@ -63,10 +68,16 @@ impl FfmpegTranscoder {
 
         // Build FFmpeg command based on media type
         let media_type = get_media_type(source);
-        let mut cmd = self.build_ffmpeg_command(source, &output, quality, media_type);
+        let cmd = self.build_ffmpeg_command(source, &output, quality, media_type);
 
-        // Execute and capture output
-        let result = cmd.output().map_err(|e| TranscodeError::FfmpegError(e.to_string()))?;
+        // Execute under the FFmpeg supervisor so stuck transcodes get killed
+        let result = process_manager::run_supervised(
+            cmd,
+            JobKind::Transcode,
+            &source.to_string_lossy(),
+            TRANSCODE_TIMEOUT,
+        )
+        .map_err(|e| TranscodeError::FfmpegError(e.to_string()))?;
 
         if result.status.success() && output.exists() {
             Ok(output)
@@ -81,6 +92,64 @@ impl FfmpegTranscoder {
         }
     }
 
+    /// Copy the video/audio streams into an MP4 without re-encoding.
+    ///
+    /// Used for containers the WebView can't open whose codecs it can
+    /// (see `probe::is_remux_compatible`). Output goes to a temp file first so
+    /// a partial remux is never served from the cache.
+    pub fn remux_sync(&self, source: &Path) -> Result<PathBuf, TranscodeError> {
+        if let Some(cached) = self.cache.get_remux(source) {
+            return Ok(cached);
+        }
+
+        if !source.exists() {
+            return Err(TranscodeError::SourceNotFound(source.to_path_buf()));
+        }
+
+        let output = self.cache.get_remux_path(source);
+        let partial = output.with_extension("part");
+
+        let mut cmd = Command::new(&self.ffmpeg_path);
+        cmd.arg("-y")
+            .arg("-hide_banner")
+            .arg("-loglevel").arg("warning")
+            .arg("-i")
+            .arg(source)
+            .args([
+                "-map", "0:v:0?",
+                "-map", "0:a:0?",
+                "-c", "copy",
+                "-tag:v", "avc1",             // Safari/WebKit wants avc1, not the MKV tag
+                "-movflags", "+faststart",    // Index up front for instant seeking
+                "-f", "mp4",
+            ])
+            .arg(&partial)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+
+        let result = process_manager::run_supervised(
+            cmd,
+            JobKind::Transcode,
+            &source.to_string_lossy(),
+            TRANSCODE_TIMEOUT,
+        )
+        .map_err(|e| TranscodeError::FfmpegError(e.to_string()))?;
+
+        if result.status.success() && partial.exists() {
+            std::fs::rename(&partial, &output)
+                .map_err(|e| TranscodeError::TranscodeFailed(e.to_string()))?;
+            Ok(output)
+        } else {
+            let _ = std::fs::remove_file(&partial);
+            let stderr = String::from_utf8_lossy(&result.stderr);
+            Err(TranscodeError::TranscodeFailed(format!(
+                "Remux exited with status: {:?}, stderr: {}",
+                result.status.code(),
+                stderr.chars().take(500).collect::<String>()
+            )))
+        }
+    }
+
     /// Build FFmpeg command for transcoding
     fn build_ffmpeg_command(
         &self,
@@ -7,6 +7,7 @@ pub mod quality;
 pub mod cache;
 pub mod ffmpeg_pipe;
 pub mod detector;
+pub mod capabilities;
 
 pub mod commands;
 
@@ -85,6 +85,9 @@ function App() {
         import('./core/store/formatStore').then(({ formatActions }) => {
             formatActions.initialize();
         });
+        import('./lib/hls-player').then(({ loadStreamToken }) => {
+            loadStreamToken();
+        });
 
         // Register Strategies
         dndRegistry.register('TAG', TagDropStrategy);
@@ -95,6 +98,20 @@ function App() {
             notification.success('Indexing Complete', 'Library update finished');
         });
 
+        // FFmpeg installed or removed while the app is running
+        listen<{ available: boolean; requeued: number }>('environment:ffmpeg', (event) => {
+            if (event.payload.available) {
+                notification.success(
+                    'FFmpeg Detected',
+                    event.payload.requeued > 0
+                        ? `Regenerating ${event.payload.requeued} thumbnails`
+                        : 'Video previews are available'
+                );
+            } else {
+                notification.warning('FFmpeg Unavailable', 'Video thumbnails and playback are limited');
+            }
+        });
+
         // Notify Splash Screen
         window.dispatchEvent(new CustomEvent('app-ready'));
 
@@ -156,37 +173,65 @@ function App() {
         }
     };
 
+    const handleOpenOtherLibrary = async () => {
+        const selected = await open({
+            directory: true,
+            multiple: false,
+            title: 'Select Library Folder'
+        });
+        if (typeof selected === 'string') {
+            await system.retryStartup(selected);
+        }
+    };
+
     return (
         <Show
             when={!system.loading()}
             fallback={<Loader fullscreen text="Initializing Mundam..." />}
         >
             <Show
-                when={system.rootPath()}
+                when={!system.startupError()}
                 fallback={
                     <div class="welcome-screen">
                         <img src={effectiveLogo()} alt="Mundam Logo" class="welcome-logo" />
-                        {/* <h1>Mundam</h1> */}
-                        <p>Start by choosing a folder to monitor for visual references.</p>
-                        <button class="primary-btn" onClick={handleSelectFolder}>
-                            Initialize Library
+                        <p>The library could not be opened.</p>
+                        <p>{system.startupError()?.error}</p>
+                        <button class="primary-btn" onClick={() => system.retryStartup()}>
+                            Retry
+                        </button>
+                        <button onClick={handleOpenOtherLibrary}>
+                            Open Different Library
                         </button>
                     </div>
                 }
             >
-                <AppShell
-                    sidebar={<LibrarySidebar />}
-                    inspector={<FileInspector />}
-                    statusbar={<GlobalStatusbar />}
+                <Show
+                    when={system.rootPath()}
+                    fallback={
+                        <div class="welcome-screen">
+                            <img src={effectiveLogo()} alt="Mundam Logo" class="welcome-logo" />
+                            {/* <h1>Mundam</h1> */}
+                            <p>Start by choosing a folder to monitor for visual references.</p>
+                            <button class="primary-btn" onClick={handleSelectFolder}>
+                                Initialize Library
+                            </button>
+                        </div>
+                    }
                 >
-                    <Viewport />
-                </AppShell>
-                <Sonner position="bottom-right" richColors />
-                <SettingsModal
-                    isOpen={isSettingsOpen()}
-                    onClose={() => setIsSettingsOpen(false)}
-                    initialTab="general"
-                />
+                    <AppShell
+                        sidebar={<LibrarySidebar />}
+                        inspector={<FileInspector />}
+                        statusbar={<GlobalStatusbar />}
+                    >
+                        <Viewport />
+                    </AppShell>
+                    <Sonner position="bottom-right" richColors />
+                    <SettingsModal
+                        isOpen={isSettingsOpen()}
+                        onClose={() => setIsSettingsOpen(false)}
+                        initialTab="general"
+                    />
+                </Show>
             </Show>
         </Show>
     );
@@ -2,6 +2,7 @@ import { Component } from 'solid-js';
 import { type ImageItem } from '../../../../types';
 import { Accordion, AudioPlayer } from '../../../ui';
 import { InspectorTags } from '../base/InspectorTags';
+import { Derivatives } from '../base/Derivatives';
 import { CommonMetadata } from '../base/CommonMetadata';
 import { useAudioSource } from '../../../../core/hooks/useAudioSource';
 import './AudioInspector.css';
@@ -27,6 +28,7 @@ export const AudioInspector: Component<AudioInspectorProps> = props => {
             <Accordion>
                 <CommonMetadata item={props.item} />
                 <InspectorTags itemId={props.item.id} />
+                <Derivatives itemId={props.item.id} />
             </Accordion>
         </div>
     );
@@ -1,4 +1,4 @@
-import { Component, createSignal, createEffect } from 'solid-js';
+import { Component, createSignal, createEffect, Show } from 'solid-js';
 import { Info, FileText, Calendar, HardDrive } from 'lucide-solid';
 import { AccordionItem } from '../../../ui/Accordion';
 import { Input } from '../../../ui/Input';
@@ -28,6 +28,18 @@ const formatDate = (dateStr: string) => {
     }
 };
 
+// Capture time as the camera's clock showed it, when it recorded its offset
+const formatCaptureTime = (dateStr: string, offsetMinutes: number | null | undefined) => {
+    const date = new Date(dateStr);
+    if (isNaN(date.getTime())) return '-';
+    if (offsetMinutes == null) return date.toLocaleString();
+    const local = new Date(date.getTime() + offsetMinutes * 60_000);
+    const sign = offsetMinutes < 0 ? '-' : '+';
+    const abs = Math.abs(offsetMinutes);
+    const offset = `${sign}${String(Math.floor(abs / 60)).padStart(2, '0')}:${String(abs % 60).padStart(2, '0')}`;
+    return `${local.toLocaleString(undefined, { timeZone: 'UTC' })} (UTC${offset})`;
+};
+
 export const CommonMetadata: Component<CommonMetadataProps> = props => {
     const [notes, setNotes] = createSignal(props.item?.notes || '');
     const lib = useLibrary();
@@ -78,6 +90,17 @@ export const CommonMetadata: Component<CommonMetadataProps> = props => {
                         {props.item ? formatBytes(props.item.size) : '-'}
                     </span>
                 </div>
+                <Show when={props.item?.captured_at}>
+                    {capturedAt => (
+                        <div class="inspector-meta-item">
+                            <span class="inspector-meta-label">Taken</span>
+                            <span class="inspector-meta-value">
+                                <Calendar size={10} />
+                                {formatCaptureTime(capturedAt(), props.item?.capture_offset)}
+                            </span>
+                        </div>
+                    )}
+                </Show>
                 <div class="inspector-meta-item">
                     <span class="inspector-meta-label">Created</span>
                     <span class="inspector-meta-value">
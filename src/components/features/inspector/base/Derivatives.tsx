@@ -0,0 +1,95 @@
+import { Component, createResource, For, Show } from 'solid-js';
+import { Copy, Loader2, Trash2 } from 'lucide-solid';
+import { AccordionItem } from '../../../ui/Accordion';
+import { Button } from '../../../ui/Button';
+import { useNotification } from '../../../../core/hooks';
+import { tauriService } from '../../../../core/tauri/services';
+import { isAppError } from '../../../../types';
+import './Derivatives.css';
+
+interface DerivativesProps {
+    itemId: number;
+}
+
+const KIND_LABELS = {
+    proxy: 'Proxy',
+    export: 'Export',
+    transcode: 'Transcode',
+    preview: 'Preview'
+};
+
+const formatBytes = (bytes: number) => {
+    if (bytes === 0) return '0 Bytes';
+    const k = 1024;
+    const sizes = ['Bytes', 'KB', 'MB', 'GB', 'TB'];
+    const i = Math.floor(Math.log(bytes) / Math.log(k));
+    return parseFloat((bytes / Math.pow(k, i)).toFixed(2)) + ' ' + sizes[i];
+};
+
+const fetchDerivatives = async (id: number) => {
+    try {
+        return await tauriService.getDerivatives(id);
+    } catch (e) {
+        console.error('Failed to load derivatives:', e);
+        return [];
+    }
+};
+
+/**
+ * Every version of the asset Mundam knows of: proxies, exports, transcodes
+ * and viewer conversions. Cleaning up deletes the ones that are made again
+ * on demand.
+ */
+export const Derivatives: Component<DerivativesProps> = props => {
+    const notification = useNotification();
+    const [derivatives, { refetch }] = createResource(() => props.itemId, fetchDerivatives);
+
+    const cleanUp = async () => {
+        try {
+            const cleanup = await tauriService.cleanUpDerivatives(props.itemId);
+            notification.success('Derived Files Cleaned Up', `${cleanup.deleted} deleted, ${formatBytes(cleanup.freedBytes)} freed`);
+        } catch (e) {
+            notification.error('Failed to Clean Up', isAppError(e) ? e.message : undefined);
+        }
+        refetch();
+    };
+
+    return (
+        <AccordionItem value="derivatives" title="Derived Files" icon={<Copy size={14} />} lazy>
+            <Show
+                when={!derivatives.loading}
+                fallback={
+                    <div class="inspector-loading-spinner">
+                        <Loader2 class="animate-spin" size={20} />
+                    </div>
+                }
+            >
+                <Show
+                    when={(derivatives() ?? []).length > 0}
+                    fallback={<div class="inspector-no-data">No proxies, exports or transcodes.</div>}
+                >
+                    <ul class="derivatives">
+                        <For each={derivatives()}>
+                            {derivative => (
+                                <li class="derivative" classList={{ missing: !derivative.exists }} title={derivative.path}>
+                                    <span class="inspector-meta-label">
+                                        {KIND_LABELS[derivative.kind]}
+                                        {derivative.label ? ` · ${derivative.label}` : ''}
+                                        {derivative.exists ? '' : ' · missing'}
+                                    </span>
+                                    <span class="inspector-meta-value">
+                                        {derivative.path.split(/[\\/]/).pop()}
+                                        {derivative.size != null ? ` (${formatBytes(derivative.size)})` : ''}
+                                    </span>
+                                </li>
+                            )}
+                        </For>
+                    </ul>
+                    <Button variant="ghost" size="sm" onClick={cleanUp} title="Delete transcodes and previews, which are made again when needed">
+                        <Trash2 size={14} /> Clean Up
+                    </Button>
+                </Show>
+            </Show>
+        </AccordionItem>
+    );
+};
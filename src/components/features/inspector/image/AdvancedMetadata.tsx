@@ -22,7 +22,7 @@ export const AdvancedMetadata: Component<AdvancedMetadataProps> = props => {
     const [exif] = createResource(() => props.item.path, fetchExif);
 
     return (
-        <AccordionItem value="advanced" title="Advanced Data" icon={<List size={14} />}>
+        <AccordionItem value="advanced" title="Advanced Data" icon={<List size={14} />} lazy>
             <Show
                 when={!exif.loading}
                 fallback={
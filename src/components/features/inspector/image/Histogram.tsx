@@ -0,0 +1,87 @@
+import { Component, createResource, For, Show } from 'solid-js';
+import { ChartColumn, Loader2 } from 'lucide-solid';
+import { AccordionItem } from '../../../ui/Accordion';
+import { tauriService, type Histogram as HistogramData } from '../../../../core/tauri/services';
+import './Histogram.css';
+
+interface HistogramProps {
+    itemId: number;
+}
+
+const fetchHistogram = async (id: number) => {
+    try {
+        return await tauriService.getImageHistogram(id);
+    } catch (e) {
+        console.error('Failed to load histogram:', e);
+        return null;
+    }
+};
+
+const CHANNELS = ['luminance', 'red', 'green', 'blue'] as const;
+
+/** Closed SVG path of a channel in a 256x100 box, scaled to the tallest bin. */
+const channelPath = (bins: number[], peak: number) => {
+    const points = bins.map((count, i) => `L${i},${(100 - (count / peak) * 100).toFixed(1)}`);
+    return `M0,100 ${points.join(' ')} L255,100 Z`;
+};
+
+const formatShare = (share: number) => `${(share * 100).toFixed(1)}%`;
+
+/**
+ * RGB and luminance histograms, computed by the backend from the display
+ * variant, with how much of the image is clipped at each end.
+ */
+export const Histogram: Component<HistogramProps> = props => {
+    const [histogram] = createResource(() => props.itemId, fetchHistogram);
+
+    // The end bins are left out of the scale, so a clipped image keeps a readable curve
+    const peak = (data: HistogramData) =>
+        Math.max(1, ...CHANNELS.flatMap(channel => data[channel].slice(1, 255)));
+
+    return (
+        <AccordionItem value="histogram" title="Histogram" icon={<ChartColumn size={14} />} lazy>
+            <Show
+                when={!histogram.loading}
+                fallback={
+                    <div class="inspector-loading-spinner">
+                        <Loader2 class="animate-spin" size={20} />
+                    </div>
+                }
+            >
+                <Show
+                    when={histogram() && histogram()!.pixels > 0}
+                    fallback={<div class="inspector-no-data">No histogram for this file.</div>}
+                >
+                    <svg class="histogram" viewBox="0 0 255 100" preserveAspectRatio="none">
+                        <For each={CHANNELS}>
+                            {channel => (
+                                <path
+                                    class={`histogram-${channel}`}
+                                    d={channelPath(histogram()![channel], peak(histogram()!))}
+                                />
+                            )}
+                        </For>
+                    </svg>
+                    <div class="inspector-grid">
+                        <div class="inspector-meta-item">
+                            <span class="inspector-meta-label">Clipped Shadows</span>
+                            <span class="inspector-meta-value">
+                                {formatShare(histogram()!.clipping.luminance.shadows)}
+                            </span>
+                        </div>
+                        <div class="inspector-meta-item">
+                            <span class="inspector-meta-label">Clipped Highlights</span>
+                            <span class="inspector-meta-value">
+                                {formatShare(histogram()!.clipping.luminance.highlights)}
+                            </span>
+                        </div>
+                        <div class="inspector-meta-item">
+                            <span class="inspector-meta-label">Mean Luminance</span>
+                            <span class="inspector-meta-value">{Math.round(histogram()!.meanLuminance)}</span>
+                        </div>
+                    </div>
+                </Show>
+            </Show>
+        </AccordionItem>
+    );
+};
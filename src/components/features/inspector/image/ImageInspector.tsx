@@ -3,7 +3,9 @@ import { type ImageItem } from '../../../../types';
 import { CommonMetadata } from '../base/CommonMetadata';
 import { ImageMetadata } from './ImageMetadata.tsx';
 import { InspectorTags } from '../base/InspectorTags';
+import { Derivatives } from '../base/Derivatives';
 import { AdvancedMetadata } from './AdvancedMetadata.tsx';
+import { Histogram } from './Histogram.tsx';
 import { Accordion } from '../../../ui/Accordion';
 import './ImageInspector.css';
 
@@ -29,7 +31,9 @@ export const ImageInspector: Component<ImageInspectorProps> = props => {
             <Accordion>
                 <CommonMetadata item={props.item} />
                 <ImageMetadata item={props.item} />
+                <Histogram itemId={props.item.id} />
                 <InspectorTags itemId={props.item.id} />
+                <Derivatives itemId={props.item.id} />
                 <AdvancedMetadata item={props.item} />
             </Accordion>
         </div>
@@ -0,0 +1,69 @@
+import { Component, createResource, For, Show } from 'solid-js';
+import { FileImage, Loader2 } from 'lucide-solid';
+import { AccordionItem } from '../../../ui/Accordion';
+import { tauriService } from '../../../../core/tauri/services';
+
+interface ModelDependenciesProps {
+    itemId: number;
+}
+
+const fetchDependencies = async (id: number) => {
+    try {
+        return await tauriService.getModelDependencies(id);
+    } catch (e) {
+        console.error('Failed to load model dependencies:', e);
+        return [];
+    }
+};
+
+/**
+ * Material libraries and textures the model references. Missing ones are
+ * why a preview renders untextured.
+ */
+export const ModelDependencies: Component<ModelDependenciesProps> = props => {
+    const [dependencies] = createResource(() => props.itemId, fetchDependencies);
+    const missing = () => (dependencies() ?? []).filter(d => !d.resolved).length;
+
+    return (
+        <AccordionItem
+            value="model-dependencies"
+            title="Materials & Textures"
+            icon={<FileImage size={14} />}
+            lazy
+        >
+            <Show
+                when={!dependencies.loading}
+                fallback={
+                    <div class="inspector-loading-spinner">
+                        <Loader2 class="animate-spin" size={20} />
+                    </div>
+                }
+            >
+                <Show
+                    when={(dependencies() ?? []).length > 0}
+                    fallback={<div class="inspector-no-data">No external materials or textures.</div>}
+                >
+                    <Show when={missing() > 0}>
+                        <div class="model-dependencies-summary">
+                            {missing()} missing, the preview renders without them.
+                        </div>
+                    </Show>
+                    <ul class="model-dependencies">
+                        <For each={dependencies()}>
+                            {dependency => (
+                                <li
+                                    class="model-dependency"
+                                    classList={{ missing: !dependency.resolved }}
+                                    title={dependency.resolved ?? 'Not found next to the model'}
+                                >
+                                    <span class="inspector-meta-label">{dependency.kind}</span>
+                                    <span class="inspector-meta-value">{dependency.reference}</span>
+                                </li>
+                            )}
+                        </For>
+                    </ul>
+                </Show>
+            </Show>
+        </AccordionItem>
+    );
+};
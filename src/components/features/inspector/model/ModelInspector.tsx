@@ -2,7 +2,9 @@ import { Component } from 'solid-js';
 import { type ImageItem } from '../../../../types';
 import { Accordion, AccordionItem } from '../../../ui/Accordion';
 import { InspectorTags } from '../base/InspectorTags';
+import { Derivatives } from '../base/Derivatives';
 import { CommonMetadata } from '../base/CommonMetadata';
+import { ModelDependencies } from './ModelDependencies';
 import { Box, Layers } from 'lucide-solid';
 import './ModelInspector.css';
 
@@ -51,7 +53,9 @@ export const ModelInspector: Component<ModelInspectorProps> = props => {
                         </div>
                     </div>
                 </AccordionItem>
+                <ModelDependencies itemId={props.item.id} />
                 <InspectorTags itemId={props.item.id} />
+                <Derivatives itemId={props.item.id} />
             </Accordion>
         </div>
     );
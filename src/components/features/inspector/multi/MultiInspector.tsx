@@ -1,6 +1,7 @@
 import { Component, For } from 'solid-js';
 import { type ImageItem } from '../../../../types';
 import { InspectorTags } from '../base/InspectorTags';
+import { SharedExif } from './SharedExif';
 import { Accordion, AccordionItem } from '../../../ui/Accordion';
 import { Layers } from 'lucide-solid';
 import './MultiInspector.css';
@@ -52,6 +53,7 @@ export const MultiInspector: Component<MultiInspectorProps> = props => {
 
             <Accordion>
                 <InspectorTags itemIds={props.items.map(i => i.id)} />
+                <SharedExif itemIds={props.items.map(i => i.id)} />
                 <AccordionItem value="info" title="Batch Actions" icon={<Layers size={14} />}>
                     <div class="inspector-field-group">
                         <p class="batch-hint">
@@ -0,0 +1,72 @@
+import { Component, createResource, Show, For } from 'solid-js';
+import { List, Loader2 } from 'lucide-solid';
+import { AccordionItem } from '../../../ui/Accordion';
+import { tagService } from '../../../../lib/tags';
+import '../image/AdvancedMetadata.css';
+
+/** Selections larger than this are summarized from their first items. */
+const MAX_EXIF_ITEMS = 200;
+
+interface SharedExifProps {
+    itemIds: number[];
+}
+
+/** Fields every file has, with their value when they all agree. */
+const summarize = (exifs: Record<string, string>[]): [string, string | null][] => {
+    if (exifs.length === 0) return [];
+    const [first, ...rest] = exifs;
+    return Object.keys(first)
+        .filter(key => rest.every(exif => key in exif))
+        .sort()
+        .map(key => [key, rest.every(exif => exif[key] === first[key]) ? first[key] : null]);
+};
+
+const fetchShared = async (ids: number[]) => {
+    try {
+        const batch = await tagService.getExifBatch(ids.slice(0, MAX_EXIF_ITEMS));
+        return summarize(Object.values(batch));
+    } catch (e) {
+        console.error('Failed to load EXIF:', e);
+        return [];
+    }
+};
+
+export const SharedExif: Component<SharedExifProps> = props => {
+    const [fields] = createResource(() => props.itemIds, fetchShared);
+
+    return (
+        <AccordionItem value="exif" title="Shared EXIF" icon={<List size={14} />} lazy>
+            <Show
+                when={!fields.loading}
+                fallback={
+                    <div class="inspector-loading-spinner">
+                        <Loader2 class="animate-spin" size={20} />
+                    </div>
+                }
+            >
+                <div class="inspector-field-group">
+                    <Show when={props.itemIds.length > MAX_EXIF_ITEMS}>
+                        <p class="batch-hint">Compared across the first {MAX_EXIF_ITEMS} items.</p>
+                    </Show>
+                    <Show
+                        when={(fields() || []).length > 0}
+                        fallback={<div class="inspector-no-data">No EXIF fields in common.</div>}
+                    >
+                        <div class="inspector-exif-grid">
+                            <For each={fields()}>
+                                {([key, value]) => (
+                                    <div class="inspector-meta-item">
+                                        <span class="inspector-meta-label">{key}</span>
+                                        <span class="inspector-meta-value exif-value">
+                                            {value ?? 'Mixed'}
+                                        </span>
+                                    </div>
+                                )}
+                            </For>
+                        </div>
+                    </Show>
+                </div>
+            </Show>
+        </AccordionItem>
+    );
+};
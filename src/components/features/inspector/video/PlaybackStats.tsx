@@ -0,0 +1,73 @@
+import { Component, createResource, Show } from 'solid-js';
+import { Gauge, Loader2 } from 'lucide-solid';
+import { AccordionItem } from '../../../ui/Accordion';
+import { tauriService } from '../../../../core/tauri/services';
+
+interface PlaybackStatsProps {
+    itemId: number;
+}
+
+const fetchStats = async (id: number) => {
+    try {
+        return await tauriService.getTranscodeStats(id);
+    } catch (e) {
+        console.error('Failed to load transcode stats:', e);
+        return null;
+    }
+};
+
+const formatFactor = (factor: number | null) => (factor == null ? '-' : `${factor.toFixed(2)}×`);
+
+/**
+ * How fast the segments streamed for this file were transcoded. A realtime
+ * factor below 1× means playback outruns FFmpeg and stutters.
+ */
+export const PlaybackStats: Component<PlaybackStatsProps> = props => {
+    const [stats] = createResource(() => props.itemId, fetchStats);
+
+    return (
+        <AccordionItem value="playback" title="Playback" icon={<Gauge size={14} />} lazy>
+            <Show
+                when={!stats.loading}
+                fallback={
+                    <div class="inspector-loading-spinner">
+                        <Loader2 class="animate-spin" size={20} />
+                    </div>
+                }
+            >
+                <Show
+                    when={stats() && stats()!.segments.length > 0}
+                    fallback={<div class="inspector-no-data">Not streamed through the transcoder yet.</div>}
+                >
+                    <div class="inspector-grid">
+                        <div class="inspector-meta-item">
+                            <span class="inspector-meta-label">Realtime Factor</span>
+                            <span class="inspector-meta-value">{formatFactor(stats()!.realtimeFactor)}</span>
+                        </div>
+                        <div class="inspector-meta-item">
+                            <span class="inspector-meta-label">Slowest Segment</span>
+                            <span class="inspector-meta-value">{formatFactor(stats()!.slowestRealtimeFactor)}</span>
+                        </div>
+                        <div class="inspector-meta-item">
+                            <span class="inspector-meta-label">Encoder</span>
+                            <span class="inspector-meta-value">{stats()!.encoders[0]}</span>
+                        </div>
+                        <div class="inspector-meta-item">
+                            <span class="inspector-meta-label">Waiting for FFmpeg</span>
+                            <span class="inspector-meta-value">
+                                {Math.round(stats()!.averageQueuedMs ?? 0)} ms
+                            </span>
+                        </div>
+                        <div class="inspector-meta-item">
+                            <span class="inspector-meta-label">Segments</span>
+                            <span class="inspector-meta-value">
+                                {stats()!.transcoded} transcoded
+                                {stats()!.failed > 0 ? `, ${stats()!.failed} failed` : ''}
+                            </span>
+                        </div>
+                    </div>
+                </Show>
+            </Show>
+        </AccordionItem>
+    );
+};
@@ -2,7 +2,9 @@ import { Component, Show } from 'solid-js';
 import { type ImageItem } from '../../../../types';
 import { Accordion, VideoPlayer as UIVideoPlayer, Loader } from '../../../ui';
 import { InspectorTags } from '../base/InspectorTags';
+import { Derivatives } from '../base/Derivatives';
 import { CommonMetadata } from '../base/CommonMetadata';
+import { PlaybackStats } from './PlaybackStats';
 import { useVideoSource } from '../../../../core/hooks/useVideoSource';
 import './VideoInspector.css';
 
@@ -39,6 +41,8 @@ export const VideoInspector: Component<VideoInspectorProps> = props => {
             <Accordion>
                 <CommonMetadata item={props.item} />
                 <InspectorTags itemId={props.item.id} />
+                <PlaybackStats itemId={props.item.id} />
+                <Derivatives itemId={props.item.id} />
             </Accordion>
         </div>
     );
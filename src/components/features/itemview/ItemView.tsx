@@ -203,7 +203,7 @@ const ItemViewContent: Component = () => {
                             }
                         >
                             <ImageViewer
-                                src={`image://localhost/${encodeURIComponent(item()!.path)}`}
+                                src={`image://localhost/id/${item()!.id}`}
                                 alt={item()!.filename}
                             />
                         </Match>
@@ -215,11 +215,12 @@ const ItemViewContent: Component = () => {
                         </Match>
                         <Match when={mediaType() === 'font'}>
                             <FontView
-                                src={`font://localhost/${encodeURIComponent(item()!.path)}`}
+                                src={`font://localhost/id/${item()!.id}`}
                                 fontName={item()!.filename}
                             />
                         </Match>
                         <Match when={getMediaType(item()!.filename) === 'model3d'}>
+                            {/* By path, so glTF buffers and textures resolve next to it */}
                             <ModelViewer
                                 src={`model://localhost/${encodeURIComponent(item()!.path)}`}
                                 filename={item()!.filename}
@@ -1,4 +1,4 @@
-import { Component, createMemo, Show, createEffect } from "solid-js";
+import { Component, createSignal, Show, createEffect } from "solid-js";
 import "@google/model-viewer";
 import { Loader } from "../../../../ui/Loader";
 import { useItemViewContext } from "../../ItemViewContext";
@@ -52,14 +52,11 @@ export const ModelViewer: Component<ModelViewerProps> = (props) => {
         }
     });
 
-    // Determine the path to the cached GLB content
-    const glbUrl = createMemo(() => {
-        // ... (same logic)
-        if (!props.thumbnail) return null;
-        const filename = props.thumbnail.split(/[\\/]/).pop();
-        if (!filename) return null;
-        const stem = filename.replace(/\.[^/.]+$/, "");
-        return `thumb://localhost/${stem}.glb`;
+    // The model protocol converts formats other than glTF, which can fail
+    const [failed, setFailed] = createSignal(false);
+    createEffect(() => {
+        void props.src;
+        setFailed(false);
     });
 
     return (
@@ -67,16 +64,17 @@ export const ModelViewer: Component<ModelViewerProps> = (props) => {
             class="model-viewer-container" 
             style={{ "background-color": modelSettings().backgroundColor }}
         >
-            <Show when={glbUrl()} fallback={
+            <Show when={!failed()} fallback={
                 <div class="model-placeholder">
                      <span class="model-icon">🧊</span>
-                     <p>Preview pending or unavailable</p>
+                     <p>Preview unavailable</p>
                 </div>
             }>
                 {/* @ts-ignore */}
                 <model-viewer
                     ref={viewerRef}
-                    src={glbUrl()!}
+                    src={props.src}
+                    on:error={() => setFailed(true)}
                     poster={props.thumbnail ? `thumb://localhost/${props.thumbnail}` : undefined}
                     alt={`3D model: ${props.filename}`}
                     shadow-intensity={modelSettings().backgroundColor === '#111111' ? "1" : "0.5"}
@@ -75,7 +75,7 @@ const computeDisplayValue = (item: Partial<SearchCriterion>, metadata: any): str
         return `${val / m} ${label}`;
     }
 
-    if (['added_at', 'created_at', 'modified_at'].includes(key)) {
+    if (['added_at', 'created_at', 'modified_at', 'captured_at'].includes(key)) {
         if (Array.isArray(val)) {
             return `${formatToDisplay(val[0])} to ${formatToDisplay(val[1])}`;
         }
@@ -119,6 +119,7 @@ const SEARCH_FIELDS = [
     { value: 'added_at', label: 'Date added', type: 'date' },
     { value: 'created_at', label: 'Date creation', type: 'date' },
     { value: 'modified_at', label: 'Date modified', type: 'date' },
+    { value: 'captured_at', label: 'Date taken', type: 'date' },
     { value: 'rating', label: 'Rating', type: 'rating' },
     { value: 'notes', label: 'Notes', type: 'text' },
     { value: 'folder', label: 'Folder', type: 'folder' }
@@ -275,7 +276,7 @@ export const AdvancedSearchModal: Component<AdvancedSearchModalProps> = props =>
                 const mult = Number(item.unitMultiplier || '1048576');
                 setEditingValue(Number(item.value[0]) / mult);
                 setEditingValue2(Number(item.value[1]) / mult);
-            } else if (['added_at', 'created_at', 'modified_at'].includes(item.key)) {
+            } else if (['added_at', 'created_at', 'modified_at', 'captured_at'].includes(item.key)) {
                 setEditingValue(fromISO(item.value[0]));
                 setEditingValue2(fromISO(item.value[1]));
             } else {
@@ -286,7 +287,7 @@ export const AdvancedSearchModal: Component<AdvancedSearchModalProps> = props =>
             if (item.key === 'size') {
                 const mult = Number(item.unitMultiplier || '1048576');
                 setEditingValue(Number(item.value) / mult);
-            } else if (['added_at', 'created_at', 'modified_at'].includes(item.key)) {
+            } else if (['added_at', 'created_at', 'modified_at', 'captured_at'].includes(item.key)) {
                 setEditingValue(fromISO(String(item.value)));
             } else {
                 setEditingValue(item.value);
@@ -337,7 +338,7 @@ export const AdvancedSearchModal: Component<AdvancedSearchModalProps> = props =>
                             displayValue = `${editingValue()} ${label}`;
                         }
                     } else if (c.operator === 'between') {
-                        if (['added_at', 'created_at', 'modified_at'].includes(c.key)) {
+                        if (['added_at', 'created_at', 'modified_at', 'captured_at'].includes(c.key)) {
                             const v1 = formatToISO(editingValue());
                             const v2 = formatToISO(editingValue2());
                             finalValue = [v1, v2];
@@ -346,7 +347,7 @@ export const AdvancedSearchModal: Component<AdvancedSearchModalProps> = props =>
                             finalValue = [editingValue(), editingValue2()];
                             displayValue = `${editingValue()} to ${editingValue2()}`;
                         }
-                    } else if (['added_at', 'created_at', 'modified_at'].includes(c.key)) {
+                    } else if (['added_at', 'created_at', 'modified_at', 'captured_at'].includes(c.key)) {
                         finalValue = formatToISO(editingValue());
                         displayValue = formatToDisplay(finalValue);
                     } else if (c.key === 'folder') {
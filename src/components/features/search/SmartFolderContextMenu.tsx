@@ -1,5 +1,5 @@
 import { Component, createMemo } from "solid-js";
-import { Edit, Trash2 } from "lucide-solid";
+import { Copy, Edit, Trash2 } from "lucide-solid";
 import { ContextMenu, ContextMenuItem } from "../../ui/ContextMenu";
 import { SmartFolder } from "../../../core/store/metadataStore";
 
@@ -10,6 +10,7 @@ interface SmartFolderContextMenuProps {
     folder: SmartFolder | null;
     onClose: () => void;
     onEdit: (folder: SmartFolder) => void;
+    onCopyJson: (folder: SmartFolder) => void;
     onDelete: (folder: SmartFolder) => void;
 }
 
@@ -25,6 +26,12 @@ export const SmartFolderContextMenu: Component<SmartFolderContextMenuProps> = (p
                 icon: Edit, 
                 action: () => props.onEdit(folder) 
             },
+            {
+                type: 'item',
+                label: 'Copy as JSON',
+                icon: Copy,
+                action: () => props.onCopyJson(folder)
+            },
             { type: 'separator' },
             {
                 type: 'item', 
@@ -1,13 +1,15 @@
 import { Component, For, Show, createSignal } from "solid-js";
-import { FolderHeart } from "lucide-solid";
+import { ClipboardPaste, FolderHeart } from "lucide-solid";
 import { useMetadata, useFilters, useNotification } from "../../../core/hooks";
 import { SidebarPanel } from "../../ui/SidebarPanel";
+import { Button } from "../../ui/Button";
 import { SmartFolderContextMenu } from "./SmartFolderContextMenu";
 import { AdvancedSearchModal } from "./AdvancedSearchModal";
 import { SmartFolderDeleteModal } from "./SmartFolderDeleteModal";
 import { SearchGroup } from "../../../core/store/filterStore";
 import { SmartFolder } from "../../../core/store/metadataStore";
 import { cn } from "../../../lib/utils";
+import { isAppError } from "../../../types";
 import "./smart-folders.css";
 
 export const SmartFoldersSidebarPanel: Component = () => {
@@ -67,13 +69,49 @@ export const SmartFoldersSidebarPanel: Component = () => {
         setIsDeleteModalOpen(true);
     };
 
+    const handleCopyJson = async (folder: SmartFolder) => {
+        try {
+            const json = await metadata.exportSmartFolder(folder.id);
+            await navigator.clipboard.writeText(json);
+            notification.success("Smart Folder Copied", `"${folder.name}" can be pasted into another library`);
+        } catch (err) {
+            notification.error("Failed to Copy Smart Folder", isAppError(err) ? err.message : String(err));
+        }
+    };
+
+    const handleImport = async () => {
+        try {
+            const json = await navigator.clipboard.readText();
+            const result = await metadata.importSmartFolder(json);
+            const created = result.createdTags.length > 0
+                ? ` Created tags: ${result.createdTags.join(", ")}.`
+                : "";
+            notification.success("Smart Folder Imported", `Added "${result.name}".${created}`);
+        } catch (err) {
+            notification.error("Failed to Import Smart Folder", isAppError(err) ? err.message : String(err));
+        }
+    };
+
     const isActive = (json: string) => {
         if (!filters.advancedSearch) return false;
         return JSON.stringify(filters.advancedSearch) === json;
     };
 
     return (
-        <SidebarPanel title="Smart Folders" class="panel-smart-folders">
+        <SidebarPanel
+            title="Smart Folders"
+            class="panel-smart-folders"
+            actions={
+                <Button
+                    variant="ghost"
+                    size="icon-xs"
+                    title="Paste Smart Folder from Clipboard"
+                    onClick={handleImport}
+                >
+                    <ClipboardPaste size={14} />
+                </Button>
+            }
+        >
             <div class="smart-folders-list">
                 <Show when={metadata.smartFolders.length === 0}>
                     <div class="smart-folders-empty">
@@ -108,6 +146,7 @@ export const SmartFoldersSidebarPanel: Component = () => {
                 folder={selectedFolder()}
                 onClose={() => setContextMenuOpen(false)}
                 onEdit={handleEdit}
+                onCopyJson={handleCopyJson}
                 onDelete={handleDeleteClick}
             />
 
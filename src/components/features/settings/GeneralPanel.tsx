@@ -25,6 +25,9 @@ export const GeneralPanel: Component = () => {
     const [cacheRetentionDays, setCacheRetentionDays] = createSignal<string>('30');
     const [cleaningCache, setCleaningCache] = createSignal(false);
     const [clearingCache, setClearingCache] = createSignal(false);
+    const [timezone, setTimezone] = createSignal<string>('local');
+    const [repairingDates, setRepairingDates] = createSignal(false);
+    const [collationLocale, setCollationLocale] = createSignal<string>('default');
     const [cacheStats, setCacheStats] = createSignal<{ size_bytes: number; file_count: number }>({
         size_bytes: 0,
         file_count: 0
@@ -35,6 +38,12 @@ export const GeneralPanel: Component = () => {
         const threadVal = await tauriService.getSetting('thumbnail_threads');
         if (threadVal !== null && threadVal !== undefined) setThreads(String(threadVal));
 
+        const timezoneVal = await tauriService.getSetting('default_timezone');
+        if (typeof timezoneVal === 'string') setTimezone(timezoneVal);
+
+        const collationVal = await tauriService.getSetting('collation_locale');
+        if (typeof collationVal === 'string' && collationVal) setCollationLocale(collationVal);
+
         const retentionVal = await tauriService.getSetting('cache_retention_days');
         if (retentionVal !== null && retentionVal !== undefined)
             setCacheRetentionDays(String(retentionVal));
@@ -48,8 +57,8 @@ export const GeneralPanel: Component = () => {
         setOptimizing(true);
         toast.info('Starting database optimization...');
         try {
-            await tauriService.runDbMaintenance();
-            toast.success('Database optimization complete.');
+            const report = await tauriService.runDbMaintenance();
+            toast.success(`Database optimization complete. Reclaimed ${formatBytes(report.reclaimedBytes)}.`);
         } catch (e) {
             toast.error('Failed to optimize database.');
             console.error(e);
@@ -68,6 +77,41 @@ export const GeneralPanel: Component = () => {
         }
     };
 
+    const handleTimezoneChange = async (val: string) => {
+        setTimezone(val);
+        try {
+            await tauriService.setSetting('default_timezone', val);
+            toast.success('Capture dates without a time zone were updated.');
+        } catch (e) {
+            toast.error('Failed to save settings.');
+        }
+    };
+
+    const handleCollationChange = async (val: string) => {
+        setCollationLocale(val);
+        try {
+            await tauriService.setSetting('collation_locale', val);
+            toast.success('Filenames now sort for the selected language.');
+        } catch (e) {
+            toast.error('Failed to save settings.');
+        }
+    };
+
+    const handleRepairDates = async () => {
+        setRepairingDates(true);
+        try {
+            const repair = await tauriService.repairCaptureTimes();
+            toast.success(
+                `Updated ${repair.renormalized} capture dates. ${repair.requeued} files will be read again.`
+            );
+        } catch (e) {
+            toast.error('Failed to repair capture dates.');
+            console.error(e);
+        } finally {
+            setRepairingDates(false);
+        }
+    };
+
     const handleRetentionChange = async (val: string) => {
         setCacheRetentionDays(val);
         const days = parseInt(val);
@@ -127,6 +171,24 @@ export const GeneralPanel: Component = () => {
         { value: '90', label: '90 days' }
     ];
 
+    const timezoneOptions = [
+        { value: 'local', label: 'This computer' },
+        { value: 'UTC', label: 'UTC' },
+        ...[-10, -8, -7, -6, -5, -4, -3, 1, 2, 3, 4, 5.5, 7, 8, 9, 10, 12].map(hours => {
+            const sign = hours < 0 ? '-' : '+';
+            const abs = Math.abs(hours);
+            const value = `${sign}${String(Math.floor(abs)).padStart(2, '0')}:${abs % 1 ? '30' : '00'}`;
+            return { value, label: `UTC${value}` };
+        })
+    ];
+
+    const collationOptions = [
+        { value: 'default', label: 'Default' },
+        { value: 'da', label: 'Danish / Norwegian' },
+        { value: 'es', label: 'Spanish' },
+        { value: 'sv', label: 'Swedish / Finnish' }
+    ];
+
     const qualityOptions = [
         { value: 'preview', label: 'Preview (Faster, smaller files)' },
         { value: 'standard', label: 'Standard (Balanced)' },
@@ -232,6 +294,45 @@ export const GeneralPanel: Component = () => {
                 </div>
             </SectionGroup>
 
+            <SectionGroup
+                title="Capture Dates"
+                description="Time zone assumed for capture dates recorded by cameras that don't store one. Dates are sorted in UTC."
+            >
+                <div class="general-setting-row">
+                    <span class="setting-label">Default Time Zone:</span>
+                    <div style={{ width: '200px' }}>
+                        <Select
+                            options={timezoneOptions}
+                            value={timezone()}
+                            onValueChange={handleTimezoneChange}
+                            placeholder="Select time zone"
+                        />
+                    </div>
+                </div>
+                <div class="setting-action-row">
+                    <Button onClick={handleRepairDates} loading={repairingDates()} variant="outline">
+                        Repair Capture Dates
+                    </Button>
+                </div>
+            </SectionGroup>
+
+            <SectionGroup
+                title="Sorting"
+                description="Filenames sort by letter regardless of accents, with numbers in order (img2 before img10). Some languages keep accented letters apart, like Swedish Å, Ä and Ö after Z."
+            >
+                <div class="general-setting-row">
+                    <span class="setting-label">Language:</span>
+                    <div style={{ width: '200px' }}>
+                        <Select
+                            options={collationOptions}
+                            value={collationLocale()}
+                            onValueChange={handleCollationChange}
+                            placeholder="Select language"
+                        />
+                    </div>
+                </div>
+            </SectionGroup>
+
             <SectionGroup
                 title="Library Maintenance"
                 description="Optimize the database to improve performance and reduce file size (VACUUM + ANALYZE)."
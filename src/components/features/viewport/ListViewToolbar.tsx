@@ -105,7 +105,9 @@ export const ListViewToolbar: Component = () => {
                             label: 'File Size',
                             action: () => filters.setSortBy('size')
                         },
-                        { type: 'item', label: 'Rating', action: () => filters.setSortBy('rating') }
+                        { type: 'item', label: 'Rating', action: () => filters.setSortBy('rating') },
+                        { type: 'item', label: 'Duration', action: () => filters.setSortBy('duration') },
+                        { type: 'item', label: 'Date Taken', action: () => filters.setSortBy('captured_at') }
                     ]}
                 />
 
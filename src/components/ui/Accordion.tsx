@@ -4,6 +4,7 @@ import {
   splitProps, 
   Show, 
   createSignal, 
+  createEffect,
   createContext,
   useContext,
   Accessor,
@@ -134,6 +135,8 @@ export interface AccordionItemProps {
   disabled?: boolean;
   /** Whether this item is open by default (only works outside of Accordion context) */
   defaultOpen?: boolean;
+  /** Mount the content on first open, for content that is costly to load */
+  lazy?: boolean;
   /** Additional CSS class */
   class?: string;
 }
@@ -146,6 +149,7 @@ export const AccordionItem: Component<AccordionItemProps> = (props) => {
     "icon",
     "disabled",
     "defaultOpen",
+    "lazy",
     "class",
   ]);
 
@@ -161,6 +165,12 @@ export const AccordionItem: Component<AccordionItemProps> = (props) => {
     return standaloneOpen();
   };
 
+  // Stays true once opened, so closing doesn't unmount lazy content
+  const [opened, setOpened] = createSignal(false);
+  createEffect(() => {
+    if (isOpen()) setOpened(true);
+  });
+
   const toggle = () => {
     if (local.disabled) return;
     
@@ -217,7 +227,7 @@ export const AccordionItem: Component<AccordionItemProps> = (props) => {
         hidden={!isOpen()}
       >
         <div class="ui-accordion-content-inner">
-          {local.children}
+          <Show when={!local.lazy || opened()}>{local.children}</Show>
         </div>
       </div>
     </div>
@@ -16,6 +16,8 @@ export const useMetadata = () => {
     loadSmartFolders: metadataActions.loadSmartFolders,
     saveSmartFolder: metadataActions.saveSmartFolder,
     deleteSmartFolder: metadataActions.deleteSmartFolder,
+    exportSmartFolder: metadataActions.exportSmartFolder,
+    importSmartFolder: metadataActions.importSmartFolder,
     refreshAll: metadataActions.refreshAll,
     notifyTagUpdate: metadataActions.notifyTagUpdate
   };
@@ -1,4 +1,4 @@
-import { loading, progress, rootPath, systemActions } from "../store/systemStore";
+import { loading, progress, rootPath, startupError, systemActions } from "../store/systemStore";
 
 export const useSystem = () => {
   return {
@@ -6,9 +6,11 @@ export const useSystem = () => {
     loading,
     progress,
     rootPath,
+    startupError,
     
     // Actions
     initialize: systemActions.initialize,
+    retryStartup: systemActions.retryStartup,
     setRootLocation: systemActions.setRootLocation,
     updateProgress: systemActions.updateProgress,
     clearProgress: systemActions.clearProgress
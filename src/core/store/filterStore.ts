@@ -2,7 +2,7 @@ import { createStore } from "solid-js/store";
 import { batch } from "solid-js";
 import { APP_CONFIG } from "../../config/constants";
 
-export type SortField = "modified_at" | "added_at" | "created_at" | "filename" | "format" | "size" | "rating";
+export type SortField = "modified_at" | "added_at" | "created_at" | "filename" | "format" | "size" | "rating" | "duration" | "captured_at";
 export type SortOrder = "asc" | "desc";
 export type ViewLayout = "masonry-v" | "masonry-h" | "grid" | "list";
 
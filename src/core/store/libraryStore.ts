@@ -1,7 +1,7 @@
 import { createStore, reconcile } from "solid-js/store";
 import { getImages } from "../../lib/db";
 import { invoke } from "@tauri-apps/api/core";
-import { tagService } from "../../lib/tags";
+import { tagService, MAX_FILTER_WINDOW, type FilterDelta, type RowDelta } from "../../lib/tags";
 import { filterState, filterActions } from "./filterStore";
 
 
@@ -18,6 +18,37 @@ import { APP_CONFIG } from "../../config/constants";
 const BATCH_SIZE = APP_CONFIG.BATCH_SIZE;
 let currentOffset = 0;
 
+// The backend keeps the rows of the grid and pushes changes to them as deltas
+let subscription: { id: number; version: number } | null = null;
+let subscribeRequest = 0;
+
+const applyRowDeltas = (items: ImageItem[], ops: RowDelta[]): ImageItem[] => {
+  const rows = [...items];
+  for (const op of ops) {
+    switch (op.op) {
+      case "remove": {
+        const at = rows.findIndex(i => i.id === op.id);
+        if (at >= 0) rows.splice(at, 1);
+        break;
+      }
+      case "insert":
+        rows.splice(op.index, 0, op.item);
+        break;
+      case "move": {
+        const at = rows.findIndex(i => i.id === op.id);
+        if (at >= 0) rows.splice(op.index, 0, rows.splice(at, 1)[0]);
+        break;
+      }
+      case "update": {
+        const at = rows.findIndex(i => i.id === op.item.id);
+        if (at >= 0) rows[at] = op.item;
+        break;
+      }
+    }
+  }
+  return rows;
+};
+
 const [libraryState, setLibraryState] = createStore<LibraryState>({
   items: [],
   isFetching: false,
@@ -40,6 +71,48 @@ export const libraryActions = {
 
     if (reset) {
       currentOffset = 0;
+      libraryActions.setActiveContext(anyFilter ? {
+        tagIds: filterState.selectedTags,
+        matchAll: true,
+        untagged: isUntagged,
+        folderId: folderId || undefined,
+        recursive,
+        sortBy,
+        sortOrder,
+        advancedQuery,
+        searchQuery: filterState.searchQuery
+      } : null);
+
+      const request = ++subscribeRequest;
+      const previous = subscription;
+      subscription = null;
+      if (previous) tagService.unsubscribeFilter(previous.id).catch(() => {});
+      try {
+        const delta = await tagService.subscribeFilter({
+          tagIds: filterState.selectedTags,
+          matchAll: true,
+          untagged: isUntagged,
+          folderId: folderId || undefined,
+          recursive,
+          sortBy,
+          sortOrder,
+          advancedQuery,
+          searchQuery: filterState.searchQuery
+        }, { offset: 0, limit: BATCH_SIZE });
+        // A newer refresh started meanwhile
+        if (request !== subscribeRequest) {
+          tagService.unsubscribeFilter(delta.subscriptionId).catch(() => {});
+          return;
+        }
+        subscription = { id: delta.subscriptionId, version: delta.version };
+        setLibraryState("items", reconcile(applyRowDeltas([], delta.ops), { key: "id" }));
+        setLibraryState("totalItems", delta.total);
+        currentOffset = BATCH_SIZE;
+        return;
+      } catch (err) {
+        console.error("Failed to subscribe to the grid filter, paging instead:", err);
+      }
+
       let firstBatch;
       if (anyFilter) {
         firstBatch = await tagService.getImagesFiltered(
@@ -50,6 +123,15 @@ export const libraryActions = {
       }
       setLibraryState("items", reconcile(firstBatch, { key: "id" }));
       currentOffset = BATCH_SIZE;
+    } else if (subscription) {
+      try {
+        const delta = await tagService.setFilterWindow(subscription.id, { offset: 0, limit: Math.max(currentOffset, BATCH_SIZE) });
+        libraryActions.applyFilterDelta(delta);
+      } catch (err) {
+        console.error("Grid subscription lost, subscribing again:", err);
+        await libraryActions.refreshImages(true);
+      }
+      return;
     } else {
       let fresh;
       if (anyFilter) {
@@ -90,6 +172,19 @@ export const libraryActions = {
       const sortBy = filterState.sortBy;
       const sortOrder = filterState.sortOrder;
 
+      if (subscription && currentOffset + BATCH_SIZE <= MAX_FILTER_WINDOW) {
+        // Growing the window at its end only queries the rows past it
+        const delta = await tagService.setFilterWindow(subscription.id, { offset: 0, limit: currentOffset + BATCH_SIZE });
+        libraryActions.applyFilterDelta(delta);
+        currentOffset += BATCH_SIZE;
+        return;
+      }
+      if (subscription) {
+        // Past the largest window, the grid pages without deltas
+        tagService.unsubscribeFilter(subscription.id).catch(() => {});
+        subscription = null;
+      }
+
       let nextBatch;
       const advancedQuery = filterState.advancedSearch ? JSON.stringify(filterState.advancedSearch) : undefined;
 
@@ -137,7 +232,22 @@ export const libraryActions = {
     );
   },
 
+  applyFilterDelta: (delta: FilterDelta) => {
+    if (!subscription || delta.subscriptionId !== subscription.id || delta.version <= subscription.version) return;
+    if (delta.version !== subscription.version + 1) {
+      // A delta went missing, so the rows can't be trusted anymore
+      libraryActions.refreshImages(true);
+      return;
+    }
+    subscription.version = delta.version;
+    setLibraryState("items", reconcile(applyRowDeltas(libraryState.items, delta.ops), { key: "id" }));
+    setLibraryState("totalItems", delta.total);
+  },
+
   handleBatchChange: (payload: any) => {
+      // The subscribed window gets its changes as row deltas instead
+      if (subscription) return;
+
       // 1. Handle Removals
       if (payload.removed && payload.removed.length > 0) {
           const removedIds = new Set(payload.removed.map((r: any) => r.id));
@@ -223,6 +333,15 @@ export const libraryActions = {
       } catch (err) {
           console.error("Failed to set thumbnail priority:", err);
       }
+  },
+
+  // Missing thumbnails of the open grid are rendered before the rest of the library
+  setActiveContext: async (filter: Record<string, unknown> | null) => {
+      try {
+          await invoke("set_active_context", { filter });
+      } catch (err) {
+          console.error("Failed to set the active thumbnail context:", err);
+      }
   }
 
 
@@ -18,6 +18,27 @@ export interface SmartFolder {
   created_at: string;
 }
 
+/** Outcome of importing a smart folder shared as JSON. */
+export interface SmartFolderImport {
+  id: number;
+  name: string;
+  createdTags: string[];
+  remappedTags: number;
+  remappedFolders: number;
+  remappedCollections: number;
+}
+
+/** Statistics of a smart folder recorded on some day. */
+export interface SmartFolderSnapshot {
+  id: number;
+  smart_folder_id: number;
+  taken_at: string;
+  image_count: number;
+  untagged_count: number;
+  /** Images per rating, from unrated (index 0) to five stars. */
+  ratings: number[];
+}
+
 interface MetadataState {
   tags: Tag[];
   locations: FolderNode[];
@@ -84,6 +105,26 @@ export const metadataActions = {
     }
   },
 
+  exportSmartFolder: async (id: number): Promise<string> => {
+    const { invoke } = await import("@tauri-apps/api/core");
+    return await invoke("export_smart_folder", { id }) as string;
+  },
+
+  importSmartFolder: async (json: string): Promise<SmartFolderImport> => {
+    const { invoke } = await import("@tauri-apps/api/core");
+    const result = await invoke("import_smart_folder", { json }) as SmartFolderImport;
+    await metadataActions.loadSmartFolders();
+    if (result.createdTags.length > 0) {
+      await metadataActions.loadTags();
+    }
+    return result;
+  },
+
+  getSmartFolderTrends: async (id: number, days?: number): Promise<SmartFolderSnapshot[]> => {
+    const { invoke } = await import("@tauri-apps/api/core");
+    return await invoke("get_smart_folder_trends", { id, days }) as SmartFolderSnapshot[];
+  },
+
   notifyTagUpdate: () => {
     setMetadataState("tagUpdateVersion", v => v + 1);
     metadataActions.loadStats();
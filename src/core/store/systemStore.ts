@@ -1,9 +1,11 @@
 import { createSignal } from "solid-js";
-import { FileFormat } from "../../types";
+import { AppStatus, FileFormat } from "../../types";
 import { listen } from "@tauri-apps/api/event";
-import { addLocation, initDb } from "../../lib/db";
+import { addLocation, initDb, retryAppInit } from "../../lib/db";
 import { tauriService } from "../tauri/services";
+import { registerWebviewCodecs } from "../../lib/stream-utils";
 import { metadataActions } from "./metadataStore";
+import type { FilterDelta } from "../../lib/tags";
 
 export interface ProgressPayload {
   total: number;
@@ -16,6 +18,7 @@ const [progress, setProgress] = createSignal<ProgressPayload | null>(null);
 const [rootPath, setRootPath] = createSignal<string | null>(null);
 const [initialized, setInitialized] = createSignal(false);
 const [supportedFormats, setSupportedFormats] = createSignal<FileFormat[]>([]);
+const [startupError, setStartupError] = createSignal<AppStatus | null>(null);
 
 export const systemActions = {
   initialize: async () => {
@@ -23,7 +26,13 @@ export const systemActions = {
     
     try {
       setLoading(true);
-      await initDb();
+      await registerWebviewCodecs();
+      const status = await initDb();
+      if (status.stage === "failed") {
+        setStartupError(status);
+        return;
+      }
+      setStartupError(null);
       await metadataActions.loadLocations();
       await metadataActions.loadTags();
       await metadataActions.loadSmartFolders();
@@ -89,6 +98,12 @@ export const systemActions = {
         });
       });
 
+      listen<FilterDelta>("grid:delta", (e) => {
+        import("./libraryStore").then(({ libraryActions }) => {
+            libraryActions.applyFilterDelta(e.payload);
+        });
+      });
+
       setInitialized(true);
     } catch (err) {
       console.error("Initialization failed:", err);
@@ -97,6 +112,17 @@ export const systemActions = {
     }
   },
 
+  /** Retries a failed startup, with another library when `dbPath` is given. */
+  retryStartup: async (dbPath?: string) => {
+    setLoading(true);
+    try {
+      await retryAppInit(dbPath);
+    } catch (err) {
+      console.error("Retry failed:", err);
+    }
+    await systemActions.initialize();
+  },
+
   setRootLocation: async (path: string) => {
     await addLocation(path);
     await metadataActions.loadLocations();
@@ -121,4 +147,4 @@ export const systemActions = {
   }
 };
 
-export { loading, progress, rootPath, supportedFormats };
+export { loading, progress, rootPath, supportedFormats, startupError };
@@ -1,10 +1,213 @@
 import { invoke } from "@tauri-apps/api/core";
+import type { ImageItem } from "../../types";
 
 // Define strict types for Tauri commands
 export interface StartIndexingArgs {
   path: string;
 }
 
+/** Outcome of `run_db_maintenance`. */
+export interface MaintenanceReport {
+  sizeBefore: number;
+  sizeAfter: number;
+  reclaimedBytes: number;
+  /** A full VACUUM ran to switch the library to incremental vacuum. */
+  fullVacuum: boolean;
+}
+
+/** Outcome of `repair_capture_times`. */
+export interface CaptureTimeRepair {
+  /** Capture times recorded without an offset, converted again. */
+  renormalized: number;
+  /** Files without a capture time, queued to be read again. */
+  requeued: number;
+}
+
+/** A segment transcoded for streaming; see `get_transcode_stats`. */
+export interface SegmentStat {
+  segmentIndex: number;
+  quality: string;
+  encoder: string;
+  queuedMs: number;
+  encodeMs: number;
+  mediaSecs: number;
+  /** Media seconds per second of encoding; null for failed segments. */
+  realtimeFactor: number | null;
+  error: string | null;
+  recordedAt: string;
+}
+
+/** Reply of `get_transcode_stats`. */
+export interface TranscodeStats {
+  imageId: number;
+  /** Newest first. */
+  segments: SegmentStat[];
+  transcoded: number;
+  failed: number;
+  realtimeFactor: number | null;
+  slowestRealtimeFactor: number | null;
+  averageQueuedMs: number | null;
+  encoders: string[];
+}
+
+/** A material library or texture a 3D model references; see `get_model_dependencies`. */
+export interface ModelDependency {
+  kind: "material" | "texture";
+  /** The path as written in the model. */
+  reference: string;
+  /** Where it was found; null when missing. */
+  resolved: string | null;
+}
+
+/** A file derived from an asset; see `get_derivatives`. */
+export interface Derivative {
+  id: number;
+  imageId: number;
+  kind: "proxy" | "export" | "transcode" | "preview";
+  path: string;
+  /** Which variant, e.g. a quality or codec. */
+  label: string | null;
+  size: number | null;
+  createdAt: string;
+  /** Whether the file is still on disk. */
+  exists: boolean;
+}
+
+/** Reply of `clean_up_derivatives`. */
+export interface DerivativeCleanup {
+  deleted: number;
+  forgotten: number;
+  freedBytes: number;
+}
+
+/** Editing proxy generation; see `get_proxy_settings`. */
+export interface ProxySettings {
+  /** Queue proxies for videos added to the watched folders. */
+  enabled: boolean;
+  folder: string | null;
+  preset: "prores_proxy" | "dnxhr_lb";
+  watchFolders: string[];
+}
+
+/** A copy in a duplicate group, with what tells it apart. */
+export interface DuplicateFile {
+  id: number;
+  path: string;
+  filename: string;
+  width: number | null;
+  height: number | null;
+  size: number;
+  format: string;
+  rating: number;
+  tagCount: number;
+  annotationCount: number;
+  hasNotes: boolean;
+  hasCaptureTime: boolean;
+  /** Tags and annotations, plus one each for a rating, notes and a capture time. */
+  metadataScore: number;
+}
+
+/** Identical files found by `find_duplicates`. */
+export interface DuplicateSet {
+  contentHash: string;
+  size: number;
+  wastedBytes: number;
+  files: DuplicateFile[];
+}
+
+/** Reply of `get_duplicate_group`. */
+export interface DuplicateGroup {
+  index: number;
+  groupCount: number;
+  files: DuplicateFile[];
+  suggestedKeepId: number;
+}
+
+/** Reply of `resolve_duplicates`. */
+export interface DuplicateResolution {
+  keptId: number;
+  removed: number[];
+  skipped: string[];
+  freedBytes: number;
+}
+
+/** Share of pixels at either end of a channel. */
+export interface Clipping {
+  shadows: number;
+  highlights: number;
+}
+
+/** Reply of `get_image_histogram`: 256 bins per channel. */
+export interface Histogram {
+  red: number[];
+  green: number[];
+  blue: number[];
+  luminance: number[];
+  pixels: number;
+  clipping: { red: Clipping; green: Clipping; blue: Clipping; luminance: Clipping };
+  /** 0-255. */
+  meanLuminance: number;
+}
+
+/** An image found by `get_similar_images`. */
+export interface SimilarImage {
+  image: ImageItem;
+  /** Perceptual hash bits that differ, 0 for the same picture. */
+  distance: number;
+}
+
+/** A mounted memory card, also sent with the `import:source-detected` event. */
+export interface ImportSource {
+  root: string;
+  name: string;
+  count: number;
+  photos: number;
+  videos: number;
+  totalBytes: number;
+  /** Camera wall-clock times, RFC 3339. */
+  earliest: string | null;
+  latest: string | null;
+}
+
+/** A folder followed as a live shooting target. */
+export interface CaptureSession {
+  folder: string;
+  /** Tag applied to each shot, under `Sessions`. */
+  tag: string;
+  startedAt: string;
+  shots: number;
+}
+
+/** Payload of the `capture:shot` event. */
+export interface CaptureShot {
+  imageId: number;
+  folderId: number;
+  path: string;
+  /** Whether `?maxdim=2048` is served from the cache. */
+  previewReady: boolean;
+  shots: number;
+}
+
+/** Reply of `get_db_status`. Timestamps are RFC 3339. */
+export interface DbStatus {
+  path: string;
+  fileBytes: number;
+  walBytes: number;
+  pageSize: number;
+  pageCount: number;
+  freelistCount: number;
+  /** Share of the file taken by free pages, 0 to 1. */
+  fragmentation: number;
+  incrementalVacuum: boolean;
+  cacheHits: number;
+  cacheMisses: number;
+  cacheHitRate: number | null;
+  lastVacuumAt: string | null;
+  lastFullVacuumAt: string | null;
+  lastAnalyzeAt: string | null;
+  lastCheckpointAt: string | null;
+}
+
 export const tauriService = {
   /**
    * Starts the background indexing process for the given directory path.
@@ -33,15 +236,104 @@ export const tauriService = {
       }
   },
 
-  runDbMaintenance: async (): Promise<void> => {
+  runDbMaintenance: async (): Promise<MaintenanceReport> => {
       try {
-          await invoke("run_db_maintenance");
+          return await invoke<MaintenanceReport>("run_db_maintenance");
       } catch (error) {
           console.error("Failed to run DB maintenance:", error);
           throw error;
       }
   },
 
+  getDbStatus: async (): Promise<DbStatus> => {
+      return await invoke<DbStatus>("get_db_status");
+  },
+
+  checkpointDb: async (): Promise<DbStatus> => {
+      return await invoke<DbStatus>("checkpoint_db");
+  },
+
+  getTranscodeStats: async (id: number): Promise<TranscodeStats> => {
+      return await invoke<TranscodeStats>("get_transcode_stats", { id });
+  },
+
+  getModelDependencies: async (imageId: number): Promise<ModelDependency[]> => {
+      return await invoke<ModelDependency[]>("get_model_dependencies", { imageId });
+  },
+
+  getDerivatives: async (imageId: number): Promise<Derivative[]> => {
+      return await invoke<Derivative[]>("get_derivatives", { imageId });
+  },
+
+  linkDerivative: async (imageId: number, path: string, kind: Derivative["kind"], label?: string): Promise<number> => {
+      return await invoke<number>("link_derivative", { imageId, path, kind, label });
+  },
+
+  deleteDerivative: async (id: number, deleteFile: boolean): Promise<void> => {
+      return await invoke("delete_derivative", { id, deleteFile });
+  },
+
+  cleanUpDerivatives: async (imageId?: number): Promise<DerivativeCleanup> => {
+      return await invoke<DerivativeCleanup>("clean_up_derivatives", { imageId });
+  },
+
+  getImageHistogram: async (id: number): Promise<Histogram> => {
+      return await invoke<Histogram>("get_image_histogram", { id });
+  },
+
+  /** Memory cards mounted now; copy one with an `ingest_card` job. */
+  getImportSources: async (): Promise<ImportSource[]> => {
+      return await invoke<ImportSource[]>("get_import_sources");
+  },
+
+  startCaptureSession: async (folder: string, tag?: string): Promise<CaptureSession> => {
+      return await invoke<CaptureSession>("start_capture_session", { folder, tag });
+  },
+
+  stopCaptureSession: async (): Promise<CaptureSession | null> => {
+      return await invoke<CaptureSession | null>("stop_capture_session");
+  },
+
+  getCaptureSession: async (): Promise<CaptureSession | null> => {
+      return await invoke<CaptureSession | null>("get_capture_session");
+  },
+
+  /** Images that look like `imageId`, closest first. */
+  getSimilarImages: async (imageId: number, threshold?: number): Promise<SimilarImage[]> => {
+      return await invoke<SimilarImage[]>("get_similar_images", { imageId, threshold });
+  },
+
+  /** Every group of identical files in the library, biggest waste first. */
+  findDuplicates: async (): Promise<DuplicateSet[]> => {
+      return await invoke<DuplicateSet[]>("find_duplicates");
+  },
+
+  /** Duplicate group `index`, biggest waste first; `null` past the last one. */
+  getDuplicateGroup: async (index: number): Promise<DuplicateGroup | null> => {
+      return await invoke<DuplicateGroup | null>("get_duplicate_group", { index });
+  },
+
+  resolveDuplicates: async (keepId: number, action: "delete" | "move", destination?: string): Promise<DuplicateResolution> => {
+      return await invoke<DuplicateResolution>("resolve_duplicates", { keepId, action, destination });
+  },
+
+  getProxySettings: async (): Promise<ProxySettings> => {
+      return await invoke<ProxySettings>("get_proxy_settings");
+  },
+
+  setProxySettings: async (settings: ProxySettings): Promise<ProxySettings> => {
+      return await invoke<ProxySettings>("set_proxy_settings", { settings });
+  },
+
+  /** Queues proxies of the given videos; returns the ids of the jobs queued. */
+  queueProxies: async (imageIds: number[]): Promise<number[]> => {
+      return await invoke<number[]>("queue_proxies", { imageIds });
+  },
+
+  repairCaptureTimes: async (): Promise<CaptureTimeRepair> => {
+      return await invoke<CaptureTimeRepair>("repair_capture_times");
+  },
+
   getSetting: async (key: string): Promise<any> => {
       try {
           return await invoke("get_setting", { key });
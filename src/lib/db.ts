@@ -1,11 +1,39 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import type { AppStatus } from "../types";
 
 // We primarily use the Rust backend for DB operations now.
 // This file wraps those invocations or provides legacy support where needed.
 
-export async function initDb() {
-  console.log("Database management is handled by the Backend (Rust).");
-  // No-op or perform specific frontend-only inits if needed
+/**
+ * Waits until the backend has opened the library, or failed to.
+ * Resolves with the final status: `ready` or `failed`.
+ */
+export async function initDb(): Promise<AppStatus> {
+  let resolveDone: (status: AppStatus) => void = () => {};
+  const done = new Promise<AppStatus>((resolve) => (resolveDone = resolve));
+  const settle = (status: AppStatus) => {
+    console.log(`[Startup] ${status.stage}`);
+    if (status.stage === "ready" || status.stage === "failed") resolveDone(status);
+  };
+
+  // Subscribe before asking, so a stage reached in between isn't missed
+  const unlisten = await listen<AppStatus>("app:init-progress", (e) => settle(e.payload));
+  try {
+    settle(await getAppStatus());
+    return await done;
+  } finally {
+    unlisten();
+  }
+}
+
+export async function getAppStatus() {
+  return await invoke<AppStatus>("get_app_status");
+}
+
+/** Starts over after a failed startup, optionally with another library. */
+export async function retryAppInit(dbPath?: string) {
+  return await invoke<AppStatus>("retry_app_init", { dbPath: dbPath ?? null });
 }
 
 export async function addLocation(path: string) {
@@ -7,6 +7,7 @@
 
 import Hls from 'hls.js';
 import { fetch } from '@tauri-apps/plugin-http';
+import { invoke } from '@tauri-apps/api/core';
 import { onCleanup, createSignal, createEffect, Accessor } from 'solid-js';
 
 export interface HlsPlayerOptions {
@@ -39,6 +40,31 @@ const DEFAULT_OPTIONS: Required<HlsPlayerOptions> = {
 /** HLS streaming server base URL */
 export const HLS_SERVER_URL = 'http://127.0.0.1:9876';
 
+/** Access token required by the streaming server, loaded once at startup */
+let streamToken = '';
+
+/**
+ * Load the streaming server token from the backend
+ */
+export async function loadStreamToken(): Promise<void> {
+    try {
+        streamToken = await invoke<string>('get_stream_token');
+    } catch (error) {
+        console.error('Failed to load stream token:', error);
+    }
+}
+
+/**
+ * Append the streaming server token to a URL
+ * @param url - Streaming server URL
+ * @returns The URL with the `token` query parameter
+ */
+export function withStreamToken(url: string): string {
+    if (!streamToken) return url;
+    const separator = url.includes('?') ? '&' : '?';
+    return `${url}${separator}token=${encodeURIComponent(streamToken)}`;
+}
+
 /**
  * Get the HLS playlist URL for a video file
  * @param filePath - Absolute path to the video file
@@ -46,7 +72,7 @@ export const HLS_SERVER_URL = 'http://127.0.0.1:9876';
  */
 export function getHlsPlaylistUrl(filePath: string, quality: string = 'standard'): string {
     const encodedPath = encodeURIComponent(filePath);
-    return `${HLS_SERVER_URL}/playlist/${encodedPath}?quality=${quality}`;
+    return withStreamToken(`${HLS_SERVER_URL}/playlist/${encodedPath}?quality=${quality}`);
 }
 
 /**
@@ -56,7 +82,7 @@ export function getHlsPlaylistUrl(filePath: string, quality: string = 'standard'
  */
 export function getHlsProbeUrl(filePath: string): string {
     const encodedPath = encodeURIComponent(filePath);
-    return `${HLS_SERVER_URL}/probe/${encodedPath}`;
+    return withStreamToken(`${HLS_SERVER_URL}/probe/${encodedPath}`);
 }
 
 /**
@@ -70,6 +96,8 @@ export interface VideoProbeResult {
     container: string | null;
     width: number | null;
     height: number | null;
+    /** Streams can be copied into MP4 without re-encoding */
+    can_remux?: boolean;
 }
 
 /**
@@ -94,7 +122,7 @@ export async function probeVideo(filePath: string): Promise<VideoProbeResult> {
 export async function isHlsServerAvailable(): Promise<boolean> {
     try {
         console.log(`DEBUG: Checking HLS server at ${HLS_SERVER_URL}/health`);
-        const response = await fetch(`${HLS_SERVER_URL}/health`, {
+        const response = await fetch(withStreamToken(`${HLS_SERVER_URL}/health`), {
             method: 'GET',
         });
         console.log(`DEBUG: HLS server response status: ${response.status}`);
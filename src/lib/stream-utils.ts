@@ -6,9 +6,9 @@
 
 import { invoke } from '@tauri-apps/api/core';
 import { formatActions } from '../core/store/formatStore';
-import { HLS_SERVER_URL, getHlsPlaylistUrl, type VideoProbeResult } from './hls-player';
+import { HLS_SERVER_URL, getHlsPlaylistUrl, withStreamToken, type VideoProbeResult } from './hls-player';
 
-export { HLS_SERVER_URL };
+export { HLS_SERVER_URL, withStreamToken };
 
 // Re-export HLS utilities for convenience
 export {
@@ -112,16 +112,25 @@ export function isNativeFormat(path: string): boolean {
 
 /**
  * Get the appropriate audio URL for a file path
+ * @param mode - How to stream formats that would otherwise use HLS
  */
-export function getAudioUrl(path: string, quality: TranscodeQuality = 'standard'): string {
+export function getAudioUrl(
+  path: string,
+  quality: TranscodeQuality = 'standard',
+  mode: 'progressive' | 'hls' = 'progressive'
+): string {
   const encodedPath = encodeURIComponent(path);
 
   if (needsLinearAudio(path)) {
      // Linear HLS (Live/Async)
-     return `${HLS_SERVER_URL}/hls-live/${encodedPath}/index.m3u8?quality=${quality}&mode=audio`;
+     return withStreamToken(`${HLS_SERVER_URL}/hls-live/${encodedPath}/index.m3u8?quality=${quality}&mode=audio`);
   }
 
   if (needsStandardHlsAudio(path)) {
+     if (mode === 'progressive') {
+       // Single-pass AAC pipe, starts faster than segmenting for short files
+       return withStreamToken(`${HLS_SERVER_URL}/audio/${encodedPath}?quality=${quality}`);
+     }
      // Standard HLS (Playlist/VOD)
      return getHlsPlaylistUrl(path, quality);
   }
@@ -151,7 +160,12 @@ export function getVideoUrl(path: string, quality: TranscodeQuality = 'standard'
 
   if (isLinear) {
     // Linear HLS (Live) - e.g. SWF or MJPEG
-    return `${HLS_SERVER_URL}/hls-live/${encodedPath}/index.m3u8?quality=${quality}&mode=live`;
+    return withStreamToken(`${HLS_SERVER_URL}/hls-live/${encodedPath}/index.m3u8?quality=${quality}&mode=live`);
+  }
+
+  if (probe?.can_remux) {
+    // Remux (stream copy) to MP4 - e.g. MKV with H.264/AAC, instant and seekable
+    return `video-stream://localhost/${encodedPath}?quality=${quality}&mode=remux`;
   }
 
   if (needsHlsTranscoding(path) || (probe && !probe.is_native)) {
@@ -178,6 +192,46 @@ export function getMediaType(path: string): 'audio' | 'video' | 'image' | 'font'
 
 // --- Tauri Commands ---
 
+/** Containers probed with `canPlayType`, by MIME type. */
+const PROBED_CONTAINERS = [
+  'video/mp4', 'video/quicktime', 'video/webm', 'video/ogg', 'video/x-matroska',
+  'audio/mpeg', 'audio/mp4', 'audio/aac', 'audio/wav', 'audio/flac', 'audio/ogg', 'audio/webm',
+];
+
+/** Codecs probed with `canPlayType`, by FFprobe name. */
+const PROBED_CODECS: Record<string, string> = {
+  h264: 'video/mp4; codecs="avc1.42E01E"',
+  hevc: 'video/mp4; codecs="hvc1.1.6.L93.B0"',
+  vp8: 'video/webm; codecs="vp8"',
+  vp9: 'video/webm; codecs="vp09.00.10.08"',
+  av1: 'video/mp4; codecs="av01.0.05M.08"',
+  aac: 'audio/mp4; codecs="mp4a.40.2"',
+  mp3: 'audio/mpeg',
+  alac: 'audio/mp4; codecs="alac"',
+  flac: 'audio/flac',
+  opus: 'audio/ogg; codecs="opus"',
+  vorbis: 'audio/ogg; codecs="vorbis"',
+  ac3: 'audio/mp4; codecs="ac-3"',
+  eac3: 'audio/mp4; codecs="ec-3"',
+  pcm: 'audio/wav; codecs="1"',
+};
+
+/**
+ * Reports to the backend which containers and codecs this WebView plays,
+ * so playback decisions follow the actual WebView instead of assumptions.
+ */
+export async function registerWebviewCodecs(): Promise<void> {
+  const probe = document.createElement('video');
+  const plays = (type: string) => probe.canPlayType(type) !== '';
+  const containers = PROBED_CONTAINERS.filter(plays);
+  const codecs = Object.keys(PROBED_CODECS).filter((codec) => plays(PROBED_CODECS[codec]));
+  try {
+    await invoke('register_webview_codecs', { codecs: { containers, codecs } });
+  } catch (error) {
+    console.error('Failed to register WebView codecs:', error);
+  }
+}
+
 /**
  * Check if FFmpeg is available in the system
  */
@@ -16,6 +16,54 @@ export interface LibraryStats {
   folder_counts_recursive: { folder_id: number; count: number }[];
 }
 
+export interface GridFilter {
+  tagIds?: number[];
+  matchAll?: boolean;
+  untagged?: boolean;
+  folderId?: number;
+  recursive?: boolean;
+  /** Only images of this manual collection; sort by `position` for its own order. */
+  collectionId?: number;
+  sortBy?: string;
+  sortOrder?: string;
+  advancedQuery?: string;
+  searchQuery?: string;
+}
+
+/** Rows of a filter the grid shows. */
+export interface FilterWindow {
+  offset: number;
+  limit: number;
+}
+
+/** A change to a subscribed window, applied in order. */
+export type RowDelta =
+  | { op: "remove"; id: number }
+  | { op: "insert"; index: number; item: any }
+  | { op: "move"; id: number; index: number }
+  | { op: "update"; item: any };
+
+/** Changes to a subscribed window, sent as `grid:delta` events. */
+export interface FilterDelta {
+  subscriptionId: number;
+  version: number;
+  total: number;
+  ops: RowDelta[];
+}
+
+/** Most rows a window holds (see `filter_subscriptions::MAX_WINDOW`). */
+export const MAX_FILTER_WINDOW = 20000;
+
+/** A temporary scope pushed for the session; searches intersect with it
+ * through a `working_set` criterion holding its id. */
+export interface WorkingSet {
+  id: number;
+  label: string | null;
+  count: number;
+  /** Position on the stack, 1 for the bottom set. */
+  depth: number;
+}
+
 export const tagService = {
   createTag: async (name: string, parent_id?: number | null, color?: string | null): Promise<number> => {
     return await invoke("create_tag", { name, parentId: parent_id, color });
@@ -60,7 +108,8 @@ export const tagService = {
     sort_by?: string,
     sort_order?: string,
     advanced_query?: string,
-    search_query?: string
+    search_query?: string,
+    collectionId?: number
   ): Promise<any[]> => {
     return await invoke("get_images_filtered", { 
       limit, 
@@ -70,6 +119,7 @@ export const tagService = {
       untagged,
       folderId,
       recursive,
+      collectionId,
       sortBy: sort_by,
       sortOrder: sort_order,
       advancedQuery: advanced_query,
@@ -84,7 +134,8 @@ export const tagService = {
     folderId?: number,
     recursive: boolean = false,
     advanced_query?: string,
-    search_query?: string
+    search_query?: string,
+    collectionId?: number
   ): Promise<number> => {
     return await invoke("get_image_count_filtered", { 
       tagIds, 
@@ -92,11 +143,42 @@ export const tagService = {
       untagged,
       folderId,
       recursive,
+      collectionId,
       advancedQuery: advanced_query,
       searchQuery: search_query
     });
   },
 
+  subscribeFilter: async (filter: GridFilter, window: FilterWindow): Promise<FilterDelta> => {
+    return await invoke("subscribe_filter", { filter, window });
+  },
+
+  setFilterWindow: async (subscriptionId: number, window: FilterWindow): Promise<FilterDelta> => {
+    return await invoke("set_filter_window", { subscriptionId, window });
+  },
+
+  unsubscribeFilter: async (subscriptionId: number): Promise<void> => {
+    return await invoke("unsubscribe_filter", { subscriptionId });
+  },
+
+  /** Pushes a working set, narrowed to the images of the current top set. */
+  pushWorkingSet: async (imageIds: number[], label?: string): Promise<WorkingSet> => {
+    return await invoke<WorkingSet>("push_working_set", { imageIds, label });
+  },
+
+  /** Removes the top working set and returns the one now on top. */
+  popWorkingSet: async (): Promise<WorkingSet | null> => {
+    return await invoke<WorkingSet | null>("pop_working_set");
+  },
+
+  getWorkingSets: async (): Promise<WorkingSet[]> => {
+    return await invoke<WorkingSet[]>("get_working_sets");
+  },
+
+  clearWorkingSets: async (): Promise<void> => {
+    return await invoke("clear_working_sets");
+  },
+
   updateImageRating: async (id: number, rating: number): Promise<void> => {
     return await invoke("update_image_rating", { id, rating });
   },
@@ -107,5 +189,19 @@ export const tagService = {
 
   getImageExif: async (path: string): Promise<Record<string, string>> => {
     return await invoke("get_image_exif", { path });
+  },
+
+  // EXIF of several images, keyed by id; missing files are left out
+  getExifBatch: async (ids: number[]): Promise<Record<number, Record<string, string>>> => {
+    return await invoke("get_exif_batch", { ids });
+  },
+
+  // Tags given to new files indexed under a folder and its subfolders
+  getFolderDefaultTags: async (folderId: number): Promise<Tag[]> => {
+    return await invoke("get_folder_default_tags", { folderId });
+  },
+
+  setFolderDefaultTags: async (folderId: number, tagIds: number[]): Promise<void> => {
+    return await invoke("set_folder_default_tags", { folderId, tagIds });
   }
 };
@@ -13,6 +13,15 @@ export interface ImageItem {
   modified_at: string;
   added_at: string;
   folder_id: number;
+  /** Seconds, for audio, video and animated GIF/WebP. */
+  duration?: number | null;
+  frame_count?: number | null;
+  /** Whether an animated image plays forever. */
+  loops?: boolean | null;
+  /** Capture time from EXIF, in UTC. */
+  captured_at?: string | null;
+  /** Offset the camera recorded with the capture time, in minutes east of UTC. */
+  capture_offset?: number | null;
 }
 
 export interface FileFormat {
@@ -21,3 +30,24 @@ export interface FileFormat {
     mimeTypes: string[];
     typeCategory: 'Image' | 'Video' | 'Audio' | 'Project' | 'Archive' | 'Model3D' | 'Font' | 'Unknown';
 }
+
+/** Rejection value of every backend command (see `src-tauri/src/error.rs`). */
+export interface AppError {
+    code: 'db' | 'migration' | 'tauri' | 'io' | 'transcoding' | 'image' | 'not_found' | 'internal' | 'read_only'
+        | 'remote' | 'ffmpeg_missing' | 'unsupported' | 'busy' | 'generic';
+    message: string;
+    context: string | null;
+}
+
+/** Whether a caught rejection is an `AppError`, as opposed to e.g. a frontend exception. */
+export function isAppError(value: unknown): value is AppError {
+    return typeof value === 'object' && value !== null && 'code' in value && 'message' in value;
+}
+
+/** Startup progress of the backend, sent with `app:init-progress`. */
+export interface AppStatus {
+    stage: 'starting' | 'database' | 'configuration' | 'workers' | 'watchers' | 'ready' | 'failed';
+    error: string | null;
+    dbPath: string;
+    readOnly: boolean;
+}